@@ -0,0 +1,326 @@
+use crate::cursor::{clock_offset, index_offset};
+use crate::shared_types::{DefaultPyErr, IntegratedOperationException, SharedType, SourceGuard};
+use crate::type_conversions::ToPython;
+use crate::y_map::YMap;
+use crate::y_text::{chunk_len, offset_kind, text_chunks, TextChunk, YText};
+use crate::y_transaction::YTransaction;
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use yrs::{Map, OffsetKind, Text, ID};
+
+/// Comments anchored to ranges of a `YText`, eg. review comments of a collaborative document.
+/// Annotations are stored in a companion `YMap`, so they are synchronized together with the text.
+/// Every annotation remembers the characters it covers and the one right after them, instead of
+/// indexes, so ranges follow local and remote edits of the text: text inserted within a range,
+/// or right at its end, extends it, and deleting annotated text shrinks it. Once the character
+/// right after a range has been deleted, the range ends after its last remaining character.
+///
+/// Every annotation is stored under a unique identifier as a dictionary with a `comment`, which
+/// can be any value supported by shared types, and a `resolved` flag.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import Annotations, YDoc
+///
+/// doc = YDoc()
+/// text = doc.get_text('content')
+/// annotations = Annotations(text, doc.get_map('comments'))
+/// with doc.begin_transaction() as txn:
+///     text.extend(txn, 'hello world')
+///     id = annotations.add(txn, 6, 11, {'author': 'alice', 'body': 'Which world?'})
+/// ...
+/// with doc.begin_transaction() as txn:
+///     for annotation in annotations.list(txn):
+///         print(annotation['start'], annotation['end'], annotation['comment'])
+/// ```
+#[pyclass(unsendable)]
+pub struct Annotations {
+    text: Text,
+    map: Map,
+    guard: SourceGuard,
+}
+
+/// Characters an annotation is anchored to.
+struct Anchors {
+    /// The character right before the annotated ones, or `None` at the start of the text.
+    start: Option<ID>,
+    /// Ranges `(id, len)` of annotated characters, in order of the text.
+    chars: Vec<(ID, u32)>,
+    /// The character right after the annotated ones, or `None` at the end of the text.
+    end: Option<ID>,
+}
+
+impl Anchors {
+    /// Returns anchors of text between the `start` and `end` indexes. Embedded values have no
+    /// identity, so they are left out.
+    fn new(chunks: &[TextChunk], start: u32, end: u32, kind: &OffsetKind) -> Self {
+        let end = end.max(start);
+        let mut anchors = Anchors {
+            start: None,
+            chars: Vec::new(),
+            end: None,
+        };
+        let mut index = 0;
+        for chunk in chunks {
+            let len = chunk_len(chunk, kind);
+            if let TextChunk::String(Some(id), chunk) = chunk {
+                let offset =
+                    |i: u32| clock_offset(chunk, i.max(index).min(index + len) - index, kind);
+                if index < start {
+                    let before = offset(start.min(index + len) - 1);
+                    anchors.start = Some(ID::new(id.client, id.clock + before));
+                }
+                let (from, to) = (offset(start), offset(end));
+                if from < to {
+                    anchors
+                        .chars
+                        .push((ID::new(id.client, id.clock + from), to - from));
+                }
+                if end < index + len && anchors.end.is_none() {
+                    anchors.end = Some(ID::new(id.client, id.clock + to));
+                }
+            }
+            index += len;
+        }
+        anchors
+    }
+
+    /// Returns current `(start, end)` indexes of anchored text. Once all annotated characters
+    /// and the one right after them have been deleted, the range collapses right after the
+    /// character before them.
+    fn range(&self, chunks: &[TextChunk], kind: &OffsetKind) -> (u32, u32) {
+        let mut first: Option<u32> = None;
+        let mut last: Option<u32> = None;
+        let mut before = None;
+        let mut after = None;
+        let mut index = 0;
+        for chunk in chunks {
+            if let TextChunk::String(Some(start), chunk) = chunk {
+                let end = start.clock + chunk.encode_utf16().count() as u32;
+                let contains = |id: &ID| {
+                    start.client == id.client && start.clock <= id.clock && id.clock < end
+                };
+                let at = |clock: u32| index + index_offset(chunk, clock - start.clock, kind);
+                for (id, len) in self.chars.iter() {
+                    let (from, to) = (start.clock.max(id.clock), end.min(id.clock + len));
+                    if start.client == id.client && from < to {
+                        first = Some(first.map_or(at(from), |first| first.min(at(from))));
+                        last = Some(last.map_or(at(to), |last| last.max(at(to))));
+                    }
+                }
+                if let Some(id) = self.start.as_ref().filter(|id| contains(id)) {
+                    before = Some(at(id.clock + 1));
+                }
+                if let Some(id) = self.end.as_ref().filter(|id| contains(id)) {
+                    after = Some(at(id.clock));
+                }
+            }
+            index += chunk_len(chunk, kind);
+        }
+        let end = match &self.end {
+            Some(_) => after.or(last).or(before).unwrap_or(0),
+            None => index,
+        };
+        (first.unwrap_or(end).min(end), end)
+    }
+
+    /// Encodes anchors as values which can be stored in a map: `chars` as a flat list of
+    /// `client, clock, len` triples, while `start` and `end` as `[client, clock]` or `None`.
+    fn encode(&self, py: Python, entry: &PyDict) -> PyResult<()> {
+        let chars = self
+            .chars
+            .iter()
+            .flat_map(|(id, len)| vec![id.client, u64::from(id.clock), u64::from(*len)])
+            .collect::<Vec<u64>>();
+        entry.set_item("start", encode_id(py, &self.start))?;
+        entry.set_item("chars", PyList::new(py, chars))?;
+        entry.set_item("end", encode_id(py, &self.end))
+    }
+
+    fn decode(entry: &PyDict) -> PyResult<Self> {
+        // Integers are stored in maps as floating point numbers
+        let chars: Vec<f64> = match entry.get_item("chars") {
+            Some(chars) => chars.extract()?,
+            None => return Err(malformed()),
+        };
+        if chars.len() % 3 != 0 {
+            return Err(malformed());
+        }
+        let chars = chars
+            .chunks(3)
+            .map(|c| (ID::new(c[0] as u64, c[1] as u32), c[2] as u32))
+            .collect();
+        Ok(Anchors {
+            start: decode_id(entry.get_item("start"))?,
+            chars,
+            end: decode_id(entry.get_item("end"))?,
+        })
+    }
+}
+
+fn malformed() -> PyErr {
+    PyValueError::new_err("Malformed range of an annotation")
+}
+
+fn encode_id(py: Python, id: &Option<ID>) -> PyObject {
+    match id {
+        Some(id) => PyList::new(py, [id.client, u64::from(id.clock)]).into(),
+        None => py.None(),
+    }
+}
+
+fn decode_id(value: Option<&PyAny>) -> PyResult<Option<ID>> {
+    match value {
+        Some(value) if !value.is_none() => match value.extract::<Vec<f64>>()?.as_slice() {
+            [client, clock] => Ok(Some(ID::new(*client as u64, *clock as u32))),
+            _ => Err(malformed()),
+        },
+        _ => Ok(None),
+    }
+}
+
+impl Annotations {
+    /// Returns a current `(start, end)` range of an annotation stored as a given dictionary.
+    fn range(
+        &self,
+        entry: &PyDict,
+        chunks: &[TextChunk],
+        kind: &OffsetKind,
+    ) -> PyResult<(u32, u32)> {
+        Ok(Anchors::decode(entry)?.range(chunks, kind))
+    }
+
+    /// Returns a stored dictionary of an annotation with a given `id`.
+    fn entry<'p>(&self, py: Python<'p>, id: &str) -> PyResult<&'p PyDict> {
+        match self.map.get(id) {
+            Some(value) => Ok(value.into_py(py).into_ref(py).downcast()?),
+            None => Err(PyKeyError::new_err(id.to_string())),
+        }
+    }
+}
+
+#[pymethods]
+impl Annotations {
+    #[new]
+    pub fn new(text: &PyCell<YText>, map: &YMap) -> PyResult<Self> {
+        let text_inner = match &text.borrow().0 {
+            SharedType::Integrated(inner) => inner.clone(),
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        let map = match &map.0 {
+            SharedType::Integrated(inner) => inner.clone(),
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        let guard = SourceGuard::new(text, Some(text_inner.as_ref()), &text.borrow().1);
+        Ok(Annotations {
+            text: text_inner,
+            map,
+            guard,
+        })
+    }
+
+    /// Annotates text between the `start` and `end` indexes with a given `comment`, and returns
+    /// an identifier of the new annotation. Indexes past the end of the text stick to its end.
+    pub fn add(
+        &mut self,
+        py: Python,
+        txn: &mut YTransaction,
+        start: u32,
+        end: u32,
+        comment: PyObject,
+    ) -> PyResult<String> {
+        self.guard.check(py)?;
+        let chunks = text_chunks(&self.text, txn);
+        let kind = offset_kind(&self.text, &chunks);
+        let id: String = py
+            .import("uuid")?
+            .call_method0("uuid4")?
+            .getattr("hex")?
+            .extract()?;
+        let entry = PyDict::new(py);
+        Anchors::new(&chunks, start, end, &kind).encode(py, entry)?;
+        entry.set_item("comment", comment)?;
+        entry.set_item("resolved", false)?;
+        YMap::from(self.map.clone()).set(txn, &id, entry.into())?;
+        Ok(id)
+    }
+
+    /// Marks an annotation with a given `id` as resolved, or as unresolved if `resolved` is
+    /// `False`. Raises a `KeyError` if there's no such annotation.
+    pub fn resolve(
+        &mut self,
+        py: Python,
+        txn: &mut YTransaction,
+        id: &str,
+        resolved: Option<bool>,
+    ) -> PyResult<()> {
+        self.guard.check(py)?;
+        let entry = self.entry(py, id)?;
+        entry.set_item("resolved", resolved.unwrap_or(true))?;
+        YMap::from(self.map.clone()).set(txn, id, entry.into())?;
+        Ok(())
+    }
+
+    /// Removes an annotation with a given `id`. Raises a `KeyError` if there's no such annotation.
+    pub fn remove(&mut self, py: Python, txn: &mut YTransaction, id: &str) -> PyResult<()> {
+        self.guard.check(py)?;
+        self.entry(py, id)?;
+        self.map.remove(txn, id);
+        Ok(())
+    }
+
+    /// Returns current `(start, end)` indexes of an annotation with a given `id`. Raises a
+    /// `KeyError` if there's no such annotation.
+    pub fn range_of(&self, py: Python, txn: &mut YTransaction, id: &str) -> PyResult<(u32, u32)> {
+        self.guard.check(py)?;
+        let entry = self.entry(py, id)?;
+        let chunks = text_chunks(&self.text, txn);
+        let kind = offset_kind(&self.text, &chunks);
+        self.range(entry, &chunks, &kind)
+    }
+
+    /// Returns annotations as dictionaries with their `id`, current `start` and `end` indexes,
+    /// `comment` and `resolved` flag, ordered by their ranges. Resolved annotations are only
+    /// included if `include_resolved` is `True`.
+    pub fn list(
+        &self,
+        py: Python,
+        txn: &mut YTransaction,
+        include_resolved: Option<bool>,
+    ) -> PyResult<PyObject> {
+        self.guard.check(py)?;
+        let chunks = text_chunks(&self.text, txn);
+        let kind = offset_kind(&self.text, &chunks);
+        let include_resolved = include_resolved.unwrap_or(false);
+        let mut annotations = Vec::new();
+        for (id, value) in self.map.iter() {
+            let entry: &PyDict = match value.into_py(py).into_ref(py).downcast() {
+                Ok(entry) => entry,
+                // Entries which aren't annotations are skipped
+                Err(_) => continue,
+            };
+            let resolved = match entry.get_item("resolved") {
+                Some(resolved) => resolved.is_true()?,
+                None => false,
+            };
+            if resolved && !include_resolved {
+                continue;
+            }
+            let (start, end) = self.range(entry, &chunks, &kind)?;
+            let annotation = PyDict::new(py);
+            annotation.set_item("id", id)?;
+            annotation.set_item("start", start)?;
+            annotation.set_item("end", end)?;
+            annotation.set_item("comment", entry.get_item("comment"))?;
+            annotation.set_item("resolved", resolved)?;
+            annotations.push((start, end, id.to_string(), annotation));
+        }
+        annotations.sort_by(|a, b| (a.0, a.1, &a.2).cmp(&(b.0, b.1, &b.2)));
+        let annotations = annotations
+            .into_iter()
+            .map(|(_, _, _, annotation)| annotation);
+        Ok(PyList::new(py, annotations).into())
+    }
+}
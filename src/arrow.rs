@@ -0,0 +1,337 @@
+//! Export of `YArray` rows into Arrow tables.
+//!
+//! Columns are built directly from `lib0::any::Any` values and handed over to `pyarrow` through
+//! the [Arrow C data interface](https://arrow.apache.org/docs/format/CDataInterface.html), so no
+//! Python object is created for individual cells.
+
+use lib0::any::Any;
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+const ARROW_FLAG_NULLABLE: i64 = 2;
+
+/// `struct ArrowSchema` of the Arrow C data interface.
+#[repr(C)]
+struct ArrowSchema {
+    format: *const c_char,
+    name: *const c_char,
+    metadata: *const c_char,
+    flags: i64,
+    n_children: i64,
+    children: *mut *mut ArrowSchema,
+    dictionary: *mut ArrowSchema,
+    release: Option<unsafe extern "C" fn(*mut ArrowSchema)>,
+    private_data: *mut c_void,
+}
+
+/// Memory owned by an exported `ArrowSchema`, freed by its release callback.
+struct SchemaData {
+    format: CString,
+    name: CString,
+    children: Vec<ArrowSchema>,
+    children_ptrs: Vec<*mut ArrowSchema>,
+}
+
+impl ArrowSchema {
+    fn new(format: &str, name: &str, flags: i64, children: Vec<ArrowSchema>) -> Self {
+        let mut data = Box::new(SchemaData {
+            format: CString::new(format).unwrap(),
+            name: CString::new(name).unwrap_or_default(),
+            children,
+            children_ptrs: Vec::new(),
+        });
+        data.children_ptrs = data
+            .children
+            .iter_mut()
+            .map(|child| child as *mut ArrowSchema)
+            .collect();
+        ArrowSchema {
+            format: data.format.as_ptr(),
+            name: data.name.as_ptr(),
+            metadata: ptr::null(),
+            flags,
+            n_children: data.children.len() as i64,
+            children: data.children_ptrs.as_mut_ptr(),
+            dictionary: ptr::null_mut(),
+            release: Some(release_schema),
+            private_data: Box::into_raw(data) as *mut c_void,
+        }
+    }
+}
+
+unsafe extern "C" fn release_schema(schema: *mut ArrowSchema) {
+    // SAFETY: `private_data` was created by `ArrowSchema::new` and the release callback is
+    // called at most once, as it is cleared right after. Children are released by their `Drop`.
+    let schema = &mut *schema;
+    drop(Box::from_raw(schema.private_data as *mut SchemaData));
+    schema.private_data = ptr::null_mut();
+    schema.release = None;
+}
+
+impl Drop for ArrowSchema {
+    fn drop(&mut self) {
+        // A structure imported by pyarrow has been moved out and has its `release` cleared.
+        if let Some(release) = self.release {
+            unsafe { release(self) }
+        }
+    }
+}
+
+/// `struct ArrowArray` of the Arrow C data interface.
+#[repr(C)]
+struct ArrowArray {
+    length: i64,
+    null_count: i64,
+    offset: i64,
+    n_buffers: i64,
+    n_children: i64,
+    buffers: *mut *const c_void,
+    children: *mut *mut ArrowArray,
+    dictionary: *mut ArrowArray,
+    release: Option<unsafe extern "C" fn(*mut ArrowArray)>,
+    private_data: *mut c_void,
+}
+
+/// Memory owned by an exported `ArrowArray`, freed by its release callback.
+struct ArrayData {
+    _column: Option<Column>,
+    _validity: Vec<u8>,
+    buffers: Vec<*const c_void>,
+    children: Vec<ArrowArray>,
+    children_ptrs: Vec<*mut ArrowArray>,
+}
+
+impl ArrowArray {
+    fn new(
+        length: usize,
+        null_count: usize,
+        column: Option<Column>,
+        validity: Vec<u8>,
+        buffers: Vec<*const c_void>,
+        children: Vec<ArrowArray>,
+    ) -> Self {
+        let mut data = Box::new(ArrayData {
+            _column: column,
+            _validity: validity,
+            buffers,
+            children,
+            children_ptrs: Vec::new(),
+        });
+        data.children_ptrs = data
+            .children
+            .iter_mut()
+            .map(|child| child as *mut ArrowArray)
+            .collect();
+        ArrowArray {
+            length: length as i64,
+            null_count: null_count as i64,
+            offset: 0,
+            n_buffers: data.buffers.len() as i64,
+            n_children: data.children.len() as i64,
+            buffers: data.buffers.as_mut_ptr(),
+            children: data.children_ptrs.as_mut_ptr(),
+            dictionary: ptr::null_mut(),
+            release: Some(release_array),
+            private_data: Box::into_raw(data) as *mut c_void,
+        }
+    }
+}
+
+unsafe extern "C" fn release_array(array: *mut ArrowArray) {
+    // SAFETY: see `release_schema`.
+    let array = &mut *array;
+    drop(Box::from_raw(array.private_data as *mut ArrayData));
+    array.private_data = ptr::null_mut();
+    array.release = None;
+}
+
+impl Drop for ArrowArray {
+    fn drop(&mut self) {
+        if let Some(release) = self.release {
+            unsafe { release(self) }
+        }
+    }
+}
+
+/// Values of a single table column, laid out as Arrow buffers.
+enum Column {
+    Null,
+    Boolean(Vec<u8>),
+    Int64(Vec<i64>),
+    Float64(Vec<f64>),
+    Utf8(Vec<i32>, Vec<u8>),
+}
+
+impl Column {
+    fn format(&self) -> &'static str {
+        match self {
+            Column::Null => "n",
+            Column::Boolean(_) => "b",
+            Column::Int64(_) => "l",
+            Column::Float64(_) => "g",
+            Column::Utf8(_, _) => "u",
+        }
+    }
+
+    /// Picks the narrowest Arrow type able to hold all `cells` and copies them into its buffers.
+    fn new(name: &str, cells: &[Option<Any>]) -> PyResult<Self> {
+        let mut column = Column::Null;
+        for cell in cells.iter().flatten() {
+            column = match (column, cell) {
+                (Column::Null | Column::Boolean(_), Any::Bool(_)) => Column::Boolean(Vec::new()),
+                (Column::Null | Column::Int64(_), Any::BigInt(_)) => Column::Int64(Vec::new()),
+                (Column::Null | Column::Int64(_) | Column::Float64(_), Any::Number(_))
+                | (Column::Float64(_), Any::BigInt(_)) => Column::Float64(Vec::new()),
+                (Column::Null | Column::Utf8(_, _), Any::String(_)) => {
+                    Column::Utf8(Vec::new(), Vec::new())
+                }
+                (column, Any::Null | Any::Undefined) => column,
+                _ => {
+                    return Err(PyTypeError::new_err(format!(
+                        "Values of column '{name}' can't be stored in a single Arrow column"
+                    )))
+                }
+            };
+        }
+        let len = cells.len();
+        let cells = cells.iter().map(|cell| cell.as_ref());
+        match &mut column {
+            Column::Null => {}
+            Column::Boolean(bits) => {
+                bits.resize((len + 7) / 8, 0);
+                for (i, cell) in cells.enumerate() {
+                    if let Some(Any::Bool(true)) = cell {
+                        bits[i / 8] |= 1 << (i % 8);
+                    }
+                }
+            }
+            Column::Int64(values) => values.extend(cells.map(|cell| match cell {
+                Some(Any::BigInt(value)) => *value,
+                _ => 0,
+            })),
+            Column::Float64(values) => values.extend(cells.map(|cell| match cell {
+                Some(Any::Number(value)) => *value,
+                Some(Any::BigInt(value)) => *value as f64,
+                _ => 0.0,
+            })),
+            Column::Utf8(offsets, data) => {
+                offsets.push(0);
+                for cell in cells {
+                    if let Some(Any::String(value)) = cell {
+                        data.extend_from_slice(value.as_bytes());
+                    }
+                    let offset = i32::try_from(data.len()).map_err(|_| {
+                        PyTypeError::new_err(format!(
+                            "Column '{name}' holds too much text for an Arrow column"
+                        ))
+                    })?;
+                    offsets.push(offset);
+                }
+            }
+        }
+        Ok(column)
+    }
+
+    /// Exports the column, with its buffers moved into the private data of the returned array.
+    fn into_ffi(self, name: &str, cells: &[Option<Any>]) -> (ArrowSchema, ArrowArray) {
+        let schema = ArrowSchema::new(self.format(), name, ARROW_FLAG_NULLABLE, Vec::new());
+        let len = cells.len();
+        let is_valid =
+            |cell: &Option<Any>| !matches!(cell, None | Some(Any::Null | Any::Undefined));
+        let null_count = cells.iter().filter(|cell| !is_valid(cell)).count();
+        let mut validity: Vec<u8> = Vec::new();
+        let mut buffers: Vec<*const c_void> = Vec::new();
+        if let Column::Null = self {
+            // The null layout has no buffers at all.
+        } else if null_count > 0 {
+            validity.resize((len + 7) / 8, 0);
+            for (i, cell) in cells.iter().enumerate() {
+                if is_valid(cell) {
+                    validity[i / 8] |= 1 << (i % 8);
+                }
+            }
+            buffers.push(validity.as_ptr() as *const c_void);
+        } else {
+            buffers.push(ptr::null());
+        }
+        match &self {
+            Column::Null => {}
+            Column::Boolean(bits) => buffers.push(bits.as_ptr() as *const c_void),
+            Column::Int64(values) => buffers.push(values.as_ptr() as *const c_void),
+            Column::Float64(values) => buffers.push(values.as_ptr() as *const c_void),
+            Column::Utf8(offsets, data) => {
+                buffers.push(offsets.as_ptr() as *const c_void);
+                buffers.push(data.as_ptr() as *const c_void);
+            }
+        }
+        // Moving the vectors into the array data leaves their heap allocations, and so the
+        // buffer pointers, intact.
+        let array = ArrowArray::new(len, null_count, Some(self), validity, buffers, Vec::new());
+        (schema, array)
+    }
+}
+
+/// Collects rows of maps into table columns, ordered by their first appearance. Keys of a
+/// single row are visited in sorted order, as maps don't preserve insertion order.
+#[derive(Default)]
+pub(crate) struct TableBuilder {
+    rows: usize,
+    names: Vec<String>,
+    index: HashMap<String, usize>,
+    columns: Vec<Vec<Option<Any>>>,
+}
+
+impl TableBuilder {
+    pub fn push_row<I: IntoIterator<Item = (String, Any)>>(&mut self, entries: I) {
+        let mut entries: Vec<(String, Any)> = entries.into_iter().collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (key, value) in entries {
+            let column = match self.index.get(&key) {
+                Some(&column) => column,
+                None => {
+                    self.index.insert(key.clone(), self.names.len());
+                    self.names.push(key);
+                    self.columns.push(Vec::new());
+                    self.columns.len() - 1
+                }
+            };
+            let cells = &mut self.columns[column];
+            cells.resize(self.rows, None);
+            cells.push(Some(value));
+        }
+        self.rows += 1;
+    }
+
+    /// Builds a `pyarrow.Table` from collected rows.
+    pub fn into_table(self, py: Python) -> PyResult<PyObject> {
+        let pyarrow = py.import("pyarrow")?;
+        let mut fields = Vec::with_capacity(self.names.len());
+        let mut arrays = Vec::with_capacity(self.names.len());
+        for (name, mut cells) in self.names.iter().zip(self.columns) {
+            cells.resize(self.rows, None);
+            let (field, array) = Column::new(name, &cells)?.into_ffi(name, &cells);
+            fields.push(field);
+            arrays.push(array);
+        }
+        let schema = ArrowSchema::new("+s", "", 0, fields);
+        let array = ArrowArray::new(self.rows, 0, None, Vec::new(), vec![ptr::null()], arrays);
+        // pyarrow moves both structures out, leaving them released. If the import fails, they
+        // are released when dropped.
+        let batch = pyarrow.getattr("RecordBatch")?.call_method1(
+            "_import_from_c",
+            (
+                &array as *const ArrowArray as usize,
+                &schema as *const ArrowSchema as usize,
+            ),
+        )?;
+        let table = pyarrow
+            .getattr("Table")?
+            .call_method1("from_batches", (vec![batch],))?;
+        Ok(table.into())
+    }
+}
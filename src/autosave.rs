@@ -0,0 +1,207 @@
+use crate::logging::{self, Level};
+use crate::shutdown::{self, Callback};
+use crate::y_doc::diff;
+use pyo3::exceptions::PyOSError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use yrs::updates::decoder::Decode;
+use yrs::{Doc, StateVector, Update};
+
+enum Message {
+    /// An update of the saved document, using lib0 v1 encoding.
+    Update(Vec<u8>),
+    /// Saves pending changes right away, and notifies the sender once they have been saved.
+    Flush(Sender<()>),
+    Stop,
+}
+
+/// A destination of saved document states.
+pub(crate) enum Target {
+    /// A file, which is replaced atomically with every save.
+    Path(PathBuf),
+    /// A callable receiving document states as `bytes`.
+    Callback(Callback),
+}
+
+impl Target {
+    /// Writes a document state to this target. Only callbacks acquire the GIL.
+    fn save(&self, state: &[u8]) -> PyResult<()> {
+        match self {
+            Target::Path(path) => {
+                let mut temporary = path.clone().into_os_string();
+                temporary.push(".tmp");
+                std::fs::write(&temporary, state)
+                    .and_then(|_| std::fs::rename(&temporary, path))
+                    .map_err(|e| PyOSError::new_err(format!("Cannot save {:?}: {}", path, e)))
+            }
+            Target::Callback(callback) => shutdown::with_gil(|py| {
+                callback.call1(py, (PyBytes::new(py, state),))?;
+                Ok(())
+            })
+            .unwrap_or(Ok(())),
+        }
+    }
+}
+
+/// Keeps a replica of a document on a background thread, which receives updates of the
+/// document and saves its whole state once per `interval` if it has changed.
+fn run(receiver: Receiver<Message>, target: Target, interval: Duration, saves: Arc<AtomicUsize>) {
+    // Documents can't be shared between threads, so the replica lives on this thread only
+    let replica = Doc::new();
+    let mut changed = false;
+    let mut deadline = Instant::now() + interval;
+    let save = |changed: &mut bool| {
+        if !std::mem::replace(changed, false) {
+            return;
+        }
+        let state = diff(&replica, &StateVector::default());
+        match target.save(&state) {
+            Ok(()) => {
+                saves.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(err) => {
+                shutdown::with_gil(|py| {
+                    logging::emit(py, Level::Error, "autosave_error", |record| {
+                        record.set_item("exception", err.value(py))
+                    });
+                    err.print(py);
+                });
+            }
+        }
+    };
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(Message::Update(update)) => {
+                if let Ok(update) = Update::decode_v1(&update) {
+                    replica.transact().apply_update(update);
+                    changed = true;
+                }
+            }
+            Ok(Message::Flush(done)) => {
+                save(&mut changed);
+                let _ = done.send(());
+            }
+            Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => {
+                save(&mut changed);
+                break;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                save(&mut changed);
+                deadline = Instant::now() + interval;
+            }
+        }
+    }
+}
+
+/// Periodically saves a document on a background thread, see `YDoc.start_autosave`. Autosaves
+/// can be used as context managers, which stop them on exit.
+#[pyclass(unsendable)]
+pub struct Autosave {
+    sender: Sender<Message>,
+    worker: Option<JoinHandle<()>>,
+    /// Cleared once the autosave stops, which silences its document observer.
+    active: Rc<Cell<bool>>,
+    saves: Arc<AtomicUsize>,
+}
+
+impl Autosave {
+    /// Starts a background thread saving a document, given its current state using lib0 v1
+    /// encoding. Returns the autosave with a sender of updates of the document.
+    pub(crate) fn start(state: Vec<u8>, target: Target, interval: Duration) -> Self {
+        let (sender, receiver) = channel();
+        let saves = Arc::new(AtomicUsize::new(0));
+        let worker_saves = saves.clone();
+        let _ = sender.send(Message::Update(state));
+        let worker = thread::spawn(move || run(receiver, target, interval, worker_saves));
+        Autosave {
+            sender,
+            worker: Some(worker),
+            active: Rc::new(Cell::new(true)),
+            saves,
+        }
+    }
+
+    /// Returns a function sending updates of a document to the background thread, until the
+    /// autosave stops.
+    pub(crate) fn updates(&self) -> impl Fn(Vec<u8>) {
+        let sender = self.sender.clone();
+        let active = self.active.clone();
+        move |update| {
+            if active.get() {
+                let _ = sender.send(Message::Update(update));
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl Autosave {
+    /// Number of times the document has been saved so far.
+    #[getter]
+    pub fn saves(&self) -> usize {
+        self.saves.load(Ordering::Relaxed)
+    }
+
+    /// Checks if the document is still being saved.
+    #[getter]
+    pub fn running(&self) -> bool {
+        self.active.get()
+    }
+
+    /// Saves changes made so far right away, and blocks until they have been saved.
+    pub fn flush(&self, py: Python) {
+        if !self.active.get() {
+            return;
+        }
+        let (done, saved) = channel();
+        if self.sender.send(Message::Flush(done)).is_ok() {
+            py.allow_threads(move || {
+                let _ = saved.recv();
+            });
+        }
+    }
+
+    /// Saves pending changes and stops the background thread. Changes made afterwards are not
+    /// saved anymore.
+    pub fn stop(&mut self, py: Python) {
+        self.active.set(false);
+        if let Some(worker) = self.worker.take() {
+            let _ = self.sender.send(Message::Stop);
+            py.allow_threads(move || {
+                let _ = worker.join();
+            });
+        }
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        py: Python,
+        _exception_type: Option<&PyAny>,
+        _exception_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> bool {
+        self.stop(py);
+        false
+    }
+}
+
+impl Drop for Autosave {
+    fn drop(&mut self) {
+        // The background thread saves pending changes on its own, without being waited for
+        self.active.set(false);
+        let _ = self.sender.send(Message::Stop);
+    }
+}
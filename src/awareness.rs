@@ -0,0 +1,299 @@
+use crate::shared_types::EncodingError;
+use crate::type_conversions::BinaryInput;
+use crate::y_doc::YDoc;
+use lib0::decoding::{Cursor, Read};
+use lib0::encoding::Write;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// Clients whose states have changed, passed to observers of an `Awareness`.
+#[derive(Default)]
+struct Changes {
+    added: Vec<u64>,
+    updated: Vec<u64>,
+    removed: Vec<u64>,
+}
+
+impl Changes {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Presence information of the users of a document, eg. their names and cursor positions, which
+/// is exchanged with the awareness protocol of y-protocols, but isn't stored in the document.
+/// Every client has a state, which can be any JSON serializable value, and a clock which is
+/// incremented whenever its state changes, so that replicas keep the latest state of every client.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import Awareness, YDoc
+///
+/// doc = YDoc()
+/// awareness = Awareness(doc)
+/// awareness.observe(lambda changes, origin: print(changes))
+/// awareness.set_local_state({'user': 'alice', 'cursor': 3})
+/// send(awareness.encode_update())
+/// ```
+#[pyclass(unsendable)]
+pub struct Awareness {
+    client_id: u64,
+    /// Clocks of all known clients, including those which states have been removed.
+    clocks: RefCell<HashMap<u64, u32>>,
+    /// JSON encoded states of clients, keyed by their ids.
+    states: RefCell<BTreeMap<u64, String>>,
+    /// Times of the last updates of clients, which are renewed even if their states haven't
+    /// changed.
+    updated: RefCell<HashMap<u64, Instant>>,
+    next_id: Cell<u32>,
+    observers: RefCell<Vec<(u32, PyObject)>>,
+}
+
+impl Awareness {
+    fn clock(&self, client: u64) -> u32 {
+        self.clocks.borrow().get(&client).copied().unwrap_or(0)
+    }
+
+    /// Sets a JSON encoded state of a client, where `None` removes its state, and records how the
+    /// client has changed in `changes`.
+    fn set_state(&self, client: u64, clock: u32, state: Option<String>, changes: &mut Changes) {
+        self.clocks.borrow_mut().insert(client, clock);
+        self.updated.borrow_mut().insert(client, Instant::now());
+        let mut states = self.states.borrow_mut();
+        match state {
+            Some(state) => match states.insert(client, state.clone()) {
+                Some(previous) if previous == state => {}
+                Some(_) => changes.updated.push(client),
+                None => changes.added.push(client),
+            },
+            None => {
+                if states.remove(&client).is_some() {
+                    changes.removed.push(client)
+                }
+            }
+        }
+    }
+
+    /// Returns a state of a given client, or `None` if it has no state.
+    pub(crate) fn state(&self, py: Python, client: u64) -> PyResult<PyObject> {
+        match self.states.borrow().get(&client) {
+            Some(state) => Ok(py.import("json")?.call_method1("loads", (state,))?.into()),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Increments the clock of the local client without changing its state, so that other
+    /// replicas know it's still present. Returns `false` if the local client has no state.
+    pub(crate) fn renew_local_state(&self) -> bool {
+        let state = match self.states.borrow().get(&self.client_id) {
+            Some(state) => state.clone(),
+            None => return false,
+        };
+        let clock = self.clock(self.client_id) + 1;
+        self.set_state(self.client_id, clock, Some(state), &mut Changes::default());
+        true
+    }
+
+    /// Returns the time elapsed since the last update of a given client, if it has been updated.
+    pub(crate) fn elapsed(&self, client: u64) -> Option<Duration> {
+        self.updated
+            .borrow()
+            .get(&client)
+            .map(|updated| updated.elapsed())
+    }
+
+    /// Returns ids of remote clients with a state which haven't been updated for `ttl`.
+    pub(crate) fn outdated(&self, ttl: Duration) -> Vec<u64> {
+        self.states
+            .borrow()
+            .keys()
+            .copied()
+            .filter(|client| *client != self.client_id)
+            .filter(|client| self.elapsed(*client).map_or(true, |elapsed| elapsed >= ttl))
+            .collect()
+    }
+
+    /// Calls observers with given changes, once states are not borrowed anymore, so that
+    /// observers can read them.
+    fn notify(&self, py: Python, changes: Changes, origin: PyObject) -> PyResult<()> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let dict = PyDict::new(py);
+        dict.set_item("added", changes.added)?;
+        dict.set_item("updated", changes.updated)?;
+        dict.set_item("removed", changes.removed)?;
+        let observers: Vec<PyObject> = self
+            .observers
+            .borrow()
+            .iter()
+            .map(|(_, callback)| callback.clone_ref(py))
+            .collect();
+        for callback in observers {
+            callback.call1(py, (dict, origin.clone_ref(py)))?;
+        }
+        Ok(())
+    }
+
+    /// Encodes states of given clients, or of all clients with known clocks if `None`.
+    pub(crate) fn encode(&self, clients: Option<Vec<u64>>) -> Vec<u8> {
+        let clients = clients.unwrap_or_else(|| {
+            let mut clients: Vec<u64> = self.clocks.borrow().keys().copied().collect();
+            clients.sort_unstable();
+            clients
+        });
+        let states = self.states.borrow();
+        let mut update = Vec::new();
+        update.write_var(clients.len() as u32);
+        for client in clients {
+            update.write_var(client);
+            update.write_var(self.clock(client));
+            update.write_string(states.get(&client).map_or("null", String::as_str));
+        }
+        update
+    }
+
+    /// Applies an encoded awareness update received from another replica.
+    pub(crate) fn apply(&self, py: Python, update: &[u8], origin: PyObject) -> PyResult<()> {
+        let invalid = |e: lib0::error::Error| EncodingError::new_err(e.to_string());
+        let mut cursor = Cursor::new(update);
+        let mut changes = Changes::default();
+        let len: u32 = cursor.read_var().map_err(invalid)?;
+        for _ in 0..len {
+            let client: u64 = cursor.read_var().map_err(invalid)?;
+            let mut clock: u32 = cursor.read_var().map_err(invalid)?;
+            let state = cursor.read_string().map_err(invalid)?;
+            let state = if state == "null" {
+                None
+            } else {
+                Some(state.to_string())
+            };
+            let current = self.clock(client);
+            let has_state = self.states.borrow().contains_key(&client);
+            if current < clock || (current == clock && state.is_none() && has_state) {
+                if state.is_none() && client == self.client_id && has_state {
+                    // The local state can't be removed by other replicas, so it's renewed instead
+                    clock += 1;
+                    let local = self.states.borrow().get(&client).cloned();
+                    self.set_state(client, clock, local, &mut changes);
+                } else {
+                    self.set_state(client, clock, state, &mut changes);
+                }
+            }
+        }
+        self.notify(py, changes, origin)
+    }
+}
+
+#[pymethods]
+impl Awareness {
+    /// Creates an awareness of users of a given document, where the local state belongs to the
+    /// client of the document.
+    #[new]
+    pub fn new(doc: &YDoc) -> Self {
+        Awareness {
+            client_id: doc.client_id(),
+            clocks: RefCell::default(),
+            states: RefCell::default(),
+            updated: RefCell::default(),
+            next_id: Cell::new(0),
+            observers: RefCell::default(),
+        }
+    }
+
+    /// Id of the local client.
+    #[getter]
+    pub fn client_id(&self) -> u64 {
+        self.client_id
+    }
+
+    /// Returns the state of the local client, or `None` if it has no state.
+    pub fn get_local_state(&self, py: Python) -> PyResult<PyObject> {
+        self.state(py, self.client_id)
+    }
+
+    /// Sets a state of the local client, which has to be JSON serializable. Setting `None`
+    /// removes the local state, eg. when a user leaves a document.
+    pub fn set_local_state(&self, py: Python, state: PyObject) -> PyResult<()> {
+        let state = if state.is_none(py) {
+            None
+        } else {
+            Some(
+                py.import("json")?
+                    .call_method1("dumps", (state,))?
+                    .extract()?,
+            )
+        };
+        let mut changes = Changes::default();
+        let clock = self.clock(self.client_id) + 1;
+        self.set_state(self.client_id, clock, state, &mut changes);
+        self.notify(py, changes, "local".into_py(py))
+    }
+
+    /// Returns states of all clients with a state, keyed by their ids.
+    pub fn get_states(&self, py: Python) -> PyResult<PyObject> {
+        let json = py.import("json")?;
+        let dict = PyDict::new(py);
+        for (client, state) in self.states.borrow().iter() {
+            dict.set_item(client, json.call_method1("loads", (state,))?)?;
+        }
+        Ok(dict.into())
+    }
+
+    /// Removes states of given clients, eg. once a connection of a remote client is closed.
+    /// Removals are propagated to other replicas by `encode_update`.
+    pub fn remove_states(
+        &self,
+        py: Python,
+        clients: Vec<u64>,
+        origin: Option<PyObject>,
+    ) -> PyResult<()> {
+        let mut changes = Changes::default();
+        for client in clients {
+            if self.states.borrow().contains_key(&client) {
+                let clock = self.clock(client) + 1;
+                self.set_state(client, clock, None, &mut changes);
+            }
+        }
+        self.notify(py, changes, origin.unwrap_or_else(|| py.None()))
+    }
+
+    /// Encodes states of given `clients`, or of all known clients, as an update of the awareness
+    /// protocol, which can be applied to other replicas with `apply_update`.
+    pub fn encode_update(&self, py: Python, clients: Option<Vec<u64>>) -> PyObject {
+        PyBytes::new(py, &self.encode(clients)).into()
+    }
+
+    /// Applies an update encoded by `encode_update` of another replica. Observers are called
+    /// with a given `origin`.
+    pub fn apply_update(
+        &self,
+        py: Python,
+        update: BinaryInput,
+        origin: Option<PyObject>,
+    ) -> PyResult<()> {
+        self.apply(py, &update, origin.unwrap_or_else(|| py.None()))
+    }
+
+    /// Subscribes a `callback(changes, origin)` which is called whenever states of clients
+    /// change. `changes` is a dictionary with lists of `added`, `updated` and `removed` client
+    /// ids, and `origin` is "local" for changes of the local state. Returns an id which can be
+    /// used to cancel the subscription with `unobserve`.
+    pub fn observe(&self, callback: PyObject) -> u32 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.observers.borrow_mut().push((id, callback));
+        id
+    }
+
+    /// Cancels a subscription created with `observe`.
+    pub fn unobserve(&self, subscription_id: u32) {
+        self.observers
+            .borrow_mut()
+            .retain(|(id, _)| *id != subscription_id);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::y_doc::YDoc;
+use crate::y_transaction::YTransaction;
+use pyo3::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A transaction of an active `YDoc.batch()` scope, shared by a document and its batches.
+#[derive(Clone, Default)]
+pub(crate) struct BatchState(Rc<RefCell<Option<Py<YTransaction>>>>);
+
+impl BatchState {
+    /// Returns a transaction of an active batch, if any.
+    pub(crate) fn transaction(&self, py: Python) -> Option<Py<YTransaction>> {
+        self.0.borrow().as_ref().map(|txn| txn.clone_ref(py))
+    }
+
+    fn start(&self, txn: Py<YTransaction>) {
+        *self.0.borrow_mut() = Some(txn);
+    }
+
+    fn finish(&self) {
+        self.0.borrow_mut().take();
+    }
+}
+
+/// A scope returned by `YDoc.batch()`, in which all transactions of a document are grouped into a
+/// single one. Transactions started with `YDoc.begin_transaction` or `YDoc.transact`, as well as
+/// updates applied with `apply_update`, share a transaction which is only committed once the
+/// scope is exited. Observers are therefore called once for all the changes made in the scope,
+/// and `AfterTransactionEvent.get_update` returns a single update containing all of them, which
+/// reduces network chatter during bulk imports.
+///
+/// Changes can't be rolled back, so they are committed even if the scope is exited with an
+/// exception. Nested batches share the transaction of the outermost batch.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YDoc
+///
+/// doc = YDoc()
+/// doc.observe_after_transaction(lambda e: send(e.get_update()))
+/// with doc.batch():
+///     for row in rows:
+///         with doc.begin_transaction() as txn:
+///             doc.get_array('rows').append(txn, row)
+/// # a single update is sent here
+/// ```
+#[pyclass(unsendable)]
+pub struct Batch {
+    doc: Py<YDoc>,
+    /// A transaction started by this batch, unless it's nested in another batch.
+    txn: Option<Py<YTransaction>>,
+}
+
+impl Batch {
+    pub(crate) fn new(doc: Py<YDoc>) -> Self {
+        Batch { doc, txn: None }
+    }
+}
+
+#[pymethods]
+impl Batch {
+    /// Starts grouping transactions of the document, and returns their shared transaction.
+    fn __enter__(&mut self, py: Python) -> PyResult<Py<YTransaction>> {
+        YDoc::doc_ref(self.doc.as_ref(py))?;
+        let doc = self.doc.borrow(py);
+        if let Some(txn) = doc.batch.transaction(py) {
+            return Ok(txn);
+        }
+        let mut txn = doc.begin_transaction();
+        txn.batched = true;
+        let txn = Py::new(py, txn)?;
+        doc.batch.start(txn.clone_ref(py));
+        self.txn = Some(txn.clone_ref(py));
+        Ok(txn)
+    }
+
+    /// Commits the shared transaction, unless this batch is nested in another batch.
+    fn __exit__(
+        &mut self,
+        py: Python,
+        _exception_type: Option<&PyAny>,
+        _exception_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> bool {
+        if let Some(txn) = self.txn.take() {
+            self.doc.borrow(py).batch.finish();
+            let mut txn = txn.borrow_mut(py);
+            txn.batched = false;
+            txn.commit();
+        }
+        false
+    }
+}
@@ -0,0 +1,236 @@
+use crate::location::Location;
+use crate::logging;
+use crate::shared_types::{DefaultPyErr, PreliminaryObservationException, SharedType};
+use crate::shutdown;
+use crate::y_map::YMap;
+use crate::y_transaction::YTransaction;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::pyclass::CompareOp;
+use pyo3::types::PyDict;
+use std::rc::Rc;
+use yrs::types::DeepObservable;
+use yrs::{Map, SubscriptionId};
+
+/// Resolution of conflicts between remote changes and local changes of a bound dictionary which
+/// haven't been flushed yet.
+#[derive(Clone, Copy, PartialEq)]
+enum Conflict {
+    /// Remote changes overwrite local changes of the same keys.
+    CrdtWins,
+    /// Local changes are kept, and overwrite remote changes once they're flushed.
+    LocalWins,
+}
+
+struct State {
+    map: Map,
+    /// Location of the map, which knows its document.
+    location: Location,
+    target: PyObject,
+    /// Plain copies of entries of the map, as of the last synchronization with the target.
+    base: Py<PyDict>,
+    conflict: Conflict,
+}
+
+impl State {
+    /// Returns a wrapper of the map.
+    fn map(&self) -> YMap {
+        YMap::located(self.map.clone(), self.location.clone())
+    }
+
+    /// Checks if an entry of the target has been changed since the last synchronization.
+    fn locally_changed(&self, py: Python, key: &PyAny) -> PyResult<bool> {
+        let target = self.target.as_ref(py);
+        let current = if target.contains(key)? {
+            Some(target.get_item(key)?)
+        } else {
+            None
+        };
+        changed(current, self.base.as_ref(py).get_item(key))
+    }
+
+    /// Copies changes of the map made since the last synchronization to the target.
+    fn pull(&self, py: Python) -> PyResult<()> {
+        let remote = self.map().to_dict(Some(true), None)?;
+        let remote: &PyDict = remote.as_ref(py).downcast()?;
+        let base = self.base.as_ref(py);
+        let target = self.target.as_ref(py);
+        let deepcopy = py.import("copy")?.getattr("deepcopy")?;
+        let removed = base
+            .keys()
+            .iter()
+            .filter(|key| !remote.contains(key).unwrap_or(true));
+        let keys: Vec<&PyAny> = remote.keys().iter().chain(removed).collect();
+        for key in keys {
+            let value = remote.get_item(key);
+            if !changed(value, base.get_item(key))? {
+                continue;
+            }
+            if self.conflict == Conflict::CrdtWins || !self.locally_changed(py, key)? {
+                match value {
+                    Some(value) => target.set_item(key, deepcopy.call1((value,))?)?,
+                    None if target.contains(key)? => target.del_item(key)?,
+                    None => {}
+                }
+            }
+            match value {
+                Some(value) => base.set_item(key, value)?,
+                None => base.del_item(key)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes changes of the target made since the last synchronization to the map, and returns
+    /// their keys.
+    fn push(&self, py: Python, txn: &mut YTransaction) -> PyResult<Vec<String>> {
+        let base = self.base.as_ref(py);
+        let target = self.target.as_ref(py);
+        let deepcopy = py.import("copy")?.getattr("deepcopy")?;
+        let mut keys: Vec<&PyAny> = target.iter()?.collect::<PyResult<_>>()?;
+        for key in base.keys().iter() {
+            if !target.contains(key)? {
+                keys.push(key);
+            }
+        }
+        let mut map = self.map();
+        let mut flushed = Vec::new();
+        for key in keys {
+            if !self.locally_changed(py, key)? {
+                continue;
+            }
+            let name: String = key.extract()?;
+            if target.contains(key)? {
+                let value = deepcopy.call1((target.get_item(key)?,))?;
+                map.set(txn, &name, value.into())?;
+                base.set_item(key, value)?;
+            } else {
+                map.pop(txn, &name, Some(py.None()))?;
+                base.del_item(key)?;
+            }
+            flushed.push(name);
+        }
+        Ok(flushed)
+    }
+}
+
+/// Checks if a value of an entry differs from its previous value, where `None` stands for a
+/// missing entry.
+fn changed(value: Option<&PyAny>, previous: Option<&PyAny>) -> PyResult<bool> {
+    match (value, previous) {
+        (Some(value), Some(previous)) => {
+            Ok(!value.rich_compare(previous, CompareOp::Eq)?.is_true()?)
+        }
+        (value, previous) => Ok(value.is_some() != previous.is_some()),
+    }
+}
+
+/// Keeps a plain, dictionary-like `target` object in sync with a `YMap`, as returned by `bind`.
+///
+/// Changes of the map, including changes of nested shared types, are copied to the target as
+/// soon as they're committed, with nested shared types converted into `str`, `list` and `dict`
+/// values. Changes of the target are written to the map by `flush_local_changes`, which compares
+/// top level entries of the target with their values as of the last synchronization.
+#[pyclass(unsendable)]
+pub struct Binding {
+    state: Rc<State>,
+    subscription: Option<SubscriptionId>,
+}
+
+#[pymethods]
+impl Binding {
+    /// The dictionary-like object kept in sync with the map.
+    #[getter]
+    pub fn target(&self, py: Python) -> PyObject {
+        self.state.target.clone_ref(py)
+    }
+
+    /// Writes entries of the target which have been changed since the last synchronization to the
+    /// map, within a given transaction or a new transaction of the document of the map. Nested
+    /// values are written as plain values, replacing nested shared types. Returns keys of the
+    /// written entries.
+    pub fn flush_local_changes(
+        &self,
+        py: Python,
+        txn: Option<PyRefMut<YTransaction>>,
+    ) -> PyResult<Vec<String>> {
+        if let Some(mut txn) = txn {
+            return self.state.push(py, &mut txn);
+        }
+        let txn = self.state.location.transaction(py)?;
+        let txn: &PyCell<YTransaction> = txn.as_ref(py).downcast()?;
+        let flushed = self.state.push(py, &mut txn.borrow_mut())?;
+        txn.borrow_mut().commit();
+        Ok(flushed)
+    }
+
+    /// Stops copying changes of the map to the target.
+    pub fn unbind(&mut self) {
+        if let Some(subscription) = self.subscription.take() {
+            let mut map = self.state.map.clone();
+            map.unobserve_deep(subscription);
+        }
+    }
+}
+
+/// Binds a `YMap` to a plain, dictionary-like `target` object, eg. a `dict`, which is kept updated
+/// with changes of the map. Changes made to the target are written back to the map with
+/// `Binding.flush_local_changes`. This makes it possible to use a synchronized map as a regular
+/// dictionary.
+///
+/// The target is filled with entries of the map right away. `conflict` decides what happens when
+/// the map receives a change of an entry which has been changed in the target since the last
+/// synchronization: with "crdt-wins" (the default) the change of the target is overwritten, while
+/// with "local-wins" it's kept and written to the map by the next flush. Entries of the target
+/// which are missing from the map are kept in both cases.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YDoc, bind
+///
+/// doc = YDoc()
+/// settings = {}
+/// binding = bind(doc.get_map('settings'), settings)
+/// settings['theme'] = 'dark'
+/// binding.flush_local_changes()
+/// ```
+#[pyfunction]
+pub fn bind(py: Python, map: &YMap, target: PyObject, conflict: Option<&str>) -> PyResult<Binding> {
+    let conflict = match conflict.unwrap_or("crdt-wins") {
+        "crdt-wins" => Conflict::CrdtWins,
+        "local-wins" => Conflict::LocalWins,
+        other => {
+            return Err(PyValueError::new_err(format!(
+                "'{other}' is not a valid conflict resolution (crdt-wins or local-wins)."
+            )))
+        }
+    };
+    let location = map.1.clone();
+    let mut map = match &map.0 {
+        SharedType::Integrated(map) => map.clone(),
+        SharedType::Prelim(_) => return Err(PreliminaryObservationException::default_message()),
+    };
+    let state = Rc::new(State {
+        map: map.clone(),
+        location,
+        target,
+        base: PyDict::new(py).into(),
+        conflict,
+    });
+    state.pull(py)?;
+    let observed = state.clone();
+    let subscription = map
+        .observe_deep(move |_, _| {
+            shutdown::with_gil(|py| {
+                if let Err(err) = observed.pull(py) {
+                    logging::callback_failed(py, err)
+                }
+            });
+        })
+        .into();
+    Ok(Binding {
+        state,
+        subscription: Some(subscription),
+    })
+}
@@ -0,0 +1,32 @@
+use crate::mmap_doc;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// Version of the bundled yrs crate, which has to match the one in Cargo.toml.
+const YRS_VERSION: &str = "0.12.2";
+/// Version of the bundled lib0 crate, which has to match the one in Cargo.toml.
+const LIB0_VERSION: &str = "0.12.2";
+/// Versions of the lib0 encoding of updates which can be applied and produced.
+const UPDATE_ENCODINGS: [u32; 1] = [1];
+/// Messages of y-protocols understood by `SyncConnection`.
+const PROTOCOL_MESSAGES: [&str; 4] = ["sync", "awareness", "auth", "query_awareness"];
+
+/// Returns versions of Ypy and of the bundled yrs and lib0 crates, along with versions of the
+/// formats it supports, so that deployments can check their compatibility with peers at startup:
+///
+/// - `y_py`, `yrs` and `lib0`: versions of the packages,
+/// - `update_encodings`: versions of the lib0 encoding of updates, which have to be supported by
+///   peers exchanging updates with Ypy,
+/// - `protocol_messages`: message types of y-protocols understood by `SyncConnection`,
+/// - `mmap_format`: version of the layout of files of `MmapDoc`.
+#[pyfunction]
+pub fn versions(py: Python) -> PyResult<PyObject> {
+    let versions = PyDict::new(py);
+    versions.set_item("y_py", env!("CARGO_PKG_VERSION"))?;
+    versions.set_item("yrs", YRS_VERSION)?;
+    versions.set_item("lib0", LIB0_VERSION)?;
+    versions.set_item("update_encodings", UPDATE_ENCODINGS.to_vec())?;
+    versions.set_item("protocol_messages", PROTOCOL_MESSAGES.to_vec())?;
+    versions.set_item("mmap_format", mmap_doc::FORMAT)?;
+    Ok(versions.into())
+}
@@ -0,0 +1,263 @@
+use crate::location::Location;
+use crate::shared_types::{check_root_type, is_within, YPyType};
+use crate::y_array::YArray;
+use crate::y_doc::YDoc;
+use crate::y_map::YMap;
+use crate::y_text::YText;
+use crate::y_transaction::YTransaction;
+use crate::y_xml::{YXmlElement, YXmlText};
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::convert::TryFrom;
+use yrs::types::{Branch, TYPE_REFS_XML_ELEMENT, TYPE_REFS_XML_TEXT};
+use yrs::{Transaction, Xml, XmlElement, XmlText};
+
+/// Deep-copies a shared type into another document (or another place of the same document) as
+/// fresh items, which are independent from the source.
+///
+/// A `target` can be:
+///
+/// - a `YDoc`, in which case `key` is the name of the root type receiving the copied content. If
+///   `txn` is not given, a new transaction of the target document is used.
+/// - a `YMap`, in which case the copy is stored under `key`.
+/// - a `YArray`, in which case the copy is inserted at index `key`, or appended if it's `None`.
+/// - a `YXmlElement`, in which case an XML `source` is inserted as a child at index `key`, or
+///   appended if it's `None`.
+///
+/// `YText` content is copied as a plain string, without formatting attributes. XML types can
+/// only be copied into document roots and other XML elements.
+///
+/// Returns a copied shared type.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YDoc, copy_shared
+///
+/// notebook = YDoc()
+/// other = YDoc()
+/// page = notebook.get_map('page')
+/// with other.begin_transaction() as txn:
+///     pages = txn.get_array('pages')
+///     copy_shared(page, pages, txn=txn)
+/// ```
+#[pyfunction]
+pub fn copy_shared(
+    py: Python,
+    source: &PyAny,
+    target: &PyAny,
+    key: Option<&PyAny>,
+    txn: Option<&PyCell<YTransaction>>,
+) -> PyResult<PyObject> {
+    let source = source_type(source)?;
+    if let Ok(doc) = target.downcast::<PyCell<YDoc>>() {
+        let name: String = match key {
+            Some(key) => key.extract()?,
+            None => return Err(PyTypeError::new_err("A root type name is required")),
+        };
+        return match txn {
+            Some(txn) => copy_into_root(py, &source, &mut txn.borrow_mut(), &name),
+            None => {
+                YDoc::doc_ref(doc)?;
+                copy_into_root(py, &source, &mut doc.borrow().begin_transaction(), &name)
+            }
+        };
+    }
+
+    let txn = match txn {
+        Some(txn) => txn,
+        None => {
+            return Err(PyTypeError::new_err(
+                "A transaction is required to copy into a shared type",
+            ))
+        }
+    };
+    let mut txn = txn.borrow_mut();
+    let txn: &mut YTransaction = &mut txn;
+    if let Ok(map) = target.downcast::<PyCell<YMap>>() {
+        let key: String = match key {
+            Some(key) => key.extract()?,
+            None => {
+                return Err(PyTypeError::new_err(
+                    "A key is required to copy into a YMap",
+                ))
+            }
+        };
+        map.borrow_mut().set(txn, &key, prelim_copy(py, &source)?)?;
+        map.borrow().__getitem__(&key)
+    } else if let Ok(array) = target.downcast::<PyCell<YArray>>() {
+        let index = match key {
+            Some(key) => key.extract()?,
+            None => array.borrow().__len__() as u32,
+        };
+        let copy = prelim_copy(py, &source)?;
+        array.borrow_mut().insert(py, txn, index, copy)
+    } else if let Ok(parent) = target.downcast::<PyCell<YXmlElement>>() {
+        let parent = parent.borrow();
+        let location = parent.1.child(parent.0.as_ref());
+        let parent = &parent.0;
+        let index = match key {
+            Some(key) => key.extract()?,
+            None => parent.len(),
+        };
+        match &source {
+            YPyType::XmlElement(element) => {
+                let element = element.borrow();
+                let element = &element.0;
+                check_not_within(element.as_ref(), parent.as_ref())?;
+                let copy = parent.insert_elem(txn, index, element.tag());
+                copy_xml_element(txn, element, &copy);
+                Ok(YXmlElement(copy, location).into_py(py))
+            }
+            YPyType::XmlText(text) => {
+                let copy = parent.insert_text(txn, index);
+                copy_xml_text(txn, &text.borrow().0, &copy);
+                Ok(YXmlText(copy, location).into_py(py))
+            }
+            _ => Err(PyTypeError::new_err(
+                "Only XML types can be copied into a YXmlElement",
+            )),
+        }
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "Cannot copy into {}, expected a YDoc, YMap, YArray or YXmlElement",
+            target.get_type().name()?
+        )))
+    }
+}
+
+fn source_type(source: &PyAny) -> PyResult<YPyType> {
+    if let Ok(element) = source.extract() {
+        Ok(YPyType::XmlElement(element))
+    } else if let Ok(text) = source.extract() {
+        Ok(YPyType::XmlText(text))
+    } else {
+        YPyType::try_from(source).map_err(|_| {
+            PyTypeError::new_err("Only YText, YArray, YMap, YXmlElement and YXmlText can be copied")
+        })
+    }
+}
+
+/// Copies content of a `source` into a root type `name`, which has the same type as the source.
+fn copy_into_root(
+    py: Python,
+    source: &YPyType,
+    txn: &mut YTransaction,
+    name: &str,
+) -> PyResult<PyObject> {
+    match source {
+        YPyType::Text(text) => {
+            let mut root = txn.get_text(name)?;
+            root.extend(txn, &text.borrow().__str__())?;
+            Ok(root.into_py(py))
+        }
+        YPyType::Array(array) => {
+            let items = prelim_items(py, array.borrow().to_list(Some(false), None, None)?)?;
+            let mut root = txn.get_array(name)?;
+            root.extend(py, txn, items)?;
+            Ok(root.into_py(py))
+        }
+        YPyType::Map(map) => {
+            let entries = map.borrow().to_dict(Some(false), None)?;
+            let mut root = txn.get_map(name)?;
+            for (key, value) in entries.as_ref(py).downcast::<PyDict>()?.iter() {
+                let value = prelim_copy_value(py, value)?;
+                root.set(txn, key.extract()?, value)?;
+            }
+            Ok(root.into_py(py))
+        }
+        YPyType::XmlElement(element) => {
+            let element = element.borrow();
+            let element = &element.0;
+            let root = txn.get_xml_element(name);
+            check_root_type(root.as_ref(), name, TYPE_REFS_XML_ELEMENT)?;
+            check_not_within(element.as_ref(), root.as_ref())?;
+            copy_xml_element(txn, element, &root);
+            Ok(YXmlElement(root, Location::root(&txn.doc, name)).into_py(py))
+        }
+        YPyType::XmlText(text) => {
+            let root = txn.get_xml_text(name);
+            check_root_type(root.as_ref(), name, TYPE_REFS_XML_TEXT)?;
+            copy_xml_text(txn, &text.borrow().0, &root);
+            Ok(YXmlText(root, Location::root(&txn.doc, name)).into_py(py))
+        }
+    }
+}
+
+/// Builds a preliminary instance holding a deep copy of a given shared type.
+fn prelim_copy(py: Python, source: &YPyType) -> PyResult<PyObject> {
+    match source {
+        YPyType::Text(text) => Ok(YText::new(Some(text.borrow().__str__())).into_py(py)),
+        YPyType::Array(array) => {
+            let items = prelim_items(py, array.borrow().to_list(Some(false), None, None)?)?;
+            Ok(YArray::new(Some(items))?.into_py(py))
+        }
+        YPyType::Map(map) => {
+            let entries = map.borrow().to_dict(Some(false), None)?;
+            let copy = PyDict::new(py);
+            for (key, value) in entries.as_ref(py).downcast::<PyDict>()?.iter() {
+                copy.set_item(key, prelim_copy_value(py, value)?)?;
+            }
+            Ok(YMap::new(copy)?.into_py(py))
+        }
+        YPyType::XmlElement(_) | YPyType::XmlText(_) => Err(PyTypeError::new_err(
+            "XML types can only be copied into document roots and YXmlElement instances",
+        )),
+    }
+}
+
+fn prelim_copy_value(py: Python, value: &PyAny) -> PyResult<PyObject> {
+    match source_type(value) {
+        Ok(shared) => prelim_copy(py, &shared),
+        Err(_) => Ok(value.into()),
+    }
+}
+
+fn prelim_items(py: Python, items: PyObject) -> PyResult<PyObject> {
+    let items = items
+        .as_ref(py)
+        .iter()?
+        .map(|item| prelim_copy_value(py, item?))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(PyList::new(py, items).into())
+}
+
+pub(crate) fn copy_xml_element(txn: &mut Transaction, source: &XmlElement, target: &XmlElement) {
+    for (name, value) in source.attributes() {
+        target.insert_attribute(txn, name, value);
+    }
+    let mut child = source.first_child();
+    while let Some(node) = child {
+        child = match &node {
+            Xml::Element(element) => {
+                let copy = target.push_elem_back(txn, element.tag());
+                copy_xml_element(txn, element, &copy);
+                element.next_sibling()
+            }
+            Xml::Text(text) => {
+                let copy = target.push_text_back(txn);
+                copy_xml_text(txn, text, &copy);
+                text.next_sibling()
+            }
+        };
+    }
+}
+
+pub(crate) fn copy_xml_text(txn: &mut Transaction, source: &XmlText, target: &XmlText) {
+    for (name, value) in source.attributes() {
+        target.insert_attribute(txn, name, value);
+    }
+    target.push(txn, &source.to_string());
+}
+
+/// Prevents copying an XML element into itself or into one of its descendants, which would never
+/// stop copying freshly created children.
+fn check_not_within(source: &Branch, target: &Branch) -> PyResult<()> {
+    if is_within(target, source) {
+        return Err(PyValueError::new_err(
+            "Cannot copy an XML element into itself or its descendants",
+        ));
+    }
+    Ok(())
+}
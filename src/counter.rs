@@ -0,0 +1,100 @@
+use crate::shared_types::{DefaultPyErr, IntegratedOperationException, SharedType, SourceGuard};
+use crate::y_map::YMap;
+use crate::y_transaction::YTransaction;
+use lib0::any::Any;
+use pyo3::exceptions::PyOverflowError;
+use pyo3::prelude::*;
+use yrs::types::Value;
+use yrs::Map;
+
+/// A number shared by all replicas of a document, which can be incremented concurrently without
+/// losing increments, as opposed to `map.set(txn, 'count', map['count'] + 1)`, where concurrent
+/// writes of the same key overwrite each other.
+///
+/// A counter is backed by a `YMap`, in which every client only updates its own entry holding a
+/// sum of its increments. A value of the counter is a sum of all entries, so it converges once
+/// replicas have exchanged their updates. Increments can be negative.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YCounter, YDoc
+///
+/// doc = YDoc()
+/// likes = YCounter(doc.get_map('likes'))
+/// with doc.begin_transaction() as txn:
+///     likes.increment(txn)
+///     print(likes.value(txn))
+/// ```
+#[pyclass(unsendable)]
+pub struct YCounter {
+    map: Map,
+    guard: SourceGuard,
+}
+
+impl YCounter {
+    /// Returns a sum of increments of a given client.
+    fn entry(&self, client_id: u64) -> i64 {
+        self.map
+            .get(&client_id.to_string())
+            .and_then(integer)
+            .unwrap_or(0)
+    }
+}
+
+/// Returns an integer stored as a value of a map. Integers which fit into JavaScript numbers are
+/// stored as floating point numbers.
+fn integer(value: Value) -> Option<i64> {
+    match value {
+        Value::Any(Any::Number(n)) => Some(n as i64),
+        Value::Any(Any::BigInt(n)) => Some(n),
+        _ => None,
+    }
+}
+
+#[pymethods]
+impl YCounter {
+    #[new]
+    pub fn new(map: &PyCell<YMap>) -> PyResult<Self> {
+        let inner = match &map.borrow().0 {
+            SharedType::Integrated(inner) => inner.clone(),
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        let guard = SourceGuard::new(map, Some(inner.as_ref()), &map.borrow().1);
+        Ok(YCounter { map: inner, guard })
+    }
+
+    /// Adds `n` (1 by default) to this counter, and returns its new value.
+    pub fn increment(
+        &mut self,
+        py: Python,
+        txn: &mut YTransaction,
+        n: Option<i64>,
+    ) -> PyResult<i64> {
+        self.guard.check(py)?;
+        let client_id = txn.client_id;
+        let total = self
+            .entry(client_id)
+            .checked_add(n.unwrap_or(1))
+            .ok_or_else(|| PyOverflowError::new_err("The counter has overflowed"))?;
+        YMap::from(self.map.clone()).set(txn, &client_id.to_string(), total.into_py(py))?;
+        self.value(py, txn)
+    }
+
+    /// Returns a current value of this counter.
+    pub fn value(&self, py: Python, _txn: &YTransaction) -> PyResult<i64> {
+        self.guard.check(py)?;
+        let mut value: i64 = 0;
+        for (key, entry) in self.map.iter() {
+            // Entries which don't belong to clients are skipped
+            let entry = match integer(entry) {
+                Some(entry) if key.parse::<u64>().is_ok() => entry,
+                _ => continue,
+            };
+            value = value
+                .checked_add(entry)
+                .ok_or_else(|| PyOverflowError::new_err("The counter has overflowed"))?;
+        }
+        Ok(value)
+    }
+}
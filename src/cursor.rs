@@ -0,0 +1,437 @@
+use crate::shared_types::{
+    DefaultPyErr, EncodingError, IntegratedOperationException, SharedType, SourceGuard,
+};
+use crate::snapshot::{
+    read_items, read_structs, write_padding, write_var_uint, Item, Owner, Reader,
+};
+use crate::y_text::{char_len, chunk_len, item_chunks, offset_kind, text_chunks, TextChunk, YText};
+use crate::y_transaction::YTransaction;
+use lib0::any::Any;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use yrs::types::{PathSegment, Value};
+use yrs::updates::decoder::Decode;
+use yrs::{DeleteSet, Doc, OffsetKind, Snapshot, Text, Transaction, Update, ID};
+
+/// A position within a text, which refers to the character right after it instead of an index,
+/// so that it stays in place when the text around it changes.
+#[derive(Clone)]
+enum Position {
+    Start,
+    /// Before characters given by ranges `(id, len)`, starting with the one right after the
+    /// position, and preceded by a number of units of content without ids (eg. embedded values).
+    /// Following ranges are used to find the place of the position once the character right
+    /// after it has been deleted.
+    Before(u32, Vec<(ID, u32)>),
+    End,
+}
+
+/// A selection within a `YText`, eg. a cursor of a user in a collaborative text editor. Both ends
+/// of the selection are stored relative to the characters they point at, so they follow local and
+/// remote edits of the text: inserting text before a cursor moves it forward, and deleting a
+/// selected fragment collapses the selection at the place of the deletion. Indexes use the offset
+/// kind of the document, like all other `YText` methods. Embedded values have no identity a cursor
+/// could refer to, so a position right before one follows the text after it.
+///
+/// A new cursor is collapsed at the start of the text.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YDoc, Cursor
+///
+/// doc = YDoc()
+/// text = doc.get_text('name')
+/// cursor = Cursor(text)
+/// with doc.begin_transaction() as txn:
+///     text.extend(txn, 'hello world')
+///     cursor.set(txn, 6, 11)
+/// ...
+/// with doc.begin_transaction() as txn:
+///     anchor, head = cursor.range(txn)
+/// ```
+#[pyclass(unsendable)]
+pub struct Cursor {
+    text: Text,
+    guard: SourceGuard,
+    anchor: Position,
+    head: Position,
+}
+
+#[pymethods]
+impl Cursor {
+    #[new]
+    pub fn new(text: &PyCell<YText>) -> PyResult<Self> {
+        let inner = match &text.borrow().0 {
+            SharedType::Integrated(inner) => inner.clone(),
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        let guard = SourceGuard::new(text, Some(inner.as_ref()), &text.borrow().1);
+        Ok(Cursor {
+            text: inner,
+            guard,
+            anchor: Position::Start,
+            head: Position::Start,
+        })
+    }
+
+    /// Moves this cursor to select text between the `anchor` and `head` indexes. If `head` is not
+    /// given, the selection is collapsed at the `anchor`. Indexes past the end of the text stick
+    /// to its end.
+    pub fn set(
+        &mut self,
+        py: Python,
+        txn: &mut YTransaction,
+        anchor: u32,
+        head: Option<u32>,
+    ) -> PyResult<()> {
+        self.guard.check(py)?;
+        let chunks = text_chunks(&self.text, txn);
+        let kind = offset_kind(&self.text, &chunks);
+        self.anchor = position(&chunks, anchor, &kind);
+        self.head = match head {
+            Some(head) => position(&chunks, head, &kind),
+            None => self.anchor.clone(),
+        };
+        Ok(())
+    }
+
+    /// Returns current `(anchor, head)` indexes of this cursor. The anchor can be placed after
+    /// the head, if the text was selected backwards.
+    pub fn range(&self, py: Python, txn: &mut YTransaction) -> PyResult<(u32, u32)> {
+        self.guard.check(py)?;
+        let chunks = text_chunks(&self.text, txn);
+        let kind = offset_kind(&self.text, &chunks);
+        let len = self.text.len();
+        Ok((
+            resolve(&chunks, &self.anchor, len, &kind),
+            resolve(&chunks, &self.head, len, &kind),
+        ))
+    }
+}
+
+/// Returns a position of a character at a given `index`.
+fn position(chunks: &[TextChunk], mut index: u32, kind: &OffsetKind) -> Position {
+    let mut chunks = chunks.iter();
+    let mut skip = 0;
+    let mut ranges = Vec::new();
+    for chunk in chunks.by_ref() {
+        let len = chunk_len(chunk, kind);
+        if index < len {
+            match chunk {
+                TextChunk::String(Some(id), chunk) => {
+                    let offset = clock_offset(chunk, index, kind);
+                    let len = chunk.encode_utf16().count() as u32;
+                    ranges.push((ID::new(id.client, id.clock + offset), len - offset));
+                }
+                _ => skip = len - index,
+            }
+            break;
+        }
+        index -= len;
+    }
+    if ranges.is_empty() && skip == 0 {
+        return Position::End;
+    }
+    for chunk in chunks {
+        match chunk {
+            TextChunk::String(Some(id), chunk) => {
+                ranges.push((*id, chunk.encode_utf16().count() as u32))
+            }
+            chunk if ranges.is_empty() => skip += chunk_len(chunk, kind),
+            _ => {}
+        }
+    }
+    Position::Before(skip, ranges)
+}
+
+/// Returns a current index of a given `position`. Once the character right after a position has
+/// been deleted, the position moves to the closest following character which still exists.
+fn resolve(chunks: &[TextChunk], position: &Position, len: u32, kind: &OffsetKind) -> u32 {
+    let (skip, ranges) = match position {
+        Position::Start => return 0,
+        Position::Before(skip, ranges) => (*skip, ranges),
+        Position::End => return len,
+    };
+    for (range, range_len) in ranges {
+        // Characters of a range come from a single item, so the one with the lowest clock is the
+        // first one in the text.
+        let range_end = range.clock + range_len;
+        let mut first: Option<(u32, u32)> = None;
+        let mut index = 0;
+        for chunk in chunks {
+            if let TextChunk::String(Some(start), chunk) = chunk {
+                let end = start.clock + chunk.encode_utf16().count() as u32;
+                let clock = start.clock.max(range.clock);
+                if start.client == range.client && clock < end.min(range_end) {
+                    let at = index + index_offset(chunk, clock - start.clock, kind);
+                    if first.map_or(true, |(first, _)| clock < first) {
+                        first = Some((clock, at));
+                    }
+                }
+            }
+            index += chunk_len(chunk, kind);
+        }
+        if let Some((_, at)) = first {
+            return at.saturating_sub(skip);
+        }
+    }
+    len.saturating_sub(skip)
+}
+
+/// A start of a page read by `range` methods: an index, or a continuation token returned by a
+/// read of a previous page. Tokens refer to the first character or element of a page by its id.
+#[derive(FromPyObject)]
+pub enum PageStart {
+    Index(u32),
+    Token(String),
+}
+
+impl PageStart {
+    /// Returns a current index of this start within a text with given visible `chunks`. A token
+    /// pointing at a deleted character resolves to the place of the deletion.
+    pub(crate) fn text_index(
+        &self,
+        text: &Text,
+        txn: &mut Transaction,
+        chunks: &[TextChunk],
+        kind: &OffsetKind,
+    ) -> PyResult<u32> {
+        let id = match self {
+            PageStart::Index(index) => return Ok(*index),
+            PageStart::Token(token) => decode_token(token)?,
+        };
+        let mut ranges = Vec::new();
+        for (start, chunk) in item_chunks(text, txn) {
+            let len = chunk.encode_utf16().count() as u32;
+            if !ranges.is_empty() {
+                ranges.push((start, len));
+            } else if start.client == id.client
+                && start.clock <= id.clock
+                && id.clock < start.clock + len
+            {
+                ranges.push((id, start.clock + len - id.clock));
+            }
+        }
+        let position = if ranges.is_empty() {
+            Position::End
+        } else {
+            Position::Before(0, ranges)
+        };
+        Ok(resolve(chunks, &position, text.len(), kind))
+    }
+
+    /// Returns a current index of this start within a sequence with given `elements`. A token
+    /// pointing at a deleted element resolves to the place of the deletion.
+    pub(crate) fn element_index(
+        &self,
+        elements: &[(ID, u32)],
+        deletes: &DeleteSet,
+    ) -> PyResult<u32> {
+        let id = match self {
+            PageStart::Index(index) => return Ok(*index),
+            PageStart::Token(token) => decode_token(token)?,
+        };
+        let mut index = 0;
+        for (start, len) in elements {
+            for clock in start.clock..start.clock + len {
+                let unit = ID::new(start.client, clock);
+                if unit == id {
+                    return Ok(index);
+                }
+                if !deletes.is_deleted(&unit) {
+                    index += 1;
+                }
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// Encodes an id of the first character or element of a page as a continuation token.
+pub(crate) fn encode_token(id: ID) -> String {
+    format!("{}:{}", id.client, id.clock)
+}
+
+fn decode_token(token: &str) -> PyResult<ID> {
+    token
+        .split_once(':')
+        .and_then(|(client, clock)| Some(ID::new(client.parse().ok()?, clock.parse().ok()?)))
+        .ok_or_else(|| PyValueError::new_err(format!("'{token}' is not a valid token")))
+}
+
+/// Returns string contents of a text with given `chunks` between `start` and `end` indexes,
+/// together with an id of the first character following them. Characters crossing either of the
+/// indexes belong to the page they start in.
+pub(crate) fn text_page(
+    chunks: &[TextChunk],
+    start: u32,
+    end: u32,
+    kind: &OffsetKind,
+) -> (String, Option<ID>) {
+    let mut page = String::new();
+    let mut index = 0;
+    for chunk in chunks {
+        let (id, chunk) = match chunk {
+            TextChunk::String(id, chunk) => (id, chunk),
+            TextChunk::Embed => {
+                index += 1;
+                continue;
+            }
+        };
+        let mut clock = id.map(|id| id.clock);
+        for c in chunk.chars() {
+            if index >= end {
+                if let (Some(id), Some(clock)) = (id, clock) {
+                    return (page, Some(ID::new(id.client, clock)));
+                }
+            } else if index >= start {
+                page.push(c);
+            }
+            index += char_len(c, kind);
+            clock = clock.map(|clock| clock + c.len_utf16() as u32);
+        }
+    }
+    (page, None)
+}
+
+/// Returns an id of the element at a given `index` of a sequence with given `elements`.
+pub(crate) fn element_at(elements: &[(ID, u32)], deletes: &DeleteSet, index: u32) -> Option<ID> {
+    elements
+        .iter()
+        .flat_map(|(start, len)| {
+            (start.clock..start.clock + len).map(move |c| ID::new(start.client, c))
+        })
+        .filter(|unit| !deletes.is_deleted(unit))
+        .nth(index as usize)
+}
+
+/// Returns ids of all elements of a sequence at a given `path` from its root type, in order,
+/// including deleted ones, as ranges `(id, len)` of consecutive ids. Elements are read from an
+/// encoded document `state`, as yrs exposes ids of characters of texts only.
+pub(crate) fn element_ids(
+    state: &[u8],
+    path: &[PathSegment],
+    deletes: &DeleteSet,
+) -> PyResult<Vec<(ID, u32)>> {
+    let (items, _) = read_items(state)?;
+    let mut segments = path.iter();
+    let mut owner = match segments.next() {
+        Some(PathSegment::Key(name)) => Owner::Root(name),
+        _ => return Ok(Vec::new()),
+    };
+    for segment in segments {
+        let id = match segment {
+            PathSegment::Key(key) => items
+                .iter()
+                .map(|item| (item, ID::new(item.client, item.clock as u32)))
+                .find(|(item, id)| {
+                    item.owner == Some(owner)
+                        && item.key == Some(key.as_ref())
+                        && !deletes.is_deleted(id)
+                })
+                .map(|(_, id)| id),
+            PathSegment::Index(index) => {
+                element_at(&sequence_ids(state, &items, owner)?, deletes, *index)
+            }
+        };
+        owner = match id {
+            Some(id) => Owner::Type(id.client, id.clock as u64),
+            None => return Ok(Vec::new()),
+        };
+    }
+    sequence_ids(state, &items, owner)
+}
+
+/// Returns ids of elements of a sequence contained by a given `owner`. Items of the sequence are
+/// integrated into a text of a new document, with a character in place of every element, which
+/// orders them the same way they are ordered in their document. Yrs expects blocks of every client
+/// to have no gaps, so other structs are replaced with padding in another root type.
+pub(crate) fn sequence_ids(state: &[u8], items: &[Item], owner: Owner) -> PyResult<Vec<(ID, u32)>> {
+    let structs = read_structs(&mut Reader::new(state))?;
+    // Items are read from the same state, so they follow structs other than skipped ranges
+    let mut items = items.iter();
+    let mut uncounted = DeleteSet::new();
+    let mut update = Vec::new();
+    write_var_uint(&mut update, structs.len() as u64);
+    for (client, start, blocks) in structs.iter() {
+        write_var_uint(&mut update, blocks.len() as u64 + 1);
+        write_var_uint(&mut update, *client);
+        write_var_uint(&mut update, *start);
+        for block in blocks.iter() {
+            let item = if block.is_skip() { None } else { items.next() };
+            match item {
+                Some(item) if item.owner == Some(owner) && item.key.is_none() => {
+                    if !block.is_countable() {
+                        uncounted.insert(ID::new(*client, block.clock as u32), block.len as u32);
+                    }
+                    block.write_placeholder(&mut update, "sequence");
+                }
+                _ => write_padding(&mut update, "padding", block.len),
+            }
+        }
+        // Yrs panics while splitting blocks by a snapshot, unless every client has a clock of
+        // at least 3
+        write_padding(&mut update, "padding", 3);
+    }
+    // Deletions are left out, so that deleted elements keep their place
+    write_var_uint(&mut update, 0);
+    let update = Update::decode_v1(&update).map_err(|e| EncodingError::new_err(e.to_string()))?;
+
+    let doc = Doc::new();
+    let mut txn = doc.transact();
+    let text = txn.get_text("sequence");
+    txn.apply_update(update);
+    let snapshot = txn.snapshot();
+    let mut elements: Vec<(ID, u32)> = Vec::new();
+    for diff in text.diff_range(&mut txn, Some(&snapshot), Some(&Snapshot::default()), |c| {
+        c.id
+    }) {
+        let (len, id) = match (diff.insert, diff.ychange) {
+            (Value::Any(Any::String(chunk)), Some(id)) => (chunk.len() as u32, id),
+            _ => continue,
+        };
+        for clock in id.clock..id.clock + len {
+            let unit = ID::new(id.client, clock);
+            match elements.last_mut() {
+                _ if uncounted.is_deleted(&unit) => {}
+                Some((start, len))
+                    if start.client == unit.client && start.clock + *len == clock =>
+                {
+                    *len += 1
+                }
+                _ => elements.push((unit, 1)),
+            }
+        }
+    }
+    Ok(elements)
+}
+
+/// Converts an `index` within a string chunk into an offset of item clocks, which are counted in
+/// UTF-16 code units. Indexes in the middle of a character are moved to its start.
+pub(crate) fn clock_offset(chunk: &str, index: u32, kind: &OffsetKind) -> u32 {
+    let mut units = 0;
+    let mut offset = 0;
+    for c in chunk.chars() {
+        units += char_len(c, kind);
+        if units > index {
+            break;
+        }
+        offset += c.len_utf16() as u32;
+    }
+    offset
+}
+
+/// Converts an offset of item clocks within a string chunk into an index.
+pub(crate) fn index_offset(chunk: &str, offset: u32, kind: &OffsetKind) -> u32 {
+    let mut units = 0;
+    let mut clock = 0;
+    for c in chunk.chars() {
+        if clock >= offset {
+            break;
+        }
+        clock += c.len_utf16() as u32;
+        units += char_len(c, kind);
+    }
+    units
+}
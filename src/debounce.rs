@@ -0,0 +1,325 @@
+use crate::logging::{self, Level};
+use crate::shutdown::{self, Callback};
+use crate::type_conversions::ToPython;
+use crate::y_text::char_len;
+use lib0::any::Any;
+use pyo3::intern;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use yrs::types::{Attrs, Change, Delta, Value};
+use yrs::OffsetKind;
+
+/// Formatting attributes of a text delta, where `None` removes an attribute.
+type Attributes = BTreeMap<String, PyObject>;
+
+/// Inserted content of a delta.
+pub(crate) enum Content {
+    /// A chunk of a text.
+    Text(String),
+    /// An embedded object of a text, which is never merged with adjacent content.
+    Embed(PyObject),
+    /// Elements of an array.
+    Values(Vec<PyObject>),
+}
+
+/// An operation of a delta, owning its values so that it outlives the event it comes from.
+pub(crate) enum Op {
+    Insert(Content, Option<Attributes>),
+    Retain(u32, Option<Attributes>),
+    Delete(u32),
+}
+
+impl Op {
+    fn len(&self, kind: &OffsetKind) -> u32 {
+        match self {
+            Op::Insert(Content::Text(text), _) => text.chars().map(|c| char_len(c, kind)).sum(),
+            Op::Insert(Content::Embed(_), _) => 1,
+            Op::Insert(Content::Values(values), _) => values.len() as u32,
+            Op::Retain(len, _) | Op::Delete(len) => *len,
+        }
+    }
+
+    /// Splits off the first `len` units of this operation, leaving the rest of it in place.
+    fn split(&mut self, len: u32, kind: &OffsetKind) -> Op {
+        match self {
+            Op::Insert(Content::Text(text), attrs) => {
+                let mut units = 0;
+                let at = text
+                    .char_indices()
+                    .find(|(_, c)| {
+                        units += char_len(*c, kind);
+                        units > len
+                    })
+                    .map_or(text.len(), |(i, _)| i);
+                let rest = text.split_off(at);
+                let head = std::mem::replace(text, rest);
+                Op::Insert(Content::Text(head), attrs.clone())
+            }
+            Op::Insert(Content::Values(values), attrs) => {
+                let rest = values.split_off(len as usize);
+                let head = std::mem::replace(values, rest);
+                Op::Insert(Content::Values(head), attrs.clone())
+            }
+            Op::Insert(Content::Embed(_), _) => unreachable!("embeds have a length of 1"),
+            Op::Retain(n, attrs) => {
+                *n -= len;
+                Op::Retain(len, attrs.clone())
+            }
+            Op::Delete(n) => {
+                *n -= len;
+                Op::Delete(len)
+            }
+        }
+    }
+
+    fn into_py(self, py: Python) -> PyObject {
+        let result = PyDict::new(py);
+        let attrs = match self {
+            Op::Insert(content, attrs) => {
+                let value = match content {
+                    Content::Text(text) => text.into_py(py),
+                    Content::Embed(value) => value,
+                    Content::Values(values) => values.into_py(py),
+                };
+                result.set_item(intern!(py, "insert"), value).unwrap();
+                attrs
+            }
+            Op::Retain(len, attrs) => {
+                result.set_item(intern!(py, "retain"), len).unwrap();
+                attrs
+            }
+            Op::Delete(len) => {
+                result.set_item(intern!(py, "delete"), len).unwrap();
+                None
+            }
+        };
+        if let Some(attrs) = attrs {
+            result
+                .set_item(intern!(py, "attributes"), attrs.into_py(py))
+                .unwrap();
+        }
+        result.into()
+    }
+}
+
+fn attributes(py: Python, attrs: Option<&Attrs>) -> Option<Attributes> {
+    attrs.map(|attrs| {
+        attrs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone().into_py(py)))
+            .collect()
+    })
+}
+
+/// Converts a delta of a text event into operations.
+pub(crate) fn text_delta(py: Python, delta: &[Delta]) -> Vec<Op> {
+    delta
+        .iter()
+        .map(|delta| match delta {
+            Delta::Inserted(Value::Any(Any::String(text)), attrs) => Op::Insert(
+                Content::Text(text.to_string()),
+                attributes(py, attrs.as_deref()),
+            ),
+            Delta::Inserted(value, attrs) => Op::Insert(
+                Content::Embed(value.clone().into_py(py)),
+                attributes(py, attrs.as_deref()),
+            ),
+            Delta::Retain(len, attrs) => Op::Retain(*len, attributes(py, attrs.as_deref())),
+            Delta::Deleted(len) => Op::Delete(*len),
+        })
+        .collect()
+}
+
+/// Converts a delta of an array event into operations.
+pub(crate) fn array_delta(py: Python, delta: &[Change]) -> Vec<Op> {
+    delta
+        .iter()
+        .map(|change| match change {
+            Change::Added(values) => Op::Insert(
+                Content::Values(values.iter().map(|v| v.clone().into_py(py)).collect()),
+                None,
+            ),
+            Change::Retain(len) => Op::Retain(*len, None),
+            Change::Removed(len) => Op::Delete(*len),
+        })
+        .collect()
+}
+
+/// Merges attributes of a retain operation into attributes of an operation it's applied to.
+/// Removed attributes are kept as `None` when composing retains, so that they're still removed
+/// when the composed delta is applied.
+fn compose_attributes(
+    py: Python,
+    a: Option<Attributes>,
+    b: Option<Attributes>,
+    keep_removed: bool,
+) -> Option<Attributes> {
+    let mut attrs = a.unwrap_or_default();
+    for (key, value) in b.unwrap_or_default() {
+        if value.is_none(py) && !keep_removed {
+            attrs.remove(&key);
+        } else {
+            attrs.insert(key, value);
+        }
+    }
+    if attrs.is_empty() {
+        None
+    } else {
+        Some(attrs)
+    }
+}
+
+/// Appends an operation to a delta, merging it with the last operation when possible.
+fn push(delta: &mut Vec<Op>, op: Op) {
+    let op = match (delta.last_mut(), op) {
+        (Some(Op::Delete(n)), Op::Delete(len)) => {
+            *n += len;
+            return;
+        }
+        (Some(Op::Retain(n, None)), Op::Retain(len, None)) => {
+            *n += len;
+            return;
+        }
+        (Some(Op::Insert(Content::Text(text), None)), Op::Insert(Content::Text(more), None)) => {
+            text.push_str(&more);
+            return;
+        }
+        (
+            Some(Op::Insert(Content::Values(values), None)),
+            Op::Insert(Content::Values(more), None),
+        ) => {
+            values.extend(more);
+            return;
+        }
+        (_, op) => op,
+    };
+    delta.push(op)
+}
+
+/// Takes the next operation of a delta, or its first `len` units, and advances the delta. A delta
+/// is followed by an infinite retain.
+fn take(
+    delta: &mut std::iter::Peekable<std::vec::IntoIter<Op>>,
+    len: u32,
+    kind: &OffsetKind,
+) -> Op {
+    match delta.peek_mut() {
+        Some(op) if op.len(kind) > len => op.split(len, kind),
+        Some(_) => delta.next().unwrap(),
+        None => Op::Retain(len, None),
+    }
+}
+
+/// Composes two consecutive deltas into a single delta, which has the same effect as applying
+/// `a` followed by `b`.
+fn compose(py: Python, a: Vec<Op>, b: Vec<Op>, kind: &OffsetKind) -> Vec<Op> {
+    let mut a = a.into_iter().peekable();
+    let mut b = b.into_iter().peekable();
+    let mut delta = Vec::new();
+    loop {
+        match (a.peek(), b.peek()) {
+            (None, None) => break,
+            (_, Some(Op::Insert(..))) => push(&mut delta, b.next().unwrap()),
+            (Some(Op::Delete(_)), _) => push(&mut delta, a.next().unwrap()),
+            (a_op, b_op) => {
+                let len = match (a_op, b_op) {
+                    (Some(a_op), Some(b_op)) => a_op.len(kind).min(b_op.len(kind)),
+                    (Some(op), None) | (None, Some(op)) => op.len(kind),
+                    (None, None) => unreachable!(),
+                };
+                let a_op = take(&mut a, len, kind);
+                let b_op = take(&mut b, len, kind);
+                match (a_op, b_op) {
+                    (Op::Retain(_, a_attrs), Op::Retain(_, b_attrs)) => {
+                        let attrs = compose_attributes(py, a_attrs, b_attrs, true);
+                        push(&mut delta, Op::Retain(len, attrs))
+                    }
+                    (Op::Insert(content, a_attrs), Op::Retain(_, b_attrs)) => {
+                        let attrs = compose_attributes(py, a_attrs, b_attrs, false);
+                        push(&mut delta, Op::Insert(content, attrs))
+                    }
+                    (Op::Retain(..), Op::Delete(_)) => push(&mut delta, Op::Delete(len)),
+                    // Content inserted by `a` and deleted by `b` is dropped altogether
+                    (Op::Insert(..), Op::Delete(_)) => {}
+                    _ => unreachable!("inserts of `b` and deletes of `a` are taken as a whole"),
+                }
+            }
+        }
+    }
+    if let Some(Op::Retain(_, None)) = delta.last() {
+        delta.pop();
+    }
+    delta
+}
+
+#[derive(Default)]
+struct Pending {
+    delta: Option<Vec<Op>>,
+    deadline: Option<Instant>,
+}
+
+/// Coalesces deltas of events which follow each other within a given delay, and passes them to a
+/// callback as a single delta once no event has happened for the duration of the delay. Callbacks
+/// are called on a timer thread, which acquires the GIL on its own.
+#[derive(Clone)]
+pub(crate) struct Debouncer {
+    pending: Arc<Mutex<Pending>>,
+    delay: Duration,
+    callback: Callback,
+}
+
+impl Debouncer {
+    pub(crate) fn new(callback: Callback, delay_ms: u64) -> Self {
+        Debouncer {
+            pending: Arc::default(),
+            delay: Duration::from_millis(delay_ms),
+            callback,
+        }
+    }
+
+    /// Adds a delta of an event to the coalesced delta, and postpones the callback.
+    pub(crate) fn push(&self, py: Python, delta: Vec<Op>, kind: &OffsetKind) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.delta = Some(match pending.delta.take() {
+            Some(previous) => compose(py, previous, delta, kind),
+            None => delta,
+        });
+        let scheduled = pending.deadline.is_some();
+        pending.deadline = Some(Instant::now() + self.delay);
+        if !scheduled {
+            let debouncer = self.clone();
+            thread::spawn(move || debouncer.run());
+        }
+    }
+
+    /// Waits for the deadline of the coalesced delta, which moves with every new event, and then
+    /// passes the delta to the callback.
+    fn run(self) {
+        let delta = loop {
+            let wait = {
+                let mut pending = self.pending.lock().unwrap();
+                let deadline = pending.deadline.unwrap();
+                let now = Instant::now();
+                if now >= deadline {
+                    pending.deadline = None;
+                    break pending.delta.take().unwrap_or_default();
+                }
+                deadline - now
+            };
+            thread::sleep(wait);
+        };
+        shutdown::with_gil(|py| {
+            let delta = PyList::new(py, delta.into_iter().map(|op| op.into_py(py)));
+            if let Err(err) = self.callback.call1(py, (delta,)) {
+                logging::emit(py, Level::Error, "observer_error", |record| {
+                    record.set_item("exception", err.value(py))
+                });
+                err.print(py);
+            }
+        });
+    }
+}
@@ -0,0 +1,176 @@
+use crate::snapshot::{self, read_delete_set, read_structs, write_delete_set, Reader};
+use crate::type_conversions::BinaryInput;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::pyclass::CompareOp;
+use pyo3::types::{PyBytes, PyList};
+use std::collections::HashMap;
+
+/// A set of deleted items of a document, described by ranges of their clocks grouped by ids of
+/// clients which have created them. Unlike state vectors, delete sets reflect deletions, which
+/// makes them necessary to compact stored updates without losing information, as done by
+/// y-leveldb.
+///
+/// Ranges of every client are kept sorted, with overlapping and adjacent ranges merged.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import DeleteSet
+///
+/// deleted = DeleteSet()
+/// for update in stored_updates:
+///     deleted = deleted.merge(DeleteSet.from_update(update))
+/// for client, ranges in deleted.items():
+///     print(client, ranges)
+/// ```
+#[pyclass]
+#[derive(Clone, Default, PartialEq)]
+pub struct DeleteSet(snapshot::DeleteSet);
+
+#[pymethods]
+impl DeleteSet {
+    /// Creates a delete set with given `ranges` of clients, each a list of `(clock, len)` pairs,
+    /// which is empty by default.
+    #[new]
+    pub fn new(ranges: Option<HashMap<u64, Vec<(u64, u64)>>>) -> PyResult<Self> {
+        let mut delete_set = DeleteSet::default();
+        for (client, ranges) in ranges.unwrap_or_default() {
+            for (clock, len) in ranges {
+                delete_set.add(client, clock, len)?;
+            }
+        }
+        Ok(delete_set)
+    }
+
+    /// Decodes a delete set encoded using lib0 v1 encoding, eg. `AfterTransactionEvent.delete_set`.
+    #[staticmethod]
+    pub fn decode(data: BinaryInput) -> PyResult<Self> {
+        let mut reader = Reader::new(&data);
+        let delete_set = read_delete_set(&mut reader)?;
+        reader.finish()?;
+        Ok(DeleteSet::normalized(delete_set))
+    }
+
+    /// Reads a delete set of an update encoded using lib0 v1 encoding.
+    #[staticmethod]
+    pub fn from_update(update: BinaryInput) -> PyResult<Self> {
+        let mut reader = Reader::new(&update);
+        read_structs(&mut reader)?;
+        let delete_set = read_delete_set(&mut reader)?;
+        reader.finish()?;
+        Ok(DeleteSet::normalized(delete_set))
+    }
+
+    /// Encodes this delete set using lib0 v1 encoding.
+    pub fn encode(&self, py: Python) -> PyObject {
+        let mut buffer = Vec::new();
+        write_delete_set(&mut buffer, &self.0);
+        PyBytes::new(py, &buffer).into()
+    }
+
+    /// Marks `len` items of a given client, starting from `clock`, as deleted.
+    pub fn add(&mut self, client: u64, clock: u64, len: u64) -> PyResult<()> {
+        if clock.checked_add(len).is_none() {
+            return Err(PyValueError::new_err(format!(
+                "Range {client}:{clock} exceeds the range of clocks"
+            )));
+        }
+        if len > 0 {
+            let ranges = self.0.entry(client).or_insert_with(Vec::new);
+            ranges.push((clock, len));
+            *ranges = merge_ranges(ranges);
+        }
+        Ok(())
+    }
+
+    /// Returns a new delete set with items deleted in this or `other` delete set.
+    pub fn merge(&self, other: &DeleteSet) -> DeleteSet {
+        let mut delete_set = self.0.clone();
+        for (client, ranges) in other.0.iter() {
+            delete_set
+                .entry(*client)
+                .or_insert_with(Vec::new)
+                .extend_from_slice(ranges);
+        }
+        DeleteSet::normalized(delete_set)
+    }
+
+    /// Checks if all `len` items of a given client, starting from `clock`, are deleted.
+    pub fn is_deleted(&self, client: u64, clock: u64, len: Option<u64>) -> bool {
+        snapshot::is_deleted(&self.0, client, clock, len.unwrap_or(1))
+    }
+
+    /// Returns deleted ranges of a given client as `(clock, len)` pairs, which is empty for
+    /// clients without deletions.
+    pub fn __getitem__(&self, client: u64) -> Vec<(u64, u64)> {
+        self.0.get(&client).cloned().unwrap_or_default()
+    }
+
+    pub fn __contains__(&self, client: u64) -> bool {
+        self.0.contains_key(&client)
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns an iterator over ids of clients.
+    pub fn __iter__(&self, py: Python) -> PyResult<PyObject> {
+        Ok(PyList::new(py, self.0.keys()).as_ref().iter()?.into())
+    }
+
+    /// Returns a list of `(client, ranges)` pairs, ordered by client ids.
+    pub fn items(&self) -> Vec<(u64, Vec<(u64, u64)>)> {
+        self.0
+            .iter()
+            .map(|(client, ranges)| (*client, ranges.clone()))
+            .collect()
+    }
+
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyObject {
+        let py = other.py();
+        match (other.extract::<PyRef<DeleteSet>>(), op) {
+            (Ok(other), CompareOp::Eq) => (*self == *other).into_py(py),
+            (Ok(other), CompareOp::Ne) => (*self != *other).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    pub fn __repr__(&self) -> String {
+        let clients: Vec<String> = self
+            .0
+            .iter()
+            .map(|(client, ranges)| format!("{client}: {ranges:?}"))
+            .collect();
+        format!("DeleteSet({{{}}})", clients.join(", "))
+    }
+}
+
+impl DeleteSet {
+    /// Creates a delete set from ranges in any order, merging ranges which overlap.
+    pub(crate) fn normalized(mut delete_set: snapshot::DeleteSet) -> Self {
+        for ranges in delete_set.values_mut() {
+            *ranges = merge_ranges(ranges);
+        }
+        delete_set.retain(|_, ranges| !ranges.is_empty());
+        DeleteSet(delete_set)
+    }
+}
+
+/// Sorts `(clock, len)` ranges, merging overlapping and adjacent ones and dropping empty ones.
+fn merge_ranges(ranges: &[(u64, u64)]) -> Vec<(u64, u64)> {
+    let mut sorted = ranges.to_vec();
+    sorted.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(sorted.len());
+    for (clock, len) in sorted.into_iter().filter(|(_, len)| *len > 0) {
+        let end = clock.saturating_add(len);
+        match merged.last_mut() {
+            Some((start, last_len)) if *start + *last_len >= clock => {
+                *last_len = (*start + *last_len).max(end) - *start;
+            }
+            _ => merged.push((clock, len)),
+        }
+    }
+    merged
+}
@@ -0,0 +1,255 @@
+use crate::logging::{self, Level};
+use crate::shutdown::{self, Callback};
+use pyo3::once_cell::GILOnceCell;
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread::{self, JoinHandle};
+
+enum Job {
+    Call(Callback, Py<PyTuple>),
+    Stop,
+}
+
+/// Number of jobs which have been queued but haven't finished yet.
+#[derive(Default)]
+struct Pending {
+    count: Mutex<usize>,
+    done: Condvar,
+}
+
+impl Pending {
+    fn start(&self) {
+        *self.count.lock().unwrap() += 1;
+    }
+
+    fn finish(&self) {
+        let mut count = self.count.lock().unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.done.notify_all();
+        }
+    }
+}
+
+/// A thread of a `Dispatcher`, which is stopped once the dispatcher is closed or the interpreter
+/// exits.
+struct Worker {
+    sender: Mutex<Sender<Job>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Worker {
+    fn stop(&self, py: Python) {
+        let thread = self.thread.lock().unwrap().take();
+        if let Some(thread) = thread {
+            let _ = self.sender.lock().unwrap().send(Job::Stop);
+            py.allow_threads(move || {
+                let _ = thread.join();
+            });
+        }
+    }
+}
+
+/// Workers of all dispatchers, which are stopped by `stop_workers`.
+static WORKERS: GILOnceCell<Mutex<Vec<Weak<Worker>>>> = GILOnceCell::new();
+
+/// Stops threads of all dispatchers. Threads waiting for the GIL while the interpreter finalizes
+/// are terminated by Python, which aborts the process, so they are stopped beforehand. Queued
+/// callbacks are dropped, as the interpreter is finalizing already.
+pub(crate) fn stop_workers(py: Python) {
+    let workers = match WORKERS.get(py) {
+        Some(workers) => std::mem::take(&mut *workers.lock().unwrap()),
+        None => return,
+    };
+    for worker in workers.iter().filter_map(Weak::upgrade) {
+        worker.stop(py);
+    }
+}
+
+/// A handle used by observers to queue callbacks of a `Dispatcher`.
+#[derive(Clone)]
+pub(crate) struct DispatchHandle {
+    sender: Sender<Job>,
+    pending: Arc<Pending>,
+}
+
+impl DispatchHandle {
+    /// Queues a call of a `callback` with given `args` on the thread of the dispatcher. If the
+    /// dispatcher has been closed, the callback is called right away instead.
+    pub(crate) fn dispatch(
+        &self,
+        py: Python,
+        callback: Callback,
+        args: Py<PyTuple>,
+    ) -> PyResult<()> {
+        self.pending.start();
+        if let Err(err) = self.sender.send(Job::Call(callback, args)) {
+            self.pending.finish();
+            if let Job::Call(callback, args) = err.0 {
+                callback.call1(py, args.as_ref(py))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs observer callbacks on a dedicated thread, which acquires the GIL on its own. This way a
+/// thread committing transactions, eg. a request handler of a server, doesn't wait for callbacks
+/// doing heavy work, such as broadcasting updates over the network or persisting them.
+///
+/// Callbacks are called one at a time, in order in which they have been queued. Exceptions raised
+/// by callbacks are passed to an `on_error(exception)` callback if it's provided, and printed
+/// otherwise. As Python runs signal handlers on the main thread only, signals such as
+/// `KeyboardInterrupt` don't interrupt callbacks in the middle of their work.
+///
+/// Only callbacks registered with `YDoc.observe_after_transaction` can be dispatched, which
+/// receive a `TransactionRecord` instead of an `AfterTransactionEvent`, as events are only valid
+/// while transactions are being committed. A dispatcher can be used as a context manager, which
+/// closes it on exit.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import Dispatcher, YDoc
+///
+/// dispatcher = Dispatcher()
+/// doc = YDoc()
+/// doc.observe_after_transaction(lambda record: broadcast(record.get_update()), dispatcher)
+/// ```
+#[pyclass]
+pub struct Dispatcher {
+    handle: DispatchHandle,
+    worker: Arc<Worker>,
+}
+
+impl Dispatcher {
+    pub(crate) fn handle(&self) -> DispatchHandle {
+        self.handle.clone()
+    }
+}
+
+#[pymethods]
+impl Dispatcher {
+    #[new]
+    pub fn new(py: Python, on_error: Option<PyObject>) -> Self {
+        let (sender, receiver) = channel();
+        let pending = Arc::new(Pending::default());
+        let worker_pending = pending.clone();
+        let worker = thread::spawn(move || {
+            // Callbacks dropped by `shutdown` aren't called, and neither are callbacks queued
+            // while the interpreter is finalizing
+            let run = |callback: Callback, args: Py<PyTuple>| {
+                shutdown::with_gil(|py| {
+                    if let Err(err) = callback.call1(py, args.as_ref(py)) {
+                        logging::emit(py, Level::Error, "observer_error", |record| {
+                            record.set_item("exception", err.value(py))
+                        });
+                        let reported = match &on_error {
+                            Some(on_error) => on_error.call1(py, (err.value(py),)),
+                            None => Err(err),
+                        };
+                        if let Err(err) = reported {
+                            err.print(py);
+                        }
+                    }
+                });
+                worker_pending.finish();
+            };
+            for job in receiver.iter() {
+                match job {
+                    Job::Call(callback, args) => run(callback, args),
+                    Job::Stop => break,
+                }
+            }
+            // Callbacks queued while the dispatcher was being closed are still called
+            for job in receiver.try_iter() {
+                if let Job::Call(callback, args) = job {
+                    run(callback, args);
+                }
+            }
+        });
+        let worker = Arc::new(Worker {
+            sender: Mutex::new(sender.clone()),
+            thread: Mutex::new(Some(worker)),
+        });
+        let mut workers = WORKERS
+            .get_or_init(py, || Mutex::new(Vec::new()))
+            .lock()
+            .unwrap();
+        workers.retain(|worker| worker.strong_count() > 0);
+        workers.push(Arc::downgrade(&worker));
+        Dispatcher {
+            handle: DispatchHandle { sender, pending },
+            worker,
+        }
+    }
+
+    /// Number of callbacks which have been queued but haven't finished yet.
+    #[getter]
+    pub fn pending(&self) -> usize {
+        *self.handle.pending.count.lock().unwrap()
+    }
+
+    /// Blocks until all queued callbacks have finished.
+    pub fn wait(&self, py: Python) {
+        let pending = self.handle.pending.clone();
+        py.allow_threads(move || {
+            let mut count = pending.count.lock().unwrap();
+            while *count > 0 {
+                count = pending.done.wait(count).unwrap();
+            }
+        })
+    }
+
+    /// Waits for queued callbacks to finish and stops the thread of this dispatcher. Callbacks
+    /// dispatched afterwards are called right away by the thread committing a transaction.
+    pub fn close(&mut self, py: Python) {
+        self.worker.stop(py)
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        py: Python,
+        _exception_type: Option<&PyAny>,
+        _exception_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> bool {
+        self.close(py);
+        false
+    }
+}
+
+/// A record of a committed transaction, passed to `YDoc.observe_after_transaction` callbacks run
+/// by a `Dispatcher`. Unlike `AfterTransactionEvent`, it's encoded as soon as the transaction is
+/// committed, so it remains valid afterwards.
+#[pyclass]
+pub struct TransactionRecord {
+    /// A state vector of the document before the transaction, using lib0 v1 encoding.
+    #[pyo3(get)]
+    pub before_state: PyObject,
+    /// A state vector of the document after the transaction, using lib0 v1 encoding.
+    #[pyo3(get)]
+    pub after_state: PyObject,
+    /// A delete set of the transaction, using lib0 v1 encoding.
+    #[pyo3(get)]
+    pub delete_set: PyObject,
+    /// Origin of the transaction, see `YTransaction.with_origin`.
+    #[pyo3(get)]
+    pub origin: PyObject,
+    pub(crate) update: PyObject,
+}
+
+#[pymethods]
+impl TransactionRecord {
+    /// Returns an update of the committed transaction, using lib0 v1 encoding and transforms
+    /// registered with `YDoc.add_update_transform`.
+    pub fn get_update(&self, py: Python) -> PyObject {
+        self.update.clone_ref(py)
+    }
+}
@@ -0,0 +1,268 @@
+use crate::logging;
+use crate::shutdown::{self, Callback};
+use crate::type_conversions::BinaryInput;
+use crate::y_doc::{apply_update, encode_state_as_update, YDoc};
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use yrs::SubscriptionId;
+
+/// Callbacks registered with `DocRegistry.observe`, optionally limited to a single document.
+#[derive(Default)]
+struct Observers {
+    next_id: u32,
+    callbacks: Vec<(u32, Option<String>, Callback)>,
+}
+
+struct Entry {
+    doc: Py<YDoc>,
+    refs: usize,
+    idle_since: Option<Instant>,
+    /// Cleared once the document is unloaded, which detaches its update observer.
+    loaded: Rc<Cell<bool>>,
+}
+
+/// Manages a set of named `YDoc` instances, eg. collaborative rooms of a server.
+///
+/// Documents are loaded lazily on their first `acquire` using an optional `load(name)` callback,
+/// which returns a document update or `None` for new documents. Each `acquire` must be paired with
+/// a `release`. Documents which are no longer in use for at least `idle_timeout` seconds are
+/// unloaded by `unload_idle`, after their state has been passed to an optional
+/// `save(name, update)` callback.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import DocRegistry
+///
+/// storage = {}
+/// registry = DocRegistry(load=storage.get, save=storage.__setitem__, idle_timeout=60)
+/// registry.observe(lambda name, update: broadcast(name, update))
+///
+/// doc = registry.acquire('room')
+/// ...
+/// registry.release('room')
+/// registry.unload_idle()
+/// ```
+#[pyclass(unsendable)]
+pub struct DocRegistry {
+    load: Option<PyObject>,
+    save: Option<PyObject>,
+    idle_timeout: Duration,
+    entries: HashMap<String, Entry>,
+    observers: Rc<RefCell<Observers>>,
+}
+
+#[pymethods]
+impl DocRegistry {
+    #[new]
+    pub fn new(
+        load: Option<PyObject>,
+        save: Option<PyObject>,
+        idle_timeout: Option<f64>,
+    ) -> PyResult<Self> {
+        let idle_timeout = idle_timeout.unwrap_or(0.0);
+        if !idle_timeout.is_finite() || idle_timeout < 0.0 {
+            return Err(PyValueError::new_err(
+                "idle_timeout must be a non-negative number of seconds",
+            ));
+        }
+        Ok(DocRegistry {
+            load,
+            save,
+            idle_timeout: Duration::from_secs_f64(idle_timeout),
+            entries: HashMap::new(),
+            observers: Rc::default(),
+        })
+    }
+
+    /// Returns a document registered under a given `name`, loading it if necessary, and marks it
+    /// as being in use until a matching `release` call.
+    pub fn acquire(&mut self, py: Python, name: &str) -> PyResult<Py<YDoc>> {
+        if let Some(entry) = self.entries.get_mut(name) {
+            entry.refs += 1;
+            entry.idle_since = None;
+            return Ok(entry.doc.clone_ref(py));
+        }
+        let doc = Py::new(py, YDoc::new(None, None, None)?)?;
+        if let Some(load) = &self.load {
+            let update = load.call1(py, (name,))?;
+            if !update.is_none(py) {
+                let update: BinaryInput = update.extract(py)?;
+                apply_update(py, &mut doc.borrow_mut(py), update)?;
+            }
+        }
+        let loaded = Rc::new(Cell::new(true));
+        self.forward_updates(&mut doc.borrow_mut(py), name, loaded.clone());
+        self.entries.insert(
+            name.to_string(),
+            Entry {
+                doc: doc.clone_ref(py),
+                refs: 1,
+                idle_since: None,
+                loaded,
+            },
+        );
+        Ok(doc)
+    }
+
+    /// Marks a document previously returned by `acquire` as no longer used by the caller.
+    pub fn release(&mut self, name: &str) -> PyResult<()> {
+        let entry = self.entry(name)?;
+        if entry.refs == 0 {
+            return Err(PyValueError::new_err(format!(
+                "Document '{name}' has been released more times than acquired"
+            )));
+        }
+        entry.refs -= 1;
+        if entry.refs == 0 {
+            entry.idle_since = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Returns a number of `acquire` calls not yet matched by `release` for a given document.
+    pub fn refcount(&mut self, name: &str) -> PyResult<usize> {
+        Ok(self.entry(name)?.refs)
+    }
+
+    /// Saves and unloads all documents which haven't been in use for at least `idle_timeout`
+    /// seconds. Returns names of unloaded documents.
+    pub fn unload_idle(&mut self, py: Python) -> PyResult<Vec<String>> {
+        let idle_timeout = self.idle_timeout;
+        let mut names: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| match entry.idle_since {
+                Some(since) => since.elapsed() >= idle_timeout,
+                None => false,
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        for name in names.iter() {
+            self.unload(py, name)?;
+        }
+        Ok(names)
+    }
+
+    /// Saves and unloads a given document. Fails if the document is still in use.
+    pub fn unload(&mut self, py: Python, name: &str) -> PyResult<()> {
+        let refs = self.entry(name)?.refs;
+        if refs > 0 {
+            return Err(PyValueError::new_err(format!(
+                "Document '{name}' is still in use ({refs} references)"
+            )));
+        }
+        self.save_doc(py, name)?;
+        if let Some(entry) = self.entries.remove(name) {
+            entry.loaded.set(false);
+        }
+        Ok(())
+    }
+
+    /// Passes a current state of all loaded documents to the `save` callback.
+    pub fn flush(&self, py: Python) -> PyResult<()> {
+        let mut names: Vec<&String> = self.entries.keys().collect();
+        names.sort();
+        for name in names {
+            self.save_doc(py, name)?;
+        }
+        Ok(())
+    }
+
+    /// Subscribes a `callback(name, update)` to updates of all documents managed by this registry,
+    /// or only to the document called `name` if given. Updates are encoded using lib0 v1 encoding.
+    /// Subscriptions outlive unloading and reloading of documents. Returns a subscription id which
+    /// can be used to cancel the callback with `unobserve`.
+    pub fn observe(&mut self, py: Python, callback: PyObject, name: Option<String>) -> u32 {
+        let callback = Callback::new(py, callback);
+        let mut observers = self.observers.borrow_mut();
+        let id = observers.next_id;
+        observers.next_id += 1;
+        observers.callbacks.push((id, name, callback));
+        id
+    }
+
+    /// Cancels the observer callback associated with the `subscription_id`.
+    pub fn unobserve(&mut self, subscription_id: u32) {
+        let mut observers = self.observers.borrow_mut();
+        observers
+            .callbacks
+            .retain(|(id, _, _)| *id != subscription_id);
+    }
+
+    /// Returns names of all loaded documents.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.entries.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn __contains__(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("DocRegistry({})", self.names().join(", "))
+    }
+}
+
+impl DocRegistry {
+    fn entry(&mut self, name: &str) -> PyResult<&mut Entry> {
+        self.entries
+            .get_mut(name)
+            .ok_or_else(|| PyKeyError::new_err(format!("Document '{name}' is not loaded")))
+    }
+
+    fn save_doc(&self, py: Python, name: &str) -> PyResult<()> {
+        if let (Some(save), Some(entry)) = (&self.save, self.entries.get(name)) {
+            let update = encode_state_as_update(&entry.doc.borrow(py), None)?;
+            save.call1(py, (name, update))?;
+        }
+        Ok(())
+    }
+
+    /// Passes updates of a given document to registered observers for as long as it's loaded.
+    fn forward_updates(&self, doc: &mut YDoc, name: &str, loaded: Rc<Cell<bool>>) {
+        let observers = self.observers.clone();
+        let name = name.to_string();
+        // The observer doesn't need to be cancelled, as it's dropped together with the document
+        let _: SubscriptionId = doc
+            .inner
+            .observe_transaction_cleanup(move |txn, event| {
+                let unchanged =
+                    event.before_state == event.after_state && event.delete_set.is_empty();
+                if !loaded.get() || unchanged {
+                    return;
+                }
+                shutdown::with_gil(|py| {
+                    let callbacks: Vec<Callback> = observers
+                        .borrow()
+                        .callbacks
+                        .iter()
+                        .filter(|(_, doc_name, _)| doc_name.as_ref().map_or(true, |n| *n == name))
+                        .map(|(_, _, callback)| callback.clone())
+                        .collect();
+                    if callbacks.is_empty() {
+                        return;
+                    }
+                    let update = PyBytes::new(py, &txn.encode_update_v1());
+                    for callback in callbacks {
+                        if let Err(err) = callback.call1(py, (name.as_str(), update)) {
+                            logging::callback_failed(py, err)
+                        }
+                    }
+                });
+            })
+            .into();
+    }
+}
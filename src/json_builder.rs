@@ -1,9 +1,11 @@
 use std::{collections::HashMap, convert::TryFrom};
 
 use lib0::any::Any;
-use pyo3::{exceptions::PyTypeError, PyErr, PyObject, PyResult, Python};
+use pyo3::{exceptions::PyTypeError, PyAny, PyErr, PyObject, PyResult, Python};
+use yrs::types::Value;
 
-use crate::shared_types::{CompatiblePyType, YPyType};
+use crate::shared_types::{CompatiblePyType, SharedType, YPyType};
+use crate::type_conversions::{dict_key, plain_any};
 
 #[derive(Clone, Debug)]
 pub(crate) struct JsonBuilder(String);
@@ -25,6 +27,11 @@ impl From<JsonBuilder> for String {
     }
 }
 
+/// Appends a given string to the `buffer` as a quoted and escaped JSON string.
+pub(crate) fn push_json_string(buffer: &mut String, value: &str) {
+    Any::String(value.into()).to_json(buffer)
+}
+
 pub(crate) trait JsonBuildable {
     type JsonError;
     fn build_json(&self, buffer: &mut String) -> Result<(), Self::JsonError>;
@@ -41,12 +48,9 @@ impl<'a> JsonBuildable for CompatiblePyType<'a> {
             }
             CompatiblePyType::Int(i) => buffer.push_str(&i.to_string()),
             CompatiblePyType::Float(f) => buffer.push_str(&f.to_string()),
-            CompatiblePyType::String(s) => {
-                let string: String = s.extract().unwrap();
-                buffer.reserve(string.len() + 2);
-                buffer.push_str("\"");
-                buffer.push_str(&string);
-                buffer.push_str("\"");
+            CompatiblePyType::String(s) => push_json_string(buffer, s.to_str()?),
+            CompatiblePyType::Bytes(_) | CompatiblePyType::Buffer(_) => {
+                Any::try_from(self.clone())?.build_json(buffer)?
             }
             CompatiblePyType::List(list) => {
                 buffer.push_str("[");
@@ -64,7 +68,7 @@ impl<'a> JsonBuildable for CompatiblePyType<'a> {
                 buffer.push_str("{");
                 let length = dict.len();
                 for (i, (k, v)) in dict.iter().enumerate() {
-                    CompatiblePyType::try_from(k)?.build_json(buffer)?;
+                    push_json_string(buffer, &dict_key(k)?);
                     buffer.push_str(":");
                     CompatiblePyType::try_from(v)?.build_json(buffer)?;
                     if i + 1 < length {
@@ -74,7 +78,7 @@ impl<'a> JsonBuildable for CompatiblePyType<'a> {
                 buffer.push_str("}");
             }
             CompatiblePyType::YType(y_type) => y_type.build_json(buffer)?,
-            CompatiblePyType::None => buffer.push_str("null"),
+            CompatiblePyType::None | CompatiblePyType::Undefined => buffer.push_str("null"),
         }
 
         Ok(())
@@ -86,9 +90,9 @@ impl<'a> JsonBuildable for YPyType<'a> {
 
     fn build_json(&self, buffer: &mut String) -> Result<(), Self::JsonError> {
         let json = match self {
-            YPyType::Text(text) => Ok(text.borrow().to_json()),
-            YPyType::Array(array) => array.borrow().to_json(),
-            YPyType::Map(map) => map.borrow().to_json(),
+            YPyType::Text(text) => Ok(text.borrow().to_json(None, None)),
+            YPyType::Array(array) => array.borrow().to_json(None, None),
+            YPyType::Map(map) => map.borrow().to_json(None, None),
             xml => Err(PyTypeError::new_err(format!(
                 "XML elements cannot be converted to a JSON format: {xml}"
             ))),
@@ -117,7 +121,7 @@ impl JsonBuildable for HashMap<String, PyObject> {
                 if i != 0 {
                     buffer.push_str(",");
                 }
-                buffer.push_str(k);
+                push_json_string(buffer, k);
                 buffer.push_str(":");
                 value.build_json(buffer)?;
             }
@@ -150,3 +154,174 @@ impl JsonBuildable for Vec<PyObject> {
         Ok(())
     }
 }
+
+/// Size of the chunks of JSON passed by `JsonWriter` to a file object.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Serializes values of shared types into JSON, passing it in chunks to the `write` method of a
+/// Python file object. Nested shared types are written as they are traversed, so that neither an
+/// `Any` representation nor a string of the whole value has to be built.
+pub(crate) struct JsonWriter<'py> {
+    file: &'py PyAny,
+    buffer: String,
+}
+
+impl<'py> JsonWriter<'py> {
+    pub fn new(file: &'py PyAny) -> PyResult<Self> {
+        if !file.hasattr("write")? {
+            let found = file.get_type().name()?;
+            return Err(PyTypeError::new_err(format!(
+                "JSON can only be written to a file object with a write method, found {found}"
+            )));
+        }
+        Ok(JsonWriter {
+            file,
+            buffer: String::with_capacity(CHUNK_SIZE),
+        })
+    }
+
+    /// Writes a `value` read from a shared type.
+    pub fn write_value(&mut self, value: Value) -> PyResult<()> {
+        match value {
+            Value::YArray(array) => {
+                self.buffer.push('[');
+                for (i, item) in array.iter().enumerate() {
+                    if i != 0 {
+                        self.buffer.push(',');
+                    }
+                    self.write_value(item)?;
+                }
+                self.buffer.push(']');
+            }
+            Value::YMap(map) => {
+                self.buffer.push('{');
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i != 0 {
+                        self.buffer.push(',');
+                    }
+                    push_json_string(&mut self.buffer, key);
+                    self.buffer.push(':');
+                    self.write_value(value)?;
+                }
+                self.buffer.push('}');
+            }
+            Value::YText(text) => push_json_string(&mut self.buffer, &text.to_string()),
+            value => value.to_json().to_json(&mut self.buffer),
+        }
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes an already serialized piece of JSON.
+    pub fn write_str(&mut self, json: &str) -> PyResult<()> {
+        self.buffer.push_str(json);
+        self.flush()
+    }
+
+    /// Writes the rest of the buffered JSON.
+    pub fn finish(mut self) -> PyResult<()> {
+        self.flush()
+    }
+
+    fn flush(&mut self) -> PyResult<()> {
+        if !self.buffer.is_empty() {
+            self.file.call_method1("write", (self.buffer.as_str(),))?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+}
+
+/// Converts a `value` read from a shared type into its JSON representation. Shared types nested
+/// more than `depth` levels below the `value` are not resolved, but replaced by placeholders of
+/// the form `{"$type": "YMap", "length": 3}`.
+pub(crate) fn limited_json(value: Value, depth: usize) -> Any {
+    let nested = |value: Value| match depth.checked_sub(1) {
+        Some(depth) => limited_json(value, depth),
+        None => placeholder_of(value),
+    };
+    match value {
+        Value::YArray(array) => Any::Array(array.iter().map(&nested).collect()),
+        Value::YMap(map) => Any::Map(Box::new(
+            map.iter()
+                .map(|(key, value)| (key.to_string(), nested(value)))
+                .collect(),
+        )),
+        value => value.to_json(),
+    }
+}
+
+/// Equivalent of `limited_json` for contents of a preliminary `YArray`.
+pub(crate) fn limited_prelim_array(py: Python, items: &[PyObject], depth: usize) -> PyResult<Any> {
+    let items = items
+        .iter()
+        .map(|item| limited_py_json(item.as_ref(py), depth))
+        .collect::<PyResult<_>>()?;
+    Ok(Any::Array(items))
+}
+
+/// Equivalent of `limited_json` for contents of a preliminary `YMap`.
+pub(crate) fn limited_prelim_map(
+    py: Python,
+    entries: &HashMap<String, PyObject>,
+    depth: usize,
+) -> PyResult<Any> {
+    let entries = entries
+        .iter()
+        .map(|(key, value)| Ok((key.clone(), limited_py_json(value.as_ref(py), depth)?)))
+        .collect::<PyResult<_>>()?;
+    Ok(Any::Map(Box::new(entries)))
+}
+
+/// Converts a Python value stored in a preliminary shared type, resolving a nested shared type
+/// only if `depth` hasn't been exhausted.
+fn limited_py_json(value: &PyAny, depth: usize) -> PyResult<Any> {
+    let shared = match CompatiblePyType::try_from(value)? {
+        CompatiblePyType::YType(shared) => shared,
+        _ => return plain_any(value),
+    };
+    let depth = match depth.checked_sub(1) {
+        Some(depth) => depth,
+        None => {
+            return Ok(match shared {
+                YPyType::Text(text) => placeholder("YText", text.borrow().__len__()),
+                YPyType::Array(array) => placeholder("YArray", array.borrow().__len__()),
+                YPyType::Map(map) => placeholder("YMap", map.borrow().__len__()),
+                YPyType::XmlElement(xml) => placeholder("YXmlElement", xml.borrow().__len__()),
+                YPyType::XmlText(xml) => placeholder("YXmlText", xml.borrow().__len__()),
+            })
+        }
+    };
+    let py = value.py();
+    match shared {
+        YPyType::Array(array) => match &array.borrow().0 {
+            SharedType::Integrated(array) => Ok(limited_json(Value::YArray(array.clone()), depth)),
+            SharedType::Prelim(items) => limited_prelim_array(py, items, depth),
+        },
+        YPyType::Map(map) => match &map.borrow().0 {
+            SharedType::Integrated(map) => Ok(limited_json(Value::YMap(map.clone()), depth)),
+            SharedType::Prelim(entries) => limited_prelim_map(py, entries, depth),
+        },
+        _ => plain_any(value),
+    }
+}
+
+fn placeholder_of(value: Value) -> Any {
+    match value {
+        Value::YText(text) => placeholder("YText", text.len() as usize),
+        Value::YArray(array) => placeholder("YArray", array.len() as usize),
+        Value::YMap(map) => placeholder("YMap", map.len() as usize),
+        Value::YXmlElement(xml) => placeholder("YXmlElement", xml.len() as usize),
+        Value::YXmlText(xml) => placeholder("YXmlText", xml.len() as usize),
+        value => value.to_json(),
+    }
+}
+
+fn placeholder(name: &str, length: usize) -> Any {
+    let mut entries = HashMap::with_capacity(2);
+    entries.insert("$type".to_string(), Any::String(name.into()));
+    entries.insert("length".to_string(), Any::Number(length as f64));
+    Any::Map(Box::new(entries))
+}
@@ -0,0 +1,495 @@
+use crate::location::Location;
+use crate::shared_types::{root_branches, CompatiblePyType, YError};
+use lib0::any::Any;
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+use yrs::types::{Branch, PathSegment, TypeRefs, TYPE_REFS_ARRAY, TYPE_REFS_MAP, TYPE_REFS_TEXT};
+use yrs::{Array, Doc, Map, Text};
+
+create_exception!(
+    y_py,
+    ValidationError,
+    YError,
+    "Occurs when a change of a shared type is rejected by a JSON Schema attached with YDoc.set_schema."
+);
+
+/// Keywords which don't constrain values, and are accepted but ignored.
+const ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl Kind {
+    fn parse(name: &str) -> Option<Kind> {
+        match name {
+            "null" => Some(Kind::Null),
+            "boolean" => Some(Kind::Boolean),
+            "integer" => Some(Kind::Integer),
+            "number" => Some(Kind::Number),
+            "string" => Some(Kind::String),
+            "array" => Some(Kind::Array),
+            "object" => Some(Kind::Object),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Null => "null",
+            Kind::Boolean => "boolean",
+            Kind::Integer => "integer",
+            Kind::Number => "number",
+            Kind::String => "string",
+            Kind::Array => "array",
+            Kind::Object => "object",
+        }
+    }
+
+    fn matches(self, value: &Any) -> bool {
+        match (self, value) {
+            (Kind::Null, Any::Null) => true,
+            (Kind::Boolean, Any::Bool(_)) => true,
+            (Kind::Integer, Any::Number(n)) => n.fract() == 0.0,
+            (Kind::Integer, Any::BigInt(_)) => true,
+            (Kind::Number, Any::Number(_)) | (Kind::Number, Any::BigInt(_)) => true,
+            (Kind::String, Any::String(_)) => true,
+            (Kind::Array, Any::Array(_)) => true,
+            (Kind::Object, Any::Map(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Describes a kind of a value in validation errors.
+fn describe(value: &Any) -> &'static str {
+    match value {
+        Any::Null => "null",
+        Any::Undefined => "undefined",
+        Any::Bool(_) => "boolean",
+        Any::Number(n) if n.fract() == 0.0 => "integer",
+        Any::Number(_) => "number",
+        Any::BigInt(_) => "integer",
+        Any::String(_) => "string",
+        Any::Buffer(_) => "bytes",
+        Any::Array(_) => "array",
+        Any::Map(_) => "object",
+    }
+}
+
+fn number(value: &Any) -> Option<f64> {
+    match value {
+        Any::Number(n) => Some(*n),
+        Any::BigInt(n) => Some(*n as f64),
+        _ => None,
+    }
+}
+
+/// Compares values the way JSON does, where numbers are equal regardless of their representation.
+fn same(a: &Any, b: &Any) -> bool {
+    match (a, b) {
+        (Any::Array(a), Any::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same(a, b))
+        }
+        (Any::Map(a), Any::Map(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).map_or(false, |b| same(a, b)))
+        }
+        _ => match (number(a), number(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => a == b,
+        },
+    }
+}
+
+fn invalid(pointer: &str, reason: &str) -> PyErr {
+    PyValueError::new_err(format!("Invalid JSON Schema at '{pointer}': {reason}"))
+}
+
+fn bound(value: &Any, pointer: &str) -> PyResult<f64> {
+    number(value).ok_or_else(|| invalid(pointer, "expected a number"))
+}
+
+fn count(value: &Any, pointer: &str) -> PyResult<usize> {
+    match number(value) {
+        Some(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+        _ => Err(invalid(pointer, "expected a non-negative integer")),
+    }
+}
+
+/// A JSON Schema compiled from its JSON representation. Supported keywords are those describing
+/// plain JSON values: `type`, `enum`, `const`, numeric bounds, string lengths, `items`,
+/// `minItems`, `maxItems`, `properties`, `required` and `additionalProperties`.
+#[derive(Default)]
+pub(crate) struct Schema {
+    /// Set for the `false` schema, which rejects all values.
+    reject: bool,
+    kinds: Option<Vec<Kind>>,
+    /// Values allowed by `enum` or `const`.
+    allowed: Option<Vec<Any>>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    items: Option<Rc<Schema>>,
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    properties: HashMap<String, Rc<Schema>>,
+    required: Vec<String>,
+    additional: Option<Rc<Schema>>,
+}
+
+impl Schema {
+    /// Compiles a schema given as a dictionary or a boolean. Fails with a `ValueError` for
+    /// malformed schemas and keywords which aren't supported.
+    pub(crate) fn from_py(schema: &PyAny) -> PyResult<Schema> {
+        let schema = Any::try_from(CompatiblePyType::try_from(schema)?)?;
+        Schema::compile(&schema, "#")
+    }
+
+    fn compile(schema: &Any, pointer: &str) -> PyResult<Schema> {
+        let keywords = match schema {
+            Any::Bool(allowed) => {
+                return Ok(Schema {
+                    reject: !allowed,
+                    ..Schema::default()
+                })
+            }
+            Any::Map(keywords) => keywords,
+            _ => return Err(invalid(pointer, "expected an object or a boolean")),
+        };
+        let mut compiled = Schema::default();
+        for (keyword, value) in keywords.iter() {
+            let pointer = format!("{pointer}/{keyword}");
+            let pointer = pointer.as_str();
+            match (keyword.as_str(), value) {
+                ("type", Any::String(name)) => {
+                    let kind = Kind::parse(name).ok_or_else(|| invalid(pointer, "unknown type"))?;
+                    compiled.kinds = Some(vec![kind]);
+                }
+                ("type", Any::Array(names)) => {
+                    let kinds = names
+                        .iter()
+                        .map(|name| match name {
+                            Any::String(name) => Kind::parse(name),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<Kind>>>();
+                    compiled.kinds = Some(kinds.ok_or_else(|| invalid(pointer, "unknown type"))?);
+                }
+                ("enum", Any::Array(values)) => compiled.allowed = Some(values.to_vec()),
+                ("const", value) => compiled.allowed = Some(vec![value.clone()]),
+                ("minimum", value) => compiled.minimum = Some(bound(value, pointer)?),
+                ("maximum", value) => compiled.maximum = Some(bound(value, pointer)?),
+                ("exclusiveMinimum", value) => {
+                    compiled.exclusive_minimum = Some(bound(value, pointer)?)
+                }
+                ("exclusiveMaximum", value) => {
+                    compiled.exclusive_maximum = Some(bound(value, pointer)?)
+                }
+                ("minLength", value) => compiled.min_length = Some(count(value, pointer)?),
+                ("maxLength", value) => compiled.max_length = Some(count(value, pointer)?),
+                ("minItems", value) => compiled.min_items = Some(count(value, pointer)?),
+                ("maxItems", value) => compiled.max_items = Some(count(value, pointer)?),
+                ("items", value) => {
+                    compiled.items = Some(Rc::new(Schema::compile(value, pointer)?))
+                }
+                ("properties", Any::Map(properties)) => {
+                    for (name, property) in properties.iter() {
+                        let property = Schema::compile(property, &format!("{pointer}/{name}"))?;
+                        compiled.properties.insert(name.clone(), Rc::new(property));
+                    }
+                }
+                ("required", Any::Array(names)) => {
+                    for name in names.iter() {
+                        match name {
+                            Any::String(name) => compiled.required.push(name.to_string()),
+                            _ => return Err(invalid(pointer, "expected an array of strings")),
+                        }
+                    }
+                }
+                ("additionalProperties", value) => {
+                    compiled.additional = Some(Rc::new(Schema::compile(value, pointer)?))
+                }
+                (keyword, _) if ANNOTATIONS.contains(&keyword) => {}
+                ("type", _) | ("enum", _) | ("properties", _) | ("required", _) => {
+                    return Err(invalid(pointer, "unexpected value"))
+                }
+                (keyword, _) => {
+                    return Err(invalid(
+                        pointer,
+                        &format!("unsupported keyword '{keyword}'"),
+                    ))
+                }
+            }
+        }
+        Ok(compiled)
+    }
+
+    /// Schema of values stored under a given key of an object.
+    fn property(&self, key: &str) -> Option<&Rc<Schema>> {
+        self.properties
+            .get(key)
+            .or_else(|| self.additional.as_ref())
+    }
+
+    /// Validates a `value` found at a given `location`, and returns a description of the first
+    /// violation of this schema.
+    fn validate(&self, value: &Any, location: &str) -> Result<(), String> {
+        if self.reject {
+            return Err(format!("{location}: no value is allowed here"));
+        }
+        if let Some(kinds) = &self.kinds {
+            if !kinds.iter().any(|kind| kind.matches(value)) {
+                let expected: Vec<&str> = kinds.iter().map(|kind| kind.name()).collect();
+                return Err(format!(
+                    "{location}: expected {}, found {}",
+                    expected.join(" or "),
+                    describe(value)
+                ));
+            }
+        }
+        if let Some(allowed) = &self.allowed {
+            if !allowed.iter().any(|allowed| same(allowed, value)) {
+                return Err(format!(
+                    "{location}: value is not one of the allowed values"
+                ));
+            }
+        }
+        match value {
+            Any::Number(_) | Any::BigInt(_) => {
+                let n = number(value).unwrap_or_default();
+                if let Some(min) = self.minimum.filter(|min| n < *min) {
+                    return Err(format!("{location}: expected a number >= {min}, found {n}"));
+                }
+                if let Some(max) = self.maximum.filter(|max| n > *max) {
+                    return Err(format!("{location}: expected a number <= {max}, found {n}"));
+                }
+                if let Some(min) = self.exclusive_minimum.filter(|min| n <= *min) {
+                    return Err(format!("{location}: expected a number > {min}, found {n}"));
+                }
+                if let Some(max) = self.exclusive_maximum.filter(|max| n >= *max) {
+                    return Err(format!("{location}: expected a number < {max}, found {n}"));
+                }
+            }
+            Any::String(s) => {
+                let len = s.chars().count();
+                if let Some(min) = self.min_length.filter(|min| len < *min) {
+                    return Err(format!(
+                        "{location}: expected at least {min} characters, found {len}"
+                    ));
+                }
+                if let Some(max) = self.max_length.filter(|max| len > *max) {
+                    return Err(format!(
+                        "{location}: expected at most {max} characters, found {len}"
+                    ));
+                }
+            }
+            Any::Array(items) => {
+                self.check_length(items.len(), location)?;
+                if let Some(schema) = &self.items {
+                    for (i, item) in items.iter().enumerate() {
+                        schema.validate(item, &format!("{location}[{i}]"))?;
+                    }
+                }
+            }
+            Any::Map(entries) => {
+                if let Some(name) = self
+                    .required
+                    .iter()
+                    .find(|name| !entries.contains_key(*name))
+                {
+                    return Err(format!("{location}: missing required property '{name}'"));
+                }
+                let mut keys: Vec<&String> = entries.keys().collect();
+                keys.sort();
+                for key in keys {
+                    if let Some(schema) = self.property(key) {
+                        schema.validate(&entries[key], &format!("{location}.{key}"))?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Validates a number of items of an array.
+    fn check_length(&self, len: usize, location: &str) -> Result<(), String> {
+        if let Some(min) = self.min_items.filter(|min| len < *min) {
+            return Err(format!(
+                "{location}: expected at least {min} items, found {len}"
+            ));
+        }
+        if let Some(max) = self.max_items.filter(|max| len > *max) {
+            return Err(format!(
+                "{location}: expected at most {max} items, found {len}"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Schema constraining contents of a shared type, together with the location of the shared type
+/// within its document, eg. `tasks[2].tags`.
+pub(crate) struct Scope {
+    schema: Rc<Schema>,
+    location: String,
+}
+
+impl Scope {
+    /// Checks `values` inserted at a given `index` of an array of a given length.
+    pub(crate) fn check_insert(&self, index: u32, len: u32, values: &[Any]) -> PyResult<()> {
+        let result = self
+            .schema
+            .check_length(len as usize + values.len(), &self.location)
+            .and_then(|_| match &self.schema.items {
+                Some(schema) => values.iter().enumerate().try_for_each(|(i, value)| {
+                    let index = index as usize + i;
+                    schema.validate(value, &format!("{}[{index}]", self.location))
+                }),
+                None => Ok(()),
+            });
+        result.map_err(ValidationError::new_err)
+    }
+
+    /// Checks a value replacing an element at a given `index` of an array.
+    pub(crate) fn check_replace(&self, index: u32, value: &Any) -> PyResult<()> {
+        match &self.schema.items {
+            Some(schema) => schema
+                .validate(value, &format!("{}[{index}]", self.location))
+                .map_err(ValidationError::new_err),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks a removal of items from an array, which leaves it with `len` items.
+    pub(crate) fn check_delete(&self, len: u32) -> PyResult<()> {
+        self.schema
+            .check_length(len as usize, &self.location)
+            .map_err(ValidationError::new_err)
+    }
+
+    /// Checks a value stored under a given `key` of a map.
+    pub(crate) fn check_set(&self, key: &str, value: &Any) -> PyResult<()> {
+        match self.schema.property(key) {
+            Some(schema) => schema
+                .validate(value, &format!("{}.{key}", self.location))
+                .map_err(ValidationError::new_err),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks a removal of an entry of a map.
+    pub(crate) fn check_remove(&self, key: &str) -> PyResult<()> {
+        if self.schema.required.iter().any(|name| name == key) {
+            return Err(ValidationError::new_err(format!(
+                "{}: missing required property '{key}'",
+                self.location
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// JSON Schemas attached to root types with `YDoc.set_schema`, keyed by root type names, which
+/// are shared by a document and its transactions.
+#[derive(Clone, Default)]
+pub(crate) struct Schemas(Rc<RefCell<HashMap<String, Rc<Schema>>>>);
+
+impl Schemas {
+    pub(crate) fn set(&self, name: &str, schema: Option<Schema>) {
+        let mut schemas = self.0.borrow_mut();
+        match schema {
+            Some(schema) => schemas.insert(name.to_string(), Rc::new(schema)),
+            None => schemas.remove(name),
+        };
+    }
+
+    /// Returns the type of a root type `name` declared by its schema, if the schema only allows
+    /// arrays, objects or strings.
+    pub(crate) fn root_type(&self, name: &str) -> Option<TypeRefs> {
+        let schemas = self.0.borrow();
+        match schemas.get(name)?.kinds.as_deref()? {
+            [Kind::Array] => Some(TYPE_REFS_ARRAY),
+            [Kind::Object] => Some(TYPE_REFS_MAP),
+            [Kind::String] => Some(TYPE_REFS_TEXT),
+            _ => None,
+        }
+    }
+
+    /// Returns the schema constraining contents of a shared type found at a given `location`, if
+    /// there's one.
+    pub(crate) fn scope(&self, branch: &Branch, location: &Location) -> Option<Scope> {
+        let schemas = self.0.borrow();
+        if schemas.is_empty() {
+            return None;
+        }
+        let path = Python::with_gil(|py| location.path(py, branch))?;
+        let mut segments = path.into_iter();
+        let mut location = match segments.next()? {
+            PathSegment::Key(name) => name.to_string(),
+            PathSegment::Index(_) => return None,
+        };
+        let mut schema = schemas.get(&location)?.clone();
+        for segment in segments {
+            schema = match segment {
+                PathSegment::Key(key) => {
+                    location = format!("{location}.{key}");
+                    schema.property(&key)?.clone()
+                }
+                PathSegment::Index(index) => {
+                    location = format!("{location}[{index}]");
+                    schema.items.clone()?
+                }
+            };
+        }
+        Some(Scope { schema, location })
+    }
+}
+
+/// Validates current contents of a root type against a `schema` about to be attached to it.
+pub(crate) fn check_root(doc: &Doc, name: &str, schema: &Schema) -> PyResult<()> {
+    let root = match root_branches(doc)
+        .into_iter()
+        .find(|(root_name, _)| root_name.as_ref() == name)
+    {
+        Some((_, root)) => root,
+        None => return Ok(()),
+    };
+    let value = match root.type_ref() {
+        TYPE_REFS_ARRAY => Array::from(root).to_json(),
+        TYPE_REFS_MAP => Map::from(root).to_json(),
+        TYPE_REFS_TEXT => Any::String(Text::from(root).to_string().into_boxed_str()),
+        _ => return Ok(()),
+    };
+    schema
+        .validate(&value, name)
+        .map_err(ValidationError::new_err)
+}
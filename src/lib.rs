@@ -1,8 +1,56 @@
 use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
+mod annotations;
+mod arrow;
+mod autosave;
+mod awareness;
+mod batch;
+mod binding;
+mod build_info;
+mod copy;
+mod counter;
+mod cursor;
+mod debounce;
+mod delete_set;
+mod dispatcher;
+mod doc_registry;
 mod json_builder;
+mod json_schema;
+mod lifecycle;
+mod local_hub;
+mod location;
+mod logging;
+mod markdown;
+mod merge_report;
+mod metrics;
+mod mmap_doc;
+mod msgpack;
+mod origin;
+mod panics;
+mod presence;
+mod prosemirror;
+mod protocol;
+mod readonly;
+mod register;
+mod replay;
+mod revert;
+mod root_proxy;
+mod schema;
 mod shared_types;
+mod shutdown;
+mod simulator;
+mod snapshot;
+mod state_vector;
+mod stats;
+mod template;
+mod testing;
 mod type_conversions;
+mod update_buffer;
+mod update_filter;
+mod update_split;
+mod update_transform;
+mod versions;
+mod view;
 mod y_array;
 mod y_doc;
 mod y_map;
@@ -13,15 +61,39 @@ use crate::y_doc::*;
 
 /// Python bindings for Y.rs
 #[pymodule]
-pub fn y_py(_py: Python, m: &PyModule) -> PyResult<()> {
+pub fn y_py(py: Python, m: &PyModule) -> PyResult<()> {
     // Data Types
     m.add_class::<y_doc::YDoc>()?;
+    m.add_class::<batch::Batch>()?;
+    m.add_class::<binding::Binding>()?;
+    m.add_class::<dispatcher::Dispatcher>()?;
+    m.add_class::<dispatcher::TransactionRecord>()?;
+    m.add_class::<autosave::Autosave>()?;
     m.add_class::<y_transaction::YTransaction>()?;
+    m.add_class::<origin::OriginScope>()?;
     m.add_class::<y_text::YText>()?;
     m.add_class::<y_array::YArray>()?;
     m.add_class::<y_map::YMap>()?;
     m.add_class::<y_xml::YXmlText>()?;
     m.add_class::<y_xml::YXmlElement>()?;
+    m.add_class::<view::View>()?;
+    m.add_class::<doc_registry::DocRegistry>()?;
+    m.add_class::<update_buffer::UpdateBuffer>()?;
+    m.add_class::<cursor::Cursor>()?;
+    m.add_class::<annotations::Annotations>()?;
+    m.add_class::<counter::YCounter>()?;
+    m.add_class::<register::YRegister>()?;
+    m.add_class::<mmap_doc::MmapDoc>()?;
+    m.add_class::<awareness::Awareness>()?;
+    m.add_class::<presence::Presence>()?;
+    m.add_class::<protocol::SyncConnection>()?;
+    m.add_class::<local_hub::LocalHub>()?;
+    m.add_class::<readonly::ReadOnlyDoc>()?;
+    m.add_class::<readonly::ReadOnly>()?;
+    m.add_class::<root_proxy::RootProxy>()?;
+    m.add_class::<simulator::Simulator>()?;
+    m.add_class::<state_vector::StateVector>()?;
+    m.add_class::<delete_set::DeleteSet>()?;
     // Events
     m.add_class::<y_text::YTextEvent>()?;
     m.add_class::<y_array::YArrayEvent>()?;
@@ -29,9 +101,92 @@ pub fn y_py(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<y_xml::YXmlTextEvent>()?;
     m.add_class::<y_xml::YXmlEvent>()?;
     m.add_class::<y_doc::AfterTransactionEvent>()?;
+    // Errors
+    m.add("YError", py.get_type::<shared_types::YError>())?;
+    m.add(
+        "IntegrationError",
+        py.get_type::<shared_types::IntegrationError>(),
+    )?;
+    m.add(
+        "MultipleIntegrationError",
+        py.get_type::<type_conversions::MultipleIntegrationError>(),
+    )?;
+    m.add(
+        "TransactionError",
+        py.get_type::<shared_types::TransactionError>(),
+    )?;
+    m.add(
+        "EncodingError",
+        py.get_type::<shared_types::EncodingError>(),
+    )?;
+    m.add(
+        "PreliminaryAccessError",
+        py.get_type::<shared_types::PreliminaryAccessError>(),
+    )?;
+    m.add(
+        "PreliminaryObservationException",
+        py.get_type::<shared_types::PreliminaryObservationException>(),
+    )?;
+    m.add(
+        "IntegratedOperationException",
+        py.get_type::<shared_types::IntegratedOperationException>(),
+    )?;
+    m.add("ReadOnlyError", py.get_type::<readonly::ReadOnlyError>())?;
+    m.add(
+        "UpdateRejectedError",
+        py.get_type::<update_filter::UpdateRejectedError>(),
+    )?;
+    m.add(
+        "LimitExceededError",
+        py.get_type::<update_filter::LimitExceededError>(),
+    )?;
+    m.add("InternalError", py.get_type::<panics::InternalError>())?;
+    m.add(
+        "ValidationError",
+        py.get_type::<json_schema::ValidationError>(),
+    )?;
+    // Values
+    m.add_class::<type_conversions::Undefined>()?;
+    m.add("Undefined", type_conversions::Undefined::instance(py))?;
     // Functions
     m.add_wrapped(wrap_pyfunction!(encode_state_vector))?;
     m.add_wrapped(wrap_pyfunction!(encode_state_as_update))?;
     m.add_wrapped(wrap_pyfunction!(apply_update))?;
+    m.add_wrapped(wrap_pyfunction!(validate_update))?;
+    m.add_wrapped(wrap_pyfunction!(copy::copy_shared))?;
+    m.add_wrapped(wrap_pyfunction!(binding::bind))?;
+    m.add_wrapped(wrap_pyfunction!(update_split::split_update))?;
+    m.add_wrapped(wrap_pyfunction!(merge_report::explain_merge))?;
+    m.add_wrapped(wrap_pyfunction!(replay::replay_updates))?;
+    m.add_wrapped(wrap_pyfunction!(type_conversions::set_conversion_options))?;
+    m.add_wrapped(wrap_pyfunction!(metrics::enable_metrics))?;
+    m.add_wrapped(wrap_pyfunction!(metrics::disable_metrics))?;
+    m.add_wrapped(wrap_pyfunction!(metrics::reset_metrics))?;
+    m.add_wrapped(wrap_pyfunction!(metrics::get_metrics))?;
+    m.add_wrapped(wrap_pyfunction!(logging::set_log_handler))?;
+    m.add_wrapped(wrap_pyfunction!(panics::set_panic_hook))?;
+    m.add_wrapped(wrap_pyfunction!(shutdown::shutdown))?;
+    m.add_wrapped(wrap_pyfunction!(build_info::versions))?;
+    // Submodules
+    let schema_module = PyModule::new(py, "schema")?;
+    schema::register(py, schema_module)?;
+    m.add_submodule(schema_module)?;
+    let protocol_module = PyModule::new(py, "protocol")?;
+    protocol::register(protocol_module)?;
+    m.add_submodule(protocol_module)?;
+    let testing_module = PyModule::new(py, "testing")?;
+    testing::register(testing_module)?;
+    m.add_submodule(testing_module)?;
+    // Allows `import y_py.schema`, `import y_py.protocol` and `import y_py.testing`, which
+    // otherwise only exist as attributes
+    let modules = py.import("sys")?.getattr("modules")?;
+    modules.set_item("y_py.schema", schema_module)?;
+    modules.set_item("y_py.protocol", protocol_module)?;
+    modules.set_item("y_py.testing", testing_module)?;
+    // Observers must not be called once the interpreter starts finalizing
+    let at_exit = wrap_pyfunction!(shutdown::at_exit)(py)?;
+    py.import("atexit")?.call_method1("register", (at_exit,))?;
+    // Locations of panics are recorded for `InternalError` messages
+    panics::install();
     Ok(())
 }
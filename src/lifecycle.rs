@@ -0,0 +1,68 @@
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Default)]
+struct State {
+    destroyed: bool,
+    next_id: u32,
+    callbacks: Vec<(u32, PyObject)>,
+}
+
+/// Lifecycle of a document: whether it has been destroyed with `YDoc.destroy`, and callbacks
+/// registered with `YDoc.on_destroy`.
+#[derive(Clone, Default)]
+pub(crate) struct Lifecycle(Rc<RefCell<State>>);
+
+impl Lifecycle {
+    pub(crate) fn is_destroyed(&self) -> bool {
+        self.0.borrow().destroyed
+    }
+
+    /// Fails with a `RuntimeError` if the document has been destroyed.
+    pub(crate) fn check(&self) -> PyResult<()> {
+        if self.is_destroyed() {
+            Err(PyRuntimeError::new_err("The document has been destroyed"))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn add(&self, callback: PyObject) -> u32 {
+        let mut state = self.0.borrow_mut();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.callbacks.push((id, callback));
+        id
+    }
+
+    pub(crate) fn remove(&self, id: u32) {
+        self.0
+            .borrow_mut()
+            .callbacks
+            .retain(|(callback_id, _)| *callback_id != id);
+    }
+
+    /// Marks the document as destroyed and calls its `on_destroy` callbacks, which are dropped
+    /// afterwards. All callbacks are called even if some of them fail, and the first exception is
+    /// raised afterwards. Does nothing if the document has already been destroyed.
+    pub(crate) fn destroy(&self, py: Python) -> PyResult<()> {
+        let callbacks = {
+            let mut state = self.0.borrow_mut();
+            if state.destroyed {
+                return Ok(());
+            }
+            state.destroyed = true;
+            std::mem::take(&mut state.callbacks)
+        };
+        // Callbacks are called once the state is released, as they may inspect the document
+        let mut result = Ok(());
+        for (_, callback) in callbacks {
+            if let Err(err) = callback.call0(py) {
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+}
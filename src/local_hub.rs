@@ -0,0 +1,494 @@
+use crate::awareness::Awareness;
+use crate::origin;
+use crate::shutdown;
+use crate::simulator::Rng;
+use crate::snapshot::{read_structs, Reader};
+use crate::y_doc::{apply_update_with_origin, diff, YDoc};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::AsPyPointer;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use yrs::updates::decoder::{Decode, DecoderV1};
+use yrs::{StateVector, SubscriptionId, Update};
+
+#[derive(Clone)]
+enum Payload {
+    /// An update of a document, using lib0 v1 encoding.
+    Update(Vec<u8>),
+    /// An update of an awareness.
+    Awareness(Vec<u8>),
+}
+
+/// A message in flight to a member of a hub.
+struct Message {
+    /// Simulated time at which the message is delivered.
+    due: f64,
+    target: u64,
+    payload: Payload,
+}
+
+struct Member {
+    id: u64,
+    doc: Py<YDoc>,
+    /// An awareness of the member, with a subscription relaying its changes.
+    awareness: Option<(Py<Awareness>, u32)>,
+    /// Cleared once the member leaves the hub, which silences its document observer.
+    active: Rc<Cell<bool>>,
+    /// State vector of the document, which is kept up to date by its observer.
+    state_vector: Rc<RefCell<StateVector>>,
+}
+
+#[derive(Default)]
+struct State {
+    members: Vec<Member>,
+    /// Messages in flight, in order in which they have been sent.
+    queue: Vec<Message>,
+    /// Updates which have reached their targets before updates they depend on, together with
+    /// their targets.
+    held: Vec<(u64, Vec<u8>)>,
+    now: f64,
+    latency: f64,
+    /// Maximum random delay added to the latency of each message, which reorders messages.
+    jitter: f64,
+    /// Probability of a message being lost.
+    drop_rate: f64,
+    /// Number of messages lost so far.
+    dropped: usize,
+    /// Source of randomness of jitter and losses, seeded so that runs are reproducible.
+    rng: Rng,
+    next_id: u64,
+    /// A weak reference to the hub, which is the origin of delivered changes.
+    hub: Option<PyObject>,
+}
+
+impl State {
+    /// Sends a payload from a given member to all other members.
+    fn broadcast(&mut self, source: u64, payload: &Payload) {
+        let targets: Vec<u64> = self
+            .members
+            .iter()
+            .map(|m| m.id)
+            .filter(|id| *id != source)
+            .collect();
+        for target in targets {
+            self.send(target, payload.clone());
+        }
+    }
+
+    /// Puts a payload in flight to a given member, unless it's lost on the way.
+    fn send(&mut self, target: u64, payload: Payload) {
+        if self.drop_rate > 0.0 && self.rng.chance(self.drop_rate) {
+            self.dropped += 1;
+            return;
+        }
+        let mut due = self.now + self.latency;
+        if self.jitter > 0.0 {
+            due += self.rng.sample() * self.jitter;
+        }
+        self.queue.push(Message {
+            due,
+            target,
+            payload,
+        });
+    }
+}
+
+/// Delivers messages which are due, or all of them if `all` is true, and returns their number.
+fn deliver(py: Python, state: &Rc<RefCell<State>>, all: bool) -> PyResult<usize> {
+    let mut delivered = 0;
+    loop {
+        // The state is released while a message is applied, as it calls observers
+        let (message, doc, awareness, state_vector, hub) = {
+            let mut state = state.borrow_mut();
+            let now = state.now;
+            let next = state
+                .queue
+                .iter()
+                .enumerate()
+                .filter(|(_, message)| all || message.due <= now)
+                .min_by(|(_, a), (_, b)| a.due.partial_cmp(&b.due).unwrap())
+                .map(|(i, _)| i);
+            let message = match next {
+                Some(i) => state.queue.remove(i),
+                None => return Ok(delivered),
+            };
+            if all {
+                state.now = state.now.max(message.due);
+            }
+            let member = state.members.iter().find(|m| m.id == message.target);
+            let (doc, awareness, state_vector) = match member {
+                Some(member) => (
+                    member.doc.clone_ref(py),
+                    member.awareness.as_ref().map(|(a, _)| a.clone_ref(py)),
+                    member.state_vector.clone(),
+                ),
+                None => continue,
+            };
+            let hub = match &state.hub {
+                Some(hub) => hub.call0(py)?,
+                None => py.None(),
+            };
+            (message, doc, awareness, state_vector, hub)
+        };
+        match message.payload {
+            Payload::Update(update) => {
+                if ready(&state_vector.borrow(), &update) {
+                    apply_update_with_origin(py, &doc.borrow(py), &update, hub.clone_ref(py))?;
+                    release(py, state, message.target, &doc, &state_vector, hub)?;
+                } else {
+                    state.borrow_mut().held.push((message.target, update));
+                }
+            }
+            Payload::Awareness(update) => {
+                if let Some(awareness) = awareness {
+                    awareness.borrow(py).apply(py, &update, hub)?;
+                }
+            }
+        }
+        delivered += 1;
+    }
+}
+
+/// Checks if a document with a given `state` has all blocks preceding those of an `update`. Yrs
+/// keeps blocks which can't be integrated yet aside, but loses some of them when several updates
+/// arrive out of order, so such updates are held back until their predecessors have been applied.
+fn ready(state: &StateVector, update: &[u8]) -> bool {
+    match read_structs(&mut Reader::new(update)) {
+        Ok(structs) => structs
+            .iter()
+            .all(|(client, clock, _)| state.get(client) as u64 >= *clock),
+        // Malformed updates are rejected once they're applied
+        Err(_) => true,
+    }
+}
+
+/// Applies updates held back for a given member which can be applied to its document now.
+fn release(
+    py: Python,
+    state: &Rc<RefCell<State>>,
+    target: u64,
+    doc: &Py<YDoc>,
+    state_vector: &RefCell<StateVector>,
+    hub: PyObject,
+) -> PyResult<()> {
+    let mut held: Vec<Vec<u8>> = {
+        let mut state = state.borrow_mut();
+        let (held, others): (Vec<_>, Vec<_>) = std::mem::take(&mut state.held)
+            .into_iter()
+            .partition(|(t, _)| *t == target);
+        state.held = others;
+        held.into_iter().map(|(_, update)| update).collect()
+    };
+    while let Some(i) = held
+        .iter()
+        .position(|update| ready(&state_vector.borrow(), update))
+    {
+        let update = held.remove(i);
+        apply_update_with_origin(py, &doc.borrow(py), &update, hub.clone_ref(py))?;
+    }
+    let mut state = state.borrow_mut();
+    state
+        .held
+        .extend(held.into_iter().map(|update| (target, update)));
+    Ok(())
+}
+
+/// Connects documents living in the same process, eg. replicas of a test or a simulation, or
+/// mirrors of rooms on a single server, and propagates updates and awareness states between them
+/// automatically. Changes made to a member are sent to all other members, and applied with the
+/// hub as their origin.
+///
+/// Messages are delivered right away by default. Given a `latency`, in seconds, they are delivered
+/// once the simulated time of the hub has been moved forward by at least that much with
+/// `advance`, so that concurrent edits can be reproduced deterministically. `flush` delivers all
+/// messages in flight.
+///
+/// An unreliable network can be simulated as well: each message is delayed by a further random
+/// amount of up to `jitter` seconds, which reorders messages, and is lost with a probability of
+/// `drop_rate`. Lost messages are never sent again. Updates which arrive before updates they
+/// depend on are held by their recipients until these have been applied. Random choices are
+/// derived from a `seed`, so that runs are reproducible.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import LocalHub, YDoc
+///
+/// hub = LocalHub(latency=0.1)
+/// a, b = YDoc(), YDoc()
+/// hub.join(a)
+/// hub.join(b)
+/// with a.begin_transaction() as txn:
+///     a.get_text('text').extend(txn, 'hello')
+/// assert str(b.get_text('text')) == ''
+/// hub.advance(0.1)
+/// assert str(b.get_text('text')) == 'hello'
+/// ```
+#[pyclass(unsendable, weakref)]
+pub struct LocalHub(Rc<RefCell<State>>);
+
+#[pymethods]
+impl LocalHub {
+    #[new]
+    pub fn new(
+        latency: Option<f64>,
+        jitter: Option<f64>,
+        drop_rate: Option<f64>,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        let latency = latency.unwrap_or(0.0);
+        if !latency.is_finite() || latency < 0.0 {
+            return Err(PyValueError::new_err(
+                "latency must be a non-negative number of seconds",
+            ));
+        }
+        let jitter = jitter.unwrap_or(0.0);
+        if !jitter.is_finite() || jitter < 0.0 {
+            return Err(PyValueError::new_err(
+                "jitter must be a non-negative number of seconds",
+            ));
+        }
+        let drop_rate = drop_rate.unwrap_or(0.0);
+        if !(0.0..=1.0).contains(&drop_rate) {
+            return Err(PyValueError::new_err(
+                "drop_rate must be a number between 0 and 1",
+            ));
+        }
+        Ok(LocalHub(Rc::new(RefCell::new(State {
+            latency,
+            jitter,
+            drop_rate,
+            rng: Rng(seed.unwrap_or(0)),
+            ..State::default()
+        }))))
+    }
+
+    /// Delay of messages sent between members, in seconds.
+    #[getter]
+    pub fn latency(&self) -> f64 {
+        self.0.borrow().latency
+    }
+
+    /// Simulated time of the hub, in seconds, which is moved forward by `advance`.
+    #[getter]
+    pub fn time(&self) -> f64 {
+        self.0.borrow().now
+    }
+
+    /// Number of messages in flight.
+    #[getter]
+    pub fn pending(&self) -> usize {
+        self.0.borrow().queue.len()
+    }
+
+    /// Number of messages lost so far, following the `drop_rate` of the hub.
+    #[getter]
+    pub fn dropped(&self) -> usize {
+        self.0.borrow().dropped
+    }
+
+    /// Documents connected to the hub, in order in which they have joined.
+    #[getter]
+    pub fn docs(&self, py: Python) -> Vec<Py<YDoc>> {
+        let state = self.0.borrow();
+        state.members.iter().map(|m| m.doc.clone_ref(py)).collect()
+    }
+
+    /// Connects a document, and optionally an awareness of its users, to the hub. The document
+    /// exchanges its whole state, and awareness states, with all other members.
+    pub fn join(
+        slf: &PyCell<Self>,
+        py: Python,
+        doc: Py<YDoc>,
+        awareness: Option<Py<Awareness>>,
+    ) -> PyResult<()> {
+        let state = slf.borrow().0.clone();
+        if state.borrow().hub.is_none() {
+            let weakref = py.import("weakref")?.call_method1("ref", (slf,))?;
+            state.borrow_mut().hub = Some(weakref.into());
+        }
+        if state
+            .borrow()
+            .members
+            .iter()
+            .any(|m| m.doc.as_ptr() == doc.as_ptr())
+        {
+            return Err(PyValueError::new_err(
+                "The document has already joined the hub",
+            ));
+        }
+        let hub_key = slf.as_ptr() as usize;
+        let id = {
+            let mut state = state.borrow_mut();
+            state.next_id += 1;
+            state.next_id
+        };
+        let active = Rc::new(Cell::new(true));
+        let new_state = diff(&doc.borrow(py).inner, &StateVector::default());
+        let state_vector = Update::decode(&mut DecoderV1::from(new_state.as_slice()))
+            .map(|update| update.state_vector())
+            .unwrap_or_default();
+        let state_vector = Rc::new(RefCell::new(state_vector));
+        let observer_state = Rc::downgrade(&state);
+        let observer_active = active.clone();
+        let observer_state_vector = state_vector.clone();
+        // The observer stays attached to the document, it's silenced once the document leaves
+        let _: SubscriptionId = doc
+            .try_borrow_mut(py)?
+            .inner
+            .observe_transaction_cleanup(move |txn, event| {
+                *observer_state_vector.borrow_mut() = event.after_state.clone();
+                let unchanged =
+                    event.before_state == event.after_state && event.delete_set.is_empty();
+                if !observer_active.get() || unchanged || from_hub(hub_key) {
+                    return;
+                }
+                let state = match observer_state.upgrade() {
+                    Some(state) => state,
+                    None => return,
+                };
+                let payload = Payload::Update(txn.encode_update_v1());
+                shutdown::with_gil(|py| {
+                    if let Err(err) = relay(py, &state, id, &payload) {
+                        err.restore(py)
+                    }
+                });
+            })
+            .into();
+        let awareness = match awareness {
+            Some(awareness) => {
+                let relay = AwarenessRelay {
+                    state: Rc::downgrade(&state),
+                    member: id,
+                    hub: hub_key,
+                };
+                let subscription = awareness.borrow(py).observe(relay.into_py(py));
+                Some((awareness, subscription))
+            }
+            None => None,
+        };
+
+        // The new member and other members exchange their whole states
+        {
+            let mut state = state.borrow_mut();
+            let mut messages = Vec::new();
+            let new_awareness = awareness.as_ref().map(|(a, _)| a.borrow(py).encode(None));
+            for member in &state.members {
+                messages.push((member.id, Payload::Update(new_state.clone())));
+                messages.push((
+                    id,
+                    Payload::Update(diff(&member.doc.borrow(py).inner, &StateVector::default())),
+                ));
+                if let (Some((awareness, _)), Some(new_awareness)) =
+                    (&member.awareness, &new_awareness)
+                {
+                    messages.push((member.id, Payload::Awareness(new_awareness.clone())));
+                    messages.push((id, Payload::Awareness(awareness.borrow(py).encode(None))));
+                }
+            }
+            for (target, payload) in messages {
+                state.send(target, payload);
+            }
+            state.members.push(Member {
+                id,
+                doc,
+                awareness,
+                active,
+                state_vector,
+            });
+        }
+        deliver(py, &state, false)?;
+        Ok(())
+    }
+
+    /// Disconnects a document from the hub. Messages in flight to the document are dropped.
+    pub fn leave(&self, py: Python, doc: Py<YDoc>) -> PyResult<()> {
+        let member = {
+            let mut state = self.0.borrow_mut();
+            let index = state
+                .members
+                .iter()
+                .position(|m| m.doc.as_ptr() == doc.as_ptr())
+                .ok_or_else(|| PyValueError::new_err("The document hasn't joined the hub"))?;
+            let member = state.members.remove(index);
+            state.queue.retain(|message| message.target != member.id);
+            state.held.retain(|(target, _)| *target != member.id);
+            member
+        };
+        member.active.set(false);
+        if let Some((awareness, subscription)) = member.awareness {
+            awareness.borrow(py).unobserve(subscription);
+        }
+        Ok(())
+    }
+
+    /// Moves the simulated time forward by a given number of `seconds`, and delivers messages
+    /// which are due. Returns a number of delivered messages.
+    pub fn advance(&self, py: Python, seconds: f64) -> PyResult<usize> {
+        if !seconds.is_finite() || seconds < 0.0 {
+            return Err(PyValueError::new_err(
+                "seconds must be a non-negative number",
+            ));
+        }
+        self.0.borrow_mut().now += seconds;
+        deliver(py, &self.0, false)
+    }
+
+    /// Delivers all messages in flight, moving the simulated time forward as needed. Returns a
+    /// number of delivered messages.
+    pub fn flush(&self, py: Python) -> PyResult<usize> {
+        deliver(py, &self.0, true)
+    }
+}
+
+/// Checks if a transaction being committed has been started by a hub to deliver a message.
+fn from_hub(hub: usize) -> bool {
+    origin::committing().map_or(false, |origin| origin.as_ptr() as usize == hub)
+}
+
+/// Sends a payload of a member to other members of a hub, and delivers messages which are due.
+fn relay(py: Python, state: &Rc<RefCell<State>>, member: u64, payload: &Payload) -> PyResult<()> {
+    state.borrow_mut().broadcast(member, payload);
+    deliver(py, state, false)?;
+    Ok(())
+}
+
+/// An observer of an awareness of a hub member, which relays changes made to the awareness to
+/// other members.
+#[pyclass(unsendable)]
+pub struct AwarenessRelay {
+    state: Weak<RefCell<State>>,
+    member: u64,
+    hub: usize,
+}
+
+#[pymethods]
+impl AwarenessRelay {
+    fn __call__(&self, py: Python, changes: &PyDict, origin: PyObject) -> PyResult<()> {
+        if origin.as_ptr() as usize == self.hub {
+            return Ok(());
+        }
+        let mut clients = Vec::new();
+        for key in ["added", "updated", "removed"].iter() {
+            if let Some(changed) = changes.get_item(key) {
+                clients.extend(changed.extract::<Vec<u64>>()?);
+            }
+        }
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+        let awareness = {
+            let state = state.borrow();
+            let member = state.members.iter().find(|m| m.id == self.member);
+            match member.and_then(|m| m.awareness.as_ref()) {
+                Some((awareness, _)) => awareness.clone_ref(py),
+                None => return Ok(()),
+            }
+        };
+        let update = awareness.borrow(py).encode(Some(clients));
+        relay(py, &state, self.member, &Payload::Awareness(update))
+    }
+}
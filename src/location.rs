@@ -0,0 +1,285 @@
+use crate::shared_types::{nested_branches, TransactionError};
+use crate::y_array::YArray;
+use crate::y_doc::YDoc;
+use crate::y_map::YMap;
+use crate::y_text::YText;
+use crate::y_xml::{YXmlElement, YXmlText};
+use pyo3::prelude::*;
+use pyo3::AsPyPointer;
+use std::cell::RefCell;
+use std::rc::Rc;
+use yrs::types::{Branch, BranchPtr, Path, PathSegment};
+use yrs::types::{TYPE_REFS_ARRAY, TYPE_REFS_MAP, TYPE_REFS_TEXT};
+use yrs::types::{TYPE_REFS_XML_ELEMENT, TYPE_REFS_XML_TEXT};
+use yrs::{Array, Map, Text, XmlElement, XmlText};
+
+/// A weak reference to a `YDoc`, shared by the document with its transactions. It's set once the
+/// document is used from Python, eg. by `YDoc.get_map` or `YDoc.begin_transaction`, since the
+/// Python object of a document doesn't exist yet while it's being constructed.
+#[derive(Clone, Default)]
+pub(crate) struct DocRef(Rc<RefCell<Option<PyObject>>>);
+
+impl DocRef {
+    /// Makes this a reference to `doc`, unless it already refers to it.
+    pub(crate) fn bind(&self, doc: &PyCell<YDoc>) -> PyResult<()> {
+        if self.0.borrow().is_none() {
+            let weak = doc.py().import("weakref")?.call_method1("ref", (doc,))?;
+            *self.0.borrow_mut() = Some(weak.into());
+        }
+        Ok(())
+    }
+
+    /// Checks if the document has been used from Python, so that it's known whether it's alive.
+    pub(crate) fn is_known(&self) -> bool {
+        self.0.borrow().is_some()
+    }
+
+    /// Returns the referenced `YDoc`, unless it's unknown or has been dropped.
+    pub(crate) fn get(&self, py: Python) -> Option<PyObject> {
+        let weak = self.0.borrow().as_ref()?.clone_ref(py);
+        let doc = weak.call0(py).ok()?;
+        if doc.is_none(py) {
+            None
+        } else {
+            Some(doc)
+        }
+    }
+}
+
+/// A place of an integrated shared type: the document containing it, and either the name of the
+/// root type it is or the shared type it's nested in. Locations are handed down from root types
+/// obtained from a document to wrappers of shared types nested in them, so that these don't have
+/// to be searched for. Shared types which haven't been reached from their document, eg. wrappers
+/// created from event targets of unknown documents, have an unknown location.
+#[derive(Clone, Default)]
+pub(crate) struct Location(Option<Rc<Link>>);
+
+struct Link {
+    doc: DocRef,
+    parent: Parent,
+}
+
+enum Parent {
+    /// A name of a root type.
+    Root(Rc<str>),
+    /// A shared type containing a nested one, together with its own location.
+    Nested(BranchPtr, Location),
+}
+
+thread_local! {
+    /// Shared types whose contents are being converted into Python objects, together with their
+    /// locations, so that `Location::current` can locate nested shared types without passing
+    /// locations through every conversion.
+    static CONTAINERS: RefCell<Vec<(BranchPtr, Location)>> = RefCell::new(Vec::new());
+}
+
+/// Leaves a container entered by `Location::within`, even if the conversion panics.
+struct Entered;
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        CONTAINERS.with(|containers| containers.borrow_mut().pop());
+    }
+}
+
+impl Location {
+    /// Returns a location of a root type `name` of a given document.
+    pub(crate) fn root(doc: &DocRef, name: &str) -> Self {
+        Location(Some(Rc::new(Link {
+            doc: doc.clone(),
+            parent: Parent::Root(name.into()),
+        })))
+    }
+
+    /// Returns a location of shared types nested directly in a `parent` located here.
+    pub(crate) fn child(&self, parent: &Branch) -> Self {
+        match &self.0 {
+            Some(link) => Location(Some(Rc::new(Link {
+                doc: link.doc.clone(),
+                parent: Parent::Nested(BranchPtr::from(parent), self.clone()),
+            }))),
+            None => Location::default(),
+        }
+    }
+
+    /// Runs `f`, which converts contents of a `parent` located here into Python objects, so that
+    /// wrappers of shared types nested in it are located by `Location::current`.
+    pub(crate) fn within<T, F>(&self, parent: &Branch, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        CONTAINERS.with(|containers| {
+            containers
+                .borrow_mut()
+                .push((BranchPtr::from(parent), self.clone()))
+        });
+        let _entered = Entered;
+        f()
+    }
+
+    /// Returns a location of a shared type nested in the innermost container entered with
+    /// `within`, or an unknown location outside of containers.
+    pub(crate) fn current() -> Self {
+        CONTAINERS.with(|containers| match containers.borrow().last() {
+            Some((parent, location)) => location.child(parent),
+            None => Location::default(),
+        })
+    }
+
+    /// Returns a reference to the document of a shared type located here, if it's known.
+    pub(crate) fn doc_ref(&self) -> Option<&DocRef> {
+        self.0.as_ref().map(|link| &link.doc)
+    }
+
+    /// Returns the `YDoc` containing a shared type located here, or `None` if the document is
+    /// unknown or has been dropped.
+    pub(crate) fn doc(&self, py: Python) -> PyObject {
+        self.doc_ref()
+            .and_then(|doc| doc.get(py))
+            .unwrap_or_else(|| py.None())
+    }
+
+    /// Returns an address of the `YDoc` containing a shared type located here, if it's known, so
+    /// that subscriptions of the shared type can be dropped when the document is destroyed.
+    pub(crate) fn doc_key(&self, py: Python) -> Option<usize> {
+        let doc = self.doc_ref()?.get(py)?;
+        Some(doc.as_ptr() as usize)
+    }
+
+    /// Starts a new transaction of the `YDoc` containing a shared type located here. Fails with a
+    /// `TransactionError` if the document is unknown.
+    pub(crate) fn transaction(&self, py: Python) -> PyResult<PyObject> {
+        match self.doc_ref().and_then(|doc| doc.get(py)) {
+            Some(doc) => doc.call_method0(py, "begin_transaction"),
+            None => Err(TransactionError::new_err(
+                "Document of this shared type is unknown, use YDoc.begin_transaction instead",
+            )),
+        }
+    }
+
+    /// Returns a shared type containing a given `branch` located here, with its own location.
+    /// Returns `None` for root types, shared types of unknown or dropped documents and shared
+    /// types which have been removed from their parent since they were located.
+    pub(crate) fn parent(&self, py: Python, branch: &Branch) -> Option<(BranchPtr, Location)> {
+        let link = self.0.as_ref()?;
+        // Branches of parents are only valid as long as their document is alive
+        link.doc.get(py)?;
+        match &link.parent {
+            Parent::Root(_) => None,
+            Parent::Nested(parent, location) => {
+                segment(*parent, branch)?;
+                Some((*parent, location.clone()))
+            }
+        }
+    }
+
+    /// Returns a path leading to a given `branch` located here from its root type, or `None` if
+    /// it's unknown, as described by `parent`.
+    pub(crate) fn path(&self, py: Python, branch: &Branch) -> Option<Vec<PathSegment>> {
+        let mut link = self.0.as_ref()?;
+        link.doc.get(py)?;
+        let mut path = Vec::new();
+        let mut current = BranchPtr::from(branch);
+        loop {
+            match &link.parent {
+                Parent::Root(name) => {
+                    path.push(PathSegment::Key(name.clone()));
+                    break;
+                }
+                Parent::Nested(parent, location) => {
+                    path.push(segment(*parent, &current)?);
+                    current = *parent;
+                    link = location.0.as_ref()?;
+                }
+            }
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Returns a path segment under which a `child` is nested in a `parent`, if it's still there.
+fn segment(parent: BranchPtr, child: &Branch) -> Option<PathSegment> {
+    let child = BranchPtr::from(child);
+    nested_branches(parent)
+        .into_iter()
+        .find(|(_, nested)| *nested == child)
+        .map(|(segment, _)| segment)
+}
+
+/// Wraps a `branch` located at a given `location` in the Ypy class of its shared type.
+pub(crate) fn branch_into_py(py: Python, branch: BranchPtr, location: Location) -> PyObject {
+    match branch.type_ref() {
+        TYPE_REFS_TEXT => YText::located(Text::from(branch), location).into_py(py),
+        TYPE_REFS_ARRAY => YArray::located(Array::from(branch), location).into_py(py),
+        TYPE_REFS_MAP => YMap::located(Map::from(branch), location).into_py(py),
+        TYPE_REFS_XML_ELEMENT => YXmlElement(XmlElement::from(branch), location).into_py(py),
+        TYPE_REFS_XML_TEXT => YXmlText(XmlText::from(branch), location).into_py(py),
+        _ => py.None(),
+    }
+}
+
+/// Returns a shared type containing a given `branch` located at `location`, or `None` if it's a
+/// root type or its parent is unknown.
+pub(crate) fn parent_into_py(py: Python, branch: &Branch, location: &Location) -> PyObject {
+    match location.parent(py, branch) {
+        Some((parent, location)) => branch_into_py(py, parent, location),
+        None => py.None(),
+    }
+}
+
+/// A shared type observed by a deep observer, which locates targets of the events it receives.
+#[derive(Clone)]
+pub(crate) struct Observed {
+    branch: BranchPtr,
+    location: Location,
+}
+
+impl Observed {
+    pub(crate) fn new(branch: &Branch, location: &Location) -> Self {
+        Observed {
+            branch: BranchPtr::from(branch),
+            location: location.clone(),
+        }
+    }
+
+    /// Returns a location of an event `target`, which is reached by following a `path` relative
+    /// to the observed shared type, as returned by `path()` of events.
+    pub(crate) fn locate(&self, target: &Branch, path: Path) -> Location {
+        if self.location.0.is_none() {
+            return Location::default();
+        }
+        let target = BranchPtr::from(target);
+        let mut current = self.branch;
+        let mut location = self.location.clone();
+        for segment in path {
+            let next = nested_branches(current)
+                .into_iter()
+                .find(|(nested, _)| *nested == segment);
+            match next {
+                Some((_, next)) => {
+                    location = location.child(&current);
+                    current = next;
+                }
+                None => break,
+            }
+        }
+        if current == target {
+            return location;
+        }
+        // Yrs counts indexes of event paths in blocks rather than elements, which may lead astray
+        let mut pending = vec![(self.branch, self.location.clone())];
+        while let Some((branch, location)) = pending.pop() {
+            if branch == target {
+                return location;
+            }
+            let nested = location.child(&branch);
+            pending.extend(
+                nested_branches(branch)
+                    .into_iter()
+                    .map(|(_, child)| (child, nested.clone())),
+            );
+        }
+        Location::default()
+    }
+}
@@ -0,0 +1,94 @@
+use pyo3::once_cell::GILOnceCell;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Severity of a record, using level numbers of the Python `logging` module.
+#[derive(Clone, Copy)]
+pub(crate) enum Level {
+    Debug = 10,
+    Info = 20,
+    Warning = 30,
+    Error = 40,
+}
+
+/// Set while a log handler is registered, so that records aren't built needlessly.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static HANDLER: GILOnceCell<Mutex<Option<PyObject>>> = GILOnceCell::new();
+
+/// Checks if a log handler is registered.
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Passes a record of an `event` to the log handler, if one is registered. Besides "event" and
+/// "level" entries, the record contains entries set by `fields`. Exceptions raised by the handler
+/// are printed, as records are emitted by operations which can't fail because of them.
+pub(crate) fn emit<F>(py: Python, level: Level, event: &str, fields: F)
+where
+    F: FnOnce(&PyDict) -> PyResult<()>,
+{
+    if !enabled() {
+        return;
+    }
+    let handler = HANDLER.get_or_init(py, || Mutex::new(None));
+    // The lock is released before calling the handler, which may emit records of its own
+    let handler = match handler.lock().unwrap().as_ref() {
+        Some(handler) => handler.clone_ref(py),
+        None => return,
+    };
+    let record = PyDict::new(py);
+    let result = record
+        .set_item("event", event)
+        .and_then(|_| record.set_item("level", level as u8))
+        .and_then(|_| fields(record))
+        .and_then(|_| handler.call1(py, (record,)));
+    if let Err(err) = result {
+        err.print(py);
+    }
+}
+
+/// Reports an exception raised by an observer callback, which is emitted as an "observer_error"
+/// record and then restored, so that it's raised by the operation which triggered the callback.
+pub(crate) fn callback_failed(py: Python, err: PyErr) {
+    emit(py, Level::Error, "observer_error", |record| {
+        record.set_item("exception", err.value(py))
+    });
+    err.restore(py)
+}
+
+/// Registers a `handler(record)` which receives structured records of events happening inside of
+/// Ypy, or removes a registered handler if `None` is given. There can be only one handler at a
+/// time, shared by all documents.
+///
+/// A record is a dictionary with an "event" name and a "level" of the Python `logging` module,
+/// followed by entries describing the event:
+///
+/// - "transaction_committed" (`DEBUG`): a transaction which changed its document has been
+///   committed, with numbers of `inserted` and `deleted` elements.
+/// - "update_applied" (`DEBUG`): a remote update of a given `size` in bytes has been applied.
+/// - "update_rejected" (`WARNING`): a remote update of a given `size` couldn't be applied, for a
+///   given `reason`.
+/// - "observer_error" (`ERROR`): an observer callback raised an `exception`.
+/// - "gc_run" (`INFO`): `YDoc.compact` has garbage collected a document with a given `client_id`,
+///   saving `removed` bytes of its encoded state.
+///
+/// Exceptions raised by the handler are printed and otherwise ignored.
+///
+/// Example:
+///
+/// ```python
+/// import logging
+/// import y_py as Y
+///
+/// logger = logging.getLogger('y_py')
+/// Y.set_log_handler(lambda record: logger.log(record['level'], '%s', record))
+/// ```
+#[pyfunction]
+pub fn set_log_handler(py: Python, handler: Option<PyObject>) {
+    let enabled = handler.is_some();
+    *HANDLER.get_or_init(py, || Mutex::new(None)).lock().unwrap() = handler;
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
@@ -0,0 +1,618 @@
+use crate::y_xml::children;
+use lib0::any::Any;
+use std::mem;
+use std::rc::Rc;
+use yrs::types::Attrs;
+use yrs::{Transaction, Xml, XmlElement, XmlText};
+
+/// Inline formatting of a run of text. In `YText` it's stored using attribute names of the Quill
+/// editor: `bold`, `italic`, `strike`, `code` and `link`.
+#[derive(Clone, Default, PartialEq)]
+pub(crate) struct Style {
+    bold: bool,
+    italic: bool,
+    strike: bool,
+    code: bool,
+    link: Option<String>,
+}
+
+impl Style {
+    fn from_attrs(attrs: &Attrs) -> Self {
+        let flag = |name: &str| matches!(attrs.get(name), Some(Any::Bool(true)));
+        Style {
+            bold: flag("bold"),
+            italic: flag("italic"),
+            strike: flag("strike"),
+            code: flag("code"),
+            link: match attrs.get("link") {
+                Some(Any::String(url)) => Some(url.to_string()),
+                _ => None,
+            },
+        }
+    }
+
+    fn to_attrs(&self) -> Attrs {
+        let mut attrs = Attrs::new();
+        let flags = [
+            ("bold", self.bold),
+            ("italic", self.italic),
+            ("strike", self.strike),
+            ("code", self.code),
+        ];
+        for (name, set) in flags {
+            if set {
+                attrs.insert(Rc::from(name), Any::Bool(true));
+            }
+        }
+        if let Some(url) = &self.link {
+            attrs.insert(Rc::from("link"), Any::String(url.as_str().into()));
+        }
+        attrs
+    }
+}
+
+pub(crate) struct Run {
+    text: String,
+    style: Style,
+}
+
+/// Kinds of Markdown blocks. Lists, quotes and code blocks consist of one block per line.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum BlockKind {
+    Paragraph,
+    Heading(u8),
+    Bullet,
+    Ordered,
+    Quote,
+    Code,
+}
+
+impl BlockKind {
+    /// Returns a kind of a line of `YText` ended by a newline character with given attributes.
+    fn from_attrs(attrs: &Attrs) -> Self {
+        let level = match attrs.get("header") {
+            Some(Any::Number(level)) => *level as i64,
+            Some(Any::BigInt(level)) => *level,
+            _ => 0,
+        };
+        let set =
+            |name: &str| !matches!(attrs.get(name), None | Some(Any::Null | Any::Bool(false)));
+        if (1..=6).contains(&level) {
+            BlockKind::Heading(level as u8)
+        } else if let Some(Any::String(list)) = attrs.get("list") {
+            if list.as_ref() == "ordered" {
+                BlockKind::Ordered
+            } else {
+                BlockKind::Bullet
+            }
+        } else if set("blockquote") {
+            BlockKind::Quote
+        } else if set("code-block") {
+            BlockKind::Code
+        } else {
+            BlockKind::Paragraph
+        }
+    }
+
+    fn to_attrs(self) -> Attrs {
+        let (name, value) = match self {
+            BlockKind::Paragraph => return Attrs::new(),
+            BlockKind::Heading(level) => ("header", Any::Number(level as f64)),
+            BlockKind::Bullet => ("list", Any::String("bullet".into())),
+            BlockKind::Ordered => ("list", Any::String("ordered".into())),
+            BlockKind::Quote => ("blockquote", Any::Bool(true)),
+            BlockKind::Code => ("code-block", Any::Bool(true)),
+        };
+        let mut attrs = Attrs::new();
+        attrs.insert(Rc::from(name), value);
+        attrs
+    }
+}
+
+pub(crate) struct Block {
+    kind: BlockKind,
+    runs: Vec<Run>,
+}
+
+/// Appends a run of text, merging it with the last run if they have the same style.
+fn push_run(runs: &mut Vec<Run>, text: &str, style: &Style) {
+    if text.is_empty() {
+        return;
+    }
+    match runs.last_mut() {
+        Some(last) if &last.style == style => last.text.push_str(text),
+        _ => runs.push(Run {
+            text: text.to_string(),
+            style: style.clone(),
+        }),
+    }
+}
+
+/// Parses a subset of Markdown: ATX headings, paragraphs, bullet and ordered lists, quotes,
+/// fenced code blocks, and bold, italic, strikethrough, code and link inline formatting. Anything
+/// else is kept as text.
+pub(crate) fn parse(markdown: &str) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut in_code = false;
+    // Lines of a paragraph are joined until a blank line
+    let mut in_paragraph = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code = !in_code;
+            in_paragraph = false;
+            continue;
+        }
+        if in_code {
+            let mut runs = Vec::new();
+            push_run(&mut runs, line, &Style::default());
+            blocks.push(Block {
+                kind: BlockKind::Code,
+                runs,
+            });
+        } else if trimmed.is_empty() {
+            in_paragraph = false;
+        } else {
+            let (kind, content) = block_prefix(trimmed);
+            match blocks.last_mut() {
+                Some(last) if in_paragraph && kind == BlockKind::Paragraph => {
+                    push_run(&mut last.runs, " ", &Style::default());
+                    parse_inline(content, Style::default(), &mut last.runs);
+                }
+                _ => {
+                    let mut runs = Vec::new();
+                    parse_inline(content, Style::default(), &mut runs);
+                    blocks.push(Block { kind, runs });
+                }
+            }
+            in_paragraph = kind == BlockKind::Paragraph;
+        }
+    }
+    blocks
+}
+
+/// Returns a kind of a block starting with a given line, and its content without block markers.
+fn block_prefix(line: &str) -> (BlockKind, &str) {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if (1..=6).contains(&level) && line[level..].starts_with(' ') {
+        return (BlockKind::Heading(level as u8), line[level..].trim_start());
+    }
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(content) = line.strip_prefix(marker) {
+            return (BlockKind::Bullet, content);
+        }
+    }
+    if let Some(content) = line.strip_prefix('>') {
+        return (BlockKind::Quote, content.trim_start());
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        if let Some(content) = line[digits..].strip_prefix(". ") {
+            return (BlockKind::Ordered, content);
+        }
+    }
+    (BlockKind::Paragraph, line)
+}
+
+/// Markers of inline formatting, which is toggled by their occurrences.
+const MARKERS: [&str; 4] = ["**", "~~", "*", "_"];
+
+fn toggle(style: &mut Style, marker: &str) {
+    match marker {
+        "**" => style.bold = !style.bold,
+        "~~" => style.strike = !style.strike,
+        _ => style.italic = !style.italic,
+    }
+}
+
+fn is_toggled(style: &Style, marker: &str) -> bool {
+    match marker {
+        "**" => style.bold,
+        "~~" => style.strike,
+        _ => style.italic,
+    }
+}
+
+/// Parses inline formatting of a `text`, and appends its runs to `runs`.
+fn parse_inline(text: &str, mut style: Style, runs: &mut Vec<Run>) {
+    let mut rest = text;
+    let mut previous = None;
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        if c == '\\' {
+            if let Some(escaped) = after.chars().next() {
+                push_run(runs, &after[..escaped.len_utf8()], &style);
+                rest = &after[escaped.len_utf8()..];
+                previous = Some(escaped);
+                continue;
+            }
+        } else if c == '`' {
+            if let Some(end) = after.find('`') {
+                let code = Style {
+                    code: true,
+                    ..style.clone()
+                };
+                push_run(runs, &after[..end], &code);
+                rest = &after[end + 1..];
+                previous = Some('`');
+                continue;
+            }
+        } else if c == '[' {
+            if let Some((label, url, len)) = link(rest) {
+                let linked = Style {
+                    link: Some(url.to_string()),
+                    ..style.clone()
+                };
+                parse_inline(label, linked, runs);
+                rest = &rest[len..];
+                previous = Some(')');
+                continue;
+            }
+        }
+        if let Some(marker) = MARKERS.iter().find(|marker| rest.starts_with(**marker)) {
+            let after = &rest[marker.len()..];
+            let next = after.chars().next();
+            // Underscores within words, like in snake_case, are not markers
+            let intraword = *marker == "_"
+                && previous.map_or(false, char::is_alphanumeric)
+                && next.map_or(false, char::is_alphanumeric);
+            // Markers which are never closed are kept as text
+            if !intraword && (is_toggled(&style, marker) || after.contains(marker)) {
+                toggle(&mut style, marker);
+                rest = after;
+                previous = marker.chars().last();
+                continue;
+            }
+        }
+        push_run(runs, &rest[..c.len_utf8()], &style);
+        rest = after;
+        previous = Some(c);
+    }
+}
+
+/// Parses a `[label](url)` link at the start of a `text`, returning its label, URL and length.
+fn link(text: &str) -> Option<(&str, &str, usize)> {
+    let close = text.find("](")?;
+    let end = close + 2 + text[close + 2..].find(')')?;
+    Some((&text[1..close], &text[close + 2..end], end + 1))
+}
+
+/// Renders blocks as Markdown, using the syntax accepted by `parse`.
+pub(crate) fn render(blocks: &[Block]) -> String {
+    let mut markdown = String::new();
+    let mut previous: Option<BlockKind> = None;
+    let mut number = 0;
+    for block in blocks {
+        let is_empty = block.runs.iter().all(|run| run.text.trim().is_empty());
+        if block.kind == BlockKind::Paragraph && is_empty {
+            continue;
+        }
+        if let Some(previous) = previous {
+            if previous == BlockKind::Code && block.kind != BlockKind::Code {
+                markdown.push_str("\n```");
+            }
+            // Lines of lists, quotes and code blocks are kept together
+            let joined = previous == block.kind
+                && matches!(
+                    block.kind,
+                    BlockKind::Bullet | BlockKind::Ordered | BlockKind::Quote | BlockKind::Code
+                );
+            markdown.push_str(if joined { "\n" } else { "\n\n" });
+        }
+        number = match (block.kind, previous) {
+            (BlockKind::Ordered, Some(BlockKind::Ordered)) => number + 1,
+            (BlockKind::Ordered, _) => 1,
+            _ => 0,
+        };
+        let content = render_inline(&block.runs);
+        match block.kind {
+            BlockKind::Paragraph => {
+                // Text which looks like a start of another block is escaped
+                if block_prefix(&content).0 != BlockKind::Paragraph {
+                    markdown.push('\\');
+                }
+                markdown.push_str(&content);
+            }
+            BlockKind::Heading(level) => {
+                markdown.push_str(&"#".repeat(level as usize));
+                markdown.push(' ');
+                markdown.push_str(&content);
+            }
+            BlockKind::Bullet => {
+                markdown.push_str("- ");
+                markdown.push_str(&content);
+            }
+            BlockKind::Ordered => {
+                markdown.push_str(&format!("{number}. "));
+                markdown.push_str(&content);
+            }
+            BlockKind::Quote => {
+                markdown.push_str("> ");
+                markdown.push_str(&content);
+            }
+            BlockKind::Code => {
+                if previous != Some(BlockKind::Code) {
+                    markdown.push_str("```\n");
+                }
+                for run in block.runs.iter() {
+                    markdown.push_str(&run.text);
+                }
+            }
+        }
+        previous = Some(block.kind);
+    }
+    if previous == Some(BlockKind::Code) {
+        markdown.push_str("\n```");
+    }
+    if !markdown.is_empty() {
+        markdown.push('\n');
+    }
+    markdown
+}
+
+fn render_inline(runs: &[Run]) -> String {
+    let mut markdown = String::new();
+    let mut start = 0;
+    while start < runs.len() {
+        let link = &runs[start].style.link;
+        let len = runs[start..]
+            .iter()
+            .take_while(|run| &run.style.link == link)
+            .count();
+        let group = &runs[start..start + len];
+        match link {
+            Some(url) => {
+                markdown.push('[');
+                render_styled(group, &mut markdown);
+                markdown.push_str("](");
+                markdown.push_str(url);
+                markdown.push(')');
+            }
+            None => render_styled(group, &mut markdown),
+        }
+        start += len;
+    }
+    markdown
+}
+
+/// Appends `runs` to `markdown`, opening and closing markers of bold, italic and strikethrough
+/// formatting only where formatting changes.
+fn render_styled(runs: &[Run], markdown: &mut String) {
+    let mut open: Vec<&str> = Vec::new();
+    for run in runs {
+        let flags = [
+            ("**", run.style.bold),
+            ("*", run.style.italic),
+            ("~~", run.style.strike),
+        ];
+        let wanted: Vec<&str> = flags
+            .iter()
+            .filter(|(_, set)| *set)
+            .map(|(marker, _)| *marker)
+            .collect();
+        let kept = open
+            .iter()
+            .take_while(|marker| wanted.contains(marker))
+            .count();
+        for marker in open.drain(kept..).rev() {
+            markdown.push_str(marker);
+        }
+        for marker in wanted {
+            if !open.contains(&marker) {
+                markdown.push_str(marker);
+                open.push(marker);
+            }
+        }
+        if run.style.code {
+            markdown.push('`');
+            markdown.push_str(&run.text);
+            markdown.push('`');
+        } else {
+            for c in run.text.chars() {
+                if "\\`*_[]~".contains(c) {
+                    markdown.push('\\');
+                }
+                markdown.push(c);
+            }
+        }
+    }
+    for marker in open.into_iter().rev() {
+        markdown.push_str(marker);
+    }
+}
+
+/// Splits formatted chunks of a `YText` into blocks. Following the convention of the Quill editor,
+/// lines end with newline characters, whose attributes describe the kind of a block.
+pub(crate) fn text_blocks(chunks: Vec<(String, Attrs)>) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut runs = Vec::new();
+    for (chunk, attrs) in chunks.iter() {
+        let style = Style::from_attrs(attrs);
+        let mut lines = chunk.split('\n');
+        push_run(&mut runs, lines.next().unwrap_or_default(), &style);
+        for line in lines {
+            blocks.push(Block {
+                kind: BlockKind::from_attrs(attrs),
+                runs: mem::take(&mut runs),
+            });
+            push_run(&mut runs, line, &style);
+        }
+    }
+    if !runs.is_empty() {
+        blocks.push(Block {
+            kind: BlockKind::Paragraph,
+            runs,
+        });
+    }
+    blocks
+}
+
+/// Converts blocks into formatted chunks of a `YText`, in the format read by `text_blocks`.
+pub(crate) fn text_chunks(blocks: &[Block]) -> Vec<(String, Attrs)> {
+    let mut chunks = Vec::new();
+    for block in blocks {
+        for run in block.runs.iter() {
+            chunks.push((run.text.clone(), run.style.to_attrs()));
+        }
+        chunks.push(("\n".to_string(), block.kind.to_attrs()));
+    }
+    chunks
+}
+
+/// Converts children of an XML element into blocks. Recognized elements are headings `h1`-`h6`,
+/// paragraphs `p`, lists `ul` and `ol` with `li` items, quotes `blockquote` with paragraphs and
+/// code blocks `pre`, containing text formatted with `strong`, `em`, `s`, `code` and `a` elements.
+pub(crate) fn xml_blocks(element: &XmlElement) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    for child in children(element) {
+        let element = match &child {
+            Xml::Element(element) => element,
+            Xml::Text(_) => {
+                let mut runs = Vec::new();
+                xml_runs(&child, &Style::default(), &mut runs);
+                blocks.push(Block {
+                    kind: BlockKind::Paragraph,
+                    runs,
+                });
+                continue;
+            }
+        };
+        let tag = element.tag().to_string();
+        let container = match tag.as_str() {
+            "ul" => Some(BlockKind::Bullet),
+            "ol" => Some(BlockKind::Ordered),
+            "blockquote" => Some(BlockKind::Quote),
+            _ => None,
+        };
+        if let Some(kind) = container {
+            for item in children(element) {
+                let mut runs = Vec::new();
+                xml_runs(&item, &Style::default(), &mut runs);
+                blocks.push(Block { kind, runs });
+            }
+        } else if tag == "pre" {
+            let mut runs = Vec::new();
+            xml_runs(&child, &Style::default(), &mut runs);
+            let code: String = runs.into_iter().map(|run| run.text).collect();
+            for line in code.split('\n') {
+                let mut runs = Vec::new();
+                push_run(&mut runs, line, &Style::default());
+                blocks.push(Block {
+                    kind: BlockKind::Code,
+                    runs,
+                });
+            }
+        } else {
+            let level = tag
+                .strip_prefix('h')
+                .and_then(|level| level.parse::<u8>().ok())
+                .filter(|level| (1..=6).contains(level));
+            let mut runs = Vec::new();
+            xml_runs(&child, &Style::default(), &mut runs);
+            blocks.push(Block {
+                kind: level.map_or(BlockKind::Paragraph, BlockKind::Heading),
+                runs,
+            });
+        }
+    }
+    blocks
+}
+
+/// Appends runs of text found within an XML `node` to `runs`.
+fn xml_runs(node: &Xml, style: &Style, runs: &mut Vec<Run>) {
+    match node {
+        Xml::Text(text) => push_run(runs, &text.to_string(), style),
+        Xml::Element(element) => {
+            let mut style = style.clone();
+            let tag = element.tag().to_string();
+            match tag.as_str() {
+                "strong" | "b" => style.bold = true,
+                "em" | "i" => style.italic = true,
+                "s" | "del" => style.strike = true,
+                "code" => style.code = true,
+                "a" => style.link = element.get_attribute("href"),
+                _ => {}
+            }
+            for child in children(element) {
+                xml_runs(&child, &style, runs);
+            }
+        }
+    }
+}
+
+/// Appends elements representing `blocks` to an XML `element`, in the format read by
+/// `xml_blocks`.
+pub(crate) fn write_xml(txn: &mut Transaction, element: &XmlElement, blocks: &[Block]) {
+    // Lists, quotes and code blocks group consecutive blocks of the same kind
+    let mut group: Option<(BlockKind, XmlElement, Option<XmlText>)> = None;
+    for block in blocks {
+        let tag = match block.kind {
+            BlockKind::Bullet => "ul",
+            BlockKind::Ordered => "ol",
+            BlockKind::Quote => "blockquote",
+            BlockKind::Code => "pre",
+            BlockKind::Paragraph | BlockKind::Heading(_) => {
+                group = None;
+                let tag = match block.kind {
+                    BlockKind::Heading(level) => format!("h{level}"),
+                    _ => "p".to_string(),
+                };
+                let paragraph = element.push_elem_back(txn, tag.as_str());
+                write_runs(txn, &paragraph, &block.runs);
+                continue;
+            }
+        };
+        if matches!(&group, Some((kind, _, _)) if *kind != block.kind) {
+            group = None;
+        }
+        let (_, parent, code) =
+            group.get_or_insert_with(|| (block.kind, element.push_elem_back(txn, tag), None));
+        match block.kind {
+            BlockKind::Code => {
+                let text: String = block.runs.iter().map(|run| run.text.as_str()).collect();
+                match code {
+                    Some(code) => {
+                        code.push(txn, "\n");
+                        code.push(txn, &text);
+                    }
+                    None => {
+                        let node = parent.push_text_back(txn);
+                        node.push(txn, &text);
+                        *code = Some(node);
+                    }
+                }
+            }
+            BlockKind::Quote => {
+                let paragraph = parent.push_elem_back(txn, "p");
+                write_runs(txn, &paragraph, &block.runs);
+            }
+            _ => {
+                let item = parent.push_elem_back(txn, "li");
+                write_runs(txn, &item, &block.runs);
+            }
+        }
+    }
+}
+
+fn write_runs(txn: &mut Transaction, parent: &XmlElement, runs: &[Run]) {
+    for run in runs {
+        let mut target = parent.clone();
+        if let Some(url) = &run.style.link {
+            target = target.push_elem_back(txn, "a");
+            target.insert_attribute(txn, "href", url);
+        }
+        let wrappers = [
+            ("strong", run.style.bold),
+            ("em", run.style.italic),
+            ("s", run.style.strike),
+            ("code", run.style.code),
+        ];
+        for (tag, set) in wrappers {
+            if set {
+                target = target.push_elem_back(txn, tag);
+            }
+        }
+        let text = target.push_text_back(txn);
+        text.push(txn, &run.text);
+    }
+}
@@ -0,0 +1,356 @@
+use crate::snapshot::{
+    is_deleted, read_delete_set, read_items, read_structs, write_delete_set, write_var_uint,
+    DeleteSet, Item, Owner, Reader,
+};
+use crate::type_conversions::BinaryInput;
+use crate::y_doc::{diff, YDoc};
+use lib0::any::Any;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use yrs::types::{Change, DeepObservable, Delta, Event, Events, PathSegment, Value};
+use yrs::{StateVector, Subscription, Transaction};
+
+/// Changes made by one of the replicas which the other replica doesn't know about.
+struct Side {
+    state: StateVector,
+    known: StateVector,
+    deletes: DeleteSet,
+    known_deletes: DeleteSet,
+}
+
+impl Side {
+    /// Checks if an item unit with a given id has been inserted or deleted by this replica alone.
+    fn changed(&self, client: u64, clock: u64) -> bool {
+        let inserted =
+            clock >= self.known.get(&client) as u64 && clock < self.state.get(&client) as u64;
+        inserted
+            || (is_deleted(&self.deletes, client, clock, 1)
+                && !is_deleted(&self.known_deletes, client, clock, 1))
+    }
+}
+
+/// Describes edits made concurrently by two replicas to the same parts of a document: overlapping
+/// or adjacent ranges of the same text or array, and the same keys of a map. This allows to show
+/// which parts of a merged document have been edited by both sides, eg. to let users review them.
+///
+/// `update_a` and `update_b` are states of the replicas encoded using lib0 v1 encoding, eg. with
+/// `encode_state_as_update`. Returns a list of overlapping edits, each of them a dictionary with
+/// a `kind` ("text", "array" or "map") and a `path` leading to the edited shared type from its
+/// root type. Ranges `a` and `b` of texts and arrays are `(start, end)` index ranges of content
+/// changed by each replica in the merged document, where removed content takes no space. Map
+/// edits have a `key` and a `winner`, which is "a" or "b" depending on whose value has been kept
+/// after the merge, or `None` if the entry has been removed.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import encode_state_as_update, explain_merge
+///
+/// report = explain_merge(encode_state_as_update(mine), encode_state_as_update(theirs))
+/// for edit in report:
+///     print(f"Both of you edited {'/'.join(map(str, edit['path']))}")
+/// ```
+#[pyfunction]
+pub fn explain_merge(
+    py: Python,
+    update_a: BinaryInput,
+    update_b: BinaryInput,
+) -> PyResult<PyObject> {
+    let doc_a = YDoc::new(None, None, None)?;
+    doc_a.begin_transaction().apply_v1(update_a)?;
+    let doc_b = YDoc::new(None, None, None)?;
+    doc_b.begin_transaction().apply_v1(update_b)?;
+    let state_a = diff(&doc_a.inner, &StateVector::default());
+    let state_b = diff(&doc_b.inner, &StateVector::default());
+    let vector_a = doc_a.inner.transact().state_vector();
+    let vector_b = doc_b.inner.transact().state_vector();
+    let sides = [
+        Side {
+            state: vector_a.clone(),
+            known: vector_b.clone(),
+            deletes: delete_set(&state_a)?,
+            known_deletes: delete_set(&state_b)?,
+        },
+        Side {
+            state: vector_b.clone(),
+            known: vector_a.clone(),
+            deletes: delete_set(&state_b)?,
+            known_deletes: delete_set(&state_a)?,
+        },
+    ];
+
+    let merged = YDoc::new(None, None, None)?;
+    merged
+        .begin_transaction()
+        .apply_v1(BinaryInput::Borrowed(&state_a))?;
+    merged
+        .begin_transaction()
+        .apply_v1(BinaryInput::Borrowed(&state_b))?;
+    let state = diff(&merged.inner, &StateVector::default());
+    let (items, deletes) = read_items(&state)?;
+    let mut roots = BTreeMap::new();
+    for item in items.iter() {
+        if let Some(Owner::Root(name)) = item.owner {
+            let kind = roots.entry(name).or_insert(RootKind::Map);
+            if item.is_text {
+                *kind = RootKind::Text;
+            } else if item.key.is_none() && *kind == RootKind::Map {
+                *kind = RootKind::Array;
+            }
+        }
+    }
+
+    let mut edits: Vec<(Vec<Segment>, &PyDict)> = Vec::new();
+
+    // Changes of each replica are applied on top of the state of the other one, so that their
+    // positions refer to the merged document.
+    let changed_a = replay(&state_b, diff(&doc_a.inner, &vector_b), &roots)?;
+    let changed_b = replay(&state_a, diff(&doc_b.inner, &vector_a), &roots)?;
+    for a in changed_a.iter() {
+        for b in changed_b.iter().filter(|b| b.path == a.path) {
+            for range_a in a.ranges.iter() {
+                for range_b in b.ranges.iter() {
+                    if range_a.0 <= range_b.1 && range_b.0 <= range_a.1 {
+                        let edit = PyDict::new(py);
+                        edit.set_item("kind", if a.is_text { "text" } else { "array" })?;
+                        edit.set_item("path", PyList::new(py, a.path.iter()))?;
+                        edit.set_item("a", range_a)?;
+                        edit.set_item("b", range_b)?;
+                        edits.push((a.path.clone(), edit));
+                    }
+                }
+            }
+        }
+    }
+
+    // A map entry has been changed by a replica if any of the items ever assigned to its key has
+    // been inserted or deleted by that replica.
+    let mut entries: BTreeMap<(Owner, &str), Vec<&Item>> = BTreeMap::new();
+    for item in items.iter() {
+        if let (Some(owner), Some(key)) = (item.owner, item.key) {
+            entries.entry((owner, key)).or_default().push(item);
+        }
+    }
+    for ((owner, key), entry) in entries {
+        let mut changed = [false, false];
+        for item in entry.iter() {
+            for (side, changed) in sides.iter().zip(changed.iter_mut()) {
+                *changed |= (item.clock..item.clock + item.len)
+                    .any(|clock| side.changed(item.client, clock));
+            }
+        }
+        if changed != [true, true] {
+            continue;
+        }
+        let path = match owner {
+            Owner::Root(name) => vec![Segment::Key(name.to_string())],
+            Owner::Type(client, clock) => match locate(&state, client, clock, &roots)? {
+                Some(path) => path,
+                // Entries of removed shared types are not reported
+                None => continue,
+            },
+        };
+        let winner = entry
+            .iter()
+            .find(|item| !is_deleted(&deletes, item.client, item.clock, item.len))
+            .and_then(|item| {
+                ["a", "b"]
+                    .iter()
+                    .zip(sides.iter())
+                    .find(|(_, side)| side.changed(item.client, item.clock))
+                    .map(|(name, _)| *name)
+            });
+        let edit = PyDict::new(py);
+        edit.set_item("kind", "map")?;
+        edit.set_item("path", PyList::new(py, path.iter()))?;
+        edit.set_item("key", key)?;
+        edit.set_item("winner", winner)?;
+        edits.push((path, edit));
+    }
+
+    edits.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(PyList::new(py, edits.into_iter().map(|(_, edit)| edit)).into())
+}
+
+/// Returns a delete set of a lib0 v1 encoded document state.
+fn delete_set(state: &[u8]) -> PyResult<DeleteSet> {
+    let mut reader = Reader::new(state);
+    read_structs(&mut reader)?;
+    read_delete_set(&mut reader)
+}
+
+/// A kind of a root type, inferred from its content, as root types have no type in updates.
+#[derive(Clone, Copy, PartialEq)]
+enum RootKind {
+    Map,
+    Array,
+    Text,
+}
+
+/// A segment of a path leading to a shared type from its root type.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    Key(String),
+    Index(u32),
+}
+
+impl ToPyObject for Segment {
+    fn to_object(&self, py: Python) -> PyObject {
+        match self {
+            Segment::Key(key) => key.into_py(py),
+            Segment::Index(index) => index.into_py(py),
+        }
+    }
+}
+
+/// A change of a shared type, recorded while replaying changes of a replica.
+struct Changed {
+    path: Vec<Segment>,
+    is_text: bool,
+    /// `(start, end)` index ranges of changed content of a sequence, where removed content takes
+    /// no space.
+    ranges: Vec<(u32, u32)>,
+    /// Changed keys of a map.
+    keys: Vec<String>,
+}
+
+impl Changed {
+    fn new(root: &str, txn: &Transaction, event: &Event) -> Self {
+        let mut path = vec![Segment::Key(root.to_string())];
+        path.extend(event.path().into_iter().map(|segment| match segment {
+            PathSegment::Key(key) => Segment::Key(key.to_string()),
+            PathSegment::Index(index) => Segment::Index(index),
+        }));
+        let mut changed = Changed {
+            path,
+            is_text: false,
+            ranges: Vec::new(),
+            keys: Vec::new(),
+        };
+        match event {
+            Event::Text(event) => changed.text_delta(event.delta(txn)),
+            Event::XmlText(event) => changed.text_delta(event.delta(txn)),
+            Event::Array(event) => changed.array_delta(event.delta(txn)),
+            Event::XmlElement(event) => changed.array_delta(event.delta(txn)),
+            Event::Map(event) => {
+                changed.keys = event.keys(txn).keys().map(|key| key.to_string()).collect()
+            }
+        }
+        changed
+    }
+
+    fn text_delta(&mut self, delta: &[Delta]) {
+        self.is_text = true;
+        let mut index = 0;
+        for change in delta {
+            match change {
+                Delta::Inserted(value, _) => {
+                    // Replayed documents use default options, which count text in bytes
+                    let len = match value {
+                        Value::Any(Any::String(chunk)) => chunk.len() as u32,
+                        _ => 1,
+                    };
+                    self.mark(index, len);
+                    index += len;
+                }
+                Delta::Deleted(_) => self.mark(index, 0),
+                Delta::Retain(len, attributes) => {
+                    if attributes.is_some() {
+                        self.mark(index, *len);
+                    }
+                    index += len;
+                }
+            }
+        }
+    }
+
+    fn array_delta(&mut self, delta: &[Change]) {
+        let mut index = 0;
+        for change in delta {
+            match change {
+                Change::Added(values) => {
+                    self.mark(index, values.len() as u32);
+                    index += values.len() as u32;
+                }
+                Change::Removed(_) => self.mark(index, 0),
+                Change::Retain(len) => index += len,
+            }
+        }
+    }
+
+    /// Adds `len` indexes starting at a given `index` to changed ranges.
+    fn mark(&mut self, index: u32, len: u32) {
+        match self.ranges.last_mut() {
+            Some((_, end)) if *end >= index => *end = (*end).max(index + len),
+            _ => self.ranges.push((index, index + len)),
+        }
+    }
+}
+
+/// Applies lib0 v1 encoded `changes` to a document loaded from a `base` state, and returns changes
+/// of shared types they have caused. Indexes of changed ranges refer to the resulting document.
+fn replay(
+    base: &[u8],
+    changes: Vec<u8>,
+    roots: &BTreeMap<&str, RootKind>,
+) -> PyResult<Vec<Changed>> {
+    let doc = YDoc::new(None, None, None)?;
+    doc.begin_transaction()
+        .apply_v1(BinaryInput::Borrowed(base))?;
+    let recorded = Rc::new(RefCell::new(Vec::new()));
+    let mut subscriptions: Vec<Subscription<Events>> = Vec::new();
+    let mut txn = doc.inner.transact();
+    for (name, kind) in roots.iter() {
+        let root = name.to_string();
+        let recorded = recorded.clone();
+        let callback = move |txn: &Transaction, events: &Events| {
+            let mut recorded = recorded.borrow_mut();
+            for event in events.iter() {
+                recorded.push(Changed::new(&root, txn, event));
+            }
+        };
+        subscriptions.push(match kind {
+            RootKind::Map => txn.get_map(name).observe_deep(callback),
+            RootKind::Array => txn.get_array(name).observe_deep(callback),
+            RootKind::Text => txn.get_text(name).observe_deep(callback),
+        });
+    }
+    drop(txn);
+    doc.begin_transaction()
+        .apply_v1(BinaryInput::Owned(changes))?;
+    drop(subscriptions);
+    Ok(recorded.take())
+}
+
+/// Returns a path leading to a nested shared type held by an item with a given id, or `None` if
+/// that item has been deleted. The path is found by deleting the item from a copy of a document
+/// and observing where the deletion happens.
+fn locate(
+    state: &[u8],
+    client: u64,
+    clock: u64,
+    roots: &BTreeMap<&str, RootKind>,
+) -> PyResult<Option<Vec<Segment>>> {
+    let mut deletes = DeleteSet::new();
+    deletes.insert(client, vec![(clock, 1)]);
+    let mut update = Vec::new();
+    write_var_uint(&mut update, 0);
+    write_delete_set(&mut update, &deletes);
+    // Shared types nested in the deleted one may report changes too, so the parent of the item is
+    // the closest one to the root
+    let parent = replay(state, update, roots)?
+        .into_iter()
+        .min_by_key(|changed| changed.path.len());
+    Ok(parent.and_then(|mut changed| {
+        let segment = match (changed.keys.pop(), changed.ranges.first()) {
+            (Some(key), _) => Segment::Key(key),
+            (None, Some((index, _))) => Segment::Index(*index),
+            (None, None) => return None,
+        };
+        changed.path.push(segment);
+        Some(changed.path)
+    }))
+}
@@ -0,0 +1,147 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// If set, durations of operations listed by `Metric` are collected.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Kinds of operations, which are measured once metrics are enabled.
+#[derive(Clone, Copy)]
+pub(crate) enum Metric {
+    /// Transactions, from their start until they're committed.
+    Transactions,
+    /// Conversions of values between Python and Yrs.
+    Conversions,
+    /// Updates applied to documents.
+    Updates,
+    /// Calls of callbacks observing documents and shared types.
+    Callbacks,
+}
+
+const METRICS: [(Metric, &str); 4] = [
+    (Metric::Transactions, "transactions"),
+    (Metric::Conversions, "conversions"),
+    (Metric::Updates, "updates"),
+    (Metric::Callbacks, "callbacks"),
+];
+
+/// Number of measured operations of a single kind with their total and maximum durations, in
+/// nanoseconds.
+struct Counter {
+    count: AtomicU64,
+    total: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Counter {
+    const fn new() -> Self {
+        Counter {
+            count: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.total.store(0, Ordering::Relaxed);
+        self.max.store(0, Ordering::Relaxed);
+    }
+}
+
+static TRANSACTIONS: Counter = Counter::new();
+static CONVERSIONS: Counter = Counter::new();
+static UPDATES: Counter = Counter::new();
+static CALLBACKS: Counter = Counter::new();
+
+impl Metric {
+    fn counter(self) -> &'static Counter {
+        match self {
+            Metric::Transactions => &TRANSACTIONS,
+            Metric::Conversions => &CONVERSIONS,
+            Metric::Updates => &UPDATES,
+            Metric::Callbacks => &CALLBACKS,
+        }
+    }
+}
+
+/// Returns a starting time of a measured operation, or `None` if metrics are disabled.
+pub(crate) fn start() -> Option<Instant> {
+    if ENABLED.load(Ordering::Relaxed) {
+        Some(Instant::now())
+    } else {
+        None
+    }
+}
+
+pub(crate) fn record(metric: Metric, elapsed: Duration) {
+    let counter = metric.counter();
+    let nanos = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+    counter.count.fetch_add(1, Ordering::Relaxed);
+    counter.total.fetch_add(nanos, Ordering::Relaxed);
+    counter.max.fetch_max(nanos, Ordering::Relaxed);
+}
+
+/// Calls `f`, recording its duration as a given `metric` if metrics are enabled.
+pub(crate) fn measure<T, F: FnOnce() -> T>(metric: Metric, f: F) -> T {
+    match start() {
+        Some(started) => {
+            let result = f();
+            record(metric, started.elapsed());
+            result
+        }
+        None => f(),
+    }
+}
+
+/// Starts collecting counts and durations of transactions, conversions of values, applied updates
+/// and observer callbacks across all documents. Metrics are disabled by default, as measuring
+/// adds a small overhead to every operation.
+///
+/// Example:
+///
+/// ```python
+/// import y_py as Y
+///
+/// Y.enable_metrics()
+/// run_workload()
+/// print(Y.get_metrics()['callbacks'])
+/// ```
+#[pyfunction]
+pub fn enable_metrics() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Stops collecting metrics. Metrics collected so far are kept until `reset_metrics` is called.
+#[pyfunction]
+pub fn disable_metrics() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Clears all metrics collected so far.
+#[pyfunction]
+pub fn reset_metrics() {
+    for (metric, _) in METRICS {
+        metric.counter().reset();
+    }
+}
+
+/// Returns collected metrics as a dictionary with "transactions", "conversions", "updates" and
+/// "callbacks" entries. Each of them is a dictionary with a `count` of measured operations and
+/// their `total_time` and `max_time` in seconds.
+#[pyfunction]
+pub fn get_metrics(py: Python) -> PyResult<PyObject> {
+    let metrics = PyDict::new(py);
+    for (metric, name) in METRICS {
+        let counter = metric.counter();
+        let entry = PyDict::new(py);
+        entry.set_item("count", counter.count.load(Ordering::Relaxed))?;
+        let total = Duration::from_nanos(counter.total.load(Ordering::Relaxed));
+        entry.set_item("total_time", total.as_secs_f64())?;
+        let max = Duration::from_nanos(counter.max.load(Ordering::Relaxed));
+        entry.set_item("max_time", max.as_secs_f64())?;
+        metrics.set_item(name, entry)?;
+    }
+    Ok(metrics.into())
+}
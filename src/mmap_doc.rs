@@ -0,0 +1,238 @@
+use crate::shared_types::EncodingError;
+use crate::y_doc::{diff, YDoc};
+use crate::y_transaction::YTransaction;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PySlice, PyTuple};
+use std::convert::TryInto;
+use yrs::StateVector;
+
+/// Identifies files of memory-mapped documents.
+const MAGIC: &[u8; 4] = b"YPYM";
+/// Version of the file layout.
+pub(crate) const FORMAT: u32 = 1;
+/// A header consists of the magic bytes, the format version and a length of the stored state.
+const HEADER_LEN: usize = 16;
+/// Default capacity of new files, in bytes.
+const DEFAULT_CAPACITY: usize = 16 * 1024 * 1024;
+
+/// An experimental document shared by many processes, eg. workers of a gunicorn-style server,
+/// through a memory-mapped file, without a central broker. Every process keeps its own replica of
+/// the document, available as `doc`, which is merged with the state stored in the file by `sync`
+/// and `transact`, while other processes are locked out of the file.
+///
+/// The file holds the whole state of the document, encoded as a single update, so it has to be
+/// large enough to hold it: a new file is created with a given `capacity`, and writing a larger
+/// state raises a `ValueError`. Locking relies on `fcntl.flock`, which is only available on Unix.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import MmapDoc
+///
+/// shared = MmapDoc('/tmp/document.ypy')
+/// shared.transact(lambda txn: shared.doc.get_text('text').extend(txn, 'hello'))
+/// ...
+/// # in another process
+/// shared = MmapDoc('/tmp/document.ypy')
+/// shared.sync()
+/// print(shared.doc.get_text('text'))
+/// ```
+#[pyclass(unsendable)]
+pub struct MmapDoc {
+    doc: Py<YDoc>,
+    /// An open file and its memory map, unless this document has been closed.
+    file: Option<(PyObject, PyObject)>,
+    capacity: usize,
+}
+
+impl MmapDoc {
+    fn mapped(&self, py: Python) -> PyResult<(PyObject, PyObject)> {
+        match &self.file {
+            Some((file, map)) => Ok((file.clone_ref(py), map.clone_ref(py))),
+            None => Err(PyRuntimeError::new_err("The document has been closed")),
+        }
+    }
+
+    /// Calls `f` while holding an exclusive lock of the file.
+    fn locked<T, F>(&self, py: Python, f: F) -> PyResult<T>
+    where
+        F: FnOnce() -> PyResult<T>,
+    {
+        let (file, _) = self.mapped(py)?;
+        let fcntl = py.import("fcntl")?;
+        let fd = file.call_method0(py, "fileno")?;
+        fcntl.call_method1("flock", (fd.clone_ref(py), fcntl.getattr("LOCK_EX")?))?;
+        let result = f();
+        fcntl.call_method1("flock", (fd, fcntl.getattr("LOCK_UN")?))?;
+        result
+    }
+
+    /// Reads a state stored in the file, which is `None` until a state is written.
+    fn read_state(&self, py: Python) -> PyResult<Option<Vec<u8>>> {
+        let (_, map) = self.mapped(py)?;
+        let map = map.as_ref(py);
+        let header = map.get_item(PySlice::new(py, 0, HEADER_LEN as isize, 1))?;
+        let header: &[u8] = header.extract()?;
+        if &header[..4] != MAGIC {
+            return Ok(None);
+        }
+        let format = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let len = u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize;
+        if format != FORMAT || len > self.capacity - HEADER_LEN {
+            return Err(EncodingError::new_err(
+                "The file doesn't contain a valid document",
+            ));
+        }
+        let end = (HEADER_LEN + len) as isize;
+        let state = map.get_item(PySlice::new(py, HEADER_LEN as isize, end, 1))?;
+        Ok(Some(state.extract()?))
+    }
+
+    fn write_state(&self, py: Python, state: &[u8]) -> PyResult<()> {
+        if state.len() > self.capacity - HEADER_LEN {
+            return Err(PyValueError::new_err(format!(
+                "The document takes {} bytes, which exceeds the capacity of the file",
+                state.len()
+            )));
+        }
+        let (_, map) = self.mapped(py)?;
+        let map = map.as_ref(py);
+        let end = (HEADER_LEN + state.len()) as isize;
+        map.set_item(
+            PySlice::new(py, HEADER_LEN as isize, end, 1),
+            PyBytes::new(py, state),
+        )?;
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&FORMAT.to_le_bytes());
+        header.extend_from_slice(&(state.len() as u64).to_le_bytes());
+        map.set_item(
+            PySlice::new(py, 0, HEADER_LEN as isize, 1),
+            PyBytes::new(py, &header),
+        )?;
+        map.call_method0("flush")?;
+        Ok(())
+    }
+
+    /// Merges the state stored in the file into the local replica and writes the merged state
+    /// back, while the file is locked. Returns `true` if the local replica has changed.
+    fn merge(&self, py: Python) -> PyResult<bool> {
+        let doc = self.doc.borrow(py);
+        let before = doc.inner.transact().state_vector();
+        let stored = self.read_state(py)?;
+        if let Some(stored) = &stored {
+            let mut txn = doc.begin_transaction();
+            txn.integrate_v1(stored)?;
+            txn.commit();
+        }
+        let changed = doc.inner.transact().state_vector() != before;
+        let merged = diff(&doc.inner, &StateVector::default());
+        if stored.as_ref() != Some(&merged) {
+            self.write_state(py, &merged)?;
+        }
+        Ok(changed)
+    }
+}
+
+#[pymethods]
+impl MmapDoc {
+    /// Opens a document stored in a file at a given `path`, creating the file if it doesn't exist
+    /// yet. Files smaller than `capacity` bytes are extended, while existing files keep their size
+    /// by default. `client_id` is used by the local replica, and has to be unique among all
+    /// processes.
+    #[new]
+    pub fn new(
+        py: Python,
+        path: PyObject,
+        capacity: Option<usize>,
+        client_id: Option<u64>,
+    ) -> PyResult<Self> {
+        let file = py.import("builtins")?.call_method1("open", (path, "a+b"))?;
+        let fd = file.call_method0("fileno")?;
+        let size: usize = py
+            .import("os")?
+            .call_method1("fstat", (fd,))?
+            .getattr("st_size")?
+            .extract()?;
+        let capacity = match capacity {
+            Some(capacity) => capacity.max(size),
+            None if size > 0 => size,
+            None => DEFAULT_CAPACITY,
+        };
+        if capacity <= HEADER_LEN {
+            return Err(PyValueError::new_err("The capacity is too small"));
+        }
+        if size < capacity {
+            file.call_method1("truncate", (capacity,))?;
+        }
+        let map = py.import("mmap")?.call_method1("mmap", (fd, capacity))?;
+        let doc = Py::new(py, YDoc::new(client_id, None, None)?)?;
+        let shared = MmapDoc {
+            doc,
+            file: Some((file.into(), map.into())),
+            capacity,
+        };
+        shared.sync(py)?;
+        Ok(shared)
+    }
+
+    /// The local replica of the document.
+    #[getter]
+    pub fn doc(&self, py: Python) -> Py<YDoc> {
+        self.doc.clone_ref(py)
+    }
+
+    /// Size of the file, which limits the size of the stored state.
+    #[getter]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Merges changes made by other processes into the local replica, and stores changes of the
+    /// local replica in the file. Returns `True` if the local replica has received new changes.
+    pub fn sync(&self, py: Python) -> PyResult<bool> {
+        self.locked(py, || self.merge(py))
+    }
+
+    /// Calls a `callback(txn)` with a transaction of the local replica, which has been synced
+    /// beforehand, and stores the changes made by the callback in the file afterwards. Other
+    /// processes are locked out of the file in the meantime, so that none of their changes are
+    /// missed. Returns the result of the callback.
+    pub fn transact(&self, py: Python, callback: PyObject) -> PyResult<PyObject> {
+        self.locked(py, || {
+            self.merge(py)?;
+            YDoc::doc_ref(self.doc.as_ref(py))?;
+            let txn: Py<YTransaction> = Py::new(py, self.doc.borrow(py).begin_transaction())?;
+            let result = callback.call1(py, PyTuple::new(py, [txn.clone_ref(py)]));
+            txn.borrow_mut(py).commit();
+            let state = diff(&self.doc.borrow(py).inner, &StateVector::default());
+            self.write_state(py, &state)?;
+            result
+        })
+    }
+
+    /// Unmaps and closes the file. The local replica remains usable.
+    pub fn close(&mut self, py: Python) -> PyResult<()> {
+        if let Some((file, map)) = self.file.take() {
+            map.call_method0(py, "close")?;
+            file.call_method0(py, "close")?;
+        }
+        Ok(())
+    }
+
+    fn __enter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        py: Python,
+        _exception_type: Option<&PyAny>,
+        _exception_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> PyResult<bool> {
+        self.close(py)?;
+        Ok(false)
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+
+use lib0::any::Any;
+use pyo3::exceptions::PyValueError;
+use pyo3::PyResult;
+
+/// Maximum nesting depth of arrays and maps accepted by the decoder.
+const MAX_DECODE_DEPTH: usize = 512;
+
+/// Largest integer which can be stored in a 64-bit float without losing precision.
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+/// Serializes a given value using the MessagePack format.
+pub(crate) fn encode(value: &Any) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    write_any(&mut buffer, value, false);
+    buffer
+}
+
+/// Serializes a given value using the MessagePack format, with map entries sorted by their keys.
+/// Equal values always produce the same bytes, which makes the result suitable for hashing.
+pub(crate) fn encode_canonical(value: &Any) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    write_any(&mut buffer, value, true);
+    buffer
+}
+
+/// Deserializes a single MessagePack value. Fails if data is malformed, uses unsupported types
+/// (extensions or non-string map keys) or contains trailing bytes.
+pub(crate) fn decode(data: &[u8]) -> PyResult<Any> {
+    let mut decoder = Decoder { data, pos: 0 };
+    let value = decoder.read_any(0)?;
+    if decoder.pos != data.len() {
+        return Err(invalid("unexpected trailing bytes"));
+    }
+    Ok(value)
+}
+
+fn invalid(reason: &str) -> pyo3::PyErr {
+    PyValueError::new_err(format!("Invalid msgpack data: {reason}"))
+}
+
+fn write_any(buffer: &mut Vec<u8>, value: &Any, sorted: bool) {
+    match value {
+        Any::Null | Any::Undefined => buffer.push(0xc0),
+        Any::Bool(false) => buffer.push(0xc2),
+        Any::Bool(true) => buffer.push(0xc3),
+        Any::Number(n) if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER => {
+            write_int(buffer, *n as i64)
+        }
+        Any::Number(n) => {
+            buffer.push(0xcb);
+            buffer.extend_from_slice(&n.to_be_bytes());
+        }
+        Any::BigInt(n) => write_int(buffer, *n),
+        Any::String(s) => {
+            write_len(buffer, s.len(), Some(0xa0), [0xd9, 0xda, 0xdb]);
+            buffer.extend_from_slice(s.as_bytes());
+        }
+        Any::Buffer(b) => {
+            write_len(buffer, b.len(), None, [0xc4, 0xc5, 0xc6]);
+            buffer.extend_from_slice(b);
+        }
+        Any::Array(items) => {
+            write_collection_len(buffer, items.len(), 0x90, [0xdc, 0xdd]);
+            for item in items.iter() {
+                write_any(buffer, item, sorted);
+            }
+        }
+        Any::Map(entries) => {
+            write_collection_len(buffer, entries.len(), 0x80, [0xde, 0xdf]);
+            let mut entries: Vec<_> = entries.iter().collect();
+            if sorted {
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            }
+            for (key, value) in entries {
+                write_any(buffer, &Any::String(key.as_str().into()), sorted);
+                write_any(buffer, value, sorted);
+            }
+        }
+    }
+}
+
+fn write_int(buffer: &mut Vec<u8>, n: i64) {
+    if (0..=0x7f).contains(&n) || (-32..0).contains(&n) {
+        buffer.push(n as i8 as u8);
+    } else if n >= 0 {
+        if n <= 0xff {
+            buffer.extend_from_slice(&[0xcc, n as u8]);
+        } else if n <= 0xffff {
+            buffer.push(0xcd);
+            buffer.extend_from_slice(&(n as u16).to_be_bytes());
+        } else if n <= 0xffff_ffff {
+            buffer.push(0xce);
+            buffer.extend_from_slice(&(n as u32).to_be_bytes());
+        } else {
+            buffer.push(0xcf);
+            buffer.extend_from_slice(&(n as u64).to_be_bytes());
+        }
+    } else if n >= -0x80 {
+        buffer.extend_from_slice(&[0xd0, n as i8 as u8]);
+    } else if n >= -0x8000 {
+        buffer.push(0xd1);
+        buffer.extend_from_slice(&(n as i16).to_be_bytes());
+    } else if n >= -0x8000_0000 {
+        buffer.push(0xd2);
+        buffer.extend_from_slice(&(n as i32).to_be_bytes());
+    } else {
+        buffer.push(0xd3);
+        buffer.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+/// Writes a length header of a string or binary value. `fixed` is a tag of the compact format
+/// able to store lengths up to 31 bytes, if the type has one.
+fn write_len(buffer: &mut Vec<u8>, len: usize, fixed: Option<u8>, tags: [u8; 3]) {
+    match fixed {
+        Some(tag) if len <= 31 => buffer.push(tag | len as u8),
+        _ if len <= 0xff => buffer.extend_from_slice(&[tags[0], len as u8]),
+        _ if len <= 0xffff => {
+            buffer.push(tags[1]);
+            buffer.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        _ => {
+            buffer.push(tags[2]);
+            buffer.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+}
+
+fn write_collection_len(buffer: &mut Vec<u8>, len: usize, fixed: u8, tags: [u8; 2]) {
+    if len <= 15 {
+        buffer.push(fixed | len as u8);
+    } else if len <= 0xffff {
+        buffer.push(tags[0]);
+        buffer.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        buffer.push(tags[1]);
+        buffer.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn read_bytes(&mut self, len: usize) -> PyResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| invalid("unexpected end of data"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> PyResult<[u8; N]> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    fn read_u8(&mut self) -> PyResult<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_u16(&mut self) -> PyResult<usize> {
+        Ok(u16::from_be_bytes(self.read_array()?) as usize)
+    }
+
+    fn read_u32(&mut self) -> PyResult<usize> {
+        Ok(u32::from_be_bytes(self.read_array()?) as usize)
+    }
+
+    fn read_any(&mut self, depth: usize) -> PyResult<Any> {
+        if depth > MAX_DECODE_DEPTH {
+            return Err(invalid("maximum nesting depth exceeded"));
+        }
+        let tag = self.read_u8()?;
+        let value = match tag {
+            0x00..=0x7f => int_to_any(tag as i64),
+            0xe0..=0xff => int_to_any(tag as i8 as i64),
+            0xc0 => Any::Null,
+            0xc2 => Any::Bool(false),
+            0xc3 => Any::Bool(true),
+            0xcc => int_to_any(self.read_u8()? as i64),
+            0xcd => int_to_any(self.read_u16()? as i64),
+            0xce => int_to_any(self.read_u32()? as i64),
+            0xcf => {
+                let n = u64::from_be_bytes(self.read_array()?);
+                let n = i64::try_from(n).map_err(|_| invalid("integer out of range"))?;
+                int_to_any(n)
+            }
+            0xd0 => int_to_any(self.read_u8()? as i8 as i64),
+            0xd1 => int_to_any(i16::from_be_bytes(self.read_array()?) as i64),
+            0xd2 => int_to_any(i32::from_be_bytes(self.read_array()?) as i64),
+            0xd3 => int_to_any(i64::from_be_bytes(self.read_array()?)),
+            0xca => Any::Number(f32::from_be_bytes(self.read_array()?) as f64),
+            0xcb => Any::Number(f64::from_be_bytes(self.read_array()?)),
+            0xa0..=0xbf => self.read_str((tag & 0x1f) as usize)?,
+            0xd9 => {
+                let len = self.read_u8()? as usize;
+                self.read_str(len)?
+            }
+            0xda => {
+                let len = self.read_u16()?;
+                self.read_str(len)?
+            }
+            0xdb => {
+                let len = self.read_u32()?;
+                self.read_str(len)?
+            }
+            0xc4 => {
+                let len = self.read_u8()? as usize;
+                Any::Buffer(self.read_bytes(len)?.into())
+            }
+            0xc5 => {
+                let len = self.read_u16()?;
+                Any::Buffer(self.read_bytes(len)?.into())
+            }
+            0xc6 => {
+                let len = self.read_u32()?;
+                Any::Buffer(self.read_bytes(len)?.into())
+            }
+            0x90..=0x9f => self.read_seq((tag & 0x0f) as usize, depth)?,
+            0xdc => {
+                let len = self.read_u16()?;
+                self.read_seq(len, depth)?
+            }
+            0xdd => {
+                let len = self.read_u32()?;
+                self.read_seq(len, depth)?
+            }
+            0x80..=0x8f => self.read_map((tag & 0x0f) as usize, depth)?,
+            0xde => {
+                let len = self.read_u16()?;
+                self.read_map(len, depth)?
+            }
+            0xdf => {
+                let len = self.read_u32()?;
+                self.read_map(len, depth)?
+            }
+            _ => return Err(invalid(&format!("unsupported type tag 0x{tag:02x}"))),
+        };
+        Ok(value)
+    }
+
+    fn read_str(&mut self, len: usize) -> PyResult<Any> {
+        let bytes = self.read_bytes(len)?;
+        let s = std::str::from_utf8(bytes).map_err(|_| invalid("string is not valid UTF-8"))?;
+        Ok(Any::String(s.into()))
+    }
+
+    fn read_seq(&mut self, len: usize, depth: usize) -> PyResult<Any> {
+        // Every element takes at least one byte, which bounds allocations for malformed lengths.
+        let mut items = Vec::with_capacity(len.min(self.data.len() - self.pos));
+        for _ in 0..len {
+            items.push(self.read_any(depth + 1)?);
+        }
+        Ok(Any::Array(items.into_boxed_slice()))
+    }
+
+    fn read_map(&mut self, len: usize, depth: usize) -> PyResult<Any> {
+        let mut entries = HashMap::with_capacity(len.min(self.data.len() - self.pos));
+        for _ in 0..len {
+            let key = match self.read_any(depth + 1)? {
+                Any::String(key) => key.to_string(),
+                _ => return Err(invalid("map keys must be strings")),
+            };
+            let value = self.read_any(depth + 1)?;
+            entries.insert(key, value);
+        }
+        Ok(Any::Map(Box::new(entries)))
+    }
+}
+
+fn int_to_any(n: i64) -> Any {
+    if (n as f64).abs() <= MAX_SAFE_INTEGER {
+        Any::Number(n as f64)
+    } else {
+        Any::BigInt(n)
+    }
+}
@@ -0,0 +1,94 @@
+use crate::y_transaction::YTransaction;
+use pyo3::prelude::*;
+use std::cell::RefCell;
+
+thread_local! {
+    /// Origin of a transaction which is being committed on this thread, read by events created
+    /// while observers are called.
+    static COMMITTING: RefCell<Option<PyObject>> = RefCell::new(None);
+}
+
+/// Origins of a transaction, which are pushed and popped by `YTransaction.with_origin` scopes.
+#[derive(Default)]
+pub(crate) struct Origins {
+    stack: Vec<PyObject>,
+    /// The innermost origin pushed so far, with its depth, which is passed to events.
+    innermost: Option<(usize, PyObject)>,
+}
+
+impl Origins {
+    /// Returns the origin of the innermost active scope.
+    pub(crate) fn current(&self) -> Option<&PyObject> {
+        self.stack.last()
+    }
+
+    pub(crate) fn push(&mut self, origin: PyObject) {
+        self.stack.push(origin.clone());
+        let depth = self.stack.len();
+        if self.innermost.as_ref().map_or(true, |(d, _)| depth >= *d) {
+            self.innermost = Some((depth, origin));
+        }
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.stack.pop();
+    }
+
+    /// Calls `f`, which commits a transaction, so that events created in the meantime see the
+    /// innermost origin of the transaction.
+    pub(crate) fn committing<F: FnOnce()>(&self, f: F) {
+        let origin = self.innermost.as_ref().map(|(_, origin)| origin.clone());
+        let previous = COMMITTING.with(|committing| committing.replace(origin));
+        f();
+        COMMITTING.with(|committing| *committing.borrow_mut() = previous);
+    }
+}
+
+/// Returns the origin of a transaction which is being committed, for events created by its
+/// observers.
+pub(crate) fn committing() -> Option<PyObject> {
+    COMMITTING.with(|committing| committing.borrow().clone())
+}
+
+/// Converts an origin of an event into a Python object.
+pub(crate) fn into_py(py: Python, origin: &Option<PyObject>) -> PyObject {
+    match origin {
+        Some(origin) => origin.clone_ref(py),
+        None => py.None(),
+    }
+}
+
+/// A scope returned by `YTransaction.with_origin`, in which changes made with a transaction are
+/// tagged with a given origin. Scopes can be nested, and the origin of the enclosing scope is
+/// restored once a scope is exited.
+#[pyclass(unsendable)]
+pub struct OriginScope {
+    txn: Py<YTransaction>,
+    origin: PyObject,
+}
+
+impl OriginScope {
+    pub(crate) fn new(txn: Py<YTransaction>, origin: PyObject) -> Self {
+        OriginScope { txn, origin }
+    }
+}
+
+#[pymethods]
+impl OriginScope {
+    fn __enter__(&self, py: Python) -> Py<YTransaction> {
+        let mut txn = self.txn.borrow_mut(py);
+        txn.origins.push(self.origin.clone_ref(py));
+        self.txn.clone_ref(py)
+    }
+
+    fn __exit__(
+        &self,
+        py: Python,
+        _exception_type: Option<&PyAny>,
+        _exception_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> bool {
+        self.txn.borrow_mut(py).origins.pop();
+        false
+    }
+}
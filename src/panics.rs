@@ -0,0 +1,113 @@
+use crate::shared_types::YError;
+use pyo3::create_exception;
+use pyo3::prelude::*;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+create_exception!(
+    y_py,
+    InternalError,
+    YError,
+    "Occurs when an operation fails because of an internal error of Ypy or Yrs, eg. a Rust panic. The document which was being changed may be left in an inconsistent state."
+);
+
+/// Set when panics caught by `guard` should be printed by the previous panic hook too.
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Location of the last panic of the current thread, recorded by the panic hook.
+    static LOCATION: RefCell<Option<String>> = RefCell::new(None);
+    /// Number of panic boundaries the current thread is running within.
+    static DEPTH: Cell<usize> = Cell::new(0);
+    /// Set while running code whose panics are expected, see `catch_expected`.
+    static EXPECTED: Cell<bool> = Cell::new(false);
+}
+
+/// Installs a panic hook recording locations of panics caught by `guard`, which are included in
+/// messages of the `InternalError`s it raises. The hook chains to the previously installed one,
+/// which by default prints panics to stderr, except for caught panics: these are only passed on
+/// once enabled with `set_panic_hook`, so panics of other Rust code running in the same process
+/// are reported as usual.
+pub(crate) fn install() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if EXPECTED
+            .try_with(|expected| expected.get())
+            .unwrap_or(false)
+        {
+            return;
+        }
+        let caught = DEPTH.try_with(|depth| depth.get() > 0).unwrap_or(false);
+        if caught {
+            if let Some(location) = info.location() {
+                let location = location.to_string();
+                let _ = LOCATION.try_with(|last| *last.borrow_mut() = Some(location));
+            }
+        }
+        if !caught || VERBOSE.load(Ordering::Relaxed) {
+            previous(info)
+        }
+    }));
+}
+
+/// Formats a panic payload the same way as the panic itself, followed by its location.
+fn message(payload: Box<dyn Any + Send>) -> String {
+    let message = if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else {
+        "panic from Rust code".to_string()
+    };
+    match LOCATION.with(|last| last.borrow_mut().take()) {
+        Some(location) => format!("{message} (at {location})"),
+        None => message,
+    }
+}
+
+/// Runs `f` behind a panic boundary, returning the message of its panic if it panics.
+pub(crate) fn catch<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce() -> T,
+{
+    DEPTH.with(|depth| depth.set(depth.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    DEPTH.with(|depth| depth.set(depth.get() - 1));
+    result.map_err(message)
+}
+
+/// Runs `f`, which panics in cases known in advance, returning `None` if it does. Unlike `catch`,
+/// these panics are neither recorded nor printed, even once enabled with `set_panic_hook`.
+pub(crate) fn catch_expected<T, F>(f: F) -> Option<T>
+where
+    F: FnOnce() -> T,
+{
+    let expected = EXPECTED.with(|expected| expected.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(f)).ok();
+    EXPECTED.with(|current| current.set(expected));
+    result
+}
+
+/// Runs `f` behind a panic boundary, so that a panic of Yrs or Ypy is raised as an
+/// `InternalError` carrying the Rust message, rather than as a `PanicException`, which isn't an
+/// `Exception` and usually takes the whole interpreter down.
+pub(crate) fn guard<T, F>(f: F) -> PyResult<T>
+where
+    F: FnOnce() -> PyResult<T>,
+{
+    catch(f).unwrap_or_else(|message| Err(InternalError::new_err(message)))
+}
+
+/// Configures how Rust panics are reported. Panics of operations changing shared types are always
+/// raised as `InternalError`s, and panics of applying updates as `EncodingError`s, with the Rust
+/// message and the location of the panic. Panics elsewhere, eg. in methods of `YDoc`,
+/// `YTransaction`, events or iterators, are raised by PyO3 as a `PanicException`. Given `verbose`,
+/// panics raised as `InternalError`s or `EncodingError`s are also printed to stderr by the default
+/// Rust panic hook, along with a backtrace if the `RUST_BACKTRACE` environment variable is set.
+/// Panics of other Rust code are always passed to the default hook.
+#[pyfunction]
+pub fn set_panic_hook(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
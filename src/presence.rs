@@ -0,0 +1,214 @@
+use crate::awareness::Awareness;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+/// A list of users present in a document, built on top of an `Awareness`. The local user
+/// announces its `state`, which is renewed by `heartbeat` so that other replicas know it's still
+/// there, while remote users which haven't been heard of for `ttl` seconds are removed by `prune`.
+/// Callbacks registered with `on_join` and `on_leave` are called whenever remote users appear or
+/// disappear, either because of awareness updates received from other replicas or of `prune`.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import Awareness, Presence, YDoc
+///
+/// awareness = Awareness(YDoc())
+/// presence = Presence(awareness, {'user': 'alice'}, ttl=30)
+/// presence.on_join(lambda client, state: print(state['user'], 'joined'))
+/// presence.on_leave(lambda client: print(client, 'left'))
+///
+/// # every few seconds
+/// if presence.heartbeat():
+///     send(awareness.encode_update([awareness.client_id]))
+/// presence.prune()
+/// ```
+#[pyclass(unsendable)]
+pub struct Presence {
+    awareness: Py<Awareness>,
+    observer: Py<PresenceObserver>,
+    subscription: Cell<Option<u32>>,
+    ttl: Duration,
+}
+
+#[pymethods]
+impl Presence {
+    /// Creates a presence of the local user of a given `awareness`, with a given `state`. Remote
+    /// users are considered gone once they haven't updated their states for `ttl` seconds.
+    #[new]
+    pub fn new(
+        py: Python,
+        awareness: Py<Awareness>,
+        state: PyObject,
+        ttl: Option<f64>,
+    ) -> PyResult<Self> {
+        let ttl = ttl.unwrap_or(30.0);
+        if !ttl.is_finite() || ttl <= 0.0 {
+            return Err(PyValueError::new_err(
+                "ttl must be a positive number of seconds",
+            ));
+        }
+        let observer = Py::new(
+            py,
+            PresenceObserver {
+                awareness: awareness.clone_ref(py),
+                next_id: Cell::new(0),
+                on_join: RefCell::default(),
+                on_leave: RefCell::default(),
+            },
+        )?;
+        let subscription = {
+            let awareness = awareness.borrow(py);
+            awareness.set_local_state(py, state)?;
+            awareness.observe(observer.clone_ref(py).into_py(py))
+        };
+        Ok(Presence {
+            awareness,
+            observer,
+            subscription: Cell::new(Some(subscription)),
+            ttl: Duration::from_secs_f64(ttl),
+        })
+    }
+
+    /// Number of seconds after which remote users which haven't updated their states are
+    /// considered gone.
+    #[getter]
+    pub fn ttl(&self) -> f64 {
+        self.ttl.as_secs_f64()
+    }
+
+    /// Changes the state of the local user.
+    pub fn set_state(&self, py: Python, state: PyObject) -> PyResult<()> {
+        self.awareness.borrow(py).set_local_state(py, state)
+    }
+
+    /// Returns states of remote users which are present, keyed by their client ids.
+    pub fn peers(&self, py: Python) -> PyResult<PyObject> {
+        let awareness = self.awareness.borrow(py);
+        let states = awareness.get_states(py)?;
+        let states: &PyDict = states.as_ref(py).downcast()?;
+        states.del_item(awareness.client_id()).ok();
+        Ok(states.into())
+    }
+
+    /// Renews the state of the local user, if it hasn't been updated for half of `ttl`. Returns
+    /// `True` if it has been renewed, in which case the local state should be sent to other
+    /// replicas with `Awareness.encode_update`.
+    pub fn heartbeat(&self, py: Python) -> bool {
+        let awareness = self.awareness.borrow(py);
+        match awareness.elapsed(awareness.client_id()) {
+            Some(elapsed) if elapsed >= self.ttl / 2 => awareness.renew_local_state(),
+            _ => false,
+        }
+    }
+
+    /// Removes states of remote users which haven't been updated for `ttl`, and calls `on_leave`
+    /// callbacks for each of them. Returns their client ids.
+    pub fn prune(&self, py: Python) -> PyResult<Vec<u64>> {
+        let awareness = self.awareness.borrow(py);
+        let outdated = awareness.outdated(self.ttl);
+        if !outdated.is_empty() {
+            awareness.remove_states(py, outdated.clone(), Some("timeout".into_py(py)))?;
+        }
+        Ok(outdated)
+    }
+
+    /// Subscribes a `callback(client_id, state)` which is called whenever a remote user joins.
+    /// Returns an id which can be used to cancel the subscription with `unobserve`.
+    pub fn on_join(&self, py: Python, callback: PyObject) -> u32 {
+        let observer = self.observer.borrow(py);
+        let id = observer.next_id();
+        observer.on_join.borrow_mut().push((id, callback));
+        id
+    }
+
+    /// Subscribes a `callback(client_id)` which is called whenever a remote user leaves. Returns
+    /// an id which can be used to cancel the subscription with `unobserve`.
+    pub fn on_leave(&self, py: Python, callback: PyObject) -> u32 {
+        let observer = self.observer.borrow(py);
+        let id = observer.next_id();
+        observer.on_leave.borrow_mut().push((id, callback));
+        id
+    }
+
+    /// Cancels a subscription created with `on_join` or `on_leave`.
+    pub fn unobserve(&self, py: Python, subscription_id: u32) {
+        let observer = self.observer.borrow(py);
+        observer
+            .on_join
+            .borrow_mut()
+            .retain(|(id, _)| *id != subscription_id);
+        observer
+            .on_leave
+            .borrow_mut()
+            .retain(|(id, _)| *id != subscription_id);
+    }
+
+    /// Removes the state of the local user, eg. when the user leaves a document, and stops
+    /// calling `on_join` and `on_leave` callbacks.
+    pub fn close(&self, py: Python) -> PyResult<()> {
+        if let Some(subscription) = self.subscription.take() {
+            let awareness = self.awareness.borrow(py);
+            awareness.unobserve(subscription);
+            awareness.set_local_state(py, py.None())?;
+        }
+        Ok(())
+    }
+}
+
+/// Observer of an awareness which calls `on_join` and `on_leave` callbacks of a `Presence`.
+#[pyclass(unsendable)]
+pub struct PresenceObserver {
+    awareness: Py<Awareness>,
+    next_id: Cell<u32>,
+    on_join: RefCell<Vec<(u32, PyObject)>>,
+    on_leave: RefCell<Vec<(u32, PyObject)>>,
+}
+
+impl PresenceObserver {
+    fn next_id(&self) -> u32 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        id
+    }
+}
+
+#[pymethods]
+impl PresenceObserver {
+    fn __call__(&self, py: Python, changes: &PyDict, _origin: PyObject) -> PyResult<()> {
+        let awareness = self.awareness.borrow(py);
+        let remote = |key: &str| -> PyResult<Vec<u64>> {
+            let clients: Vec<u64> = match changes.get_item(key) {
+                Some(clients) => clients.extract()?,
+                None => Vec::new(),
+            };
+            Ok(clients
+                .into_iter()
+                .filter(|client| *client != awareness.client_id())
+                .collect())
+        };
+        // Callbacks are copied, so that they can subscribe or unsubscribe callbacks themselves
+        let callbacks = |callbacks: &RefCell<Vec<(u32, PyObject)>>| -> Vec<PyObject> {
+            callbacks
+                .borrow()
+                .iter()
+                .map(|(_, callback)| callback.clone_ref(py))
+                .collect()
+        };
+        for client in remote("added")? {
+            let state = awareness.state(py, client)?;
+            for callback in callbacks(&self.on_join) {
+                callback.call1(py, (client, state.clone_ref(py)))?;
+            }
+        }
+        for client in remote("removed")? {
+            for callback in callbacks(&self.on_leave) {
+                callback.call1(py, (client,))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,643 @@
+use crate::y_text::{char_len, formatted_chunks, offset_kind, TextChunk};
+use crate::y_xml::children;
+use lib0::any::Any;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+use yrs::types::text::YChange;
+use yrs::types::{Attrs, BranchPtr};
+use yrs::{Text, Transaction, Xml, XmlElement, XmlText};
+
+/// Leaf nodes of the basic ProseMirror schema. Positions in a document depend on which nodes are
+/// leaves, as an empty leaf takes a single position while an empty node with content takes two.
+pub(crate) const DEFAULT_LEAVES: [&str; 3] = ["image", "hard_break", "horizontal_rule"];
+
+/// A node of a ProseMirror document, stored the way y-prosemirror does: elements become XML
+/// elements with string attributes, while consecutive text nodes share a single `YXmlText`, in
+/// which marks are formatting attributes named after mark types with mark attributes as values.
+#[derive(Clone, PartialEq)]
+pub(crate) enum Node {
+    Element(Element),
+    Text(String, Attrs),
+}
+
+#[derive(Clone, PartialEq)]
+pub(crate) struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    content: Vec<Node>,
+}
+
+/// A child of an XML element: either an element or text made of ProseMirror text nodes.
+#[derive(PartialEq)]
+enum Child {
+    Element(Element),
+    Text(Vec<Node>),
+}
+
+/// A single position of a document, as ProseMirror sees it: the start or the end of a node, a
+/// leaf node or a character of text.
+#[derive(Clone)]
+enum Token {
+    Open(Element),
+    Close,
+    Leaf(Element),
+    Char(char, Rc<Attrs>),
+}
+
+impl Token {
+    /// Returns a number of positions taken by this token. Text is indexed in UTF-16 code units.
+    fn width(&self) -> u32 {
+        match self {
+            Token::Char(c, _) => c.len_utf16() as u32,
+            _ => 1,
+        }
+    }
+}
+
+/// Returns ProseMirror nodes represented by children of an XML `element`.
+pub(crate) fn read(txn: &mut Transaction, element: &XmlElement) -> Vec<Node> {
+    let mut content = Vec::new();
+    for child in children(element) {
+        match read_child(txn, &child) {
+            Child::Element(element) => content.push(Node::Element(element)),
+            Child::Text(nodes) => {
+                for node in nodes {
+                    if let Node::Text(text, marks) = node {
+                        push_text(&mut content, &text, marks);
+                    }
+                }
+            }
+        }
+    }
+    content
+}
+
+fn read_child(txn: &mut Transaction, node: &Xml) -> Child {
+    match node {
+        Xml::Element(element) => {
+            let mut attrs: Vec<_> = element
+                .attributes()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect();
+            attrs.sort();
+            Child::Element(Element {
+                name: element.tag().to_string(),
+                attrs,
+                content: read(txn, element),
+            })
+        }
+        Xml::Text(text) => {
+            let mut content = Vec::new();
+            for (chunk, marks) in formatted_chunks(&text_view(text), txn) {
+                push_text(&mut content, &chunk, marks);
+            }
+            Child::Text(content)
+        }
+    }
+}
+
+/// Returns a `Text` view of an XML text, which gives access to its formatting.
+fn text_view(text: &XmlText) -> Text {
+    Text::from(BranchPtr::from(text.as_ref()))
+}
+
+/// Appends a text node to `content`, merging it with a preceding text node with the same marks.
+fn push_text(content: &mut Vec<Node>, text: &str, marks: Attrs) {
+    if text.is_empty() {
+        return;
+    }
+    if let Some(Node::Text(last, last_marks)) = content.last_mut() {
+        if *last_marks == marks {
+            last.push_str(text);
+            return;
+        }
+    }
+    content.push(Node::Text(text.to_string(), marks));
+}
+
+/// Returns a ProseMirror JSON `doc` node with given `content`.
+pub(crate) fn to_json(content: &[Node]) -> Any {
+    let mut json = HashMap::new();
+    json.insert("type".to_string(), Any::String("doc".into()));
+    json.insert(
+        "content".to_string(),
+        Any::Array(content.iter().map(node_json).collect()),
+    );
+    Any::Map(Box::new(json))
+}
+
+fn node_json(node: &Node) -> Any {
+    let mut json = HashMap::new();
+    match node {
+        Node::Element(element) => {
+            json.insert(
+                "type".to_string(),
+                Any::String(element.name.as_str().into()),
+            );
+            if !element.attrs.is_empty() {
+                let attrs = element
+                    .attrs
+                    .iter()
+                    .map(|(name, value)| (name.clone(), Any::String(value.as_str().into())))
+                    .collect();
+                json.insert("attrs".to_string(), Any::Map(Box::new(attrs)));
+            }
+            if !element.content.is_empty() {
+                let content = element.content.iter().map(node_json).collect();
+                json.insert("content".to_string(), Any::Array(content));
+            }
+        }
+        Node::Text(text, marks) => {
+            json.insert("type".to_string(), Any::String("text".into()));
+            json.insert("text".to_string(), Any::String(text.as_str().into()));
+            if !marks.is_empty() {
+                let mut names: Vec<&Rc<str>> = marks.keys().collect();
+                names.sort();
+                let marks = names
+                    .into_iter()
+                    .map(|name| {
+                        let mut mark = HashMap::new();
+                        mark.insert("type".to_string(), Any::String(name.as_ref().into()));
+                        if let Any::Map(attrs) = &marks[name] {
+                            if !attrs.is_empty() {
+                                mark.insert("attrs".to_string(), Any::Map(attrs.clone()));
+                            }
+                        }
+                        Any::Map(Box::new(mark))
+                    })
+                    .collect();
+                json.insert("marks".to_string(), Any::Array(marks));
+            }
+        }
+    }
+    Any::Map(Box::new(json))
+}
+
+fn field<'a>(json: &'a Any, name: &str) -> Option<&'a Any> {
+    match json {
+        Any::Map(entries) => entries.get(name),
+        _ => None,
+    }
+}
+
+fn string_field<'a>(json: &'a Any, name: &str) -> Option<&'a str> {
+    match field(json, name) {
+        Some(Any::String(value)) => Some(&**value),
+        _ => None,
+    }
+}
+
+fn number_field(json: &Any, name: &str) -> PyResult<Option<u32>> {
+    match field(json, name) {
+        None => Ok(None),
+        Some(Any::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => Ok(Some(*n as u32)),
+        Some(Any::BigInt(n)) if *n >= 0 => Ok(Some(*n as u32)),
+        Some(_) => Err(PyValueError::new_err(format!(
+            "{name} must be a non-negative integer"
+        ))),
+    }
+}
+
+fn required(step: &Any, name: &str) -> PyResult<u32> {
+    number_field(step, name)?
+        .ok_or_else(|| PyValueError::new_err(format!("Step is missing its {name}")))
+}
+
+fn parse_nodes(json: Option<&Any>) -> PyResult<Vec<Node>> {
+    match json {
+        None | Some(Any::Null) => Ok(Vec::new()),
+        Some(Any::Array(nodes)) => nodes.iter().map(parse_node).collect(),
+        Some(_) => Err(PyValueError::new_err("Node content must be an array")),
+    }
+}
+
+fn parse_node(json: &Any) -> PyResult<Node> {
+    match string_field(json, "type") {
+        Some("text") => {
+            let text = string_field(json, "text")
+                .ok_or_else(|| PyValueError::new_err("Text nodes must have a text"))?;
+            let mut marks = Attrs::new();
+            if let Some(Any::Array(items)) = field(json, "marks") {
+                for mark in items.iter() {
+                    let (name, attrs) = parse_mark(mark)?;
+                    marks.insert(name, attrs);
+                }
+            }
+            Ok(Node::Text(text.to_string(), marks))
+        }
+        Some(name) => {
+            let mut attrs = Vec::new();
+            if let Some(Any::Map(entries)) = field(json, "attrs") {
+                for (attr, value) in entries.iter() {
+                    if let Some(value) = attr_value(value) {
+                        attrs.push((attr.clone(), value));
+                    }
+                }
+            }
+            attrs.sort();
+            Ok(Node::Element(Element {
+                name: name.to_string(),
+                attrs,
+                content: parse_nodes(field(json, "content"))?,
+            }))
+        }
+        None => Err(PyValueError::new_err("Nodes must have a type")),
+    }
+}
+
+/// Returns a name of a mark and attributes stored for it, which are an empty map for marks
+/// without attributes.
+fn parse_mark(json: &Any) -> PyResult<(Rc<str>, Any)> {
+    let name = string_field(json, "type")
+        .ok_or_else(|| PyValueError::new_err("Marks must have a type"))?;
+    let attrs = match field(json, "attrs") {
+        Some(Any::Map(attrs)) => Any::Map(attrs.clone()),
+        _ => Any::Map(Box::new(HashMap::new())),
+    };
+    Ok((Rc::from(name), attrs))
+}
+
+/// Converts a value of a node attribute into a string, as XML attributes can only store strings.
+/// Values other than strings are stored as JSON, and null values aren't stored at all.
+fn attr_value(value: &Any) -> Option<String> {
+    match value {
+        Any::Null | Any::Undefined => None,
+        Any::String(value) => Some(value.to_string()),
+        value => {
+            let mut json = String::new();
+            value.to_json(&mut json);
+            Some(json)
+        }
+    }
+}
+
+fn flatten(content: &[Node], leaves: &[String], tokens: &mut Vec<Token>) {
+    for node in content {
+        match node {
+            Node::Text(text, marks) => {
+                let marks = Rc::new(marks.clone());
+                tokens.extend(text.chars().map(|c| Token::Char(c, marks.clone())));
+            }
+            Node::Element(element) if leaves.contains(&element.name) => {
+                tokens.push(Token::Leaf(element.clone()))
+            }
+            Node::Element(element) => {
+                tokens.push(Token::Open(Element {
+                    content: Vec::new(),
+                    ..element.clone()
+                }));
+                flatten(&element.content, leaves, tokens);
+                tokens.push(Token::Close);
+            }
+        }
+    }
+}
+
+fn unflatten(tokens: Vec<Token>) -> PyResult<Vec<Node>> {
+    let unbalanced = || PyValueError::new_err("Steps have produced an invalid document");
+    let mut stack: Vec<(Element, Vec<Node>)> = Vec::new();
+    let mut content = Vec::new();
+    for token in tokens {
+        match token {
+            Token::Open(element) => stack.push((element, mem::take(&mut content))),
+            Token::Close => {
+                let (mut element, parent) = stack.pop().ok_or_else(unbalanced)?;
+                element.content = mem::replace(&mut content, parent);
+                content.push(Node::Element(element));
+            }
+            Token::Leaf(element) => content.push(Node::Element(element)),
+            Token::Char(c, marks) => {
+                if let Some(Node::Text(text, last)) = content.last_mut() {
+                    if *last == *marks {
+                        text.push(c);
+                        continue;
+                    }
+                }
+                content.push(Node::Text(c.to_string(), (*marks).clone()));
+            }
+        }
+    }
+    if stack.is_empty() {
+        Ok(content)
+    } else {
+        Err(unbalanced())
+    }
+}
+
+/// Returns an index of a token starting at a given ProseMirror position.
+fn token_index(tokens: &[Token], pos: u32) -> PyResult<usize> {
+    let mut current = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        if current == pos {
+            return Ok(index);
+        }
+        current += token.width();
+        if current > pos {
+            break;
+        }
+    }
+    if current == pos {
+        Ok(tokens.len())
+    } else {
+        Err(PyValueError::new_err(format!(
+            "Position {pos} is out of range"
+        )))
+    }
+}
+
+/// Returns tokens of a serialized slice, without the starts and the ends of its open nodes,
+/// which are joined with nodes surrounding a replaced range.
+fn slice_tokens(slice: Option<&Any>, leaves: &[String]) -> PyResult<Vec<Token>> {
+    let slice = match slice {
+        Some(slice) => slice,
+        None => return Ok(Vec::new()),
+    };
+    let mut tokens = Vec::new();
+    flatten(&parse_nodes(field(slice, "content"))?, leaves, &mut tokens);
+    let open_start = number_field(slice, "openStart")?.unwrap_or(0) as usize;
+    let open_end = number_field(slice, "openEnd")?.unwrap_or(0) as usize;
+    let is_open = open_start + open_end <= tokens.len()
+        && tokens[..open_start]
+            .iter()
+            .all(|token| matches!(token, Token::Open(_)))
+        && tokens[tokens.len() - open_end..]
+            .iter()
+            .all(|token| matches!(token, Token::Close));
+    if !is_open {
+        return Err(PyValueError::new_err(
+            "Slice is open deeper than its content",
+        ));
+    }
+    tokens.truncate(tokens.len() - open_end);
+    tokens.drain(..open_start);
+    Ok(tokens)
+}
+
+/// Applies serialized ProseMirror `steps` to a document with given `content`, and returns its new
+/// content.
+pub(crate) fn apply_steps(content: &[Node], steps: &Any, leaves: &[String]) -> PyResult<Vec<Node>> {
+    let steps = match steps {
+        Any::Array(steps) => steps,
+        _ => return Err(PyValueError::new_err("Steps must be a JSON array")),
+    };
+    let mut tokens = Vec::new();
+    flatten(content, leaves, &mut tokens);
+    for step in steps.iter() {
+        apply_step(&mut tokens, step, leaves)?;
+    }
+    unflatten(tokens)
+}
+
+fn apply_step(tokens: &mut Vec<Token>, step: &Any, leaves: &[String]) -> PyResult<()> {
+    let kind = string_field(step, "stepType")
+        .ok_or_else(|| PyValueError::new_err("Steps must have a stepType"))?;
+    match kind {
+        "replace" => {
+            let from = token_index(tokens, required(step, "from")?)?;
+            let to = token_index(tokens, required(step, "to")?)?.max(from);
+            let slice = slice_tokens(field(step, "slice"), leaves)?;
+            tokens.splice(from..to, slice);
+        }
+        "replaceAround" => {
+            let from = token_index(tokens, required(step, "from")?)?;
+            let to = token_index(tokens, required(step, "to")?)?;
+            let gap_from = token_index(tokens, required(step, "gapFrom")?)?;
+            let gap_to = token_index(tokens, required(step, "gapTo")?)?;
+            if !(from <= gap_from && gap_from <= gap_to && gap_to <= to) {
+                return Err(PyValueError::new_err(
+                    "Gap must be within the replaced range",
+                ));
+            }
+            let mut slice = slice_tokens(field(step, "slice"), leaves)?;
+            let insert = token_index(&slice, required(step, "insert")?)?;
+            let gap: Vec<Token> = tokens[gap_from..gap_to].to_vec();
+            slice.splice(insert..insert, gap);
+            tokens.splice(from..to, slice);
+        }
+        "addMark" | "removeMark" => {
+            let from = token_index(tokens, required(step, "from")?)?;
+            let to = token_index(tokens, required(step, "to")?)?.max(from);
+            let mark = field(step, "mark")
+                .ok_or_else(|| PyValueError::new_err("Step is missing its mark"))?;
+            let (name, attrs) = parse_mark(mark)?;
+            for token in &mut tokens[from..to] {
+                if let Token::Char(_, marks) = token {
+                    let marks = Rc::make_mut(marks);
+                    if kind == "addMark" {
+                        marks.insert(name.clone(), attrs.clone());
+                    } else {
+                        marks.remove(&name);
+                    }
+                }
+            }
+        }
+        "attr" => {
+            let pos = required(step, "pos")?;
+            let index = token_index(tokens, pos)?;
+            let attr = string_field(step, "attr")
+                .ok_or_else(|| PyValueError::new_err("Step is missing its attr"))?;
+            let element = match tokens.get_mut(index) {
+                Some(Token::Open(element)) | Some(Token::Leaf(element)) => element,
+                _ => {
+                    return Err(PyValueError::new_err(format!(
+                        "There is no node at position {pos}"
+                    )))
+                }
+            };
+            element.attrs.retain(|(name, _)| name != attr);
+            if let Some(value) = field(step, "value").and_then(attr_value) {
+                element.attrs.push((attr.to_string(), value));
+                element.attrs.sort();
+            }
+        }
+        kind => {
+            return Err(PyValueError::new_err(format!(
+                "Unsupported step type: {kind}"
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Updates children of an XML `element`, so that they represent given ProseMirror nodes. Like in
+/// y-prosemirror, unchanged children at the start and the end are kept, while children in between
+/// are updated in place if their types match, which preserves concurrent changes made to them.
+pub(crate) fn write(txn: &mut Transaction, element: &XmlElement, content: &[Node]) {
+    let existing = children(element);
+    let current: Vec<Child> = existing
+        .iter()
+        .map(|child| read_child(txn, child))
+        .collect();
+    let target = group(content);
+    let prefix = current
+        .iter()
+        .zip(target.iter())
+        .take_while(|(current, target)| current == target)
+        .count();
+    let suffix = current[prefix..]
+        .iter()
+        .rev()
+        .zip(target[prefix..].iter().rev())
+        .take_while(|(current, target)| current == target)
+        .count();
+    let mut index = prefix as u32;
+    let mut existing = existing[prefix..existing.len() - suffix].iter();
+    for child in &target[prefix..target.len() - suffix] {
+        match (existing.next(), child) {
+            (Some(Xml::Element(node)), Child::Element(target))
+                if node.tag() == target.name.as_str() =>
+            {
+                write_element(txn, node, target)
+            }
+            (Some(Xml::Text(node)), Child::Text(target)) => write_text(txn, node, target),
+            (node, child) => {
+                // A replacement is inserted right after the replaced child, which is removed
+                // afterwards. `yrs` inserts children at the start of an element without a right
+                // origin, so their position among children of other clients would depend on
+                // client ids.
+                let at = if node.is_some() { index + 1 } else { index };
+                match child {
+                    Child::Element(target) => {
+                        let node = element.insert_elem(txn, at, target.name.as_str());
+                        write_element(txn, &node, target);
+                    }
+                    Child::Text(target) => {
+                        let node = element.insert_text(txn, at);
+                        write_text(txn, &node, target);
+                    }
+                }
+                if node.is_some() {
+                    element.remove_range(txn, index, 1);
+                }
+            }
+        }
+        index += 1;
+    }
+    let remaining = existing.count() as u32;
+    if remaining > 0 {
+        element.remove_range(txn, index, remaining);
+    }
+}
+
+/// Groups nodes into children of an XML element, joining consecutive text nodes.
+fn group(content: &[Node]) -> Vec<Child> {
+    let mut children = Vec::new();
+    for node in content {
+        match node {
+            Node::Element(element) => children.push(Child::Element(element.clone())),
+            Node::Text(..) => match children.last_mut() {
+                Some(Child::Text(nodes)) => nodes.push(node.clone()),
+                _ => children.push(Child::Text(vec![node.clone()])),
+            },
+        }
+    }
+    children
+}
+
+fn write_element(txn: &mut Transaction, node: &XmlElement, target: &Element) {
+    let current: Vec<(String, String)> = node
+        .attributes()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+    for (name, _) in current.iter() {
+        if !target.attrs.iter().any(|(attr, _)| attr == name) {
+            node.remove_attribute(txn, name);
+        }
+    }
+    for (name, value) in target.attrs.iter() {
+        if !current.contains(&(name.clone(), value.clone())) {
+            node.insert_attribute(txn, name.as_str(), value.as_str());
+        }
+    }
+    write(txn, node, &target.content);
+}
+
+/// Updates a text, so that it contains given text nodes. Only the changed part of the text is
+/// replaced, after which formatting is updated where marks differ.
+fn write_text(txn: &mut Transaction, node: &XmlText, target: &[Node]) {
+    let text = text_view(node);
+    let chunks: Vec<TextChunk> = text
+        .diff(txn, YChange::identity)
+        .iter()
+        .map(TextChunk::from_diff)
+        .collect();
+    let kind = offset_kind(&text, &chunks);
+    let current: Vec<char> = formatted_chunks(&text, txn)
+        .iter()
+        .flat_map(|(chunk, _)| chunk.chars())
+        .collect();
+    let wanted: Vec<char> = target
+        .iter()
+        .flat_map(|node| match node {
+            Node::Text(text, _) => text.chars().collect(),
+            Node::Element(_) => Vec::new(),
+        })
+        .collect();
+    let prefix = current
+        .iter()
+        .zip(wanted.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = current[prefix..]
+        .iter()
+        .rev()
+        .zip(wanted[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let start: u32 = current[..prefix].iter().map(|c| char_len(*c, &kind)).sum();
+    let removed: u32 = current[prefix..current.len() - suffix]
+        .iter()
+        .map(|c| char_len(*c, &kind))
+        .sum();
+    if removed > 0 {
+        text.remove_range(txn, start, removed);
+    }
+    let inserted: String = wanted[prefix..wanted.len() - suffix].iter().collect();
+    if !inserted.is_empty() {
+        text.insert(txn, start, &inserted);
+    }
+
+    let chunks = formatted_chunks(&text, txn);
+    let current: Vec<&Attrs> = chunks
+        .iter()
+        .flat_map(|(chunk, attrs)| chunk.chars().map(move |_| attrs))
+        .collect();
+    let mut formats: Vec<(u32, u32, Attrs)> = Vec::new();
+    let mut index = 0;
+    let mut position = 0;
+    for node in target {
+        if let Node::Text(chunk, marks) = node {
+            for c in chunk.chars() {
+                let len = char_len(c, &kind);
+                if let Some(attrs) = current.get(position).filter(|attrs| **attrs != marks) {
+                    let mut update = marks.clone();
+                    for name in attrs.keys() {
+                        if !marks.contains_key(name) {
+                            update.insert(name.clone(), Any::Null);
+                        }
+                    }
+                    let merged = match formats.last_mut() {
+                        Some((start, length, last))
+                            if *start + *length == index && *last == update =>
+                        {
+                            *length += len;
+                            true
+                        }
+                        _ => false,
+                    };
+                    if !merged {
+                        formats.push((index, len, update));
+                    }
+                }
+                index += len;
+                position += 1;
+            }
+        }
+    }
+    for (start, len, attrs) in formats {
+        text.format(txn, start, len, attrs);
+    }
+}
@@ -0,0 +1,301 @@
+use crate::awareness::Awareness;
+use crate::shared_types::EncodingError;
+use crate::type_conversions::BinaryInput;
+use crate::y_doc::{apply_update_with_origin, diff, YDoc};
+use lib0::decoding::{Cursor, Read};
+use lib0::encoding::Write;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3::wrap_pyfunction;
+use std::cell::Cell;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::StateVector;
+
+/// Message type of y-protocols sync messages.
+const MESSAGE_SYNC: u32 = 0;
+/// Message type of y-protocols awareness messages.
+const MESSAGE_AWARENESS: u32 = 1;
+/// Message type of y-protocols authentication messages.
+const MESSAGE_AUTH: u32 = 2;
+/// Message type of y-protocols messages requesting awareness states.
+const MESSAGE_QUERY_AWARENESS: u32 = 3;
+/// Sync message subtype carrying a state vector of the sender.
+const SYNC_STEP1: u32 = 0;
+/// Sync message subtype carrying updates missing from a state vector of the receiver.
+const SYNC_STEP2: u32 = 1;
+/// Sync message subtype carrying a new update of the sender.
+const SYNC_UPDATE: u32 = 2;
+/// Authentication message subtype rejecting a client.
+const AUTH_PERMISSION_DENIED: u32 = 0;
+
+/// Encodes a y-protocols authentication message, which tells a client that it has been denied
+/// access to a document for a given `reason`. Clients compatible with y-websocket stop syncing
+/// once they receive it.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import protocol
+///
+/// if not authorized(user, room):
+///     websocket.send(protocol.encode_auth_denied('not a member of this room'))
+///     websocket.close()
+/// ```
+#[pyfunction]
+pub fn encode_auth_denied(py: Python, reason: &str) -> PyObject {
+    let mut message = Vec::new();
+    message.write_var(MESSAGE_AUTH);
+    message.write_var(AUTH_PERMISSION_DENIED);
+    message.write_string(reason);
+    PyBytes::new(py, &message).into()
+}
+
+/// Returns true if a given y-protocols `message` is an authentication message.
+#[pyfunction]
+pub fn is_auth_message(message: BinaryInput) -> bool {
+    let mut cursor = Cursor::new(&message);
+    matches!(cursor.read_var::<u32>(), Ok(MESSAGE_AUTH))
+}
+
+/// Decodes a y-protocols authentication message rejecting a client, and returns the reason of
+/// the rejection. Raises an `EncodingError` if the message is malformed or of another type.
+#[pyfunction]
+pub fn decode_auth_denied(message: BinaryInput) -> PyResult<String> {
+    let mut cursor = Cursor::new(&message);
+    let invalid = |e: lib0::error::Error| EncodingError::new_err(e.to_string());
+    if cursor.read_var::<u32>().map_err(invalid)? != MESSAGE_AUTH {
+        return Err(EncodingError::new_err("Not an authentication message"));
+    }
+    if cursor.read_var::<u32>().map_err(invalid)? != AUTH_PERMISSION_DENIED {
+        return Err(EncodingError::new_err(
+            "Unsupported authentication message subtype",
+        ));
+    }
+    let reason = cursor.read_string().map_err(invalid)?.to_string();
+    if cursor.has_content() {
+        return Err(EncodingError::new_err(
+            "Unexpected trailing bytes in authentication message",
+        ));
+    }
+    Ok(reason)
+}
+
+fn encode_message(message_type: u32, subtype: Option<u32>, payload: &[u8]) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.write_var(message_type);
+    if let Some(subtype) = subtype {
+        message.write_var(subtype);
+    }
+    message.write_buf(payload);
+    message
+}
+
+/// Drives the y-protocols sync and awareness protocols of a single connection to a remote peer,
+/// eg. a client of a server or the server of a client, independently of the transport carrying
+/// its messages, so it can be used with ZeroMQ, WebRTC data channels or plain TCP as well as
+/// websockets. Messages are compatible with y-websocket.
+///
+/// `greeting` returns the first message to send once the connection is open. Every message
+/// received from the peer is passed to `receive`, which returns a list of replies to send back.
+/// Local changes are sent with `encode_update`, and changes of awareness states with
+/// `encode_awareness`. Updates received from the peer are applied with the connection as their
+/// origin, so that observers can avoid sending them back.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import Awareness, SyncConnection, YDoc
+///
+/// doc = YDoc()
+/// conn = SyncConnection(doc, Awareness(doc))
+/// doc.observe_after_transaction(
+///     lambda e: e.origin is not conn and sock.send(conn.encode_update(e.get_update()))
+/// )
+/// sock.send(conn.greeting())
+/// for message in sock:
+///     for reply in conn.receive(message):
+///         sock.send(reply)
+/// ```
+#[pyclass(unsendable)]
+pub struct SyncConnection {
+    doc: Py<YDoc>,
+    awareness: Option<Py<Awareness>>,
+    /// A flag set once the peer has replied to the greeting, which is set while the connection is
+    /// borrowed immutably, as observers called while updates are applied may use the connection.
+    synced: Cell<bool>,
+    denied: Option<String>,
+}
+
+impl SyncConnection {
+    fn receive_sync(
+        &self,
+        py: Python,
+        cursor: &mut Cursor,
+        origin: PyObject,
+    ) -> PyResult<Vec<Vec<u8>>> {
+        let invalid = |e: lib0::error::Error| EncodingError::new_err(e.to_string());
+        match cursor.read_var::<u32>().map_err(invalid)? {
+            SYNC_STEP1 => {
+                let state_vector = StateVector::decode_v1(cursor.read_buf().map_err(invalid)?)
+                    .map_err(|e| EncodingError::new_err(e.to_string()))?;
+                let update = diff(&self.doc.borrow(py).inner, &state_vector);
+                Ok(vec![encode_message(
+                    MESSAGE_SYNC,
+                    Some(SYNC_STEP2),
+                    &update,
+                )])
+            }
+            SYNC_STEP2 => {
+                apply_update_with_origin(
+                    py,
+                    &self.doc.borrow(py),
+                    cursor.read_buf().map_err(invalid)?,
+                    origin,
+                )?;
+                self.synced.set(true);
+                Ok(Vec::new())
+            }
+            SYNC_UPDATE => {
+                apply_update_with_origin(
+                    py,
+                    &self.doc.borrow(py),
+                    cursor.read_buf().map_err(invalid)?,
+                    origin,
+                )?;
+                Ok(Vec::new())
+            }
+            subtype => Err(EncodingError::new_err(format!(
+                "Unsupported sync message subtype {subtype}"
+            ))),
+        }
+    }
+}
+
+#[pymethods]
+impl SyncConnection {
+    /// Creates a connection synchronizing a given document, and optionally awareness states of
+    /// its users, with a remote peer.
+    #[new]
+    pub fn new(doc: Py<YDoc>, awareness: Option<Py<Awareness>>) -> Self {
+        SyncConnection {
+            doc,
+            awareness,
+            synced: Cell::new(false),
+            denied: None,
+        }
+    }
+
+    /// The synchronized document.
+    #[getter]
+    pub fn doc(&self, py: Python) -> Py<YDoc> {
+        self.doc.clone_ref(py)
+    }
+
+    /// Awareness states exchanged over this connection, if any.
+    #[getter]
+    pub fn awareness(&self, py: Python) -> Option<Py<Awareness>> {
+        self.awareness
+            .as_ref()
+            .map(|awareness| awareness.clone_ref(py))
+    }
+
+    /// Set once the peer has sent all updates missing from the document, in reply to the
+    /// greeting.
+    #[getter]
+    pub fn synced(&self) -> bool {
+        self.synced.get()
+    }
+
+    /// A reason given by the peer for denying access to the document, if it did.
+    #[getter]
+    pub fn denied(&self) -> Option<String> {
+        self.denied.clone()
+    }
+
+    /// Returns the first message to send to the peer: a sync step 1 message with a state vector
+    /// of the document, to which the peer replies with updates missing from the document.
+    pub fn greeting(&self, py: Python) -> PyResult<PyObject> {
+        let doc = self.doc.borrow(py);
+        doc.lifecycle.check()?;
+        let state_vector = doc.inner.transact().state_vector().encode_v1();
+        let message = encode_message(MESSAGE_SYNC, Some(SYNC_STEP1), &state_vector);
+        Ok(PyBytes::new(py, &message).into())
+    }
+
+    /// Handles a `message` received from the peer, and returns a list of messages to send back.
+    /// Sync messages are answered, or applied to the document, awareness updates are applied to
+    /// the awareness of this connection, and awareness queries are answered with all known
+    /// awareness states. An authentication message denying access sets `denied`. Raises an
+    /// `EncodingError` if the message is malformed or of an unknown type.
+    pub fn receive(
+        slf: &PyCell<Self>,
+        py: Python,
+        message: BinaryInput,
+    ) -> PyResult<Vec<PyObject>> {
+        let invalid = |e: lib0::error::Error| EncodingError::new_err(e.to_string());
+        let origin: PyObject = slf.into();
+        let mut cursor = Cursor::new(&message);
+        let replies = match cursor.read_var::<u32>().map_err(invalid)? {
+            MESSAGE_SYNC => slf.borrow().receive_sync(py, &mut cursor, origin)?,
+            MESSAGE_AWARENESS => {
+                let update = cursor.read_buf().map_err(invalid)?;
+                if let Some(awareness) = slf.borrow().awareness(py) {
+                    awareness.borrow(py).apply(py, update, origin)?;
+                }
+                Vec::new()
+            }
+            MESSAGE_AUTH => {
+                slf.borrow_mut().denied =
+                    Some(decode_auth_denied(BinaryInput::Borrowed(&message))?);
+                Vec::new()
+            }
+            MESSAGE_QUERY_AWARENESS => match slf.borrow().awareness(py) {
+                Some(awareness) => {
+                    let update = awareness.borrow(py).encode(None);
+                    vec![encode_message(MESSAGE_AWARENESS, None, &update)]
+                }
+                None => Vec::new(),
+            },
+            message_type => {
+                return Err(EncodingError::new_err(format!(
+                    "Unsupported message type {message_type}"
+                )))
+            }
+        };
+        Ok(replies
+            .into_iter()
+            .map(|reply| PyBytes::new(py, &reply).into())
+            .collect())
+    }
+
+    /// Encodes an update of the document, eg. obtained with `AfterTransactionEvent.get_update`,
+    /// as a sync message to send to the peer.
+    pub fn encode_update(&self, py: Python, update: BinaryInput) -> PyObject {
+        PyBytes::new(
+            py,
+            &encode_message(MESSAGE_SYNC, Some(SYNC_UPDATE), &update),
+        )
+        .into()
+    }
+
+    /// Encodes awareness states of given `clients`, or of all known clients, as an awareness
+    /// message to send to the peer. Raises a `ValueError` if this connection has no awareness.
+    pub fn encode_awareness(&self, py: Python, clients: Option<Vec<u64>>) -> PyResult<PyObject> {
+        match &self.awareness {
+            Some(awareness) => {
+                let update = awareness.borrow(py).encode(clients);
+                Ok(PyBytes::new(py, &encode_message(MESSAGE_AWARENESS, None, &update)).into())
+            }
+            None => Err(PyValueError::new_err("This connection has no awareness")),
+        }
+    }
+}
+
+pub(crate) fn register(m: &PyModule) -> PyResult<()> {
+    m.add_wrapped(wrap_pyfunction!(encode_auth_denied))?;
+    m.add_wrapped(wrap_pyfunction!(is_auth_message))?;
+    m.add_wrapped(wrap_pyfunction!(decode_auth_denied))?;
+    Ok(())
+}
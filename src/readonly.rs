@@ -0,0 +1,350 @@
+use crate::dispatcher::Dispatcher;
+use crate::shared_types::YError;
+use crate::y_array::{YArray, YArrayEvent};
+use crate::y_doc::YDoc;
+use crate::y_map::{ItemView, ValueView, YMap, YMapEvent};
+use crate::y_text::{YText, YTextEvent};
+use crate::y_transaction::YTransaction;
+use crate::y_xml::{YXmlElement, YXmlEvent, YXmlText, YXmlTextEvent, YXmlTreeWalker};
+use pyo3::create_exception;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::pyclass::CompareOp;
+use pyo3::types::{PyDict, PyIterator, PyList, PyTuple};
+use yrs::SubscriptionId;
+
+create_exception!(
+    y_py,
+    ReadOnlyError,
+    YError,
+    "Occurs when a shared type obtained from a read-only view of a YDoc is modified."
+);
+
+/// A read-only view of a `YDoc`, returned by `YDoc.readonly_view`. Shared types obtained from it
+/// can be read and observed as usual, but raise a `ReadOnlyError` when passed a transaction, which
+/// all modifying methods require. This makes it possible to hand out documents to code which
+/// should not change them, eg. connections of users with read-only permissions.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YDoc
+///
+/// doc = YDoc()
+/// view = doc.readonly_view()
+/// text = view.get_text('name')
+/// text.observe(lambda event: print(event.delta))
+/// with doc.begin_transaction() as txn:
+///     text.extend(txn, 'hello')  # raises ReadOnlyError
+/// ```
+#[pyclass(unsendable)]
+pub struct ReadOnlyDoc {
+    /// A weak reference to the underlying `YDoc`, as observers of read-only shared types refer
+    /// back to this view.
+    doc: PyObject,
+}
+
+impl ReadOnlyDoc {
+    pub(crate) fn new(doc: &PyCell<YDoc>) -> PyResult<Self> {
+        let py = doc.py();
+        let doc = py.import("weakref")?.call_method1("ref", (doc,))?.into();
+        Ok(ReadOnlyDoc { doc })
+    }
+
+    fn doc<'py>(&self, py: Python<'py>) -> PyResult<&'py PyCell<YDoc>> {
+        let doc = self.doc.call0(py)?.into_ref(py);
+        doc.downcast().map_err(|_| {
+            PyRuntimeError::new_err("The document of this read-only view has been dropped")
+        })
+    }
+
+    fn get(slf: &PyCell<Self>, method: &str, name: &str) -> PyResult<PyObject> {
+        let py = slf.py();
+        let shared = slf.borrow().doc(py)?.call_method1(method, (name,))?;
+        let view: PyObject = slf.into();
+        protect(shared, &view)
+    }
+}
+
+#[pymethods]
+impl ReadOnlyDoc {
+    /// Gets globally unique identifier of the underlying `YDoc` instance.
+    #[getter]
+    pub fn client_id(&self, py: Python) -> PyResult<u64> {
+        Ok(self.doc(py)?.borrow().client_id())
+    }
+
+    /// Returns a read-only `YText` root type of a given `name`.
+    pub fn get_text(slf: &PyCell<Self>, name: &str) -> PyResult<PyObject> {
+        Self::get(slf, "get_text", name)
+    }
+
+    /// Returns a read-only `YArray` root type of a given `name`.
+    pub fn get_array(slf: &PyCell<Self>, name: &str) -> PyResult<PyObject> {
+        Self::get(slf, "get_array", name)
+    }
+
+    /// Returns a read-only `YMap` root type of a given `name`.
+    pub fn get_map(slf: &PyCell<Self>, name: &str) -> PyResult<PyObject> {
+        Self::get(slf, "get_map", name)
+    }
+
+    /// Returns a read-only `YXmlElement` root type of a given `name`.
+    pub fn get_xml_element(slf: &PyCell<Self>, name: &str) -> PyResult<PyObject> {
+        Self::get(slf, "get_xml_element", name)
+    }
+
+    /// Returns a read-only `YXmlText` root type of a given `name`.
+    pub fn get_xml_text(slf: &PyCell<Self>, name: &str) -> PyResult<PyObject> {
+        Self::get(slf, "get_xml_text", name)
+    }
+
+    /// Returns a hash of the state of the underlying document, see `YDoc.state_hash`.
+    pub fn state_hash(&self, py: Python) -> PyResult<String> {
+        self.doc(py)?.borrow().state_hash(py)
+    }
+
+    /// Returns a snapshot of the underlying document, see `YDoc.snapshot`.
+    pub fn snapshot(&self, py: Python) -> PyResult<PyObject> {
+        self.doc(py)?.borrow().snapshot(py)
+    }
+
+    /// Returns statistics of the underlying document, see `YDoc.stats`.
+    pub fn stats(&self, py: Python) -> PyResult<PyObject> {
+        self.doc(py)?.borrow().stats(py)
+    }
+
+    /// Subscribes a callback to transactions committed on the underlying document.
+    pub fn observe_after_transaction(
+        &self,
+        py: Python,
+        callback: PyObject,
+        dispatcher: Option<PyRef<Dispatcher>>,
+        weak: Option<bool>,
+    ) -> PyResult<SubscriptionId> {
+        let doc = self.doc(py)?.borrow_mut();
+        YDoc::observe_after_transaction(doc, py, callback, dispatcher, weak)
+    }
+}
+
+/// A read-only proxy of a shared type, map view or event. Attributes are forwarded to the proxied
+/// object, and shared types they return are wrapped in read-only proxies as well.
+#[pyclass(unsendable)]
+pub struct ReadOnly {
+    inner: PyObject,
+    doc: PyObject,
+}
+
+#[pymethods]
+impl ReadOnly {
+    fn __getattr__(&self, py: Python, name: &str) -> PyResult<PyObject> {
+        let value = self.inner.getattr(py, name)?;
+        match name {
+            "transaction" => Err(ReadOnlyError::new_err(
+                "Transactions cannot be started from a read-only shared type",
+            )),
+            "doc" => Ok(self.doc.clone_ref(py)),
+            _ if value.as_ref(py).is_callable() => Ok(ReadOnlyMethod {
+                method: value,
+                doc: self.doc.clone_ref(py),
+            }
+            .into_py(py)),
+            _ => protect(value.as_ref(py), &self.doc),
+        }
+    }
+
+    fn __len__(&self, py: Python) -> PyResult<usize> {
+        self.inner.as_ref(py).len()
+    }
+
+    fn __bool__(&self, py: Python) -> PyResult<bool> {
+        self.inner.as_ref(py).is_true()
+    }
+
+    fn __str__(&self, py: Python) -> PyResult<String> {
+        Ok(self.inner.as_ref(py).str()?.to_string())
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!("ReadOnly({})", self.inner.as_ref(py).repr()?))
+    }
+
+    fn __getitem__(&self, py: Python, key: &PyAny) -> PyResult<PyObject> {
+        protect(self.inner.as_ref(py).get_item(key)?, &self.doc)
+    }
+
+    fn __contains__(&self, py: Python, item: &PyAny) -> PyResult<bool> {
+        self.inner.as_ref(py).contains(unwrap(item))
+    }
+
+    fn __iter__(&self, py: Python) -> PyResult<ReadOnlyIterator> {
+        Ok(ReadOnlyIterator {
+            iter: self.inner.as_ref(py).iter()?.into(),
+            doc: self.doc.clone_ref(py),
+        })
+    }
+
+    fn __richcmp__(&self, py: Python, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        Ok(self
+            .inner
+            .as_ref(py)
+            .rich_compare(unwrap(other), op)?
+            .into())
+    }
+
+    fn __hash__(&self, py: Python) -> PyResult<isize> {
+        self.inner.as_ref(py).hash()
+    }
+}
+
+/// A method of a proxied object, which refuses transactions and protects its results.
+#[pyclass(unsendable)]
+pub struct ReadOnlyMethod {
+    method: PyObject,
+    doc: PyObject,
+}
+
+#[pymethods]
+impl ReadOnlyMethod {
+    #[args(args = "*", kwargs = "**")]
+    fn __call__(&self, py: Python, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+        let args = args
+            .iter()
+            .map(|arg| self.argument(py, arg))
+            .collect::<PyResult<Vec<_>>>()?;
+        let kwargs = match kwargs {
+            Some(kwargs) => {
+                let checked = PyDict::new(py);
+                for (key, value) in kwargs.iter() {
+                    checked.set_item(key, self.argument(py, value)?)?;
+                }
+                Some(checked)
+            }
+            None => None,
+        };
+        let result = self.method.call(py, PyTuple::new(py, args), kwargs)?;
+        protect(result.as_ref(py), &self.doc)
+    }
+}
+
+impl ReadOnlyMethod {
+    fn argument(&self, py: Python, arg: &PyAny) -> PyResult<PyObject> {
+        if arg.downcast::<PyCell<YTransaction>>().is_ok() {
+            Err(ReadOnlyError::new_err(
+                "Shared types obtained from a read-only view cannot be modified",
+            ))
+        } else if arg.downcast::<PyCell<ReadOnly>>().is_ok() {
+            Ok(unwrap(arg).into())
+        } else if arg.is_callable() {
+            // Observers receive events of read-only shared types
+            let callback = ReadOnlyCallback {
+                callback: arg.into(),
+                doc: self.doc.clone_ref(py),
+            };
+            Ok(callback.into_py(py))
+        } else {
+            Ok(arg.into())
+        }
+    }
+}
+
+/// A callback passed to a proxied object, which receives protected arguments.
+#[pyclass(unsendable)]
+pub struct ReadOnlyCallback {
+    callback: PyObject,
+    doc: PyObject,
+}
+
+#[pymethods]
+impl ReadOnlyCallback {
+    #[args(args = "*")]
+    fn __call__(&self, py: Python, args: &PyTuple) -> PyResult<PyObject> {
+        let args = args
+            .iter()
+            .map(|arg| protect(arg, &self.doc))
+            .collect::<PyResult<Vec<_>>>()?;
+        self.callback.call1(py, PyTuple::new(py, args))
+    }
+}
+
+#[pyclass(unsendable)]
+pub struct ReadOnlyIterator {
+    iter: Py<PyIterator>,
+    doc: PyObject,
+}
+
+#[pymethods]
+impl ReadOnlyIterator {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python) -> PyResult<Option<PyObject>> {
+        match self.iter.as_ref(py).next() {
+            Some(item) => Ok(Some(protect(item?, &self.doc)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Wraps shared types, map views and events in read-only proxies, including those nested in
+/// lists, tuples and dictionaries.
+fn protect(value: &PyAny, doc: &PyObject) -> PyResult<PyObject> {
+    let py = value.py();
+    if let Ok(list) = value.downcast::<PyList>() {
+        let items = list
+            .iter()
+            .map(|item| protect(item, doc))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(PyList::new(py, items).into())
+    } else if let Ok(tuple) = value.downcast::<PyTuple>() {
+        let items = tuple
+            .iter()
+            .map(|item| protect(item, doc))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(PyTuple::new(py, items).into())
+    } else if let Ok(dict) = value.downcast::<PyDict>() {
+        let protected = PyDict::new(py);
+        for (key, value) in dict.iter() {
+            protected.set_item(key, protect(value, doc)?)?;
+        }
+        Ok(protected.into())
+    } else if is_mutable(value) {
+        let proxy = ReadOnly {
+            inner: value.into(),
+            doc: doc.clone_ref(py),
+        };
+        Ok(proxy.into_py(py))
+    } else {
+        Ok(value.into())
+    }
+}
+
+/// Checks if a value gives access to modifying methods of shared types.
+fn is_mutable(value: &PyAny) -> bool {
+    value.downcast::<PyCell<YText>>().is_ok()
+        || value.downcast::<PyCell<YArray>>().is_ok()
+        || value.downcast::<PyCell<YMap>>().is_ok()
+        || value.downcast::<PyCell<YXmlElement>>().is_ok()
+        || value.downcast::<PyCell<YXmlText>>().is_ok()
+        || value.downcast::<PyCell<ItemView>>().is_ok()
+        || value.downcast::<PyCell<ValueView>>().is_ok()
+        || value.downcast::<PyCell<YXmlTreeWalker>>().is_ok()
+        || value.downcast::<PyCell<YTextEvent>>().is_ok()
+        || value.downcast::<PyCell<YArrayEvent>>().is_ok()
+        || value.downcast::<PyCell<YMapEvent>>().is_ok()
+        || value.downcast::<PyCell<YXmlEvent>>().is_ok()
+        || value.downcast::<PyCell<YXmlTextEvent>>().is_ok()
+}
+
+/// Returns an object proxied by a given value, or the value itself if it's not a proxy.
+fn unwrap(value: &PyAny) -> &PyAny {
+    match value.downcast::<PyCell<ReadOnly>>() {
+        Ok(proxy) => proxy
+            .borrow()
+            .inner
+            .clone_ref(value.py())
+            .into_ref(value.py()),
+        Err(_) => value,
+    }
+}
@@ -0,0 +1,205 @@
+use crate::logging;
+use crate::metrics::{self, Metric};
+use crate::shared_types::{DefaultPyErr, IntegratedOperationException, SharedType, SourceGuard};
+use crate::shutdown::{self, Callback};
+use crate::type_conversions::ToPython;
+use crate::y_map::YMap;
+use crate::y_transaction::YTransaction;
+use lib0::any::Any;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use yrs::types::Value;
+use yrs::{Map, SubscriptionId};
+
+/// The current entry of a register: a value with its timestamp, written by a given client.
+struct Entry {
+    timestamp: f64,
+    client_id: u64,
+    value: Value,
+}
+
+/// Returns the current entry of a register stored under a given `key`, which is the entry with
+/// the latest timestamp, with ties broken by client identifiers.
+fn current(map: &Map, key: &str) -> Option<Entry> {
+    let prefix = format!("{key}:");
+    let mut current: Option<Entry> = None;
+    for (entry_key, value) in map.iter() {
+        let client_id = match entry_key.strip_prefix(&prefix).map(str::parse::<u64>) {
+            Some(Ok(client_id)) => client_id,
+            _ => continue,
+        };
+        let (timestamp, value) = match value {
+            Value::Any(Any::Map(mut fields)) => {
+                match (fields.remove("timestamp"), fields.remove("value")) {
+                    (Some(Any::Number(timestamp)), Some(value)) => (timestamp, Value::Any(value)),
+                    _ => continue,
+                }
+            }
+            _ => continue,
+        };
+        let newer = match &current {
+            Some(entry) => (timestamp, client_id) > (entry.timestamp, entry.client_id),
+            None => true,
+        };
+        if newer {
+            current = Some(Entry {
+                timestamp,
+                client_id,
+                value,
+            });
+        }
+    }
+    current
+}
+
+/// A single value stored under a `key` of a `YMap`, eg. a setting of a shared document, which is
+/// resolved deterministically when replicas change it concurrently: the value written with the
+/// latest timestamp wins, and ties are broken by client identifiers of the writers.
+///
+/// Every client writes its values to its own entry of the map, under a `"{key}:{client_id}"` key,
+/// as concurrent writes of the same key would be resolved by client identifiers alone.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YDoc, YRegister
+///
+/// doc = YDoc()
+/// theme = YRegister(doc.get_map('settings'), 'theme')
+/// theme.observe(lambda value: print('theme changed to', value))
+/// with doc.begin_transaction() as txn:
+///     theme.set(txn, 'dark')
+/// ```
+#[pyclass(unsendable)]
+pub struct YRegister {
+    map: Map,
+    key: String,
+    guard: SourceGuard,
+}
+
+#[pymethods]
+impl YRegister {
+    #[new]
+    pub fn new(map: &PyCell<YMap>, key: String) -> PyResult<Self> {
+        let inner = match &map.borrow().0 {
+            SharedType::Integrated(inner) => inner.clone(),
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        let guard = SourceGuard::new(map, Some(inner.as_ref()), &map.borrow().1);
+        Ok(YRegister {
+            map: inner,
+            key,
+            guard,
+        })
+    }
+
+    /// Key of the map under which this register is stored.
+    #[getter]
+    pub fn key(&self) -> String {
+        self.key.clone()
+    }
+
+    /// Writes a `value` with a given `timestamp` in seconds since the epoch, which defaults to the
+    /// current time. Returns `True` if the value has become the current value of this register,
+    /// or `False` without writing it if the current value has a later timestamp.
+    ///
+    /// Without an explicit timestamp, the value always becomes current: if the current value has
+    /// a later timestamp, eg. because of a clock skew between replicas, the timestamp is moved
+    /// right after it.
+    pub fn set(
+        &mut self,
+        py: Python,
+        txn: &mut YTransaction,
+        value: PyObject,
+        timestamp: Option<f64>,
+    ) -> PyResult<bool> {
+        self.guard.check(py)?;
+        let client_id = txn.client_id;
+        let latest = current(&self.map, &self.key).map(|entry| (entry.timestamp, entry.client_id));
+        let timestamp = match timestamp {
+            Some(timestamp) => timestamp,
+            None => {
+                let now: f64 = py.import("time")?.call_method0("time")?.extract()?;
+                match latest {
+                    Some((latest, _)) if latest >= now => {
+                        latest + latest.abs().max(1.0) * f64::EPSILON
+                    }
+                    _ => now,
+                }
+            }
+        };
+        if let Some(latest) = latest {
+            if (timestamp, client_id) < latest {
+                return Ok(false);
+            }
+        }
+        let entry = PyDict::new(py);
+        entry.set_item("timestamp", timestamp)?;
+        entry.set_item("value", value)?;
+        let key = format!("{}:{}", self.key, client_id);
+        YMap::from(self.map.clone()).set(txn, &key, entry.into())?;
+        Ok(true)
+    }
+
+    /// Returns the current value of this register, or `None` if it has never been written.
+    pub fn get(&self, py: Python, _txn: &YTransaction) -> PyResult<PyObject> {
+        self.guard.check(py)?;
+        Ok(match current(&self.map, &self.key) {
+            Some(entry) => entry.value.into_py(py),
+            None => py.None(),
+        })
+    }
+
+    /// Returns a timestamp of the current value of this register, or `None` if it has never been
+    /// written.
+    pub fn timestamp(&self, py: Python, _txn: &YTransaction) -> PyResult<Option<f64>> {
+        self.guard.check(py)?;
+        Ok(current(&self.map, &self.key).map(|entry| entry.timestamp))
+    }
+
+    /// Subscribes a `callback(value)` which is called with the current value of this register
+    /// whenever a transaction changes any of its entries, including writes which lose against a
+    /// value with a later timestamp. If `weak` is `True`, only a weak reference to the callback
+    /// is held, like in `YDoc.observe_after_transaction`.
+    pub fn observe(
+        &mut self,
+        py: Python,
+        callback: PyObject,
+        weak: Option<bool>,
+    ) -> PyResult<SubscriptionId> {
+        self.guard.check(py)?;
+        let callback = Callback::observer(py, callback, weak)?;
+        callback.set_owner(self.guard.location().doc_key(py));
+        let map = self.map.clone();
+        let key = self.key.clone();
+        let prefix = format!("{key}:");
+        Ok(self
+            .map
+            .observe(move |txn, event| {
+                let changed = event
+                    .keys(txn)
+                    .keys()
+                    .any(|entry_key| entry_key.starts_with(&prefix));
+                if !changed {
+                    return;
+                }
+                shutdown::with_gil(|py| {
+                    let value = match current(&map, &key) {
+                        Some(entry) => entry.value.into_py(py),
+                        None => py.None(),
+                    };
+                    if let Err(err) =
+                        metrics::measure(Metric::Callbacks, || callback.call1(py, (value,)))
+                    {
+                        logging::callback_failed(py, err)
+                    }
+                });
+            })
+            .into())
+    }
+
+    /// Cancels an observer callback associated with a given `subscription_id`.
+    pub fn unobserve(&mut self, subscription_id: SubscriptionId) {
+        self.map.unobserve(subscription_id);
+    }
+}
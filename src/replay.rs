@@ -0,0 +1,197 @@
+use crate::delete_set::DeleteSet;
+use crate::shared_types::EncodingError;
+use crate::snapshot::{read_structs, Block, Parent, Reader};
+use crate::type_conversions::BinaryInput;
+use crate::y_doc::YDoc;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{StateVector, Update};
+
+/// Location of a block: a path of its parent shared type from its root type, where `None` stands
+/// for elements of sequences, followed by a map key of the block, if any.
+struct Location {
+    parent: Rc<Vec<Option<String>>>,
+    key: Option<String>,
+}
+
+impl Location {
+    /// Returns a path of a change made by a block, which is a path of its parent type for
+    /// elements of sequences.
+    fn path(&self) -> Vec<Option<&str>> {
+        let mut path: Vec<Option<&str>> = self.parent.iter().map(Option::as_deref).collect();
+        if let Some(key) = self.key.as_deref() {
+            path.push(Some(key));
+        }
+        path
+    }
+}
+
+/// Locations of all blocks seen during a replay, by client id and clock ranges of blocks.
+#[derive(Default)]
+struct Locations(HashMap<u64, BTreeMap<u64, (u64, Rc<Location>)>>);
+
+impl Locations {
+    fn find(&self, client: u64, clock: u64) -> Option<&Rc<Location>> {
+        self.overlapping(client, clock, clock.saturating_add(1))
+            .next()
+    }
+
+    /// Returns locations of blocks of a given client overlapping with a range of clocks from
+    /// `start` to `end`.
+    fn overlapping(
+        &self,
+        client: u64,
+        start: u64,
+        end: u64,
+    ) -> impl Iterator<Item = &Rc<Location>> {
+        self.0
+            .get(&client)
+            .into_iter()
+            .flat_map(move |blocks| blocks.range(..end).rev())
+            .take_while(move |(_, (block_end, _))| *block_end > start)
+            .map(|(_, (_, location))| location)
+    }
+
+    /// Adds locations of blocks of an update, which may refer to other blocks of the same update
+    /// in any order. Blocks which cannot be located yet are skipped.
+    fn add(&mut self, structs: &[(u64, u64, Vec<Block>)]) {
+        let mut pending: Vec<(u64, &Block)> = structs
+            .iter()
+            .flat_map(|(client, _, blocks)| blocks.iter().map(move |block| (*client, block)))
+            .filter(|(client, block)| self.find(*client, block.clock).is_none())
+            .collect();
+        loop {
+            let count = pending.len();
+            pending.retain(|(client, block)| {
+                let location = match block.parent {
+                    Parent::None => return false,
+                    Parent::Root(name) => Location {
+                        parent: Rc::new(vec![Some(name.to_string())]),
+                        key: block.parent_sub.map(str::to_string),
+                    },
+                    Parent::Type(client, clock) => match self.find(client, clock) {
+                        Some(parent) => {
+                            let mut path = parent.parent.as_ref().clone();
+                            path.push(parent.key.clone());
+                            Location {
+                                parent: Rc::new(path),
+                                key: block.parent_sub.map(str::to_string),
+                            }
+                        }
+                        None => return true,
+                    },
+                    Parent::SameAs(client, clock) => match self.find(client, clock) {
+                        Some(origin) => Location {
+                            parent: origin.parent.clone(),
+                            key: origin.key.clone(),
+                        },
+                        None => return true,
+                    },
+                };
+                let end = block.clock.saturating_add(block.len);
+                self.0
+                    .entry(*client)
+                    .or_default()
+                    .insert(block.clock, (end, Rc::new(location)));
+                false
+            });
+            if pending.is_empty() || pending.len() == count {
+                break;
+            }
+        }
+    }
+}
+
+/// Applies lib0 v1 encoded `updates` to a new document one by one, and calls an `observer` with a
+/// summary of changes made by each of them. This allows to build a timeline of a document history
+/// from a log of updates, eg. for auditing, without creating a document per update or encoding
+/// intermediate states.
+///
+/// `updates` can be any iterable of updates, eg. a generator reading them from a storage. Each
+/// summary is a dictionary with:
+///
+/// - `index`: a position of the update in `updates`,
+/// - `inserted`: a dictionary of ranges `(start, end)` of clocks of items integrated by the update,
+///   keyed by client ids,
+/// - `deleted`: a `DeleteSet` of items deleted by the update,
+/// - `paths`: sorted locations of changes, as passed to filters registered with
+///   `YDoc.add_update_filter`.
+///
+/// Items of an update may only be integrated once updates they depend on have been applied, in
+/// which case they are reported by the summary of the latter update. Returns the document with
+/// all updates applied.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import replay_updates
+///
+/// def record(summary):
+///     for client, (start, end) in summary['inserted'].items():
+///         timeline.append((summary['index'], client, end - start, summary['paths']))
+///
+/// doc = replay_updates(stored_updates, record)
+/// ```
+#[pyfunction]
+pub fn replay_updates(py: Python, updates: &PyAny, observer: PyObject) -> PyResult<YDoc> {
+    let doc = YDoc::new(None, None, None)?;
+    let mut locations = Locations::default();
+    let mut pending: Vec<(Vec<u8>, StateVector)> = Vec::new();
+    for (index, update) in updates.iter()?.enumerate() {
+        let update: BinaryInput = update?.extract()?;
+        let mut txn = doc.begin_transaction();
+        txn.apply_v1(BinaryInput::Borrowed(&*update))?;
+        locations.add(&read_structs(&mut Reader::new(&update))?);
+        // Yrs forgets items waiting for missing dependencies once another update is integrated,
+        // so updates which haven't been fully integrated yet are applied again
+        loop {
+            let state = txn.state_vector();
+            for (update, _) in pending.iter() {
+                txn.integrate_v1(update)?;
+            }
+            if txn.state_vector() == state {
+                break;
+            }
+        }
+        let end = Update::decode_v1(&update)
+            .map_err(|e| EncodingError::new_err(e.to_string()))?
+            .state_vector();
+        pending.push((update.to_vec(), end));
+        let state = txn.state_vector();
+        pending.retain(|(_, end)| end.iter().any(|(client, clock)| state.get(client) < *clock));
+
+        let inserted = PyDict::new(py);
+        let mut paths = BTreeSet::new();
+        for (client, end) in txn.state_vector().iter() {
+            let (start, end) = (txn.before_state.get(client) as u64, *end as u64);
+            if start < end {
+                inserted.set_item(client, (start, end))?;
+                for location in locations.overlapping(*client, start, end) {
+                    paths.insert(location.path());
+                }
+            }
+        }
+        let deleted = DeleteSet::decode(BinaryInput::Owned(txn.inner.delete_set.encode_v1()))?;
+        for (client, ranges) in deleted.items() {
+            for (clock, len) in ranges {
+                for location in locations.overlapping(client, clock, clock.saturating_add(len)) {
+                    paths.insert(location.path());
+                }
+            }
+        }
+        txn.commit();
+
+        let summary = PyDict::new(py);
+        summary.set_item("index", index)?;
+        summary.set_item("inserted", inserted)?;
+        summary.set_item("deleted", Py::new(py, deleted)?)?;
+        let paths = paths.into_iter().map(|path| PyTuple::new(py, path));
+        summary.set_item("paths", PyList::new(py, paths))?;
+        observer.call1(py, (summary,))?;
+    }
+    Ok(doc)
+}
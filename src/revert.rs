@@ -0,0 +1,393 @@
+use crate::cursor::sequence_ids;
+use crate::shared_types::{nested_branches, root_branches};
+use crate::snapshot::{
+    is_deleted, read_delete_set, read_items, read_structs, Block, DeleteSet, Item, Owner, Reader,
+    Unit,
+};
+use crate::type_conversions::{take_integration_error, PyObjectWrapper, ToPython};
+use crate::y_text::{char_len, current_offset_kind};
+use crate::y_transaction::YTransaction;
+use lib0::any::Any;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use yrs::types::{BranchPtr, PathSegment, TYPE_REFS_TEXT, TYPE_REFS_XML_TEXT};
+use yrs::{Array, Doc, Map, OffsetKind, Text, Transaction, ID};
+
+/// Ids of items inserted and deleted by a change of a document, eg. an update or transactions
+/// of a given origin.
+#[derive(Default)]
+pub(crate) struct Change {
+    inserted: DeleteSet,
+    deleted: DeleteSet,
+}
+
+impl Change {
+    /// Reads ids of items inserted and deleted by an update using lib0 v1 encoding.
+    pub(crate) fn from_update(update: &[u8]) -> PyResult<Self> {
+        let mut reader = Reader::new(update);
+        let mut inserted = DeleteSet::new();
+        for (client, _, blocks) in read_structs(&mut reader)? {
+            let ranges = inserted.entry(client).or_default();
+            for block in blocks.iter().filter(|block| !block.is_skip()) {
+                ranges.push((block.clock, block.len));
+            }
+        }
+        let deleted = read_delete_set(&mut reader)?;
+        Ok(Change { inserted, deleted })
+    }
+
+    /// Reads ids of items inserted and deleted by a transaction, given the state vectors of its
+    /// document before and after the transaction, and its delete set using lib0 v1 encoding.
+    pub(crate) fn from_transaction(
+        before: &yrs::StateVector,
+        after: &yrs::StateVector,
+        delete_set: &[u8],
+    ) -> PyResult<Self> {
+        let mut inserted = DeleteSet::new();
+        for (client, clock) in after.iter() {
+            let start = before.get(client);
+            if *clock > start {
+                inserted.insert(*client, vec![(start as u64, (*clock - start) as u64)]);
+            }
+        }
+        let deleted = read_delete_set(&mut Reader::new(delete_set))?;
+        Ok(Change { inserted, deleted })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.deleted.is_empty()
+    }
+
+    pub(crate) fn extend(&mut self, other: &Change) {
+        merge(&mut self.inserted, &other.inserted);
+        merge(&mut self.deleted, &other.deleted);
+    }
+}
+
+fn merge(set: &mut DeleteSet, other: &DeleteSet) {
+    for (client, ranges) in other.iter() {
+        set.entry(*client)
+            .or_default()
+            .extend(ranges.iter().copied());
+    }
+}
+
+/// Changes made by transactions of every origin, in order.
+type Changes = Vec<(PyObject, Change)>;
+
+/// Changes of a document made by transactions with an origin, recorded once
+/// `YDoc.record_origins` has been called.
+#[derive(Clone, Default)]
+pub(crate) struct OriginLog(Rc<RefCell<Option<Changes>>>);
+
+impl OriginLog {
+    pub(crate) fn is_recording(&self) -> bool {
+        self.0.borrow().is_some()
+    }
+
+    pub(crate) fn start(&self) {
+        self.0.borrow_mut().get_or_insert_with(Vec::new);
+    }
+
+    pub(crate) fn push(&self, origin: PyObject, change: Change) {
+        if let Some(log) = self.0.borrow_mut().as_mut() {
+            log.push((origin, change));
+        }
+    }
+
+    /// Removes changes made by transactions of a given origin from the log, and returns them
+    /// merged into a single change.
+    pub(crate) fn take(&self, py: Python, origin: &PyAny) -> PyResult<Change> {
+        let mut merged = Change::default();
+        if let Some(log) = self.0.borrow_mut().as_mut() {
+            let mut kept = Vec::with_capacity(log.len());
+            for (recorded, change) in log.drain(..) {
+                if recorded.as_ref(py).eq(origin)? {
+                    merged.extend(&change);
+                } else {
+                    kept.push((recorded, change));
+                }
+            }
+            *log = kept;
+        }
+        Ok(merged)
+    }
+}
+
+/// An operation undoing a part of a change.
+enum Op {
+    /// Deletes a range of a sequence.
+    Delete(u32, u32),
+    /// Restores deleted text at a given index.
+    Text(u32, String),
+    /// Restores a deleted embedded value of a text at a given index.
+    Embed(u32, Any),
+    /// Restores deleted elements of an array at a given index.
+    Values(u32, Vec<Any>),
+    Remove(String),
+    Set(String, Any),
+}
+
+/// Numbers of item units affected by reverting a change.
+#[derive(Default)]
+struct Counts {
+    removed: u32,
+    restored: u32,
+    skipped: u32,
+}
+
+/// Collects operations undoing a change. Items are read from an encoded state of the document,
+/// which keeps deleted content, while their order within sequences is given by `sequence_ids`.
+struct Inverse<'a> {
+    change: &'a Change,
+    /// Encoded state of the document.
+    state: &'a [u8],
+    items: &'a [Item<'a>],
+    /// Structs of the items, at the same indexes.
+    blocks: &'a [&'a Block<'a>],
+    /// Indexes of items of every client, in order of their clocks.
+    clients: HashMap<u64, Vec<usize>>,
+    deletes: yrs::DeleteSet,
+    ops: Vec<(BranchPtr, bool, Vec<Op>)>,
+    counts: Counts,
+}
+
+impl<'a> Inverse<'a> {
+    fn inserted(&self, id: ID) -> bool {
+        is_deleted(&self.change.inserted, id.client, id.clock as u64, 1)
+    }
+
+    /// Checks if a unit of an item has been deleted by the change, but not inserted by it.
+    fn restorable(&self, id: ID) -> bool {
+        is_deleted(&self.change.deleted, id.client, id.clock as u64, 1) && !self.inserted(id)
+    }
+
+    /// Returns an index of the item containing a given unit.
+    fn find(&self, id: ID) -> Option<usize> {
+        let clock = id.clock as u64;
+        let indexes = self.clients.get(&id.client)?;
+        let next = indexes.partition_point(|i| self.items[*i].clock <= clock);
+        let i = indexes[next.checked_sub(1)?];
+        Some(i).filter(|i| clock < self.items[*i].clock + self.items[*i].len)
+    }
+
+    /// Returns a value of a map entry, unless it's a shared type or its content has been garbage
+    /// collected.
+    fn entry_value(&self, i: usize) -> Option<Any> {
+        match self.blocks[i].units().pop() {
+            Some(Unit::Value(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Appends an operation, merging it with the last operation when possible.
+    fn push(ops: &mut Vec<Op>, op: Op) {
+        let op = match (ops.last_mut(), op) {
+            (Some(Op::Delete(start, len)), Op::Delete(index, n)) if *start + *len == index => {
+                *len += n;
+                return;
+            }
+            (Some(Op::Text(at, text)), Op::Text(index, more)) if *at == index => {
+                text.push_str(&more);
+                return;
+            }
+            (Some(Op::Values(at, values)), Op::Values(index, more)) if *at == index => {
+                values.extend(more);
+                return;
+            }
+            (_, op) => op,
+        };
+        ops.push(op)
+    }
+
+    /// Collects operations undoing the change within a shared type contained by a given `owner`
+    /// and its nested shared types.
+    fn walk(&mut self, owner: Owner, branch: BranchPtr) -> PyResult<()> {
+        let mut ops = Vec::new();
+        let is_text = matches!(branch.type_ref(), TYPE_REFS_TEXT | TYPE_REFS_XML_TEXT);
+        let kind = match is_text {
+            true => current_offset_kind(&Text::from(branch)),
+            false => OffsetKind::Utf32,
+        };
+        let mut visible = Vec::new();
+        let mut index = 0;
+        let mut units: Option<(usize, Vec<Unit>)> = None;
+        for (start, len) in sequence_ids(self.state, self.items, owner)? {
+            for clock in start.clock..start.clock + len {
+                let id = ID::new(start.client, clock);
+                let i = match self.find(id) {
+                    Some(i) => i,
+                    None => continue,
+                };
+                if units.as_ref().map(|(j, _)| *j) != Some(i) {
+                    units = Some((i, self.blocks[i].units()));
+                }
+                let offset = (clock as u64 - self.items[i].clock) as usize;
+                let unit = match units.as_ref().and_then(|(_, units)| units.get(offset)) {
+                    Some(Unit::Surrogate) | None => continue,
+                    Some(unit) => unit,
+                };
+                if !self.deletes.is_deleted(&id) {
+                    visible.push(id);
+                    let len = match unit {
+                        Unit::Char(c) => char_len(*c, &kind),
+                        _ => 1,
+                    };
+                    if self.inserted(id) {
+                        Self::push(&mut ops, Op::Delete(index, len));
+                        self.counts.removed += 1;
+                    }
+                    index += len;
+                    continue;
+                }
+                if !self.restorable(id) {
+                    continue;
+                }
+                let op = match unit {
+                    Unit::Char(c) => Op::Text(index, c.to_string()),
+                    Unit::Value(value) if is_text => Op::Embed(index, value.clone()),
+                    Unit::Value(value) => Op::Values(index, vec![value.clone()]),
+                    _ => {
+                        // Nested shared types and garbage collected content can't be restored
+                        self.counts.skipped += 1;
+                        continue;
+                    }
+                };
+                Self::push(&mut ops, op);
+                self.counts.restored += 1;
+            }
+        }
+
+        let mut entries: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (i, item) in self.items.iter().enumerate() {
+            match item.key {
+                Some(key) if item.owner == Some(owner) => entries.entry(key).or_default().push(i),
+                _ => {}
+            }
+        }
+        let mut entry_ids = HashMap::new();
+        for (key, entries) in entries {
+            let id = |i: usize| ID::new(self.items[i].client, self.items[i].clock as u32);
+            let current = entries.iter().find(|i| !self.deletes.is_deleted(&id(**i)));
+            // An entry deleted by the change holds the value the key had before it
+            let previous = entries.iter().rev().find(|i| self.restorable(id(**i)));
+            match (current, previous) {
+                (Some(&i), previous) if self.inserted(id(i)) => {
+                    match previous.and_then(|j| self.entry_value(*j)) {
+                        Some(value) => ops.push(Op::Set(key.to_string(), value)),
+                        None => ops.push(Op::Remove(key.to_string())),
+                    }
+                    self.counts.removed += 1;
+                }
+                (Some(&i), _) => {
+                    entry_ids.insert(key, id(i));
+                }
+                (None, Some(&j)) => match self.entry_value(j) {
+                    Some(value) => {
+                        ops.push(Op::Set(key.to_string(), value));
+                        self.counts.restored += 1;
+                    }
+                    None => self.counts.skipped += 1,
+                },
+                (None, None) => {}
+            }
+        }
+
+        if !ops.is_empty() {
+            self.ops.push((branch, is_text, ops));
+        }
+        for (segment, inner) in nested_branches(branch) {
+            let id = match segment {
+                PathSegment::Index(i) => visible.get(i as usize).copied(),
+                PathSegment::Key(key) => entry_ids.get(key.as_ref()).copied(),
+            };
+            if let Some(id) = id {
+                self.walk(Owner::Type(id.client, id.clock as u64), inner)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reverts a change of a document where possible, by deleting items inserted by the change and
+/// restoring copies of content deleted by it. Returns a dictionary with numbers of `removed`,
+/// `restored` and `skipped` units of content. Root types which haven't been obtained with
+/// `YDoc.get_*` are left out.
+pub(crate) fn revert(
+    py: Python,
+    doc: &Doc,
+    txn: &mut YTransaction,
+    change: Change,
+) -> PyResult<PyObject> {
+    let state = txn.encode_diff_v1(&yrs::StateVector::default());
+    let structs = read_structs(&mut Reader::new(&state))?;
+    let (items, _) = read_items(&state)?;
+    // Items are read from the same state, so they follow structs other than skipped ranges
+    let blocks: Vec<&Block> = structs
+        .iter()
+        .flat_map(|(_, _, blocks)| blocks.iter().filter(|block| !block.is_skip()))
+        .collect();
+    let mut clients: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, item) in items.iter().enumerate() {
+        clients.entry(item.client).or_default().push(i);
+    }
+    let mut inverse = Inverse {
+        change: &change,
+        state: &state,
+        items: &items,
+        blocks: &blocks,
+        clients,
+        deletes: txn.snapshot().delete_set,
+        ops: Vec::new(),
+        counts: Counts::default(),
+    };
+    for (name, root) in root_branches(doc) {
+        inverse.walk(Owner::Root(&name), root)?;
+    }
+
+    let txn: &mut Transaction = txn;
+    for (branch, is_text, ops) in inverse.ops {
+        // Operations are applied from the end of a sequence, so that their indexes stay valid
+        for op in ops.into_iter().rev() {
+            match op {
+                Op::Delete(index, len) if is_text => {
+                    Text::from(branch).remove_range(txn, index, len);
+                }
+                Op::Delete(index, len) => {
+                    Array::from(branch).remove_range(txn, index, len);
+                }
+                Op::Text(index, text) => {
+                    Text::from(branch).insert(txn, index, &text);
+                }
+                Op::Embed(index, value) => {
+                    Text::from(branch).insert_embed(txn, index, value);
+                }
+                Op::Values(index, values) => {
+                    let array = Array::from(branch);
+                    for (i, value) in values.into_iter().enumerate() {
+                        let value = PyObjectWrapper(value.into_py(py));
+                        array.insert(txn, index + i as u32, value);
+                        take_integration_error()?;
+                    }
+                }
+                Op::Remove(key) => {
+                    Map::from(branch).remove(txn, &key);
+                }
+                Op::Set(key, value) => {
+                    Map::from(branch).insert(txn, key, PyObjectWrapper(value.into_py(py)));
+                    take_integration_error()?;
+                }
+            }
+        }
+    }
+
+    let counts = &inverse.counts;
+    let result = PyDict::new(py);
+    result.set_item("removed", counts.removed)?;
+    result.set_item("restored", counts.restored)?;
+    result.set_item("skipped", counts.skipped)?;
+    Ok(result.into())
+}
@@ -0,0 +1,195 @@
+use crate::shared_types::root_branches;
+use crate::snapshot::{is_deleted, read_items, Owner};
+use crate::y_array::YArray;
+use crate::y_doc::{diff, YDoc};
+use crate::y_map::YMap;
+use crate::y_text::YText;
+use crate::y_xml::{YXmlElement, YXmlText};
+use pyo3::exceptions::{PyAttributeError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyType};
+use pyo3::AsPyPointer;
+use std::collections::{BTreeMap, HashMap};
+use yrs::types::{
+    TypeRefs, TYPE_REFS_ARRAY, TYPE_REFS_MAP, TYPE_REFS_TEXT, TYPE_REFS_XML_ELEMENT,
+    TYPE_REFS_XML_TEXT,
+};
+use yrs::{Doc, StateVector};
+
+/// A proxy returned by `YDoc.root`, which gives access to root types of a document as its
+/// attributes, eg. `doc.root.tasks` in place of `doc.get_array('tasks')`. Root types which aren't
+/// valid Python identifiers can be accessed with `doc.root['my-tasks']`.
+///
+/// The type of a root type is resolved in the following order:
+///
+/// - a type declared by calling the proxy, eg. `doc.root(tasks=YArray)`, which returns a new
+///   proxy with the declarations,
+/// - the type of an existing root type,
+/// - a type inferred from contents of a root type received from a remote peer, which is a `YMap`
+///   if it has any entries, a `YText` if it contains a string and a `YArray` otherwise. XML root
+///   types can't be told apart from these, so they have to be declared,
+/// - a type declared by a JSON Schema attached with `YDoc.set_schema`, whose `type` is either
+///   "array", "object" or "string".
+///
+/// Missing root types are created. Accessing a root type whose type can't be resolved raises an
+/// `AttributeError`, and accessing one as a type different from its actual type a `TypeError`.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YArray, YDoc
+///
+/// doc = YDoc()
+/// root = doc.root(tasks=YArray)
+/// with doc.begin_transaction() as txn:
+///     root.tasks.append(txn, 'Write docs')
+/// assert list(doc.root.tasks) == ['Write docs']
+/// ```
+#[pyclass(unsendable)]
+pub struct RootProxy {
+    doc: Py<YDoc>,
+    declared: HashMap<String, TypeRefs>,
+}
+
+impl RootProxy {
+    pub(crate) fn new(doc: &PyCell<YDoc>) -> Self {
+        RootProxy {
+            doc: doc.into(),
+            declared: HashMap::new(),
+        }
+    }
+
+    /// Resolves the type of a root type `name`, if possible.
+    fn type_of(&self, py: Python, name: &str) -> PyResult<Option<TypeRefs>> {
+        if let Some(type_ref) = self.declared.get(name) {
+            return Ok(Some(*type_ref));
+        }
+        let doc = self.doc.borrow(py);
+        let existing = root_branches(&doc.inner)
+            .into_iter()
+            .find(|(root, _)| root.as_ref() == name)
+            .map(|(_, branch)| branch.type_ref());
+        let existing = match existing {
+            Some(type_ref) => Some(type_ref),
+            None => remote_roots(&doc.inner)?.remove(name).flatten(),
+        };
+        Ok(existing.or_else(|| doc.schemas.root_type(name)))
+    }
+
+    fn get(&self, py: Python, name: &str) -> PyResult<PyObject> {
+        let method = match self.type_of(py, name)? {
+            Some(TYPE_REFS_TEXT) => "get_text",
+            Some(TYPE_REFS_ARRAY) => "get_array",
+            Some(TYPE_REFS_MAP) => "get_map",
+            Some(TYPE_REFS_XML_ELEMENT) => "get_xml_element",
+            Some(TYPE_REFS_XML_TEXT) => "get_xml_text",
+            _ => {
+                return Err(PyAttributeError::new_err(format!(
+                    "Type of root type '{name}' can't be inferred, it has to be declared, eg. doc.root({name}=YMap)"
+                )))
+            }
+        };
+        self.doc.call_method1(py, method, (name,))
+    }
+}
+
+#[pymethods]
+impl RootProxy {
+    /// Returns a new proxy with types of root types declared by keyword arguments, eg.
+    /// `doc.root(tasks=YArray, title=YText)`. Declarations take precedence over inferred types.
+    #[args(types = "**")]
+    pub fn __call__(&self, py: Python, types: Option<&PyDict>) -> PyResult<RootProxy> {
+        let mut declared = self.declared.clone();
+        if let Some(types) = types {
+            for (name, class) in types.iter() {
+                let name: String = name.extract()?;
+                let type_ref = class
+                    .downcast::<PyType>()
+                    .ok()
+                    .and_then(|class| type_ref_of(py, class));
+                match type_ref {
+                    Some(type_ref) => declared.insert(name, type_ref),
+                    None => {
+                        return Err(PyTypeError::new_err(format!(
+                            "Type of root type '{name}' must be one of YText, YArray, YMap, YXmlElement or YXmlText, found {class}"
+                        )))
+                    }
+                };
+            }
+        }
+        Ok(RootProxy {
+            doc: self.doc.clone_ref(py),
+            declared,
+        })
+    }
+
+    fn __getattr__(&self, py: Python, name: &str) -> PyResult<PyObject> {
+        // Private and special attributes are looked up by Python itself, eg. by `copy` or IPython
+        if name.starts_with('_') {
+            return Err(PyAttributeError::new_err(format!(
+                "'RootProxy' object has no attribute '{name}'"
+            )));
+        }
+        self.get(py, name)
+    }
+
+    fn __getitem__(&self, py: Python, name: &str) -> PyResult<PyObject> {
+        self.get(py, name)
+    }
+
+    /// Lists names of existing and declared root types, so that they can be completed in
+    /// interactive sessions.
+    fn __dir__(&self, py: Python) -> PyResult<Vec<String>> {
+        let doc = self.doc.borrow(py);
+        let mut names: Vec<String> = root_branches(&doc.inner)
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        names.extend(remote_roots(&doc.inner)?.into_keys());
+        names.extend(self.declared.keys().cloned());
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+}
+
+/// Returns the type of root types corresponding to a Ypy shared type class.
+fn type_ref_of(py: Python, class: &PyType) -> Option<TypeRefs> {
+    let is = |other: &PyType| class.as_ptr() == other.as_ptr();
+    if is(py.get_type::<YText>()) {
+        Some(TYPE_REFS_TEXT)
+    } else if is(py.get_type::<YArray>()) {
+        Some(TYPE_REFS_ARRAY)
+    } else if is(py.get_type::<YMap>()) {
+        Some(TYPE_REFS_MAP)
+    } else if is(py.get_type::<YXmlElement>()) {
+        Some(TYPE_REFS_XML_ELEMENT)
+    } else if is(py.get_type::<YXmlText>()) {
+        Some(TYPE_REFS_XML_TEXT)
+    } else {
+        None
+    }
+}
+
+/// Infers types of root types from contents of the document state, for root types which have
+/// only been received from remote peers and so have no type yet. Root types whose contents have
+/// all been deleted can't be inferred.
+fn remote_roots(doc: &Doc) -> PyResult<BTreeMap<String, Option<TypeRefs>>> {
+    let state = diff(doc, &StateVector::default());
+    let (items, deletes) = read_items(&state)?;
+    let mut roots = BTreeMap::new();
+    for item in items.iter() {
+        if let Some(Owner::Root(name)) = item.owner {
+            let type_ref = roots.entry(name.to_string()).or_insert(None);
+            if item.key.is_some() {
+                *type_ref = Some(TYPE_REFS_MAP);
+            } else if item.is_text && *type_ref != Some(TYPE_REFS_MAP) {
+                *type_ref = Some(TYPE_REFS_TEXT);
+            } else if type_ref.is_none() && !is_deleted(&deletes, item.client, item.clock, item.len)
+            {
+                *type_ref = Some(TYPE_REFS_ARRAY);
+            }
+        }
+    }
+    Ok(roots)
+}
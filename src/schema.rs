@@ -0,0 +1,682 @@
+use crate::y_array::YArray;
+use crate::y_doc::YDoc;
+use crate::y_map::YMap;
+use crate::y_text::YText;
+use crate::y_transaction::YTransaction;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyAttributeError, PyKeyError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyList, PyString, PyTuple, PyType};
+
+create_exception!(
+    y_py,
+    SchemaError,
+    PyTypeError,
+    "Occurs when a value inserted through a schema accessor doesn't match the declared schema."
+);
+
+/// Declared shape of a single value stored in a document.
+#[derive(Clone)]
+enum Node {
+    /// A primitive value, which must be an instance of one of the given types.
+    Field { types: Py<PyTuple>, optional: bool },
+    /// A `YText` shared type.
+    Text,
+    /// A `YArray` shared type with all of its items matching the inner schema.
+    Array(Box<Node>),
+    /// A `YMap` shared type with a fixed set of fields.
+    Map(Vec<(String, Node)>),
+}
+
+impl Node {
+    /// Reads a schema node declared in Python. Bare types and tuples of types are shorthands for
+    /// required `Field` declarations.
+    fn from_py(value: &PyAny) -> PyResult<Node> {
+        if let Ok(field) = value.extract::<PyRef<FieldSchema>>() {
+            Ok(field.0.clone())
+        } else if value.extract::<PyRef<TextSchema>>().is_ok() {
+            Ok(Node::Text)
+        } else if let Ok(array) = value.extract::<PyRef<ArraySchema>>() {
+            Ok(array.0.clone())
+        } else if let Ok(map) = value.extract::<PyRef<MapSchema>>() {
+            Ok(map.0.clone())
+        } else if value.downcast::<PyType>().is_ok() || value.downcast::<PyTuple>().is_ok() {
+            Node::field(value, false)
+        } else {
+            Err(PyTypeError::new_err(format!(
+                "Expected a schema declaration or a type, found {}",
+                value.get_type().name()?
+            )))
+        }
+    }
+
+    fn field(types: &PyAny, optional: bool) -> PyResult<Node> {
+        let py = types.py();
+        let types = match types.downcast::<PyTuple>() {
+            Ok(tuple) => tuple,
+            Err(_) => PyTuple::new(py, [types]),
+        };
+        if types.is_empty() || types.iter().any(|t| t.downcast::<PyType>().is_err()) {
+            return Err(PyTypeError::new_err(
+                "Field expects a type or a non-empty tuple of types",
+            ));
+        }
+        Ok(Node::Field {
+            types: types.into(),
+            optional,
+        })
+    }
+
+    fn fields(fields: &PyDict) -> PyResult<Vec<(String, Node)>> {
+        let mut entries = Vec::with_capacity(fields.len());
+        for (name, node) in fields.iter() {
+            let name: String = name
+                .extract()
+                .map_err(|_| PyTypeError::new_err("Map field names must be strings"))?;
+            entries.push((name, Node::from_py(node)?));
+        }
+        Ok(entries)
+    }
+
+    /// Returns a human readable description of values accepted by this node.
+    fn describe(&self, py: Python) -> PyResult<String> {
+        Ok(match self {
+            Node::Field { types, optional } => {
+                let mut names = type_names(types.as_ref(py))?;
+                if *optional {
+                    names.push("None".to_string());
+                }
+                names.join(" or ")
+            }
+            Node::Text => "str".to_string(),
+            Node::Array(_) => "list".to_string(),
+            Node::Map(_) => "dict".to_string(),
+        })
+    }
+
+    fn repr(&self, py: Python) -> PyResult<String> {
+        Ok(match self {
+            Node::Field { types, optional } => {
+                let names = type_names(types.as_ref(py))?;
+                let types = if names.len() == 1 {
+                    names[0].clone()
+                } else {
+                    format!("({},)", names.join(", "))
+                };
+                if *optional {
+                    format!("Field({types}, optional=True)")
+                } else {
+                    format!("Field({types})")
+                }
+            }
+            Node::Text => "Text()".to_string(),
+            Node::Array(items) => format!("Array({})", items.repr(py)?),
+            Node::Map(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, node)| Ok(format!("'{name}': {}", node.repr(py)?)))
+                    .collect::<PyResult<Vec<_>>>()?;
+                format!("Map({{{}}})", fields.join(", "))
+            }
+        })
+    }
+
+    /// Validates a plain Python `value` against this node and converts it into a value ready to
+    /// be inserted into a document: texts, lists and dictionaries become preliminary `YText`,
+    /// `YArray` and `YMap` instances. `path` locates the value in error messages.
+    fn convert(&self, py: Python, value: &PyAny, path: &str) -> PyResult<PyObject> {
+        match self {
+            Node::Field { types, optional } => {
+                let accepted = if value.is_none() {
+                    *optional
+                } else {
+                    is_instance(value, types.as_ref(py))?
+                };
+                if accepted {
+                    Ok(value.into())
+                } else {
+                    Err(mismatch(py, self, value, path))
+                }
+            }
+            Node::Text => match value.downcast::<PyString>() {
+                Ok(text) => Ok(YText::new(Some(text.to_str()?.to_string())).into_py(py)),
+                Err(_) => Err(mismatch(py, self, value, path)),
+            },
+            Node::Array(items) => {
+                if value.downcast::<PyList>().is_err() && value.downcast::<PyTuple>().is_err() {
+                    return Err(mismatch(py, self, value, path));
+                }
+                let converted = value
+                    .iter()?
+                    .enumerate()
+                    .map(|(i, item)| items.convert(py, item?, &format!("{path}[{i}]")))
+                    .collect::<PyResult<Vec<_>>>()?;
+                Ok(YArray::new(Some(converted.into_py(py)))?.into_py(py))
+            }
+            Node::Map(fields) => {
+                let dict = match value.downcast::<PyDict>() {
+                    Ok(dict) => dict,
+                    Err(_) => return Err(mismatch(py, self, value, path)),
+                };
+                check_known_fields(fields, dict, path)?;
+                let converted = PyDict::new(py);
+                for (name, node) in fields.iter() {
+                    let field_path = join_path(path, name);
+                    match dict.get_item(name.as_str()) {
+                        Some(item) => {
+                            converted.set_item(name, node.convert(py, item, &field_path)?)?
+                        }
+                        None => match node.empty(py) {
+                            Some(empty) => {
+                                let item = node.convert(py, empty.as_ref(py), &field_path)?;
+                                converted.set_item(name, item)?
+                            }
+                            None if node.is_optional() => {}
+                            None => {
+                                let message = format!("{field_path}: missing required field");
+                                return Err(SchemaError::new_err(message));
+                            }
+                        },
+                    }
+                }
+                Ok(YMap::new(converted)?.into_py(py))
+            }
+        }
+    }
+
+    /// Returns a plain value used to initialize a field missing from an inserted dictionary.
+    /// Primitive fields have no default.
+    fn empty(&self, py: Python) -> Option<PyObject> {
+        match self {
+            Node::Field { .. } => None,
+            Node::Text => Some(PyString::new(py, "").into()),
+            Node::Array(_) => Some(PyList::empty(py).into()),
+            Node::Map(_) => Some(PyDict::new(py).into()),
+        }
+    }
+
+    fn is_optional(&self) -> bool {
+        matches!(self, Node::Field { optional: true, .. })
+    }
+
+    /// Wraps a value read from a document into a schema accessor. Values which don't have the
+    /// declared shape (eg. written by a peer unaware of the schema) are returned as they are.
+    fn wrap(&self, py: Python, value: PyObject) -> PyResult<PyObject> {
+        match self {
+            Node::Array(items) => match value.extract::<Py<YArray>>(py) {
+                Ok(shared) => Ok(ArrayAccessor {
+                    shared,
+                    items: (**items).clone(),
+                }
+                .into_py(py)),
+                Err(_) => Ok(value),
+            },
+            Node::Map(fields) => match value.extract::<Py<YMap>>(py) {
+                Ok(shared) => Ok(MapAccessor {
+                    shared,
+                    fields: fields.clone(),
+                }
+                .into_py(py)),
+                Err(_) => Ok(value),
+            },
+            _ => Ok(value),
+        }
+    }
+}
+
+fn type_names(types: &PyTuple) -> PyResult<Vec<String>> {
+    types
+        .iter()
+        .map(|t| Ok(t.downcast::<PyType>()?.name()?.to_string()))
+        .collect()
+}
+
+/// Checks if a `value` is an instance of any of given `types`. Since `bool` is a subclass of `int`,
+/// booleans only match when `bool` is declared explicitly.
+fn is_instance(value: &PyAny, types: &PyTuple) -> PyResult<bool> {
+    let py = value.py();
+    let is_bool = value.downcast::<PyBool>().is_ok();
+    for t in types.iter() {
+        let t = t.downcast::<PyType>()?;
+        if is_bool && !t.is(py.get_type::<PyBool>()) {
+            continue;
+        }
+        if value.is_instance(t)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{path}.{name}")
+    }
+}
+
+fn mismatch(py: Python, node: &Node, value: &PyAny, path: &str) -> PyErr {
+    let expected = match node.describe(py) {
+        Ok(expected) => expected,
+        Err(err) => return err,
+    };
+    let found = value
+        .get_type()
+        .name()
+        .map(str::to_string)
+        .unwrap_or_default();
+    let message = format!("expected {expected}, found {found}");
+    if path.is_empty() {
+        SchemaError::new_err(message)
+    } else {
+        SchemaError::new_err(format!("{path}: {message}"))
+    }
+}
+
+fn find_field<'a>(fields: &'a [(String, Node)], name: &str) -> Option<&'a Node> {
+    fields
+        .iter()
+        .find(|(field, _)| field == name)
+        .map(|(_, node)| node)
+}
+
+fn check_known_fields(fields: &[(String, Node)], dict: &PyDict, path: &str) -> PyResult<()> {
+    for key in dict.keys() {
+        let known = match key.extract::<&str>() {
+            Ok(key) => find_field(fields, key).is_some(),
+            Err(_) => false,
+        };
+        if !known {
+            let message = format!("unexpected field {}", key.repr()?);
+            return Err(SchemaError::new_err(if path.is_empty() {
+                message
+            } else {
+                format!("{path}: {message}")
+            }));
+        }
+    }
+    Ok(())
+}
+
+/// Declares a primitive field holding instances of given `types` (a type or a tuple of types).
+/// Optional fields also accept `None` and can be omitted when inserting a map.
+#[pyclass(name = "Field", module = "y_py.schema")]
+pub struct FieldSchema(Node);
+
+#[pymethods]
+impl FieldSchema {
+    #[new]
+    pub fn new(types: &PyAny, optional: Option<bool>) -> PyResult<Self> {
+        Node::field(types, optional.unwrap_or(false)).map(FieldSchema)
+    }
+
+    /// Raises a `SchemaError` if a given value doesn't match this declaration.
+    pub fn validate(&self, value: &PyAny) -> PyResult<()> {
+        self.0.convert(value.py(), value, "").map(|_| ())
+    }
+
+    pub fn __repr__(&self) -> PyResult<String> {
+        Python::with_gil(|py| self.0.repr(py))
+    }
+}
+
+/// Declares a `YText` field. It's inserted from a `str`.
+#[pyclass(name = "Text", module = "y_py.schema")]
+pub struct TextSchema;
+
+#[pymethods]
+impl TextSchema {
+    #[new]
+    pub fn new() -> Self {
+        TextSchema
+    }
+
+    /// Raises a `SchemaError` if a given value doesn't match this declaration.
+    pub fn validate(&self, value: &PyAny) -> PyResult<()> {
+        Node::Text.convert(value.py(), value, "").map(|_| ())
+    }
+
+    pub fn __repr__(&self) -> String {
+        "Text()".to_string()
+    }
+}
+
+/// Declares a `YArray` field with all of its elements matching the `items` declaration. It's
+/// inserted from a `list` or a `tuple`.
+#[pyclass(name = "Array", module = "y_py.schema")]
+pub struct ArraySchema(Node);
+
+#[pymethods]
+impl ArraySchema {
+    #[new]
+    pub fn new(items: &PyAny) -> PyResult<Self> {
+        Ok(ArraySchema(Node::Array(Box::new(Node::from_py(items)?))))
+    }
+
+    /// Raises a `SchemaError` if a given value doesn't match this declaration.
+    pub fn validate(&self, value: &PyAny) -> PyResult<()> {
+        self.0.convert(value.py(), value, "").map(|_| ())
+    }
+
+    pub fn __repr__(&self) -> PyResult<String> {
+        Python::with_gil(|py| self.0.repr(py))
+    }
+}
+
+/// Declares a `YMap` field with a fixed set of `fields`, given as a dictionary of field names and
+/// their declarations. It's inserted from a `dict`. Unknown fields are rejected, while missing
+/// texts, arrays and maps are initialized empty.
+#[pyclass(name = "Map", module = "y_py.schema")]
+pub struct MapSchema(Node);
+
+#[pymethods]
+impl MapSchema {
+    #[new]
+    pub fn new(fields: &PyDict) -> PyResult<Self> {
+        Ok(MapSchema(Node::Map(Node::fields(fields)?)))
+    }
+
+    /// Raises a `SchemaError` if a given value doesn't match this declaration.
+    pub fn validate(&self, value: &PyAny) -> PyResult<()> {
+        self.0.convert(value.py(), value, "").map(|_| ())
+    }
+
+    pub fn __repr__(&self) -> PyResult<String> {
+        Python::with_gil(|py| self.0.repr(py))
+    }
+}
+
+/// Declares root types of a document, given as a dictionary of root names and their `Map`,
+/// `Array` or `Text` declarations.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YDoc, schema
+///
+/// Todo = schema.Map({"title": schema.Text(), "done": bool})
+/// Model = schema.Model({"todos": schema.Array(Todo)})
+///
+/// doc = YDoc()
+/// model = Model.bind(doc)
+/// with doc.begin_transaction() as txn:
+///     model.todos.append(txn, {"title": "write docs", "done": False})
+/// print(str(model.todos[0].title))
+/// ```
+#[pyclass(name = "Model", module = "y_py.schema")]
+pub struct Model(Vec<(String, Node)>);
+
+#[pymethods]
+impl Model {
+    #[new]
+    pub fn new(roots: &PyDict) -> PyResult<Self> {
+        let roots = Node::fields(roots)?;
+        for (name, node) in roots.iter() {
+            if let Node::Field { .. } = node {
+                return Err(PyTypeError::new_err(format!(
+                    "Root type '{name}' must be declared as a Map, an Array or a Text"
+                )));
+            }
+        }
+        Ok(Model(roots))
+    }
+
+    /// Returns a `Document` giving access to root types of a given `YDoc` through this model.
+    pub fn bind(&self, doc: &PyCell<YDoc>) -> PyResult<Document> {
+        let py = doc.py();
+        let mut roots = Vec::with_capacity(self.0.len());
+        for (name, node) in self.0.iter() {
+            let root = match node {
+                Node::Text => YDoc::get_text(doc, name)?.into_py(py),
+                Node::Array(_) => node.wrap(py, YDoc::get_array(doc, name)?.into_py(py))?,
+                Node::Map(_) => node.wrap(py, YDoc::get_map(doc, name)?.into_py(py))?,
+                Node::Field { .. } => unreachable!(),
+            };
+            roots.push((name.clone(), root));
+        }
+        Ok(Document {
+            doc: doc.into(),
+            roots,
+        })
+    }
+
+    pub fn __repr__(&self) -> PyResult<String> {
+        Python::with_gil(|py| {
+            let roots = self
+                .0
+                .iter()
+                .map(|(name, node)| Ok(format!("'{name}': {}", node.repr(py)?)))
+                .collect::<PyResult<Vec<_>>>()?;
+            Ok(format!("Model({{{}}})", roots.join(", ")))
+        })
+    }
+}
+
+/// Root types of a `YDoc` bound to a `Model`, available as attributes or items. Texts are returned
+/// as `YText` instances, while arrays and maps are wrapped with validating accessors.
+#[pyclass(module = "y_py.schema")]
+pub struct Document {
+    doc: PyObject,
+    roots: Vec<(String, PyObject)>,
+}
+
+#[pymethods]
+impl Document {
+    /// The `YDoc` this model is bound to.
+    #[getter]
+    pub fn doc(&self) -> PyObject {
+        self.doc.clone()
+    }
+
+    pub fn __getattr__(&self, name: &str) -> PyResult<PyObject> {
+        self.root(name)
+            .ok_or_else(|| PyAttributeError::new_err(format!("Unknown root type '{name}'")))
+    }
+
+    pub fn __getitem__(&self, name: &str) -> PyResult<PyObject> {
+        self.root(name)
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+}
+
+impl Document {
+    fn root(&self, name: &str) -> Option<PyObject> {
+        self.roots
+            .iter()
+            .find(|(root, _)| root == name)
+            .map(|(_, value)| value.clone())
+    }
+}
+
+/// A `YMap` accessed through a `Map` declaration. Fields are available as attributes (or with
+/// `get`), and writes are validated against the declaration before being applied.
+#[pyclass(module = "y_py.schema")]
+pub struct MapAccessor {
+    shared: Py<YMap>,
+    fields: Vec<(String, Node)>,
+}
+
+#[pymethods]
+impl MapAccessor {
+    /// The underlying `YMap`.
+    #[getter]
+    pub fn shared(&self) -> Py<YMap> {
+        self.shared.clone()
+    }
+
+    /// Returns a value of a declared field, or `None` if it's not set.
+    pub fn get(&self, name: &str) -> PyResult<PyObject> {
+        let node = self.field(name)?;
+        Python::with_gil(|py| {
+            let value = self.shared.borrow(py).get(name, None);
+            node.wrap(py, value)
+        })
+    }
+
+    pub fn __getattr__(&self, name: &str) -> PyResult<PyObject> {
+        match find_field(&self.fields, name) {
+            Some(_) => self.get(name),
+            None => Err(PyAttributeError::new_err(format!("Unknown field '{name}'"))),
+        }
+    }
+
+    /// Validates a `value` against the declaration of field `name` and sets it.
+    pub fn set(&self, txn: &mut YTransaction, name: &str, value: &PyAny) -> PyResult<()> {
+        let py = value.py();
+        let value = self.field(name)?.convert(py, value, name)?;
+        self.shared.borrow_mut(py).set(txn, name, value)?;
+        Ok(())
+    }
+
+    /// Validates all entries of `values` and sets them. Nothing is written if any of the entries
+    /// doesn't match its declaration.
+    pub fn update(&self, txn: &mut YTransaction, values: &PyDict) -> PyResult<()> {
+        let py = values.py();
+        check_known_fields(&self.fields, values, "")?;
+        let mut converted = Vec::with_capacity(values.len());
+        for (name, value) in values.iter() {
+            let name: &str = name.extract()?;
+            let node = self.field(name)?;
+            converted.push((name, node.convert(py, value, name)?));
+        }
+        let mut shared = self.shared.borrow_mut(py);
+        for (name, value) in converted {
+            shared.set(txn, name, value)?;
+        }
+        Ok(())
+    }
+
+    /// Converts the underlying `YMap` into a dictionary, including nested shared types.
+    pub fn to_dict(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| self.shared.borrow(py).to_dict(Some(true), None))
+    }
+
+    pub fn __repr__(&self) -> PyResult<String> {
+        Python::with_gil(|py| {
+            let fields = self.fields.iter().map(|(name, _)| name.as_str());
+            Ok(format!(
+                "MapAccessor({}) {}",
+                fields.collect::<Vec<_>>().join(", "),
+                self.shared.borrow(py).__str__()
+            ))
+        })
+    }
+}
+
+impl MapAccessor {
+    fn field(&self, name: &str) -> PyResult<&Node> {
+        find_field(&self.fields, name)
+            .ok_or_else(|| SchemaError::new_err(format!("unexpected field '{name}'")))
+    }
+}
+
+/// A `YArray` accessed through an `Array` declaration. Elements are wrapped according to the
+/// declaration, and inserted values are validated before being applied.
+#[pyclass(module = "y_py.schema")]
+pub struct ArrayAccessor {
+    shared: Py<YArray>,
+    items: Node,
+}
+
+#[pymethods]
+impl ArrayAccessor {
+    /// The underlying `YArray`.
+    #[getter]
+    pub fn shared(&self) -> Py<YArray> {
+        self.shared.clone()
+    }
+
+    pub fn __len__(&self) -> usize {
+        Python::with_gil(|py| self.shared.borrow(py).__len__())
+    }
+
+    pub fn __getitem__(&self, index: isize) -> PyResult<PyObject> {
+        Python::with_gil(|py| self.item(py, index))
+    }
+
+    pub fn __iter__(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| {
+            let len = self.shared.borrow(py).__len__() as isize;
+            let items = (0..len)
+                .map(|i| self.item(py, i))
+                .collect::<PyResult<Vec<_>>>()?;
+            Ok(PyAny::iter(PyList::new(py, items))?.into_py(py))
+        })
+    }
+
+    /// Validates an `item` and adds it to the end of the array.
+    pub fn append(&self, txn: &mut YTransaction, item: &PyAny) -> PyResult<()> {
+        let py = item.py();
+        let mut shared = self.shared.borrow_mut(py);
+        let item = self
+            .items
+            .convert(py, item, &format!("[{}]", shared.__len__()))?;
+        shared.append(py, txn, item)?;
+        Ok(())
+    }
+
+    /// Validates an `item` and inserts it at a given `index`.
+    pub fn insert(&self, txn: &mut YTransaction, index: u32, item: &PyAny) -> PyResult<()> {
+        let py = item.py();
+        let item = self.items.convert(py, item, &format!("[{index}]"))?;
+        self.shared.borrow_mut(py).insert(py, txn, index, item)?;
+        Ok(())
+    }
+
+    /// Validates all `items` and appends them to the end of the array. Nothing is written if any
+    /// of the items doesn't match the declaration.
+    pub fn extend(&self, txn: &mut YTransaction, items: &PyAny) -> PyResult<()> {
+        let py = items.py();
+        let mut shared = self.shared.borrow_mut(py);
+        let start = shared.__len__();
+        let converted = items
+            .iter()?
+            .enumerate()
+            .map(|(i, item)| self.items.convert(py, item?, &format!("[{}]", start + i)))
+            .collect::<PyResult<Vec<_>>>()?;
+        shared.extend(py, txn, converted.into_py(py))?;
+        Ok(())
+    }
+
+    /// Removes the element at a given `index`.
+    pub fn delete(&self, txn: &mut YTransaction, index: u32) -> PyResult<()> {
+        Python::with_gil(|py| self.shared.borrow_mut(py).delete(txn, index))
+    }
+
+    /// Converts the underlying `YArray` into a list, including nested shared types.
+    pub fn to_list(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| self.shared.borrow(py).to_list(Some(true), None, None))
+    }
+
+    pub fn __repr__(&self) -> PyResult<String> {
+        Python::with_gil(|py| {
+            Ok(format!(
+                "ArrayAccessor({}) {}",
+                self.items.repr(py)?,
+                self.shared.borrow(py).__str__()
+            ))
+        })
+    }
+}
+
+impl ArrayAccessor {
+    fn item(&self, py: Python, index: isize) -> PyResult<PyObject> {
+        let value = self.shared.as_ref(py).get_item(index)?;
+        self.items.wrap(py, value.into())
+    }
+}
+
+/// Registers schema declarations and accessors in a `y_py.schema` submodule.
+pub(crate) fn register(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<FieldSchema>()?;
+    m.add_class::<TextSchema>()?;
+    m.add_class::<ArraySchema>()?;
+    m.add_class::<MapSchema>()?;
+    m.add_class::<Model>()?;
+    m.add_class::<Document>()?;
+    m.add_class::<MapAccessor>()?;
+    m.add_class::<ArrayAccessor>()?;
+    m.add("SchemaError", py.get_type::<SchemaError>())?;
+    Ok(())
+}
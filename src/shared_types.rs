@@ -1,20 +1,55 @@
 use crate::{
+    location::Location,
+    panics,
     y_array::YArray,
+    y_doc::YDoc,
     y_map::YMap,
     y_text::YText,
     y_xml::{YXmlElement, YXmlText},
 };
 use pyo3::create_exception;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError};
+use pyo3::pyclass::CompareOp;
 use pyo3::types as pytypes;
 use pyo3::{exceptions::PyException, prelude::*};
+use std::convert::TryFrom;
 use std::fmt::Display;
+use std::rc::Rc;
+use yrs::types::xml::Xml;
 use yrs::types::TYPE_REFS_XML_TEXT;
+use yrs::types::{Branch, BranchPtr, PathSegment, Value};
 use yrs::types::{TypeRefs, TYPE_REFS_ARRAY, TYPE_REFS_MAP, TYPE_REFS_TEXT};
 use yrs::{types::TYPE_REFS_XML_ELEMENT, SubscriptionId};
+use yrs::{Array, Doc, Map, XmlElement};
 
 // Common errors
-create_exception!(y_py, PreliminaryObservationException, PyException, "Occurs when an observer is attached to a Y type that is not integrated into a YDoc. Y types can only be observed once they have been added to a YDoc.");
-create_exception!(y_py, IntegratedOperationException, PyException, "Occurs when a method requires a type to be integrated (embedded into a YDoc), but is called on a preliminary type.");
+create_exception!(
+    y_py,
+    YError,
+    PyException,
+    "Base class of all errors raised by Ypy."
+);
+create_exception!(
+    y_py,
+    IntegrationError,
+    YError,
+    "Occurs when a Ypy data type cannot be integrated into a YDoc."
+);
+create_exception!(
+    y_py,
+    TransactionError,
+    YError,
+    "Occurs when a transaction is used in an invalid way, e.g. after it has been closed."
+);
+create_exception!(
+    y_py,
+    EncodingError,
+    YError,
+    "Occurs due to issues in the encoding/decoding process of y_py updates."
+);
+create_exception!(y_py, PreliminaryAccessError, YError, "Occurs when an operation requires a Ypy data type to be integrated into a YDoc, but it is still preliminary.");
+create_exception!(y_py, PreliminaryObservationException, PreliminaryAccessError, "Occurs when an observer is attached to a Y type that is not integrated into a YDoc. Y types can only be observed once they have been added to a YDoc.");
+create_exception!(y_py, IntegratedOperationException, PreliminaryAccessError, "Occurs when a method requires a type to be integrated (embedded into a YDoc), but is called on a preliminary type.");
 
 /// Creates a default error with a common message string for throwing a `PyErr`.
 pub(crate) trait DefaultPyErr {
@@ -25,7 +60,7 @@ pub(crate) trait DefaultPyErr {
 impl DefaultPyErr for PreliminaryObservationException {
     fn default_message() -> PyErr {
         PreliminaryObservationException::new_err(
-            "Cannot observe a preliminary type. Must be added to a YDoc first",
+            "Cannot observe a preliminary type. Must be added to a YDoc first, e.g. by inserting it into a root type obtained with `YDoc.get_map`.",
         )
     }
 }
@@ -33,7 +68,7 @@ impl DefaultPyErr for PreliminaryObservationException {
 impl DefaultPyErr for IntegratedOperationException {
     fn default_message() -> PyErr {
         IntegratedOperationException::new_err(
-            "This operation requires the type to be integrated into a YDoc.",
+            "This operation requires the type to be integrated into a YDoc. Insert it into a root type obtained with `YDoc.get_map` or `YDoc.get_array` first.",
         )
     }
 }
@@ -57,10 +92,13 @@ pub enum CompatiblePyType<'a> {
     Int(&'a pytypes::PyInt),
     Float(&'a pytypes::PyFloat),
     String(&'a pytypes::PyString),
+    Bytes(&'a pytypes::PyBytes),
+    Buffer(&'a PyAny),
     List(&'a pytypes::PyList),
     Dict(&'a pytypes::PyDict),
     YType(YPyType<'a>),
     None,
+    Undefined,
 }
 
 #[derive(Clone)]
@@ -80,6 +118,233 @@ impl<I, P> SharedType<I, P> {
         SharedType::Prelim(prelim)
     }
 }
+
+impl<I: AsRef<Branch>, P> SharedType<I, P> {
+    /// Returns a branch of an integrated shared type, or `None` if it's preliminary.
+    pub(crate) fn branch(&self) -> Option<&Branch> {
+        match self {
+            SharedType::Integrated(shared) => Some(shared.as_ref()),
+            SharedType::Prelim(_) => None,
+        }
+    }
+}
+
+/// Returns a branch of a shared type stored in a given value, if it holds one.
+fn value_branch(value: Value) -> Option<BranchPtr> {
+    match value {
+        Value::YText(text) => Some(BranchPtr::from(text.as_ref())),
+        Value::YArray(array) => Some(BranchPtr::from(array.as_ref())),
+        Value::YMap(map) => Some(BranchPtr::from(map.as_ref())),
+        Value::YXmlElement(xml) => Some(BranchPtr::from(xml.as_ref())),
+        Value::YXmlText(xml) => Some(BranchPtr::from(xml.as_ref())),
+        Value::Any(_) => None,
+    }
+}
+
+/// Returns branches of shared types nested directly inside of a given branch, together with
+/// path segments leading to them.
+pub(crate) fn nested_branches(branch: BranchPtr) -> Vec<(PathSegment, BranchPtr)> {
+    match branch.type_ref() {
+        TYPE_REFS_ARRAY => Array::from(branch)
+            .iter()
+            .enumerate()
+            .filter_map(|(i, value)| Some((PathSegment::Index(i as u32), value_branch(value)?)))
+            .collect(),
+        TYPE_REFS_MAP => Map::from(branch)
+            .iter()
+            .filter_map(|(key, value)| Some((PathSegment::Key(key.into()), value_branch(value)?)))
+            .collect(),
+        TYPE_REFS_XML_ELEMENT => {
+            let mut children = Vec::new();
+            let mut child = XmlElement::from(branch).first_child();
+            while let Some(xml) = child {
+                let index = PathSegment::Index(children.len() as u32);
+                child = match xml {
+                    Xml::Element(element) => {
+                        children.push((index, BranchPtr::from(element.as_ref())));
+                        element.next_sibling()
+                    }
+                    Xml::Text(text) => {
+                        children.push((index, BranchPtr::from(text.as_ref())));
+                        text.next_sibling()
+                    }
+                };
+            }
+            children
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Checks if `branch` is `ancestor` itself or is nested anywhere inside of it.
+pub(crate) fn is_within(branch: &Branch, ancestor: &Branch) -> bool {
+    let target = BranchPtr::from(branch);
+    let mut pending = vec![BranchPtr::from(ancestor)];
+    while let Some(current) = pending.pop() {
+        if current == target {
+            return true;
+        }
+        pending.extend(nested_branches(current).into_iter().map(|(_, child)| child));
+    }
+    false
+}
+
+/// Returns names and branches of root types of a document. Root types which have been received in
+/// updates, but never obtained with `get_*`, have no type yet and are skipped. Yrs 0.12 only lists
+/// root types through `Doc::root_refs`, which panics when it reaches one of them, so these
+/// expected panics are caught silently.
+pub(crate) fn root_branches(doc: &Doc) -> Vec<(Rc<str>, BranchPtr)> {
+    let mut roots = doc.root_refs();
+    let mut branches = Vec::new();
+    loop {
+        // The iterator moves past a root type before converting it
+        match panics::catch_expected(|| roots.next()) {
+            Some(Some((name, root))) => {
+                branches.extend(value_branch(root).map(|b| (name.into(), b)))
+            }
+            Some(None) => break,
+            None => continue,
+        }
+    }
+    branches
+}
+
+/// Returns a name of the Ypy class corresponding to a given type reference.
+fn type_ref_name(type_ref: TypeRefs) -> &'static str {
+    match type_ref {
+        TYPE_REFS_TEXT => "YText",
+        TYPE_REFS_ARRAY => "YArray",
+        TYPE_REFS_MAP => "YMap",
+        TYPE_REFS_XML_ELEMENT => "YXmlElement",
+        TYPE_REFS_XML_TEXT => "YXmlText",
+        _ => "an unknown type",
+    }
+}
+
+/// Ensures that a root type `name` has been defined with an `expected` type. Accessing a root type
+/// as a different kind than the one it was created with would misinterpret its contents.
+pub(crate) fn check_root_type(branch: &Branch, name: &str, expected: TypeRefs) -> PyResult<()> {
+    let actual = branch.type_ref();
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "Root type '{name}' already exists as {}, it cannot be accessed as {}",
+            type_ref_name(actual),
+            type_ref_name(expected)
+        )))
+    }
+}
+
+fn branch_address(branch: &Branch) -> usize {
+    branch as *const Branch as usize
+}
+
+/// Returns an address of the branch backing a given Ypy shared type, if it's integrated.
+fn branch_address_of(value: &PyAny) -> Option<usize> {
+    if let Ok(text) = value.downcast::<PyCell<YText>>() {
+        text.borrow().0.branch().map(branch_address)
+    } else if let Ok(array) = value.downcast::<PyCell<YArray>>() {
+        array.borrow().0.branch().map(branch_address)
+    } else if let Ok(map) = value.downcast::<PyCell<YMap>>() {
+        map.borrow().0.branch().map(branch_address)
+    } else if let Ok(xml) = value.downcast::<PyCell<YXmlElement>>() {
+        Some(branch_address(xml.borrow().0.as_ref()))
+    } else if let Ok(xml) = value.downcast::<PyCell<YXmlText>>() {
+        Some(branch_address(xml.borrow().0.as_ref()))
+    } else {
+        None
+    }
+}
+
+/// Compares integrated shared types by identity of their underlying branches, so that different
+/// wrappers of the same shared type are equal. Comparisons involving preliminary instances fall
+/// back to the default Python identity check.
+pub(crate) fn compare_branches(
+    py: Python,
+    branch: Option<&Branch>,
+    other: &PyAny,
+    op: CompareOp,
+) -> PyObject {
+    match (branch.map(branch_address), branch_address_of(other), op) {
+        (Some(this), Some(other), CompareOp::Eq) => (this == other).into_py(py),
+        (Some(this), Some(other), CompareOp::Ne) => (this != other).into_py(py),
+        _ => py.NotImplemented(),
+    }
+}
+
+/// Hashes an integrated shared type by its underlying branch. Preliminary instances are
+/// unhashable, since they change identity once integrated.
+pub(crate) fn hash_branch(branch: Option<&Branch>) -> PyResult<isize> {
+    branch
+        .map(|branch| branch_address(branch) as isize)
+        .ok_or_else(|| PyTypeError::new_err("Preliminary shared types are unhashable"))
+}
+
+/// Guards iterators and views which read contents of a shared type through raw pointers. It keeps
+/// the Python object of the shared type alive, together with its location, which holds a weak
+/// reference to its document (when known), so that accessing contents of a dropped document
+/// raises a `RuntimeError` instead of reading freed memory.
+#[derive(Clone)]
+pub(crate) struct SourceGuard {
+    _source: PyObject,
+    branch: Option<BranchPtr>,
+    location: Location,
+}
+
+impl SourceGuard {
+    pub(crate) fn new(source: &PyAny, branch: Option<&Branch>, location: &Location) -> Self {
+        SourceGuard {
+            _source: source.into(),
+            branch: branch.map(BranchPtr::from),
+            location: location.clone(),
+        }
+    }
+
+    /// Returns the location of the guarded shared type.
+    pub(crate) fn location(&self) -> &Location {
+        &self.location
+    }
+
+    /// Runs `f`, which converts contents of the guarded shared type into Python objects, so that
+    /// shared types nested in it are located.
+    pub(crate) fn within<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        match &self.branch {
+            Some(branch) => self.location.within(branch, f),
+            None => f(),
+        }
+    }
+
+    /// Fails if the document containing the guarded shared type has been dropped or destroyed.
+    pub(crate) fn check(&self, py: Python) -> PyResult<()> {
+        let doc_ref = match self.location.doc_ref() {
+            Some(doc_ref) if doc_ref.is_known() => doc_ref,
+            _ => return Ok(()),
+        };
+        let doc = doc_ref.get(py).ok_or_else(|| {
+            PyRuntimeError::new_err("The document containing this shared type has been dropped")
+        })?;
+        let doc: &PyCell<YDoc> = doc.as_ref(py).downcast()?;
+        // A document which is borrowed mutably is in use, so it can't have been destroyed
+        if let Ok(doc) = doc.try_borrow() {
+            doc.lifecycle.check()?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns a hex encoded SHA-256 digest of given bytes.
+pub(crate) fn sha256_hex(py: Python, data: &[u8]) -> PyResult<String> {
+    let data = pytypes::PyBytes::new(py, data);
+    py.import("hashlib")?
+        .call_method1("sha256", (data,))?
+        .call_method0("hexdigest")?
+        .extract()
+}
+
 #[derive(Clone)]
 pub enum YPyType<'a> {
     Text(&'a PyCell<YText>),
@@ -99,6 +364,19 @@ impl<'a> YPyType<'a> {
         }
     }
 
+    /// Converts this shared type into plain Python data: `YText` into `str`, `YArray` into `list`
+    /// and `YMap` into `dict`, recursively resolving nested shared types. XML types are converted
+    /// into their string representation.
+    pub fn to_plain(&self, py: Python) -> PyResult<PyObject> {
+        match self {
+            YPyType::Text(text) => Ok(text.borrow().__str__().into_py(py)),
+            YPyType::Array(array) => array.borrow().to_list(Some(true), None, None),
+            YPyType::Map(map) => map.borrow().to_dict(Some(true), None),
+            YPyType::XmlElement(xml) => Ok(xml.borrow().__str__().into_py(py)),
+            YPyType::XmlText(xml) => Ok(xml.borrow().__str__().into_py(py)),
+        }
+    }
+
     pub fn type_ref(&self) -> TypeRefs {
         match self {
             YPyType::Text(_) => TYPE_REFS_TEXT,
@@ -110,6 +388,15 @@ impl<'a> YPyType<'a> {
     }
 }
 
+/// Converts an element of a preliminary shared type into plain Python data, resolving shared
+/// types with `YPyType::to_plain`. Other values are returned as they are.
+pub fn plain_value(py: Python, value: &PyObject) -> PyResult<PyObject> {
+    match YPyType::try_from(value.as_ref(py)) {
+        Ok(y_type) => y_type.to_plain(py),
+        Err(_) => Ok(value.clone_ref(py)),
+    }
+}
+
 impl<'a> Display for YPyType<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let info = match self {
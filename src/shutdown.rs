@@ -0,0 +1,166 @@
+use crate::dispatcher;
+use pyo3::ffi;
+use pyo3::once_cell::GILOnceCell;
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+struct Slot {
+    callback: Mutex<Option<PyObject>>,
+    /// Address of a document owning the subscription, or zero if it's unknown.
+    owner: AtomicUsize,
+}
+
+/// Set once the interpreter starts finalizing.
+static FINALIZING: AtomicBool = AtomicBool::new(false);
+
+/// Callbacks of all subscriptions, which are dropped by `shutdown`.
+static CALLBACKS: GILOnceCell<Mutex<Vec<Weak<Slot>>>> = GILOnceCell::new();
+
+/// Checks if the interpreter is finalizing, in which case Python code must not be called anymore.
+pub(crate) fn finalizing() -> bool {
+    FINALIZING.load(Ordering::Relaxed) || unsafe { ffi::Py_IsInitialized() } == 0
+}
+
+/// Acquires the GIL and calls `f`, unless the interpreter is finalizing. Observers are called by
+/// Y.rs outside of Python, eg. when a document is dropped during finalization, at which point
+/// acquiring the GIL would crash the process.
+pub(crate) fn with_gil<F, R>(f: F) -> Option<R>
+where
+    F: FnOnce(Python) -> R,
+{
+    if finalizing() {
+        return None;
+    }
+    Python::with_gil(|py| if finalizing() { None } else { Some(f(py)) })
+}
+
+/// A callback of a subscription, which can be dropped by `shutdown` while a shared type or
+/// document still holds the subscription. A weak callback holds a weak reference to a Python
+/// callable, and is dropped once the callable has been garbage collected.
+#[derive(Clone)]
+pub(crate) struct Callback {
+    slot: Arc<Slot>,
+    weak: bool,
+}
+
+impl Callback {
+    pub(crate) fn new(py: Python, callback: PyObject) -> Self {
+        let slot = Arc::new(Slot {
+            callback: Mutex::new(Some(callback)),
+            owner: AtomicUsize::new(0),
+        });
+        let mut callbacks = registry(py).lock().unwrap();
+        // Callbacks of cancelled subscriptions are forgotten whenever the registry fills up
+        if callbacks.len() == callbacks.capacity() {
+            callbacks.retain(|callback| callback.strong_count() > 0);
+        }
+        callbacks.push(Arc::downgrade(&slot));
+        Callback { slot, weak: false }
+    }
+
+    /// Returns a callback of an observer, which only holds a weak reference to a `callback` if
+    /// `weak` is `True`. Bound methods are referenced with `weakref.WeakMethod`, so that they're
+    /// kept alive by their receivers rather than by the subscription.
+    pub(crate) fn observer(py: Python, callback: PyObject, weak: Option<bool>) -> PyResult<Self> {
+        if !weak.unwrap_or(false) {
+            return Ok(Callback::new(py, callback));
+        }
+        let weakref = py.import("weakref")?;
+        let is_method: bool = py
+            .import("inspect")?
+            .call_method1("ismethod", (callback.clone_ref(py),))?
+            .extract()?;
+        let reference = if is_method {
+            weakref.call_method1("WeakMethod", (callback,))?
+        } else {
+            weakref.call_method1("ref", (callback,))?
+        };
+        let mut callback = Callback::new(py, reference.into());
+        callback.weak = true;
+        Ok(callback)
+    }
+
+    /// Remembers a document owning the subscription, given by the address of its Python object,
+    /// so that the callback is dropped when the document is destroyed.
+    pub(crate) fn set_owner(&self, owner: Option<usize>) {
+        self.slot.owner.store(owner.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Calls this callback with given `args`. Returns `None` without calling it if it has been
+    /// dropped by `shutdown` or garbage collected.
+    pub(crate) fn call1(&self, py: Python, args: impl IntoPy<Py<PyTuple>>) -> PyResult<PyObject> {
+        // The lock is released before the call, as the callback may call `shutdown` itself
+        let mut callback = match self.slot.callback.lock().unwrap().as_ref() {
+            Some(callback) => callback.clone_ref(py),
+            None => return Ok(py.None()),
+        };
+        if self.weak {
+            callback = callback.call0(py)?;
+            if callback.is_none(py) {
+                self.slot.callback.lock().unwrap().take();
+                return Ok(py.None());
+            }
+        }
+        callback.call1(py, args)
+    }
+}
+
+fn registry(py: Python) -> &Mutex<Vec<Weak<Slot>>> {
+    CALLBACKS.get_or_init(py, || Mutex::new(Vec::new()))
+}
+
+/// Drops callbacks of all subscriptions of Ypy objects, including callbacks queued by a
+/// `Dispatcher` which haven't been called yet. Shared types and documents remain usable, and
+/// callbacks subscribed afterwards are called as usual.
+///
+/// Ypy calls this function when the interpreter exits, after which observers aren't called
+/// anymore. Calling it beforehand, eg. when a server is shutting down, releases objects
+/// referenced by callbacks while they can still be finalized safely.
+///
+/// Example:
+///
+/// ```python
+/// import y_py as Y
+///
+/// doc = Y.YDoc()
+/// doc.observe_after_transaction(lambda event: print(event.get_update()))
+/// Y.shutdown()
+/// with doc.begin_transaction() as txn:
+///     doc.get_text('text').extend(txn, 'hello')  # nothing is printed
+/// ```
+#[pyfunction]
+pub fn shutdown(py: Python) {
+    let callbacks = std::mem::take(&mut *registry(py).lock().unwrap());
+    for callback in callbacks {
+        if let Some(slot) = callback.upgrade() {
+            // Callbacks are dropped after releasing the lock, as their finalizers may run code
+            let callback = slot.callback.lock().unwrap().take();
+            drop(callback);
+        }
+    }
+}
+
+/// Drops callbacks of subscriptions owned by a document at a given address, see `YDoc.destroy`.
+pub(crate) fn release_owned(py: Python, owner: usize) {
+    let slots: Vec<Arc<Slot>> = registry(py)
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter(|slot| slot.owner.load(Ordering::Relaxed) == owner)
+        .collect();
+    for slot in slots {
+        let callback = slot.callback.lock().unwrap().take();
+        drop(callback);
+    }
+}
+
+/// Called by `atexit` when the interpreter starts finalizing.
+#[pyfunction]
+pub(crate) fn at_exit(py: Python) {
+    FINALIZING.store(true, Ordering::Relaxed);
+    shutdown(py);
+    dispatcher::stop_workers(py)
+}
@@ -0,0 +1,282 @@
+use crate::metrics::{self, Metric};
+use crate::y_doc::{diff, YDoc};
+use pyo3::exceptions::{PyAssertionError, PyValueError};
+use pyo3::prelude::*;
+use yrs::{Doc, Transaction};
+
+/// Names of root types changed by the built-in random operations.
+const TEXT: &str = "text";
+const ARRAY: &str = "array";
+const MAP: &str = "map";
+const MAP_KEYS: [&str; 4] = ["a", "b", "c", "d"];
+
+/// A SplitMix64 generator, so that simulations are reproducible on every platform.
+#[derive(Default)]
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number from `0..n`, where `n` must be positive.
+    fn below(&mut self, n: u32) -> u32 {
+        (self.next_u64() % n as u64) as u32
+    }
+
+    /// Returns a number from `0.0..1.0`.
+    pub(crate) fn sample(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub(crate) fn chance(&mut self, probability: f64) -> bool {
+        self.sample() < probability
+    }
+}
+
+/// Simulates concurrent editing of a document by a number of replicas, which exchange their
+/// updates in a random order. Simulations are deterministic: the same `seed` always produces the
+/// same operations and interleavings, so failures can be reproduced.
+///
+/// Replicas perform random operations on "text", "array" and "map" root types by default.
+/// Custom `operations` can be used instead to exercise a schema of an application. Each of them is
+/// a callable receiving a transaction, a replica and a `random.Random` instance derived from the
+/// seed, which should be the only source of randomness of an operation.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import Simulator
+///
+/// def add_todo(txn, doc, rand):
+///     todos = txn.get_array('todos')
+///     todos.insert(txn, rand.randint(0, len(todos)), {'done': False})
+///
+/// sim = Simulator(replicas=3, seed=42, operations=[add_todo])
+/// sim.run(200)
+/// ```
+#[pyclass(unsendable)]
+pub struct Simulator {
+    replicas: Vec<Py<YDoc>>,
+    operations: Option<Vec<PyObject>>,
+    seed: u64,
+    rng: Rng,
+    history: Vec<String>,
+}
+
+#[pymethods]
+impl Simulator {
+    /// Creates a simulation of a given number of `replicas`, which have client ids starting
+    /// from 1.
+    #[new]
+    pub fn new(
+        py: Python,
+        replicas: Option<usize>,
+        seed: Option<u64>,
+        operations: Option<Vec<PyObject>>,
+    ) -> PyResult<Self> {
+        let replicas = replicas.unwrap_or(2);
+        if replicas == 0 {
+            return Err(PyValueError::new_err(
+                "A simulation needs at least one replica",
+            ));
+        }
+        if matches!(&operations, Some(operations) if operations.is_empty()) {
+            return Err(PyValueError::new_err(
+                "At least one operation has to be provided",
+            ));
+        }
+        let replicas = (1..=replicas as u64)
+            .map(|client_id| Py::new(py, YDoc::new(Some(client_id), None, None)?))
+            .collect::<PyResult<_>>()?;
+        let seed = seed.unwrap_or(0);
+        Ok(Simulator {
+            replicas,
+            operations,
+            seed,
+            rng: Rng(seed),
+            history: Vec::new(),
+        })
+    }
+
+    /// Returns simulated documents.
+    #[getter]
+    pub fn replicas(&self, py: Python) -> Vec<Py<YDoc>> {
+        self.replicas.iter().map(|doc| doc.clone_ref(py)).collect()
+    }
+
+    #[getter]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns descriptions of all steps performed so far.
+    #[getter]
+    pub fn history(&self) -> Vec<String> {
+        self.history.clone()
+    }
+
+    /// Performs a single random step: either an operation on one of the replicas, or delivery of
+    /// updates from one replica to another, which happens with a given `sync_probability`.
+    /// Returns a description of the step.
+    pub fn step(&mut self, py: Python, sync_probability: Option<f64>) -> PyResult<String> {
+        let sync_probability = sync_probability.unwrap_or(0.3);
+        if !(0.0..=1.0).contains(&sync_probability) {
+            return Err(PyValueError::new_err(
+                "sync_probability must be a number between 0 and 1",
+            ));
+        }
+        let count = self.replicas.len() as u32;
+        let description = if count > 1 && self.rng.chance(sync_probability) {
+            let source = self.rng.below(count) as usize;
+            // Any replica other than the source
+            let target = (source + 1 + self.rng.below(count - 1) as usize) % count as usize;
+            self.sync(py, source, target)?;
+            format!("sync {source} -> {target}")
+        } else {
+            let replica = self.rng.below(count) as usize;
+            let operation = self.operate(py, replica)?;
+            format!("replica {replica}: {operation}")
+        };
+        self.history.push(description.clone());
+        Ok(description)
+    }
+
+    /// Performs a given number of random `steps`, then synchronizes all replicas and checks that
+    /// they have converged.
+    pub fn run(&mut self, py: Python, steps: usize, sync_probability: Option<f64>) -> PyResult<()> {
+        for _ in 0..steps {
+            self.step(py, sync_probability)?;
+        }
+        self.sync_all(py)?;
+        self.assert_converged(py)
+    }
+
+    /// Delivers all updates of a `source` replica which a `target` replica is missing.
+    pub fn sync(&self, py: Python, source: usize, target: usize) -> PyResult<()> {
+        let source = self.replica(py, source)?;
+        let target = self.replica(py, target)?;
+        let state_vector = target.inner.transact().state_vector();
+        let update = diff(&source.inner, &state_vector);
+        // Updates are applied like remote ones, so that update filters of replicas are respected.
+        // They are exchanged in memory, so transforms of replicas are skipped.
+        let mut txn = target.begin_transaction();
+        metrics::measure(Metric::Updates, || txn.integrate_v1(&update))
+    }
+
+    /// Exchanges updates between all replicas, so that all of them have the same state.
+    pub fn sync_all(&self, py: Python) -> PyResult<()> {
+        for replica in 1..self.replicas.len() {
+            self.sync(py, replica, 0)?;
+        }
+        for replica in 1..self.replicas.len() {
+            self.sync(py, 0, replica)?;
+        }
+        Ok(())
+    }
+
+    /// Raises an `AssertionError` unless all replicas have the same state.
+    pub fn assert_converged(&self, py: Python) -> PyResult<()> {
+        let expected = self.replicas[0].borrow(py).state_hash(py)?;
+        for (index, replica) in self.replicas.iter().enumerate().skip(1) {
+            if replica.borrow(py).state_hash(py)? != expected {
+                return Err(PyAssertionError::new_err(format!(
+                    "Replicas 0 and {index} have diverged after {} steps (seed {})",
+                    self.history.len(),
+                    self.seed
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Simulator {
+    fn replica<'py>(&self, py: Python<'py>, index: usize) -> PyResult<PyRef<'py, YDoc>> {
+        match self.replicas.get(index) {
+            Some(doc) => Ok(doc.clone_ref(py).into_ref(py).borrow()),
+            None => Err(PyValueError::new_err(format!(
+                "There is no replica {index}, the simulation has {} of them",
+                self.replicas.len()
+            ))),
+        }
+    }
+
+    /// Performs an operation on a given replica, and returns its description.
+    fn operate(&mut self, py: Python, replica: usize) -> PyResult<String> {
+        let doc = self.replicas[replica].clone_ref(py);
+        let operation = match &self.operations {
+            Some(operations) => {
+                operations[self.rng.below(operations.len() as u32) as usize].clone_ref(py)
+            }
+            None => return Ok(self.random_operation(&doc.borrow(py).inner)),
+        };
+        let rand = py
+            .import("random")?
+            .call_method1("Random", (self.rng.next_u64(),))?;
+        YDoc::doc_ref(doc.as_ref(py))?;
+        let txn = Py::new(py, doc.borrow(py).begin_transaction())?;
+        let result = operation.call1(py, (txn.clone_ref(py), doc, rand));
+        txn.borrow_mut(py).commit();
+        result?;
+        let name = operation.getattr(py, "__name__");
+        match name {
+            Ok(name) => name.extract(py),
+            Err(_) => Ok(operation.as_ref(py).repr()?.to_string()),
+        }
+    }
+
+    /// Performs a random change of a built-in root type, and returns its description.
+    fn random_operation(&mut self, doc: &Doc) -> String {
+        let mut txn: Transaction = doc.transact();
+        match self.rng.below(3) {
+            0 => {
+                let text = txn.get_text(TEXT);
+                let len = text.len();
+                if len > 0 && self.rng.chance(0.3) {
+                    let index = self.rng.below(len);
+                    let count = 1 + self.rng.below((len - index).min(3));
+                    text.remove_range(&mut txn, index, count);
+                    format!("{TEXT}.delete({index}, {count})")
+                } else {
+                    let index = self.rng.below(len + 1);
+                    let chunk: String = (0..1 + self.rng.below(3))
+                        .map(|_| (b'a' + self.rng.below(26) as u8) as char)
+                        .collect();
+                    text.insert(&mut txn, index, &chunk);
+                    format!("{TEXT}.insert({index}, {chunk:?})")
+                }
+            }
+            1 => {
+                let array = txn.get_array(ARRAY);
+                let len = array.len();
+                if len > 0 && self.rng.chance(0.3) {
+                    let index = self.rng.below(len);
+                    array.remove_range(&mut txn, index, 1);
+                    format!("{ARRAY}.delete({index})")
+                } else {
+                    let index = self.rng.below(len + 1);
+                    let value = self.rng.below(1000);
+                    array.insert(&mut txn, index, value as f64);
+                    format!("{ARRAY}.insert({index}, {value})")
+                }
+            }
+            _ => {
+                let map = txn.get_map(MAP);
+                let key = MAP_KEYS[self.rng.below(MAP_KEYS.len() as u32) as usize];
+                if self.rng.chance(0.3) {
+                    map.remove(&mut txn, key);
+                    format!("{MAP}.delete({key:?})")
+                } else {
+                    let value = self.rng.below(1000);
+                    map.insert(&mut txn, key.to_string(), value as f64);
+                    format!("{MAP}.set({key:?}, {value})")
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,780 @@
+use crate::shared_types::EncodingError;
+use lib0::any::Any;
+use lib0::decoding::Cursor;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use yrs::block::{
+    BLOCK_GC_REF_NUMBER, BLOCK_ITEM_ANY_REF_NUMBER, BLOCK_ITEM_BINARY_REF_NUMBER,
+    BLOCK_ITEM_DELETED_REF_NUMBER, BLOCK_ITEM_DOC_REF_NUMBER, BLOCK_ITEM_EMBED_REF_NUMBER,
+    BLOCK_ITEM_FORMAT_REF_NUMBER, BLOCK_ITEM_JSON_REF_NUMBER, BLOCK_ITEM_MOVE_REF_NUMBER,
+    BLOCK_ITEM_STRING_REF_NUMBER, BLOCK_ITEM_TYPE_REF_NUMBER, BLOCK_SKIP_REF_NUMBER, HAS_ORIGIN,
+    HAS_PARENT_SUB, HAS_RIGHT_ORIGIN,
+};
+use yrs::types::{TYPE_REFS_XML_ELEMENT, TYPE_REFS_XML_HOOK};
+use yrs::updates::decoder::Decode;
+
+/// Clock ranges `(clock, len)` of deleted items, grouped by client id.
+pub(crate) type DeleteSet = BTreeMap<u64, Vec<(u64, u64)>>;
+/// Next expected clock of every client.
+pub(crate) type StateVector = BTreeMap<u64, u64>;
+
+const CONTENT_REF_MASK: u8 = 0x1f;
+
+/// Encodes a snapshot of a document, given its full state `update` and its `state_vector`, both
+/// using lib0 v1 encoding. A snapshot consists of a delete set followed by a state vector, as
+/// state vectors alone don't reflect deletions.
+pub(crate) fn encode_snapshot(update: &[u8], state_vector: &[u8]) -> PyResult<Vec<u8>> {
+    let mut reader = Reader::new(update);
+    read_structs(&mut reader)?;
+    let delete_set = read_delete_set(&mut reader)?;
+    let mut buffer = Vec::new();
+    write_delete_set(&mut buffer, &delete_set);
+    buffer.extend_from_slice(state_vector);
+    Ok(buffer)
+}
+
+/// Rewrites a full state `update` of a document into an update, which reproduces the state of
+/// that document at the time a given `snapshot` was taken. Blocks created after the snapshot are
+/// dropped, while deletions are replaced with the ones recorded by the snapshot.
+///
+/// Fails if content which was visible at the time of the snapshot has been garbage collected
+/// since then.
+pub(crate) fn rewind_update(update: &[u8], snapshot: &[u8]) -> PyResult<Vec<u8>> {
+    let mut reader = Reader::new(snapshot);
+    let delete_set = read_delete_set(&mut reader)?;
+    let state_vector = read_state_vector(&mut reader)?;
+    reader.finish()?;
+
+    let mut reader = Reader::new(update);
+    let structs = read_structs(&mut reader)?;
+    reader.finish_delete_set()?;
+    let mut clients = Vec::new();
+    for (client, clock, structs) in structs.iter() {
+        let (client, clock) = (*client, *clock);
+        let end = state_vector.get(&client).copied().unwrap_or(0);
+        let mut kept = Vec::new();
+        for block in structs.iter() {
+            if block.clock >= end {
+                break;
+            }
+            let len = block.len.min(end - block.clock);
+            if block.is_deleted_content() && !is_deleted(&delete_set, client, block.clock, len) {
+                return Err(PyValueError::new_err(
+                    "Content present at the time of the snapshot has been garbage collected, \
+                    use a document created with skip_gc=True to keep its history",
+                ));
+            }
+            kept.push((block, len));
+        }
+        if !kept.is_empty() {
+            clients.push((client, clock, kept));
+        }
+    }
+
+    let mut buffer = Vec::new();
+    write_var_uint(&mut buffer, clients.len() as u64);
+    for (client, clock, structs) in clients {
+        write_var_uint(&mut buffer, structs.len() as u64);
+        write_var_uint(&mut buffer, client);
+        write_var_uint(&mut buffer, clock);
+        for (block, len) in structs {
+            block.write(&mut buffer, len);
+        }
+    }
+    write_delete_set(&mut buffer, &delete_set);
+    Ok(buffer)
+}
+
+/// Checks a lib0 v1 encoded `update` for problems which would prevent it from being integrated,
+/// and returns their descriptions. Malformed encoding is reported together with its byte offset.
+pub(crate) fn validate_update(py: Python, update: &[u8]) -> Vec<String> {
+    let mut reader = Reader::new(update);
+    let parsed = read_structs(&mut reader).and_then(|structs| {
+        let delete_set = read_delete_set(&mut reader)?;
+        reader.finish()?;
+        Ok((structs, delete_set))
+    });
+    let (structs, delete_set) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => return vec![e.value(py).to_string()],
+    };
+
+    let mut problems = Vec::new();
+    let mut clients = HashSet::new();
+    for (client, _, blocks) in structs.iter() {
+        if !clients.insert(*client) {
+            problems.push(format!(
+                "structs of client {client} are listed more than once"
+            ));
+        }
+        for block in blocks.iter() {
+            let clock = block.clock;
+            if block.len == 0 {
+                problems.push(format!("struct {client}:{clock} is empty"));
+            }
+            if clock.checked_add(block.len).is_none() {
+                problems.push(format!(
+                    "struct {client}:{clock} exceeds the range of clocks"
+                ));
+            }
+            if let Parent::SameAs(other, other_clock) | Parent::Type(other, other_clock) =
+                block.parent
+            {
+                // Origins and parents always exist before the items referring to them
+                if other == *client && other_clock >= clock {
+                    problems.push(format!(
+                        "struct {client}:{clock} refers to {other}:{other_clock}, which is created after it"
+                    ));
+                }
+            }
+        }
+    }
+    for (client, ranges) in delete_set.iter() {
+        for (clock, len) in ranges.iter() {
+            if *len == 0 {
+                problems.push(format!("deleted range {client}:{clock} is empty"));
+            } else if clock.checked_add(*len).is_none() {
+                problems.push(format!(
+                    "deleted range {client}:{clock} exceeds the range of clocks"
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// Decodes a snapshot produced by `YDoc.snapshot` into a `yrs` snapshot, which can be used to
+/// compare the current state of shared types with that version.
+pub(crate) fn decode_snapshot(data: &[u8]) -> PyResult<yrs::Snapshot> {
+    yrs::Snapshot::decode_v1(data)
+        .map_err(|e| EncodingError::new_err(format!("Malformed snapshot: {e}")))
+}
+
+/// Checks if a given clock range of a client is entirely covered by a delete set.
+pub(crate) fn is_deleted(delete_set: &DeleteSet, client: u64, clock: u64, len: u64) -> bool {
+    let mut ranges = match delete_set.get(&client) {
+        Some(ranges) => ranges.clone(),
+        None => return false,
+    };
+    ranges.sort_unstable();
+    let mut covered = clock;
+    for (start, range_len) in ranges {
+        if start > covered {
+            break;
+        }
+        covered = covered.max(start.saturating_add(range_len));
+    }
+    covered >= clock.saturating_add(len)
+}
+
+/// A single struct of an update, with its encoded item header kept as it was.
+pub(crate) struct Block<'a> {
+    pub(crate) clock: u64,
+    pub(crate) len: u64,
+    info: u8,
+    header: &'a [u8],
+    content: Content<'a>,
+    pub(crate) parent: Parent<'a>,
+    /// A key of a map entry, unless the parent is inherited from an origin.
+    pub(crate) parent_sub: Option<&'a str>,
+    /// Number of bytes taken by this struct in its update.
+    pub(crate) size: usize,
+}
+
+/// A parent of a struct, as it's referenced by its encoding.
+#[derive(Clone, Copy)]
+pub(crate) enum Parent<'a> {
+    /// Garbage collected and skipped structs have no parent.
+    None,
+    Root(&'a str),
+    /// An id of an item containing a nested shared type.
+    Type(u64, u64),
+    /// An id of an origin item, which has the same parent.
+    SameAs(u64, u64),
+}
+
+/// Sizes of values inserted by a struct.
+#[derive(Default)]
+pub(crate) struct ContentSize {
+    /// Length of the longest string, in UTF-16 code units.
+    pub(crate) longest_string: u64,
+    /// Nesting depth of lists and maps, where values which aren't nested have a depth of zero.
+    pub(crate) depth: usize,
+}
+
+enum Content<'a> {
+    /// Length of garbage collected structs, skipped ranges and deleted item content.
+    Len(u64),
+    /// Encoded elements of JSON and `Any` content.
+    Elements(Vec<&'a [u8]>),
+    String(&'a str),
+    /// Encoded content of other kinds, which always has a length of 1.
+    Single(&'a [u8]),
+}
+
+impl<'a> Block<'a> {
+    pub(crate) fn is_deleted_content(&self) -> bool {
+        let content_ref = self.info & CONTENT_REF_MASK;
+        self.info == BLOCK_GC_REF_NUMBER || content_ref == BLOCK_ITEM_DELETED_REF_NUMBER
+    }
+
+    pub(crate) fn is_skip(&self) -> bool {
+        self.info == BLOCK_SKIP_REF_NUMBER
+    }
+
+    /// Checks if this struct holds content found only in texts: strings, embeds and formatting.
+    pub(crate) fn is_text_content(&self) -> bool {
+        matches!(
+            self.info & CONTENT_REF_MASK,
+            BLOCK_ITEM_STRING_REF_NUMBER
+                | BLOCK_ITEM_EMBED_REF_NUMBER
+                | BLOCK_ITEM_FORMAT_REF_NUMBER
+        )
+    }
+
+    /// Checks if this struct holds content counted by indexes of sequences, which excludes
+    /// formatting and moved ranges.
+    pub(crate) fn is_countable(&self) -> bool {
+        !matches!(
+            self.info & CONTENT_REF_MASK,
+            BLOCK_ITEM_FORMAT_REF_NUMBER | BLOCK_ITEM_MOVE_REF_NUMBER
+        )
+    }
+
+    /// Writes this item as an item of a root text `root`, with its content replaced by a string
+    /// of the same length. Items which inherit their parent from an origin keep it, so all items
+    /// of a sequence have to be written this way to keep their order.
+    pub(crate) fn write_placeholder(&self, buffer: &mut Vec<u8>, root: &str) {
+        let info = self.info & !(CONTENT_REF_MASK | HAS_PARENT_SUB);
+        buffer.push(info | BLOCK_ITEM_STRING_REF_NUMBER);
+        if self.info & (HAS_ORIGIN | HAS_RIGHT_ORIGIN) != 0 {
+            buffer.extend_from_slice(self.header);
+        } else {
+            write_var_uint(buffer, 1);
+            write_var_string(buffer, root);
+        }
+        write_var_string(buffer, &"x".repeat(self.len as usize));
+    }
+
+    /// Returns sizes of values inserted by this struct.
+    pub(crate) fn content_size(&self) -> PyResult<ContentSize> {
+        let mut size = ContentSize::default();
+        match &self.content {
+            Content::String(_) => size.longest_string = self.len,
+            Content::Elements(elements)
+                if self.info & CONTENT_REF_MASK == BLOCK_ITEM_ANY_REF_NUMBER =>
+            {
+                for element in elements {
+                    Reader::new(element).measure_any(0, &mut size)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(size)
+    }
+
+    /// Writes the first `len` elements of this struct.
+    pub(crate) fn write(&self, buffer: &mut Vec<u8>, len: u64) {
+        buffer.push(self.info);
+        buffer.extend_from_slice(self.header);
+        match &self.content {
+            Content::Len(_) => write_var_uint(buffer, len),
+            Content::Elements(elements) => {
+                write_var_uint(buffer, len);
+                for element in elements.iter().take(len as usize) {
+                    buffer.extend_from_slice(element);
+                }
+            }
+            Content::String(s) if len < self.len => {
+                // Lengths of strings are counted in UTF-16 code units
+                let units: Vec<u16> = s.encode_utf16().take(len as usize).collect();
+                write_var_string(buffer, &String::from_utf16_lossy(&units));
+            }
+            Content::String(s) => write_var_string(buffer, s),
+            Content::Single(raw) => buffer.extend_from_slice(raw),
+        }
+    }
+
+    /// Returns units of content of this struct, one for every clock it takes.
+    pub(crate) fn units(&self) -> Vec<Unit> {
+        let kind = self.info & CONTENT_REF_MASK;
+        let value = |element: &[u8]| -> Option<Any> {
+            match kind {
+                BLOCK_ITEM_ANY_REF_NUMBER => Any::decode(&mut Cursor::new(element)).ok(),
+                BLOCK_ITEM_JSON_REF_NUMBER | BLOCK_ITEM_EMBED_REF_NUMBER => {
+                    Any::from_json(Reader::new(element).var_string().ok()?).ok()
+                }
+                BLOCK_ITEM_BINARY_REF_NUMBER => {
+                    let mut reader = Reader::new(element);
+                    let len = reader.var_uint().ok()?;
+                    Some(Any::Buffer(reader.bytes(len as usize).ok()?.into()))
+                }
+                _ => None,
+            }
+        };
+        match &self.content {
+            Content::String(s) => s
+                .chars()
+                .flat_map(|c| {
+                    let surrogate = Some(Unit::Surrogate).filter(|_| c.len_utf16() > 1);
+                    std::iter::once(Unit::Char(c)).chain(surrogate)
+                })
+                .collect(),
+            Content::Elements(elements) => elements
+                .iter()
+                .map(|element| value(element).map_or(Unit::Opaque, Unit::Value))
+                .collect(),
+            Content::Single(raw) => vec![value(raw).map_or(Unit::Opaque, Unit::Value)],
+            Content::Len(len) => (0..*len).map(|_| Unit::Opaque).collect(),
+        }
+    }
+}
+
+/// A unit of content of a struct, eg. a character of a string or an element of an array.
+pub(crate) enum Unit {
+    Char(char),
+    /// The second UTF-16 code unit of a character which takes two of them.
+    Surrogate,
+    Value(Any),
+    /// Content which can't be copied, eg. a nested shared type or garbage collected content.
+    Opaque,
+}
+
+/// Reads all structs of an update, grouped by client id together with their starting clock.
+pub(crate) fn read_structs<'a>(
+    reader: &mut Reader<'a>,
+) -> PyResult<Vec<(u64, u64, Vec<Block<'a>>)>> {
+    let clients_len = reader.var_uint()?;
+    let mut clients = Vec::new();
+    for _ in 0..clients_len {
+        let structs_len = reader.var_uint()?;
+        let client = reader.var_uint()?;
+        let start = reader.var_uint()?;
+        let mut clock = start;
+        let mut structs = Vec::new();
+        for _ in 0..structs_len {
+            let block = read_block(reader, clock)?;
+            clock = clock.saturating_add(block.len);
+            structs.push(block);
+        }
+        clients.push((client, start, structs));
+    }
+    Ok(clients)
+}
+
+fn read_block<'a>(reader: &mut Reader<'a>, clock: u64) -> PyResult<Block<'a>> {
+    let start = reader.pos;
+    let info = reader.byte()?;
+    if info == BLOCK_GC_REF_NUMBER || info == BLOCK_SKIP_REF_NUMBER {
+        let len = reader.var_uint()?;
+        return Ok(Block {
+            clock,
+            len,
+            info,
+            header: &[],
+            content: Content::Len(len),
+            parent: Parent::None,
+            parent_sub: None,
+            size: reader.pos - start,
+        });
+    }
+
+    let header_start = reader.pos;
+    let origin = if info & HAS_ORIGIN != 0 {
+        Some(reader.id()?)
+    } else {
+        None
+    };
+    let right_origin = if info & HAS_RIGHT_ORIGIN != 0 {
+        Some(reader.id()?)
+    } else {
+        None
+    };
+    let (parent, parent_sub) = match origin.or(right_origin) {
+        Some((client, clock)) => (Parent::SameAs(client, clock), None),
+        None => {
+            let parent = if reader.var_uint()? == 1 {
+                Parent::Root(reader.var_string()?)
+            } else {
+                let (client, clock) = reader.id()?;
+                Parent::Type(client, clock)
+            };
+            let parent_sub = if info & HAS_PARENT_SUB != 0 {
+                Some(reader.var_string()?)
+            } else {
+                None
+            };
+            (parent, parent_sub)
+        }
+    };
+    let header = &reader.data[header_start..reader.pos];
+
+    let content_start = reader.pos;
+    let content = match info & CONTENT_REF_MASK {
+        BLOCK_ITEM_DELETED_REF_NUMBER => Content::Len(reader.var_uint()?),
+        BLOCK_ITEM_JSON_REF_NUMBER => {
+            let len = reader.var_uint()?;
+            let mut elements = Vec::new();
+            for _ in 0..len {
+                let start = reader.pos;
+                reader.skip_var_bytes()?;
+                elements.push(&reader.data[start..reader.pos]);
+            }
+            Content::Elements(elements)
+        }
+        BLOCK_ITEM_ANY_REF_NUMBER => {
+            let len = reader.var_uint()?;
+            let mut elements = Vec::new();
+            for _ in 0..len {
+                let start = reader.pos;
+                reader.skip_any()?;
+                elements.push(&reader.data[start..reader.pos]);
+            }
+            Content::Elements(elements)
+        }
+        BLOCK_ITEM_STRING_REF_NUMBER => Content::String(reader.var_string()?),
+        BLOCK_ITEM_BINARY_REF_NUMBER | BLOCK_ITEM_EMBED_REF_NUMBER => {
+            reader.skip_var_bytes()?;
+            Content::Single(&reader.data[content_start..reader.pos])
+        }
+        BLOCK_ITEM_FORMAT_REF_NUMBER => {
+            reader.skip_var_bytes()?;
+            reader.skip_var_bytes()?;
+            Content::Single(&reader.data[content_start..reader.pos])
+        }
+        BLOCK_ITEM_TYPE_REF_NUMBER => {
+            let type_ref = reader.var_uint()?;
+            if type_ref == TYPE_REFS_XML_ELEMENT as u64 || type_ref == TYPE_REFS_XML_HOOK as u64 {
+                reader.skip_var_bytes()?;
+            }
+            Content::Single(&reader.data[content_start..reader.pos])
+        }
+        BLOCK_ITEM_DOC_REF_NUMBER => {
+            reader.skip_var_bytes()?;
+            reader.skip_any()?;
+            Content::Single(&reader.data[content_start..reader.pos])
+        }
+        BLOCK_ITEM_MOVE_REF_NUMBER => {
+            // Flags, followed by IDs of the start and, unless the moved range is collapsed, the
+            // end of the moved range. Flags are a signed integer, but the collapsed flag is its
+            // lowest bit either way.
+            let flags = reader.var_uint()?;
+            reader.id()?;
+            if flags & 1 == 0 {
+                reader.id()?;
+            }
+            Content::Single(&reader.data[content_start..reader.pos])
+        }
+        other => return Err(invalid(start, &format!("unsupported content type {other}"))),
+    };
+    let len = match &content {
+        Content::Len(len) => *len,
+        Content::Elements(elements) => elements.len() as u64,
+        Content::String(s) => s.encode_utf16().count() as u64,
+        Content::Single(_) => 1,
+    };
+    Ok(Block {
+        clock,
+        len,
+        info,
+        header,
+        content,
+        parent,
+        parent_sub,
+        size: reader.pos - start,
+    })
+}
+
+/// A shared type containing an item: either a root type with a given name, or a nested shared
+/// type held by an item with a given id.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Owner<'a> {
+    Root(&'a str),
+    Type(u64, u64),
+}
+
+/// A struct of a document state, together with the shared type containing it.
+pub(crate) struct Item<'a> {
+    pub(crate) client: u64,
+    pub(crate) clock: u64,
+    pub(crate) len: u64,
+    /// Unknown for garbage collected structs.
+    pub(crate) owner: Option<Owner<'a>>,
+    /// A key of a map entry.
+    pub(crate) key: Option<&'a str>,
+    pub(crate) is_text: bool,
+}
+
+/// Reads structs of a lib0 v1 encoded document state, together with its delete set. Items only
+/// reference their parent and map key if they have no origin, so these are resolved through
+/// their origins, which always have the same parent.
+pub(crate) fn read_items(state: &[u8]) -> PyResult<(Vec<Item>, DeleteSet)> {
+    let mut reader = Reader::new(state);
+    let structs = read_structs(&mut reader)?;
+    let deletes = read_delete_set(&mut reader)?;
+    let mut items = Vec::new();
+    let mut origins = Vec::new();
+    let mut index: HashMap<u64, Vec<(u64, usize)>> = HashMap::new();
+    for (client, _, blocks) in structs.iter() {
+        for block in blocks.iter().filter(|block| !block.is_skip()) {
+            index
+                .entry(*client)
+                .or_default()
+                .push((block.clock, items.len()));
+            let (owner, origin) = match block.parent {
+                Parent::None => (None, None),
+                Parent::Root(name) => (Some(Owner::Root(name)), None),
+                Parent::Type(client, clock) => (Some(Owner::Type(client, clock)), None),
+                Parent::SameAs(client, clock) => (None, Some((client, clock))),
+            };
+            items.push(Item {
+                client: *client,
+                clock: block.clock,
+                len: block.len,
+                owner,
+                key: block.parent_sub,
+                is_text: block.is_text_content(),
+            });
+            origins.push(origin);
+        }
+    }
+
+    let find = |(client, clock): (u64, u64)| {
+        let blocks = index.get(&client)?;
+        let next = blocks.partition_point(|(start, _)| *start <= clock);
+        next.checked_sub(1).map(|i| blocks[i].1)
+    };
+    for i in 0..items.len() {
+        let mut pending = Vec::new();
+        let mut current = i;
+        // Every origin is followed at most once, which also guards against malformed cycles
+        while items[current].owner.is_none() {
+            match origins[current].take().and_then(find) {
+                Some(origin) => {
+                    pending.push(current);
+                    current = origin;
+                }
+                None => break,
+            }
+        }
+        let (owner, key) = (items[current].owner, items[current].key);
+        for j in pending {
+            items[j].owner = owner;
+            items[j].key = key;
+        }
+    }
+    Ok((items, deletes))
+}
+
+pub(crate) fn read_delete_set(reader: &mut Reader) -> PyResult<DeleteSet> {
+    let mut delete_set = DeleteSet::new();
+    let clients_len = reader.var_uint()?;
+    for _ in 0..clients_len {
+        let client = reader.var_uint()?;
+        let ranges_len = reader.var_uint()?;
+        let ranges = delete_set.entry(client).or_insert_with(Vec::new);
+        for _ in 0..ranges_len {
+            ranges.push((reader.var_uint()?, reader.var_uint()?));
+        }
+    }
+    Ok(delete_set)
+}
+
+pub(crate) fn write_delete_set(buffer: &mut Vec<u8>, delete_set: &DeleteSet) {
+    write_var_uint(buffer, delete_set.len() as u64);
+    for (client, ranges) in delete_set.iter() {
+        write_var_uint(buffer, *client);
+        write_var_uint(buffer, ranges.len() as u64);
+        for (clock, len) in ranges.iter() {
+            write_var_uint(buffer, *clock);
+            write_var_uint(buffer, *len);
+        }
+    }
+}
+
+pub(crate) fn read_state_vector(reader: &mut Reader) -> PyResult<StateVector> {
+    let mut state_vector = StateVector::new();
+    let clients_len = reader.var_uint()?;
+    for _ in 0..clients_len {
+        let client = reader.var_uint()?;
+        state_vector.insert(client, reader.var_uint()?);
+    }
+    Ok(state_vector)
+}
+
+/// Writes an item of a root text `root` without origins, holding a string of a given length.
+pub(crate) fn write_padding(buffer: &mut Vec<u8>, root: &str, len: u64) {
+    buffer.push(BLOCK_ITEM_STRING_REF_NUMBER);
+    write_var_uint(buffer, 1);
+    write_var_string(buffer, root);
+    write_var_string(buffer, &"x".repeat(len as usize));
+}
+
+pub(crate) fn write_var_uint(buffer: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buffer.push(0x80 | (n & 0x7f) as u8);
+        n >>= 7;
+    }
+    buffer.push(n as u8);
+}
+
+fn write_var_string(buffer: &mut Vec<u8>, s: &str) {
+    write_var_uint(buffer, s.len() as u64);
+    buffer.extend_from_slice(s.as_bytes());
+}
+
+fn invalid(offset: usize, reason: &str) -> PyErr {
+    EncodingError::new_err(format!("Malformed data at byte {offset}: {reason}"))
+}
+
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    pub(crate) fn finish(&self) -> PyResult<()> {
+        if self.pos != self.data.len() {
+            return Err(invalid(self.pos, "unexpected trailing bytes"));
+        }
+        Ok(())
+    }
+
+    /// Skips a delete set following update structs.
+    fn finish_delete_set(&mut self) -> PyResult<()> {
+        read_delete_set(self)?;
+        self.finish()
+    }
+
+    fn byte(&mut self) -> PyResult<u8> {
+        let byte = *self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| invalid(self.pos, "unexpected end of data"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, len: usize) -> PyResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| invalid(self.pos, "unexpected end of data"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn var_uint(&mut self) -> PyResult<u64> {
+        let start = self.pos;
+        let mut n = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift >= 64 {
+                return Err(invalid(start, "variable length integer is too long"));
+            }
+            n |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Skips a variable length integer, either signed or unsigned.
+    fn skip_var(&mut self) -> PyResult<()> {
+        while self.byte()? & 0x80 != 0 {}
+        Ok(())
+    }
+
+    fn id(&mut self) -> PyResult<(u64, u64)> {
+        Ok((self.var_uint()?, self.var_uint()?))
+    }
+
+    fn skip_var_bytes(&mut self) -> PyResult<()> {
+        let len = self.var_uint()? as usize;
+        self.bytes(len)?;
+        Ok(())
+    }
+
+    fn var_string(&mut self) -> PyResult<&'a str> {
+        let start = self.pos;
+        let len = self.var_uint()? as usize;
+        std::str::from_utf8(self.bytes(len)?).map_err(|_| invalid(start, "malformed string"))
+    }
+
+    /// Reads a value written using lib0 `Any` encoding, nested at a given `depth`, and records
+    /// its sizes.
+    fn measure_any(&mut self, depth: usize, size: &mut ContentSize) -> PyResult<()> {
+        match self.data.get(self.pos) {
+            // string
+            Some(119) => {
+                self.byte()?;
+                let len = self.var_string()?.encode_utf16().count() as u64;
+                size.longest_string = size.longest_string.max(len);
+            }
+            // map
+            Some(118) => {
+                self.byte()?;
+                size.depth = size.depth.max(depth + 1);
+                for _ in 0..self.var_uint()? {
+                    self.skip_var_bytes()?;
+                    self.measure_any(depth + 1, size)?;
+                }
+            }
+            // array
+            Some(117) => {
+                self.byte()?;
+                size.depth = size.depth.max(depth + 1);
+                for _ in 0..self.var_uint()? {
+                    self.measure_any(depth + 1, size)?;
+                }
+            }
+            _ => self.skip_any()?,
+        }
+        Ok(())
+    }
+
+    /// Skips a value written using lib0 `Any` encoding.
+    fn skip_any(&mut self) -> PyResult<()> {
+        let start = self.pos;
+        match self.byte()? {
+            // undefined, null, true and false
+            127 | 126 | 121 | 120 => {}
+            // integer
+            125 => self.skip_var()?,
+            // float32
+            124 => {
+                self.bytes(4)?;
+            }
+            // float64 and bigint
+            123 | 122 => {
+                self.bytes(8)?;
+            }
+            // string and buffer
+            119 | 116 => self.skip_var_bytes()?,
+            // map
+            118 => {
+                for _ in 0..self.var_uint()? {
+                    self.skip_var_bytes()?;
+                    self.skip_any()?;
+                }
+            }
+            // array
+            117 => {
+                for _ in 0..self.var_uint()? {
+                    self.skip_any()?;
+                }
+            }
+            other => return Err(invalid(start, &format!("unknown value type {other}"))),
+        }
+        Ok(())
+    }
+}
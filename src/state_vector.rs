@@ -0,0 +1,145 @@
+use crate::snapshot::{read_state_vector, write_var_uint, Reader};
+use crate::type_conversions::BinaryInput;
+use pyo3::prelude::*;
+use pyo3::pyclass::CompareOp;
+use pyo3::types::{PyBytes, PyDict, PyList};
+use std::collections::{BTreeMap, HashMap};
+
+/// A state vector of a document, which maps ids of clients to the next clocks expected from them.
+/// It describes which updates a document has seen, eg. to compute updates missing from a peer
+/// with `encode_state_as_update`, which accepts `StateVector` instances wherever an encoded state
+/// vector is expected.
+///
+/// State vectors are partially ordered: `a >= b` if `a` dominates `b`, which means that a
+/// document with state `a` has seen all updates seen by a document with state `b`. Vectors of
+/// replicas which have made concurrent changes are neither greater nor smaller than each other.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import StateVector
+///
+/// remote = StateVector.decode(payload)
+/// if not doc.state_vector() >= remote:
+///     request_missing_updates(remote)
+/// ```
+#[pyclass]
+#[derive(Clone, Default, PartialEq)]
+pub struct StateVector(BTreeMap<u64, u64>);
+
+#[pymethods]
+impl StateVector {
+    /// Creates a state vector with given `clocks` of clients, which is empty by default.
+    #[new]
+    pub fn new(clocks: Option<HashMap<u64, u64>>) -> Self {
+        let clocks = clocks.unwrap_or_default();
+        StateVector(clocks.into_iter().filter(|(_, clock)| *clock > 0).collect())
+    }
+
+    /// Decodes a state vector encoded using lib0 v1 encoding, eg. by `encode_state_vector`.
+    #[staticmethod]
+    pub fn decode(data: BinaryInput) -> PyResult<Self> {
+        let mut reader = Reader::new(&data);
+        let clocks = read_state_vector(&mut reader)?;
+        reader.finish()?;
+        Ok(StateVector(clocks))
+    }
+
+    /// Encodes this state vector using lib0 v1 encoding.
+    pub fn encode(&self, py: Python) -> PyObject {
+        PyBytes::new(py, &self.encode_v1()).into()
+    }
+
+    /// Checks if this state vector has seen all clocks of `other` state vector.
+    pub fn dominates(&self, other: &StateVector) -> bool {
+        other
+            .0
+            .iter()
+            .all(|(client, clock)| self.__getitem__(*client) >= *clock)
+    }
+
+    /// Returns a new state vector with the greater clock of every client of this and `other`
+    /// state vector.
+    pub fn merge(&self, other: &StateVector) -> StateVector {
+        let mut clocks = self.0.clone();
+        for (client, clock) in other.0.iter() {
+            let entry = clocks.entry(*client).or_default();
+            *entry = (*entry).max(*clock);
+        }
+        StateVector(clocks)
+    }
+
+    /// Returns a clock of a given client, which is 0 for unknown clients.
+    pub fn __getitem__(&self, client: u64) -> u64 {
+        self.0.get(&client).copied().unwrap_or(0)
+    }
+
+    pub fn __contains__(&self, client: u64) -> bool {
+        self.0.contains_key(&client)
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns an iterator over ids of clients.
+    pub fn __iter__(&self, py: Python) -> PyResult<PyObject> {
+        Ok(PyList::new(py, self.0.keys()).as_ref().iter()?.into())
+    }
+
+    /// Returns a list of `(client, clock)` pairs, ordered by client ids.
+    pub fn items(&self) -> Vec<(u64, u64)> {
+        self.0
+            .iter()
+            .map(|(client, clock)| (*client, *clock))
+            .collect()
+    }
+
+    /// Returns clocks of clients as a dictionary.
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        for (client, clock) in self.0.iter() {
+            dict.set_item(client, clock)?;
+        }
+        Ok(dict.into())
+    }
+
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyObject {
+        let py = other.py();
+        let other = match other.extract::<PyRef<StateVector>>() {
+            Ok(other) => other,
+            Err(_) => return py.NotImplemented(),
+        };
+        let other = &*other;
+        let result = match op {
+            CompareOp::Eq => self == other,
+            CompareOp::Ne => self != other,
+            CompareOp::Ge => self.dominates(other),
+            CompareOp::Le => other.dominates(self),
+            CompareOp::Gt => self != other && self.dominates(other),
+            CompareOp::Lt => self != other && other.dominates(self),
+        };
+        result.into_py(py)
+    }
+
+    pub fn __repr__(&self) -> String {
+        let clocks: Vec<String> = self
+            .0
+            .iter()
+            .map(|(client, clock)| format!("{client}: {clock}"))
+            .collect();
+        format!("StateVector({{{}}})", clocks.join(", "))
+    }
+}
+
+impl StateVector {
+    pub(crate) fn encode_v1(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        write_var_uint(&mut buffer, self.0.len() as u64);
+        for (client, clock) in self.0.iter() {
+            write_var_uint(&mut buffer, *client);
+            write_var_uint(&mut buffer, *clock);
+        }
+        buffer
+    }
+}
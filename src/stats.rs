@@ -0,0 +1,188 @@
+use crate::snapshot::{is_deleted, read_delete_set, read_structs, Block, Parent, Reader};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::{BTreeMap, HashMap};
+use std::mem::size_of;
+use std::rc::Rc;
+use yrs::block::ItemContent;
+use yrs::ID;
+
+/// Sizes of a document, computed from its full state update.
+#[derive(Default)]
+pub(crate) struct DocStats<'a> {
+    blocks: usize,
+    deleted_blocks: usize,
+    gc_blocks: usize,
+    delete_set_ranges: usize,
+    /// Number of blocks and their encoded size in bytes, per root type.
+    roots: BTreeMap<&'a str, (usize, usize)>,
+}
+
+impl<'a> DocStats<'a> {
+    /// Computes statistics of a document given its full state update, using lib0 v1 encoding.
+    pub(crate) fn from_update(update: &'a [u8]) -> PyResult<Self> {
+        let mut reader = Reader::new(update);
+        let structs = read_structs(&mut reader)?;
+        let delete_set = read_delete_set(&mut reader)?;
+        reader.finish()?;
+
+        let mut stats = DocStats {
+            delete_set_ranges: delete_set.values().map(Vec::len).sum(),
+            ..DocStats::default()
+        };
+        let blocks: Vec<(u64, &Block)> = structs
+            .iter()
+            .flat_map(|(client, _, blocks)| blocks.iter().map(move |block| (*client, block)))
+            .collect();
+        let roots = resolve_roots(&blocks);
+        for ((client, block), root) in blocks.iter().zip(roots) {
+            stats.blocks += 1;
+            if is_deleted(&delete_set, *client, block.clock, block.len) {
+                stats.deleted_blocks += 1;
+            }
+            if block.is_deleted_content() {
+                stats.gc_blocks += 1;
+            }
+            if let Some(root) = root {
+                let entry = stats.roots.entry(root).or_insert((0, 0));
+                entry.0 += 1;
+                entry.1 += block.size;
+            }
+        }
+        Ok(stats)
+    }
+
+    pub(crate) fn into_py_dict(self, py: Python) -> PyResult<&PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("blocks", self.blocks)?;
+        dict.set_item("deleted_blocks", self.deleted_blocks)?;
+        dict.set_item("gc_blocks", self.gc_blocks)?;
+        dict.set_item("delete_set_ranges", self.delete_set_ranges)?;
+        let roots = PyDict::new(py);
+        for (name, (blocks, size)) in self.roots {
+            let root = PyDict::new(py);
+            root.set_item("blocks", blocks)?;
+            root.set_item("size", size)?;
+            roots.set_item(name, root)?;
+        }
+        dict.set_item("roots", roots)?;
+        Ok(dict)
+    }
+}
+
+/// Approximate size of a block struct, which yrs doesn't expose. It's summed from sizes of fields
+/// of an item: its id and origins, content, parent key, flags and pointers to its parent, moved
+/// item and neighbours.
+const BLOCK_SIZE: usize = 3 * size_of::<Option<ID>>()
+    + size_of::<ItemContent>()
+    + size_of::<Option<Rc<str>>>()
+    + size_of::<u32>()
+    + 5 * size_of::<usize>();
+
+/// Estimated numbers of bytes held in memory by a document, computed from its full state update.
+/// Every block takes a boxed `Block` struct, and items which content hasn't been garbage
+/// collected additionally hold their content, which takes roughly as many bytes as its encoding.
+#[derive(Default)]
+pub(crate) struct MemoryUsage {
+    /// Bytes held by blocks which haven't been deleted.
+    blocks: usize,
+    /// Bytes held by deleted blocks, including garbage collected ones.
+    deleted: usize,
+    /// Bytes held by the delete set of the document.
+    delete_set: usize,
+}
+
+impl MemoryUsage {
+    /// Estimates memory usage of a document given its full state update, using lib0 v1 encoding.
+    pub(crate) fn from_update(update: &[u8]) -> PyResult<Self> {
+        let mut reader = Reader::new(update);
+        let structs = read_structs(&mut reader)?;
+        let delete_set = read_delete_set(&mut reader)?;
+        reader.finish()?;
+
+        let block_size = BLOCK_SIZE + size_of::<usize>();
+        let range_size = 2 * size_of::<u32>();
+        let mut usage = MemoryUsage {
+            delete_set: delete_set.values().map(Vec::len).sum::<usize>() * range_size,
+            ..MemoryUsage::default()
+        };
+        for (client, _, blocks) in &structs {
+            for block in blocks.iter().filter(|block| !block.is_skip()) {
+                if block.is_deleted_content() {
+                    usage.deleted += block_size;
+                } else if is_deleted(&delete_set, *client, block.clock, block.len) {
+                    usage.deleted += block_size + block.size;
+                } else {
+                    usage.blocks += block_size + block.size;
+                }
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Converts this estimate into a dictionary, given a size of pending updates which can't be
+    /// integrated yet.
+    pub(crate) fn into_py_dict(self, py: Python, pending: usize) -> PyResult<&PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("blocks", self.blocks)?;
+        dict.set_item("deleted", self.deleted)?;
+        dict.set_item("delete_set", self.delete_set)?;
+        dict.set_item("pending", pending)?;
+        let total = self.blocks + self.deleted + self.delete_set + pending;
+        dict.set_item("total", total)?;
+        Ok(dict)
+    }
+}
+
+/// Finds a name of the root type containing each of given blocks. Items only reference their
+/// parents directly if they have no origins, so parents are followed through chains of origins.
+fn resolve_roots<'a>(blocks: &[(u64, &Block<'a>)]) -> Vec<Option<&'a str>> {
+    let mut clients: HashMap<u64, Vec<(u64, u64, usize)>> = HashMap::new();
+    for (index, (client, block)) in blocks.iter().enumerate() {
+        let end = block.clock.saturating_add(block.len);
+        clients
+            .entry(*client)
+            .or_default()
+            .push((block.clock, end, index));
+    }
+    for ranges in clients.values_mut() {
+        ranges.sort_unstable();
+    }
+    let find = |client: u64, clock: u64| -> Option<usize> {
+        let ranges = clients.get(&client)?;
+        let i = ranges.partition_point(|(_, end, _)| *end <= clock);
+        match ranges.get(i) {
+            Some((start, _, index)) if *start <= clock => Some(*index),
+            _ => None,
+        }
+    };
+
+    let mut resolved: Vec<Option<Option<&'a str>>> = vec![None; blocks.len()];
+    for start in 0..blocks.len() {
+        let mut chain = Vec::new();
+        let mut index = start;
+        let root = loop {
+            if let Some(root) = resolved[index] {
+                break root;
+            }
+            // Malformed updates may contain cycles
+            if chain.len() > blocks.len() {
+                break None;
+            }
+            chain.push(index);
+            let next = match blocks[index].1.parent {
+                Parent::None => break None,
+                Parent::Root(name) => break Some(name),
+                Parent::Type(client, clock) | Parent::SameAs(client, clock) => find(client, clock),
+            };
+            match next {
+                Some(next) => index = next,
+                None => break None,
+            }
+        };
+        for index in chain {
+            resolved[index] = Some(root);
+        }
+    }
+    resolved.into_iter().map(Option::flatten).collect()
+}
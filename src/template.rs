@@ -0,0 +1,159 @@
+use crate::shared_types::{check_root_type, root_branches, SharedType};
+use crate::type_conversions::plain_any;
+use crate::y_array::YArray;
+use crate::y_map::YMap;
+use crate::y_text::YText;
+use crate::y_transaction::YTransaction;
+use crate::y_xml::{YXmlElement, YXmlText};
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyString, PyType};
+use pyo3::AsPyPointer;
+use std::ops::DerefMut;
+use yrs::types::{TYPE_REFS_XML_ELEMENT, TYPE_REFS_XML_TEXT};
+use yrs::Doc;
+
+/// Initial content of a root type, declared by a spec passed to `YDoc.apply_template`.
+pub(crate) enum Template {
+    Text(String),
+    Array(Py<PyList>),
+    Map(Py<PyDict>),
+    XmlElement,
+    XmlText,
+}
+
+impl Template {
+    /// Reads a template of a root type `name`. Plain strings, lists and dictionaries declare
+    /// `YText`, `YArray` and `YMap` root types, as do preliminary shared types, while Ypy
+    /// classes declare empty root types of their kind. Contents are converted right away, so
+    /// that invalid values are reported before any root type is changed.
+    pub(crate) fn from_py(name: &str, value: &PyAny) -> PyResult<Template> {
+        let py = value.py();
+        let template = if let Ok(text) = value.downcast::<PyString>() {
+            Template::Text(text.to_str()?.to_string())
+        } else if let Ok(list) = value.downcast::<PyList>() {
+            Template::Array(list.into())
+        } else if let Ok(dict) = value.downcast::<PyDict>() {
+            Template::Map(dict.into())
+        } else if let Ok(text) = value.downcast::<PyCell<YText>>() {
+            match &text.borrow().0 {
+                SharedType::Prelim(text) => Template::Text(text.clone()),
+                SharedType::Integrated(_) => return Err(integrated(name)),
+            }
+        } else if let Ok(array) = value.downcast::<PyCell<YArray>>() {
+            match &array.borrow().0 {
+                SharedType::Prelim(items) => Template::Array(PyList::new(py, items).into()),
+                SharedType::Integrated(_) => return Err(integrated(name)),
+            }
+        } else if let Ok(map) = value.downcast::<PyCell<YMap>>() {
+            match &map.borrow().0 {
+                SharedType::Prelim(entries) => {
+                    let dict = PyDict::new(py);
+                    for (key, value) in entries.iter() {
+                        dict.set_item(key, value)?;
+                    }
+                    Template::Map(dict.into())
+                }
+                SharedType::Integrated(_) => return Err(integrated(name)),
+            }
+        } else if let Ok(class) = value.downcast::<PyType>() {
+            let is = |other: &PyType| class.as_ptr() == other.as_ptr();
+            if is(py.get_type::<YText>()) {
+                Template::Text(String::new())
+            } else if is(py.get_type::<YArray>()) {
+                Template::Array(PyList::empty(py).into())
+            } else if is(py.get_type::<YMap>()) {
+                Template::Map(PyDict::new(py).into())
+            } else if is(py.get_type::<YXmlElement>()) {
+                Template::XmlElement
+            } else if is(py.get_type::<YXmlText>()) {
+                Template::XmlText
+            } else {
+                return Err(unsupported(name, value));
+            }
+        } else {
+            return Err(unsupported(name, value));
+        };
+        match &template {
+            Template::Array(items) => {
+                plain_any(items.as_ref(py))?;
+            }
+            Template::Map(entries) => {
+                plain_any(entries.as_ref(py))?;
+            }
+            _ => {}
+        }
+        Ok(template)
+    }
+
+    /// Creates a root type `name` of a given document and fills it with the content of this
+    /// template, unless the root type already has content. Returns `true` if the root type has
+    /// been created or filled.
+    pub(crate) fn apply(
+        self,
+        py: Python,
+        doc: &Doc,
+        txn: &mut YTransaction,
+        name: &str,
+    ) -> PyResult<bool> {
+        let existed = root_branches(doc)
+            .iter()
+            .any(|(root, _)| root.as_ref() == name);
+        let filled = match self {
+            Template::Text(content) => {
+                let mut root = txn.get_text(name)?;
+                if root.__len__() > 0 {
+                    return Ok(false);
+                }
+                root.extend(txn, &content)?;
+                !content.is_empty()
+            }
+            Template::Array(items) => {
+                let mut root = txn.get_array(name)?;
+                if root.__len__() > 0 {
+                    return Ok(false);
+                }
+                let items = items.as_ref(py);
+                root.extend(py, txn, items.into())?;
+                !items.is_empty()
+            }
+            Template::Map(entries) => {
+                let mut root = txn.get_map(name)?;
+                if root.__len__() > 0 {
+                    return Ok(false);
+                }
+                let entries = entries.as_ref(py);
+                root.update(txn, entries.into())?;
+                !entries.is_empty()
+            }
+            Template::XmlElement => {
+                let root = txn.deref_mut().get_xml_element(name);
+                check_root_type(root.as_ref(), name, TYPE_REFS_XML_ELEMENT)?;
+                false
+            }
+            Template::XmlText => {
+                let root = txn.deref_mut().get_xml_text(name);
+                check_root_type(root.as_ref(), name, TYPE_REFS_XML_TEXT)?;
+                false
+            }
+        };
+        Ok(filled || !existed)
+    }
+}
+
+fn integrated(name: &str) -> PyErr {
+    PyTypeError::new_err(format!(
+        "Template of root type '{name}' must be a preliminary shared type, found an integrated one"
+    ))
+}
+
+fn unsupported(name: &str, value: &PyAny) -> PyErr {
+    let found = value
+        .get_type()
+        .name()
+        .map(str::to_string)
+        .unwrap_or_default();
+    PyTypeError::new_err(format!(
+        "Template of root type '{name}' must be a string, a list, a dictionary, a preliminary shared type or a Ypy shared type class, found {found}"
+    ))
+}
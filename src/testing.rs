@@ -0,0 +1,47 @@
+use crate::awareness::Awareness;
+use crate::local_hub::LocalHub;
+use crate::y_doc::YDoc;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::wrap_pyfunction;
+
+/// Connects two documents, and optionally a pair of `awareness` instances of their users, with a
+/// simulated unreliable network, so that resilience of applications can be tested
+/// deterministically.
+///
+/// Each message is lost with a probability of `drop_rate`, and otherwise delayed by a random
+/// number of up to `delay_ms` milliseconds, which reorders messages sent close to each other.
+/// Lost messages are never sent again. All random choices are derived from a `seed`, so a failing
+/// test can be replayed. Returns the `LocalHub` exchanging the messages, whose simulated time is
+/// moved forward with `advance`, and whose `flush` delivers all messages still in flight.
+#[pyfunction]
+pub fn lossy_channel(
+    py: Python,
+    doc_a: Py<YDoc>,
+    doc_b: Py<YDoc>,
+    drop_rate: Option<f64>,
+    delay_ms: Option<f64>,
+    seed: Option<u64>,
+    awareness: Option<(Py<Awareness>, Py<Awareness>)>,
+) -> PyResult<Py<LocalHub>> {
+    let delay_ms = delay_ms.unwrap_or(0.0);
+    if !delay_ms.is_finite() || delay_ms < 0.0 {
+        return Err(PyValueError::new_err(
+            "delay_ms must be a non-negative number of milliseconds",
+        ));
+    }
+    let hub = LocalHub::new(None, Some(delay_ms / 1000.0), drop_rate, seed)?;
+    let hub = Py::new(py, hub)?;
+    let (awareness_a, awareness_b) = match awareness {
+        Some((a, b)) => (Some(a), Some(b)),
+        None => (None, None),
+    };
+    LocalHub::join(hub.as_ref(py), py, doc_a, awareness_a)?;
+    LocalHub::join(hub.as_ref(py), py, doc_b, awareness_b)?;
+    Ok(hub)
+}
+
+pub(crate) fn register(m: &PyModule) -> PyResult<()> {
+    m.add_wrapped(wrap_pyfunction!(lossy_channel))?;
+    Ok(())
+}
@@ -1,21 +1,31 @@
 use lib0::any::Any;
+use pyo3::buffer::PyBuffer;
 use pyo3::create_exception;
-use pyo3::exceptions::PyException;
 use pyo3::exceptions::PyTypeError;
+use pyo3::exceptions::PyValueError;
+use pyo3::ffi;
+use pyo3::intern;
+use pyo3::once_cell::GILOnceCell;
 use pyo3::prelude::*;
 use pyo3::types as pytypes;
 use pyo3::types::PyList;
-use std::collections::HashMap;
+use pyo3::AsPyPointer;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
-use std::convert::TryInto;
 use std::ops::Deref;
+use std::os::raw::{c_int, c_void};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use yrs::block::{ItemContent, Prelim};
 use yrs::types::Events;
 use yrs::types::{Attrs, Branch, BranchPtr, Change, Delta, EntryChange, Value};
 use yrs::{Array, Map, Text, Transaction};
 
+use crate::location::{Location, Observed};
+use crate::metrics::{self, Metric};
 use crate::shared_types::CompatiblePyType;
-use crate::shared_types::{SharedType, YPyType};
+use crate::shared_types::{IntegrationError, SharedType, YPyType};
+use crate::state_vector::StateVector;
 use crate::y_array::YArray;
 use crate::y_array::YArrayEvent;
 use crate::y_map::YMap;
@@ -26,7 +36,125 @@ use crate::y_xml::YXmlEvent;
 use crate::y_xml::YXmlTextEvent;
 use crate::y_xml::{YXmlElement, YXmlText};
 
-create_exception!(y_py, MultipleIntegrationError, PyException, "A Ypy data type instance cannot be integrated into multiple YDocs or the same YDoc multiple times");
+create_exception!(y_py, MultipleIntegrationError, IntegrationError, "A Ypy data type instance cannot be integrated into multiple YDocs or the same YDoc multiple times");
+
+/// If set, `Any::Undefined` values are converted into the `Undefined` sentinel rather than `None`.
+static UNDEFINED_AS_SENTINEL: AtomicBool = AtomicBool::new(false);
+/// If set, `Any::Buffer` values are converted into mutable `bytearray` objects rather than `bytes`.
+static BUFFER_AS_BYTEARRAY: AtomicBool = AtomicBool::new(false);
+/// If set, binary values and encoded payloads are converted into read-only `memoryview` objects
+/// sharing memory with Ypy rather than `bytes`.
+static BUFFER_AS_MEMORYVIEW: AtomicBool = AtomicBool::new(false);
+/// Maximum nesting depth of Python values converted into `Any`. Zero means no limit.
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(0);
+/// Maximum number of elements of Python values converted into `Any`. Zero means no limit.
+static MAX_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns true if `undefined` values are converted into `Undefined` rather than `None`.
+pub(crate) fn undefined_as_sentinel() -> bool {
+    UNDEFINED_AS_SENTINEL.load(Ordering::Relaxed)
+}
+
+/// Configures how values stored in Ypy documents are converted into Python objects. Options which
+/// are not provided keep their current setting.
+///
+/// - `undefined_sentinel`: Yjs distinguishes between `null` and `undefined` values. Both of them
+///   are converted into `None` by default. When this option is enabled, `undefined` values are
+///   returned as `y_py.Undefined` instead.
+/// - `bytearray_buffers`: binary values are returned as immutable `bytes` by default. When this
+///   option is enabled, they are returned as `bytearray` objects instead.
+/// - `memoryview_buffers`: binary values and payloads encoded by Ypy (updates, state vectors and
+///   delete sets) are copied into `bytes` by default. When this option is enabled, they are
+///   returned as read-only `memoryview` objects sharing memory with Ypy instead, so that large
+///   payloads are never copied. `bytearray_buffers` takes precedence for binary values.
+/// - `max_depth`: maximum nesting depth of lists and dictionaries inserted into shared types.
+///   Deeper values raise a `ValueError`. Zero (the default) means no limit.
+/// - `max_size`: maximum number of elements (counting nested ones) of a single value inserted into
+///   shared types. Larger values raise a `ValueError`. Zero (the default) means no limit.
+#[pyfunction]
+pub fn set_conversion_options(
+    undefined_sentinel: Option<bool>,
+    bytearray_buffers: Option<bool>,
+    memoryview_buffers: Option<bool>,
+    max_depth: Option<usize>,
+    max_size: Option<usize>,
+) {
+    if let Some(undefined_sentinel) = undefined_sentinel {
+        UNDEFINED_AS_SENTINEL.store(undefined_sentinel, Ordering::Relaxed);
+    }
+    if let Some(bytearray_buffers) = bytearray_buffers {
+        BUFFER_AS_BYTEARRAY.store(bytearray_buffers, Ordering::Relaxed);
+    }
+    if let Some(memoryview_buffers) = memoryview_buffers {
+        BUFFER_AS_MEMORYVIEW.store(memoryview_buffers, Ordering::Relaxed);
+    }
+    if let Some(max_depth) = max_depth {
+        MAX_DEPTH.store(max_depth, Ordering::Relaxed);
+    }
+    if let Some(max_size) = max_size {
+        MAX_SIZE.store(max_size, Ordering::Relaxed);
+    }
+}
+
+/// Converts a binary payload produced by Ypy into a Python object: a `bytes` copy by default, or a
+/// read-only `memoryview` taking ownership of the payload if `memoryview_buffers` are enabled.
+pub(crate) fn binary_into_py(py: Python, bytes: Vec<u8>) -> PyObject {
+    if BUFFER_AS_MEMORYVIEW.load(Ordering::Relaxed) {
+        let exporter = Py::new(py, BinaryOutput(bytes)).unwrap();
+        unsafe { PyObject::from_owned_ptr(py, ffi::PyMemoryView_FromObject(exporter.as_ptr())) }
+    } else {
+        pytypes::PyBytes::new(py, &bytes).into()
+    }
+}
+
+/// Owner of a binary payload exposed to Python through the buffer protocol. Memory views created
+/// over it keep it alive.
+#[pyclass]
+pub struct BinaryOutput(Vec<u8>);
+
+#[pymethods]
+impl BinaryOutput {
+    unsafe fn __getbuffer__(
+        slf: PyRefMut<Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        let ptr = slf.0.as_ptr() as *mut c_void;
+        let len = slf.0.len() as ffi::Py_ssize_t;
+        if ffi::PyBuffer_FillInfo(view, slf.as_ptr(), ptr, len, 1, flags) == -1 {
+            return Err(PyErr::fetch(slf.py()));
+        }
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(&self, _view: *mut ffi::Py_buffer) {}
+}
+
+/// A sentinel value representing JavaScript `undefined`, as opposed to `None` which represents
+/// `null`. Inserting `y_py.Undefined` into a shared type always stores an `undefined` value.
+#[pyclass(name = "UndefinedType")]
+pub struct Undefined;
+
+impl Undefined {
+    /// Returns the `y_py.Undefined` singleton.
+    pub fn instance(py: Python) -> PyObject {
+        static INSTANCE: GILOnceCell<PyObject> = GILOnceCell::new();
+        INSTANCE
+            .get_or_init(py, || Py::new(py, Undefined).unwrap().into_py(py))
+            .clone_ref(py)
+    }
+}
+
+#[pymethods]
+impl Undefined {
+    pub fn __repr__(&self) -> &'static str {
+        "Undefined"
+    }
+
+    pub fn __bool__(&self) -> bool {
+        false
+    }
+}
 
 pub trait ToPython {
     fn into_py(self, py: Python) -> PyObject;
@@ -64,14 +192,41 @@ impl<'a> TryFrom<&'a PyAny> for CompatiblePyType<'a> {
             Ok(Self::Int(i))
         } else if py_any.is_none() {
             Ok(Self::None)
+        } else if py_any.is_instance_of::<Undefined>()? {
+            Ok(Self::Undefined)
         } else if let Ok(f) = py_any.downcast::<pytypes::PyFloat>() {
             Ok(Self::Float(f))
         } else if let Ok(s) = py_any.downcast::<pytypes::PyString>() {
             Ok(Self::String(s))
+        } else if let Ok(bytes) = py_any.downcast::<pytypes::PyBytes>() {
+            Ok(Self::Bytes(bytes))
+        } else if py_any.downcast::<pytypes::PyByteArray>().is_ok() {
+            Ok(Self::Buffer(py_any))
         } else if let Ok(list) = py_any.downcast::<pytypes::PyList>() {
             Ok(Self::List(list))
         } else if let Ok(dict) = py_any.downcast::<pytypes::PyDict>() {
             Ok(Self::Dict(dict))
+        } else if let Ok(tuple) = py_any.downcast::<pytypes::PyTuple>() {
+            Ok(Self::List(PyList::new(py_any.py(), tuple)))
+        } else if py_any.is_instance_of::<pytypes::PySet>()?
+            || py_any.is_instance_of::<pytypes::PyFrozenSet>()?
+        {
+            // Sets are stored as arrays, sorted to keep the element order deterministic.
+            let sorted = py_any
+                .py()
+                .import("builtins")?
+                .call_method1("sorted", (py_any,))
+                .map_err(|err| {
+                    PyTypeError::new_err(format!(
+                        "Cannot integrate a set with elements that cannot be sorted: {err}"
+                    ))
+                })?;
+            Ok(Self::List(sorted.downcast::<pytypes::PyList>()?))
+        } else if is_ndarray(py_any) {
+            // NumPy arrays are stored as their nested list equivalent.
+            Self::try_from(py_any.call_method0("tolist")?)
+        } else if PyBuffer::<u8>::get(py_any).is_ok() {
+            Ok(Self::Buffer(py_any))
         } else if let Ok(v) = YPyType::try_from(py_any) {
             Ok(Self::YType(v))
         } else {
@@ -82,6 +237,78 @@ impl<'a> TryFrom<&'a PyAny> for CompatiblePyType<'a> {
     }
 }
 
+/// A binary payload (such as an encoded update or state vector) received from Python. Payloads
+/// stored in `bytes` objects or in other read-only, C-contiguous byte buffers (such as a
+/// `memoryview` of `bytes`) are borrowed without copying. Writable buffers (`bytearray` etc.) are
+/// copied once, so that they can't change while being read, and lists of integers are still
+/// accepted. `StateVector` instances are encoded, so that they can be passed instead of encoded
+/// state vectors.
+pub enum BinaryInput<'a> {
+    Borrowed(&'a [u8]),
+    Buffer(PyBuffer<u8>),
+    Owned(Vec<u8>),
+}
+
+impl<'a> BinaryInput<'a> {
+    pub fn into_boxed_slice(self) -> Box<[u8]> {
+        match self {
+            BinaryInput::Owned(bytes) => bytes.into_boxed_slice(),
+            other => other.deref().into(),
+        }
+    }
+}
+
+impl<'a> Deref for BinaryInput<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            BinaryInput::Borrowed(bytes) => bytes,
+            // Only read-only, C-contiguous buffers are borrowed, and the buffer is held until the
+            // payload is dropped, so its memory stays valid and unchanged while it's read.
+            BinaryInput::Buffer(buffer) => unsafe {
+                std::slice::from_raw_parts(buffer.buf_ptr() as *const u8, buffer.len_bytes())
+            },
+            BinaryInput::Owned(bytes) => bytes.as_slice(),
+        }
+    }
+}
+
+impl<'a> FromPyObject<'a> for BinaryInput<'a> {
+    fn extract(ob: &'a PyAny) -> PyResult<Self> {
+        if let Ok(bytes) = ob.downcast::<pytypes::PyBytes>() {
+            Ok(BinaryInput::Borrowed(bytes.as_bytes()))
+        } else if let Ok(state_vector) = ob.extract::<PyRef<StateVector>>() {
+            Ok(BinaryInput::Owned(state_vector.encode_v1()))
+        } else if let Ok(buffer) = PyBuffer::<u8>::get(ob) {
+            if buffer.readonly() && buffer.is_c_contiguous() {
+                Ok(BinaryInput::Buffer(buffer))
+            } else {
+                Ok(BinaryInput::Owned(buffer.to_vec(ob.py())?))
+            }
+        } else {
+            Ok(BinaryInput::Owned(ob.extract()?))
+        }
+    }
+}
+
+/// Extracts a dictionary key, which must be a string to be stored in a shared type.
+pub(crate) fn dict_key(key: &PyAny) -> PyResult<String> {
+    key.extract().map_err(|_| {
+        let type_name = key.get_type().name().unwrap_or("unknown");
+        PyTypeError::new_err(format!(
+            "Dictionary keys must be strings, found key {key:?} of type '{type_name}'"
+        ))
+    })
+}
+
+/// Checks if a given object is a NumPy array (or scalar). NumPy is not a dependency of Ypy, so
+/// these objects are recognized by the array interface they expose.
+fn is_ndarray(py_any: &PyAny) -> bool {
+    py_any.hasattr("__array_interface__").unwrap_or(false)
+        && py_any.hasattr("tolist").unwrap_or(false)
+}
+
 impl<'a> FromPyObject<'a> for CompatiblePyType<'a> {
     fn extract(ob: &'a PyAny) -> PyResult<Self> {
         Self::try_from(ob)
@@ -95,39 +322,39 @@ impl ToPython for Delta {
         match self {
             Delta::Inserted(value, attrs) => {
                 let value = value.clone().into_py(py);
-                result.set_item("insert", value).unwrap();
+                result.set_item(intern!(py, "insert"), value).unwrap();
 
                 if let Some(attrs) = attrs {
-                    let attrs = attrs_into_py(attrs.deref());
-                    result.set_item("attributes", attrs).unwrap();
+                    let attrs = attrs_into_py(py, attrs.deref());
+                    result.set_item(intern!(py, "attributes"), attrs).unwrap();
                 }
             }
             Delta::Retain(len, attrs) => {
-                result.set_item("retain", len).unwrap();
+                result.set_item(intern!(py, "retain"), len).unwrap();
 
                 if let Some(attrs) = attrs {
-                    let attrs = attrs_into_py(attrs.deref());
-                    result.set_item("attributes", attrs).unwrap();
+                    let attrs = attrs_into_py(py, attrs.deref());
+                    result.set_item(intern!(py, "attributes"), attrs).unwrap();
                 }
             }
             Delta::Deleted(len) => {
-                result.set_item("delete", len).unwrap();
+                result.set_item(intern!(py, "delete"), len).unwrap();
             }
         }
         result.into()
     }
 }
 
-fn attrs_into_py(attrs: &Attrs) -> PyObject {
-    Python::with_gil(|py| {
-        let o = pytypes::PyDict::new(py);
-        for (key, value) in attrs.iter() {
-            let key = key.as_ref();
-            let value = Value::Any(value.clone()).into_py(py);
-            o.set_item(key, value).unwrap();
-        }
-        o.into()
-    })
+fn attrs_into_py(py: Python, attrs: &Attrs) -> PyObject {
+    let o = pytypes::PyDict::new(py);
+    for (key, value) in attrs.iter() {
+        // Formatting attributes come from a small, repeating set of names, so reuse them
+        // instead of allocating a new string for every delta entry.
+        let key = pytypes::PyString::intern(py, key.as_ref());
+        let value = value.clone().into_py(py);
+        o.set_item(key, value).unwrap();
+    }
+    o.into()
 }
 
 impl ToPython for &Change {
@@ -137,13 +364,13 @@ impl ToPython for &Change {
             Change::Added(values) => {
                 let values: Vec<PyObject> =
                     values.into_iter().map(|v| v.clone().into_py(py)).collect();
-                result.set_item("insert", values).unwrap();
+                result.set_item(intern!(py, "insert"), values).unwrap();
             }
             Change::Removed(len) => {
-                result.set_item("delete", len).unwrap();
+                result.set_item(intern!(py, "delete"), len).unwrap();
             }
             Change::Retain(len) => {
-                result.set_item("retain", len).unwrap();
+                result.set_item(intern!(py, "retain"), len).unwrap();
             }
         }
         result.into()
@@ -156,24 +383,24 @@ struct EntryChangeWrapper<'a>(&'a EntryChange);
 impl<'a> IntoPy<PyObject> for EntryChangeWrapper<'a> {
     fn into_py(self, py: Python) -> PyObject {
         let result = pytypes::PyDict::new(py);
-        let action = "action";
+        let action = intern!(py, "action");
         match self.0 {
             EntryChange::Inserted(new) => {
                 let new_value = new.clone().into_py(py);
-                result.set_item(action, "add").unwrap();
-                result.set_item("newValue", new_value).unwrap();
+                result.set_item(action, intern!(py, "add")).unwrap();
+                result.set_item(intern!(py, "newValue"), new_value).unwrap();
             }
             EntryChange::Updated(old, new) => {
                 let old_value = old.clone().into_py(py);
                 let new_value = new.clone().into_py(py);
-                result.set_item(action, "update").unwrap();
-                result.set_item("oldValue", old_value).unwrap();
-                result.set_item("newValue", new_value).unwrap();
+                result.set_item(action, intern!(py, "update")).unwrap();
+                result.set_item(intern!(py, "oldValue"), old_value).unwrap();
+                result.set_item(intern!(py, "newValue"), new_value).unwrap();
             }
             EntryChange::Removed(old) => {
                 let old_value = old.clone().into_py(py);
-                result.set_item(action, "delete").unwrap();
-                result.set_item("oldValue", old_value).unwrap();
+                result.set_item(action, intern!(py, "delete")).unwrap();
+                result.set_item(intern!(py, "oldValue"), old_value).unwrap();
             }
         }
         result.into()
@@ -197,11 +424,70 @@ impl Deref for PyObjectWrapper {
     }
 }
 
+/// A Python value converted before it's inserted into a shared type, so that a value which can't
+/// be converted raises an error before anything is inserted. Preliminary shared types are kept as
+/// they are, to be integrated once the item holding them is created.
+pub(crate) enum PreparedValue {
+    Any(Any),
+    Prelim(PyObjectWrapper),
+}
+
+impl PreparedValue {
+    pub(crate) fn new(value: &PyAny) -> PyResult<Self> {
+        match CompatiblePyType::try_from(value)? {
+            CompatiblePyType::YType(y_type) if y_type.is_prelim() => {
+                Ok(PreparedValue::Prelim(PyObjectWrapper(value.into())))
+            }
+            py_type => Ok(PreparedValue::Any(Any::try_from(py_type)?)),
+        }
+    }
+}
+
+impl Prelim for PreparedValue {
+    fn into_content(self, txn: &mut Transaction) -> (ItemContent, Option<Self>) {
+        match self {
+            PreparedValue::Any(any) => (ItemContent::Any(vec![any]), None),
+            PreparedValue::Prelim(wrapper) => {
+                let (content, wrapper) = wrapper.into_content(txn);
+                (content, wrapper.map(PreparedValue::Prelim))
+            }
+        }
+    }
+
+    fn integrate(self, txn: &mut Transaction, inner_ref: BranchPtr) {
+        if let PreparedValue::Prelim(wrapper) = self {
+            wrapper.integrate(txn, inner_ref);
+        }
+    }
+}
+
+thread_local! {
+    /// An error raised while converting or integrating a Python value inserted into a shared type.
+    /// `Prelim` methods can't return errors, so it's kept here until the operation which inserted
+    /// the value raises it with `take_integration_error`.
+    static INTEGRATION_ERROR: RefCell<Option<PyErr>> = RefCell::new(None);
+}
+
+/// Keeps an error raised within a `Prelim` method. Only the first error of an insertion is kept.
+fn defer_integration_error(err: PyErr) {
+    INTEGRATION_ERROR.with(|error| {
+        error.borrow_mut().get_or_insert(err);
+    });
+}
+
+/// Raises the error kept by a `Prelim` method during the last insertion into a shared type, if any.
+pub(crate) fn take_integration_error() -> PyResult<()> {
+    match INTEGRATION_ERROR.with(|error| error.borrow_mut().take()) {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
 impl Prelim for PyObjectWrapper {
     fn into_content(self, txn: &mut Transaction) -> (ItemContent, Option<Self>) {
         Python::with_gil(|py| {
             let valid_type: CompatiblePyType = self.0.extract(py).unwrap_or_else(|err| {
-                err.restore(py);
+                defer_integration_error(err);
                 CompatiblePyType::None
             });
             let (item_content, py_any) = valid_type.into_content(txn);
@@ -213,7 +499,7 @@ impl Prelim for PyObjectWrapper {
     fn integrate(self, txn: &mut Transaction, inner_ref: BranchPtr) {
         Python::with_gil(|py| {
             let valid_type: CompatiblePyType = self.0.extract(py).unwrap_or_else(|err| {
-                err.restore(py);
+                defer_integration_error(err);
                 CompatiblePyType::None
             });
             valid_type.integrate(txn, inner_ref);
@@ -228,10 +514,13 @@ impl<'a> From<CompatiblePyType<'a>> for PyObject {
             CompatiblePyType::Int(i) => i.into(),
             CompatiblePyType::Float(f) => f.into(),
             CompatiblePyType::String(s) => s.into(),
+            CompatiblePyType::Bytes(b) => b.into(),
+            CompatiblePyType::Buffer(buffer) => buffer.into(),
             CompatiblePyType::List(list) => list.into(),
             CompatiblePyType::Dict(dict) => dict.into(),
             CompatiblePyType::YType(y_type) => y_type.into(),
             CompatiblePyType::None => Python::with_gil(|py| py.None()),
+            CompatiblePyType::Undefined => Python::with_gil(Undefined::instance),
         }
     }
 }
@@ -247,7 +536,7 @@ impl<'a> Prelim for CompatiblePyType<'a> {
         };
 
         let content = content.unwrap_or_else(|err| {
-            Python::with_gil(|py| err.restore(py));
+            defer_integration_error(err);
             ItemContent::Any(vec![])
         });
 
@@ -272,32 +561,46 @@ impl<'a> Prelim for CompatiblePyType<'a> {
                             text.push(txn, v.as_str());
                         }
                         y_text.0 = SharedType::Integrated(text.clone());
+                        y_text.1 = Location::current();
                     }
                     YPyType::Array(v) => {
                         let array = Array::from(inner_ref);
                         let mut y_array = v.borrow_mut();
+                        // Shared types nested in the array are located within it
+                        let location = Location::current();
                         if let SharedType::Prelim(items) = y_array.0.to_owned() {
                             let len = array.len();
-                            YArray::insert_multiple_at(&array, txn, len, items);
+                            let inserted = location.within(&inner_ref, || {
+                                YArray::insert_multiple_at(&array, txn, len, items)
+                            });
+                            if let Err(err) = inserted {
+                                defer_integration_error(err);
+                            }
                         }
                         y_array.0 = SharedType::Integrated(array.clone());
+                        y_array.1 = location;
                     }
                     YPyType::Map(v) => {
                         let map = Map::from(inner_ref);
                         let mut y_map = v.borrow_mut();
+                        let location = Location::current();
                         Python::with_gil(|py| {
                             if let SharedType::Prelim(ref entries) = y_map.0 {
                                 for (k, v) in entries {
-                                    let x: CompatiblePyType = v.extract(py).unwrap_or_else(|err| {
-                                        err.restore(py);
-                                        CompatiblePyType::None
-                                    });
-                                    map.insert(txn, k.to_owned(), x);
+                                    match PreparedValue::new(v.as_ref(py)) {
+                                        Ok(value) => {
+                                            location.within(&inner_ref, || {
+                                                map.insert(txn, k.to_owned(), value)
+                                            });
+                                        }
+                                        Err(err) => defer_integration_error(err),
+                                    }
                                 }
                             }
                         });
                         
                         y_map.0 = SharedType::Integrated(map.clone());
+                        y_map.1 = location;
                     }
                     YPyType::XmlElement(_) | YPyType::XmlText(_) => unreachable!("As defined in Shared::is_prelim(), neither XML type can ever exist outside a YDoc"),
                 }
@@ -311,42 +614,207 @@ impl<'a> TryFrom<CompatiblePyType<'a>> for Any {
     type Error = PyErr;
 
     fn try_from(py_type: CompatiblePyType<'a>) -> Result<Self, Self::Error> {
+        metrics::measure(Metric::Conversions, || AnyConverter::new().convert(py_type))
+    }
+}
+
+/// Converts a Python value into the plain value it would be read as once inserted into a shared
+/// type. Unlike `Any::try_from`, shared types are accepted and replaced by their content.
+pub(crate) fn plain_any(value: &PyAny) -> PyResult<Any> {
+    let mut converter = AnyConverter::new();
+    converter.shared_types = true;
+    converter.convert(CompatiblePyType::try_from(value)?)
+}
+
+/// A container whose elements are being converted into `Any` values.
+struct ConversionFrame<'a> {
+    container: *mut pyo3::ffi::PyObject,
+    children: std::vec::IntoIter<(Option<String>, &'a PyAny)>,
+    key: Option<String>,
+    array: Vec<Any>,
+    map: Option<HashMap<String, Any>>,
+}
+
+impl<'a> ConversionFrame<'a> {
+    fn push(&mut self, value: Any) {
+        match (&mut self.map, self.key.take()) {
+            (Some(map), Some(key)) => {
+                map.insert(key, value);
+            }
+            _ => self.array.push(value),
+        }
+    }
+
+    fn into_any(self) -> Any {
+        match self.map {
+            Some(map) => Any::Map(Box::new(map)),
+            None => Any::Array(self.array.into_boxed_slice()),
+        }
+    }
+}
+
+/// Converts Python values into their `Any` representation. Nested lists and dictionaries are
+/// traversed using an explicit stack rather than recursion, so that deeply nested inputs cannot
+/// exhaust the native stack. Conversion fails with a `ValueError` when the input contains itself
+/// or when it exceeds the limits configured with `set_conversion_options`.
+struct AnyConverter<'a> {
+    stack: Vec<ConversionFrame<'a>>,
+    ancestors: HashSet<*mut pyo3::ffi::PyObject>,
+    size: usize,
+    max_depth: usize,
+    max_size: usize,
+    /// Set when shared types are converted into their content rather than rejected.
+    shared_types: bool,
+}
+
+impl<'a> AnyConverter<'a> {
+    fn new() -> Self {
+        AnyConverter {
+            stack: Vec::new(),
+            ancestors: HashSet::new(),
+            size: 0,
+            max_depth: MAX_DEPTH.load(Ordering::Relaxed),
+            max_size: MAX_SIZE.load(Ordering::Relaxed),
+            shared_types: false,
+        }
+    }
+
+    fn convert(mut self, value: CompatiblePyType<'a>) -> PyResult<Any> {
+        let mut current = value;
+        loop {
+            let mut converted = self.visit(current)?;
+            // Pass converted values up the stack until a container with unconverted elements is found.
+            loop {
+                let frame = match self.stack.last_mut() {
+                    Some(frame) => frame,
+                    None => return Ok(converted.expect("root value has been converted")),
+                };
+                if let Some(value) = converted.take() {
+                    frame.push(value);
+                }
+                if let Some((key, child)) = frame.children.next() {
+                    frame.key = key;
+                    current = CompatiblePyType::try_from(child)?;
+                    break;
+                }
+                let frame = self.stack.pop().unwrap();
+                self.ancestors.remove(&frame.container);
+                converted = Some(frame.into_any());
+            }
+        }
+    }
+
+    /// Converts a primitive value, or starts the conversion of a container by pushing it onto
+    /// the stack, in which case `None` is returned.
+    fn visit(&mut self, value: CompatiblePyType<'a>) -> PyResult<Option<Any>> {
         const MAX_JS_NUMBER: i64 = 2_i64.pow(53) - 1;
-        match py_type {
-            CompatiblePyType::Bool(b) => Ok(Any::Bool(b.extract()?)),
-            CompatiblePyType::String(s) => Ok(Any::String(s.extract::<String>()?.into_boxed_str())),
+        self.size += 1;
+        if self.max_size != 0 && self.size > self.max_size {
+            return Err(PyValueError::new_err(format!(
+                "Value exceeds the maximum size of {} elements",
+                self.max_size
+            )));
+        }
+        let any = match value {
+            CompatiblePyType::Bool(b) => Any::Bool(b.extract()?),
+            CompatiblePyType::String(s) => Any::String(s.extract::<String>()?.into_boxed_str()),
+            CompatiblePyType::Bytes(b) => Any::Buffer(b.as_bytes().into()),
+            CompatiblePyType::Buffer(buffer) => {
+                Any::Buffer(BinaryInput::extract(buffer)?.into_boxed_slice())
+            }
             CompatiblePyType::Int(i) => {
                 let num: i64 = i.extract()?;
                 if num > MAX_JS_NUMBER {
-                    Ok(Any::BigInt(num))
+                    Any::BigInt(num)
                 } else {
-                    Ok(Any::Number(num as f64))
+                    Any::Number(num as f64)
                 }
             }
-            CompatiblePyType::Float(f) => Ok(Any::Number(f.extract()?)),
+            CompatiblePyType::Float(f) => Any::Number(f.extract()?),
             CompatiblePyType::List(l) => {
-                let result: PyResult<Vec<Any>> = l
-                    .into_iter()
-                    .map(|py_any|CompatiblePyType::try_from(py_any)?.try_into())
-                    .collect();
-                result.map(|res| Any::Array(res.into_boxed_slice()))
-            },
+                let children: Vec<_> = l.iter().map(|item| (None, item)).collect();
+                self.enter(l, children, None)?;
+                return Ok(None);
+            }
             CompatiblePyType::Dict(d) => {
-                let result: PyResult<HashMap<String, Any>> = d
+                let children = d
                     .iter()
-                    .map(|(k, v)| {
-                        let key: String = k.extract()?;
-                        let value = CompatiblePyType::try_from(v)?.try_into()?;
-                        Ok((key, value))
-                    })
-                    .collect();
-                result.map(|res| Any::Map(Box::new(res)))
-            },
-            CompatiblePyType::None => Ok(Any::Null),
-            CompatiblePyType::YType(v) => Err(MultipleIntegrationError::new_err(format!(
+                    .map(|(k, v)| Ok((Some(dict_key(k)?), v)))
+                    .collect::<PyResult<Vec<_>>>()?;
+                self.enter(d, children, Some(HashMap::with_capacity(d.len())))?;
+                return Ok(None);
+            }
+            CompatiblePyType::None => Any::Null,
+            CompatiblePyType::Undefined => Any::Undefined,
+            CompatiblePyType::YType(v) if self.shared_types => return self.visit_shared(v),
+            CompatiblePyType::YType(v) => {
+                return Err(MultipleIntegrationError::new_err(format!(
                     "Cannot integrate a nested Ypy object because is already integrated into a YDoc: {v}"
-                ))),
+                )))
+            }
+        };
+        Ok(Some(any))
+    }
+
+    /// Converts a shared type into the value of its content: texts and XML nodes become strings,
+    /// while contents of preliminary arrays and maps are traversed like lists and dictionaries.
+    fn visit_shared(&mut self, value: YPyType<'a>) -> PyResult<Option<Any>> {
+        let any = match value {
+            YPyType::Text(text) => Any::String(text.borrow().__str__().into_boxed_str()),
+            YPyType::XmlElement(xml) => Any::String(xml.borrow().__str__().into_boxed_str()),
+            YPyType::XmlText(xml) => Any::String(xml.borrow().__str__().into_boxed_str()),
+            YPyType::Array(cell) => match &cell.borrow().0 {
+                SharedType::Integrated(array) => array.to_json(),
+                SharedType::Prelim(items) => {
+                    let py = cell.py();
+                    let children = items
+                        .iter()
+                        .map(|item| (None, item.clone_ref(py).into_ref(py)))
+                        .collect();
+                    self.enter(cell, children, None)?;
+                    return Ok(None);
+                }
+            },
+            YPyType::Map(cell) => match &cell.borrow().0 {
+                SharedType::Integrated(map) => map.to_json(),
+                SharedType::Prelim(entries) => {
+                    let py = cell.py();
+                    let children = entries
+                        .iter()
+                        .map(|(key, value)| (Some(key.clone()), value.clone_ref(py).into_ref(py)))
+                        .collect();
+                    self.enter(cell, children, Some(HashMap::with_capacity(entries.len())))?;
+                    return Ok(None);
+                }
+            },
+        };
+        Ok(Some(any))
+    }
+
+    /// Pushes a container onto the conversion stack, failing if it's one of its own ancestors.
+    fn enter(
+        &mut self,
+        container: &PyAny,
+        children: Vec<(Option<String>, &'a PyAny)>,
+        map: Option<HashMap<String, Any>>,
+    ) -> PyResult<()> {
+        if self.max_depth != 0 && self.stack.len() >= self.max_depth {
+            return Err(PyValueError::new_err(format!(
+                "Value exceeds the maximum nesting depth of {}",
+                self.max_depth
+            )));
         }
+        if !self.ancestors.insert(container.as_ptr()) {
+            return Err(PyValueError::new_err("circular reference"));
+        }
+        self.stack.push(ConversionFrame {
+            container: container.as_ptr(),
+            array: Vec::with_capacity(if map.is_none() { children.len() } else { 0 }),
+            children: children.into_iter(),
+            key: None,
+            map,
+        });
+        Ok(())
     }
 }
 
@@ -389,30 +857,30 @@ impl<'a> TryFrom<&'a PyAny> for YPyType<'a> {
 impl ToPython for Any {
     fn into_py(self, py: Python) -> pyo3::PyObject {
         match self {
-            Any::Null | Any::Undefined => py.None(),
+            Any::Null => py.None(),
+            Any::Undefined if UNDEFINED_AS_SENTINEL.load(Ordering::Relaxed) => {
+                Undefined::instance(py)
+            }
+            Any::Undefined => py.None(),
             Any::Bool(v) => v.into_py(py),
             Any::Number(v) => v.into_py(py),
             Any::BigInt(v) => v.into_py(py),
             Any::String(v) => v.into_py(py),
-            Any::Buffer(v) => {
+            Any::Buffer(v) if BUFFER_AS_BYTEARRAY.load(Ordering::Relaxed) => {
                 let byte_array = pytypes::PyByteArray::new(py, v.as_ref());
                 byte_array.into()
             }
+            Any::Buffer(v) => binary_into_py(py, v.into_vec()),
             Any::Array(v) => {
-                let mut a = Vec::new();
-                for value in v.iter() {
-                    let value = value.to_owned();
-                    a.push(value);
-                }
-                a.into_py(py)
+                let values = v.into_vec().into_iter().map(|value| value.into_py(py));
+                PyList::new(py, values).into()
             }
             Any::Map(v) => {
-                let mut m = HashMap::new();
-                for (k, v) in v.iter() {
-                    let value = v.to_owned();
-                    m.insert(k, value);
+                let dict = pytypes::PyDict::new(py);
+                for (key, value) in *v {
+                    dict.set_item(key, value.into_py(py)).unwrap();
                 }
-                m.into_py(py)
+                dict.into()
             }
         }
     }
@@ -420,26 +888,37 @@ impl ToPython for Any {
 
 impl ToPython for Value {
     fn into_py(self, py: Python) -> pyo3::PyObject {
-        match self {
+        metrics::measure(Metric::Conversions, || match self {
             Value::Any(v) => v.into_py(py),
-            Value::YText(v) => YText::from(v).into_py(py),
-            Value::YArray(v) => YArray::from(v).into_py(py),
-            Value::YMap(v) => YMap::from(v).into_py(py),
-            Value::YXmlElement(v) => YXmlElement(v).into_py(py),
-            Value::YXmlText(v) => YXmlText(v).into_py(py),
-        }
+            Value::YText(v) => YText::located(v, Location::current()).into_py(py),
+            Value::YArray(v) => YArray::located(v, Location::current()).into_py(py),
+            Value::YMap(v) => YMap::located(v, Location::current()).into_py(py),
+            Value::YXmlElement(v) => YXmlElement(v, Location::current()).into_py(py),
+            Value::YXmlText(v) => YXmlText(v, Location::current()).into_py(py),
+        })
     }
 }
 
-pub(crate) fn events_into_py(txn: &Transaction, events: &Events) -> PyObject {
-    Python::with_gil(|py| {
-        let py_events = events.iter().map(|event| match event {
-            yrs::types::Event::Text(e_txt) => YTextEvent::new(e_txt, txn).into_py(py),
-            yrs::types::Event::Array(e_arr) => YArrayEvent::new(e_arr, txn).into_py(py),
-            yrs::types::Event::Map(e_map) => YMapEvent::new(e_map, txn).into_py(py),
-            yrs::types::Event::XmlElement(e_xml) => YXmlEvent::new(e_xml, txn).into_py(py),
-            yrs::types::Event::XmlText(e_xml) => YXmlTextEvent::new(e_xml, txn).into_py(py),
-        });
-        PyList::new(py, py_events).into()
-    })
+/// Converts events received by a deep observer of an `observed` shared type into a Python list.
+pub(crate) fn events_into_py(
+    py: Python,
+    txn: &Transaction,
+    events: &Events,
+    observed: &Observed,
+) -> PyObject {
+    let py_events = events.iter().map(|event| {
+        let observed = observed.clone();
+        match event {
+            yrs::types::Event::Text(e_txt) => YTextEvent::new(e_txt, txn, observed).into_py(py),
+            yrs::types::Event::Array(e_arr) => YArrayEvent::new(e_arr, txn, observed).into_py(py),
+            yrs::types::Event::Map(e_map) => YMapEvent::new(e_map, txn, observed).into_py(py),
+            yrs::types::Event::XmlElement(e_xml) => {
+                YXmlEvent::new(e_xml, txn, observed).into_py(py)
+            }
+            yrs::types::Event::XmlText(e_xml) => {
+                YXmlTextEvent::new(e_xml, txn, observed).into_py(py)
+            }
+        }
+    });
+    PyList::new(py, py_events).into()
 }
@@ -0,0 +1,164 @@
+use crate::shared_types::EncodingError;
+use crate::y_doc::YDoc;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use yrs::SubscriptionId;
+
+/// Updates collected by an `UpdateBuffer`, oldest first.
+#[derive(Default)]
+struct Pending {
+    updates: Vec<Vec<u8>>,
+    /// Time of arrival of the oldest update which hasn't been compacted yet.
+    since: Option<Instant>,
+}
+
+impl Pending {
+    /// Merges all pending updates into a single one.
+    fn compact(&mut self) -> PyResult<()> {
+        if self.updates.len() > 1 {
+            let updates: Vec<&[u8]> = self.updates.iter().map(Vec::as_slice).collect();
+            let merged = yrs::merge_updates_v1(&updates)
+                .map_err(|e| EncodingError::new_err(e.to_string()))?;
+            self.updates = vec![merged];
+        }
+        self.since = None;
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.updates.iter().map(Vec::len).sum()
+    }
+}
+
+/// Collects updates of a document while they cannot be sent, eg. when an offline-first client has
+/// no connection to its server. Pending updates are merged into a single update once there are
+/// `max_updates` of them, or once the oldest of them is older than `max_delay` seconds, so the
+/// buffer doesn't grow with every single edit. Updates are encoded using lib0 v1 encoding.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YDoc, UpdateBuffer
+///
+/// doc = YDoc()
+/// buffer = UpdateBuffer(doc, max_updates=50)
+/// ...
+/// update = buffer.drain()
+/// if update is not None:
+///     send(update)
+/// ```
+#[pyclass(unsendable)]
+pub struct UpdateBuffer {
+    pending: Rc<RefCell<Pending>>,
+    /// Cleared once the buffer is closed, which detaches its update observer.
+    active: Rc<Cell<bool>>,
+}
+
+#[pymethods]
+impl UpdateBuffer {
+    #[new]
+    pub fn new(
+        doc: &mut YDoc,
+        max_updates: Option<usize>,
+        max_delay: Option<f64>,
+    ) -> PyResult<Self> {
+        let max_updates = max_updates.unwrap_or(100);
+        if max_updates == 0 {
+            return Err(PyValueError::new_err(
+                "max_updates must be a positive number",
+            ));
+        }
+        let max_delay = match max_delay {
+            Some(delay) if !delay.is_finite() || delay < 0.0 => {
+                return Err(PyValueError::new_err(
+                    "max_delay must be a non-negative number of seconds",
+                ))
+            }
+            delay => delay.map(Duration::from_secs_f64),
+        };
+        let buffer = UpdateBuffer {
+            pending: Rc::default(),
+            active: Rc::new(Cell::new(true)),
+        };
+        let pending = buffer.pending.clone();
+        let active = buffer.active.clone();
+        // The observer stays attached to the document, it's silenced once the buffer is closed
+        let _: SubscriptionId = doc
+            .inner
+            .observe_transaction_cleanup(move |txn, event| {
+                let unchanged =
+                    event.before_state == event.after_state && event.delete_set.is_empty();
+                if !active.get() || unchanged {
+                    return;
+                }
+                let mut pending = pending.borrow_mut();
+                pending.updates.push(txn.encode_update_v1());
+                let since = *pending.since.get_or_insert_with(Instant::now);
+                let overdue = max_delay.map_or(false, |delay| since.elapsed() >= delay);
+                if pending.updates.len() >= max_updates || overdue {
+                    if let Err(err) = pending.compact() {
+                        Python::with_gil(|py| err.restore(py))
+                    }
+                }
+            })
+            .into();
+        Ok(buffer)
+    }
+
+    /// Merges all pending updates into a single update right away.
+    pub fn compact(&self) -> PyResult<()> {
+        self.pending.borrow_mut().compact()
+    }
+
+    /// Returns all pending updates merged into a single update and empties the buffer. Returns
+    /// `None` if there are no pending updates.
+    pub fn drain(&self, py: Python) -> PyResult<Option<PyObject>> {
+        let mut pending = self.pending.borrow_mut();
+        pending.compact()?;
+        Ok(pending
+            .updates
+            .pop()
+            .map(|update| PyBytes::new(py, &update).into()))
+    }
+
+    /// Returns a total number of bytes of all pending updates.
+    #[getter]
+    pub fn size(&self) -> usize {
+        self.pending.borrow().size()
+    }
+
+    /// Returns true if this buffer no longer collects updates of its document.
+    #[getter]
+    pub fn closed(&self) -> bool {
+        !self.active.get()
+    }
+
+    /// Stops collecting updates. Updates which are already pending can still be drained.
+    pub fn close(&self) {
+        self.active.set(false);
+    }
+
+    /// Returns a number of pending updates.
+    pub fn __len__(&self) -> usize {
+        self.pending.borrow().updates.len()
+    }
+
+    pub fn __repr__(&self) -> String {
+        let pending = self.pending.borrow();
+        format!(
+            "UpdateBuffer(updates={}, size={})",
+            pending.updates.len(),
+            pending.size()
+        )
+    }
+}
+
+impl Drop for UpdateBuffer {
+    fn drop(&mut self) {
+        self.active.set(false);
+    }
+}
@@ -0,0 +1,447 @@
+use crate::shared_types::YError;
+use crate::snapshot::{read_delete_set, read_structs, Block, Parent, Reader};
+use pyo3::create_exception;
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyTuple};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+use yrs::updates::encoder::{Encoder, EncoderV1};
+use yrs::{StateVector, Transaction};
+
+create_exception!(
+    y_py,
+    UpdateRejectedError,
+    YError,
+    "Occurs when an update is rejected by a filter registered with YDoc.add_update_filter."
+);
+
+create_exception!(
+    y_py,
+    LimitExceededError,
+    UpdateRejectedError,
+    "Occurs when an update exceeds limits set with YDoc.set_limits."
+);
+
+/// A location of a change: a name of a root type followed by keys of map entries leading to the
+/// changed shared type or map entry, where `None` stands for elements of sequences.
+type Path<'a> = Vec<Option<&'a str>>;
+
+#[derive(Default)]
+struct Filters {
+    next_id: u32,
+    callbacks: Vec<(u32, PyObject)>,
+    limits: Limits,
+    /// Statistics of applied updates per client id, if they're collected.
+    client_stats: Option<HashMap<u64, ClientStats>>,
+    /// Upper clocks and sizes of applied updates which blocks are still waiting for the blocks
+    /// they depend on.
+    pending: Vec<(StateVector, usize)>,
+    /// Parents of integrated structs, which are read only while filters are registered.
+    known: Known,
+}
+
+/// Amounts of data applied to a document by a single client.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct ClientStats {
+    /// Number of updates containing changes of the client.
+    pub(crate) updates: u64,
+    /// Number of structs, ie. individual insertions, of the client.
+    pub(crate) operations: u64,
+    /// Number of bytes taken by the structs.
+    pub(crate) bytes: u64,
+}
+
+/// Limits of remote updates set with `YDoc.set_limits`, where zero means no limit.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Limits {
+    /// Maximum size of an update, in bytes.
+    pub(crate) max_update_size: usize,
+    /// Maximum length of a single string inserted by an update, in UTF-16 code units.
+    pub(crate) max_string_length: u64,
+    /// Maximum nesting depth of lists and maps of a single value inserted by an update.
+    pub(crate) max_depth: usize,
+}
+
+/// Filters of remote updates registered with `YDoc.add_update_filter`, shared by a document and
+/// its transactions.
+#[derive(Clone, Default)]
+pub(crate) struct UpdateFilters(Rc<RefCell<Filters>>);
+
+impl UpdateFilters {
+    pub(crate) fn add(&self, callback: PyObject) -> u32 {
+        let mut filters = self.0.borrow_mut();
+        let id = filters.next_id;
+        filters.next_id += 1;
+        filters.callbacks.push((id, callback));
+        id
+    }
+
+    pub(crate) fn remove(&self, id: u32) {
+        self.0
+            .borrow_mut()
+            .callbacks
+            .retain(|(filter_id, _)| *filter_id != id);
+    }
+
+    pub(crate) fn limits(&self) -> Limits {
+        self.0.borrow().limits
+    }
+
+    pub(crate) fn set_limits(&self, limits: Limits) {
+        self.0.borrow_mut().limits = limits;
+    }
+
+    /// Starts collecting statistics of applied updates, unless they are already collected.
+    pub(crate) fn enable_client_stats(&self) {
+        self.0
+            .borrow_mut()
+            .client_stats
+            .get_or_insert_with(HashMap::new);
+    }
+
+    /// Stops collecting statistics of applied updates, and drops the collected ones.
+    pub(crate) fn disable_client_stats(&self) {
+        self.0.borrow_mut().client_stats = None;
+    }
+
+    /// Returns statistics collected so far, or `None` if they aren't collected.
+    pub(crate) fn client_stats(&self) -> Option<HashMap<u64, ClientStats>> {
+        self.0.borrow().client_stats.clone()
+    }
+
+    /// Records an applied lib0 v1 encoded `update` in statistics, if they're collected.
+    pub(crate) fn record(&self, update: &[u8]) -> PyResult<()> {
+        if self.0.borrow().client_stats.is_none() {
+            return Ok(());
+        }
+        let mut reader = Reader::new(update);
+        let clients = read_structs(&mut reader)?;
+        let mut filters = self.0.borrow_mut();
+        let stats = match filters.client_stats.as_mut() {
+            Some(stats) => stats,
+            None => return Ok(()),
+        };
+        for (client, _, blocks) in clients {
+            let blocks: Vec<_> = blocks.iter().filter(|block| !block.is_skip()).collect();
+            if blocks.is_empty() {
+                continue;
+            }
+            let entry = stats.entry(client).or_default();
+            entry.updates += 1;
+            entry.operations += blocks.len() as u64;
+            entry.bytes += blocks.iter().map(|block| block.size as u64).sum::<u64>();
+        }
+        Ok(())
+    }
+
+    /// Remembers an applied update of `size` bytes, which blocks end at `upper` clocks, unless all
+    /// of them have been integrated into a document with a given `state`.
+    pub(crate) fn record_pending(&self, upper: StateVector, size: usize, state: &StateVector) {
+        if !covers(state, &upper) {
+            self.0.borrow_mut().pending.push((upper, size));
+        }
+    }
+
+    /// Returns a number of bytes of applied updates which blocks can't be integrated yet into a
+    /// document with a given `state`, and forgets updates which have been integrated since.
+    pub(crate) fn pending_size(&self, state: &StateVector) -> usize {
+        let mut filters = self.0.borrow_mut();
+        filters.pending.retain(|(upper, _)| !covers(state, upper));
+        filters.pending.iter().map(|(_, size)| size).sum()
+    }
+
+    /// Fails with a `LimitExceededError` if a lib0 v1 encoded `update` exceeds limits of this
+    /// document.
+    pub(crate) fn check_limits(&self, update: &[u8]) -> PyResult<()> {
+        let limits = self.limits();
+        if limits.max_update_size != 0 && update.len() > limits.max_update_size {
+            return Err(LimitExceededError::new_err(format!(
+                "The update has {} bytes (limit: {})",
+                update.len(),
+                limits.max_update_size
+            )));
+        }
+        if limits.max_string_length == 0 && limits.max_depth == 0 {
+            return Ok(());
+        }
+        let mut reader = Reader::new(update);
+        for (client, _, blocks) in read_structs(&mut reader)? {
+            for block in blocks {
+                let size = block.content_size()?;
+                let clock = block.clock;
+                let (len, max) = (size.longest_string, limits.max_string_length);
+                if max != 0 && len > max {
+                    return Err(LimitExceededError::new_err(format!(
+                        "Struct {client}:{clock} inserts a string of {len} characters (limit: {max})"
+                    )));
+                }
+                let (depth, max) = (size.depth, limits.max_depth);
+                if max != 0 && depth > max {
+                    return Err(LimitExceededError::new_err(format!(
+                        "Struct {client}:{clock} inserts a value nested {depth} levels deep (limit: {max})"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Passes locations changed by a lib0 v1 encoded `update` to all filters, and fails with an
+    /// `UpdateRejectedError` if any of them rejects it. `txn` is a transaction of the document
+    /// which is about to integrate the update.
+    pub(crate) fn check(&self, py: Python, txn: &Transaction, update: &[u8]) -> PyResult<()> {
+        // Filters are copied, so that they can be added or removed by filters themselves
+        let callbacks: Vec<PyObject> = self
+            .0
+            .borrow()
+            .callbacks
+            .iter()
+            .map(|(_, callback)| callback.clone_ref(py))
+            .collect();
+        if callbacks.is_empty() {
+            return Ok(());
+        }
+        let paths = PyList::empty(py);
+        {
+            let mut filters = self.0.borrow_mut();
+            filters.known.refresh(txn)?;
+            for path in changed_paths(&filters.known, update)? {
+                paths.append(PyTuple::new(py, path))?;
+            }
+        }
+        for callback in callbacks {
+            if !callback.call1(py, (paths,))?.is_true(py)? {
+                return Err(UpdateRejectedError::new_err(
+                    "The update has been rejected by an update filter",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks if a `state` includes all blocks up to `upper` clocks.
+fn covers(state: &StateVector, upper: &StateVector) -> bool {
+    upper
+        .iter()
+        .all(|(client, clock)| state.get(client) >= *clock)
+}
+
+/// Returns locations of all changes made by a lib0 v1 encoded `update` to a document, which
+/// integrated structs are `known`. Changes which cannot be located, eg. because they depend on
+/// other updates which haven't been received yet, have an empty path.
+fn changed_paths<'a>(known: &'a Known, update: &'a [u8]) -> PyResult<BTreeSet<Path<'a>>> {
+    let mut reader = Reader::new(update);
+    let incoming = read_structs(&mut reader)?;
+    let delete_set = read_delete_set(&mut reader)?;
+    reader.finish()?;
+    let incoming = Index::new(&incoming);
+    let find = |client: u64, clock: u64| {
+        known
+            .find(client, clock)
+            .or_else(|| incoming.find(client, clock).map(Link::of))
+    };
+    let limit = known.len + incoming.len;
+
+    let mut paths = BTreeSet::new();
+    for (client, blocks) in incoming.clients.iter() {
+        let integrated = u64::from(known.state.get(client));
+        for block in blocks.iter() {
+            let is_new = block.clock.saturating_add(block.len) > integrated;
+            if is_new && !matches!(block.parent, Parent::None) {
+                paths.insert(locate(Link::of(block), find, limit));
+            }
+        }
+    }
+    for (client, ranges) in delete_set.iter() {
+        for (clock, len) in ranges {
+            let end = clock.saturating_add(*len);
+            let deleted = known
+                .overlapping(*client, *clock, end)
+                .chain(incoming.overlapping(*client, *clock, end).map(Link::of));
+            for link in deleted {
+                if !matches!(link.parent, Parent::None) {
+                    paths.insert(locate(link, find, limit));
+                }
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// A parent of a struct together with its map key, which is all that's needed to locate it.
+#[derive(Clone, Copy)]
+pub(crate) struct Link<'a> {
+    parent: Parent<'a>,
+    parent_sub: Option<&'a str>,
+}
+
+impl<'a> Link<'a> {
+    pub(crate) fn of(block: &Block<'a>) -> Self {
+        Link {
+            parent: block.parent,
+            parent_sub: block.parent_sub,
+        }
+    }
+}
+
+/// Returns a location of a struct with a given `link`, following parents of nested shared types up
+/// to their root type. Items with origins share a parent and a map key with their origins.
+pub(crate) fn locate<'a, F>(link: Link<'a>, find: F, limit: usize) -> Path<'a>
+where
+    F: Fn(u64, u64) -> Option<Link<'a>>,
+{
+    let mut path = Vec::new();
+    let mut current = link;
+    // Malformed updates may contain cycles
+    for _ in 0..=limit {
+        let (client, clock) = match current.parent {
+            Parent::None => break,
+            Parent::Root(name) => {
+                path.push(current.parent_sub);
+                path.push(Some(name));
+                path.reverse();
+                // Elements of sequences are reported as changes of the sequences themselves
+                if path.last() == Some(&None) {
+                    path.pop();
+                }
+                return path;
+            }
+            Parent::Type(client, clock) => {
+                path.push(current.parent_sub);
+                (client, clock)
+            }
+            Parent::SameAs(client, clock) => (client, clock),
+        };
+        match find(client, clock) {
+            Some(next) => current = next,
+            None => break,
+        }
+    }
+    Vec::new()
+}
+
+/// Blocks of a single update grouped by client id, which allows to find them by their ids.
+pub(crate) struct Index<'b, 'a> {
+    clients: HashMap<u64, &'b [Block<'a>]>,
+    pub(crate) len: usize,
+}
+
+impl<'b, 'a> Index<'b, 'a> {
+    pub(crate) fn new(structs: &'b [(u64, u64, Vec<Block<'a>>)]) -> Self {
+        let mut clients = HashMap::new();
+        let mut len = 0;
+        for (client, _, blocks) in structs {
+            clients.insert(*client, blocks.as_slice());
+            len += blocks.len();
+        }
+        Index { clients, len }
+    }
+
+    pub(crate) fn find(&self, client: u64, clock: u64) -> Option<&'b Block<'a>> {
+        self.overlapping(client, clock, clock.saturating_add(1))
+            .next()
+    }
+
+    /// Returns blocks of a given client overlapping with a range of clocks from `start` to `end`.
+    pub(crate) fn overlapping(
+        &self,
+        client: u64,
+        start: u64,
+        end: u64,
+    ) -> impl Iterator<Item = &'b Block<'a>> {
+        let blocks: &'b [Block<'a>] = self.clients.get(&client).copied().unwrap_or(&[]);
+        let first = blocks.partition_point(|block| block.clock.saturating_add(block.len) <= start);
+        blocks[first..]
+            .iter()
+            .take_while(move |block| block.clock < end)
+    }
+}
+
+/// Links of structs integrated into a document, grouped by client id. They're extended with
+/// structs integrated since they were last refreshed, so that changes can be located without
+/// encoding a whole document for every update.
+#[derive(Default)]
+struct Known {
+    /// State of a document up to which structs have been read.
+    state: StateVector,
+    clients: HashMap<u64, Vec<KnownStruct>>,
+    len: usize,
+}
+
+/// An owned counterpart of a `Link` of a range of structs.
+struct KnownStruct {
+    clock: u64,
+    len: u64,
+    parent: KnownParent,
+    parent_sub: Option<Box<str>>,
+}
+
+enum KnownParent {
+    None,
+    Root(Box<str>),
+    Type(u64, u64),
+    SameAs(u64, u64),
+}
+
+impl Known {
+    /// Reads structs integrated into a document of a given `txn` since the last refresh.
+    fn refresh(&mut self, txn: &Transaction) -> PyResult<()> {
+        let state = txn.state_vector();
+        if state == self.state {
+            return Ok(());
+        }
+        if !covers(&state, &self.state) {
+            // Documents never lose their structs, but start over rather than trust a stale index
+            *self = Known::default();
+        }
+        let mut encoder = EncoderV1::new();
+        txn.encode_diff(&self.state, &mut encoder);
+        let diff = encoder.to_vec();
+        for (client, _, blocks) in read_structs(&mut Reader::new(&diff))? {
+            let known = self.clients.entry(client).or_default();
+            for block in blocks.iter().filter(|block| !block.is_skip()) {
+                let parent = match block.parent {
+                    Parent::None => KnownParent::None,
+                    Parent::Root(name) => KnownParent::Root(name.into()),
+                    Parent::Type(client, clock) => KnownParent::Type(client, clock),
+                    Parent::SameAs(client, clock) => KnownParent::SameAs(client, clock),
+                };
+                known.push(KnownStruct {
+                    clock: block.clock,
+                    len: block.len,
+                    parent,
+                    parent_sub: block.parent_sub.map(Box::from),
+                });
+                self.len += 1;
+            }
+        }
+        self.state = state;
+        Ok(())
+    }
+
+    fn find(&self, client: u64, clock: u64) -> Option<Link<'_>> {
+        self.overlapping(client, clock, clock.saturating_add(1))
+            .next()
+    }
+
+    /// Returns links of structs of a given client overlapping with a range of clocks from `start`
+    /// to `end`.
+    fn overlapping(&self, client: u64, start: u64, end: u64) -> impl Iterator<Item = Link<'_>> {
+        let structs = self.clients.get(&client).map_or(&[][..], Vec::as_slice);
+        let first = structs.partition_point(|known| known.clock.saturating_add(known.len) <= start);
+        structs[first..]
+            .iter()
+            .take_while(move |known| known.clock < end)
+            .map(|known| Link {
+                parent: match &known.parent {
+                    KnownParent::None => Parent::None,
+                    KnownParent::Root(name) => Parent::Root(name),
+                    KnownParent::Type(client, clock) => Parent::Type(*client, *clock),
+                    KnownParent::SameAs(client, clock) => Parent::SameAs(*client, *clock),
+                },
+                parent_sub: known.parent_sub.as_deref(),
+            })
+    }
+}
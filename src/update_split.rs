@@ -0,0 +1,169 @@
+use crate::snapshot::{
+    read_delete_set, read_structs, write_delete_set, write_var_uint, Block, DeleteSet, Reader,
+};
+use crate::type_conversions::{binary_into_py, BinaryInput};
+use crate::update_filter::{locate, Index, Link};
+use crate::y_doc::{diff, YDoc};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::{BTreeMap, BTreeSet};
+use yrs::block::BLOCK_GC_REF_NUMBER;
+use yrs::StateVector;
+
+/// Partitions a lib0 v1 encoded `update` into sub-updates, one per root type changed by it. This
+/// allows to implement selective sync, eg. to only send changes of a "metadata" root type to
+/// some of the peers.
+///
+/// Returns a dictionary of sub-updates keyed by root type names, limited to given `roots` if
+/// they're provided. Changes which cannot be attributed to a root type, eg. because they refer to
+/// items which are not part of the update, are stored under a `None` key. A `doc` containing the
+/// state the update has been produced against can be given to locate such changes.
+///
+/// Structs of a client are numbered consecutively regardless of the root type they belong to.
+/// Structs of other root types are therefore replaced with garbage collected placeholders, which
+/// makes every sub-update complete on its own. As a consequence, a document which applied a
+/// sub-update of one root type ignores changes of other root types made at the same time.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import encode_state_as_update, split_update
+///
+/// update = encode_state_as_update(doc, remote_state_vector)
+/// metadata_update = split_update(update, roots=['metadata']).get('metadata')
+/// ```
+#[pyfunction]
+pub fn split_update(
+    py: Python,
+    update: BinaryInput,
+    roots: Option<Vec<String>>,
+    doc: Option<PyRef<YDoc>>,
+) -> PyResult<PyObject> {
+    let state = match doc {
+        Some(doc) => diff(&doc.inner, &StateVector::default()),
+        None => Vec::new(),
+    };
+    let result = PyDict::new(py);
+    for (root, part) in split(&state, &update)? {
+        if let Some(roots) = &roots {
+            if !roots.iter().any(|name| Some(name.as_str()) == root) {
+                continue;
+            }
+        }
+        result.set_item(root, binary_into_py(py, part))?;
+    }
+    Ok(result.into())
+}
+
+/// Splits an `update` into sub-updates of root types, given a full `state` of the document it has
+/// been produced against (which may be empty). Both of them use lib0 v1 encoding.
+fn split<'a>(state: &'a [u8], update: &'a [u8]) -> PyResult<BTreeMap<Option<&'a str>, Vec<u8>>> {
+    let known = if state.is_empty() {
+        Vec::new()
+    } else {
+        read_structs(&mut Reader::new(state))?
+    };
+    let mut reader = Reader::new(update);
+    let incoming = read_structs(&mut reader)?;
+    let delete_set = read_delete_set(&mut reader)?;
+    reader.finish()?;
+    let known_index = Index::new(&known);
+    let incoming_index = Index::new(&incoming);
+    let find = |client: u64, clock: u64| {
+        known_index
+            .find(client, clock)
+            .or_else(|| incoming_index.find(client, clock))
+    };
+    let limit = known_index.len + incoming_index.len;
+    let root_of = |block| {
+        let find = |client, clock| find(client, clock).map(Link::of);
+        locate(Link::of(block), find, limit)
+            .first()
+            .copied()
+            .flatten()
+    };
+
+    let roots: Vec<Vec<Option<&str>>> = incoming
+        .iter()
+        .map(|(_, _, blocks)| blocks.iter().map(root_of).collect())
+        .collect();
+
+    let mut deletes: BTreeMap<Option<&str>, DeleteSet> = BTreeMap::new();
+    for (client, ranges) in delete_set.iter() {
+        for (clock, len) in ranges.iter() {
+            let end = clock.saturating_add(*len);
+            let mut pos = *clock;
+            while pos < end {
+                let (stop, root) = match find(*client, pos) {
+                    Some(block) => (
+                        block.clock.saturating_add(block.len).min(end),
+                        root_of(block),
+                    ),
+                    None => {
+                        // Deleted items which are unknown are attributed up to the next known one
+                        let next = known_index
+                            .overlapping(*client, pos, end)
+                            .chain(incoming_index.overlapping(*client, pos, end))
+                            .map(|block| block.clock)
+                            .min();
+                        (next.unwrap_or(end), None)
+                    }
+                };
+                let ranges = deletes.entry(root).or_default().entry(*client).or_default();
+                match ranges.last_mut() {
+                    Some((start, len)) if *start + *len == pos => *len += stop - pos,
+                    _ => ranges.push((pos, stop - pos)),
+                }
+                pos = stop;
+            }
+        }
+    }
+
+    let mut names: BTreeSet<Option<&str>> = roots.iter().flatten().copied().collect();
+    names.extend(deletes.keys().copied());
+    let mut parts = BTreeMap::new();
+    for name in names {
+        let mut clients = Vec::new();
+        for ((client, clock, blocks), roots) in incoming.iter().zip(roots.iter()) {
+            if let Some(last) = roots.iter().rposition(|root| *root == name) {
+                clients.push((*client, *clock, write_blocks(&blocks[..=last], roots, name)));
+            }
+        }
+        let mut buffer = Vec::new();
+        write_var_uint(&mut buffer, clients.len() as u64);
+        for (client, clock, (len, structs)) in clients {
+            write_var_uint(&mut buffer, len);
+            write_var_uint(&mut buffer, client);
+            write_var_uint(&mut buffer, clock);
+            buffer.extend_from_slice(&structs);
+        }
+        write_delete_set(&mut buffer, &deletes.remove(&name).unwrap_or_default());
+        parts.insert(name, buffer);
+    }
+    Ok(parts)
+}
+
+/// Encodes consecutive `blocks` of a client, replacing the ones which don't belong to a root type
+/// `name` with garbage collected placeholders. Returns a number of written structs and their
+/// encoding.
+fn write_blocks(blocks: &[Block], roots: &[Option<&str>], name: Option<&str>) -> (u64, Vec<u8>) {
+    let mut buffer = Vec::new();
+    let mut len = 0;
+    let mut gap = 0;
+    for (block, root) in blocks.iter().zip(roots) {
+        // Skipped ranges are unknown to all sub-updates alike
+        if *root == name || block.is_skip() {
+            if gap > 0 {
+                buffer.push(BLOCK_GC_REF_NUMBER);
+                write_var_uint(&mut buffer, gap);
+                len += 1;
+                gap = 0;
+            }
+            block.write(&mut buffer, block.len);
+            len += 1;
+        } else {
+            gap += block.len;
+        }
+    }
+    (len, buffer)
+}
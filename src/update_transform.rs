@@ -0,0 +1,90 @@
+use crate::type_conversions::{binary_into_py, BinaryInput};
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Default)]
+struct Transforms {
+    next_id: u32,
+    /// Pairs of `encode` and `decode` callbacks, in order of their registration.
+    callbacks: Vec<(u32, PyObject, PyObject)>,
+}
+
+/// Transforms of encoded updates registered with `YDoc.add_update_transform`, shared by a
+/// document and its transactions.
+#[derive(Clone, Default)]
+pub(crate) struct UpdateTransforms(Rc<RefCell<Transforms>>);
+
+impl UpdateTransforms {
+    pub(crate) fn add(&self, encode: PyObject, decode: PyObject) -> u32 {
+        let mut transforms = self.0.borrow_mut();
+        let id = transforms.next_id;
+        transforms.next_id += 1;
+        transforms.callbacks.push((id, encode, decode));
+        id
+    }
+
+    pub(crate) fn remove(&self, id: u32) {
+        self.0
+            .borrow_mut()
+            .callbacks
+            .retain(|(transform_id, _, _)| *transform_id != id);
+    }
+
+    /// Passes an encoded `update` through `encode` callbacks of all transforms, in order of their
+    /// registration, and returns the result as `bytes`. Untransformed updates are returned like
+    /// other binary payloads, see `binary_into_py`.
+    pub(crate) fn encode(&self, py: Python, update: Vec<u8>) -> PyResult<PyObject> {
+        let callbacks: Vec<PyObject> = self
+            .0
+            .borrow()
+            .callbacks
+            .iter()
+            .map(|(_, encode, _)| encode.clone_ref(py))
+            .collect();
+        if callbacks.is_empty() {
+            return Ok(binary_into_py(py, update));
+        }
+        let mut payload: &PyBytes = PyBytes::new(py, &update);
+        for callback in callbacks {
+            payload = transformed(callback.call1(py, (payload,))?.into_ref(py))?;
+        }
+        Ok(payload.into())
+    }
+
+    /// Reverses transforms of a received `update`, passing it through `decode` callbacks in
+    /// reverse order of their registration.
+    pub(crate) fn decode<'a>(
+        &self,
+        py: Python,
+        update: BinaryInput<'a>,
+    ) -> PyResult<BinaryInput<'a>> {
+        let callbacks: Vec<PyObject> = self
+            .0
+            .borrow()
+            .callbacks
+            .iter()
+            .rev()
+            .map(|(_, _, decode)| decode.clone_ref(py))
+            .collect();
+        if callbacks.is_empty() {
+            return Ok(update);
+        }
+        let mut payload: &PyBytes = PyBytes::new(py, &update);
+        for callback in callbacks {
+            payload = transformed(callback.call1(py, (payload,))?.into_ref(py))?;
+        }
+        Ok(BinaryInput::Owned(payload.as_bytes().to_vec()))
+    }
+}
+
+fn transformed(payload: &PyAny) -> PyResult<&PyBytes> {
+    payload.downcast::<PyBytes>().map_err(|_| {
+        PyTypeError::new_err(format!(
+            "Update transforms must return bytes, found {}",
+            payload.get_type().name().unwrap_or("unknown")
+        ))
+    })
+}
@@ -0,0 +1,173 @@
+use crate::snapshot::{read_items, Owner};
+use crate::type_conversions::ToPython;
+use lib0::any::Any;
+use pyo3::exceptions::PyKeyError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+use std::collections::{BTreeMap, HashMap};
+use yrs::types::Value;
+use yrs::{Doc, Transaction};
+
+/// Name of the root map in which `YDoc.create_version` stores versions of a document.
+pub(crate) const VERSIONS: &str = "__versions__";
+
+/// A named version of a document, stored by `YDoc.create_version`.
+pub(crate) struct Version {
+    pub(crate) name: String,
+    /// Time at which the version has been created, in seconds since the epoch.
+    pub(crate) created: f64,
+    pub(crate) snapshot: Vec<u8>,
+}
+
+impl Version {
+    fn read(name: &str, value: Value) -> Option<Self> {
+        let entry = match value {
+            Value::Any(Any::Map(entry)) => entry,
+            _ => return None,
+        };
+        let snapshot = match entry.get("snapshot") {
+            Some(Any::Buffer(snapshot)) => snapshot.to_vec(),
+            _ => return None,
+        };
+        let created = match entry.get("created") {
+            Some(Any::Number(created)) => *created,
+            Some(Any::BigInt(created)) => *created as f64,
+            _ => 0.0,
+        };
+        Some(Version {
+            name: name.to_string(),
+            created,
+            snapshot,
+        })
+    }
+
+    pub(crate) fn into_py_dict(self, py: Python) -> PyResult<&PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("name", self.name)?;
+        dict.set_item("created", self.created)?;
+        dict.set_item("snapshot", PyBytes::new(py, &self.snapshot))?;
+        Ok(dict)
+    }
+}
+
+/// Returns versions stored in a document, from the oldest to the newest one.
+pub(crate) fn list(txn: &mut Transaction) -> Vec<Version> {
+    let map = txn.get_map(VERSIONS);
+    let mut versions: Vec<Version> = map
+        .iter()
+        .filter_map(|(name, value)| Version::read(name, value))
+        .collect();
+    versions.sort_by(|a, b| {
+        a.created
+            .partial_cmp(&b.created)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    versions
+}
+
+/// Returns a version of a given name, or fails with a `KeyError` if there's no such version.
+pub(crate) fn find(txn: &mut Transaction, name: &str) -> PyResult<Version> {
+    let map = txn.get_map(VERSIONS);
+    map.get(name)
+        .and_then(|value| Version::read(name, value))
+        .ok_or_else(|| PyKeyError::new_err(format!("Unknown version '{name}'")))
+}
+
+/// A kind of a root type, ordered by precedence when a root type holds content of several kinds.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Kind {
+    Map,
+    Array,
+    Text,
+}
+
+/// Guesses kinds of root types found in a full state `update` of a document, which have no type
+/// until they're accessed, from their content. The root map storing versions is skipped.
+pub(crate) fn root_kinds(update: &[u8]) -> PyResult<BTreeMap<String, Kind>> {
+    let (items, _) = read_items(update)?;
+    let mut kinds = BTreeMap::new();
+    for item in items {
+        let name = match item.owner {
+            Some(Owner::Root(name)) if name != VERSIONS => name,
+            _ => continue,
+        };
+        let kind = match (item.key, item.is_text) {
+            (Some(_), _) => Kind::Map,
+            (None, true) => Kind::Text,
+            (None, false) => Kind::Array,
+        };
+        let entry = kinds.entry(name.to_string()).or_insert(kind);
+        *entry = kind.max(*entry);
+    }
+    Ok(kinds)
+}
+
+/// Plain content of a root type of a given kind.
+fn content(doc: &Doc, name: &str, kind: Kind) -> Any {
+    let mut txn = doc.transact();
+    match kind {
+        Kind::Text => Any::String(txn.get_text(name).to_string().into()),
+        Kind::Array => txn.get_array(name).to_json(),
+        Kind::Map => txn.get_map(name).to_json(),
+    }
+}
+
+fn entries(value: Any) -> HashMap<String, Any> {
+    match value {
+        Any::Map(entries) => *entries,
+        _ => HashMap::new(),
+    }
+}
+
+/// Compares root types of two versions of a document, `a` and `b`, given kinds of all root types
+/// of the document. Returns a dictionary of changes of root types which differ, keyed by their
+/// names.
+pub(crate) fn compare(
+    py: Python,
+    roots: BTreeMap<String, Kind>,
+    a: &Doc,
+    b: &Doc,
+) -> PyResult<PyObject> {
+    let changes = PyDict::new(py);
+    for (name, kind) in roots {
+        let before = content(a, &name, kind);
+        let after = content(b, &name, kind);
+        if before == after {
+            continue;
+        }
+        let change = PyDict::new(py);
+        match kind {
+            Kind::Text | Kind::Array => {
+                change.set_item("kind", if kind == Kind::Text { "text" } else { "array" })?;
+                change.set_item("before", before.into_py(py))?;
+                change.set_item("after", after.into_py(py))?;
+            }
+            Kind::Map => {
+                let mut before = entries(before);
+                let added = PyDict::new(py);
+                let updated = PyDict::new(py);
+                for (key, value) in entries(after) {
+                    match before.remove(&key) {
+                        None => added.set_item(key, value.into_py(py))?,
+                        Some(previous) if previous != value => {
+                            let values = (previous.into_py(py), value.into_py(py));
+                            updated.set_item(key, values)?
+                        }
+                        Some(_) => {}
+                    }
+                }
+                let removed = PyDict::new(py);
+                for (key, value) in before {
+                    removed.set_item(key, value.into_py(py))?;
+                }
+                change.set_item("kind", "map")?;
+                change.set_item("added", added)?;
+                change.set_item("removed", removed)?;
+                change.set_item("updated", updated)?;
+            }
+        }
+        changes.set_item(name, change)?;
+    }
+    Ok(changes.into())
+}
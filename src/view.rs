@@ -0,0 +1,189 @@
+use crate::shared_types::{plain_value, YPyType};
+use crate::y_array::YArrayEvent;
+use crate::y_map::YMapEvent;
+use pyo3::exceptions::PyTypeError;
+use pyo3::intern;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::convert::TryFrom;
+
+/// A plain Python mirror (`str`, `list` or `dict`) of a `YText`, `YArray` or `YMap`, kept up to
+/// date by a deep observer. Reading a view requires neither transactions nor conversions, which
+/// makes it a good fit for read-heavy code paths.
+///
+/// Changes are applied incrementally: map entries and array ranges are updated in place, so
+/// lists and dictionaries returned by `value` keep reflecting the latest state. Texts are
+/// immutable Python strings, so they are replaced instead.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import YDoc, View
+///
+/// doc = YDoc()
+/// todos = doc.get_array('todos')
+/// view = View(todos)
+/// with doc.begin_transaction() as txn:
+///     todos.append(txn, {'title': 'write docs'})
+/// assert view.value == [{'title': 'write docs'}]
+/// view.close()
+/// ```
+#[pyclass(unsendable)]
+pub struct View {
+    shared: PyObject,
+    mirror: Py<Mirror>,
+    subscription: Option<PyObject>,
+}
+
+#[pymethods]
+impl View {
+    /// Creates a view of an integrated `YText`, `YArray` or `YMap` instance.
+    #[new]
+    pub fn new(shared: &PyAny) -> PyResult<Self> {
+        let py = shared.py();
+        match YPyType::try_from(shared) {
+            Ok(YPyType::Text(_)) | Ok(YPyType::Array(_)) | Ok(YPyType::Map(_)) => {}
+            _ => {
+                return Err(PyTypeError::new_err(
+                    "Views can only be created for YText, YArray and YMap instances",
+                ))
+            }
+        }
+        let shared: PyObject = shared.into();
+        let mirror = Py::new(
+            py,
+            Mirror {
+                value: plain_value(py, &shared)?,
+                shared: shared.clone_ref(py),
+            },
+        )?;
+        let subscription = shared.call_method1(py, "observe_deep", (mirror.clone_ref(py),))?;
+        Ok(View {
+            shared,
+            mirror,
+            subscription: Some(subscription),
+        })
+    }
+
+    /// Returns a current state of an observed shared type as a plain Python value.
+    #[getter]
+    pub fn value(&self) -> PyObject {
+        Python::with_gil(|py| self.mirror.borrow(py).value.clone_ref(py))
+    }
+
+    /// Returns true if this view no longer follows changes of its shared type.
+    #[getter]
+    pub fn closed(&self) -> bool {
+        self.subscription.is_none()
+    }
+
+    /// Stops following changes of an observed shared type. The last known value stays available.
+    pub fn close(&mut self) -> PyResult<()> {
+        if let Some(subscription) = self.subscription.take() {
+            Python::with_gil(|py| self.shared.call_method1(py, "unobserve", (subscription,)))?;
+        }
+        Ok(())
+    }
+
+    pub fn __repr__(&self) -> PyResult<String> {
+        Python::with_gil(|py| {
+            let mirror = self.mirror.borrow(py);
+            let value = mirror.value.as_ref(py).repr()?;
+            Ok(format!("View({value})"))
+        })
+    }
+}
+
+/// Deep observer callback of a `View`, which applies events to the mirrored value.
+#[pyclass(unsendable)]
+pub struct Mirror {
+    shared: PyObject,
+    value: PyObject,
+}
+
+#[pymethods]
+impl Mirror {
+    fn __call__(&mut self, events: &PyList) -> PyResult<()> {
+        let py = events.py();
+        for event in events.iter() {
+            if self.apply(py, event).is_err() {
+                // The mirror got out of sync with its shared type, so it's rebuilt from scratch
+                return self.rebuild(py);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Mirror {
+    fn apply(&mut self, py: Python, event: &PyAny) -> PyResult<()> {
+        let path: Vec<&PyAny> = event.call_method0("path")?.extract()?;
+        if event.downcast::<PyCell<YMapEvent>>().is_ok() {
+            let target = self.locate(py, &path)?;
+            let keys: &PyDict = event.getattr(intern!(py, "keys"))?.downcast()?;
+            for (key, change) in keys.iter() {
+                let change: &PyDict = change.downcast()?;
+                let action: Option<&str> = match change.get_item(intern!(py, "action")) {
+                    Some(action) => Some(action.extract()?),
+                    None => None,
+                };
+                match (action, change.get_item(intern!(py, "newValue"))) {
+                    (Some("delete"), _) | (_, None) => target.del_item(key)?,
+                    (_, Some(value)) => target.set_item(key, plain_value(py, &value.into())?)?,
+                }
+            }
+        } else if event.downcast::<PyCell<YArrayEvent>>().is_ok() {
+            let target: &PyList = self.locate(py, &path)?.downcast()?;
+            let delta: &PyList = event.getattr(intern!(py, "delta"))?.downcast()?;
+            let mut index = 0;
+            for change in delta.iter() {
+                let change: &PyDict = change.downcast()?;
+                if let Some(values) = change.get_item(intern!(py, "insert")) {
+                    for value in values.iter()? {
+                        target.insert(index, plain_value(py, &value?.into())?)?;
+                        index += 1;
+                    }
+                } else if let Some(len) = change.get_item(intern!(py, "delete")) {
+                    target.del_slice(index, index + len.extract::<usize>()?)?;
+                } else if let Some(len) = change.get_item(intern!(py, "retain")) {
+                    index += len.extract::<usize>()?;
+                }
+            }
+        } else {
+            // Text deltas carry formatting which isn't part of a plain string, so texts are
+            // replaced as a whole
+            let text = event.getattr(intern!(py, "target"))?.str()?;
+            match path.split_last() {
+                Some((key, parent)) => self.locate(py, parent)?.set_item(*key, text)?,
+                None => self.value = text.into(),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a nested value of the mirror found under a given path of keys and indexes.
+    fn locate<'py>(&self, py: Python<'py>, path: &[&PyAny]) -> PyResult<&'py PyAny> {
+        let mut current = self.value.clone_ref(py).into_ref(py);
+        for key in path {
+            current = current.get_item(*key)?;
+        }
+        Ok(current)
+    }
+
+    /// Replaces the mirrored value with a fresh conversion of the shared type. Lists and
+    /// dictionaries are updated in place, so that references held by users stay current.
+    fn rebuild(&mut self, py: Python) -> PyResult<()> {
+        let fresh = plain_value(py, &self.shared)?;
+        let value = self.value.as_ref(py);
+        if let Ok(dict) = value.downcast::<PyDict>() {
+            dict.clear();
+            dict.call_method1("update", (fresh,))?;
+        } else if let Ok(list) = value.downcast::<PyList>() {
+            list.del_slice(0, list.len())?;
+            list.call_method1("extend", (fresh,))?;
+        } else {
+            self.value = fresh;
+        }
+        Ok(())
+    }
+}
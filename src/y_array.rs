@@ -1,24 +1,38 @@
 use std::convert::{TryFrom, TryInto};
 
-use crate::json_builder::JsonBuilder;
+use crate::arrow::TableBuilder;
+use crate::cursor::{element_at, element_ids, encode_token, PageStart};
+use crate::debounce::{self, Debouncer};
+use crate::json_builder::{limited_json, limited_prelim_array, JsonBuilder, JsonWriter};
+use crate::location::{parent_into_py, Location, Observed};
+use crate::logging;
+use crate::metrics::{self, Metric};
+use crate::msgpack;
+use crate::origin;
+use crate::panics;
 use crate::shared_types::{
-    CompatiblePyType, DeepSubscription, DefaultPyErr, PreliminaryObservationException,
-    ShallowSubscription, SubId,
+    compare_branches, hash_branch, plain_value, sha256_hex, CompatiblePyType, DeepSubscription,
+    DefaultPyErr, IntegratedOperationException, PreliminaryObservationException,
+    ShallowSubscription, SubId, YPyType,
 };
-use crate::type_conversions::events_into_py;
+use crate::shutdown::{self, Callback};
+use crate::type_conversions::{dict_key, events_into_py, plain_any, undefined_as_sentinel};
+use crate::y_map::YMap;
 use crate::y_transaction::YTransaction;
 
 use super::shared_types::SharedType;
 use crate::type_conversions::ToPython;
 use lib0::any::Any;
-use pyo3::exceptions::PyIndexError;
+use pyo3::exceptions::{PyIndexError, PyTypeError, PyValueError};
 
-use crate::type_conversions::PyObjectWrapper;
+use crate::type_conversions::take_integration_error;
+use crate::type_conversions::PreparedValue;
 use pyo3::prelude::*;
-use pyo3::types::{PyList, PySlice, PySliceIndices};
+use pyo3::pyclass::CompareOp;
+use pyo3::types::{PyBytes, PyDict, PyList, PySlice, PySliceIndices};
 use yrs::types::array::ArrayEvent;
-use yrs::types::DeepObservable;
-use yrs::{Array, SubscriptionId, Transaction};
+use yrs::types::{DeepObservable, Value};
+use yrs::{Array, OffsetKind, StateVector, SubscriptionId, Transaction};
 
 /// A collection used to store data in an indexed sequence structure. This type is internally
 /// implemented as a double linked list, which may squash values inserted directly one after another
@@ -39,11 +53,11 @@ use yrs::{Array, SubscriptionId, Transaction};
 /// after merging all updates together). In case of Yrs conflict resolution is solved by using
 /// unique document id to determine correct and consistent ordering.
 #[pyclass(unsendable)]
-pub struct YArray(pub SharedType<Array, Vec<PyObject>>);
+pub struct YArray(pub SharedType<Array, Vec<PyObject>>, pub(crate) Location);
 
 impl From<Array> for YArray {
     fn from(v: Array) -> Self {
-        YArray(SharedType::new(v))
+        YArray::located(v, Location::default())
     }
 }
 
@@ -58,7 +72,7 @@ impl YArray {
     #[new]
     pub fn new(init: Option<PyObject>) -> PyResult<Self> {
         let elements = init.map(Self::py_iter).unwrap_or(Ok(Vec::default()));
-        elements.map(|el_array| YArray(SharedType::prelim(el_array)))
+        elements.map(|el_array| YArray(SharedType::prelim(el_array), Location::default()))
     }
 
     /// Returns true if this is a preliminary instance of `YArray`.
@@ -74,6 +88,63 @@ impl YArray {
         }
     }
 
+    /// Returns true if this instance of `YArray` has been integrated into a `YDoc`. This is an
+    /// opposite of `prelim`.
+    #[getter]
+    pub fn integrated(&self) -> bool {
+        !self.prelim()
+    }
+
+    /// Returns the `YDoc` this `YArray` instance belongs to, or `None` if the document is unknown.
+    /// Documents are known for shared types reached from their root types, which have been
+    /// obtained from a `YDoc` or its transactions. Raises a `PreliminaryAccessError` for
+    /// preliminary instances.
+    #[getter]
+    pub fn doc(&self) -> PyResult<PyObject> {
+        match self.0.branch() {
+            Some(_) => Ok(Python::with_gil(|py| self.1.doc(py))),
+            None => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Starts a new transaction of the document this `YArray` instance belongs to, which makes it
+    /// possible to modify it without a reference to its `YDoc`. The document must be known, as
+    /// described by the `doc` property. Raises a `PreliminaryAccessError` for preliminary
+    /// instances.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// with array.transaction() as txn:
+    ///     array.append(txn, 1)
+    /// ```
+    pub fn transaction(&self) -> PyResult<PyObject> {
+        match self.0.branch() {
+            Some(_) => Python::with_gil(|py| self.1.transaction(py)),
+            None => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Returns a shared type containing this `YArray` instance, or `None` if it's a root type or
+    /// its document is unknown.
+    /// Raises a `PreliminaryAccessError` for preliminary instances.
+    #[getter]
+    pub fn parent(&self) -> PyResult<PyObject> {
+        match self.0.branch() {
+            Some(branch) => Ok(Python::with_gil(|py| parent_into_py(py, branch, &self.1))),
+            None => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Checks if both objects refer to the same integrated `YArray`.
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyObject {
+        compare_branches(other.py(), self.0.branch(), other, op)
+    }
+
+    fn __hash__(&self) -> PyResult<isize> {
+        hash_branch(self.0.branch())
+    }
+
     /// Returns a number of elements stored within this instance of `YArray`.
     pub fn __len__(&self) -> usize {
         match &self.0 {
@@ -101,91 +172,478 @@ impl YArray {
     }
 
     /// Converts an underlying contents of this `YArray` instance into their JSON representation.
-    pub fn to_json(&self) -> PyResult<String> {
+    ///
+    /// When `depth` is given, nested shared types are resolved only up to `depth` levels below
+    /// this array, while deeper ones are replaced by placeholders of the form
+    /// `{"$type": "YMap", "length": 3}`. With a `depth` of 0, only the elements of this array are
+    /// resolved.
+    pub fn to_json(
+        &self,
+        _txn: Option<PyRef<YTransaction>>,
+        depth: Option<usize>,
+    ) -> PyResult<String> {
         let mut json_builder = JsonBuilder::new();
-        match &self.0 {
-            SharedType::Integrated(array) => json_builder.append_json(&array.to_json())?,
-            SharedType::Prelim(py_vec) => json_builder.append_json(py_vec)?,
+        match (&self.0, depth) {
+            (SharedType::Integrated(array), None) => json_builder.append_json(&array.to_json())?,
+            (SharedType::Prelim(py_vec), None) => json_builder.append_json(py_vec)?,
+            (SharedType::Integrated(array), Some(depth)) => {
+                json_builder.append_json(&limited_json(Value::YArray(array.clone()), depth))?
+            }
+            (SharedType::Prelim(py_vec), Some(depth)) => {
+                let any = Python::with_gil(|py| limited_prelim_array(py, py_vec, depth))?;
+                json_builder.append_json(&any)?
+            }
         }
         Ok(json_builder.into())
     }
-    /// Adds a single item to the provided index in the array.
-    pub fn insert(&mut self, txn: &mut YTransaction, index: u32, item: PyObject) -> PyResult<()> {
-        match &mut self.0 {
+
+    /// Serializes an underlying contents of this `YArray` instance into JSON, like `to_json` does, but writes it to a given
+    /// file object in chunks instead of returning it as a single string, which keeps memory usage
+    /// low when serializing large documents.
+    pub fn write_json(&self, _txn: &YTransaction, file: &PyAny) -> PyResult<()> {
+        let mut writer = JsonWriter::new(file)?;
+        match &self.0 {
+            SharedType::Integrated(array) => writer.write_value(Value::YArray(array.clone()))?,
+            SharedType::Prelim(_) => writer.write_str(&self.to_json(None, None)?)?,
+        }
+        writer.finish()
+    }
+
+    /// Converts an underlying contents of this `YArray` instance into a Python list. Nested shared
+    /// types are returned as `YText`, `YArray` and `YMap` instances, unless `deep` is set, in which
+    /// case they are recursively converted into `str`, `list` and `dict` values.
+    ///
+    /// Optional `start` and `stop` indexes limit the conversion to a range of elements, following
+    /// the same rules as Python slices.
+    pub fn to_list(
+        &self,
+        deep: Option<bool>,
+        start: Option<isize>,
+        stop: Option<isize>,
+    ) -> PyResult<PyObject> {
+        let deep = deep.unwrap_or(false);
+        Python::with_gil(|py| {
+            let PySliceIndices { start, stop, .. } =
+                PySlice::new(py, start.unwrap_or(0), stop.unwrap_or(isize::MAX), 1)
+                    .indices(self.__len__().try_into().unwrap())?;
+            let (start, stop) = (start as usize, stop.max(start) as usize);
+            let mut values: Vec<PyObject> = Vec::with_capacity(stop - start);
+            match &self.0 {
+                SharedType::Integrated(array) => {
+                    let items = array.iter().skip(start).take(stop - start);
+                    if deep {
+                        values.extend(items.map(|value| value.to_json().into_py(py)));
+                    } else {
+                        self.1.within(array.as_ref(), || {
+                            values.extend(items.map(|value| value.into_py(py)))
+                        });
+                    }
+                }
+                SharedType::Prelim(items) if deep => {
+                    for item in &items[start..stop] {
+                        values.push(plain_value(py, item)?);
+                    }
+                }
+                SharedType::Prelim(items) => {
+                    values.extend(items[start..stop].iter().map(|item| item.clone_ref(py)));
+                }
+            }
+            Ok(PyList::new(py, values).into())
+        })
+    }
+
+    /// Converts an underlying contents of this `YArray` instance into a NumPy array. Only arrays
+    /// of numbers and booleans (or nested lists of them) can be converted. Since all numbers are
+    /// stored as 64-bit floats, integer values will produce a float array.
+    pub fn to_numpy(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| {
+            let contents = self.to_any(py)?;
+            if !Self::is_numeric(&contents) {
+                return Err(PyTypeError::new_err(
+                    "Only arrays of numeric values can be converted into a NumPy array",
+                ));
+            }
+            let numpy = py.import("numpy")?;
+            let array = numpy.call_method1("array", (contents.into_py(py),))?;
+            Ok(array.into())
+        })
+    }
+
+    /// Serializes an underlying contents of this `YArray` instance using the MessagePack format.
+    /// Unlike document updates, the result contains only the current values, without any
+    /// collaborative editing metadata.
+    pub fn to_msgpack(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| {
+            let contents = self.to_any(py)?;
+            Ok(PyBytes::new(py, &msgpack::encode(&contents)).into())
+        })
+    }
+
+    /// Returns a hex encoded SHA-256 digest of the contents of this `YArray` instance. Digests
+    /// depend only on the current elements, regardless of their editing history, so replicas can
+    /// cheaply verify that they converged.
+    pub fn content_hash(&self) -> PyResult<String> {
+        Python::with_gil(|py| {
+            let contents = self.to_any(py)?;
+            sha256_hex(py, &msgpack::encode_canonical(&contents))
+        })
+    }
+
+    /// Converts an underlying contents of this `YArray` instance into a `pyarrow.Table`. Every
+    /// element of the array is treated as a row and has to be a map. Table columns are created
+    /// from the keys of all rows, in order of their first appearance, and cells missing from a row
+    /// are set to `None`.
+    pub fn to_arrow(&self) -> PyResult<PyObject> {
+        let not_a_map =
+            || PyTypeError::new_err("Only arrays of maps can be converted into an Arrow table");
+        let mut table = TableBuilder::default();
+        Python::with_gil(|py| {
+            match &self.0 {
+                SharedType::Integrated(array) => {
+                    for row in array.iter() {
+                        match row {
+                            Value::YMap(map) => table
+                                .push_row(map.iter().map(|(k, v)| (k.to_string(), v.to_json()))),
+                            Value::Any(Any::Map(map)) => table.push_row(*map),
+                            _ => return Err(not_a_map()),
+                        }
+                    }
+                }
+                SharedType::Prelim(items) => {
+                    for item in items {
+                        let row = plain_value(py, item)?;
+                        let row: &PyDict = row.as_ref(py).downcast().map_err(|_| not_a_map())?;
+                        let mut entries = Vec::with_capacity(row.len());
+                        for (key, value) in row.iter() {
+                            let value = Any::try_from(CompatiblePyType::try_from(value)?)?;
+                            entries.push((dict_key(key)?, value));
+                        }
+                        table.push_row(entries);
+                    }
+                }
+            }
+            table.into_table(py)
+        })
+    }
+
+    /// Reads a page of at most `count` elements of this array, starting at a given index or at a
+    /// continuation token returned by a read of a previous page. Returns the elements with a
+    /// token of the next page, or `None` once the end of the array has been reached.
+    ///
+    /// Tokens are stored relative to the elements they point at, so pages stay consistent while
+    /// the array is being edited: elements inserted before a token are not read again, and a
+    /// token pointing at a deleted element continues from the place of the deletion.
+    pub fn range(
+        &self,
+        py: Python,
+        txn: &YTransaction,
+        start: PageStart,
+        count: u32,
+    ) -> PyResult<(PyObject, Option<String>)> {
+        let array = match &self.0 {
+            SharedType::Integrated(array) => array,
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        let path = self.1.path(py, array.as_ref()).ok_or_else(|| {
+            PyValueError::new_err("Elements of this array can't be located in its document")
+        })?;
+        let deletes = txn.snapshot().delete_set;
+        let elements = element_ids(
+            &txn.encode_diff_v1(&StateVector::default()),
+            &path,
+            &deletes,
+        )?;
+        let start = start.element_index(&elements, &deletes)?;
+        let items: Vec<PyObject> = self.1.within(array.as_ref(), || {
+            array
+                .iter()
+                .skip(start as usize)
+                .take(count as usize)
+                .map(|value| value.into_py(py))
+                .collect()
+        });
+        let next = element_at(&elements, &deletes, start.saturating_add(count));
+        Ok((PyList::new(py, items).into(), next.map(encode_token)))
+    }
+
+    /// Returns a list of values stored under a given `key` by every element of this array, which
+    /// has to be an array of maps, eg. rows of a table. Elements missing the key contribute a
+    /// `default` value. Other values of the rows are not converted, which makes reading a single
+    /// column much cheaper than converting the whole array.
+    pub fn column(
+        &self,
+        py: Python,
+        _txn: &YTransaction,
+        key: &str,
+        default: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let default = default.unwrap_or_else(|| py.None());
+        let not_a_row = || PyTypeError::new_err("Columns can only be read from arrays of maps");
+        let mut values: Vec<PyObject> = Vec::with_capacity(self.__len__());
+        match &self.0 {
+            SharedType::Integrated(array) => {
+                let rows = self.1.child(array.as_ref());
+                for row in array.iter() {
+                    let value = match row {
+                        Value::YMap(map) => rows
+                            .within(map.as_ref(), || map.get(key).map(|value| value.into_py(py))),
+                        Value::Any(Any::Map(entries)) => {
+                            entries.get(key).map(|value| value.clone().into_py(py))
+                        }
+                        _ => return Err(not_a_row()),
+                    };
+                    values.push(value.unwrap_or_else(|| default.clone_ref(py)));
+                }
+            }
+            SharedType::Prelim(items) => {
+                for row in items {
+                    let row = row.as_ref(py);
+                    let value = if let Ok(dict) = row.downcast::<PyDict>() {
+                        dict.get_item(key).map(|value| value.into())
+                    } else if let Ok(map) = row.downcast::<PyCell<YMap>>() {
+                        Some(map.borrow().get(key, Some(default.clone_ref(py))))
+                    } else {
+                        return Err(not_a_row());
+                    };
+                    values.push(value.unwrap_or_else(|| default.clone_ref(py)));
+                }
+            }
+        }
+        Ok(PyList::new(py, values).into())
+    }
+
+    /// Returns elements of this array, which has to be an array of maps, eg. rows of a table,
+    /// storing a given primitive `value` (`None`, a boolean, a number or a string) under a given
+    /// `key`. Rows are compared without being converted, and only matching rows are returned,
+    /// which makes querying a large table much cheaper than filtering the whole array in Python.
+    /// Integers and floats are compared by their numeric value.
+    pub fn values_where(
+        &self,
+        py: Python,
+        _txn: &YTransaction,
+        key: &str,
+        value: &PyAny,
+    ) -> PyResult<PyObject> {
+        let not_a_row = || PyTypeError::new_err("Rows can only be filtered in arrays of maps");
+        let mut rows: Vec<PyObject> = Vec::new();
+        match &self.0 {
+            SharedType::Integrated(array) => {
+                let expected = primitive(value)?;
+                for row in array.iter() {
+                    let matches = match &row {
+                        Value::YMap(map) => match map.get(key) {
+                            Some(Value::Any(actual)) => primitive_eq(&actual, &expected),
+                            _ => false,
+                        },
+                        Value::Any(Any::Map(entries)) => entries
+                            .get(key)
+                            .map_or(false, |actual| primitive_eq(actual, &expected)),
+                        _ => return Err(not_a_row()),
+                    };
+                    if matches {
+                        rows.push(self.1.within(array.as_ref(), || row.into_py(py)));
+                    }
+                }
+            }
+            SharedType::Prelim(items) => {
+                primitive(value)?;
+                for row in items {
+                    let actual = if let Ok(dict) = row.as_ref(py).downcast::<PyDict>() {
+                        dict.get_item(key).map(|value| value.into())
+                    } else if let Ok(map) = row.as_ref(py).downcast::<PyCell<YMap>>() {
+                        map.borrow().__getitem__(key).ok()
+                    } else {
+                        return Err(not_a_row());
+                    };
+                    if let Some(actual) = actual {
+                        if actual.as_ref(py).eq(value)? {
+                            rows.push(row.clone_ref(py));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(PyList::new(py, rows).into())
+    }
+
+    /// Adds a single item to the provided index in the array. Returns the inserted element, so
+    /// that preliminary shared types can be used in their integrated form right away.
+    pub fn insert(
+        &mut self,
+        py: Python,
+        txn: &mut YTransaction,
+        index: u32,
+        item: PyObject,
+    ) -> PyResult<PyObject> {
+        panics::guard(|| match &mut self.0 {
             SharedType::Integrated(array) if array.len() >= index => {
-                array.insert(txn, index, PyObjectWrapper(item));
-                Ok(())
+                Self::check_insert(array, &self.1, txn, index, std::slice::from_ref(&item))?;
+                let value = PreparedValue::new(item.as_ref(py))?;
+                self.1.within(array.as_ref(), || {
+                    array.insert(txn, index, value);
+                    take_integration_error()?;
+                    Ok(Self::inserted(py, array, index, item))
+                })
             }
             SharedType::Prelim(vec) if vec.len() >= index as usize => {
-                Ok(vec.insert(index as usize, item))
+                vec.insert(index as usize, item.clone_ref(py));
+                Ok(item)
             }
             _ => Err(PyIndexError::default_message()),
-        }
+        })
     }
 
     /// Inserts a given range of `items` into this `YArray` instance, starting at given `index`.
+    /// Returns a list of inserted elements.
     pub fn insert_range(
         &mut self,
+        py: Python,
         txn: &mut YTransaction,
         index: u32,
         items: PyObject,
-    ) -> PyResult<()> {
+    ) -> PyResult<Vec<PyObject>> {
         let items = Self::py_iter(items)?;
-        match &mut self.0 {
+        let len = items.len() as u32;
+        panics::guard(|| match &mut self.0 {
             SharedType::Integrated(array) if array.len() >= index => {
-                Self::insert_multiple_at(array, txn, index, items);
-                Ok(())
+                let inserted: Vec<PyObject> = items.iter().map(|item| item.clone_ref(py)).collect();
+                Self::check_insert(array, &self.1, txn, index, &items)?;
+                self.1.within(array.as_ref(), || {
+                    Self::insert_multiple_at(array, txn, index, items)?;
+                    Ok((index..index + len)
+                        .zip(inserted)
+                        .map(|(i, item)| Self::inserted(py, array, i, item))
+                        .collect())
+                })
             }
             SharedType::Prelim(vec) if vec.len() >= index as usize => {
-                let mut j = index;
-                for el in items {
-                    vec.insert(j as usize, el);
-                    j += 1;
-                }
-                Ok(())
+                let inserted = items.iter().map(|item| item.clone_ref(py)).collect();
+                let index = index as usize;
+                vec.splice(index..index, items);
+                Ok(inserted)
             }
             _ => Err(PyIndexError::default_message()),
-        }
+        })
     }
 
-    /// Appends a range of `items` at the end of this `YArray` instance.
-    pub fn extend(&mut self, txn: &mut YTransaction, items: PyObject) -> PyResult<()> {
+    /// Appends a range of `items` at the end of this `YArray` instance. Returns a list of
+    /// appended elements.
+    pub fn extend(
+        &mut self,
+        py: Python,
+        txn: &mut YTransaction,
+        items: PyObject,
+    ) -> PyResult<Vec<PyObject>> {
         let index = self.__len__() as u32;
-        self.insert_range(txn, index, items)
+        self.insert_range(py, txn, index, items)
     }
-    /// Adds a single item to the end of the array
-    pub fn append(&mut self, txn: &mut YTransaction, item: PyObject) {
+    /// Appends primitive `items` (`None`, booleans, numbers, strings, bytes and lists or
+    /// dictionaries of them) at the end of this `YArray` instance. Unlike `extend`, all items are
+    /// converted in a single pass and inserted as one range, which makes bulk loads of large lists
+    /// much faster. Shared types are not accepted and a `TypeError` is raised for them, in which
+    /// case the array is left untouched.
+    pub fn push_primitives(&mut self, txn: &mut YTransaction, items: &PyAny) -> PyResult<()> {
         match &mut self.0 {
-            SharedType::Integrated(array) => array.push_back(txn, PyObjectWrapper(item)),
-            SharedType::Prelim(vec) => vec.push(item),
+            SharedType::Integrated(array) => {
+                let values = items
+                    .iter()?
+                    .map(|item| Self::primitive(item?))
+                    .collect::<PyResult<Vec<Any>>>()?;
+                let index = array.len();
+                if let Some(scope) = txn.schema_scope(array.as_ref(), &self.1) {
+                    scope.check_insert(index, index, &values)?;
+                }
+                panics::guard(|| {
+                    array.insert_range(txn, index, values);
+                    Ok(())
+                })?;
+            }
+            SharedType::Prelim(vec) => {
+                let items = items.iter()?.collect::<PyResult<Vec<&PyAny>>>()?;
+                for item in items.iter() {
+                    Self::primitive(item)?;
+                }
+                vec.extend(items.into_iter().map(PyObject::from));
+            }
         }
+        Ok(())
+    }
+
+    /// Adds a single item to the end of the array. Returns the appended element, so that
+    /// preliminary shared types can be used in their integrated form right away.
+    pub fn append(
+        &mut self,
+        py: Python,
+        txn: &mut YTransaction,
+        item: PyObject,
+    ) -> PyResult<PyObject> {
+        panics::guard(|| match &mut self.0 {
+            SharedType::Integrated(array) => {
+                Self::check_insert(
+                    array,
+                    &self.1,
+                    txn,
+                    array.len(),
+                    std::slice::from_ref(&item),
+                )?;
+                let value = PreparedValue::new(item.as_ref(py))?;
+                self.1.within(array.as_ref(), || {
+                    array.push_back(txn, value);
+                    take_integration_error()?;
+                    Ok(Self::inserted(py, array, array.len() - 1, item))
+                })
+            }
+            SharedType::Prelim(vec) => {
+                vec.push(item.clone_ref(py));
+                Ok(item)
+            }
+        })
     }
     /// Removes the element that the given index from the list.
     pub fn delete(&mut self, txn: &mut YTransaction, index: u32) -> PyResult<()> {
-        match &mut self.0 {
-            SharedType::Integrated(v) if index < v.len() => Ok(v.remove(txn, index)),
+        panics::guard(|| match &mut self.0 {
+            SharedType::Integrated(v) if index < v.len() => {
+                if let Some(scope) = txn.schema_scope(v.as_ref(), &self.1) {
+                    scope.check_delete(v.len() - 1)?;
+                }
+                v.remove(txn, index);
+                Ok(())
+            }
             SharedType::Prelim(v) if index < v.len() as u32 => {
                 v.remove(index as usize);
                 Ok(())
             }
             _ => Err(PyIndexError::default_message()),
-        }
+        })
     }
 
     /// Deletes a range of items of given `length` from current `YArray` instance,
     /// starting from given `index`.
-    pub fn delete_range(&mut self, txn: &mut YTransaction, index: u32, length: u32) {
-        match &mut self.0 {
-            SharedType::Integrated(v) => v.remove_range(txn, index, length),
-            SharedType::Prelim(v) => {
-                v.drain((index as usize)..(index + length) as usize);
+    pub fn delete_range(
+        &mut self,
+        txn: &mut YTransaction,
+        index: u32,
+        length: u32,
+    ) -> PyResult<()> {
+        panics::guard(|| {
+            match &mut self.0 {
+                SharedType::Integrated(v) => {
+                    if let Some(scope) = txn.schema_scope(v.as_ref(), &self.1) {
+                        scope.check_delete(v.len().saturating_sub(length))?;
+                    }
+                    v.remove_range(txn, index, length)
+                }
+                SharedType::Prelim(v) => {
+                    v.drain((index as usize)..(index + length) as usize);
+                }
             }
-        }
+            Ok(())
+        })
     }
 
     /// Moves the element from the index source to target.
     pub fn move_to(&mut self, txn: &mut YTransaction, source: u32, target: u32) -> PyResult<()> {
-        match &mut self.0 {
+        panics::guard(|| match &mut self.0 {
             SharedType::Integrated(v) => {
                 v.move_to(txn, source, target);
                 Ok(())
@@ -204,7 +662,7 @@ impl YArray {
                 Ok(())
             }
             _ => Err(PyIndexError::default_message()),
-        }
+        })
     }
 
     /// Moves all elements found within `start`..`end` indexes range (both side inclusive) into
@@ -231,44 +689,118 @@ impl YArray {
         end: u32,
         target: u32,
     ) -> PyResult<()> {
-        match &mut self.0 {
-            SharedType::Integrated(v) => {
-                v.move_range_to(txn, start, true, end, false, target);
-                Ok(())
-            }
+        panics::guard(|| {
+            match &mut self.0 {
+                SharedType::Integrated(v) => {
+                    v.move_range_to(txn, start, true, end, false, target);
+                    Ok(())
+                }
 
-            // y-rs does nothing if end < start
-            // SharedType::Prelim(_) if end < start => Err(PyIndexError::default_message()),
-            SharedType::Prelim(_) if start < 0 as u32 || end < 0 as u32 || target < 0 as u32 => {
-                Err(PyIndexError::default_message())
-            }
-            SharedType::Prelim(v)
-                if start > v.len() as u32 || end > v.len() as u32 || target > v.len() as u32 =>
-            {
-                Err(PyIndexError::default_message())
-            }
+                // y-rs does nothing if end < start
+                // SharedType::Prelim(_) if end < start => Err(PyIndexError::default_message()),
+                SharedType::Prelim(_)
+                    if start < 0 as u32 || end < 0 as u32 || target < 0 as u32 =>
+                {
+                    Err(PyIndexError::default_message())
+                }
+                SharedType::Prelim(v)
+                    if start > v.len() as u32
+                        || end > v.len() as u32
+                        || target > v.len() as u32 =>
+                {
+                    Err(PyIndexError::default_message())
+                }
 
-            // It doesn't make sense to move a range into the same range (it's basically a no-op).
-            SharedType::Prelim(_) if target >= start && target <= end => Ok(()),
+                // It doesn't make sense to move a range into the same range (it's basically a no-op).
+                SharedType::Prelim(_) if target >= start && target <= end => Ok(()),
 
-            SharedType::Prelim(v) => {
-                let mut i: usize = 0;
-                let mut n: usize = (end - start + 1) as usize;
-                let backwards = target > end;
-
-                while n > 0 {
-                    let item = v.remove(start as usize + i);
-                    if backwards {
-                        v.insert(target as usize - 1, item);
-                    } else {
-                        v.insert(target as usize + i, item);
-                        i += 1;
+                SharedType::Prelim(v) => {
+                    let mut i: usize = 0;
+                    let mut n: usize = (end - start + 1) as usize;
+                    let backwards = target > end;
+
+                    while n > 0 {
+                        let item = v.remove(start as usize + i);
+                        if backwards {
+                            v.insert(target as usize - 1, item);
+                        } else {
+                            v.insert(target as usize + i, item);
+                            i += 1;
+                        }
+                        n -= 1;
                     }
-                    n -= 1;
+                    Ok(())
                 }
-                Ok(())
             }
+        })
+    }
+
+    /// Sorts elements of this `YArray` in place, like `list.sort` does. Elements are reordered by
+    /// moving them, so they keep their identity and concurrent changes made to them are preserved.
+    /// Elements which already are in order relative to each other stay where they are, so that as
+    /// few of them as possible are moved.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// tasks = doc.get_array('tasks')
+    /// with doc.begin_transaction() as txn:
+    ///     tasks.sort(txn, key=lambda task: task['priority'], reverse=True)
+    /// ```
+    pub fn sort(
+        &mut self,
+        py: Python,
+        txn: &mut YTransaction,
+        key: Option<PyObject>,
+        reverse: Option<bool>,
+    ) -> PyResult<()> {
+        let values: Vec<PyObject> = match &self.0 {
+            SharedType::Integrated(array) => self.1.within(array.as_ref(), || {
+                array.iter().map(|value| value.into_py(py)).collect()
+            }),
+            SharedType::Prelim(items) => items.iter().map(|item| item.clone_ref(py)).collect(),
+        };
+        // Elements are decorated with their indexes, which are all that's needed to reorder them
+        let decorated = PyList::empty(py);
+        for (index, value) in values.into_iter().enumerate() {
+            let sort_key = match &key {
+                Some(key) => key.call1(py, (value,))?,
+                None => value,
+            };
+            decorated.append((sort_key, index))?;
+        }
+        let kwargs = PyDict::new(py);
+        kwargs.set_item(
+            "key",
+            py.import("operator")?.call_method1("itemgetter", (0,))?,
+        )?;
+        kwargs.set_item("reverse", reverse.unwrap_or(false))?;
+        let sorted = py
+            .import("builtins")?
+            .getattr("sorted")?
+            .call((decorated,), Some(kwargs))?;
+        let mut order = Vec::with_capacity(self.__len__());
+        for entry in sorted.iter()? {
+            let (_, index): (&PyAny, usize) = entry?.extract()?;
+            order.push(index);
         }
+        panics::guard(|| {
+            self.reorder(txn, &order);
+            Ok(())
+        })
+    }
+
+    /// Reverses the order of elements of this `YArray` in place. Like with `sort`, elements are
+    /// reordered by moving them.
+    pub fn reverse(&mut self, txn: &mut YTransaction) -> PyResult<()> {
+        let order: Vec<usize> = (0..self.__len__()).rev().collect();
+        panics::guard(|| {
+            self.reorder(txn, &order);
+            Ok(())
+        })
     }
 
     pub fn __getitem__(&self, index: Index) -> PyResult<PyObject> {
@@ -279,6 +811,44 @@ impl YArray {
         }
     }
 
+    /// Checks if a given value is stored in this `YArray`. Values are compared on the Rust side,
+    /// without converting elements of the array into Python objects. Only nested shared types are
+    /// compared using Python equality.
+    pub fn __contains__(&self, py: Python, value: &PyAny) -> PyResult<bool> {
+        match &self.0 {
+            SharedType::Integrated(array) => {
+                if YPyType::try_from(value).is_ok() {
+                    return self.1.within(array.as_ref(), || {
+                        for element in array.iter() {
+                            if !matches!(element, Value::Any(_))
+                                && element.into_py(py).as_ref(py).eq(value)?
+                            {
+                                return Ok(true);
+                            }
+                        }
+                        Ok(false)
+                    });
+                }
+                let expected = match CompatiblePyType::try_from(value).and_then(Any::try_from) {
+                    Ok(expected) => expected,
+                    // Values which cannot be stored in a document cannot be found in it either
+                    Err(_) => return Ok(false),
+                };
+                Ok(array
+                    .iter()
+                    .any(|element| matches!(element, Value::Any(any) if any_eq(&any, &expected))))
+            }
+            SharedType::Prelim(items) => {
+                for item in items.iter() {
+                    if item.as_ref(py).eq(value)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
     /// Returns an iterator that can be used to traverse over the values stored withing this
     /// instance of `YArray`.
     ///
@@ -308,17 +878,40 @@ impl YArray {
     /// Subscribes to all operations happening over this instance of `YArray`. All changes are
     /// batched and eventually triggered during transaction commit phase.
     /// Returns a `SubscriptionId` which can be used to cancel the callback with `unobserve`.
-    pub fn observe(&mut self, f: PyObject) -> PyResult<ShallowSubscription> {
+    ///
+    /// If `debounce_ms` is given, deltas of events following each other within that many
+    /// milliseconds are merged, and passed to the callback as a single delta once the array stays
+    /// unchanged for `debounce_ms`. Debounced callbacks are called on a timer thread.
+    pub fn observe(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        weak: Option<bool>,
+        debounce_ms: Option<u64>,
+    ) -> PyResult<ShallowSubscription> {
+        let f = Callback::observer(py, f, weak)?;
+        let debouncer = debounce_ms.map(|delay| Debouncer::new(f.clone(), delay));
         match &mut self.0 {
             SharedType::Integrated(array) => {
+                f.set_owner(self.1.doc_key(py));
+                let observed = Observed::new(array.as_ref(), &self.1);
                 let sub: SubscriptionId = array
                     .observe(move |txn, e| {
-                        Python::with_gil(|py| {
-                            let event = YArrayEvent::new(e, txn);
-                            if let Err(err) = f.call1(py, (event,)) {
-                                err.restore(py)
+                        shutdown::with_gil(|py| match &debouncer {
+                            Some(debouncer) => {
+                                let delta = debounce::array_delta(py, e.delta(txn));
+                                // Array deltas contain no text, so any offset kind counts them.
+                                debouncer.push(py, delta, &OffsetKind::Utf32)
                             }
-                        })
+                            None => {
+                                let event = YArrayEvent::new(e, txn, observed.clone());
+                                if let Err(err) =
+                                    metrics::measure(Metric::Callbacks, || f.call1(py, (event,)))
+                                {
+                                    logging::callback_failed(py, err)
+                                }
+                            }
+                        });
                     })
                     .into();
                 Ok(ShallowSubscription(sub))
@@ -327,17 +920,27 @@ impl YArray {
         }
     }
     /// Observes YArray events and events of all child elements.
-    pub fn observe_deep(&mut self, f: PyObject) -> PyResult<DeepSubscription> {
+    pub fn observe_deep(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        weak: Option<bool>,
+    ) -> PyResult<DeepSubscription> {
+        let f = Callback::observer(py, f, weak)?;
         match &mut self.0 {
             SharedType::Integrated(array) => {
+                f.set_owner(self.1.doc_key(py));
+                let observed = Observed::new(array.as_ref(), &self.1);
                 let sub: SubscriptionId = array
                     .observe_deep(move |txn, events| {
-                        Python::with_gil(|py| {
-                            let events = events_into_py(txn, events);
-                            if let Err(err) = f.call1(py, (events,)) {
-                                err.restore(py)
+                        shutdown::with_gil(|py| {
+                            let events = events_into_py(py, txn, events, &observed);
+                            if let Err(err) =
+                                metrics::measure(Metric::Callbacks, || f.call1(py, (events,)))
+                            {
+                                logging::callback_failed(py, err)
                             }
-                        })
+                        });
                     })
                     .into();
                 Ok(DeepSubscription(sub))
@@ -359,12 +962,99 @@ impl YArray {
 }
 
 impl YArray {
+    /// Wraps an integrated `array` located at a given `location`.
+    pub(crate) fn located(array: Array, location: Location) -> Self {
+        YArray(SharedType::new(array), location)
+    }
+
+    /// Validates `items` about to be inserted into an array at a given `index` against a JSON
+    /// Schema attached to its root type.
+    fn check_insert(
+        array: &Array,
+        location: &Location,
+        txn: &YTransaction,
+        index: u32,
+        items: &[PyObject],
+    ) -> PyResult<()> {
+        if let Some(scope) = txn.schema_scope(array.as_ref(), location) {
+            let values = Python::with_gil(|py| {
+                items
+                    .iter()
+                    .map(|item| plain_any(item.as_ref(py)))
+                    .collect::<PyResult<Vec<Any>>>()
+            })?;
+            scope.check_insert(index, array.len(), &values)?;
+        }
+        Ok(())
+    }
+
+    /// Returns an underlying contents of this `YArray` instance as a single `Any` value.
+    fn to_any(&self, py: Python) -> PyResult<Any> {
+        match &self.0 {
+            SharedType::Integrated(array) => Ok(array.to_json()),
+            SharedType::Prelim(items) => {
+                let list = PyList::new(py, items);
+                Any::try_from(CompatiblePyType::List(list))
+            }
+        }
+    }
+
+    /// Rearranges elements, so that an element at `order[i]` ends up at index `i`. Elements of
+    /// integrated arrays are moved, except for the longest sequence of them, which already is in
+    /// the expected order.
+    fn reorder(&mut self, txn: &mut YTransaction, order: &[usize]) {
+        match &mut self.0 {
+            SharedType::Integrated(array) => {
+                let mut ranks = vec![0; order.len()];
+                for (rank, index) in order.iter().enumerate() {
+                    ranks[*index] = rank;
+                }
+                let keep = longest_increasing(&ranks);
+                // Ranks of elements in their current order
+                let mut current = ranks;
+                for (rank, index) in order.iter().enumerate() {
+                    if keep[*index] {
+                        continue;
+                    }
+                    // Every moved element is placed right after its predecessor
+                    let source = current.iter().position(|r| *r == rank).unwrap();
+                    let target = match rank {
+                        0 => 0,
+                        _ => current.iter().position(|r| *r == rank - 1).unwrap() + 1,
+                    };
+                    array.move_to(txn, source as u32, target as u32);
+                    current.remove(source);
+                    current.insert(if source < target { target - 1 } else { target }, rank);
+                }
+            }
+            SharedType::Prelim(items) => {
+                let mut slots: Vec<Option<PyObject>> =
+                    std::mem::take(items).into_iter().map(Some).collect();
+                *items = order
+                    .iter()
+                    .map(|index| slots[*index].take().unwrap())
+                    .collect();
+            }
+        }
+    }
+
     /// Gets a single element from a YArray.
+    /// Returns an element inserted from a given `item`: shared types in their integrated form,
+    /// and other values as they were given, as converting them back would only copy them.
+    fn inserted(py: Python, array: &Array, index: u32, item: PyObject) -> PyObject {
+        match array.get(index) {
+            Some(Value::Any(_)) | None => item,
+            Some(value) => value.into_py(py),
+        }
+    }
+
     fn get_element(&self, index: u32) -> PyResult<PyObject> {
         match &self.0 {
             SharedType::Integrated(v) => {
                 if let Some(value) = v.get(index as u32) {
-                    Ok(Python::with_gil(|py| value.into_py(py)))
+                    Ok(self
+                        .1
+                        .within(v.as_ref(), || Python::with_gil(|py| value.into_py(py))))
                 } else {
                     Err(PyIndexError::default_message())
                 }
@@ -385,32 +1075,34 @@ impl YArray {
             start, stop, step, ..
         } = slice.indices(self.__len__().try_into().unwrap()).unwrap();
         match &self.0 {
-            SharedType::Integrated(arr) => Python::with_gil(|py| {
-                if step < 0 {
-                    let step = step.abs() as usize;
-                    let (start, stop) = ((stop + 1) as usize, (start + 1) as usize);
-                    let values: Vec<PyObject> = arr
-                        .iter()
-                        .enumerate()
-                        .skip(start)
-                        .step_by(step)
-                        .take_while(|(i, _)| i < &stop)
-                        .map(|(_, el)| el.into_py(py))
-                        .collect();
-                    let values: Vec<PyObject> = values.into_iter().rev().collect();
-                    Ok(values.into_py(py))
-                } else {
-                    let (start, stop, step) = (start as usize, stop as usize, step as usize);
-                    let values: Vec<PyObject> = arr
-                        .iter()
-                        .enumerate()
-                        .skip(start)
-                        .step_by(step)
-                        .take_while(|(i, _)| i < &stop)
-                        .map(|(_, el)| el.into_py(py))
-                        .collect();
-                    Ok(values.into_py(py))
-                }
+            SharedType::Integrated(arr) => self.1.within(arr.as_ref(), || {
+                Python::with_gil(|py| {
+                    if step < 0 {
+                        let step = step.abs() as usize;
+                        let (start, stop) = ((stop + 1) as usize, (start + 1) as usize);
+                        let values: Vec<PyObject> = arr
+                            .iter()
+                            .enumerate()
+                            .skip(start)
+                            .step_by(step)
+                            .take_while(|(i, _)| i < &stop)
+                            .map(|(_, el)| el.into_py(py))
+                            .collect();
+                        let values: Vec<PyObject> = values.into_iter().rev().collect();
+                        Ok(values.into_py(py))
+                    } else {
+                        let (start, stop, step) = (start as usize, stop as usize, step as usize);
+                        let values: Vec<PyObject> = arr
+                            .iter()
+                            .enumerate()
+                            .skip(start)
+                            .step_by(step)
+                            .take_while(|(i, _)| i < &stop)
+                            .map(|(_, el)| el.into_py(py))
+                            .collect();
+                        Ok(values.into_py(py))
+                    }
+                })
             }),
             SharedType::Prelim(arr) => Python::with_gil(|py| {
                 if step < 0 {
@@ -429,6 +1121,24 @@ impl YArray {
         }
     }
 
+    fn primitive(item: &PyAny) -> PyResult<Any> {
+        match CompatiblePyType::try_from(item)? {
+            CompatiblePyType::YType(_) => Err(PyTypeError::new_err(
+                "push_primitives only accepts primitive values, use extend to insert shared types",
+            )),
+            py_type => Any::try_from(py_type),
+        }
+    }
+
+    /// Checks if a given value consists only of numbers and booleans, possibly nested in arrays.
+    fn is_numeric(value: &Any) -> bool {
+        match value {
+            Any::Number(_) | Any::BigInt(_) | Any::Bool(_) => true,
+            Any::Array(items) => items.iter().all(Self::is_numeric),
+            _ => false,
+        }
+    }
+
     fn normalize_index(&self, index: isize) -> u32 {
         if index < 0 {
             (self.__len__() as isize + index) as u32
@@ -437,7 +1147,12 @@ impl YArray {
         }
     }
 
-    pub fn insert_multiple_at(dst: &Array, txn: &mut Transaction, index: u32, src: Vec<PyObject>) {
+    pub fn insert_multiple_at(
+        dst: &Array,
+        txn: &mut Transaction,
+        index: u32,
+        src: Vec<PyObject>,
+    ) -> PyResult<()> {
         let mut j = index;
         let mut i = 0;
         Python::with_gil(|py| {
@@ -460,12 +1175,14 @@ impl YArray {
                     dst.insert_range(txn, j, anys);
                     j += len;
                 } else {
-                    let wrapper = PyObjectWrapper(src[i].clone());
-                    dst.insert(txn, j, wrapper);
+                    let value = PreparedValue::new(src[i].as_ref(py))?;
+                    dst.insert(txn, j, value);
+                    take_integration_error()?;
                     i += 1;
                     j += 1;
                 }
             }
+            Ok(())
         })
     }
 
@@ -490,27 +1207,122 @@ pub enum Index<'a> {
     Slice(&'a PySlice),
 }
 
+/// Converts a value compared by `YArray.values_where`, which has to be a primitive value.
+fn primitive(value: &PyAny) -> PyResult<Any> {
+    let value: CompatiblePyType = value.extract()?;
+    match value.try_into() {
+        Ok(value) if is_primitive(&value) => Ok(value),
+        _ => Err(PyTypeError::new_err(
+            "Rows can only be filtered by None, booleans, numbers and strings",
+        )),
+    }
+}
+
+fn is_primitive(value: &Any) -> bool {
+    matches!(
+        value,
+        Any::Null | Any::Bool(_) | Any::Number(_) | Any::BigInt(_) | Any::String(_)
+    )
+}
+
+/// Compares primitive values, where integers and floats are compared by their numeric value.
+fn primitive_eq(a: &Any, b: &Any) -> bool {
+    match (a, b) {
+        (Any::Number(a), Any::BigInt(b)) | (Any::BigInt(b), Any::Number(a)) => *a == *b as f64,
+        (a, b) => a == b,
+    }
+}
+
 /// Event generated by `YArray.observe` method. Emitted during transaction commit phase.
+/// Returns which of given `ranks` belong to their longest increasing subsequence.
+fn longest_increasing(ranks: &[usize]) -> Vec<bool> {
+    // Indexes of the last elements of increasing subsequences of every length found so far
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; ranks.len()];
+    for (i, rank) in ranks.iter().enumerate() {
+        let len = tails.partition_point(|&j| ranks[j] < *rank);
+        if len > 0 {
+            previous[i] = Some(tails[len - 1]);
+        }
+        if len == tails.len() {
+            tails.push(i);
+        } else {
+            tails[len] = i;
+        }
+    }
+    let mut keep = vec![false; ranks.len()];
+    let mut current = tails.last().copied();
+    while let Some(i) = current {
+        keep[i] = true;
+        current = previous[i];
+    }
+    keep
+}
+
+/// Compares `Any` values the way Python compares their converted counterparts, eg. `1 == 1.0`.
+fn any_eq(a: &Any, b: &Any) -> bool {
+    match (a, b) {
+        (Any::Array(a), Any::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| any_eq(a, b))
+        }
+        (Any::Map(a), Any::Map(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, a)| b.get(key).map_or(false, |b| any_eq(a, b)))
+        }
+        (Any::String(a), Any::String(b)) => a == b,
+        (Any::Buffer(a), Any::Buffer(b)) => a == b,
+        (Any::BigInt(a), Any::BigInt(b)) => a == b,
+        (Any::Null, Any::Null) | (Any::Undefined, Any::Undefined) => true,
+        // Both of them are converted into `None` by default
+        (Any::Null, Any::Undefined) | (Any::Undefined, Any::Null) => !undefined_as_sentinel(),
+        _ => match (as_number(a), as_number(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        },
+    }
+}
+
+/// Returns a numeric value of numbers and booleans, which are integers in Python.
+fn as_number(value: &Any) -> Option<f64> {
+    match value {
+        Any::Bool(b) => Some(*b as u8 as f64),
+        Any::Number(n) => Some(*n),
+        Any::BigInt(n) => Some(*n as f64),
+        _ => None,
+    }
+}
+
 #[pyclass(unsendable)]
 pub struct YArrayEvent {
     inner: *const ArrayEvent,
     txn: *const Transaction,
+    origin: Option<PyObject>,
     target: Option<PyObject>,
     delta: Option<PyObject>,
+    observed: Observed,
 }
 
 impl YArrayEvent {
-    pub fn new(event: &ArrayEvent, txn: &Transaction) -> Self {
+    pub(crate) fn new(event: &ArrayEvent, txn: &Transaction, observed: Observed) -> Self {
         let inner = event as *const ArrayEvent;
         let txn = txn as *const Transaction;
         YArrayEvent {
             inner,
             txn,
+            origin: origin::committing(),
             target: None,
             delta: None,
+            observed,
         }
     }
 
+    /// Returns a location of the target of this event.
+    fn location(&self) -> Location {
+        let target = self.inner().target();
+        self.observed.locate(target.as_ref(), self.inner().path())
+    }
+
     fn inner(&self) -> &ArrayEvent {
         unsafe { self.inner.as_ref().unwrap() }
     }
@@ -522,14 +1334,20 @@ impl YArrayEvent {
 
 #[pymethods]
 impl YArrayEvent {
+    /// Origin of the transaction which produced this event, see `YTransaction.with_origin`.
+    #[getter]
+    pub fn origin(&self, py: Python) -> PyObject {
+        origin::into_py(py, &self.origin)
+    }
+
     /// Returns a current shared type instance, that current event changes refer to.
     #[getter]
     pub fn target(&mut self) -> PyObject {
         if let Some(target) = self.target.as_ref() {
             target.clone()
         } else {
-            let target: PyObject =
-                Python::with_gil(|py| YArray::from(self.inner().target().clone()).into_py(py));
+            let target = YArray::located(self.inner().target().clone(), self.location());
+            let target: PyObject = Python::with_gil(|py| target.into_py(py));
             self.target = Some(target.clone());
             target
         }
@@ -559,13 +1377,17 @@ impl YArrayEvent {
         if let Some(delta) = &self.delta {
             delta.clone()
         } else {
-            let delta: PyObject = Python::with_gil(|py| {
-                let delta = self
-                    .inner()
-                    .delta(self.txn())
-                    .into_iter()
-                    .map(|change| Python::with_gil(|py| change.into_py(py)));
-                PyList::new(py, delta).into()
+            let location = self.location();
+            let target = self.inner().target();
+            let delta: PyObject = location.within(target.as_ref(), || {
+                Python::with_gil(|py| {
+                    let delta = self
+                        .inner()
+                        .delta(self.txn())
+                        .into_iter()
+                        .map(|change| change.into_py(py));
+                    PyList::new(py, delta).into()
+                })
             });
             self.delta = Some(delta.clone());
             delta
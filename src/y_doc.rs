@@ -1,19 +1,48 @@
+use crate::autosave::{Autosave, Target};
+use crate::batch::{Batch, BatchState};
+use crate::dispatcher::{Dispatcher, TransactionRecord};
+use crate::json_schema::{self, Schema, Schemas};
+use crate::lifecycle::Lifecycle;
+use crate::location::{DocRef, Location};
+use crate::logging::{self, Level};
+use crate::metrics::{self, Metric};
+use crate::origin;
+use crate::readonly::ReadOnlyDoc;
+use crate::revert::{self, Change, OriginLog};
+use crate::root_proxy::RootProxy;
+use crate::shared_types::{check_root_type, sha256_hex, EncodingError};
+use crate::shutdown::{self, Callback};
+use crate::snapshot;
+use crate::state_vector;
+use crate::stats::{DocStats, MemoryUsage};
+use crate::template::Template;
+use crate::type_conversions::{binary_into_py, BinaryInput};
+use crate::update_filter::UpdateFilters;
+use crate::update_transform::UpdateTransforms;
+use crate::versions;
 use crate::y_array::YArray;
 use crate::y_map::YMap;
 use crate::y_text::YText;
 use crate::y_transaction::YTransaction;
 use crate::y_xml::YXmlElement;
 use crate::y_xml::YXmlText;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyBytes;
 use pyo3::types::PyTuple;
-use yrs::updates::encoder::Encode;
+use pyo3::types::{PyDict, PyList, PyString};
+use pyo3::AsPyPointer;
+use std::cell::Cell;
+use std::time::Duration;
+use yrs::types::{TYPE_REFS_XML_ELEMENT, TYPE_REFS_XML_TEXT};
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::{Encode, Encoder, EncoderV1, EncoderV2};
 use yrs::AfterTransactionEvent as YrsAfterTransactionEvent;
 use yrs::Doc;
 use yrs::OffsetKind;
 use yrs::Options;
 use yrs::SubscriptionId;
 use yrs::Transaction;
+use yrs::{StateVector, Update};
 
 /// A Ypy document type. Documents are most important units of collaborative resources management.
 /// All shared collections live within a scope of their corresponding documents. All updates are
@@ -35,8 +64,24 @@ use yrs::Transaction;
 ///     output = text.to_string(txn)
 ///     print(output)
 /// ```
-#[pyclass(unsendable, subclass)]
-pub struct YDoc(pub Doc);
+#[pyclass(unsendable, subclass, weakref)]
+pub struct YDoc {
+    pub inner: Doc,
+    /// Filters and limits applied to remote updates, see `YDoc.add_update_filter`.
+    pub(crate) filters: UpdateFilters,
+    /// Transforms of encoded updates, see `YDoc.add_update_transform`.
+    pub(crate) transforms: UpdateTransforms,
+    /// Transaction shared by an active `batch` scope.
+    pub(crate) batch: BatchState,
+    /// Set once the document has been destroyed.
+    pub(crate) lifecycle: Lifecycle,
+    /// Origins of recent transactions, used by `revert_origin`.
+    pub(crate) origin_log: OriginLog,
+    /// JSON Schemas attached to root types with `set_schema`.
+    pub(crate) schemas: Schemas,
+    /// Reference to this document shared with its transactions, see `YDoc::doc_ref`.
+    pub(crate) doc_ref: DocRef,
+}
 
 #[pymethods]
 impl YDoc {
@@ -72,13 +117,125 @@ impl YDoc {
             options.skip_gc = skip_gc;
         }
 
-        Ok(YDoc(Doc::with_options(options)))
+        Ok(YDoc {
+            inner: Doc::with_options(options),
+            filters: UpdateFilters::default(),
+            transforms: UpdateTransforms::default(),
+            batch: BatchState::default(),
+            lifecycle: Lifecycle::default(),
+            origin_log: OriginLog::default(),
+            schemas: Schemas::default(),
+            doc_ref: DocRef::default(),
+        })
+    }
+
+    /// Creates a new document with root types matching a plain JSON `snapshot`, as exported from
+    /// Yjs with `Y.Doc.toJSON()`. Strings become `YText` root types, lists become `YArray` root
+    /// types and dictionaries become `YMap` root types, while their contents are stored as plain
+    /// values. Remaining arguments are passed to the `YDoc` constructor.
+    ///
+    /// This allows to seed collaborative documents with content generated outside of Ypy, eg. to
+    /// migrate from a non-CRDT storage.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc.from_json_snapshot({'title': 'Notes', 'tags': ['draft'], 'meta': {'version': 1}})
+    /// assert str(doc.get_text('title')) == 'Notes'
+    /// ```
+    #[staticmethod]
+    pub fn from_json_snapshot(
+        py: Python,
+        snapshot: &PyDict,
+        client_id: Option<u64>,
+        offset_kind: Option<String>,
+        skip_gc: Option<bool>,
+    ) -> PyResult<YDoc> {
+        let doc = YDoc::new(client_id, offset_kind, skip_gc)?;
+        let mut txn = doc.begin_transaction();
+        for (name, value) in snapshot.iter() {
+            let name: &str = name.extract()?;
+            if let Ok(text) = value.downcast::<PyString>() {
+                let mut root = txn.get_text(name)?;
+                root.extend(&mut txn, text.to_str()?)?;
+            } else if value.is_instance_of::<PyList>()? {
+                let mut root = txn.get_array(name)?;
+                root.extend(py, &mut txn, value.into())?;
+            } else if value.is_instance_of::<PyDict>()? {
+                let mut root = txn.get_map(name)?;
+                root.update(&mut txn, value.into())?;
+            } else {
+                return Err(PyTypeError::new_err(format!(
+                    "Root type '{name}' of a JSON snapshot must be a string, a list or a dictionary, found {}",
+                    value.get_type().name()?
+                )));
+            }
+        }
+        txn.commit();
+        drop(txn);
+        Ok(doc)
+    }
+
+    /// Creates root types and their initial content from a declarative `spec`, in a single
+    /// transaction, so that servers can initialize new rooms consistently. The `spec` maps names
+    /// of root types to their content: strings declare `YText` root types, lists declare `YArray`
+    /// root types and dictionaries declare `YMap` root types. Preliminary shared types can be
+    /// used as well, eg. to nest shared types in the initial content, and Ypy classes, such as
+    /// `YXmlElement`, declare empty root types of their kind.
+    ///
+    /// Root types which already have content are left untouched, so applying a template to an
+    /// initialized document is a no-op. Contents of the `spec` are checked before any root type is
+    /// changed. Returns a list of names of root types which have been created or filled.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc, YMap, YText, YXmlElement
+    ///
+    /// TEMPLATE = {
+    ///     'title': 'Untitled',
+    ///     'tasks': [YMap({'title': YText('Invite collaborators'), 'done': False})],
+    ///     'settings': {'theme': 'light'},
+    ///     'body': YXmlElement,
+    /// }
+    ///
+    /// doc = YDoc()
+    /// assert doc.apply_template(TEMPLATE) == ['title', 'tasks', 'settings', 'body']
+    /// assert doc.apply_template(TEMPLATE) == []
+    /// ```
+    pub fn apply_template(slf: &PyCell<Self>, spec: &PyDict) -> PyResult<Vec<String>> {
+        let py = slf.py();
+        let mut templates = Vec::with_capacity(spec.len());
+        for (name, value) in spec.iter() {
+            let name: String = name.extract()?;
+            let template = Template::from_py(&name, value)?;
+            templates.push((name, template));
+        }
+        let txn = YDoc::py_begin_transaction(slf)?;
+        let mut txn = txn.borrow_mut(py);
+        let mut initialized = Vec::new();
+        for (name, template) in templates {
+            if template.apply(py, &slf.borrow().inner, &mut txn, &name)? {
+                initialized.push(name);
+            }
+        }
+        txn.commit();
+        Ok(initialized)
     }
 
     /// Gets globally unique identifier of this `YDoc` instance.
     #[getter]
     pub fn client_id(&self) -> u64 {
-        self.0.client_id as u64
+        self.inner.client_id as u64
+    }
+
+    /// Returns a current state vector of this document.
+    pub fn state_vector(&self) -> PyResult<state_vector::StateVector> {
+        state_vector::StateVector::decode(BinaryInput::Owned(
+            self.inner.transact().state_vector().encode_v1(),
+        ))
     }
 
     /// Returns a new transaction for this document. Ypy shared data types execute their
@@ -86,7 +243,7 @@ impl YDoc {
     /// transaction at the time - subsequent attempts will cause exception to be thrown.
     ///
     /// Transactions started with `doc.begin_transaction` can be released by deleting the transaction object
-    /// method.
+    /// method. Within a `batch` scope, the transaction shared by the batch is returned instead.
     ///
     /// Example:
     ///
@@ -97,16 +254,40 @@ impl YDoc {
     /// with doc.begin_transaction() as txn:
     ///     text.insert(txn, 0, 'hello world')
     /// ```
-    pub fn begin_transaction(&self) -> YTransaction {
-        YTransaction::new(self.0.transact())
+    #[pyo3(name = "begin_transaction")]
+    pub fn py_begin_transaction(slf: &PyCell<Self>) -> PyResult<Py<YTransaction>> {
+        YDoc::doc_ref(slf)?;
+        slf.borrow().shared_transaction(slf.py())
     }
 
-    pub fn transact(&mut self, callback: PyObject) -> PyResult<PyObject> {
-        let txn = self.begin_transaction();
-        Python::with_gil(|py| {
-            let args = PyTuple::new(py, std::iter::once(txn.into_py(py)));
-            callback.call(py, args, None)
-        })
+    pub fn transact(slf: &PyCell<Self>, callback: PyObject) -> PyResult<PyObject> {
+        let py = slf.py();
+        let txn = YDoc::py_begin_transaction(slf)?;
+        let args = PyTuple::new(py, std::iter::once(txn));
+        callback.call(py, args, None)
+    }
+
+    /// Returns a scope grouping all transactions of this document into a single one until it's
+    /// exited, so that observers are called once with all the changes made in the scope. This
+    /// reduces network chatter during bulk imports, as a single update is emitted.
+    ///
+    /// Transactions started with `begin_transaction` or `transact`, and updates applied with
+    /// `apply_update` share a transaction, which is also returned when the scope is entered.
+    /// Changes are committed even if the scope is exited with an exception.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc, apply_update
+    ///
+    /// doc = YDoc()
+    /// doc.observe_after_transaction(lambda e: send(e.get_update()))
+    /// with doc.batch():
+    ///     for update in imported_updates:
+    ///         apply_update(doc, update)
+    /// ```
+    pub fn batch(slf: &PyCell<Self>) -> Batch {
+        Batch::new(slf.into())
     }
 
     /// Returns a `YMap` shared data type, that's accessible for subsequent accesses using given
@@ -114,10 +295,12 @@ impl YDoc {
     ///
     /// If there was no instance with this name before, it will be created and then returned.
     ///
-    /// If there was an instance with this name, but it was of different type, it will be projected
-    /// onto `YMap` instance.
-    pub fn get_map(&mut self, name: &str) -> YMap {
-        self.begin_transaction().get_map(name)
+    /// If there was an instance with this name, but it was of different type, a `TypeError` is
+    /// raised.
+    pub fn get_map(slf: &PyCell<Self>, name: &str) -> PyResult<YMap> {
+        slf.borrow().lifecycle.check()?;
+        YDoc::doc_ref(slf)?;
+        slf.borrow().begin_transaction().get_map(name)
     }
 
     /// Returns a `YXmlElement` shared data type, that's accessible for subsequent accesses using
@@ -125,10 +308,16 @@ impl YDoc {
     ///
     /// If there was no instance with this name before, it will be created and then returned.
     ///
-    /// If there was an instance with this name, but it was of different type, it will be projected
-    /// onto `YXmlElement` instance.
-    pub fn get_xml_element(&mut self, name: &str) -> YXmlElement {
-        YXmlElement(self.begin_transaction().get_xml_element(name))
+    /// If there was an instance with this name, but it was of different type, a `TypeError` is
+    /// raised.
+    pub fn get_xml_element(slf: &PyCell<Self>, name: &str) -> PyResult<YXmlElement> {
+        slf.borrow().lifecycle.check()?;
+        let shared = slf.borrow().begin_transaction().get_xml_element(name);
+        check_root_type(shared.as_ref(), name, TYPE_REFS_XML_ELEMENT)?;
+        Ok(YXmlElement(
+            shared,
+            Location::root(&YDoc::doc_ref(slf)?, name),
+        ))
     }
 
     /// Returns a `YXmlText` shared data type, that's accessible for subsequent accesses using given
@@ -136,10 +325,13 @@ impl YDoc {
     ///
     /// If there was no instance with this name before, it will be created and then returned.
     ///
-    /// If there was an instance with this name, but it was of different type, it will be projected
-    /// onto `YXmlText` instance.
-    pub fn get_xml_text(&mut self, name: &str) -> YXmlText {
-        YXmlText(self.begin_transaction().get_xml_text(name))
+    /// If there was an instance with this name, but it was of different type, a `TypeError` is
+    /// raised.
+    pub fn get_xml_text(slf: &PyCell<Self>, name: &str) -> PyResult<YXmlText> {
+        slf.borrow().lifecycle.check()?;
+        let shared = slf.borrow().begin_transaction().get_xml_text(name);
+        check_root_type(shared.as_ref(), name, TYPE_REFS_XML_TEXT)?;
+        Ok(YXmlText(shared, Location::root(&YDoc::doc_ref(slf)?, name)))
     }
 
     /// Returns a `YArray` shared data type, that's accessible for subsequent accesses using given
@@ -147,10 +339,12 @@ impl YDoc {
     ///
     /// If there was no instance with this name before, it will be created and then returned.
     ///
-    /// If there was an instance with this name, but it was of different type, it will be projected
-    /// onto `YArray` instance.
-    pub fn get_array(&mut self, name: &str) -> YArray {
-        self.begin_transaction().get_array(name)
+    /// If there was an instance with this name, but it was of different type, a `TypeError` is
+    /// raised.
+    pub fn get_array(slf: &PyCell<Self>, name: &str) -> PyResult<YArray> {
+        slf.borrow().lifecycle.check()?;
+        YDoc::doc_ref(slf)?;
+        slf.borrow().begin_transaction().get_array(name)
     }
 
     /// Returns a `YText` shared data type, that's accessible for subsequent accesses using given
@@ -158,25 +352,868 @@ impl YDoc {
     ///
     /// If there was no instance with this name before, it will be created and then returned.
     ///
-    /// If there was an instance with this name, but it was of different type, it will be projected
-    /// onto `YText` instance.
-    pub fn get_text(&mut self, name: &str) -> YText {
-        self.begin_transaction().get_text(name)
+    /// If there was an instance with this name, but it was of different type, a `TypeError` is
+    /// raised.
+    pub fn get_text(slf: &PyCell<Self>, name: &str) -> PyResult<YText> {
+        slf.borrow().lifecycle.check()?;
+        YDoc::doc_ref(slf)?;
+        slf.borrow().begin_transaction().get_text(name)
+    }
+
+    /// Returns a proxy giving access to root types of this document as its attributes, eg.
+    /// `doc.root.tasks`, which creates missing root types and infers their types. Types can be
+    /// declared by calling the proxy, eg. `doc.root(tasks=YArray).tasks`.
+    #[getter]
+    pub fn root(slf: &PyCell<Self>) -> RootProxy {
+        RootProxy::new(slf)
+    }
+
+    /// Returns a read-only view of this document. Shared types obtained from the view can be read
+    /// and observed, but raise a `ReadOnlyError` on any attempt to modify them, which is useful to
+    /// enforce permissions of individual connections on a server. The view doesn't keep this
+    /// document alive.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// view = doc.readonly_view()
+    /// text = view.get_text('name')
+    /// print(str(text))
+    /// ```
+    pub fn readonly_view(slf: &PyCell<Self>) -> PyResult<ReadOnlyDoc> {
+        ReadOnlyDoc::new(slf)
+    }
+
+    /// Returns a hex encoded SHA-256 digest of this document's state. Replicas which integrated
+    /// the same updates produce the same digest, no matter in which order they received them, so
+    /// digests can be exchanged to cheaply verify that documents converged.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc, encode_state_as_update, apply_update
+    ///
+    /// local_doc = YDoc()
+    /// remote_doc = YDoc()
+    /// apply_update(remote_doc, encode_state_as_update(local_doc))
+    /// assert local_doc.state_hash() == remote_doc.state_hash()
+    /// ```
+    pub fn state_hash(&self, py: Python) -> PyResult<String> {
+        let update = decode_update(&diff(&self.inner, &StateVector::default()))?;
+        // Blocks are split and squashed differently depending on the order in which updates were
+        // received, so the state is normalized by integrating it into an empty document at once
+        let replica = Doc::new();
+        replica.transact().apply_update(update);
+        sha256_hex(py, &diff(&replica, &StateVector::default()))
+    }
+
+    /// Synchronizes this document with `other` document living in the same process, by
+    /// exchanging their state vectors and missing updates in both directions. Afterwards both
+    /// documents have the same state.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// local_doc = YDoc()
+    /// remote_doc = YDoc()
+    /// with local_doc.begin_transaction() as txn:
+    ///     txn.get_text('name').extend(txn, 'hello')
+    /// local_doc.sync_with(remote_doc)
+    /// assert str(remote_doc.get_text('name')) == 'hello'
+    /// ```
+    pub fn sync_with(&self, py: Python, other: PyRef<YDoc>) -> PyResult<()> {
+        let local_sv = self.inner.transact().state_vector();
+        let remote_sv = other.inner.transact().state_vector();
+        let to_remote = diff(&self.inner, &remote_sv);
+        let to_local = diff(&other.inner, &local_sv);
+        let mut remote_txn = other.inner.transact();
+        let mut local_txn = self.inner.transact();
+        // Neither of the documents is changed if any of the updates is rejected
+        other.filters.check(py, &remote_txn, &to_remote)?;
+        self.filters.check(py, &local_txn, &to_local)?;
+        remote_txn.apply_update(decode_update(&to_remote)?);
+        local_txn.apply_update(decode_update(&to_local)?);
+        Ok(())
+    }
+
+    /// Captures a current state of this document as a snapshot, which can be passed to
+    /// `materialize_at` later on to browse or restore this version of the document. Snapshots
+    /// combine a state vector with a delete set, as deletions don't advance state vectors.
+    pub fn snapshot(&self, py: Python) -> PyResult<PyObject> {
+        let update = diff(&self.inner, &StateVector::default());
+        let state_vector = self.inner.transact().state_vector().encode_v1();
+        let snapshot = snapshot::encode_snapshot(&update, &state_vector)?;
+        Ok(binary_into_py(py, snapshot))
+    }
+
+    /// Rebuilds a state of this document at the time a given `snapshot` was taken with
+    /// `YDoc.snapshot`. Returns a new, detached document: changes made to it don't affect this
+    /// document.
+    ///
+    /// History is only preserved by documents created with `skip_gc=True`. If content visible in
+    /// the snapshot has been garbage collected since then, a `ValueError` is raised.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc(skip_gc=True)
+    /// text = doc.get_text('name')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, 'hello')
+    /// version = doc.snapshot()
+    /// with doc.begin_transaction() as txn:
+    ///     text.delete_range(txn, 0, 5)
+    /// assert str(doc.materialize_at(version).get_text('name')) == 'hello'
+    /// ```
+    pub fn materialize_at(&self, snapshot: BinaryInput) -> PyResult<YDoc> {
+        Ok(YDoc {
+            inner: rewind(&self.inner, &snapshot)?,
+            filters: UpdateFilters::default(),
+            transforms: UpdateTransforms::default(),
+            batch: BatchState::default(),
+            lifecycle: Lifecycle::default(),
+            origin_log: OriginLog::default(),
+            schemas: Schemas::default(),
+            doc_ref: DocRef::default(),
+        })
+    }
+
+    /// Stores a snapshot of the current state of this document as a version of a given `name`,
+    /// so that apps can keep a list of versions without an external storage. Versions live in a
+    /// root map named `__versions__`, which is synchronized with other replicas like any other
+    /// root type, and are not included in snapshots compared by `diff_versions`.
+    ///
+    /// As with `materialize_at`, versions can only be compared as long as their content hasn't
+    /// been garbage collected, ie. for documents created with `skip_gc=True`. Fails with a
+    /// `ValueError` if a version of a given `name` already exists.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc(skip_gc=True)
+    /// text = doc.get_text('name')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, 'hello')
+    /// doc.create_version('draft')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, ' world')
+    /// doc.create_version('final')
+    /// assert doc.diff_versions('draft', 'final') == {
+    ///     'name': {'kind': 'text', 'before': 'hello', 'after': 'hello world'}
+    /// }
+    /// ```
+    pub fn create_version(&self, py: Python, name: &str) -> PyResult<()> {
+        self.lifecycle.check()?;
+        let snapshot = self.snapshot(py)?;
+        let txn = self.shared_transaction(py)?;
+        let mut txn = txn.borrow_mut(py);
+        if versions::find(&mut txn, name).is_ok() {
+            return Err(PyValueError::new_err(format!(
+                "Version '{name}' already exists"
+            )));
+        }
+        let entry = PyDict::new(py);
+        entry.set_item("snapshot", snapshot)?;
+        entry.set_item("created", py.import("time")?.call_method0("time")?)?;
+        let mut map = txn.get_map(versions::VERSIONS)?;
+        map.set(&mut txn, name, entry.into())?;
+        txn.commit();
+        Ok(())
+    }
+
+    /// Returns versions of this document stored with `create_version`, from the oldest to the
+    /// newest one. Every version is a dictionary with its `name`, a `created` timestamp in
+    /// seconds since the epoch and a `snapshot`, which can be passed to `materialize_at`.
+    pub fn list_versions(&self, py: Python) -> PyResult<PyObject> {
+        let versions = versions::list(&mut self.inner.transact());
+        let list = PyList::empty(py);
+        for version in versions {
+            list.append(version.into_py_dict(py)?)?;
+        }
+        Ok(list.into())
+    }
+
+    /// Compares versions `a` and `b` of this document, stored with `create_version`. Returns a
+    /// dictionary of root types which differ between the versions, keyed by their names, where
+    /// every root type is described by a dictionary with its `kind`:
+    ///
+    /// - "text" and "array" root types have their contents `before` (in version `a`) and `after`
+    ///   (in version `b`), as a string and a list respectively,
+    /// - "map" root types have entries which have been `added` and `removed`, and entries which
+    ///   have been `updated` with their `(before, after)` values.
+    ///
+    /// Nested shared types are compared by their JSON representation. Fails with a `KeyError`
+    /// if any of the versions doesn't exist.
+    pub fn diff_versions(&self, py: Python, a: &str, b: &str) -> PyResult<PyObject> {
+        let (a, b) = {
+            let mut txn = self.inner.transact();
+            (versions::find(&mut txn, a)?, versions::find(&mut txn, b)?)
+        };
+        let update = diff(&self.inner, &StateVector::default());
+        let roots = versions::root_kinds(&update)?;
+        let doc_a = rewind(&self.inner, &a.snapshot)?;
+        let doc_b = rewind(&self.inner, &b.snapshot)?;
+        versions::compare(py, roots, &doc_a, &doc_b)
+    }
+
+    /// Returns statistics of this document, which help to monitor its growth and decide when to
+    /// compact it:
+    ///
+    /// - `blocks`: number of blocks (chunks of content) stored by the document,
+    /// - `deleted_blocks`: number of blocks which have been deleted,
+    /// - `gc_blocks`: number of deleted blocks which content has been garbage collected,
+    /// - `delete_set_ranges`: number of ranges of deleted blocks,
+    /// - `update_size` and `update_v2_size`: sizes in bytes of the full document state, using
+    ///   lib0 v1 and v2 encodings respectively,
+    /// - `roots`: for each root type, a dictionary with its number of `blocks` and their `size`
+    ///   in bytes using lib0 v1 encoding.
+    pub fn stats(&self, py: Python) -> PyResult<PyObject> {
+        let update = diff(&self.inner, &StateVector::default());
+        let stats = DocStats::from_update(&update)?.into_py_dict(py)?;
+        let mut encoder = EncoderV2::new();
+        self.inner
+            .transact()
+            .encode_diff(&StateVector::default(), &mut encoder);
+        stats.set_item("update_size", update.len())?;
+        stats.set_item("update_v2_size", encoder.to_vec().len())?;
+        Ok(stats.into())
+    }
+
+    /// Returns an estimate of memory held by this document, in bytes, to help with capacity
+    /// planning and leak detection without a heap profiler:
+    ///
+    /// - `blocks`: blocks (chunks of content) which haven't been deleted,
+    /// - `deleted`: deleted blocks, including those which content has been garbage collected,
+    /// - `delete_set`: ranges of deleted blocks,
+    /// - `pending`: updates which can't be integrated yet, because they depend on updates which
+    ///   haven't been received,
+    /// - `total`: sum of all the above.
+    ///
+    /// Sizes of contents are approximated by their encoded sizes, and memory held by Python
+    /// objects, eg. shared type wrappers and observers, is not included.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// if doc.memory_usage()['deleted'] > 10 * 1024 * 1024:
+    ///     doc.compact()
+    /// ```
+    pub fn memory_usage(&self, py: Python) -> PyResult<PyObject> {
+        let update = diff(&self.inner, &StateVector::default());
+        let usage = MemoryUsage::from_update(&update)?;
+        let pending = self
+            .filters
+            .pending_size(&self.inner.transact().state_vector());
+        Ok(usage.into_py_dict(py, pending)?.into())
+    }
+
+    /// Compacts the internal store of this document by squashing adjacent deleted blocks, and
+    /// garbage collecting content of deleted blocks unless the document has been created with
+    /// `skip_gc=True`. Long-lived documents, eg. on a server, can be compacted by maintenance jobs
+    /// once `stats` reports many deleted blocks. Returns a number of bytes saved, as a difference
+    /// of sizes of the full document state using lib0 v1 encoding.
+    ///
+    /// Compaction is committed as a transaction which doesn't change the content of the document,
+    /// so shared types observers are not called. Observers registered with
+    /// `observe_after_transaction` are called with an update containing deletions only, which
+    /// are already known to other replicas.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// if doc.stats()['deleted_blocks'] > 10000:
+    ///     saved = doc.compact()
+    /// ```
+    pub fn compact(&self) -> PyResult<usize> {
+        let before = diff(&self.inner, &StateVector::default());
+        let mut reader = snapshot::Reader::new(&before);
+        snapshot::read_structs(&mut reader)?;
+        let delete_set = snapshot::read_delete_set(&mut reader)?;
+        let mut encoded = Vec::new();
+        snapshot::write_delete_set(&mut encoded, &delete_set);
+
+        // Deleted blocks are garbage collected and squashed once the transaction deleting them is
+        // committed, so all of them are committed again
+        let mut txn = self.inner.transact();
+        txn.delete_set =
+            Decode::decode_v1(&encoded).map_err(|e| EncodingError::new_err(e.to_string()))?;
+        txn.commit();
+        drop(txn);
+        let after = diff(&self.inner, &StateVector::default());
+        let removed = before.len().saturating_sub(after.len());
+        Python::with_gil(|py| {
+            logging::emit(py, Level::Info, "gc_run", |record| {
+                record.set_item("client_id", self.client_id())?;
+                record.set_item("removed", removed)
+            })
+        });
+        Ok(removed)
     }
 
     /// Subscribes a callback to a `YDoc` lifecycle event.
-    pub fn observe_after_transaction(&mut self, callback: PyObject) -> SubscriptionId {
-        self.0
+    ///
+    /// If a `dispatcher` is given, the callback is called on its thread with a
+    /// `TransactionRecord` instead of an `AfterTransactionEvent`.
+    ///
+    /// If `weak` is `True`, the subscription only holds a weak reference to the callback, so that
+    /// it doesn't keep the callback, or the receiver of a bound method, alive. The callback stops
+    /// being called once it has been garbage collected.
+    pub fn observe_after_transaction(
+        mut slf: PyRefMut<Self>,
+        py: Python,
+        callback: PyObject,
+        dispatcher: Option<PyRef<Dispatcher>>,
+        weak: Option<bool>,
+    ) -> PyResult<SubscriptionId> {
+        let callback = Callback::observer(py, callback, weak)?;
+        callback.set_owner(Some(slf.as_ptr() as usize));
+        let transforms = slf.transforms.clone();
+        let handle = dispatcher.map(|dispatcher| dispatcher.handle());
+        Ok(slf
+            .inner
             .observe_transaction_cleanup(move |txn, event| {
-                Python::with_gil(|py| {
-                    let event = AfterTransactionEvent::new(event, txn);
-                    if let Err(err) = callback.call1(py, (event,)) {
-                        err.restore(py)
+                shutdown::with_gil(|py| {
+                    let result = match &handle {
+                        Some(handle) => TransactionRecord::new(py, event, txn, &transforms)
+                            .and_then(|record| Py::new(py, record))
+                            .and_then(|record| {
+                                let args = PyTuple::new(py, [record]).into();
+                                handle.dispatch(py, callback.clone(), args)
+                            }),
+                        None => {
+                            let event = AfterTransactionEvent::new(event, txn, transforms.clone());
+                            metrics::measure(Metric::Callbacks, || callback.call1(py, (event,)))
+                                .map(|_| ())
+                        }
+                    };
+                    if let Err(err) = result {
+                        logging::callback_failed(py, err)
+                    }
+                });
+            })
+            .into())
+    }
+
+    /// Checks if this document holds updates which can't be integrated yet, because they depend
+    /// on updates which haven't been received. Providers can use it to detect missing updates,
+    /// and request them from their peers.
+    pub fn has_pending_updates(&self) -> bool {
+        has_pending(&self.filters, &self.inner.transact())
+    }
+
+    /// Returns a number of bytes of updates which can't be integrated yet, see
+    /// `has_pending_updates`, using lib0 v1 encoding.
+    pub fn pending_update_bytes(&self) -> usize {
+        self.filters
+            .pending_size(&self.inner.transact().state_vector())
+    }
+
+    /// Subscribes a `callback()` which is called after a transaction, eg. applying a missing
+    /// update, has integrated all pending updates of this document. If `weak` is `True`, only a
+    /// weak reference to the callback is held, like in `observe_after_transaction`.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc, apply_update, encode_state_vector
+    ///
+    /// doc = YDoc()
+    /// doc.observe_pending_integrated(lambda: print('caught up'))
+    /// apply_update(doc, update)
+    /// if doc.has_pending_updates():
+    ///     request_missing_updates(encode_state_vector(doc))
+    /// ```
+    pub fn observe_pending_integrated(
+        mut slf: PyRefMut<Self>,
+        py: Python,
+        callback: PyObject,
+        weak: Option<bool>,
+    ) -> PyResult<SubscriptionId> {
+        let callback = Callback::observer(py, callback, weak)?;
+        callback.set_owner(Some(slf.as_ptr() as usize));
+        let filters = slf.filters.clone();
+        let pending = Cell::new(has_pending(&filters, &slf.inner.transact()));
+        Ok(slf
+            .inner
+            .observe_transaction_cleanup(move |txn, _| {
+                let has_pending = has_pending(&filters, txn);
+                if !pending.replace(has_pending) || has_pending {
+                    return;
+                }
+                shutdown::with_gil(|py| {
+                    if let Err(err) = metrics::measure(Metric::Callbacks, || callback.call1(py, ()))
+                    {
+                        logging::callback_failed(py, err)
+                    }
+                });
+            })
+            .into())
+    }
+
+    /// Registers a `callback(paths)` which is called before a remote update is applied to this
+    /// document with `apply_update`, `YTransaction.apply_v1` or `sync_with`, and which can reject
+    /// the update by returning a falsy value. Rejected updates are not applied at all, and raise
+    /// an `UpdateRejectedError`. Local changes are not filtered.
+    ///
+    /// `paths` is a list of locations changed by the update, where each location is a tuple of
+    /// a root type name followed by keys of map entries leading to the changed shared type or map
+    /// entry. Elements of arrays, texts and XML nodes are represented by `None`, so inserting text
+    /// into a root `YText` changes `('name',)`, while setting a title of a `YMap` nested in a root
+    /// `YArray` changes `('todos', None, 'title')`. Changes which cannot be located yet, because
+    /// they depend on updates which haven't been received, are reported with an empty tuple.
+    ///
+    /// Filtering requires decoding the full state of this document, which makes it linear to the
+    /// document size. Returns an id which can be used to remove the filter with
+    /// `remove_update_filter`.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc, apply_update
+    ///
+    /// def protect_settings(paths):
+    ///     return all(path[:1] != ('settings',) for path in paths)
+    ///
+    /// doc = YDoc()
+    /// doc.add_update_filter(protect_settings)
+    /// apply_update(doc, update)  # raises UpdateRejectedError if the update changes settings
+    /// ```
+    pub fn add_update_filter(&self, callback: PyObject) -> u32 {
+        self.filters.add(callback)
+    }
+
+    /// Removes a filter registered with `add_update_filter`.
+    pub fn remove_update_filter(&self, filter_id: u32) {
+        self.filters.remove(filter_id)
+    }
+
+    /// Limits remote updates applied to this document, so that servers applying updates from
+    /// untrusted clients can't be overwhelmed by pathological payloads. Updates exceeding any of
+    /// the limits are not applied at all, and raise a `LimitExceededError`. Limits which are not
+    /// provided keep their current setting, and zero (the default) means no limit.
+    ///
+    /// - `max_update_size`: maximum size of an update in bytes, after transforms registered with
+    ///   `add_update_transform` have decoded it.
+    /// - `max_string_length`: maximum length of a single string inserted by an update, counted in
+    ///   UTF-16 code units like in Yjs.
+    /// - `max_depth`: maximum nesting depth of lists and maps of a single value inserted by an
+    ///   update.
+    ///
+    /// Local changes are not limited.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc, LimitExceededError, apply_update
+    ///
+    /// doc = YDoc()
+    /// doc.set_limits(max_update_size=1024 * 1024, max_string_length=65536, max_depth=32)
+    /// try:
+    ///     apply_update(doc, payload)
+    /// except LimitExceededError:
+    ///     connection.close()
+    /// ```
+    pub fn set_limits(
+        &self,
+        max_update_size: Option<usize>,
+        max_string_length: Option<u64>,
+        max_depth: Option<usize>,
+    ) {
+        let mut limits = self.filters.limits();
+        if let Some(max_update_size) = max_update_size {
+            limits.max_update_size = max_update_size;
+        }
+        if let Some(max_string_length) = max_string_length {
+            limits.max_string_length = max_string_length;
+        }
+        if let Some(max_depth) = max_depth {
+            limits.max_depth = max_depth;
+        }
+        self.filters.set_limits(limits);
+    }
+
+    /// Starts collecting statistics of remote updates applied to this document per client id,
+    /// returned by `client_stats`, eg. to enforce quotas or detect abusive clients of a hosted
+    /// service. Statistics are not collected by default, as collecting them requires decoding
+    /// every applied update once more.
+    pub fn enable_client_stats(&self) {
+        self.filters.enable_client_stats()
+    }
+
+    /// Stops collecting statistics of applied updates, and drops the ones collected so far.
+    pub fn disable_client_stats(&self) {
+        self.filters.disable_client_stats()
+    }
+
+    /// Returns statistics of remote updates applied to this document since `enable_client_stats`
+    /// has been called, as a dictionary keyed by client ids. Every entry is a dictionary with a
+    /// number of `updates` containing changes of the client, a number of `operations`, ie.
+    /// individual insertions, made by the client, and a number of `bytes` taken by them. Changes
+    /// of a client may be relayed by other peers, so they're attributed to their author rather
+    /// than to a connection they've been received from.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// doc.enable_client_stats()
+    /// ...
+    /// for client_id, stats in doc.client_stats().items():
+    ///     if stats['bytes'] > quota:
+    ///         block(client_id)
+    /// ```
+    pub fn client_stats(&self, py: Python) -> PyResult<PyObject> {
+        let result = PyDict::new(py);
+        for (client_id, stats) in self.filters.client_stats().unwrap_or_default() {
+            let entry = PyDict::new(py);
+            entry.set_item("updates", stats.updates)?;
+            entry.set_item("operations", stats.operations)?;
+            entry.set_item("bytes", stats.bytes)?;
+            result.set_item(client_id, entry)?;
+        }
+        Ok(result.into())
+    }
+
+    /// Returns limits set with `set_limits` as a dictionary with `max_update_size`,
+    /// `max_string_length` and `max_depth` entries.
+    pub fn get_limits(&self, py: Python) -> PyResult<PyObject> {
+        let limits = self.filters.limits();
+        let result = PyDict::new(py);
+        result.set_item("max_update_size", limits.max_update_size)?;
+        result.set_item("max_string_length", limits.max_string_length)?;
+        result.set_item("max_depth", limits.max_depth)?;
+        Ok(result.into())
+    }
+
+    /// Registers a transform of updates of this document, eg. compression or encryption. The
+    /// `encode(update)` callback is applied to every update encoded by `encode_state_as_update`,
+    /// `YTransaction.diff_v1` and `AfterTransactionEvent.get_update`, while `decode(payload)`
+    /// reverses it before a payload is applied with `apply_update` or `YTransaction.apply_v1`.
+    /// Both of them receive and return `bytes`. When many transforms are registered, updates are
+    /// encoded in order of their registration, and decoded in reverse order.
+    ///
+    /// Returns an id which can be used to remove the transform with `remove_update_transform`.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// import zlib
+    /// from y_py import YDoc, encode_state_as_update
+    ///
+    /// doc = YDoc()
+    /// doc.add_update_transform(zlib.compress, zlib.decompress)
+    /// compressed = encode_state_as_update(doc)
+    /// ```
+    pub fn add_update_transform(&self, encode: PyObject, decode: PyObject) -> u32 {
+        self.transforms.add(encode, decode)
+    }
+
+    /// Removes a transform registered with `add_update_transform`.
+    pub fn remove_update_transform(&self, transform_id: u32) {
+        self.transforms.remove(transform_id)
+    }
+
+    /// Attaches a JSON Schema, given as a dictionary, to a root type of a given `name`. Values
+    /// inserted into the root type and into `YArray` and `YMap` types nested in it are validated
+    /// against the part of the schema describing their location, and a `ValidationError` is raised
+    /// before the shared type is changed if they don't match. Removals of required map entries
+    /// and of array elements below `minItems` are rejected as well. Passing `None` as a `schema`
+    /// detaches the current schema.
+    ///
+    /// Schemas are compiled once, when they're attached, and fail with a `ValueError` if they
+    /// are malformed. Keywords describing plain JSON values are supported: `type`, `enum`,
+    /// `const`, `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`, `minLength`,
+    /// `maxLength`, `items`, `minItems`, `maxItems`, `properties`, `required` and
+    /// `additionalProperties`. Shared types are validated as their content, eg. a `YText` as a
+    /// string, but edits of texts and XML types aren't checked. Remote updates aren't checked
+    /// either, use `add_update_filter` to reject them.
+    ///
+    /// If the root type already exists, its current content must match the schema.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc, ValidationError
+    ///
+    /// doc = YDoc()
+    /// doc.set_schema('tasks', {
+    ///     'type': 'array',
+    ///     'items': {
+    ///         'type': 'object',
+    ///         'properties': {'title': {'type': 'string'}, 'done': {'type': 'boolean'}},
+    ///         'required': ['title'],
+    ///     },
+    /// })
+    /// tasks = doc.get_array('tasks')
+    /// with doc.begin_transaction() as txn:
+    ///     tasks.append(txn, {'title': 'Write docs', 'done': False})
+    ///     tasks.append(txn, {'done': True})  # raises ValidationError
+    /// ```
+    pub fn set_schema(&self, name: &str, schema: Option<&PyAny>) -> PyResult<()> {
+        let schema = schema.map(Schema::from_py).transpose()?;
+        if let Some(schema) = &schema {
+            json_schema::check_root(&self.inner, name, schema)?;
+        }
+        self.schemas.set(name, schema);
+        Ok(())
+    }
+
+    /// Starts recording which changes are made by transactions of each origin, eg. a connection
+    /// of a user passed as an origin of the updates it receives, so that they can be reverted
+    /// with `revert_origin`. Transactions without an origin are not recorded.
+    pub fn record_origins(mut slf: PyRefMut<Self>) {
+        let log = slf.origin_log.clone();
+        if log.is_recording() {
+            return;
+        }
+        log.start();
+        let _: SubscriptionId = slf
+            .inner
+            .observe_transaction_cleanup(move |_, event| {
+                let origin = match origin::committing() {
+                    Some(origin) => origin,
+                    None => return,
+                };
+                let change = Change::from_transaction(
+                    &event.before_state,
+                    &event.after_state,
+                    &event.delete_set.encode_v1(),
+                );
+                if let Ok(change) = change {
+                    if !change.is_empty() {
+                        log.push(origin, change);
                     }
-                })
+                }
+            })
+            .into();
+    }
+
+    /// Reverts changes made by a given `update` using lib0 v1 encoding, which has been applied
+    /// to this document, where possible: content inserted by the update is deleted, and content
+    /// deleted by it is inserted again as a copy. Map entries overwritten by the update are set
+    /// back to their previous values. Changes made on top of the update are preserved. An update
+    /// should only be reverted once, as deleted content is restored again otherwise.
+    ///
+    /// Deleted content can only be restored by documents created with `skip_gc=True`, and
+    /// neither nested shared types nor formatting attributes are restored. Root types which
+    /// haven't been obtained with `get_*` methods are left as they are. Returns a dictionary
+    /// with numbers of `removed`, `restored` and `skipped` units of content, eg. characters of a
+    /// text or elements of an array.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc, apply_update
+    ///
+    /// doc = YDoc(skip_gc=True)
+    /// apply_update(doc, spam)
+    /// doc.revert_update(spam)
+    /// ```
+    pub fn revert_update(&self, py: Python, update: BinaryInput) -> PyResult<PyObject> {
+        self.revert(py, Change::from_update(&update)?)
+    }
+
+    /// Reverts changes made by all transactions of a given `origin`, compared by equality, since
+    /// `record_origins` has been called, eg. to remove everything a given user has contributed
+    /// to this document. Reverted changes are dropped from the record. See `revert_update` for
+    /// the limitations and the returned value.
+    ///
+    /// Fails with a `RuntimeError` if origins are not recorded.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc(skip_gc=True)
+    /// doc.record_origins()
+    /// with doc.begin_transaction() as txn, txn.with_origin('mallory'):
+    ///     doc.get_text('text').extend(txn, 'spam')
+    /// doc.revert_origin('mallory')
+    /// assert str(doc.get_text('text')) == ''
+    /// ```
+    pub fn revert_origin(&self, py: Python, origin: &PyAny) -> PyResult<PyObject> {
+        if !self.origin_log.is_recording() {
+            return Err(PyRuntimeError::new_err(
+                "Origins are not recorded, call record_origins first",
+            ));
+        }
+        self.revert(py, self.origin_log.take(py, origin)?)
+    }
+
+    /// Starts saving this document periodically, once per `interval` in seconds if it has
+    /// changed. A `target` is either a path of a file, which is replaced atomically with the
+    /// whole state of the document using lib0 v1 encoding, or a `callback(state)` receiving the
+    /// state as `bytes`.
+    ///
+    /// States are encoded on a background thread, which keeps its own replica of this document
+    /// and only acquires the GIL to call a callback, so that persistence doesn't stall the main
+    /// loop of an application. Errors of saves are printed and logged as `autosave_error`
+    /// records. Returns an `Autosave`, which saves pending changes when it's stopped.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// with doc.start_autosave(5.0, 'notes.ydoc'):
+    ///     run_editor(doc)
+    /// ```
+    pub fn start_autosave(
+        mut slf: PyRefMut<Self>,
+        py: Python,
+        interval: f64,
+        target: &PyAny,
+    ) -> PyResult<Autosave> {
+        slf.lifecycle.check()?;
+        if !interval.is_finite() || interval <= 0.0 {
+            return Err(PyValueError::new_err(
+                "interval must be a positive number of seconds",
+            ));
+        }
+        let target = if target.is_callable() {
+            let callback = Callback::new(py, target.into());
+            callback.set_owner(Some(slf.as_ptr() as usize));
+            Target::Callback(callback)
+        } else {
+            Target::Path(target.extract()?)
+        };
+        let state = diff(&slf.inner, &StateVector::default());
+        let autosave = Autosave::start(state, target, Duration::from_secs_f64(interval));
+        let send = autosave.updates();
+        let _: SubscriptionId = slf
+            .inner
+            .observe_transaction_cleanup(move |txn, event| {
+                if event.before_state != event.after_state || !event.delete_set.is_empty() {
+                    send(txn.encode_update_v1());
+                }
             })
-            .into()
+            .into();
+        Ok(autosave)
+    }
+
+    /// Registers a `callback()` which is called once this document is destroyed with `destroy`.
+    /// Returns an id which can be used to remove the callback with `remove_on_destroy`.
+    pub fn on_destroy(&self, callback: PyObject) -> u32 {
+        self.lifecycle.add(callback)
+    }
+
+    /// Removes a callback registered with `on_destroy`.
+    pub fn remove_on_destroy(&self, callback_id: u32) {
+        self.lifecycle.remove(callback_id)
+    }
+
+    /// Checks if this document has been destroyed with `destroy`.
+    #[getter]
+    pub fn destroyed(&self) -> bool {
+        self.lifecycle.is_destroyed()
+    }
+
+    /// Destroys this document, so that long-running servers can deterministically release
+    /// documents they don't need anymore, eg. once the last client leaves a room. Callbacks of
+    /// all subscriptions to this document and its shared types are dropped, which releases the
+    /// objects they reference and breaks reference cycles between the callbacks and the document,
+    /// and `on_destroy` callbacks are called. Destroying a document twice does nothing.
+    ///
+    /// A destroyed document can't be changed anymore: starting a transaction, or obtaining a
+    /// root type, raises a `RuntimeError`, and so do iterators, views and helpers of its shared
+    /// types, like `Annotations` and `Cursor`. Transactions started beforehand should be committed
+    /// before the document is destroyed.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// doc.observe_after_transaction(lambda e: room.broadcast(e.get_update()))
+    /// doc.on_destroy(lambda: rooms.pop(room.name))
+    /// ...
+    /// doc.destroy()
+    /// ```
+    pub fn destroy(slf: &PyCell<Self>) -> PyResult<()> {
+        let lifecycle = slf.borrow().lifecycle.clone();
+        if lifecycle.is_destroyed() {
+            return Ok(());
+        }
+        shutdown::release_owned(slf.py(), slf.as_ptr() as usize);
+        lifecycle.destroy(slf.py())
+    }
+}
+
+impl YDoc {
+    /// Reverts a change of this document, within a transaction of an active batch if there's one.
+    fn revert(&self, py: Python, change: Change) -> PyResult<PyObject> {
+        let txn = self.shared_transaction(py)?;
+        let mut txn = txn.borrow_mut(py);
+        let result = revert::revert(py, &self.inner, &mut txn, change)?;
+        txn.commit();
+        Ok(result)
+    }
+
+    /// Returns a new transaction for this document, or the transaction of an active batch.
+    fn shared_transaction(&self, py: Python) -> PyResult<Py<YTransaction>> {
+        self.lifecycle.check()?;
+        match self.batch.transaction(py) {
+            Some(txn) => Ok(txn),
+            None => Py::new(py, self.begin_transaction()),
+        }
+    }
+
+    /// Returns a new transaction for this document, which isn't shared by an active batch.
+    pub fn begin_transaction(&self) -> YTransaction {
+        let mut txn = YTransaction::new(self.inner.transact());
+        txn.filters = self.filters.clone();
+        txn.transforms = self.transforms.clone();
+        txn.client_id = self.inner.client_id;
+        txn.schemas = self.schemas.clone();
+        txn.doc = self.doc_ref.clone();
+        txn
     }
+
+    /// Returns a reference to a given document shared with its transactions, which locates shared
+    /// types obtained from them. The reference is bound to the Python object of the document when
+    /// it's first used from Python.
+    pub(crate) fn doc_ref(slf: &PyCell<Self>) -> PyResult<DocRef> {
+        let doc_ref = slf.borrow().doc_ref.clone();
+        doc_ref.bind(slf)?;
+        Ok(doc_ref)
+    }
+}
+
+/// Encodes all updates of a document missing from a given state vector using lib0 v1 encoding.
+pub(crate) fn diff(doc: &Doc, sv: &StateVector) -> Vec<u8> {
+    let mut encoder = EncoderV1::new();
+    doc.transact().encode_diff(sv, &mut encoder);
+    encoder.to_vec()
+}
+
+/// Checks if a document holds updates which are waiting for updates they depend on, given its
+/// update filters, which keep track of them.
+fn has_pending(filters: &UpdateFilters, txn: &Transaction) -> bool {
+    filters.pending_size(&txn.state_vector()) > 0
+}
+
+/// Rebuilds a state of a document at the time a given snapshot was taken, as a new document.
+fn rewind(doc: &Doc, snapshot: &[u8]) -> PyResult<Doc> {
+    let update = diff(doc, &StateVector::default());
+    let update = decode_update(&snapshot::rewind_update(&update, snapshot)?)?;
+    let doc = Doc::new();
+    doc.transact().apply_update(update);
+    Ok(doc)
+}
+
+fn decode_update(update: &[u8]) -> PyResult<Update> {
+    Update::decode_v1(update).map_err(|e| EncodingError::new_err(e.to_string()))
 }
 
 /// Encodes a state vector of a given Ypy document into its binary representation using lib0 v1
@@ -225,7 +1262,7 @@ pub fn encode_state_vector(doc: &mut YDoc) -> PyObject {
 /// apply_update(local_doc, remote_delta)
 /// ```
 #[pyfunction]
-pub fn encode_state_as_update(doc: &YDoc, vector: Option<Vec<u8>>) -> PyResult<PyObject> {
+pub fn encode_state_as_update(doc: &YDoc, vector: Option<BinaryInput>) -> PyResult<PyObject> {
     doc.begin_transaction().diff_v1(vector)
 }
 
@@ -248,30 +1285,105 @@ pub fn encode_state_as_update(doc: &YDoc, vector: Option<Vec<u8>>) -> PyResult<P
 /// apply_update(local_doc, remote_delta)
 /// ```
 #[pyfunction]
-pub fn apply_update(doc: &mut YDoc, diff: Vec<u8>) -> PyResult<()> {
-    doc.begin_transaction().apply_v1(diff)?;
+pub fn apply_update(py: Python, doc: &mut YDoc, diff: BinaryInput) -> PyResult<()> {
+    match doc.batch.transaction(py) {
+        Some(txn) => txn.borrow_mut(py).apply_v1(diff)?,
+        None => doc.begin_transaction().apply_v1(diff)?,
+    }
     Ok(())
 }
 
+/// Applies an update received from a remote peer with a given origin, passed to observers, within
+/// a transaction of an active batch if there's one.
+pub(crate) fn apply_update_with_origin(
+    py: Python,
+    doc: &YDoc,
+    update: &[u8],
+    origin: PyObject,
+) -> PyResult<()> {
+    doc.lifecycle.check()?;
+    match doc.batch.transaction(py) {
+        Some(txn) => {
+            let mut txn = txn.borrow_mut(py);
+            txn.origins.push(origin);
+            let result = txn.integrate_v1(update);
+            txn.origins.pop();
+            result
+        }
+        None => {
+            let mut txn = doc.begin_transaction();
+            txn.origins.push(origin);
+            txn.integrate_v1(update)?;
+            txn.commit();
+            Ok(())
+        }
+    }
+}
+
+/// Checks if an update using lib0 v1 encoding can be applied to a document, without applying it.
+/// Returns a list of descriptions of problems found in the update, which is empty if the update
+/// is valid. Malformed encoding is reported together with its byte offset.
+///
+/// Example:
+///
+/// ```python
+/// from y_py import apply_update, validate_update
+///
+/// problems = validate_update(payload)
+/// if problems:
+///     log.warning('Rejected update: %s', '; '.join(problems))
+/// else:
+///     apply_update(doc, payload)
+/// ```
+#[pyfunction]
+pub fn validate_update(py: Python, update: BinaryInput) -> Vec<String> {
+    snapshot::validate_update(py, &update)
+}
+
+impl TransactionRecord {
+    fn new(
+        py: Python,
+        event: &YrsAfterTransactionEvent,
+        txn: &Transaction,
+        transforms: &UpdateTransforms,
+    ) -> PyResult<Self> {
+        Ok(TransactionRecord {
+            before_state: binary_into_py(py, event.before_state.encode_v1()),
+            after_state: binary_into_py(py, event.after_state.encode_v1()),
+            delete_set: binary_into_py(py, event.delete_set.encode_v1()),
+            update: transforms.encode(py, txn.encode_update_v1())?,
+            origin: origin::into_py(py, &origin::committing()),
+        })
+    }
+}
+
 #[pyclass(unsendable)]
 pub struct AfterTransactionEvent {
     inner: *const YrsAfterTransactionEvent,
     txn: *const Transaction,
+    origin: Option<PyObject>,
     before_state: Option<PyObject>,
     after_state: Option<PyObject>,
     delete_set: Option<PyObject>,
+    transforms: UpdateTransforms,
 }
 
 impl AfterTransactionEvent {
-    fn new(event: &YrsAfterTransactionEvent, txn: &Transaction) -> Self {
+    fn new(
+        event: &YrsAfterTransactionEvent,
+        txn: &Transaction,
+        transforms: UpdateTransforms,
+    ) -> Self {
         let inner = event as *const YrsAfterTransactionEvent;
         let txn = txn as *const Transaction;
         AfterTransactionEvent {
             inner,
             txn,
+            origin: origin::committing(),
             before_state: None,
             after_state: None,
             delete_set: None,
+            transforms,
         }
     }
 
@@ -286,6 +1398,12 @@ impl AfterTransactionEvent {
 
 #[pymethods]
 impl AfterTransactionEvent {
+    /// Origin of the transaction which produced this event, see `YTransaction.with_origin`.
+    #[getter]
+    pub fn origin(&self, py: Python) -> PyObject {
+        origin::into_py(py, &self.origin)
+    }
+
     /// Returns a current shared type instance, that current event changes refer to.
     #[getter]
     pub fn before_state(&mut self) -> PyObject {
@@ -293,8 +1411,7 @@ impl AfterTransactionEvent {
             before_state.clone()
         } else {
             let before_state = self.inner().before_state.encode_v1();
-            let before_state: PyObject =
-                Python::with_gil(|py| PyBytes::new(py, &before_state).into());
+            let before_state: PyObject = Python::with_gil(|py| binary_into_py(py, before_state));
             self.before_state = Some(before_state.clone());
             before_state
         }
@@ -306,8 +1423,7 @@ impl AfterTransactionEvent {
             after_state.clone()
         } else {
             let after_state = self.inner().after_state.encode_v1();
-            let after_state: PyObject =
-                Python::with_gil(|py| PyBytes::new(py, &after_state).into());
+            let after_state: PyObject = Python::with_gil(|py| binary_into_py(py, after_state));
             self.after_state = Some(after_state.clone());
             after_state
         }
@@ -319,14 +1435,16 @@ impl AfterTransactionEvent {
             delete_set.clone()
         } else {
             let delete_set = self.inner().delete_set.encode_v1();
-            let delete_set: PyObject = Python::with_gil(|py| PyBytes::new(py, &delete_set).into());
+            let delete_set: PyObject = Python::with_gil(|py| binary_into_py(py, delete_set));
             self.delete_set = Some(delete_set.clone());
             delete_set
         }
     }
 
-    pub fn get_update(&self) -> PyObject {
+    /// Returns an update of the committed transaction, using lib0 v1 encoding and transforms
+    /// registered with `YDoc.add_update_transform`.
+    pub fn get_update(&self, py: Python) -> PyResult<PyObject> {
         let update = self.txn().encode_update_v1();
-        Python::with_gil(|py| PyBytes::new(py, &update).into())
+        self.transforms.encode(py, update)
     }
 }
@@ -1,20 +1,35 @@
-use pyo3::exceptions::{PyKeyError, PyTypeError};
+use lib0::any::Any;
+use pyo3::exceptions::{PyIndexError, PyKeyError, PyLookupError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyDict;
+use pyo3::pyclass::CompareOp;
+use pyo3::types::{PyBytes, PyDict};
 use std::collections::HashMap;
+use std::convert::TryFrom;
 
 use std::mem::ManuallyDrop;
 use std::ops::DerefMut;
 use yrs::types::map::{MapEvent, MapIter};
-use yrs::types::DeepObservable;
-use yrs::{Map, SubscriptionId, Transaction};
+use yrs::types::{Branch, BranchPtr, DeepObservable, Value};
+use yrs::{Array, Map, SubscriptionId, Transaction};
 
-use crate::json_builder::JsonBuilder;
+use crate::json_builder::{limited_json, limited_prelim_map, JsonBuilder, JsonWriter};
+use crate::location::{parent_into_py, Location, Observed};
+use crate::logging;
+use crate::metrics::{self, Metric};
+use crate::msgpack;
+use crate::origin;
+use crate::panics;
 use crate::shared_types::{
-    DeepSubscription, DefaultPyErr, PreliminaryObservationException, ShallowSubscription,
-    SharedType, SubId,
+    compare_branches, hash_branch, plain_value, sha256_hex, CompatiblePyType, DeepSubscription,
+    DefaultPyErr, IntegratedOperationException, PreliminaryObservationException,
+    ShallowSubscription, SharedType, SourceGuard, SubId,
 };
-use crate::type_conversions::{events_into_py, PyObjectWrapper, ToPython};
+use crate::shutdown::{self, Callback};
+use crate::type_conversions::{
+    dict_key, events_into_py, plain_any, take_integration_error, BinaryInput, PreparedValue,
+    ToPython,
+};
+use crate::y_array::YArray;
 use crate::y_transaction::YTransaction;
 
 /// Collection used to store key-value entries in an unordered manner. Keys are always represented
@@ -26,11 +41,14 @@ use crate::y_transaction::YTransaction;
 /// by different peers are resolved into a single value using document id seniority to establish
 /// order.
 #[pyclass(unsendable)]
-pub struct YMap(pub SharedType<Map, HashMap<String, PyObject>>);
+pub struct YMap(
+    pub SharedType<Map, HashMap<String, PyObject>>,
+    pub(crate) Location,
+);
 
 impl From<Map> for YMap {
     fn from(v: Map) -> Self {
-        YMap(SharedType::new(v))
+        YMap::located(v, Location::default())
     }
 }
 
@@ -46,11 +64,11 @@ impl YMap {
     pub fn new(dict: &PyDict) -> PyResult<Self> {
         let mut map: HashMap<String, PyObject> = HashMap::new();
         for (k, v) in dict.iter() {
-            let k = k.downcast::<pyo3::types::PyString>()?.to_string();
+            let k = dict_key(k)?;
             let v: PyObject = v.into();
             map.insert(k, v);
         }
-        Ok(YMap(SharedType::Prelim(map)))
+        Ok(YMap(SharedType::Prelim(map), Location::default()))
     }
 
     /// Returns true if this is a preliminary instance of `YMap`.
@@ -66,6 +84,63 @@ impl YMap {
         }
     }
 
+    /// Returns true if this instance of `YMap` has been integrated into a `YDoc`. This is an
+    /// opposite of `prelim`.
+    #[getter]
+    pub fn integrated(&self) -> bool {
+        !self.prelim()
+    }
+
+    /// Returns the `YDoc` this `YMap` instance belongs to, or `None` if the document is unknown.
+    /// Documents are known for shared types reached from their root types, which have been
+    /// obtained from a `YDoc` or its transactions. Raises a `PreliminaryAccessError` for
+    /// preliminary instances.
+    #[getter]
+    pub fn doc(&self) -> PyResult<PyObject> {
+        match self.0.branch() {
+            Some(_) => Ok(Python::with_gil(|py| self.1.doc(py))),
+            None => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Starts a new transaction of the document this `YMap` instance belongs to, which makes it
+    /// possible to modify it without a reference to its `YDoc`. The document must be known, as
+    /// described by the `doc` property. Raises a `PreliminaryAccessError` for preliminary
+    /// instances.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// with map.transaction() as txn:
+    ///     map.set(txn, 'key', 'value')
+    /// ```
+    pub fn transaction(&self) -> PyResult<PyObject> {
+        match self.0.branch() {
+            Some(_) => Python::with_gil(|py| self.1.transaction(py)),
+            None => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Returns a shared type containing this `YMap` instance, or `None` if it's a root type or
+    /// its document is unknown.
+    /// Raises a `PreliminaryAccessError` for preliminary instances.
+    #[getter]
+    pub fn parent(&self) -> PyResult<PyObject> {
+        match self.0.branch() {
+            Some(branch) => Ok(Python::with_gil(|py| parent_into_py(py, branch, &self.1))),
+            None => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Checks if both objects refer to the same integrated `YMap`.
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyObject {
+        compare_branches(other.py(), self.0.branch(), other, op)
+    }
+
+    fn __hash__(&self) -> PyResult<isize> {
+        hash_branch(self.0.branch())
+    }
+
     /// Returns a number of entries stored within this instance of `YMap`.
     pub fn __len__(&self) -> usize {
         match &self.0 {
@@ -98,34 +173,225 @@ impl YMap {
         format!("YMap({})", self.__str__())
     }
 
+    /// Converts contents of this `YMap` instance into a Python dictionary. Nested shared types
+    /// are returned as `YText`, `YArray` and `YMap` instances, unless `deep` is set, in which case
+    /// they are recursively converted into `str`, `list` and `dict` values.
+    ///
+    /// If `keys` are provided, only entries stored under these keys are returned. Keys missing
+    /// from this `YMap` are skipped.
+    pub fn to_dict(&self, deep: Option<bool>, keys: Option<Vec<String>>) -> PyResult<PyObject> {
+        let deep = deep.unwrap_or(false);
+        Python::with_gil(|py| {
+            let dict = PyDict::new(py);
+            match &self.0 {
+                SharedType::Integrated(map) => self.1.within(map.as_ref(), || {
+                    let set_entry = |key: &str, value: Value| {
+                        if deep {
+                            dict.set_item(key, value.to_json().into_py(py))
+                        } else {
+                            dict.set_item(key, value.into_py(py))
+                        }
+                    };
+                    if let Some(keys) = keys {
+                        for key in keys {
+                            if let Some(value) = map.get(&key) {
+                                set_entry(&key, value)?;
+                            }
+                        }
+                    } else {
+                        for (key, value) in map.iter() {
+                            set_entry(key, value)?;
+                        }
+                    }
+                    PyResult::Ok(())
+                })?,
+                SharedType::Prelim(entries) => {
+                    let set_entry = |key: &str, value: &PyObject| {
+                        if deep {
+                            dict.set_item(key, plain_value(py, value)?)
+                        } else {
+                            dict.set_item(key, value)
+                        }
+                    };
+                    if let Some(keys) = keys {
+                        for key in keys {
+                            if let Some(value) = entries.get(&key) {
+                                set_entry(&key, value)?;
+                            }
+                        }
+                    } else {
+                        for (key, value) in entries.iter() {
+                            set_entry(key, value)?;
+                        }
+                    }
+                }
+            }
+            Ok(dict.into())
+        })
+    }
+
     /// Converts contents of this `YMap` instance into a JSON representation.
-    pub fn to_json(&self) -> PyResult<String> {
+    ///
+    /// When `depth` is given, nested shared types are resolved only up to `depth` levels below
+    /// this map, while deeper ones are replaced by placeholders of the form
+    /// `{"$type": "YArray", "length": 3}`. With a `depth` of 0, only the values of this map are
+    /// resolved.
+    pub fn to_json(
+        &self,
+        _txn: Option<PyRef<YTransaction>>,
+        depth: Option<usize>,
+    ) -> PyResult<String> {
         let mut json_builder = JsonBuilder::new();
-        match &self.0 {
-            SharedType::Integrated(dict) => json_builder.append_json(&dict.to_json())?,
-            SharedType::Prelim(dict) => json_builder.append_json(dict)?,
+        match (&self.0, depth) {
+            (SharedType::Integrated(dict), None) => json_builder.append_json(&dict.to_json())?,
+            (SharedType::Prelim(dict), None) => json_builder.append_json(dict)?,
+            (SharedType::Integrated(dict), Some(depth)) => {
+                json_builder.append_json(&limited_json(Value::YMap(dict.clone()), depth))?
+            }
+            (SharedType::Prelim(dict), Some(depth)) => {
+                let any = Python::with_gil(|py| limited_prelim_map(py, dict, depth))?;
+                json_builder.append_json(&any)?
+            }
         }
         Ok(json_builder.into())
     }
 
+    /// Serializes contents of this `YMap` instance into JSON, like `to_json` does, but writes it to a given
+    /// file object in chunks instead of returning it as a single string, which keeps memory usage
+    /// low when serializing large documents.
+    pub fn write_json(&self, _txn: &YTransaction, file: &PyAny) -> PyResult<()> {
+        let mut writer = JsonWriter::new(file)?;
+        match &self.0 {
+            SharedType::Integrated(dict) => writer.write_value(Value::YMap(dict.clone()))?,
+            SharedType::Prelim(_) => writer.write_str(&self.to_json(None, None)?)?,
+        }
+        writer.finish()
+    }
+
     /// Sets a given `key`-`value` entry within this instance of `YMap`. If another entry was
     /// already stored under given `key`, it will be overridden with new `value`.
-    pub fn set(&mut self, txn: &mut YTransaction, key: &str, value: PyObject) {
+    ///
+    /// Returns the value previously stored under given `key`, or `None` if there was no such
+    /// entry.
+    pub fn set(
+        &mut self,
+        txn: &mut YTransaction,
+        key: &str,
+        value: PyObject,
+    ) -> PyResult<PyObject> {
+        let previous = match &mut self.0 {
+            SharedType::Integrated(v) => {
+                if let Some(scope) = txn.schema_scope(v.as_ref(), &self.1) {
+                    let checked = Python::with_gil(|py| plain_any(value.as_ref(py)))?;
+                    scope.check_set(key, &checked)?;
+                }
+                let location = &self.1;
+                let previous = location.within(v.as_ref(), || {
+                    v.get(key).map(|v| Python::with_gil(|py| v.into_py(py)))
+                });
+                let value = Python::with_gil(|py| PreparedValue::new(value.as_ref(py)))?;
+                panics::guard(|| {
+                    location.within(v.as_ref(), || v.insert(txn, key.to_string(), value));
+                    take_integration_error()
+                })?;
+                previous
+            }
+            SharedType::Prelim(v) => v.insert(key.to_string(), value),
+        };
+        Ok(previous.unwrap_or_else(|| Python::with_gil(|py| py.None())))
+    }
+
+    /// Parses a `json` string and stores the result under a given `key`. JSON is parsed directly
+    /// into Yrs values without creating intermediate Python objects, which makes it much faster
+    /// than `json.loads` followed by `set` for large payloads.
+    pub fn insert_json(&mut self, txn: &mut YTransaction, key: &str, json: &str) -> PyResult<()> {
+        let value = Any::from_json(json)
+            .map_err(|err| PyValueError::new_err(format!("Invalid JSON: {err}")))?;
         match &mut self.0 {
             SharedType::Integrated(v) => {
-                v.insert(txn, key.to_string(), PyObjectWrapper(value));
+                if let Some(scope) = txn.schema_scope(v.as_ref(), &self.1) {
+                    scope.check_set(key, &value)?;
+                }
+                panics::guard(|| {
+                    v.insert(txn, key.to_string(), value);
+                    Ok(())
+                })?;
             }
             SharedType::Prelim(v) => {
+                let value = Python::with_gil(|py| value.into_py(py));
                 v.insert(key.to_string(), value);
             }
         }
+        Ok(())
+    }
+
+    /// Serializes contents of this `YMap` instance using the MessagePack format. Unlike document
+    /// updates, the result contains only the current values, without any collaborative editing
+    /// metadata.
+    pub fn to_msgpack(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| {
+            let contents = self.to_any(py)?;
+            Ok(PyBytes::new(py, &msgpack::encode(&contents)).into())
+        })
+    }
+
+    /// Returns a hex encoded SHA-256 digest of the contents of this `YMap` instance. Digests
+    /// depend only on the current entries, regardless of their insertion order or editing
+    /// history, so replicas can cheaply verify that they converged.
+    pub fn content_hash(&self) -> PyResult<String> {
+        Python::with_gil(|py| {
+            let contents = self.to_any(py)?;
+            sha256_hex(py, &msgpack::encode_canonical(&contents))
+        })
+    }
+
+    /// Decodes a MessagePack encoded map, such as one produced by `to_msgpack`, and stores all of
+    /// its entries in this `YMap` instance. Values are decoded directly into Yrs values without
+    /// creating intermediate Python objects.
+    pub fn update_from_msgpack(
+        &mut self,
+        txn: &mut YTransaction,
+        data: BinaryInput,
+    ) -> PyResult<()> {
+        let entries = match msgpack::decode(&data)? {
+            Any::Map(entries) => entries,
+            _ => {
+                return Err(PyValueError::new_err(
+                    "Invalid msgpack data: expected a map at the top level",
+                ))
+            }
+        };
+        match &mut self.0 {
+            SharedType::Integrated(v) => {
+                if let Some(scope) = txn.schema_scope(v.as_ref(), &self.1) {
+                    for (key, value) in entries.iter() {
+                        scope.check_set(key, value)?;
+                    }
+                }
+                panics::guard(|| {
+                    for (key, value) in entries.into_iter() {
+                        v.insert(txn, key, value);
+                    }
+                    Ok(())
+                })?;
+            }
+            SharedType::Prelim(v) => Python::with_gil(|py| {
+                for (key, value) in entries.into_iter() {
+                    v.insert(key, value.into_py(py));
+                }
+            }),
+        }
+        Ok(())
     }
+
     /// Updates `YMap` with the key value pairs in the `items` object.
     pub fn update(&mut self, txn: &mut YTransaction, items: PyObject) -> PyResult<()> {
         Python::with_gil(|py| {
-            // Handle collection types
-            if let Ok(dict) = items.extract::<HashMap<String, PyObject>>(py) {
-                dict.into_iter().for_each(|(k, v)| self.set(txn, &k, v));
+            // Handle dictionaries, preserving the order of their entries
+            if let Ok(dict) = items.as_ref(py).downcast::<PyDict>() {
+                for (k, v) in dict.iter() {
+                    self.set(txn, &dict_key(k)?, v.into())?;
+                }
                 return Ok(());
             }
             // Handle iterable of tuples
@@ -135,7 +401,7 @@ impl YMap {
                         match value {
                             Ok(kv_pair) => {
                                 if let Ok((key, value)) = kv_pair.extract::<(String, PyObject)>() {
-                                    self.set(txn, &key, value);
+                                    self.set(txn, &key, value)?;
                                 } else {
                                     return Err(PyTypeError::new_err(format!("Update items should be formatted as (str, value) tuples, found: {}", kv_pair)));
                                 }
@@ -158,9 +424,17 @@ impl YMap {
         fallback: Option<PyObject>,
     ) -> PyResult<PyObject> {
         let popped = match &mut self.0 {
-            SharedType::Integrated(v) => v
-                .remove(txn, key)
-                .map(|v| Python::with_gil(|py| v.into_py(py))),
+            SharedType::Integrated(v) => {
+                if v.contains(key) {
+                    if let Some(scope) = txn.schema_scope(v.as_ref(), &self.1) {
+                        scope.check_remove(key)?;
+                    }
+                }
+                let removed = panics::guard(|| Ok(v.remove(txn, key)))?;
+                self.1.within(v.as_ref(), || {
+                    removed.map(|v| Python::with_gil(|py| v.into_py(py)))
+                })
+            }
             SharedType::Prelim(v) => v.remove(key),
         };
         if let Some(value) = popped {
@@ -183,15 +457,142 @@ impl YMap {
     /// or `undefined` if no such entry existed.
     pub fn __getitem__(&self, key: &str) -> PyResult<PyObject> {
         let entry = match &self.0 {
-            SharedType::Integrated(y_map) => y_map
-                .get(key)
-                .map(|value| Python::with_gil(|py| value.into_py(py))),
+            SharedType::Integrated(y_map) => self.1.within(y_map.as_ref(), || {
+                y_map
+                    .get(key)
+                    .map(|value| Python::with_gil(|py| value.into_py(py)))
+            }),
             SharedType::Prelim(hash_map) => hash_map.get(key).map(|value| value.clone()),
         };
 
         entry.ok_or_else(|| PyKeyError::new_err(format!("{key}")))
     }
 
+    /// Returns a value found by following a `path` through nested maps and arrays, where strings
+    /// are keys of map entries and integers are indexes of array elements (negative indexes count
+    /// from the end). If there is no such value, the `fallback` is returned instead.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// root = doc.get_map('root')
+    /// with doc.begin_transaction() as txn:
+    ///     root.set_path(txn, ['users', 0, 'name'], 'Alice')
+    ///
+    /// assert root.get_path(['users', 0, 'name']) == 'Alice'
+    /// assert root.get_path(['users', 1, 'name']) is None
+    /// ```
+    pub fn get_path(
+        &self,
+        path: Vec<PathSegment>,
+        fallback: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        Python::with_gil(|py| {
+            let (first, rest) = path.split_first().ok_or_else(empty_path)?;
+            let found = match &self.0 {
+                SharedType::Integrated(map) => {
+                    let mut current = map.get(first.key()?);
+                    // Shared type containing the current value, and its location
+                    let mut parent = (BranchPtr::from(map.as_ref()), self.1.clone());
+                    for segment in rest {
+                        let nested = match &current {
+                            Some(Value::YMap(map)) => Some(BranchPtr::from(map.as_ref())),
+                            Some(Value::YArray(array)) => Some(BranchPtr::from(array.as_ref())),
+                            _ => None,
+                        };
+                        if let Some(nested) = nested {
+                            parent = (nested, parent.1.child(&parent.0));
+                        }
+                        current = current.and_then(|value| child(value, segment));
+                    }
+                    let (branch, location) = parent;
+                    current.map(|value| location.within(&branch, || value.into_py(py)))
+                }
+                SharedType::Prelim(entries) => {
+                    let mut current = entries.get(first.key()?).map(|value| value.clone_ref(py));
+                    for segment in rest {
+                        current = match current {
+                            Some(value) => prelim_child(py, value, segment)?,
+                            None => None,
+                        };
+                    }
+                    current
+                }
+            };
+            Ok(found.unwrap_or_else(|| fallback.unwrap_or_else(|| py.None())))
+        })
+    }
+
+    /// Stores a `value` at a location described by a `path`, as accepted by `get_path`. Missing
+    /// maps and arrays along the path are created if `create_parents` is set: a `YMap` when the
+    /// following segment is a key, and a `YArray` when it's an index, which can be equal to the
+    /// length of an array to append to it. An existing array element at the end of the path is
+    /// replaced.
+    ///
+    /// Returns the value previously stored at the `path`, or `None` if there was no such value.
+    pub fn set_path(
+        &mut self,
+        txn: &mut YTransaction,
+        path: Vec<PathSegment>,
+        value: PyObject,
+        create_parents: Option<bool>,
+    ) -> PyResult<PyObject> {
+        let create_parents = create_parents.unwrap_or(true);
+        let (last, parents) = path.split_last().ok_or_else(empty_path)?;
+        let map = match &self.0 {
+            SharedType::Integrated(map) => map.clone(),
+            SharedType::Prelim(_) if parents.is_empty() => {
+                return self.set(txn, last.key()?, value)
+            }
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        panics::guard(|| {
+            Python::with_gil(|py| {
+                let mut current = Container::Map(map);
+                let mut location = self.1.clone();
+                for (index, segment) in parents.iter().enumerate() {
+                    let next = &path[index + 1];
+                    let nested = location.child(current.branch());
+                    current = match current.get(segment)? {
+                        Some(Value::YMap(map)) => Container::Map(map),
+                        Some(Value::YArray(array)) => Container::Array(array),
+                        Some(_) => {
+                            return Err(PyTypeError::new_err(format!(
+                                "Value at {segment} is neither a YMap nor a YArray"
+                            )))
+                        }
+                        None if create_parents => {
+                            let parent = match next {
+                                PathSegment::Key(_) => {
+                                    Py::new(py, YMap::new(PyDict::new(py))?)?.into_py(py)
+                                }
+                                PathSegment::Index(_) => {
+                                    Py::new(py, YArray::new(None)?)?.into_py(py)
+                                }
+                            };
+                            current.set(txn, segment, parent, &location)?;
+                            match current.get(segment)? {
+                                Some(Value::YMap(map)) => Container::Map(map),
+                                Some(Value::YArray(array)) => Container::Array(array),
+                                _ => unreachable!("a shared type has just been inserted"),
+                            }
+                        }
+                        None => return Err(segment.missing()),
+                    };
+                    location = nested;
+                }
+                let previous = current.set(txn, last, value, &location)?;
+                let previous = location.within(current.branch(), || {
+                    previous.map_or_else(|| py.None(), |value| value.into_py(py))
+                });
+                Ok(previous)
+            })
+        })
+    }
+
     /// Returns an item view that can be used to traverse over all entries stored within this
     /// instance of `YMap`. Order of entry is not specified.
     ///
@@ -209,36 +610,47 @@ impl YMap {
     ///     for (key, value) in map.entries(txn)):
     ///         print(key, value)
     /// ```
-    pub fn items(&self) -> ItemView {
-        ItemView(&self.0)
+    pub fn items(slf: &PyCell<Self>) -> ItemView {
+        let (inner, guard) = Self::source(slf);
+        ItemView(inner, guard)
     }
 
-    pub fn keys(&self) -> KeyView {
-        let inner: *const _ = &self.0;
-        KeyView(inner)
+    pub fn keys(slf: &PyCell<Self>) -> KeyView {
+        let (inner, guard) = Self::source(slf);
+        KeyView(inner, guard)
     }
 
-    pub fn __iter__(&self) -> KeyIterator {
-        let inner: *const _ = &self.0;
-        KeyIterator(YMapIterator::from(inner))
+    pub fn __iter__(slf: &PyCell<Self>) -> KeyIterator {
+        let (inner, guard) = Self::source(slf);
+        KeyIterator(YMapIterator::new(inner, guard))
     }
 
-    pub fn values(&self) -> ValueView {
-        let inner: *const _ = &self.0;
-        ValueView(inner)
+    pub fn values(slf: &PyCell<Self>) -> ValueView {
+        let (inner, guard) = Self::source(slf);
+        ValueView(inner, guard)
     }
 
-    pub fn observe(&mut self, f: PyObject) -> PyResult<ShallowSubscription> {
+    pub fn observe(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        weak: Option<bool>,
+    ) -> PyResult<ShallowSubscription> {
+        let f = Callback::observer(py, f, weak)?;
         match &mut self.0 {
             SharedType::Integrated(v) => {
+                f.set_owner(self.1.doc_key(py));
+                let observed = Observed::new(v.as_ref(), &self.1);
                 let sub_id: SubscriptionId = v
                     .observe(move |txn, e| {
-                        Python::with_gil(|py| {
-                            let e = YMapEvent::new(e, txn);
-                            if let Err(err) = f.call1(py, (e,)) {
-                                err.restore(py)
+                        shutdown::with_gil(|py| {
+                            let e = YMapEvent::new(e, txn, observed.clone());
+                            if let Err(err) =
+                                metrics::measure(Metric::Callbacks, || f.call1(py, (e,)))
+                            {
+                                logging::callback_failed(py, err)
                             }
-                        })
+                        });
                     })
                     .into();
                 Ok(ShallowSubscription(sub_id))
@@ -247,17 +659,27 @@ impl YMap {
         }
     }
 
-    pub fn observe_deep(&mut self, f: PyObject) -> PyResult<DeepSubscription> {
+    pub fn observe_deep(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        weak: Option<bool>,
+    ) -> PyResult<DeepSubscription> {
+        let f = Callback::observer(py, f, weak)?;
         match &mut self.0 {
             SharedType::Integrated(map) => {
+                f.set_owner(self.1.doc_key(py));
+                let observed = Observed::new(map.as_ref(), &self.1);
                 let sub: SubscriptionId = map
                     .observe_deep(move |txn, events| {
-                        Python::with_gil(|py| {
-                            let events = events_into_py(txn, events);
-                            if let Err(err) = f.call1(py, (events,)) {
-                                err.restore(py)
+                        shutdown::with_gil(|py| {
+                            let events = events_into_py(py, txn, events, &observed);
+                            if let Err(err) =
+                                metrics::measure(Metric::Callbacks, || f.call1(py, (events,)))
+                            {
+                                logging::callback_failed(py, err)
                             }
-                        })
+                        });
                     })
                     .into();
                 Ok(DeepSubscription(sub))
@@ -277,128 +699,357 @@ impl YMap {
     }
 }
 
+/// A segment of a path accepted by `YMap.get_path` and `YMap.set_path`: either a key of a map
+/// entry, or an index of an array element.
+pub enum PathSegment {
+    Key(String),
+    Index(i64),
+}
+
+impl<'a> FromPyObject<'a> for PathSegment {
+    fn extract(ob: &'a PyAny) -> PyResult<Self> {
+        if let Ok(key) = ob.extract::<String>() {
+            Ok(PathSegment::Key(key))
+        } else if let Ok(index) = ob.extract::<i64>() {
+            Ok(PathSegment::Index(index))
+        } else {
+            Err(PyTypeError::new_err(format!(
+                "Path segments should be strings or integers, found: {ob}"
+            )))
+        }
+    }
+}
+
+impl std::fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathSegment::Key(key) => write!(f, "{key:?}"),
+            PathSegment::Index(index) => write!(f, "{index}"),
+        }
+    }
+}
+
+impl PathSegment {
+    fn key(&self) -> PyResult<&str> {
+        match self {
+            PathSegment::Key(key) => Ok(key),
+            PathSegment::Index(_) => Err(PyTypeError::new_err(format!(
+                "YMap keys should be strings, found: {self}"
+            ))),
+        }
+    }
+
+    /// Returns an index of an element of a sequence of a given length, if it exists.
+    fn position(&self, len: u32) -> PyResult<Option<u32>> {
+        match self {
+            PathSegment::Index(index) => {
+                let index = if *index < 0 {
+                    *index + len as i64
+                } else {
+                    *index
+                };
+                Ok(if (0..len as i64).contains(&index) {
+                    Some(index as u32)
+                } else {
+                    None
+                })
+            }
+            PathSegment::Key(_) => Err(PyTypeError::new_err(format!(
+                "YArray indexes should be integers, found: {self}"
+            ))),
+        }
+    }
+
+    fn missing(&self) -> PyErr {
+        match self {
+            PathSegment::Key(key) => PyKeyError::new_err(key.clone()),
+            PathSegment::Index(_) => PyIndexError::default_message(),
+        }
+    }
+}
+
+fn empty_path() -> PyErr {
+    PyValueError::new_err("A path should contain at least one segment")
+}
+
+/// Returns a child of a given value found under a path `segment`, including entries of JSON-like
+/// maps and arrays which aren't shared types.
+fn child(value: Value, segment: &PathSegment) -> Option<Value> {
+    match (value, segment) {
+        (Value::YMap(map), PathSegment::Key(key)) => map.get(key),
+        (Value::YArray(array), PathSegment::Index(_)) => {
+            let index = segment.position(array.len()).ok()??;
+            array.get(index)
+        }
+        (Value::Any(Any::Map(entries)), PathSegment::Key(key)) => {
+            entries.get(key).cloned().map(Value::Any)
+        }
+        (Value::Any(Any::Array(items)), PathSegment::Index(_)) => {
+            let index = segment.position(items.len() as u32).ok()??;
+            Some(Value::Any(items[index as usize].clone()))
+        }
+        _ => None,
+    }
+}
+
+/// Returns a child of a Python object found under a path `segment`, which is used to traverse
+/// contents of preliminary types.
+fn prelim_child(py: Python, value: PyObject, segment: &PathSegment) -> PyResult<Option<PyObject>> {
+    let found = match segment {
+        PathSegment::Key(key) => value.as_ref(py).get_item(key),
+        PathSegment::Index(index) => value.as_ref(py).get_item(index),
+    };
+    match found {
+        Ok(found) => Ok(Some(found.into())),
+        Err(err)
+            if err.is_instance_of::<PyLookupError>(py) || err.is_instance_of::<PyTypeError>(py) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// A shared type which can be traversed by `YMap.set_path`.
+enum Container {
+    Map(Map),
+    Array(Array),
+}
+
+impl Container {
+    fn branch(&self) -> &Branch {
+        match self {
+            Container::Map(map) => map.as_ref(),
+            Container::Array(array) => array.as_ref(),
+        }
+    }
+
+    fn get(&self, segment: &PathSegment) -> PyResult<Option<Value>> {
+        match self {
+            Container::Map(map) => Ok(map.get(segment.key()?)),
+            Container::Array(array) => Ok(segment
+                .position(array.len())?
+                .and_then(|index| array.get(index))),
+        }
+    }
+
+    /// Stores a `value` under a path `segment` of this container located at `location`, and
+    /// returns the value which was replaced.
+    fn set(
+        &self,
+        txn: &mut YTransaction,
+        segment: &PathSegment,
+        value: PyObject,
+        location: &Location,
+    ) -> PyResult<Option<Value>> {
+        let prepared = Python::with_gil(|py| PreparedValue::new(value.as_ref(py)))?;
+        let previous = match self {
+            Container::Map(map) => {
+                let key = segment.key()?;
+                if let Some(scope) = txn.schema_scope(map.as_ref(), location) {
+                    let checked = Python::with_gil(|py| plain_any(value.as_ref(py)))?;
+                    scope.check_set(key, &checked)?;
+                }
+                let previous = map.get(key);
+                location.within(map.as_ref(), || map.insert(txn, key.to_string(), prepared));
+                previous
+            }
+            Container::Array(array) => {
+                let len = array.len();
+                match segment.position(len)? {
+                    Some(index) => {
+                        if let Some(scope) = txn.schema_scope(array.as_ref(), location) {
+                            let checked = Python::with_gil(|py| plain_any(value.as_ref(py)))?;
+                            scope.check_replace(index, &checked)?;
+                        }
+                        let previous = array.get(index);
+                        array.remove_range(txn, index, 1);
+                        location.within(array.as_ref(), || array.insert(txn, index, prepared));
+                        previous
+                    }
+                    None if matches!(segment, PathSegment::Index(index) if *index == len as i64) => {
+                        if let Some(scope) = txn.schema_scope(array.as_ref(), location) {
+                            let checked = Python::with_gil(|py| plain_any(value.as_ref(py)))?;
+                            scope.check_insert(len, len, &[checked])?;
+                        }
+                        location.within(array.as_ref(), || array.push_back(txn, prepared));
+                        None
+                    }
+                    None => return Err(segment.missing()),
+                }
+            }
+        };
+        take_integration_error()?;
+        Ok(previous)
+    }
+}
+
+type MapContents = SharedType<Map, HashMap<String, PyObject>>;
+
+impl YMap {
+    /// Wraps an integrated `map` located at a given `location`.
+    pub(crate) fn located(map: Map, location: Location) -> Self {
+        YMap(SharedType::new(map), location)
+    }
+
+    /// Returns a pointer to the contents of a given map, which is used by views and iterators,
+    /// together with a guard keeping it valid.
+    fn source(slf: &PyCell<Self>) -> (*const MapContents, SourceGuard) {
+        let map = slf.borrow();
+        let inner: *const _ = &map.0;
+        (inner, SourceGuard::new(slf, map.0.branch(), &map.1))
+    }
+
+    fn to_any(&self, py: Python) -> PyResult<Any> {
+        match &self.0 {
+            SharedType::Integrated(map) => Ok(map.to_json()),
+            SharedType::Prelim(entries) => {
+                let dict = PyDict::new(py);
+                for (key, value) in entries.iter() {
+                    dict.set_item(key, value)?;
+                }
+                Any::try_from(CompatiblePyType::Dict(dict))
+            }
+        }
+    }
+}
+
 #[pyclass(unsendable)]
-pub struct ItemView(*const SharedType<Map, HashMap<String, PyObject>>);
+pub struct ItemView(*const MapContents, SourceGuard);
 
 #[pymethods]
 impl ItemView {
     fn __iter__(slf: PyRef<Self>) -> YMapIterator {
-        YMapIterator::from(slf.0)
+        YMapIterator::new(slf.0, slf.1.clone())
     }
 
-    fn __len__(&self) -> usize {
+    fn __len__(&self, py: Python) -> PyResult<usize> {
+        self.1.check(py)?;
         unsafe {
-            match &*self.0 {
+            Ok(match &*self.0 {
                 SharedType::Integrated(map) => map.len() as usize,
                 SharedType::Prelim(map) => map.len(),
-            }
+            })
         }
     }
 
-    fn __str__(&self) -> String {
-        let vals: String = YMapIterator::from(self.0)
+    fn __str__(&self, py: Python) -> PyResult<String> {
+        self.1.check(py)?;
+        let vals: String = YMapIterator::new(self.0, self.1.clone())
             .map(|(key, val)| format!("({key}, {val})"))
             .collect::<Vec<String>>()
             .join(", ");
-        format!("{{{vals}}}")
+        Ok(format!("{{{vals}}}"))
     }
 
-    fn __repr__(&self) -> String {
-        let data = self.__str__();
-        format!("ItemView({data})")
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let data = self.__str__(py)?;
+        Ok(format!("ItemView({data})"))
     }
 
-    fn __contains__(&self, el: PyObject) -> bool {
-        let kv: Result<(String, PyObject), _> = Python::with_gil(|py| el.extract(py));
-        kv.ok()
-            .and_then(|(key, value)| unsafe {
-                match &*self.0 {
-                    SharedType::Integrated(map) if map.contains(&key) => map.get(&key).map(|v| {
-                        Python::with_gil(|py| v.into_py(py).as_ref(py).eq(value)).unwrap_or(false)
-                    }),
-                    SharedType::Prelim(map) if map.contains_key(&key) => map
-                        .get(&key)
-                        .map(|v| Python::with_gil(|py| v.as_ref(py).eq(value).unwrap_or(false))),
-                    _ => None,
-                }
-            })
-            .unwrap_or(false)
+    fn __contains__(&self, el: PyObject) -> PyResult<bool> {
+        Python::with_gil(|py| {
+            self.1.check(py)?;
+            let kv: Result<(String, PyObject), _> = el.extract(py);
+            Ok(kv
+                .ok()
+                .and_then(|(key, value)| unsafe {
+                    match &*self.0 {
+                        SharedType::Integrated(map) if map.contains(&key) => map
+                            .get(&key)
+                            .map(|v| v.into_py(py).as_ref(py).eq(value).unwrap_or(false)),
+                        SharedType::Prelim(map) if map.contains_key(&key) => map
+                            .get(&key)
+                            .map(|v| v.as_ref(py).eq(value).unwrap_or(false)),
+                        _ => None,
+                    }
+                })
+                .unwrap_or(false))
+        })
     }
 }
 
 #[pyclass(unsendable)]
-pub struct KeyView(*const SharedType<Map, HashMap<String, PyObject>>);
+pub struct KeyView(*const MapContents, SourceGuard);
 
 #[pymethods]
 impl KeyView {
     fn __iter__(slf: PyRef<Self>) -> KeyIterator {
-        KeyIterator(YMapIterator::from(slf.0))
+        KeyIterator(YMapIterator::new(slf.0, slf.1.clone()))
     }
 
-    fn __len__(&self) -> usize {
+    fn __len__(&self, py: Python) -> PyResult<usize> {
+        self.1.check(py)?;
         unsafe {
-            match &*self.0 {
+            Ok(match &*self.0 {
                 SharedType::Integrated(map) => map.len() as usize,
                 SharedType::Prelim(map) => map.len(),
-            }
+            })
         }
     }
 
-    fn __str__(&self) -> String {
-        let vals: String = YMapIterator::from(self.0)
+    fn __str__(&self, py: Python) -> PyResult<String> {
+        self.1.check(py)?;
+        let vals: String = YMapIterator::new(self.0, self.1.clone())
             .map(|(key, _)| key)
             .collect::<Vec<String>>()
             .join(", ");
-        format!("{{{vals}}}")
+        Ok(format!("{{{vals}}}"))
     }
 
-    fn __repr__(&self) -> String {
-        let data = self.__str__();
-        format!("KeyView({data})")
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let data = self.__str__(py)?;
+        Ok(format!("KeyView({data})"))
     }
 
-    fn __contains__(&self, el: PyObject) -> bool {
-        let key: Result<String, _> = Python::with_gil(|py| el.extract(py));
-        key.ok()
+    fn __contains__(&self, el: PyObject) -> PyResult<bool> {
+        let key: Result<String, _> = Python::with_gil(|py| {
+            self.1.check(py)?;
+            PyResult::Ok(el.extract(py))
+        })?;
+        Ok(key
+            .ok()
             .map(|key| unsafe {
                 match &*self.0 {
                     SharedType::Integrated(map) => map.contains(&key),
                     SharedType::Prelim(map) => map.contains_key(&key),
                 }
             })
-            .unwrap_or(false)
+            .unwrap_or(false))
     }
 }
 
 #[pyclass(unsendable)]
-pub struct ValueView(*const SharedType<Map, HashMap<String, PyObject>>);
+pub struct ValueView(*const MapContents, SourceGuard);
 
 #[pymethods]
 impl ValueView {
     fn __iter__(slf: PyRef<Self>) -> ValueIterator {
-        ValueIterator(YMapIterator::from(slf.0))
+        ValueIterator(YMapIterator::new(slf.0, slf.1.clone()))
     }
 
-    fn __len__(&self) -> usize {
+    fn __len__(&self, py: Python) -> PyResult<usize> {
+        self.1.check(py)?;
         unsafe {
-            match &*self.0 {
+            Ok(match &*self.0 {
                 SharedType::Integrated(map) => map.len() as usize,
                 SharedType::Prelim(map) => map.len(),
-            }
+            })
         }
     }
 
-    fn __str__(&self) -> String {
-        let vals: String = YMapIterator::from(self.0)
+    fn __str__(&self, py: Python) -> PyResult<String> {
+        self.1.check(py)?;
+        let vals: String = YMapIterator::new(self.0, self.1.clone())
             .map(|(_, v)| v.to_string())
             .collect::<Vec<String>>()
             .join(", ");
-        format!("{{{vals}}}")
+        Ok(format!("{{{vals}}}"))
     }
 
-    fn __repr__(&self) -> String {
-        let data = self.__str__();
-        format!("ValueView({data})")
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let data = self.__str__(py)?;
+        Ok(format!("ValueView({data})"))
     }
 }
 
@@ -408,7 +1059,7 @@ pub enum InnerYMapIterator {
 }
 
 #[pyclass(unsendable)]
-pub struct YMapIterator(ManuallyDrop<InnerYMapIterator>);
+pub struct YMapIterator(ManuallyDrop<InnerYMapIterator>, SourceGuard);
 
 impl Drop for YMapIterator {
     fn drop(&mut self) {
@@ -416,22 +1067,22 @@ impl Drop for YMapIterator {
     }
 }
 
-impl From<*const SharedType<Map, HashMap<String, PyObject>>> for YMapIterator {
-    fn from(inner_map_ptr: *const SharedType<Map, HashMap<String, PyObject>>) -> Self {
-        unsafe {
+impl YMapIterator {
+    /// Creates an iterator over map contents, which are kept alive by a given `guard`.
+    fn new(inner_map_ptr: *const MapContents, guard: SourceGuard) -> Self {
+        let shared_iter = unsafe {
             match &*inner_map_ptr {
                 SharedType::Integrated(val) => {
                     let this: *const Map = val;
-                    let shared_iter = InnerYMapIterator::Integrated((*this).iter());
-                    YMapIterator(ManuallyDrop::new(shared_iter))
+                    InnerYMapIterator::Integrated((*this).iter())
                 }
                 SharedType::Prelim(val) => {
                     let this: *const HashMap<String, PyObject> = val;
-                    let shared_iter = InnerYMapIterator::Prelim((*this).iter());
-                    YMapIterator(ManuallyDrop::new(shared_iter))
+                    InnerYMapIterator::Prelim((*this).iter())
                 }
             }
-        }
+        };
+        YMapIterator(ManuallyDrop::new(shared_iter), guard)
     }
 }
 
@@ -440,9 +1091,9 @@ impl Iterator for YMapIterator {
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.0.deref_mut() {
-            InnerYMapIterator::Integrated(iter) => {
+            InnerYMapIterator::Integrated(iter) => self.1.within(|| {
                 Python::with_gil(|py| iter.next().map(|(k, v)| (k.to_string(), v.into_py(py))))
-            }
+            }),
             InnerYMapIterator::Prelim(iter) => iter.next().map(|(k, v)| (k.clone(), v.clone())),
         }
     }
@@ -453,8 +1104,9 @@ impl YMapIterator {
     fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
         slf
     }
-    pub fn __next__(mut slf: PyRefMut<Self>) -> Option<(String, PyObject)> {
-        slf.next()
+    pub fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<(String, PyObject)>> {
+        slf.1.check(slf.py())?;
+        Ok(slf.next())
     }
 }
 
@@ -466,8 +1118,9 @@ impl KeyIterator {
     fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
         slf
     }
-    fn __next__(mut slf: PyRefMut<Self>) -> Option<String> {
-        slf.0.next().map(|(k, _)| k)
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<String>> {
+        slf.0 .1.check(slf.py())?;
+        Ok(slf.0.next().map(|(k, _)| k))
     }
 }
 
@@ -479,8 +1132,9 @@ impl ValueIterator {
     fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
         slf
     }
-    fn __next__(mut slf: PyRefMut<Self>) -> Option<PyObject> {
-        slf.0.next().map(|(_, v)| v)
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        slf.0 .1.check(slf.py())?;
+        Ok(slf.0.next().map(|(_, v)| v))
     }
 }
 
@@ -489,22 +1143,32 @@ impl ValueIterator {
 pub struct YMapEvent {
     inner: *const MapEvent,
     txn: *const Transaction,
+    origin: Option<PyObject>,
     target: Option<PyObject>,
     keys: Option<PyObject>,
+    observed: Observed,
 }
 
 impl YMapEvent {
-    pub fn new(event: &MapEvent, txn: &Transaction) -> Self {
+    pub(crate) fn new(event: &MapEvent, txn: &Transaction, observed: Observed) -> Self {
         let inner = event as *const MapEvent;
         let txn = txn as *const Transaction;
         YMapEvent {
             inner,
             txn,
+            origin: origin::committing(),
             target: None,
             keys: None,
+            observed,
         }
     }
 
+    /// Returns a location of the target of this event.
+    fn location(&self) -> Location {
+        let target = self.inner().target();
+        self.observed.locate(target.as_ref(), self.inner().path())
+    }
+
     fn inner(&self) -> &MapEvent {
         unsafe { self.inner.as_ref().unwrap() }
     }
@@ -516,14 +1180,20 @@ impl YMapEvent {
 
 #[pymethods]
 impl YMapEvent {
+    /// Origin of the transaction which produced this event, see `YTransaction.with_origin`.
+    #[getter]
+    pub fn origin(&self, py: Python) -> PyObject {
+        origin::into_py(py, &self.origin)
+    }
+
     /// Returns a current shared type instance, that current event changes refer to.
     #[getter]
     pub fn target(&mut self) -> PyObject {
         if let Some(target) = self.target.as_ref() {
             target.clone()
         } else {
-            let target: PyObject =
-                Python::with_gil(|py| YMap::from(self.inner().target().clone()).into_py(py));
+            let target = YMap::located(self.inner().target().clone(), self.location());
+            let target: PyObject = Python::with_gil(|py| target.into_py(py));
             self.target = Some(target.clone());
             target
         }
@@ -551,14 +1221,18 @@ impl YMapEvent {
         if let Some(keys) = &self.keys {
             keys.clone()
         } else {
-            let keys: PyObject = Python::with_gil(|py| {
-                let keys = self.inner().keys(self.txn());
-                let result = PyDict::new(py);
-                for (key, value) in keys.iter() {
-                    let key = &**key;
-                    result.set_item(key, value.into_py(py)).unwrap();
-                }
-                result.into()
+            let location = self.location();
+            let target = self.inner().target();
+            let keys: PyObject = location.within(target.as_ref(), || {
+                Python::with_gil(|py| {
+                    let keys = self.inner().keys(self.txn());
+                    let result = PyDict::new(py);
+                    for (key, value) in keys.iter() {
+                        let key = &**key;
+                        result.set_item(key, value.into_py(py)).unwrap();
+                    }
+                    result.into()
+                })
             });
 
             self.keys = Some(keys.clone());
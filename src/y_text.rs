@@ -1,19 +1,36 @@
+use crate::cursor::{clock_offset, element_ids, encode_token, text_page, PageStart};
+use crate::debounce::{self, Content, Debouncer};
+use crate::json_builder::{push_json_string, JsonWriter};
+use crate::location::{parent_into_py, Location, Observed};
+use crate::logging;
+use crate::markdown;
+use crate::metrics::{self, Metric};
+use crate::msgpack;
+use crate::origin;
+use crate::panics;
 use crate::shared_types::{
-    CompatiblePyType, DeepSubscription, DefaultPyErr, IntegratedOperationException,
-    PreliminaryObservationException, ShallowSubscription, SharedType, SubId,
+    compare_branches, hash_branch, sha256_hex, CompatiblePyType, DeepSubscription, DefaultPyErr,
+    IntegratedOperationException, PreliminaryObservationException, ShallowSubscription, SharedType,
+    SubId,
+};
+use crate::shutdown::{self, Callback};
+use crate::snapshot;
+use crate::type_conversions::{
+    events_into_py, take_integration_error, BinaryInput, PreparedValue, ToPython,
 };
-use crate::type_conversions::{events_into_py, ToPython};
 use crate::y_transaction::YTransaction;
 use lib0::any::Any;
+use pyo3::exceptions::{PyIndexError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::types::PyList;
-use std::collections::HashMap;
-use std::convert::TryInto;
+use pyo3::pyclass::CompareOp;
+use pyo3::types::{PyBytes, PyDict, PyList};
+use std::collections::{HashMap, VecDeque};
+use std::convert::{TryFrom, TryInto};
 use std::rc::Rc;
-use yrs::types::text::TextEvent;
-use yrs::types::Attrs;
+use yrs::types::text::{ChangeKind, Diff, TextEvent, YChange};
 use yrs::types::DeepObservable;
-use yrs::{Text, Transaction};
+use yrs::types::{Attrs, BranchPtr, Value};
+use yrs::{Array, DeleteSet, OffsetKind, Snapshot, StateVector, Text, Transaction, ID};
 
 /// A shared data type used for collaborative text editing. It enables multiple users to add and
 /// remove chunks of text in efficient manner. This type is internally represented as a mutable
@@ -30,10 +47,10 @@ use yrs::{Text, Transaction};
 /// unique document id to determine correct and consistent ordering.
 #[pyclass(unsendable)]
 #[derive(Clone)]
-pub struct YText(pub SharedType<Text, String>);
+pub struct YText(pub SharedType<Text, String>, pub(crate) Location);
 impl From<Text> for YText {
     fn from(v: Text) -> Self {
-        YText(SharedType::new(v))
+        YText::located(v, Location::default())
     }
 }
 
@@ -47,7 +64,10 @@ impl YText {
     /// document store and cannot be nested again: attempt to do so will result in an exception.
     #[new]
     pub fn new(init: Option<String>) -> Self {
-        YText(SharedType::prelim(init.unwrap_or_default()))
+        YText(
+            SharedType::prelim(init.unwrap_or_default()),
+            Location::default(),
+        )
     }
 
     /// Returns true if this is a preliminary instance of `YText`.
@@ -63,6 +83,63 @@ impl YText {
         }
     }
 
+    /// Returns true if this instance of `YText` has been integrated into a `YDoc`. This is an
+    /// opposite of `prelim`.
+    #[getter]
+    pub fn integrated(&self) -> bool {
+        !self.prelim()
+    }
+
+    /// Returns the `YDoc` this `YText` instance belongs to, or `None` if the document is unknown.
+    /// Documents are known for shared types reached from their root types, which have been
+    /// obtained from a `YDoc` or its transactions. Raises a `PreliminaryAccessError` for
+    /// preliminary instances.
+    #[getter]
+    pub fn doc(&self) -> PyResult<PyObject> {
+        match self.0.branch() {
+            Some(_) => Ok(Python::with_gil(|py| self.1.doc(py))),
+            None => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Starts a new transaction of the document this `YText` instance belongs to, which makes it
+    /// possible to modify it without a reference to its `YDoc`. The document must be known, as
+    /// described by the `doc` property. Raises a `PreliminaryAccessError` for preliminary
+    /// instances.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// with text.transaction() as txn:
+    ///     text.extend(txn, '!')
+    /// ```
+    pub fn transaction(&self) -> PyResult<PyObject> {
+        match self.0.branch() {
+            Some(_) => Python::with_gil(|py| self.1.transaction(py)),
+            None => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Returns a shared type containing this `YText` instance, or `None` if it's a root type or
+    /// its document is unknown.
+    /// Raises a `PreliminaryAccessError` for preliminary instances.
+    #[getter]
+    pub fn parent(&self) -> PyResult<PyObject> {
+        match self.0.branch() {
+            Some(branch) => Ok(Python::with_gil(|py| parent_into_py(py, branch, &self.1))),
+            None => Err(IntegratedOperationException::default_message()),
+        }
+    }
+
+    /// Checks if both objects refer to the same integrated `YText`.
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyObject {
+        compare_branches(other.py(), self.0.branch(), other, op)
+    }
+
+    fn __hash__(&self) -> PyResult<isize> {
+        hash_branch(self.0.branch())
+    }
+
     /// Returns an underlying shared string stored in this data type.
     pub fn __str__(&self) -> String {
         match &self.0 {
@@ -84,82 +161,438 @@ impl YText {
         }
     }
 
-    /// Returns an underlying shared string stored in this data type.
-    pub fn to_json(&self) -> String {
-        format!("\"{}\"", self.__str__())
+    /// Returns an underlying shared string stored in this data type. Texts contain no nested
+    /// shared types, so `depth` is accepted only for consistency with other shared types.
+    pub fn to_json(&self, _txn: Option<PyRef<YTransaction>>, _depth: Option<usize>) -> String {
+        let mut json = String::new();
+        push_json_string(&mut json, &self.__str__());
+        json
+    }
+
+    /// Writes the JSON representation of an underlying string to a given file object, in chunks
+    /// passed to its `write` method.
+    pub fn write_json(&self, _txn: &YTransaction, file: &PyAny) -> PyResult<()> {
+        let mut writer = JsonWriter::new(file)?;
+        match &self.0 {
+            SharedType::Integrated(text) => writer.write_value(Value::YText(text.clone()))?,
+            SharedType::Prelim(_) => writer.write_str(&self.to_json(None, None))?,
+        }
+        writer.finish()
+    }
+
+    /// Serializes an underlying string stored in this `YText` instance using the MessagePack
+    /// format.
+    pub fn to_msgpack(&self) -> PyObject {
+        let contents = msgpack::encode(&Any::String(self.__str__().into()));
+        Python::with_gil(|py| PyBytes::new(py, &contents).into())
+    }
+
+    /// Returns a hex encoded SHA-256 digest of the string stored in this `YText` instance.
+    /// Formatting attributes are not taken into account.
+    pub fn content_hash(&self) -> PyResult<String> {
+        let contents = msgpack::encode_canonical(&Any::String(self.__str__().into()));
+        Python::with_gil(|py| sha256_hex(py, &contents))
+    }
+
+    /// Returns contents of this `YText` instance as a list of runs `{"insert": str, "client": int}`,
+    /// where `client` is the id of the client which inserted a given run, enabling "who wrote
+    /// this" features. Embedded values are not included. The `client` is `None` for content whose
+    /// author can't be determined.
+    ///
+    /// If a `since` snapshot produced by `YDoc.snapshot` is given, every run also has a `"status"`
+    /// of either `"unchanged"`, `"added"` or `"removed"` compared to that version. Removed text is
+    /// only available in documents created with `skip_gc=True`.
+    pub fn attribution(
+        &self,
+        txn: &mut YTransaction,
+        since: Option<BinaryInput>,
+    ) -> PyResult<PyObject> {
+        let since = since
+            .map(|data| snapshot::decode_snapshot(&data))
+            .transpose()?;
+        Python::with_gil(|py| {
+            let text = match &self.0 {
+                SharedType::Integrated(text) => text,
+                SharedType::Prelim(text) => {
+                    let run = PyDict::new(py);
+                    run.set_item("insert", text)?;
+                    run.set_item("client", py.None())?;
+                    return Ok(PyList::new(py, [run]).into());
+                }
+            };
+            let chunks = text_chunks(text, txn);
+            // Content left out of diffs can only be attributed if it's unambiguous.
+            let excluded = excluded_clients(txn);
+            let unknown_client = match excluded.len() {
+                1 => excluded.keys().next().copied(),
+                _ => None,
+            };
+
+            let mut runs: Vec<(String, Option<u64>, Option<&str>)> = Vec::new();
+            let mut push_run =
+                |chunk: String, client: Option<u64>, status: Option<&'static str>| match runs
+                    .last_mut()
+                {
+                    Some(run) if run.1 == client && run.2 == status => run.0.push_str(&chunk),
+                    _ => runs.push((chunk, client, status)),
+                };
+            match &since {
+                None => {
+                    for chunk in chunks {
+                        if let TextChunk::String(id, chunk) = chunk {
+                            let client = id.map(|id| id.client).or(unknown_client);
+                            push_run(chunk, client, None);
+                        }
+                    }
+                }
+                Some(since) => {
+                    let current = diff_snapshot(&txn.snapshot(), &excluded);
+                    let since = diff_snapshot(since, &excluded);
+                    let unknown_status = match unknown_client {
+                        Some(client) if since.state_map.get(&client) >= excluded[&client] => {
+                            "unchanged"
+                        }
+                        _ => "added",
+                    };
+                    let mut chunks: VecDeque<TextChunk> = chunks.into();
+                    let diffs = text.diff_range(txn, Some(&current), Some(&since), |c| c);
+                    for diff in diffs {
+                        let chunk = match diff.insert {
+                            Value::Any(Any::String(chunk)) => chunk,
+                            _ => continue,
+                        };
+                        let status = match &diff.ychange {
+                            Some(YChange {
+                                kind: ChangeKind::Removed,
+                                id,
+                            }) => {
+                                push_run(chunk.to_string(), Some(id.client), Some("removed"));
+                                continue;
+                            }
+                            Some(_) => "added",
+                            None => "unchanged",
+                        };
+                        // Visible text is the same in both diffs, so authors of this part are
+                        // found by consuming as much text from the chunks of single items.
+                        let mut remaining = chunk.len();
+                        while remaining > 0 {
+                            match chunks.pop_front() {
+                                Some(TextChunk::String(Some(id), mut part)) => {
+                                    if part.len() > remaining {
+                                        let rest = part.split_off(remaining);
+                                        let clock = id.clock + part.encode_utf16().count() as u32;
+                                        let rest_id = ID::new(id.client, clock);
+                                        chunks.push_front(TextChunk::String(Some(rest_id), rest));
+                                    }
+                                    remaining -= part.len();
+                                    push_run(part, Some(id.client), Some(status));
+                                }
+                                Some(TextChunk::String(None, part)) => {
+                                    push_run(part, unknown_client, Some(unknown_status))
+                                }
+                                Some(TextChunk::Embed) => {}
+                                None => break,
+                            }
+                        }
+                    }
+                    for chunk in chunks {
+                        if let TextChunk::String(None, part) = chunk {
+                            push_run(part, unknown_client, Some(unknown_status));
+                        }
+                    }
+                }
+            }
+
+            let result = PyList::empty(py);
+            for (insert, client, status) in runs {
+                let run = PyDict::new(py);
+                run.set_item("insert", insert)?;
+                run.set_item("client", client)?;
+                if let Some(status) = status {
+                    run.set_item("status", status)?;
+                }
+                result.append(run)?;
+            }
+            Ok(result.into())
+        })
+    }
+
+    /// Compares contents of this `YText` instance with its version at the time a given `snapshot`
+    /// was taken with `YDoc.snapshot`, and returns a delta of the current contents merged with
+    /// removed contents, to render "track changes" views. Every operation is a dictionary with an
+    /// `"insert"`, which is a string or an embedded value, and optional `"attributes"` with its
+    /// formatting. Spans inserted or removed since the snapshot also have a `"ychange"` attribute,
+    /// which is a dictionary with a `"type"` of either `"added"` or `"removed"`, and the
+    /// `"client"` id of the author of the span.
+    ///
+    /// Removed content is only available in documents created with `skip_gc=True`. Formatting
+    /// attributes reflect the current state of the text, so removed content has none.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc(client_id=1, skip_gc=True)
+    /// text = doc.get_text('name')
+    /// with doc.begin_transaction() as txn:
+    ///     text.extend(txn, 'hello world')
+    /// version = doc.snapshot()
+    /// with doc.begin_transaction() as txn:
+    ///     text.delete_range(txn, 5, 6)
+    ///     text.extend(txn, '!')
+    ///     delta = text.diff_against_snapshot(txn, version)
+    /// assert delta == [
+    ///     {'insert': 'hello'},
+    ///     {'insert': ' world', 'attributes': {'ychange': {'type': 'removed', 'client': 1}}},
+    ///     {'insert': '!', 'attributes': {'ychange': {'type': 'added', 'client': 1}}},
+    /// ]
+    /// ```
+    pub fn diff_against_snapshot(
+        &self,
+        py: Python,
+        txn: &mut YTransaction,
+        snapshot: BinaryInput,
+    ) -> PyResult<PyObject> {
+        let text = match &self.0 {
+            SharedType::Integrated(text) => text,
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        let since = snapshot::decode_snapshot(&snapshot)?;
+        let path = self.1.path(py, text.as_ref()).ok_or_else(|| {
+            PyValueError::new_err("Contents of this text can't be located in its document")
+        })?;
+        let deletes = txn.snapshot().delete_set;
+        // Ids of all characters and embedded values, which are aligned with contents of items
+        // and current contents of the text. Content of clients left out of item diffs (see
+        // `excluded_clients`) is only known if it's visible.
+        let ids: Vec<ID> = element_ids(
+            &txn.encode_diff_v1(&StateVector::default()),
+            &path,
+            &deletes,
+        )?
+        .into_iter()
+        .flat_map(|(start, len)| {
+            (start.clock..start.clock + len).map(move |c| ID::new(start.client, c))
+        })
+        .collect();
+        let excluded = excluded_clients(txn);
+        let items = item_diffs(text, txn);
+        let visible = text.diff(txn, YChange::identity);
+        let mut item_units = diff_units(&items).into_iter();
+        let mut visible_units = diff_units(&visible).into_iter();
+
+        type Change = Option<(&'static str, u64)>;
+        let mut ops: Vec<(Content, Option<Attrs>, Change)> = Vec::new();
+        let mut p = 0;
+        while p < ids.len() {
+            let id = ids[p];
+            let is_visible = !deletes.is_deleted(&id);
+            let was_visible =
+                since.state_map.get(&id.client) > id.clock && !since.delete_set.is_deleted(&id);
+            let (insert, unit, attrs) = if !excluded.contains_key(&id.client) {
+                let (d, unit) = match item_units.next() {
+                    Some(unit) => unit,
+                    None => break,
+                };
+                // Removed content has no current formatting.
+                let attrs = match is_visible {
+                    true => visible_units
+                        .next()
+                        .and_then(|(v, _)| visible[v].attributes.as_deref()),
+                    false => None,
+                };
+                (&items[d].insert, unit, attrs)
+            } else if is_visible {
+                let (v, unit) = match visible_units.next() {
+                    Some(unit) => unit,
+                    None => break,
+                };
+                (&visible[v].insert, unit, visible[v].attributes.as_deref())
+            } else {
+                p += 1;
+                continue;
+            };
+            p += unit.map_or(1, char::len_utf16);
+            let change = match (was_visible, is_visible) {
+                (true, true) => None,
+                (false, true) => Some(("added", id.client)),
+                (true, false) => Some(("removed", id.client)),
+                (false, false) => continue,
+            };
+            match (ops.last_mut(), unit) {
+                (Some((Content::Text(text), a, c)), Some(unit))
+                    if a.as_ref() == attrs && *c == change =>
+                {
+                    text.push(unit)
+                }
+                (_, Some(unit)) => {
+                    ops.push((Content::Text(unit.to_string()), attrs.cloned(), change))
+                }
+                (_, None) => ops.push((
+                    Content::Embed(self.1.within(text.as_ref(), || insert.clone().into_py(py))),
+                    attrs.cloned(),
+                    change,
+                )),
+            }
+        }
+
+        let delta = PyList::empty(py);
+        for (content, attrs, change) in ops {
+            let op = PyDict::new(py);
+            match content {
+                Content::Text(text) => op.set_item("insert", text)?,
+                Content::Embed(value) => op.set_item("insert", value)?,
+                Content::Values(_) => unreachable!("texts have no values"),
+            }
+            let attributes = PyDict::new(py);
+            for (key, value) in attrs.into_iter().flatten() {
+                attributes.set_item(key.as_ref(), value.into_py(py))?;
+            }
+            if let Some((kind, client)) = change {
+                let ychange = PyDict::new(py);
+                ychange.set_item("type", kind)?;
+                ychange.set_item("client", client)?;
+                attributes.set_item("ychange", ychange)?;
+            }
+            if !attributes.is_empty() {
+                op.set_item("attributes", attributes)?;
+            }
+            delta.append(op)?;
+        }
+        Ok(delta.into())
     }
 
     /// Inserts a given `chunk` of text into this `YText` instance, starting at a given `index`.
+    /// An `IndexError` is raised if the index is beyond the end of the text, unless `clamp` is
+    /// set, in which case the chunk is appended instead.
     pub fn insert(
         &mut self,
         txn: &mut YTransaction,
         index: u32,
         chunk: &str,
         attributes: Option<HashMap<String, PyObject>>,
+        clamp: Option<bool>,
     ) -> PyResult<()> {
+        let (index, _) = self.check_range(index, 0, clamp.unwrap_or(false))?;
         let attributes: Option<PyResult<Attrs>> = attributes.map(Self::parse_attrs);
 
-        if let Some(Ok(attributes)) = attributes {
-            match &mut self.0 {
-                SharedType::Integrated(text) => {
-                    text.insert_with_attributes(txn, index, chunk, attributes);
-                    Ok(())
+        panics::guard(|| {
+            if let Some(Ok(attributes)) = attributes {
+                match &mut self.0 {
+                    SharedType::Integrated(text) => {
+                        text.insert_with_attributes(txn, index, chunk, attributes);
+                        Ok(())
+                    }
+                    SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
                 }
-                SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
-            }
-        } else if let Some(Err(error)) = attributes {
-            Err(error)
-        } else {
-            match &mut self.0 {
-                SharedType::Integrated(text) => text.insert(txn, index, chunk),
-                SharedType::Prelim(prelim_string) => {
-                    prelim_string.insert_str(index as usize, chunk)
+            } else if let Some(Err(error)) = attributes {
+                Err(error)
+            } else {
+                match &mut self.0 {
+                    SharedType::Integrated(text) => text.insert(txn, index, chunk),
+                    SharedType::Prelim(prelim_string) => {
+                        prelim_string.insert_str(index as usize, chunk)
+                    }
                 }
+                Ok(())
             }
-            Ok(())
-        }
+        })
     }
 
     /// Inserts a given `embed` object into this `YText` instance, starting at a given `index`.
+    /// Preliminary `YText`, `YArray` and `YMap` instances are integrated into the document as
+    /// children of this text, while other values are stored as JSON-like embeds.
     ///
     /// Optional object with defined `attributes` will be used to wrap provided `embed`
     /// with a formatting blocks.`attributes` are only supported for a `YText` instance which
-    /// already has been integrated into document store.
+    /// already has been integrated into document store. Indexes beyond the end of the text are
+    /// handled like in `insert`.
     pub fn insert_embed(
         &mut self,
         txn: &mut YTransaction,
         index: u32,
         embed: PyObject,
         attributes: Option<HashMap<String, PyObject>>,
+        clamp: Option<bool>,
     ) -> PyResult<()> {
-        match &mut self.0 {
-            SharedType::Integrated(text) => {
-                let content: PyResult<Any> = Python::with_gil(|py| {
-                    let py_type: CompatiblePyType = embed.extract(py)?;
-                    py_type.try_into()
-                });
-                if let Some(Ok(attrs)) = attributes.map(Self::parse_attrs) {
-                    text.insert_embed_with_attributes(txn, index, content?, attrs)
+        let (index, _) = self.check_range(index, 0, clamp.unwrap_or(false))?;
+        let attributes = attributes.map(Self::parse_attrs).transpose()?;
+        panics::guard(|| match &self.0 {
+            SharedType::Integrated(text) => self.1.within(text.as_ref(), || {
+                insert_embed_value(txn, text, index, embed, attributes)
+            }),
+            SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
+        })
+    }
+
+    /// Applies a `delta` in the format of `YTextEvent.delta`: a list of `{"insert": value}`,
+    /// `{"retain": length}` and `{"delete": length}` operations, starting at the beginning of
+    /// the text. Inserts and retains may have `attributes`, which format inserted or retained
+    /// content. Inserted values other than strings are embedded like with `insert_embed`.
+    pub fn apply_delta(&mut self, txn: &mut YTransaction, delta: Vec<&PyDict>) -> PyResult<()> {
+        panics::guard(|| {
+            let text = match &self.0 {
+                SharedType::Integrated(text) => text,
+                SharedType::Prelim(_) => {
+                    return Err(IntegratedOperationException::default_message())
+                }
+            };
+            let mut index = 0;
+            for operation in delta {
+                let attributes = match operation.get_item("attributes") {
+                    Some(attributes) if !attributes.is_none() => {
+                        Some(Self::parse_attrs(attributes.extract()?)?)
+                    }
+                    _ => None,
+                };
+                if let Some(insert) = operation.get_item("insert") {
+                    // Lengths of inserted values are counted like indexes of the text
+                    let len = text.len();
+                    if let Ok(chunk) = insert.extract::<&str>() {
+                        match attributes {
+                            Some(attrs) => text.insert_with_attributes(txn, index, chunk, attrs),
+                            None => text.insert(txn, index, chunk),
+                        }
+                    } else {
+                        self.1.within(text.as_ref(), || {
+                            insert_embed_value(txn, text, index, insert.into(), attributes)
+                        })?;
+                    }
+                    index += text.len() - len;
+                } else if let Some(len) = operation.get_item("retain") {
+                    let len: u32 = len.extract()?;
+                    if let Some(attrs) = attributes {
+                        text.format(txn, index, len, attrs);
+                    }
+                    index += len;
+                } else if let Some(len) = operation.get_item("delete") {
+                    text.remove_range(txn, index, len.extract()?);
                 } else {
-                    text.insert_embed(txn, index, content?)
+                    return Err(PyValueError::new_err(format!(
+                        "Invalid delta operation: {operation}"
+                    )));
                 }
-                Ok(())
             }
-            SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
-        }
+            Ok(())
+        })
     }
 
     /// Wraps an existing piece of text within a range described by `index`-`length` parameters with
     /// formatting blocks containing provided `attributes` metadata. This method only works for
-    /// `YText` instances that already have been integrated into document store.
+    /// `YText` instances that already have been integrated into document store. An `IndexError`
+    /// is raised if the range exceeds the text, unless `clamp` is set, in which case only the part
+    /// of the range within the text is formatted.
     pub fn format(
         &mut self,
         txn: &mut YTransaction,
         index: u32,
         length: u32,
         attributes: HashMap<String, PyObject>,
+        clamp: Option<bool>,
     ) -> PyResult<()> {
-        match Self::parse_attrs(attributes) {
+        let (index, length) = self.check_range(index, length, clamp.unwrap_or(false))?;
+        panics::guard(|| match Self::parse_attrs(attributes) {
             Ok(attrs) => match &mut self.0 {
                 SharedType::Integrated(text) => {
                     text.format(txn, index, length, attrs);
@@ -168,42 +601,328 @@ impl YText {
                 SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
             },
             Err(err) => Err(err),
-        }
+        })
+    }
+
+    /// Formats the whole text with provided `attributes`, which is a shortcut for calling `format`
+    /// with a range covering all of it. Attributes set to `None` are removed.
+    pub fn format_all(
+        &mut self,
+        txn: &mut YTransaction,
+        attributes: HashMap<String, PyObject>,
+    ) -> PyResult<()> {
+        let len = self.__len__() as u32;
+        self.format(txn, 0, len, attributes, None)
+    }
+
+    /// Removes all formatting attributes from a range of text described by `index`-`length`
+    /// parameters. This method only works for `YText` instances that already have been integrated
+    /// into document store.
+    pub fn clear_format(
+        &mut self,
+        txn: &mut YTransaction,
+        index: u32,
+        length: u32,
+    ) -> PyResult<()> {
+        panics::guard(|| match &mut self.0 {
+            SharedType::Integrated(text) => {
+                // Runs are cleared one by one, so that only attributes which are actually applied
+                // are removed from each of them
+                for (index, len, names) in formatted_runs(text, txn, index, index + length) {
+                    let attrs: Attrs = names.into_iter().map(|name| (name, Any::Null)).collect();
+                    text.format(txn, index, len, attrs);
+                }
+                Ok(())
+            }
+            SharedType::Prelim(_) => Err(IntegratedOperationException::default_message()),
+        })
     }
 
     /// Appends a given `chunk` of text at the end of current `YText` instance.
-    pub fn extend(&mut self, txn: &mut YTransaction, chunk: &str) {
-        match &mut self.0 {
-            SharedType::Integrated(v) => v.push(txn, chunk),
-            SharedType::Prelim(v) => v.push_str(chunk),
-        }
+    pub fn extend(&mut self, txn: &mut YTransaction, chunk: &str) -> PyResult<()> {
+        panics::guard(|| {
+            match &mut self.0 {
+                SharedType::Integrated(v) => v.push(txn, chunk),
+                SharedType::Prelim(v) => v.push_str(chunk),
+            }
+            Ok(())
+        })
     }
-    /// Deletes character at the specified index.
-    pub fn delete(&mut self, txn: &mut YTransaction, index: u32) {
-        self.delete_range(txn, index, 1);
+    /// Deletes character at the specified index. Indexes beyond the end of the text are handled
+    /// like in `delete_range`.
+    pub fn delete(
+        &mut self,
+        txn: &mut YTransaction,
+        index: u32,
+        clamp: Option<bool>,
+    ) -> PyResult<()> {
+        self.delete_range(txn, index, 1, clamp)
     }
 
     /// Deletes a specified range of of characters, starting at a given `index`.
     /// Both `index` and `length` are counted in terms of a number of UTF-8 character bytes.
-    pub fn delete_range(&mut self, txn: &mut YTransaction, index: u32, length: u32) {
-        match &mut self.0 {
-            SharedType::Integrated(v) => v.remove_range(txn, index, length),
-            SharedType::Prelim(v) => {
-                v.drain((index as usize)..(index + length) as usize);
+    /// An `IndexError` is raised if the range exceeds the text, unless `clamp` is set, in which
+    /// case only the part of the range within the text is deleted.
+    pub fn delete_range(
+        &mut self,
+        txn: &mut YTransaction,
+        index: u32,
+        length: u32,
+        clamp: Option<bool>,
+    ) -> PyResult<()> {
+        let (index, length) = self.check_range(index, length, clamp.unwrap_or(false))?;
+        if length == 0 {
+            return Ok(());
+        }
+        panics::guard(|| {
+            match &mut self.0 {
+                SharedType::Integrated(v) => v.remove_range(txn, index, length),
+                SharedType::Prelim(v) => {
+                    v.drain((index as usize)..(index + length) as usize);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Reads a page of at most `count` characters of this text, starting at a given index or at a
+    /// continuation token returned by a read of a previous page. Returns the characters with a
+    /// token of the next page, or `None` once the end of the text has been reached.
+    ///
+    /// Tokens are stored relative to the characters they point at, so pages stay consistent while
+    /// the text is being edited: characters inserted before a token are not read again, and a
+    /// token pointing at a deleted character continues from the place of the deletion.
+    pub fn range(
+        &self,
+        txn: &mut YTransaction,
+        start: PageStart,
+        count: u32,
+    ) -> PyResult<(String, Option<String>)> {
+        let text = match &self.0 {
+            SharedType::Integrated(text) => text,
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        let chunks = text_chunks(text, txn);
+        let kind = offset_kind(text, &chunks);
+        let start = start.text_index(text, txn, &chunks, &kind)?;
+        let (page, next) = text_page(&chunks, start, start.saturating_add(count), &kind);
+        Ok((page, next.map(encode_token)))
+    }
+
+    /// Returns a number of lines of this `YText` instance, separated by `\n` characters. An empty
+    /// text consists of a single empty line.
+    pub fn line_count(&self) -> usize {
+        self.__str__().matches('\n').count() + 1
+    }
+
+    /// Returns contents of a line with a given number, counted from 0, without its trailing `\n`.
+    /// Embedded values are skipped.
+    pub fn line(&self, n: usize) -> PyResult<String> {
+        let contents = self.__str__();
+        let count = contents.matches('\n').count() + 1;
+        contents
+            .split('\n')
+            .nth(n)
+            .map(str::to_string)
+            .ok_or_else(|| {
+                PyIndexError::new_err(format!(
+                    "Line {n} is out of range, the text has {count} lines"
+                ))
+            })
+    }
+
+    /// Inserts a given `chunk` of text at a `column` of a `line`, both counted from 0, which is
+    /// useful for editors working with line and column coordinates. Columns are counted like
+    /// indexes of other `YText` methods.
+    pub fn insert_at_line(
+        &mut self,
+        txn: &mut YTransaction,
+        line: usize,
+        column: u32,
+        chunk: &str,
+    ) -> PyResult<()> {
+        let (chunks, kind) = self.contents(txn);
+        let mut current = 0;
+        let mut index = 0;
+        let mut start = if line == 0 { Some(0) } else { None };
+        let mut end = None;
+        'chunks: for chunk in chunks.iter() {
+            match chunk {
+                TextChunk::String(_, chunk) => {
+                    for c in chunk.chars() {
+                        if c == '\n' {
+                            if current == line {
+                                end = Some(index);
+                                break 'chunks;
+                            }
+                            current += 1;
+                            if current == line {
+                                start = Some(index + 1);
+                            }
+                        }
+                        index += char_len(c, &kind);
+                    }
+                }
+                TextChunk::Embed => index += 1,
             }
         }
+        let (start, end) = match start {
+            Some(start) => (start, end.unwrap_or(index)),
+            None => {
+                return Err(PyIndexError::new_err(format!(
+                    "Line {line} is out of range, the text has {} lines",
+                    current + 1
+                )))
+            }
+        };
+        if column > end - start {
+            return Err(PyIndexError::new_err(format!(
+                "Column {column} is out of range, line {line} has a length of {}",
+                end - start
+            )));
+        }
+        self.insert(txn, start + column, chunk, None, None)
+    }
+
+    /// Replaces occurrences of `old` text with `new` text, like `str.replace`. If `count` is given
+    /// and not negative, only that many first occurrences are replaced. Every occurrence is
+    /// changed separately and only characters which differ are touched, so concurrent edits of
+    /// the rest of the text are preserved. Returns the number of replaced occurrences.
+    pub fn replace(
+        &mut self,
+        txn: &mut YTransaction,
+        old: &str,
+        new: &str,
+        count: Option<i64>,
+    ) -> PyResult<usize> {
+        if old.is_empty() {
+            return Err(PyValueError::new_err("The replaced text must not be empty"));
+        }
+        let limit = match count {
+            Some(count) if count >= 0 => count as usize,
+            _ => usize::MAX,
+        };
+        let (content, kind) = self.chars(txn);
+        let text: String = content.iter().map(|(c, _)| c).collect();
+        let len = old.chars().count();
+        let mut position = 0;
+        let mut offset = 0;
+        let mut matches = Vec::new();
+        for (byte, _) in text.match_indices(old).take(limit) {
+            position += text[offset..byte].chars().count();
+            offset = byte;
+            matches.push((position, position + len, new.to_string()));
+        }
+        self.replace_ranges(txn, &content, &kind, matches)
+    }
+
+    /// Replaces matches of a regular expression `pattern`, either a string or a compiled pattern
+    /// of the `re` module, with `repl`. Like in `re.sub`, `repl` can be a string with
+    /// backreferences, or a function receiving a match object and returning a replacement.
+    /// Embedded values are not visible to the pattern. Returns the number of replaced matches.
+    pub fn regex_replace(
+        &mut self,
+        py: Python,
+        txn: &mut YTransaction,
+        pattern: PyObject,
+        repl: PyObject,
+    ) -> PyResult<usize> {
+        let (content, kind) = self.chars(txn);
+        let text: String = content.iter().map(|(c, _)| c).collect();
+        let pattern = py.import("re")?.call_method1("compile", (pattern,))?;
+        let callable = repl.as_ref(py).is_callable();
+        let mut matches = Vec::new();
+        for found in pattern.call_method1("finditer", (text,))?.iter()? {
+            let found = found?;
+            let (start, end): (usize, usize) = found.call_method0("span")?.extract()?;
+            let replacement = if callable {
+                repl.call1(py, (found,))?.extract(py)?
+            } else {
+                found
+                    .call_method1("expand", (repl.clone_ref(py),))?
+                    .extract()?
+            };
+            matches.push((start, end, replacement));
+        }
+        self.replace_ranges(txn, &content, &kind, matches)
+    }
+
+    /// Returns contents of this `YText` instance as Markdown. Formatting attributes follow the
+    /// conventions of the Quill editor: `bold`, `italic`, `strike`, `code` and `link` attributes of
+    /// text, and `header`, `list`, `blockquote` and `code-block` attributes of newline characters
+    /// ending lines. Embedded values are skipped.
+    pub fn to_markdown(&self, txn: &mut YTransaction) -> String {
+        let chunks = match &self.0 {
+            SharedType::Integrated(text) => text
+                .diff(txn, YChange::identity)
+                .into_iter()
+                .filter_map(|diff| match diff.insert {
+                    Value::Any(Any::String(chunk)) => Some((
+                        chunk.to_string(),
+                        diff.attributes.map_or_else(Attrs::new, |a| *a),
+                    )),
+                    _ => None,
+                })
+                .collect(),
+            SharedType::Prelim(text) => vec![(text.clone(), Attrs::new())],
+        };
+        markdown::render(&markdown::text_blocks(chunks))
+    }
+
+    /// Replaces contents of this `YText` instance with formatted text parsed from `markdown`,
+    /// using the same attributes as `to_markdown`. Headings, paragraphs, lists, quotes, fenced
+    /// code blocks, as well as bold, italic, strikethrough, code and link formatting of text are
+    /// supported. This method only works for `YText` instances that already have been integrated
+    /// into document store.
+    pub fn from_markdown(&mut self, txn: &mut YTransaction, markdown: &str) -> PyResult<()> {
+        let text = match &self.0 {
+            SharedType::Integrated(text) => text,
+            SharedType::Prelim(_) => return Err(IntegratedOperationException::default_message()),
+        };
+        panics::guard(|| {
+            let len = text.len();
+            if len > 0 {
+                text.remove_range(txn, 0, len);
+            }
+            for (chunk, attrs) in markdown::text_chunks(&markdown::parse(markdown)) {
+                text.insert_with_attributes(txn, text.len(), &chunk, attrs);
+            }
+            Ok(())
+        })
     }
 
     /// Observes updates from the `YText` instance.
-    pub fn observe(&mut self, f: PyObject) -> PyResult<ShallowSubscription> {
+    ///
+    /// If `debounce_ms` is given, deltas of events following each other within that many
+    /// milliseconds are merged, and passed to the callback as a single delta once the text stays
+    /// unchanged for `debounce_ms`. Debounced callbacks are called on a timer thread.
+    pub fn observe(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        weak: Option<bool>,
+        debounce_ms: Option<u64>,
+    ) -> PyResult<ShallowSubscription> {
+        let f = Callback::observer(py, f, weak)?;
+        let debouncer = debounce_ms.map(|delay| Debouncer::new(f.clone(), delay));
         match &mut self.0 {
             SharedType::Integrated(text) => {
+                f.set_owner(self.1.doc_key(py));
+                let observed = Observed::new(text.as_ref(), &self.1);
                 let sub_id = text
                     .observe(move |txn, e| {
-                        Python::with_gil(|py| {
-                            let e = YTextEvent::new(e, txn);
-                            if let Err(err) = f.call1(py, (e,)) {
-                                err.restore(py)
+                        shutdown::with_gil(|py| match &debouncer {
+                            Some(debouncer) => {
+                                let delta = debounce::text_delta(py, e.delta(txn));
+                                debouncer.push(py, delta, &current_offset_kind(e.target()))
+                            }
+                            None => {
+                                let e = YTextEvent::new(e, txn, observed.clone());
+                                if let Err(err) =
+                                    metrics::measure(Metric::Callbacks, || f.call1(py, (e,)))
+                                {
+                                    logging::callback_failed(py, err)
+                                }
                             }
                         });
                     })
@@ -215,17 +934,27 @@ impl YText {
     }
 
     /// Observes updates from the `YText` instance and all of its nested children.
-    pub fn observe_deep(&mut self, f: PyObject) -> PyResult<DeepSubscription> {
+    pub fn observe_deep(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        weak: Option<bool>,
+    ) -> PyResult<DeepSubscription> {
+        let f = Callback::observer(py, f, weak)?;
         match &mut self.0 {
             SharedType::Integrated(text) => {
+                f.set_owner(self.1.doc_key(py));
+                let observed = Observed::new(text.as_ref(), &self.1);
                 let sub = text
                     .observe_deep(move |txn, events| {
-                        Python::with_gil(|py| {
-                            let events = events_into_py(txn, events);
-                            if let Err(err) = f.call1(py, (events,)) {
-                                err.restore(py)
+                        shutdown::with_gil(|py| {
+                            let events = events_into_py(py, txn, events, &observed);
+                            if let Err(err) =
+                                metrics::measure(Metric::Callbacks, || f.call1(py, (events,)))
+                            {
+                                logging::callback_failed(py, err)
                             }
-                        })
+                        });
                     })
                     .into();
                 Ok(DeepSubscription(sub))
@@ -246,41 +975,520 @@ impl YText {
 }
 
 impl YText {
+    /// Wraps an integrated `text` located at a given `location`.
+    pub(crate) fn located(text: Text, location: Location) -> Self {
+        YText(SharedType::new(text), location)
+    }
+
+    /// Checks that a range of a given `length` starting at `index` lies within this text, where
+    /// both are counted in offsets of its document. Returns the range, which is shortened to fit
+    /// the text if `clamp` is set, and raises an `IndexError` otherwise.
+    fn check_range(&self, index: u32, length: u32, clamp: bool) -> PyResult<(u32, u32)> {
+        let len = match &self.0 {
+            SharedType::Integrated(text) => text.len(),
+            SharedType::Prelim(text) => text.len() as u32,
+        };
+        let (index, length) = if clamp {
+            let index = index.min(len);
+            (index, length.min(len - index))
+        } else if index > len {
+            return Err(PyIndexError::new_err(format!(
+                "Index {index} is out of range for a text of length {len}"
+            )));
+        } else if length > len - index {
+            return Err(PyIndexError::new_err(format!(
+                "Range of length {length} at index {index} is out of range for a text of length {len}"
+            )));
+        } else {
+            (index, length)
+        };
+        if let SharedType::Prelim(text) = &self.0 {
+            // Preliminary texts are indexed by bytes, which may fall inside of a character
+            for offset in [index, index + length].iter() {
+                if !text.is_char_boundary(*offset as usize) {
+                    return Err(PyIndexError::new_err(format!(
+                        "Index {offset} is in the middle of a character"
+                    )));
+                }
+            }
+        }
+        Ok((index, length))
+    }
+
+    /// Returns visible contents of this text, split into chunks, together with the offset kind
+    /// their lengths are counted with.
+    fn contents(&self, txn: &mut Transaction) -> (Vec<TextChunk>, OffsetKind) {
+        match &self.0 {
+            SharedType::Integrated(text) => {
+                let chunks: Vec<TextChunk> = text
+                    .diff(txn, YChange::identity)
+                    .iter()
+                    .map(TextChunk::from_diff)
+                    .collect();
+                let kind = offset_kind(text, &chunks);
+                (chunks, kind)
+            }
+            SharedType::Prelim(text) => (
+                vec![TextChunk::String(None, text.clone())],
+                OffsetKind::Bytes,
+            ),
+        }
+    }
+
+    /// Returns visible characters of this text together with their indexes, and the offset kind
+    /// the indexes are counted with.
+    fn chars(&self, txn: &mut Transaction) -> (Vec<(char, u32)>, OffsetKind) {
+        let (chunks, kind) = self.contents(txn);
+        let mut chars = Vec::new();
+        let mut index = 0;
+        for chunk in chunks.iter() {
+            match chunk {
+                TextChunk::String(_, chunk) => {
+                    for c in chunk.chars() {
+                        chars.push((c, index));
+                        index += char_len(c, &kind);
+                    }
+                }
+                TextChunk::Embed => index += 1,
+            }
+        }
+        (chars, kind)
+    }
+
+    /// Replaces ranges of characters of a `content` returned by `chars` with new strings. Ranges
+    /// are given as `(start, end, replacement)` tuples of character positions, and must be sorted
+    /// and not overlap. Characters shared by the beginnings and ends of replaced ranges and their
+    /// replacements are left untouched. Returns the number of replaced ranges.
+    fn replace_ranges(
+        &mut self,
+        txn: &mut YTransaction,
+        content: &[(char, u32)],
+        kind: &OffsetKind,
+        ranges: Vec<(usize, usize, String)>,
+    ) -> PyResult<usize> {
+        let mut last = content.len();
+        for (start, end, _) in ranges.iter().rev() {
+            if start > end || *end > last {
+                return Err(PyValueError::new_err("Replaced ranges must not overlap"));
+            }
+            last = *start;
+        }
+        let count = ranges.len();
+        for (start, end, replacement) in ranges.into_iter().rev() {
+            let old = &content[start..end];
+            let new: Vec<char> = replacement.chars().collect();
+            let prefix = old
+                .iter()
+                .zip(new.iter())
+                .take_while(|((a, _), b)| a == *b)
+                .count();
+            let suffix = old[prefix..]
+                .iter()
+                .rev()
+                .zip(new[prefix..].iter().rev())
+                .take_while(|((a, _), b)| a == *b)
+                .count();
+            let (start, end) = (start + prefix, end - suffix);
+            let inserted: String = new[prefix..new.len() - suffix].iter().collect();
+            let index = match content.get(start) {
+                Some((_, index)) if start < end => *index,
+                // Insertions are placed right after the preceding character
+                _ if start > 0 => {
+                    let (c, index) = content[start - 1];
+                    index + char_len(c, kind)
+                }
+                _ => 0,
+            };
+            // Replacements are inserted before the replaced characters are deleted, so that they
+            // stay in front of concurrent insertions following these characters
+            if !inserted.is_empty() {
+                self.insert(txn, index, &inserted, None, None)?;
+            }
+            if start < end {
+                let (c, last) = content[end - 1];
+                let shift: u32 = inserted.chars().map(|c| char_len(c, kind)).sum();
+                self.delete_range(txn, index + shift, last + char_len(c, kind) - index, None)?;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Converts formatting attributes, whose values can be any JSON-like values including nested
+    /// lists and dictionaries. Conversion errors keep their type, with a message naming the
+    /// attribute which caused them.
     fn parse_attrs(attrs: HashMap<String, PyObject>) -> PyResult<Attrs> {
         Python::with_gil(|py| {
             attrs
                 .into_iter()
-                .map(|(k, v)| {
-                    let key = Rc::from(k);
-                    let value: CompatiblePyType = v.extract(py)?;
-                    Ok((key, value.try_into()?))
+                .map(|(key, value)| {
+                    let value = attr_value(value.as_ref(py)).map_err(|err| {
+                        let message =
+                            format!("Invalid value of attribute '{key}': {}", err.value(py));
+                        PyErr::from_type(err.get_type(py), message)
+                    })?;
+                    Ok((Rc::from(key), value))
                 })
                 .collect()
         })
     }
 }
 
+/// A part of visible contents of a `YText`.
+pub(crate) enum TextChunk {
+    /// A string inserted as a single item with a given id. Ids of content inserted by clients
+    /// left out of diffs (see `excluded_clients`) are unknown.
+    String(Option<ID>, String),
+    Embed,
+}
+
+impl TextChunk {
+    /// Returns a chunk of a diff, without its id.
+    pub(crate) fn from_diff<T>(diff: &Diff<T>) -> Self {
+        match &diff.insert {
+            Value::Any(Any::String(chunk)) => TextChunk::String(None, chunk.to_string()),
+            _ => TextChunk::Embed,
+        }
+    }
+}
+
+/// Returns clients whose clock doesn't exceed 2, together with their clocks. `yrs` panics while
+/// splitting blocks of such clients by a snapshot, so they are left out of snapshots passed to
+/// `Text::diff_range`.
+pub(crate) fn excluded_clients(txn: &Transaction) -> HashMap<u64, u32> {
+    txn.state_vector()
+        .iter()
+        .filter(|(_, clock)| **clock <= 2)
+        .map(|(client, clock)| (*client, *clock))
+        .collect()
+}
+
+/// Returns a copy of a `snapshot` without given `excluded` clients.
+pub(crate) fn diff_snapshot(snapshot: &Snapshot, excluded: &HashMap<u64, u32>) -> Snapshot {
+    let mut state_map = StateVector::default();
+    for (client, clock) in snapshot.state_map.iter() {
+        if !excluded.contains_key(client) {
+            state_map.set_max(*client, *clock);
+        }
+    }
+    let mut delete_set = DeleteSet::new();
+    for (client, ranges) in snapshot.delete_set.iter() {
+        if !excluded.contains_key(client) {
+            for range in ranges.iter() {
+                delete_set.insert(ID::new(*client, range.start), range.end - range.start);
+            }
+        }
+    }
+    Snapshot::new(state_map, delete_set)
+}
+
+/// Splits visible contents of a text into chunks of single items. Embedded values, which carry no
+/// ids, are recovered from current contents of the text.
+pub(crate) fn text_chunks(text: &Text, txn: &mut Transaction) -> Vec<TextChunk> {
+    let delete_set = txn.snapshot().delete_set;
+    let mut chunks = Vec::new();
+    for (id, chunk) in item_chunks(text, txn) {
+        push_visible(&mut chunks, id, &chunk, &delete_set);
+    }
+    align_chunks(chunks, text.diff(txn, YChange::identity))
+}
+
+/// Returns contents of all items of a text in order, including deleted ones. Every item is
+/// reported as added since an empty snapshot, which makes diffs of strings carry item ids.
+/// Splitting blocks by a delete set of a snapshot misplaces blocks of other shared types until the
+/// transaction is committed, so the snapshot has no deletions. Content of clients left out of
+/// diffs (see `excluded_clients`) is missing.
+pub(crate) fn item_diffs(text: &Text, txn: &mut Transaction) -> Vec<Diff<ID>> {
+    let excluded = excluded_clients(txn);
+    let snapshot = txn.snapshot();
+    let current = diff_snapshot(
+        &Snapshot::new(snapshot.state_map, DeleteSet::new()),
+        &excluded,
+    );
+    text.diff_range(txn, Some(&current), Some(&Snapshot::default()), |c| c.id)
+}
+
+/// Returns strings of all items of a text with their ids in order, including deleted ones, like
+/// [item_diffs]. Embedded values carry no ids, so deleted ones can't be told apart, and they are
+/// left out.
+pub(crate) fn item_chunks(text: &Text, txn: &mut Transaction) -> Vec<(ID, String)> {
+    item_diffs(text, txn)
+        .into_iter()
+        .filter_map(|diff| match (diff.insert, diff.ychange) {
+            (Value::Any(Any::String(chunk)), Some(id)) => Some((id, chunk.to_string())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Pushes parts of a string `chunk` inserted as a single item with a given `id`, which haven't been
+/// deleted.
+fn push_visible(chunks: &mut Vec<TextChunk>, id: ID, chunk: &str, delete_set: &DeleteSet) {
+    let mut clock = id.clock;
+    let mut visible: Option<(u32, String)> = None;
+    for c in chunk.chars() {
+        if delete_set.is_deleted(&ID::new(id.client, clock)) {
+            if let Some((start, part)) = visible.take() {
+                chunks.push(TextChunk::String(Some(ID::new(id.client, start)), part));
+            }
+        } else if let Some((_, part)) = visible.as_mut() {
+            part.push(c);
+        } else {
+            visible = Some((clock, c.to_string()));
+        }
+        clock += c.len_utf16() as u32;
+    }
+    if let Some((start, part)) = visible {
+        chunks.push(TextChunk::String(Some(ID::new(id.client, start)), part));
+    }
+}
+
+/// Recovers content left out of `known` chunks, by aligning them with `full` contents of a text.
+fn align_chunks(known: Vec<TextChunk>, full: Vec<Diff<YChange>>) -> Vec<TextChunk> {
+    // Characters of the full contents, where `None` stands for an embedded value.
+    let units: Vec<Option<char>> = full
+        .into_iter()
+        .flat_map(|diff| match diff.insert {
+            Value::Any(Any::String(chunk)) => chunk.chars().map(Some).collect(),
+            _ => vec![None],
+        })
+        .collect();
+    let mut chunks = Vec::new();
+    let mut pos = 0;
+    for chunk in known {
+        let pattern: Vec<Option<char>> = match &chunk {
+            TextChunk::String(_, chunk) => chunk.chars().map(Some).collect(),
+            TextChunk::Embed => vec![None],
+        };
+        let found = (pos..=units.len().saturating_sub(pattern.len()))
+            .find(|&i| units[i..i + pattern.len()] == pattern[..]);
+        let found = match found {
+            Some(found) => found,
+            None => break,
+        };
+        push_unknown(&mut chunks, &units[pos..found]);
+        chunks.push(chunk);
+        pos = found + pattern.len();
+    }
+    push_unknown(&mut chunks, &units[pos..]);
+    chunks
+}
+
+fn push_unknown(chunks: &mut Vec<TextChunk>, units: &[Option<char>]) {
+    for unit in units {
+        match (unit, chunks.last_mut()) {
+            (Some(c), Some(TextChunk::String(None, chunk))) => chunk.push(*c),
+            (Some(c), _) => chunks.push(TextChunk::String(None, c.to_string())),
+            (None, _) => chunks.push(TextChunk::Embed),
+        }
+    }
+}
+
+/// Returns characters of diffs, or `None` for embedded values, with indexes of their diffs.
+fn diff_units<T>(diffs: &[Diff<T>]) -> Vec<(usize, Option<char>)> {
+    diffs
+        .iter()
+        .enumerate()
+        .flat_map(|(i, diff)| match &diff.insert {
+            Value::Any(Any::String(chunk)) => chunk.chars().map(|c| (i, Some(c))).collect(),
+            _ => vec![(i, None)],
+        })
+        .collect()
+}
+
+pub(crate) fn char_len(c: char, kind: &OffsetKind) -> u32 {
+    match kind {
+        OffsetKind::Bytes => c.len_utf8() as u32,
+        OffsetKind::Utf16 => c.len_utf16() as u32,
+        OffsetKind::Utf32 => 1,
+    }
+}
+
+pub(crate) fn chunk_len(chunk: &TextChunk, kind: &OffsetKind) -> u32 {
+    match chunk {
+        TextChunk::String(_, chunk) => chunk.chars().map(|c| char_len(c, kind)).sum(),
+        TextChunk::Embed => 1,
+    }
+}
+
+/// Returns the offset kind of a document containing a given text. It's not exposed by yrs, but
+/// it's the unit of `Text::len`. Offset kinds which count current contents of the text equally
+/// also give equal indexes, so any of them can be used.
+pub(crate) fn offset_kind(text: &Text, chunks: &[TextChunk]) -> OffsetKind {
+    let len = text.len();
+    for kind in [OffsetKind::Utf32, OffsetKind::Utf16] {
+        if chunks
+            .iter()
+            .map(|chunk| chunk_len(chunk, &kind))
+            .sum::<u32>()
+            == len
+        {
+            return kind;
+        }
+    }
+    OffsetKind::Bytes
+}
+
+/// Returns the offset kind of a document containing a given text like [offset_kind], without a
+/// transaction to read its chunks. Embedded values are counted from the block length of the text,
+/// which yrs keeps in UTF-16 code units.
+pub(crate) fn current_offset_kind(text: &Text) -> OffsetKind {
+    let string = text.to_string();
+    let embeds = text
+        .as_ref()
+        .block_len
+        .saturating_sub(string.encode_utf16().count() as u32);
+    let mut chunks = vec![TextChunk::String(None, string)];
+    chunks.extend((0..embeds).map(|_| TextChunk::Embed));
+    offset_kind(text, &chunks)
+}
+
+/// Returns formatted runs of a text between `start` and `end` indexes, as `(index, len, names)`
+/// tuples, where `names` are the attributes applied to a run.
+fn formatted_runs(
+    text: &Text,
+    txn: &mut Transaction,
+    start: u32,
+    end: u32,
+) -> Vec<(u32, u32, Vec<Rc<str>>)> {
+    let diffs = text.diff(txn, YChange::identity);
+    let chunks: Vec<TextChunk> = diffs.iter().map(TextChunk::from_diff).collect();
+    let kind = offset_kind(text, &chunks);
+    let mut runs = Vec::new();
+    let mut index = 0;
+    for (diff, chunk) in diffs.iter().zip(chunks.iter()) {
+        if index >= end {
+            break;
+        }
+        let len = chunk_len(chunk, &kind);
+        if let Some(attributes) = &diff.attributes {
+            let from = index.max(start);
+            let to = (index + len).min(end);
+            if from < to && !attributes.is_empty() {
+                runs.push((from, to - from, attributes.keys().cloned().collect()));
+            }
+        }
+        index += len;
+    }
+    runs
+}
+
+/// Returns visible chunks of a text together with formatting attributes applied to them.
+/// Embedded values are skipped.
+pub(crate) fn formatted_chunks(text: &Text, txn: &mut Transaction) -> Vec<(String, Attrs)> {
+    text.diff(txn, YChange::identity)
+        .into_iter()
+        .filter_map(|diff| match diff.insert {
+            Value::Any(Any::String(chunk)) => Some((
+                chunk.to_string(),
+                diff.attributes.map(|attrs| *attrs).unwrap_or_default(),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+fn attr_value(value: &PyAny) -> PyResult<Any> {
+    match CompatiblePyType::try_from(value)? {
+        CompatiblePyType::YType(_) => Err(PyTypeError::new_err(
+            "shared types can't be formatting attributes, use insert_embed to embed them instead",
+        )),
+        value => value.try_into(),
+    }
+}
+
+/// Inserts an `embed` into a text at a given `index`. Preliminary shared types are inserted the
+/// same way elements of arrays are, which is why their position is translated into an offset of
+/// item clocks.
+fn insert_embed_value(
+    txn: &mut Transaction,
+    text: &Text,
+    index: u32,
+    embed: PyObject,
+    attributes: Option<Attrs>,
+) -> PyResult<()> {
+    Python::with_gil(|py| {
+        let py_type: CompatiblePyType = embed.extract(py)?;
+        match &py_type {
+            CompatiblePyType::YType(y_type) if y_type.is_prelim() => {
+                let offset = clock_index(text, txn, index);
+                let branch = BranchPtr::from(text.as_ref());
+                let value = PreparedValue::new(embed.as_ref(py))?;
+                Array::from(branch).insert(txn, offset, value);
+                take_integration_error()?;
+                if let Some(attrs) = attributes {
+                    text.format(txn, index, 1, attrs);
+                }
+            }
+            _ => {
+                let content: Any = py_type.try_into()?;
+                match attributes {
+                    Some(attrs) => text.insert_embed_with_attributes(txn, index, content, attrs),
+                    None => text.insert_embed(txn, index, content),
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Converts an `index` of a text into a number of item clocks of visible content preceding it.
+fn clock_index(text: &Text, txn: &mut Transaction, mut index: u32) -> u32 {
+    let chunks: Vec<TextChunk> = text
+        .diff(txn, YChange::identity)
+        .iter()
+        .map(TextChunk::from_diff)
+        .collect();
+    let kind = offset_kind(text, &chunks);
+    let mut clocks = 0;
+    for chunk in chunks.iter() {
+        let len = chunk_len(chunk, &kind);
+        if index < len {
+            return clocks
+                + match chunk {
+                    TextChunk::String(_, chunk) => clock_offset(chunk, index, &kind),
+                    TextChunk::Embed => index,
+                };
+        }
+        index -= len;
+        clocks += match chunk {
+            TextChunk::String(_, chunk) => chunk.encode_utf16().count() as u32,
+            TextChunk::Embed => 1,
+        };
+    }
+    clocks
+}
+
 /// Event generated by `YYText.observe` method. Emitted during transaction commit phase.
 #[pyclass(unsendable)]
 pub struct YTextEvent {
     inner: *const TextEvent,
     txn: *const Transaction,
+    origin: Option<PyObject>,
     target: Option<PyObject>,
     delta: Option<PyObject>,
+    observed: Observed,
 }
 
 impl YTextEvent {
-    pub fn new(event: &TextEvent, txn: &Transaction) -> Self {
+    pub(crate) fn new(event: &TextEvent, txn: &Transaction, observed: Observed) -> Self {
         let inner = event as *const TextEvent;
         let txn = txn as *const Transaction;
         YTextEvent {
             inner,
             txn,
+            origin: origin::committing(),
             target: None,
             delta: None,
+            observed,
         }
     }
 
+    /// Returns a location of the target of this event.
+    fn location(&self) -> Location {
+        let target = self.inner().target();
+        self.observed.locate(target.as_ref(), self.inner().path())
+    }
+
     fn inner(&self) -> &TextEvent {
         unsafe { self.inner.as_ref().unwrap() }
     }
@@ -292,14 +1500,20 @@ impl YTextEvent {
 
 #[pymethods]
 impl YTextEvent {
+    /// Origin of the transaction which produced this event, see `YTransaction.with_origin`.
+    #[getter]
+    pub fn origin(&self, py: Python) -> PyObject {
+        origin::into_py(py, &self.origin)
+    }
+
     /// Returns a current shared type instance, that current event changes refer to.
     #[getter]
     pub fn target(&mut self) -> PyObject {
         if let Some(target) = self.target.as_ref() {
             target.clone()
         } else {
-            let target: PyObject =
-                Python::with_gil(|py| YText::from(self.inner().target().clone()).into_py(py));
+            let target = YText::located(self.inner().target().clone(), self.location());
+            let target: PyObject = Python::with_gil(|py| target.into_py(py));
             self.target = Some(target.clone());
             target
         }
@@ -322,13 +1536,17 @@ impl YTextEvent {
         if let Some(delta) = &self.delta {
             delta.clone()
         } else {
-            let delta: PyObject = Python::with_gil(|py| {
-                let delta = self
-                    .inner()
-                    .delta(self.txn())
-                    .into_iter()
-                    .map(|d| d.clone().into_py(py));
-                PyList::new(py, delta).into()
+            let location = self.location();
+            let target = self.inner().target();
+            let delta: PyObject = location.within(target.as_ref(), || {
+                Python::with_gil(|py| {
+                    let delta = self
+                        .inner()
+                        .delta(self.txn())
+                        .into_iter()
+                        .map(|d| d.clone().into_py(py));
+                    PyList::new(py, delta).into()
+                })
             });
 
             self.delta = Some(delta.clone());
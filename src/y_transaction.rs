@@ -1,9 +1,21 @@
+use crate::delete_set::DeleteSet;
+use crate::json_schema::{Schemas, Scope};
+use crate::location::{DocRef, Location};
+use crate::logging::{self, Level};
+use crate::metrics::{self, Metric};
+use crate::origin::{OriginScope, Origins};
+use crate::panics;
+use crate::shared_types::{check_root_type, EncodingError, TransactionError};
+use crate::snapshot;
+use crate::type_conversions::{binary_into_py, BinaryInput};
+use crate::update_filter::UpdateFilters;
+use crate::update_transform::UpdateTransforms;
 use crate::{y_array::YArray, y_map::YMap, y_text::YText};
-use pyo3::exceptions::PyException;
-use pyo3::types::PyBytes;
-use pyo3::{create_exception, prelude::*};
+use pyo3::prelude::*;
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
+use std::time::Instant;
+use yrs::types::{Branch, TYPE_REFS_ARRAY, TYPE_REFS_MAP, TYPE_REFS_TEXT};
 use yrs::updates::decoder::Decode;
 use yrs::updates::encoder::{Encode, Encoder};
 use yrs::{
@@ -11,13 +23,6 @@ use yrs::{
     StateVector, Transaction, Update,
 };
 
-create_exception!(
-    y_py,
-    EncodingException,
-    PyException,
-    "Occurs due to issues in the encoding/decoding process of y_py updates."
-);
-
 /// A transaction that serves as a proxy to document block store. Ypy shared data types execute
 /// their operations in a context of a given transaction. Each document can have only one active
 /// transaction at the time - subsequent attempts will cause exception to be thrown.
@@ -38,6 +43,24 @@ create_exception!(
 pub struct YTransaction {
     pub inner: Transaction,
     pub cached_before_state: Option<PyObject>,
+    /// Set once a `with` block using this transaction has exited.
+    pub committed: bool,
+    /// Set while this transaction is shared by a `YDoc.batch()` scope, which commits it.
+    pub(crate) batched: bool,
+    /// Filters of remote updates registered on the document of this transaction.
+    pub(crate) filters: UpdateFilters,
+    /// Transforms of encoded updates registered on the document of this transaction.
+    pub(crate) transforms: UpdateTransforms,
+    /// Client id of the document of this transaction.
+    pub(crate) client_id: u64,
+    /// JSON Schemas attached to root types of the document of this transaction.
+    pub(crate) schemas: Schemas,
+    /// Origins pushed by `with_origin` scopes.
+    pub(crate) origins: Origins,
+    /// Document of this transaction, which locates root types obtained from it.
+    pub(crate) doc: DocRef,
+    /// Start of this transaction, if it's measured by metrics and hasn't been committed yet.
+    started: Option<Instant>,
 }
 
 impl Deref for YTransaction {
@@ -59,6 +82,88 @@ impl YTransaction {
         YTransaction {
             inner: txn,
             cached_before_state: None,
+            committed: false,
+            batched: false,
+            filters: UpdateFilters::default(),
+            transforms: UpdateTransforms::default(),
+            client_id: 0,
+            schemas: Schemas::default(),
+            origins: Origins::default(),
+            doc: DocRef::default(),
+            started: metrics::start(),
+        }
+    }
+
+    /// Returns the JSON Schema constraining contents of a shared type found at a given `location`,
+    /// if one has been attached to its root type with `YDoc.set_schema`.
+    pub(crate) fn schema_scope(&self, branch: &Branch, location: &Location) -> Option<Scope> {
+        self.schemas.scope(branch, location)
+    }
+
+    /// Applies a lib0 v1 encoded update, unless it's malformed, exceeds limits of the document or
+    /// is rejected by update filters.
+    pub(crate) fn integrate_v1(&mut self, diff: &[u8]) -> PyResult<()> {
+        let result = self.try_integrate_v1(diff);
+        if logging::enabled() {
+            Python::with_gil(|py| match &result {
+                Ok(()) => logging::emit(py, Level::Debug, "update_applied", |record| {
+                    record.set_item("size", diff.len())
+                }),
+                Err(err) => logging::emit(py, Level::Warning, "update_rejected", |record| {
+                    record.set_item("size", diff.len())?;
+                    record.set_item("reason", err.value(py).str()?)
+                }),
+            });
+        }
+        result
+    }
+
+    fn try_integrate_v1(&mut self, diff: &[u8]) -> PyResult<()> {
+        self.filters.check_limits(diff)?;
+        // Yrs may panic on malformed updates instead of returning an error
+        let update = panics::catch(|| Update::decode(&mut DecoderV1::from(diff)))
+            .map_err(|message| {
+                EncodingError::new_err(format!("The update could not be decoded: {message}"))
+            })?
+            .map_err(|e| EncodingError::new_err(e.to_string()))?;
+        Python::with_gil(|py| self.filters.check(py, &self.inner, diff))?;
+        let upper = update.state_vector();
+        panics::catch(|| self.apply_update(update)).map_err(|message| {
+            EncodingError::new_err(format!("The update could not be integrated: {message}"))
+        })?;
+        self.filters
+            .record_pending(upper, diff.len(), &self.state_vector());
+        self.filters.record(diff)
+    }
+
+    /// Emits a "transaction_committed" record, unless this transaction hasn't changed anything.
+    fn log_commit(&self) {
+        let inserted: u64 = self
+            .state_vector()
+            .iter()
+            .map(|(client, clock)| (clock - self.before_state.get(client)) as u64)
+            .sum();
+        let encoded = self.delete_set.encode_v1();
+        let deleted: u64 = snapshot::read_delete_set(&mut snapshot::Reader::new(&encoded))
+            .map(|delete_set| delete_set.values().flatten().map(|(_, len)| len).sum())
+            .unwrap_or(0);
+        if inserted == 0 && deleted == 0 {
+            return;
+        }
+        Python::with_gil(|py| {
+            logging::emit(py, Level::Debug, "transaction_committed", |record| {
+                record.set_item("inserted", inserted)?;
+                record.set_item("deleted", deleted)
+            })
+        });
+    }
+}
+
+impl Drop for YTransaction {
+    fn drop(&mut self) {
+        // Transactions are committed once dropped, which should be measured as well
+        if self.started.is_some() {
+            self.commit();
         }
     }
 }
@@ -83,10 +188,12 @@ impl YTransaction {
     ///
     /// If there was no instance with this name before, it will be created and then returned.
     ///
-    /// If there was an instance with this name, but it was of different type, it will be projected
-    /// onto `YText` instance.
-    pub fn get_text(&mut self, name: &str) -> YText {
-        self.deref_mut().get_text(name).into()
+    /// If there was an instance with this name, but it was of different type, a `TypeError` is
+    /// raised.
+    pub fn get_text(&mut self, name: &str) -> PyResult<YText> {
+        let shared = self.deref_mut().get_text(name);
+        check_root_type(shared.as_ref(), name, TYPE_REFS_TEXT)?;
+        Ok(YText::located(shared, Location::root(&self.doc, name)))
     }
 
     /// Returns a `YArray` shared data type, that's accessible for subsequent accesses using given
@@ -94,10 +201,12 @@ impl YTransaction {
     ///
     /// If there was no instance with this name before, it will be created and then returned.
     ///
-    /// If there was an instance with this name, but it was of different type, it will be projected
-    /// onto `YArray` instance.
-    pub fn get_array(&mut self, name: &str) -> YArray {
-        self.deref_mut().get_array(name).into()
+    /// If there was an instance with this name, but it was of different type, a `TypeError` is
+    /// raised.
+    pub fn get_array(&mut self, name: &str) -> PyResult<YArray> {
+        let shared = self.deref_mut().get_array(name);
+        check_root_type(shared.as_ref(), name, TYPE_REFS_ARRAY)?;
+        Ok(YArray::located(shared, Location::root(&self.doc, name)))
     }
 
     /// Returns a `YMap` shared data type, that's accessible for subsequent accesses using given
@@ -105,17 +214,66 @@ impl YTransaction {
     ///
     /// If there was no instance with this name before, it will be created and then returned.
     ///
-    /// If there was an instance with this name, but it was of different type, it will be projected
-    /// onto `YMap` instance.
-    pub fn get_map(&mut self, name: &str) -> YMap {
-        self.deref_mut().get_map(name).into()
+    /// If there was an instance with this name, but it was of different type, a `TypeError` is
+    /// raised.
+    pub fn get_map(&mut self, name: &str) -> PyResult<YMap> {
+        let shared = self.deref_mut().get_map(name);
+        check_root_type(shared.as_ref(), name, TYPE_REFS_MAP)?;
+        Ok(YMap::located(shared, Location::root(&self.doc, name)))
+    }
+
+    /// Origin of the innermost active `with_origin` scope of this transaction, or `None`.
+    #[getter]
+    pub fn origin(&self, py: Python) -> PyObject {
+        match self.origins.current() {
+            Some(origin) => origin.clone_ref(py),
+            None => py.None(),
+        }
+    }
+
+    /// Returns a scope in which changes made with this transaction are tagged with a given
+    /// `origin`, so that helper libraries can tag their operations without clobbering an origin
+    /// set by their caller. Scopes can be nested, and the enclosing origin is restored once a
+    /// scope is exited.
+    ///
+    /// Observers of the transaction see the innermost origin pushed while it was active, as
+    /// events are only emitted once the transaction is committed.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// from y_py import YDoc
+    ///
+    /// doc = YDoc()
+    /// text = doc.get_text('text')
+    /// text.observe(lambda event: print(event.origin))
+    /// with doc.begin_transaction() as txn:
+    ///     with txn.with_origin('user'):
+    ///         text.extend(txn, 'hello')
+    ///         with txn.with_origin('import'):
+    ///             text.extend(txn, ' world')
+    ///         assert txn.origin == 'user'
+    /// # prints 'import'
+    /// ```
+    pub fn with_origin(slf: PyRef<Self>, origin: PyObject) -> OriginScope {
+        OriginScope::new(slf.into(), origin)
     }
 
     /// Triggers a post-update series of operations without `free`ing the transaction. This includes
     /// compaction and optimization of internal representation of updates, triggering events etc.
     /// Ypy transactions are auto-committed when they are `free`d.
     pub fn commit(&mut self) {
-        self.deref_mut().commit()
+        if self.batched {
+            return;
+        }
+        if logging::enabled() {
+            self.log_commit();
+        }
+        let inner = &mut self.inner;
+        self.origins.committing(|| inner.commit());
+        if let Some(started) = self.started.take() {
+            metrics::record(Metric::Transactions, started.elapsed());
+        }
     }
 
     /// Encodes a state vector of a given transaction document into its binary representation using
@@ -148,13 +306,20 @@ impl YTransaction {
     pub fn state_vector_v1(&self) -> PyObject {
         let sv = self.state_vector();
         let payload = sv.encode_v1();
-        Python::with_gil(|py| PyBytes::new(py, &payload).into())
+        Python::with_gil(|py| binary_into_py(py, payload))
+    }
+
+    /// Returns a set of items deleted by this transaction so far.
+    pub fn delete_set(&self) -> PyResult<DeleteSet> {
+        let payload = self.inner.delete_set.encode_v1();
+        DeleteSet::decode(BinaryInput::Owned(payload))
     }
 
     /// Encodes all updates that have happened since a given version `vector` into a compact delta
     /// representation using lib0 v1 encoding. If `vector` parameter has not been provided, generated
     /// delta payload will contain all changes of a current Ypy document, working effectively as
-    /// its state snapshot.
+    /// its state snapshot. Transforms registered with `YDoc.add_update_transform` are applied to
+    /// the payload.
     ///
     /// Example:
     ///
@@ -177,21 +342,20 @@ impl YTransaction {
     ///     del local_txn
     ///     del remote_txn
     /// ```
-    pub fn diff_v1(&self, vector: Option<Vec<u8>>) -> PyResult<PyObject> {
+    pub fn diff_v1(&self, vector: Option<BinaryInput>) -> PyResult<PyObject> {
         let mut encoder = EncoderV1::new();
         let sv = if let Some(vector) = vector {
-            StateVector::decode_v1(vector.to_vec().as_slice())
-                .map_err(|e| EncodingException::new_err(e.to_string()))?
+            StateVector::decode_v1(&vector).map_err(|e| EncodingError::new_err(e.to_string()))?
         } else {
             StateVector::default()
         };
         self.encode_diff(&sv, &mut encoder);
-        let bytes: PyObject = Python::with_gil(|py| PyBytes::new(py, &encoder.to_vec()).into());
-        Ok(bytes)
+        Python::with_gil(|py| self.transforms.encode(py, encoder.to_vec()))
     }
 
     /// Applies delta update generated by the remote document replica to a current transaction's
-    /// document. This method assumes that a payload maintains lib0 v1 encoding format.
+    /// document. This method assumes that a payload maintains lib0 v1 encoding format, once
+    /// transforms registered with `YDoc.add_update_transform` have been reversed.
     ///
     /// Example:
     ///
@@ -214,13 +378,11 @@ impl YTransaction {
     ///     del local_txn
     ///     del remote_txn
     /// ```
-    pub fn apply_v1(&mut self, diff: Vec<u8>) -> PyResult<()> {
-        let diff: Vec<u8> = diff.to_vec();
-        let mut decoder = DecoderV1::from(diff.as_slice());
-        let update =
-            Update::decode(&mut decoder).map_err(|e| EncodingException::new_err(e.to_string()))?;
-        self.apply_update(update);
-        Ok(())
+    pub fn apply_v1(&mut self, diff: BinaryInput) -> PyResult<()> {
+        metrics::measure(Metric::Updates, || {
+            let diff = Python::with_gil(|py| self.transforms.decode(py, diff))?;
+            self.integrate_v1(&diff)
+        })
     }
 
     /// Allows YTransaction to be used with a Python context block.
@@ -236,7 +398,13 @@ impl YTransaction {
     ///
     /// ```
     fn __enter__<'p>(slf: PyRef<'p, Self>, _py: Python<'p>) -> PyResult<PyRef<'p, Self>> {
-        Ok(slf)
+        if slf.committed {
+            Err(TransactionError::new_err(
+                "This transaction has already been committed by a `with` block. Start a new one with `YDoc.begin_transaction`.",
+            ))
+        } else {
+            Ok(slf)
+        }
     }
 
     /// Allows YTransaction to be used with a Python context block.
@@ -259,8 +427,10 @@ impl YTransaction {
         _exception_value: Option<&'p PyAny>,
         _traceback: Option<&'p PyAny>,
     ) -> PyResult<bool> {
-        self.commit();
-        drop(self);
+        if !self.batched {
+            self.commit();
+            self.committed = true;
+        }
         Ok(exception_type.is_none())
     }
 }
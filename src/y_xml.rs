@@ -1,10 +1,23 @@
-use crate::shared_types::SubId;
+use crate::copy::{copy_xml_element, copy_xml_text};
+use crate::location::{Location, Observed};
+use crate::logging;
+use crate::markdown;
+use crate::metrics::{self, Metric};
+use crate::origin;
+use crate::panics;
+use crate::prosemirror;
+use crate::shared_types::{compare_branches, hash_branch, DefaultPyErr, SourceGuard, SubId};
+use lib0::any::Any;
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::intern;
 use pyo3::prelude::*;
+use pyo3::pyclass::CompareOp;
 use pyo3::types::{PyDict, PyList};
 use std::mem::ManuallyDrop;
 use std::ops::Deref;
 use yrs::types::xml::{Attributes, TreeWalker, XmlEvent, XmlTextEvent};
-use yrs::types::{DeepObservable, EntryChange, Path, PathSegment};
+use yrs::types::TYPE_REFS_XML_TEXT;
+use yrs::types::{Branch, BranchPtr, DeepObservable, EntryChange, Path, PathSegment};
 use yrs::SubscriptionId;
 use yrs::Transaction;
 use yrs::Xml;
@@ -12,6 +25,7 @@ use yrs::XmlElement;
 use yrs::XmlText;
 
 use crate::shared_types::{DeepSubscription, ShallowSubscription};
+use crate::shutdown::{self, Callback};
 use crate::type_conversions::{events_into_py, ToPython};
 use crate::y_transaction::YTransaction;
 
@@ -29,7 +43,7 @@ use crate::y_transaction::YTransaction;
 ///   using interleave-resistant algorithm, where order of concurrent inserts at the same index
 ///   is established using peer's document id seniority.
 #[pyclass(unsendable)]
-pub struct YXmlElement(pub XmlElement);
+pub struct YXmlElement(pub XmlElement, pub(crate) Location);
 
 #[pymethods]
 impl YXmlElement {
@@ -50,29 +64,163 @@ impl YXmlElement {
         txn: &mut YTransaction,
         index: u32,
         name: &str,
-    ) -> YXmlElement {
-        YXmlElement(self.0.insert_elem(txn, index, name))
+    ) -> PyResult<YXmlElement> {
+        panics::guard(|| {
+            let element = self.0.insert_elem(txn, index, name);
+            Ok(YXmlElement(element, self.1.child(self.0.as_ref())))
+        })
     }
 
     /// Inserts a new instance of `YXmlText` as a child of this XML node and returns it.
-    pub fn insert_xml_text(&self, txn: &mut YTransaction, index: u32) -> YXmlText {
-        YXmlText(self.0.insert_text(txn, index))
+    pub fn insert_xml_text(&self, txn: &mut YTransaction, index: u32) -> PyResult<YXmlText> {
+        panics::guard(|| {
+            let text = self.0.insert_text(txn, index);
+            Ok(YXmlText(text, self.1.child(self.0.as_ref())))
+        })
     }
 
     /// Removes a range of children XML nodes from this `YXmlElement` instance,
     /// starting at given `index`.
-    pub fn delete(&self, txn: &mut YTransaction, index: u32, length: u32) {
-        self.0.remove_range(txn, index, length)
+    pub fn delete(&self, txn: &mut YTransaction, index: u32, length: u32) -> PyResult<()> {
+        panics::guard(|| {
+            self.0.remove_range(txn, index, length);
+            Ok(())
+        })
     }
 
     /// Appends a new instance of `YXmlElement` as the last child of this XML node and returns it.
-    pub fn push_xml_element(&self, txn: &mut YTransaction, name: &str) -> YXmlElement {
-        YXmlElement(self.0.push_elem_back(txn, name))
+    pub fn push_xml_element(&self, txn: &mut YTransaction, name: &str) -> PyResult<YXmlElement> {
+        panics::guard(|| {
+            let element = self.0.push_elem_back(txn, name);
+            Ok(YXmlElement(element, self.1.child(self.0.as_ref())))
+        })
     }
 
     /// Appends a new instance of `YXmlText` as the last child of this XML node and returns it.
-    pub fn push_xml_text(&self, txn: &mut YTransaction) -> YXmlText {
-        YXmlText(self.0.push_text_back(txn))
+    pub fn push_xml_text(&self, txn: &mut YTransaction) -> PyResult<YXmlText> {
+        panics::guard(|| {
+            let text = self.0.push_text_back(txn);
+            Ok(YXmlText(text, self.1.child(self.0.as_ref())))
+        })
+    }
+
+    /// Replaces this XML node with a new element with a given tag `name`, which receives copies of
+    /// all attributes and children of this node, and returns it. Tags of XML elements cannot be
+    /// changed, so the element is recreated in place: this instance refers to the removed element
+    /// afterwards, and changes made concurrently to its contents are lost. Root elements cannot
+    /// be renamed.
+    pub fn rename(&self, txn: &mut YTransaction, name: &str) -> PyResult<YXmlElement> {
+        let parent = self
+            .0
+            .parent()
+            .ok_or_else(|| PyValueError::new_err("Root XML elements cannot be renamed"))?;
+        let index = children(&parent)
+            .iter()
+            .position(|child| std::ptr::eq(branch(child), self.0.as_ref()))
+            .ok_or_else(|| PyValueError::new_err("The XML element has been removed"))?
+            as u32;
+        panics::guard(|| {
+            let renamed = parent.insert_elem(txn, index, name);
+            copy_xml_element(txn, &self.0, &renamed);
+            parent.remove_range(txn, index + 1, 1);
+            Ok(YXmlElement(renamed, self.1.clone()))
+        })
+    }
+
+    /// Moves a child XML node from a `source` index to a `target` index, which describes a
+    /// position before the move like in `YArray.move_to`, and returns the moved node. XML nodes
+    /// are moved by copying them together with their attributes and children, and removing the
+    /// originals.
+    pub fn move_child(
+        &self,
+        py: Python,
+        txn: &mut YTransaction,
+        source: u32,
+        target: u32,
+    ) -> PyResult<PyObject> {
+        let mut children = children(&self.0);
+        if source as usize >= children.len() || target as usize > children.len() {
+            return Err(PyIndexError::default_message());
+        }
+        let node = children.swap_remove(source as usize);
+        let location = self.1.child(self.0.as_ref());
+        if source == target || source + 1 == target {
+            return Ok(xml_into_py(py, node, location));
+        }
+        panics::guard(|| {
+            let moved = match &node {
+                Xml::Element(element) => {
+                    let copy = self.0.insert_elem(txn, target, element.tag());
+                    copy_xml_element(txn, element, &copy);
+                    Xml::Element(copy)
+                }
+                Xml::Text(text) => {
+                    let copy = self.0.insert_text(txn, target);
+                    copy_xml_text(txn, text, &copy);
+                    Xml::Text(copy)
+                }
+            };
+            let removed = if target < source { source + 1 } else { source };
+            self.0.remove_range(txn, removed, 1);
+            Ok(xml_into_py(py, moved, location))
+        })
+    }
+
+    /// Returns children of this XML node as Markdown. Headings `h1`-`h6`, paragraphs `p`, lists
+    /// `ul` and `ol` with `li` items, quotes `blockquote` containing paragraphs and code blocks
+    /// `pre` are converted, together with text formatted by `strong`, `em`, `s`, `code` and `a`
+    /// elements. Content of other elements is treated as paragraphs.
+    pub fn to_markdown(&self) -> String {
+        markdown::render(&markdown::xml_blocks(&self.0))
+    }
+
+    /// Replaces children of this XML node with elements parsed from `markdown`, using the same
+    /// elements as `to_markdown`.
+    pub fn from_markdown(&self, txn: &mut YTransaction, markdown: &str) -> PyResult<()> {
+        panics::guard(|| {
+            let len = self.0.len();
+            if len > 0 {
+                self.0.remove_range(txn, 0, len);
+            }
+            markdown::write_xml(txn, &self.0, &markdown::parse(markdown));
+            Ok(())
+        })
+    }
+
+    /// Returns children of this XML node as a ProseMirror document serialized to JSON, following
+    /// the mapping of y-prosemirror: elements become nodes of the same type with attributes of
+    /// elements as node attributes, and text becomes text nodes with formatting attributes as
+    /// marks.
+    pub fn to_prosemirror_json(&self, txn: &mut YTransaction) -> String {
+        let mut json = String::new();
+        prosemirror::to_json(&prosemirror::read(txn, &self.0)).to_json(&mut json);
+        json
+    }
+
+    /// Applies ProseMirror `steps`, given as a JSON array of serialized steps, to a document
+    /// represented by children of this XML node. Replace, replace-around, mark and attribute steps
+    /// are supported. Only the changed nodes are updated, using the same mapping as
+    /// `to_prosemirror_json`. Names of `leaf_nodes` are needed to compute positions in the
+    /// document, and default to leaf nodes of the basic ProseMirror schema.
+    pub fn apply_prosemirror_steps(
+        &self,
+        txn: &mut YTransaction,
+        steps: &str,
+        leaf_nodes: Option<Vec<String>>,
+    ) -> PyResult<()> {
+        let steps = Any::from_json(steps)
+            .map_err(|err| PyValueError::new_err(format!("Invalid JSON: {err}")))?;
+        let leaves = leaf_nodes.unwrap_or_else(|| {
+            prosemirror::DEFAULT_LEAVES
+                .iter()
+                .map(|name| name.to_string())
+                .collect()
+        });
+        let content = prosemirror::apply_steps(&prosemirror::read(txn, &self.0), &steps, &leaves)?;
+        panics::guard(|| {
+            prosemirror::write(txn, &self.0, &content);
+            Ok(())
+        })
     }
 
     /// Returns a first child of this XML node.
@@ -80,12 +228,43 @@ impl YXmlElement {
     #[getter]
     pub fn first_child(&self) -> PyObject {
         Python::with_gil(|py| {
-            self.0
-                .first_child()
-                .map_or(py.None(), |xml| xml.into_py(py))
+            self.0.first_child().map_or(py.None(), |xml| {
+                xml_into_py(py, xml, self.1.child(self.0.as_ref()))
+            })
         })
     }
 
+    /// Returns the `YDoc` this `YXmlElement` instance belongs to, or `None` if the document is
+    /// unknown. Documents are known for XML nodes reached from their root elements, which have
+    /// been obtained from a `YDoc`.
+    #[getter]
+    pub fn doc(&self) -> PyResult<PyObject> {
+        Ok(Python::with_gil(|py| self.1.doc(py)))
+    }
+
+    /// Starts a new transaction of the document this `YXmlElement` instance belongs to, which makes
+    /// it possible to modify it without a reference to its `YDoc`. The document must be known, as
+    /// described by the `doc` property.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// with element.transaction() as txn:
+    ///     element.set_attribute(txn, 'key', 'value')
+    /// ```
+    pub fn transaction(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| self.1.transaction(py))
+    }
+
+    /// Checks if both objects refer to the same `YXmlElement`.
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyObject {
+        compare_branches(other.py(), Some(self.0.as_ref()), other, op)
+    }
+
+    fn __hash__(&self) -> PyResult<isize> {
+        hash_branch(Some(self.0.as_ref()))
+    }
+
     /// Returns a next XML sibling node of this XMl node.
     /// It can be either `YXmlElement`, `YXmlText` or `undefined` if current node is a last child of
     /// parent XML node.
@@ -94,7 +273,7 @@ impl YXmlElement {
         Python::with_gil(|py| {
             self.0
                 .next_sibling()
-                .map_or(py.None(), |xml| xml.into_py(py))
+                .map_or(py.None(), |xml| xml_into_py(py, xml, self.1.clone()))
         })
     }
 
@@ -106,14 +285,18 @@ impl YXmlElement {
         Python::with_gil(|py| {
             self.0
                 .prev_sibling()
-                .map_or(py.None(), |xml| xml.into_py(py))
+                .map_or(py.None(), |xml| xml_into_py(py, xml, self.1.clone()))
         })
     }
 
     /// Returns a parent `YXmlElement` node or `undefined` if current node has no parent assigned.
     #[getter]
     pub fn parent(&self) -> Option<YXmlElement> {
-        self.0.parent().map(YXmlElement)
+        let parent = self.0.parent()?;
+        Some(YXmlElement(
+            parent,
+            parent_location(self.0.as_ref(), &self.1),
+        ))
     }
 
     /// Returns a string representation of this XML node.
@@ -127,8 +310,11 @@ impl YXmlElement {
 
     /// Sets a `name` and `value` as new attribute for this XML node. If an attribute with the same
     /// `name` already existed on that node, its value with be overridden with a provided one.
-    pub fn set_attribute(&self, txn: &mut YTransaction, name: &str, value: &str) {
-        self.0.insert_attribute(txn, name, value)
+    pub fn set_attribute(&self, txn: &mut YTransaction, name: &str, value: &str) -> PyResult<()> {
+        panics::guard(|| {
+            self.0.insert_attribute(txn, name, value);
+            Ok(())
+        })
     }
 
     /// Returns a value of an attribute given its `name`. If no attribute with such name existed,
@@ -138,67 +324,128 @@ impl YXmlElement {
     }
 
     /// Removes an attribute from this XML node, given its `name`.
-    pub fn remove_attribute(&self, txn: &mut YTransaction, name: &str) {
-        self.0.remove_attribute(txn, &name);
+    pub fn remove_attribute(&self, txn: &mut YTransaction, name: &str) -> PyResult<()> {
+        panics::guard(|| {
+            self.0.remove_attribute(txn, &name);
+            Ok(())
+        })
     }
 
     /// Returns an iterator that enables to traverse over all attributes of this XML node in
     /// unspecified order.
-    pub fn attributes(&self) -> YXmlAttributes {
+    pub fn attributes(slf: &PyCell<Self>) -> YXmlAttributes {
+        let xml = slf.borrow();
+        let guard = SourceGuard::new(slf, Some(xml.0.as_ref()), &xml.1);
         unsafe {
-            let this: *const XmlElement = &self.0;
+            let this: *const XmlElement = &xml.0;
             let static_iter: ManuallyDrop<Attributes<'static>> =
                 ManuallyDrop::new((*this).attributes());
-            YXmlAttributes(static_iter)
+            YXmlAttributes(static_iter, guard)
         }
     }
 
     /// Returns an iterator that enables a deep traversal of this XML node - starting from first
     /// child over this XML node successors using depth-first strategy.
-    pub fn tree_walker(&self) -> YXmlTreeWalker {
+    pub fn tree_walker(slf: &PyCell<Self>) -> YXmlTreeWalker {
+        let xml = slf.borrow();
+        let guard = SourceGuard::new(slf, Some(xml.0.as_ref()), &xml.1);
         unsafe {
-            let this: *const XmlElement = &self.0;
+            let this: *const XmlElement = &xml.0;
             let static_iter: ManuallyDrop<TreeWalker<'static>> =
                 ManuallyDrop::new((*this).successors());
-            YXmlTreeWalker(static_iter)
+            YXmlTreeWalker(static_iter, guard, xml.0.clone(), xml.1.clone())
         }
     }
 
+    /// Returns a list of direct children of this XML node: `YXmlElement` and `YXmlText` instances.
+    pub fn children(&self, py: Python) -> Vec<PyObject> {
+        let location = self.1.child(self.0.as_ref());
+        children(&self.0)
+            .into_iter()
+            .map(|child| xml_into_py(py, child, location.clone()))
+            .collect()
+    }
+
+    /// Returns all descendant XML elements with a given tag `name`, in the depth-first order used
+    /// by `tree_walker`.
+    pub fn find_all(&self, name: &str) -> Vec<YXmlElement> {
+        self.0
+            .successors()
+            .filter_map(|node| match node {
+                Xml::Element(element) if element.tag() == name => {
+                    let location = descendant_location(&self.0, &self.1, element.as_ref());
+                    Some(YXmlElement(element, location))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the first descendant XML element with a given tag `name` in the depth-first order
+    /// used by `tree_walker`, or `None` if there is no such element.
+    pub fn first(&self, name: &str) -> Option<YXmlElement> {
+        self.0.successors().find_map(|node| match node {
+            Xml::Element(element) if element.tag() == name => {
+                let location = descendant_location(&self.0, &self.1, element.as_ref());
+                Some(YXmlElement(element, location))
+            }
+            _ => None,
+        })
+    }
+
     /// Subscribes to all operations happening over this instance of `YXmlElement`. All changes are
     /// batched and eventually triggered during transaction commit phase.
     /// Returns an `SubscriptionId` which, can be used to unsubscribe the observer.
-    pub fn observe(&mut self, f: PyObject) -> ShallowSubscription {
+    pub fn observe(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        weak: Option<bool>,
+    ) -> PyResult<ShallowSubscription> {
+        let f = Callback::observer(py, f, weak)?;
+        f.set_owner(self.1.doc_key(py));
+        let observed = Observed::new(self.0.as_ref(), &self.1);
         let sub_id = self
             .0
             .observe(move |txn, e| {
-                Python::with_gil(|py| {
-                    let event = YXmlEvent::new(e, txn);
-                    if let Err(err) = f.call1(py, (event,)) {
-                        err.restore(py)
+                shutdown::with_gil(|py| {
+                    let event = YXmlEvent::new(e, txn, observed.clone());
+                    if let Err(err) = metrics::measure(Metric::Callbacks, || f.call1(py, (event,)))
+                    {
+                        logging::callback_failed(py, err)
                     }
-                })
+                });
             })
             .into();
 
-        ShallowSubscription(sub_id)
+        Ok(ShallowSubscription(sub_id))
     }
 
     /// Subscribes to all operations happening over this instance of `YXmlElement` and all of its children.
     /// All changes are batched and eventually triggered during transaction commit phase.
     /// Returns an `SubscriptionId` which, can be used to unsubscribe the observer.
-    pub fn observe_deep(&mut self, f: PyObject) -> DeepSubscription {
+    pub fn observe_deep(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        weak: Option<bool>,
+    ) -> PyResult<DeepSubscription> {
+        let f = Callback::observer(py, f, weak)?;
+        f.set_owner(self.1.doc_key(py));
+        let observed = Observed::new(self.0.as_ref(), &self.1);
         let sub_id = self
             .0
             .observe_deep(move |txn, events| {
-                Python::with_gil(|py| {
-                    let events = events_into_py(txn, events);
-                    if let Err(err) = f.call1(py, (events,)) {
-                        err.restore(py)
+                shutdown::with_gil(|py| {
+                    let events = events_into_py(py, txn, events, &observed);
+                    if let Err(err) = metrics::measure(Metric::Callbacks, || f.call1(py, (events,)))
+                    {
+                        logging::callback_failed(py, err)
                     }
-                })
+                });
             })
             .into();
-        DeepSubscription(sub_id)
+        Ok(DeepSubscription(sub_id))
     }
 
     /// Cancels the observer callback associated with the `subscripton_id`.
@@ -227,7 +474,7 @@ impl YXmlElement {
 /// after merging all updates together). In case of Yrs conflict resolution is solved by using
 /// unique document id to determine correct and consistent ordering.
 #[pyclass(unsendable)]
-pub struct YXmlText(pub XmlText);
+pub struct YXmlText(pub XmlText, pub(crate) Location);
 
 #[pymethods]
 impl YXmlText {
@@ -238,19 +485,59 @@ impl YXmlText {
     }
 
     /// Inserts a given `chunk` of text into this `YXmlText` instance, starting at a given `index`.
-    pub fn insert(&self, txn: &mut YTransaction, index: i32, chunk: &str) {
-        self.0.insert(txn, index as u32, chunk)
+    pub fn insert(&self, txn: &mut YTransaction, index: i32, chunk: &str) -> PyResult<()> {
+        panics::guard(|| {
+            self.0.insert(txn, index as u32, chunk);
+            Ok(())
+        })
     }
 
     /// Appends a given `chunk` of text at the end of `YXmlText` instance.
-    pub fn push(&self, txn: &mut YTransaction, chunk: &str) {
-        self.0.push(txn, chunk)
+    pub fn push(&self, txn: &mut YTransaction, chunk: &str) -> PyResult<()> {
+        panics::guard(|| {
+            self.0.push(txn, chunk);
+            Ok(())
+        })
     }
 
     /// Deletes a specified range of of characters, starting at a given `index`.
     /// Both `index` and `length` are counted in terms of a number of UTF-8 character bytes.
-    pub fn delete(&self, txn: &mut YTransaction, index: u32, length: u32) {
-        self.0.remove_range(txn, index, length)
+    pub fn delete(&self, txn: &mut YTransaction, index: u32, length: u32) -> PyResult<()> {
+        panics::guard(|| {
+            self.0.remove_range(txn, index, length);
+            Ok(())
+        })
+    }
+
+    /// Returns the `YDoc` this `YXmlText` instance belongs to, or `None` if the document is
+    /// unknown. Documents are known for XML nodes reached from their root elements, which have
+    /// been obtained from a `YDoc`.
+    #[getter]
+    pub fn doc(&self) -> PyResult<PyObject> {
+        Ok(Python::with_gil(|py| self.1.doc(py)))
+    }
+
+    /// Starts a new transaction of the document this `YXmlText` instance belongs to, which makes
+    /// it possible to modify it without a reference to its `YDoc`. The document must be known, as
+    /// described by the `doc` property.
+    ///
+    /// Example:
+    ///
+    /// ```python
+    /// with text.transaction() as txn:
+    ///     text.push(txn, 'hello')
+    /// ```
+    pub fn transaction(&self) -> PyResult<PyObject> {
+        Python::with_gil(|py| self.1.transaction(py))
+    }
+
+    /// Checks if both objects refer to the same `YXmlText`.
+    fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyObject {
+        compare_branches(other.py(), Some(self.0.as_ref()), other, op)
+    }
+
+    fn __hash__(&self) -> PyResult<isize> {
+        hash_branch(Some(self.0.as_ref()))
     }
 
     /// Returns a next XML sibling node of this XMl node.
@@ -261,7 +548,7 @@ impl YXmlText {
         Python::with_gil(|py| {
             self.0
                 .next_sibling()
-                .map_or(py.None(), |xml| xml.into_py(py))
+                .map_or(py.None(), |xml| xml_into_py(py, xml, self.1.clone()))
         })
     }
 
@@ -273,7 +560,7 @@ impl YXmlText {
         Python::with_gil(|py| {
             self.0
                 .prev_sibling()
-                .map_or(py.None(), |xml| xml.into_py(py))
+                .map_or(py.None(), |xml| xml_into_py(py, xml, self.1.clone()))
         })
     }
 
@@ -281,9 +568,9 @@ impl YXmlText {
     #[getter]
     pub fn parent(&self) -> PyObject {
         Python::with_gil(|py| {
-            self.0
-                .parent()
-                .map_or(py.None(), |xml| YXmlElement(xml).into_py(py))
+            self.0.parent().map_or(py.None(), |xml| {
+                YXmlElement(xml, parent_location(self.0.as_ref(), &self.1)).into_py(py)
+            })
         })
     }
 
@@ -298,8 +585,11 @@ impl YXmlText {
 
     /// Sets a `name` and `value` as new attribute for this XML node. If an attribute with the same
     /// `name` already existed on that node, its value with be overridden with a provided one.
-    pub fn set_attribute(&self, txn: &mut YTransaction, name: &str, value: &str) {
-        self.0.insert_attribute(txn, name, value);
+    pub fn set_attribute(&self, txn: &mut YTransaction, name: &str, value: &str) -> PyResult<()> {
+        panics::guard(|| {
+            self.0.insert_attribute(txn, name, value);
+            Ok(())
+        })
     }
 
     /// Returns a value of an attribute given its `name`. If no attribute with such name existed,
@@ -309,55 +599,76 @@ impl YXmlText {
     }
 
     /// Removes an attribute from this XML node, given its `name`.
-    pub fn remove_attribute(&self, txn: &mut YTransaction, name: &str) {
-        self.0.remove_attribute(txn, name);
+    pub fn remove_attribute(&self, txn: &mut YTransaction, name: &str) -> PyResult<()> {
+        panics::guard(|| {
+            self.0.remove_attribute(txn, name);
+            Ok(())
+        })
     }
 
     /// Returns an iterator that enables to traverse over all attributes of this XML node in
     /// unspecified order.
-    pub fn attributes(&self) -> YXmlAttributes {
+    pub fn attributes(slf: &PyCell<Self>) -> YXmlAttributes {
+        let xml = slf.borrow();
+        let guard = SourceGuard::new(slf, Some(xml.0.as_ref()), &xml.1);
         unsafe {
-            let this: *const XmlText = &self.0;
+            let this: *const XmlText = &xml.0;
             let static_iter: ManuallyDrop<Attributes<'static>> =
                 ManuallyDrop::new((*this).attributes());
-            YXmlAttributes(static_iter)
+            YXmlAttributes(static_iter, guard)
         }
     }
 
     /// Subscribes to all operations happening over this instance of `YXmlText`. All changes are
     /// batched and eventually triggered during transaction commit phase.
     /// Returns an `SubscriptionId` which, which can be used to unsubscribe the callback function.
-    pub fn observe(&mut self, f: PyObject) -> ShallowSubscription {
+    pub fn observe(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        weak: Option<bool>,
+    ) -> PyResult<ShallowSubscription> {
+        let f = Callback::observer(py, f, weak)?;
+        f.set_owner(self.1.doc_key(py));
+        let observed = Observed::new(self.0.as_ref(), &self.1);
         let sub_id: SubscriptionId = self
             .0
             .observe(move |txn, e| {
-                Python::with_gil(|py| {
-                    let e = YXmlTextEvent::new(e, txn);
-                    if let Err(err) = f.call1(py, (e,)) {
-                        err.restore(py)
+                shutdown::with_gil(|py| {
+                    let e = YXmlTextEvent::new(e, txn, observed.clone());
+                    if let Err(err) = metrics::measure(Metric::Callbacks, || f.call1(py, (e,))) {
+                        logging::callback_failed(py, err)
                     }
-                })
+                });
             })
             .into();
-        ShallowSubscription(sub_id)
+        Ok(ShallowSubscription(sub_id))
     }
 
     /// Subscribes to all operations happening over this instance of `YXmlText` and its child elements. All changes are
     /// batched and eventually triggered during transaction commit phase.
     /// Returns an `SubscriptionId` which, which can be used to unsubscribe the callback function.
-    pub fn observe_deep(&mut self, f: PyObject) -> DeepSubscription {
+    pub fn observe_deep(
+        &mut self,
+        py: Python,
+        f: PyObject,
+        weak: Option<bool>,
+    ) -> PyResult<DeepSubscription> {
+        let f = Callback::observer(py, f, weak)?;
+        f.set_owner(self.1.doc_key(py));
+        let observed = Observed::new(self.0.as_ref(), &self.1);
         let sub_id: SubscriptionId = self
             .0
             .observe_deep(move |txn, events| {
-                Python::with_gil(|py| {
-                    let e = events_into_py(txn, events);
-                    if let Err(err) = f.call1(py, (e,)) {
-                        err.restore(py)
+                shutdown::with_gil(|py| {
+                    let e = events_into_py(py, txn, events, &observed);
+                    if let Err(err) = metrics::measure(Metric::Callbacks, || f.call1(py, (e,))) {
+                        logging::callback_failed(py, err)
                     }
-                })
+                });
             })
             .into();
-        DeepSubscription(sub_id)
+        Ok(DeepSubscription(sub_id))
     }
 
     /// Cancels the observer callback associated with the `subscripton_id`.
@@ -370,7 +681,7 @@ impl YXmlText {
 }
 
 #[pyclass(unsendable)]
-pub struct YXmlAttributes(ManuallyDrop<Attributes<'static>>);
+pub struct YXmlAttributes(ManuallyDrop<Attributes<'static>>, SourceGuard);
 
 impl Drop for YXmlAttributes {
     fn drop(&mut self) {
@@ -383,13 +694,21 @@ impl YXmlAttributes {
     fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
         slf
     }
-    fn __next__(mut slf: PyRefMut<Self>) -> Option<(String, String)> {
-        slf.0.next().map(|(attr, val)| (attr.to_string(), val))
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<(String, String)>> {
+        slf.1.check(slf.py())?;
+        Ok(slf.0.next().map(|(attr, val)| (attr.to_string(), val)))
     }
 }
 
+/// Iterates over descendants of an XML element, which is kept together with its location to
+/// locate the descendants.
 #[pyclass(unsendable)]
-pub struct YXmlTreeWalker(ManuallyDrop<TreeWalker<'static>>);
+pub struct YXmlTreeWalker(
+    ManuallyDrop<TreeWalker<'static>>,
+    SourceGuard,
+    XmlElement,
+    Location,
+);
 
 impl Drop for YXmlTreeWalker {
     fn drop(&mut self) {
@@ -402,12 +721,14 @@ impl YXmlTreeWalker {
     pub fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
         slf
     }
-    pub fn __next__(mut slf: PyRefMut<Self>) -> Option<PyObject> {
+    pub fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
         Python::with_gil(|py| {
-            slf.0.next().map(|v| match v {
-                Xml::Element(el) => YXmlElement(el).into_py(py),
-                Xml::Text(text) => YXmlText(text).into_py(py),
-            })
+            slf.1.check(py)?;
+            let node = slf.0.next();
+            Ok(node.map(|node| {
+                let location = descendant_location(&slf.2, &slf.3, branch(&node));
+                xml_into_py(py, node, location)
+            }))
         })
     }
 }
@@ -416,23 +737,33 @@ impl YXmlTreeWalker {
 pub struct YXmlEvent {
     inner: *const XmlEvent,
     txn: *const Transaction,
+    origin: Option<PyObject>,
     target: Option<PyObject>,
     delta: Option<PyObject>,
     keys: Option<PyObject>,
+    observed: Observed,
 }
 impl YXmlEvent {
-    pub fn new(event: &XmlEvent, txn: &Transaction) -> Self {
+    pub(crate) fn new(event: &XmlEvent, txn: &Transaction, observed: Observed) -> Self {
         let inner = event as *const XmlEvent;
         let txn = txn as *const Transaction;
         YXmlEvent {
             inner,
             txn,
+            origin: origin::committing(),
             target: None,
             delta: None,
             keys: None,
+            observed,
         }
     }
 
+    /// Returns a location of the target of this event.
+    fn location(&self) -> Location {
+        let target = self.inner().target();
+        self.observed.locate(target.as_ref(), self.inner().path())
+    }
+
     fn inner(&self) -> &XmlEvent {
         unsafe { self.inner.as_ref().unwrap() }
     }
@@ -444,6 +775,12 @@ impl YXmlEvent {
 
 #[pymethods]
 impl YXmlEvent {
+    /// Origin of the transaction which produced this event, see `YTransaction.with_origin`.
+    #[getter]
+    pub fn origin(&self, py: Python) -> PyObject {
+        origin::into_py(py, &self.origin)
+    }
+
     /// Returns a current shared type instance, that current event changes refer to.
     #[getter]
     pub fn target(&mut self) -> PyObject {
@@ -451,7 +788,8 @@ impl YXmlEvent {
             target.clone()
         } else {
             Python::with_gil(|py| {
-                let target = YXmlElement(self.inner().target().clone()).into_py(py);
+                let target = YXmlElement(self.inner().target().clone(), self.location());
+                let target = target.into_py(py);
                 self.target = Some(target.clone());
                 target
             })
@@ -504,17 +842,20 @@ impl YXmlEvent {
         if let Some(delta) = &self.delta {
             delta.clone()
         } else {
-            Python::with_gil(|py| {
-                let delta = self
-                    .inner()
-                    .delta(self.txn())
-                    .into_iter()
-                    .map(|d| Python::with_gil(|py| d.into_py(py)));
-                let result = pyo3::types::PyList::new(py, delta);
-                let delta: PyObject = result.into();
-                self.delta = Some(delta.clone());
-                delta
-            })
+            let location = self.location();
+            let target = self.inner().target();
+            let delta: PyObject = location.within(target.as_ref(), || {
+                Python::with_gil(|py| {
+                    let delta = self
+                        .inner()
+                        .delta(self.txn())
+                        .into_iter()
+                        .map(|d| d.into_py(py));
+                    pyo3::types::PyList::new(py, delta).into()
+                })
+            });
+            self.delta = Some(delta.clone());
+            delta
         }
     }
 }
@@ -523,24 +864,34 @@ impl YXmlEvent {
 pub struct YXmlTextEvent {
     inner: *const XmlTextEvent,
     txn: *const Transaction,
+    origin: Option<PyObject>,
     target: Option<PyObject>,
     delta: Option<PyObject>,
     keys: Option<PyObject>,
+    observed: Observed,
 }
 
 impl YXmlTextEvent {
-    pub fn new(event: &XmlTextEvent, txn: &Transaction) -> Self {
+    pub(crate) fn new(event: &XmlTextEvent, txn: &Transaction, observed: Observed) -> Self {
         let inner = event as *const XmlTextEvent;
         let txn = txn as *const Transaction;
         YXmlTextEvent {
             inner,
             txn,
+            origin: origin::committing(),
             target: None,
             delta: None,
             keys: None,
+            observed,
         }
     }
 
+    /// Returns a location of the target of this event.
+    fn location(&self) -> Location {
+        let target = self.inner().target();
+        self.observed.locate(target.as_ref(), self.inner().path())
+    }
+
     fn inner(&self) -> &XmlTextEvent {
         unsafe { self.inner.as_ref().unwrap() }
     }
@@ -552,6 +903,12 @@ impl YXmlTextEvent {
 
 #[pymethods]
 impl YXmlTextEvent {
+    /// Origin of the transaction which produced this event, see `YTransaction.with_origin`.
+    #[getter]
+    pub fn origin(&self, py: Python) -> PyObject {
+        origin::into_py(py, &self.origin)
+    }
+
     /// Returns a current shared type instance, that current event changes refer to.
     #[getter]
     pub fn target(&mut self) -> PyObject {
@@ -559,7 +916,8 @@ impl YXmlTextEvent {
             target.clone()
         } else {
             Python::with_gil(|py| {
-                let target = YXmlText(self.inner().target().clone()).into_py(py);
+                let target = YXmlText(self.inner().target().clone(), self.location());
+                let target = target.into_py(py);
                 self.target = Some(target.clone());
                 target
             })
@@ -618,7 +976,7 @@ impl YXmlTextEvent {
                     .inner()
                     .delta(self.txn())
                     .into_iter()
-                    .map(|d| Python::with_gil(|py| d.clone().into_py(py)));
+                    .map(|d| d.clone().into_py(py));
                 let result = pyo3::types::PyList::new(py, delta);
                 let delta: PyObject = result.into();
                 self.delta = Some(delta.clone());
@@ -628,38 +986,93 @@ impl YXmlTextEvent {
     }
 }
 
+/// Returns child nodes of an XML element.
+pub(crate) fn children(element: &XmlElement) -> Vec<Xml> {
+    let mut children = Vec::new();
+    let mut current = element.first_child();
+    while let Some(child) = current {
+        current = match &child {
+            Xml::Element(element) => element.next_sibling(),
+            Xml::Text(text) => text.next_sibling(),
+        };
+        children.push(child);
+    }
+    children
+}
+
+fn branch(node: &Xml) -> &Branch {
+    match node {
+        Xml::Element(element) => element.as_ref(),
+        Xml::Text(text) => text.as_ref(),
+    }
+}
+
+/// Wraps an XML `node` located at a given `location` in its Ypy class.
+fn xml_into_py(py: Python, node: Xml, location: Location) -> PyObject {
+    match node {
+        Xml::Element(v) => YXmlElement(v, location).into_py(py),
+        Xml::Text(v) => YXmlText(v, location).into_py(py),
+    }
+}
+
+/// Returns a location of the parent element of an XML `node` located at `location`, which is
+/// unknown if the node has been removed from it.
+fn parent_location(node: &Branch, location: &Location) -> Location {
+    Python::with_gil(|py| location.parent(py, node))
+        .map(|(_, location)| location)
+        .unwrap_or_default()
+}
+
+/// Returns a location of a `descendant` of an XML `element` located at `location`, which is found
+/// by following parents of the descendant up to the element.
+fn descendant_location(element: &XmlElement, location: &Location, descendant: &Branch) -> Location {
+    let mut ancestors = Vec::new();
+    let mut parent = match descendant.type_ref() {
+        TYPE_REFS_XML_TEXT => XmlText::from(BranchPtr::from(descendant)).parent(),
+        _ => XmlElement::from(BranchPtr::from(descendant)).parent(),
+    };
+    while let Some(ancestor) = parent {
+        if std::ptr::eq(ancestor.as_ref(), element.as_ref()) {
+            return ancestors.iter().rev().fold(
+                location.child(element.as_ref()),
+                |location, ancestor: &XmlElement| location.child(ancestor.as_ref()),
+            );
+        }
+        parent = ancestor.parent();
+        ancestors.push(ancestor);
+    }
+    Location::default()
+}
+
 // XML Type Conversions
 
 impl ToPython for Xml {
     fn into_py(self, py: Python) -> PyObject {
-        match self {
-            Xml::Element(v) => YXmlElement(v).into_py(py),
-            Xml::Text(v) => YXmlText(v).into_py(py),
-        }
+        xml_into_py(py, self, Location::current())
     }
 }
 
 impl<'a> ToPython for &EntryChange {
     fn into_py(self, py: Python) -> PyObject {
         let result = PyDict::new(py);
-        let action = "action";
+        let action = intern!(py, "action");
         match self {
             EntryChange::Inserted(new) => {
                 let new_value = new.clone().into_py(py);
-                result.set_item(action, "add").unwrap();
-                result.set_item("newValue", new_value).unwrap();
+                result.set_item(action, intern!(py, "add")).unwrap();
+                result.set_item(intern!(py, "newValue"), new_value).unwrap();
             }
             EntryChange::Updated(old, new) => {
                 let old_value = old.clone().into_py(py);
                 let new_value = new.clone().into_py(py);
-                result.set_item(action, "update").unwrap();
-                result.set_item("oldValue", old_value).unwrap();
-                result.set_item("newValue", new_value).unwrap();
+                result.set_item(action, intern!(py, "update")).unwrap();
+                result.set_item(intern!(py, "oldValue"), old_value).unwrap();
+                result.set_item(intern!(py, "newValue"), new_value).unwrap();
             }
             EntryChange::Removed(old) => {
                 let old_value = old.clone().into_py(py);
-                result.set_item(action, "delete").unwrap();
-                result.set_item("oldValue", old_value).unwrap();
+                result.set_item(action, intern!(py, "delete")).unwrap();
+                result.set_item(intern!(py, "oldValue"), old_value).unwrap();
             }
         }
         result.into()
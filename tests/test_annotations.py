@@ -0,0 +1,84 @@
+import pytest
+import y_py as Y
+from test_helper import exchange_updates
+from y_py import Annotations, YDoc
+
+
+def test_annotations():
+    doc = YDoc()
+    text = doc.get_text("text")
+    annotations = Annotations(text, doc.get_map("comments"))
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+        first = annotations.add(txn, 6, 11, "Which world?")
+        second = annotations.add(txn, 0, 5, {"author": "alice"})
+        assert annotations.range_of(txn, first) == (6, 11)
+        assert [a["id"] for a in annotations.list(txn)] == [second, first]
+
+    with doc.begin_transaction() as txn:
+        text.insert(txn, 0, "oh ")
+        text.insert(txn, 12, "wide ")
+        assert annotations.range_of(txn, second) == (3, 8)
+        assert annotations.range_of(txn, first) == (9, 19)
+        text.delete_range(txn, 3, 3)
+        assert annotations.range_of(txn, second) == (3, 5)
+
+    with doc.begin_transaction() as txn:
+        annotations.resolve(txn, first)
+        assert [a["id"] for a in annotations.list(txn)] == [second]
+        listed = annotations.list(txn, include_resolved=True)
+        assert listed[1] == {
+            "id": first,
+            "start": 6,
+            "end": 16,
+            "comment": "Which world?",
+            "resolved": True,
+        }
+        annotations.remove(txn, first)
+        with pytest.raises(KeyError):
+            annotations.range_of(txn, first)
+        with pytest.raises(KeyError):
+            annotations.resolve(txn, first)
+
+
+def test_annotations_deleted_text():
+    doc = YDoc()
+    text = doc.get_text("text")
+    annotations = Annotations(text, doc.get_map("comments"))
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello big world")
+        id = annotations.add(txn, 6, 9, "size")
+    with doc.begin_transaction() as txn:
+        # The range ends after its last character, once the one following it is gone
+        text.delete_range(txn, 9, 1)
+        assert annotations.range_of(txn, id) == (6, 9)
+        text.delete_range(txn, 6, 2)
+        assert annotations.range_of(txn, id) == (6, 7)
+        text.delete_range(txn, 6, 1)
+        assert annotations.range_of(txn, id) == (6, 6)
+
+
+def test_annotations_sync():
+    d1, d2 = YDoc(1), YDoc(2)
+    t1 = d1.get_text("text")
+    a1 = Annotations(t1, d1.get_map("comments"))
+    with d1.begin_transaction() as txn:
+        t1.extend(txn, "hello world")
+        id = a1.add(txn, 0, 5, "greeting")
+    exchange_updates([d1, d2])
+
+    t2 = d2.get_text("text")
+    a2 = Annotations(t2, d2.get_map("comments"))
+    with d2.begin_transaction() as txn:
+        t2.insert(txn, 0, ">> ")
+    exchange_updates([d1, d2])
+    with d1.begin_transaction() as txn:
+        assert a1.range_of(txn, id) == (3, 8)
+    with d2.begin_transaction() as txn:
+        assert a2.list(txn)[0]["comment"] == "greeting"
+
+
+def test_annotations_prelim():
+    doc = YDoc()
+    with pytest.raises(Y.PreliminaryAccessError):
+        Annotations(Y.YText("hello"), doc.get_map("comments"))
@@ -0,0 +1,76 @@
+import pytest
+import y_py as Y
+from y_py import YDoc
+
+
+def synced_docs():
+    local, remote = YDoc(1), YDoc(2)
+    with remote.begin_transaction() as txn:
+        settings = txn.get_map("settings")
+        settings.set(txn, "theme", "light")
+        settings.set(txn, "nested", Y.YMap({"size": 12}))
+    Y.apply_update(local, Y.encode_state_as_update(remote))
+    return local, remote
+
+
+def sync(source, target):
+    Y.apply_update(target, Y.encode_state_as_update(source, Y.encode_state_vector(target)))
+
+
+def test_bind():
+    local, remote = synced_docs()
+    settings = {}
+    binding = Y.bind(local.get_map("settings"), settings)
+    assert binding.target is settings
+    assert settings == {"theme": "light", "nested": {"size": 12}}
+
+    # Remote changes, including changes of nested shared types, are copied to the dict
+    remote_settings = remote.get_map("settings")
+    with remote.begin_transaction() as txn:
+        remote_settings.set(txn, "theme", "dark")
+        remote_settings["nested"].set(txn, "size", 14)
+    sync(remote, local)
+    assert settings == {"theme": "dark", "nested": {"size": 14}}
+
+    # Local changes are written back as a diff
+    settings["font"] = "mono"
+    del settings["theme"]
+    assert sorted(binding.flush_local_changes()) == ["font", "theme"]
+    assert binding.flush_local_changes() == []
+    sync(local, remote)
+    assert remote_settings.to_dict(deep=True) == {"font": "mono", "nested": {"size": 14}}
+
+    with local.begin_transaction() as txn:
+        settings["font"] = "serif"
+        assert binding.flush_local_changes(txn) == ["font"]
+    assert local.get_map("settings")["font"] == "serif"
+
+    binding.unbind()
+    with remote.begin_transaction() as txn:
+        remote_settings.set(txn, "theme", "light")
+    sync(remote, local)
+    assert "theme" not in settings
+
+
+@pytest.mark.parametrize("conflict", ["crdt-wins", "local-wins"])
+def test_bind_conflicts(conflict):
+    local, remote = synced_docs()
+    settings = {}
+    binding = Y.bind(local.get_map("settings"), settings, conflict=conflict)
+    settings["theme"] = "blue"
+    with remote.begin_transaction() as txn:
+        remote.get_map("settings").set(txn, "theme", "dark")
+    sync(remote, local)
+
+    binding.flush_local_changes()
+    expected = "dark" if conflict == "crdt-wins" else "blue"
+    assert settings["theme"] == expected
+    assert local.get_map("settings")["theme"] == expected
+
+
+def test_bind_invalid():
+    doc = YDoc()
+    with pytest.raises(ValueError):
+        Y.bind(doc.get_map("settings"), {}, conflict="last-wins")
+    with pytest.raises(Y.PreliminaryObservationException):
+        Y.bind(Y.YMap({}), {})
@@ -0,0 +1,20 @@
+import re
+from pathlib import Path
+
+import y_py as Y
+
+MANIFEST = Path(__file__).parent.parent / "Cargo.toml"
+
+
+def test_versions():
+    versions = Y.versions()
+    manifest = MANIFEST.read_text()
+    package = re.search(r'^version = "(.+)"', manifest, re.MULTILINE).group(1)
+    assert versions["y_py"] == package
+    for crate in ["yrs", "lib0"]:
+        dependency = re.search(rf'^{crate} = "(.+)"', manifest, re.MULTILINE).group(1)
+        assert versions[crate] == dependency
+
+    assert versions["update_encodings"] == [1]
+    assert "sync" in versions["protocol_messages"]
+    assert versions["mmap_format"] == 1
@@ -0,0 +1,85 @@
+import pytest
+import y_py as Y
+from y_py import YDoc, copy_shared
+
+
+def make_page():
+    doc = YDoc()
+    page = doc.get_map("page")
+    with doc.begin_transaction() as txn:
+        page.update(
+            txn,
+            {
+                "title": Y.YText("Notes"),
+                "cells": Y.YArray([Y.YMap({"source": Y.YText("print(1)")}), 2]),
+                "meta": {"tags": ["a"]},
+            },
+        )
+    return doc, page
+
+
+def test_copy_into_document_root():
+    _, page = make_page()
+    other = YDoc()
+    copy = copy_shared(page, other, "page")
+    assert isinstance(copy, Y.YMap)
+    assert copy.to_dict(deep=True) == page.to_dict(deep=True)
+
+    # The copy is independent from its source
+    cells = copy["cells"]
+    with other.begin_transaction() as txn:
+        cells.append(txn, 3)
+        copy["title"].extend(txn, "!")
+    assert len(page["cells"]) == 2
+    assert str(page["title"]) == "Notes"
+
+
+def test_copy_into_nested_types():
+    _, page = make_page()
+    notebook = YDoc()
+    pages = notebook.get_array("pages")
+    index = notebook.get_map("index")
+    with notebook.begin_transaction() as txn:
+        first = copy_shared(page, pages, txn=txn)
+        second = copy_shared(page["title"], index, "title", txn)
+        copy_shared(page["cells"], pages, 0, txn)
+    assert first.to_dict(deep=True) == page.to_dict(deep=True)
+    assert str(second) == "Notes"
+    assert pages.to_list(deep=True) == [
+        page["cells"].to_list(deep=True),
+        page.to_dict(deep=True),
+    ]
+    assert Y.encode_state_as_update(notebook) != Y.encode_state_as_update(page.doc)
+
+
+def test_copy_xml():
+    source = YDoc()
+    xml = source.get_xml_element("xml")
+    with source.begin_transaction() as txn:
+        paragraph = xml.push_xml_element(txn, "p")
+        paragraph.set_attribute(txn, "class", "lead")
+        paragraph.push_xml_text(txn).push(txn, "hello")
+
+    target = YDoc()
+    root = copy_shared(xml, target, "xml")
+    assert str(root) == str(xml)
+    with target.begin_transaction() as txn:
+        copy_shared(paragraph, root, 0, txn)
+        with pytest.raises(ValueError):
+            copy_shared(root, root.first_child, txn=txn)
+    assert str(root.first_child) == str(paragraph)
+    assert len(root) == 2
+
+
+def test_invalid_copies():
+    _, page = make_page()
+    other = YDoc()
+    with pytest.raises(TypeError):
+        copy_shared(page, other)
+    with pytest.raises(TypeError):
+        copy_shared(page, other.get_array("array"))
+    with pytest.raises(TypeError):
+        copy_shared({}, other, "page")
+    other.get_text("text")
+    with pytest.raises(TypeError):
+        copy_shared(page, other, "text")
@@ -0,0 +1,32 @@
+import pytest
+import y_py as Y
+from test_helper import exchange_updates
+from y_py import YCounter, YDoc
+
+
+def test_counter():
+    doc = YDoc()
+    counter = YCounter(doc.get_map("counter"))
+    with doc.begin_transaction() as txn:
+        assert counter.value(txn) == 0
+        assert counter.increment(txn) == 1
+        assert counter.increment(txn, 5) == 6
+        assert counter.increment(txn, -2) == 4
+        assert counter.value(txn) == 4
+
+
+def test_counter_concurrent_increments():
+    docs = [YDoc(1), YDoc(2), YDoc(3)]
+    counters = [YCounter(doc.get_map("counter")) for doc in docs]
+    for i, (doc, counter) in enumerate(zip(docs, counters)):
+        with doc.begin_transaction() as txn:
+            counter.increment(txn, i + 1)
+    exchange_updates(docs)
+    for doc, counter in zip(docs, counters):
+        with doc.begin_transaction() as txn:
+            assert counter.value(txn) == 6
+
+
+def test_counter_prelim():
+    with pytest.raises(Y.PreliminaryAccessError):
+        YCounter(Y.YMap({}))
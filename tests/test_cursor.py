@@ -0,0 +1,102 @@
+import pytest
+import y_py as Y
+from test_helper import exchange_updates
+from y_py import Cursor, YDoc
+
+
+def test_follows_local_edits():
+    doc = YDoc()
+    text = doc.get_text("text")
+    cursor = Cursor(text)
+    with doc.begin_transaction() as txn:
+        assert cursor.range(txn) == (0, 0)
+        text.extend(txn, "hello world")
+        assert cursor.range(txn) == (0, 0)
+        cursor.set(txn, 6, 11)
+        assert cursor.range(txn) == (6, 11)
+
+    with doc.begin_transaction() as txn:
+        # Text inserted at the cursor is placed before it
+        text.insert(txn, 6, "big ")
+        assert cursor.range(txn) == (10, 15)
+        text.delete_range(txn, 10, 3)
+        assert cursor.range(txn) == (10, 12)
+        cursor.set(txn, 3)
+        assert cursor.range(txn) == (3, 3)
+        cursor.set(txn, 12, 100)
+        assert cursor.range(txn) == (12, 12)
+
+
+def test_follows_remote_edits():
+    d1 = YDoc(1)
+    d2 = YDoc(2)
+    t1 = d1.get_text("text")
+    t2 = d2.get_text("text")
+    with d1.begin_transaction() as txn:
+        t1.extend(txn, "hello world")
+    exchange_updates([d1, d2])
+
+    cursor = Cursor(t2)
+    with d2.begin_transaction() as txn:
+        cursor.set(txn, 5, 0)
+    with d1.begin_transaction() as txn:
+        t1.insert(txn, 0, "oh, ")
+        t1.delete_range(txn, 9, 6)
+    exchange_updates([d1, d2])
+    with d2.begin_transaction() as txn:
+        assert str(t2) == "oh, hello"
+        assert cursor.range(txn) == (9, 4)
+
+
+def test_offset_kind():
+    doc = YDoc(offset_kind="utf16")
+    text = doc.get_text("text")
+    cursor = Cursor(text)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "😀 hi")
+        cursor.set(txn, 3)
+        text.insert(txn, 0, "ab")
+        assert cursor.range(txn) == (5, 5)
+
+
+def test_embed():
+    doc = YDoc()
+    text = doc.get_text("text")
+    cursor = Cursor(text)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "ab")
+        text.insert_embed(txn, 1, {"image": "x.png"})
+        cursor.set(txn, 1, 2)
+        text.insert(txn, 0, "12")
+        assert cursor.range(txn) == (3, 4)
+
+
+def test_deleted_embed():
+    doc = YDoc()
+    text = doc.get_text("text")
+    cursor = Cursor(text)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "abc")
+        text.insert_embed(txn, 1, {"image": "x.png"})
+        cursor.set(txn, 3)
+        text.delete_range(txn, 1, 1)
+        assert cursor.range(txn) == (2, 2)
+
+
+def test_other_types_intact():
+    doc = YDoc()
+    text = doc.get_text("text")
+    map = doc.get_map("map")
+    cursor = Cursor(text)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+        map.set(txn, "key", "a")
+    with doc.begin_transaction() as txn:
+        map.set(txn, "key", "b")
+        cursor.set(txn, 2)
+        assert map.to_json() == '{"key":"b"}'
+
+
+def test_prelim():
+    with pytest.raises(Y.PreliminaryAccessError):
+        Cursor(Y.YText("text"))
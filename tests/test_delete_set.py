@@ -0,0 +1,54 @@
+import pytest
+import y_py as Y
+from y_py import DeleteSet, YDoc
+
+
+def test_ranges():
+    ds = DeleteSet({1: [(5, 2), (0, 3), (3, 1)], 2: [(4, 0)]})
+    assert ds[1] == [(0, 4), (5, 2)]
+    assert ds[2] == []
+    assert 1 in ds
+    assert 2 not in ds
+    assert len(ds) == 1
+    assert list(ds) == [1]
+    assert ds.is_deleted(1, 3)
+    assert not ds.is_deleted(1, 4)
+    assert not ds.is_deleted(1, 2, 4)
+
+    ds.add(1, 4, 1)
+    assert ds.items() == [(1, [(0, 7)])]
+    assert ds.is_deleted(1, 0, 7)
+    assert repr(ds) == "DeleteSet({1: [(0, 7)]})"
+    with pytest.raises(ValueError):
+        ds.add(1, 2**64 - 1, 2)
+
+
+def test_encode_decode():
+    ds = DeleteSet({1: [(0, 3)], 7: [(10, 5), (20, 1)]})
+    assert DeleteSet.decode(ds.encode()) == ds
+    assert DeleteSet.decode(bytearray(ds.encode())) == ds
+    assert ds != DeleteSet()
+    with pytest.raises(Exception):
+        DeleteSet.decode(b"\x01\x01")
+
+
+def test_from_transactions_and_updates():
+    doc = YDoc(1)
+    text = doc.get_text("text")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+    assert DeleteSet.from_update(Y.encode_state_as_update(doc)) == DeleteSet()
+
+    events = []
+    doc.observe_after_transaction(lambda e: events.append(e.delete_set))
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 0, 6)
+        assert txn.delete_set() == DeleteSet({1: [(0, 6)]})
+    assert DeleteSet.decode(events[-1]) == DeleteSet({1: [(0, 6)]})
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 2, 3)
+        assert txn.delete_set() == DeleteSet({1: [(8, 3)]})
+
+    deleted = DeleteSet.from_update(Y.encode_state_as_update(doc))
+    assert deleted == DeleteSet.decode(events[0]).merge(DeleteSet.decode(events[1]))
+    assert deleted.items() == [(1, [(0, 6), (8, 3)])]
@@ -0,0 +1,71 @@
+import threading
+
+import y_py as Y
+from y_py import Dispatcher, YDoc
+
+
+def test_dispatcher():
+    doc = YDoc()
+    threads = []
+    updates = []
+
+    def callback(record):
+        threads.append(threading.get_ident())
+        updates.append(record.get_update())
+
+    with Dispatcher() as dispatcher:
+        doc.observe_after_transaction(callback, dispatcher)
+        with doc.begin_transaction() as txn:
+            doc.get_text("text").extend(txn, "hello")
+        dispatcher.wait()
+        assert dispatcher.pending == 0
+        assert threads and threads[0] != threading.get_ident()
+
+    remote = YDoc()
+    for update in updates:
+        Y.apply_update(remote, update)
+    assert str(remote.get_text("text")) == "hello"
+
+
+def test_dispatcher_record():
+    doc = YDoc()
+    text = doc.get_text("text")
+    records = []
+    with Dispatcher() as dispatcher:
+        doc.observe_after_transaction(records.append, dispatcher)
+        with doc.begin_transaction() as txn:
+            text.extend(txn, "hello")
+        with doc.begin_transaction() as txn:
+            text.delete_range(txn, 0, 2)
+    # Records remain valid after their transactions have been committed
+    assert records[1].before_state == records[0].after_state
+    assert records[1].after_state == Y.encode_state_vector(doc)
+    assert Y.DeleteSet.decode(records[1].delete_set) == Y.DeleteSet({doc.client_id: [(0, 2)]})
+
+
+def test_dispatcher_errors():
+    errors = []
+    doc = YDoc()
+    text = doc.get_text("text")
+
+    def callback(_):
+        raise ValueError("failed")
+
+    with Dispatcher(errors.append) as dispatcher:
+        doc.observe_after_transaction(callback, dispatcher)
+        with doc.begin_transaction() as txn:
+            text.extend(txn, "hello")
+    assert len(errors) == 1
+    assert isinstance(errors[0], ValueError)
+
+
+def test_dispatcher_closed():
+    doc = YDoc()
+    text = doc.get_text("text")
+    threads = []
+    dispatcher = Dispatcher()
+    doc.observe_after_transaction(lambda _: threads.append(threading.get_ident()), dispatcher)
+    dispatcher.close()
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    assert threads == [threading.get_ident()]
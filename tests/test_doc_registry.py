@@ -0,0 +1,95 @@
+import pytest
+import y_py as Y
+from y_py import DocRegistry, YDoc
+
+
+def test_lazy_load_and_unload():
+    source = YDoc()
+    with source.begin_transaction() as txn:
+        txn.get_map("root").set(txn, "key", "value")
+    storage = {"room": Y.encode_state_as_update(source)}
+    registry = DocRegistry(load=storage.get, save=storage.__setitem__)
+    assert "room" not in registry
+
+    doc = registry.acquire("room")
+    assert doc.get_map("root")["key"] == "value"
+    assert registry.acquire("room") is doc
+    assert registry.refcount("room") == 2
+    assert registry.names() == ["room"]
+
+    root = doc.get_map("root")
+    with doc.begin_transaction() as txn:
+        root.set(txn, "key", "changed")
+    registry.release("room")
+    assert registry.unload_idle() == []
+    registry.release("room")
+    assert registry.unload_idle() == ["room"]
+    assert len(registry) == 0
+
+    restored = registry.acquire("room")
+    assert restored is not doc
+    assert restored.get_map("root")["key"] == "changed"
+
+    new = registry.acquire("new")
+    assert len(new.get_map("root")) == 0
+
+
+def test_idle_timeout():
+    registry = DocRegistry(idle_timeout=3600)
+    registry.acquire("room")
+    registry.release("room")
+    assert registry.unload_idle() == []
+    assert "room" in registry
+    registry.unload("room")
+    assert "room" not in registry
+
+    with pytest.raises(ValueError):
+        DocRegistry(idle_timeout=-1)
+
+
+def test_reference_errors():
+    registry = DocRegistry()
+    with pytest.raises(KeyError):
+        registry.release("room")
+    registry.acquire("room")
+    with pytest.raises(ValueError):
+        registry.unload("room")
+    registry.release("room")
+    with pytest.raises(ValueError):
+        registry.release("room")
+
+
+def test_observe_updates():
+    registry = DocRegistry()
+    updates = []
+    room_updates = []
+    registry.observe(lambda name, update: updates.append(name))
+    sub = registry.observe(lambda name, update: room_updates.append(update), "room")
+
+    room = registry.acquire("room")
+    other = registry.acquire("other")
+    room_text = room.get_text("text")
+    other_text = other.get_text("text")
+    with room.begin_transaction() as txn:
+        room_text.extend(txn, "hello")
+    with other.begin_transaction() as txn:
+        other_text.extend(txn, "world")
+    assert updates == ["room", "other"]
+    assert len(room_updates) == 1
+
+    replica = YDoc()
+    Y.apply_update(replica, room_updates[0])
+    assert str(replica.get_text("text")) == "hello"
+
+    registry.unobserve(sub)
+    with room.begin_transaction() as txn:
+        room_text.extend(txn, "!")
+    assert len(room_updates) == 1
+    assert updates == ["room", "other", "room"]
+
+    # Unloaded documents no longer report updates
+    registry.release("other")
+    registry.unload("other")
+    with other.begin_transaction() as txn:
+        other_text.extend(txn, "!")
+    assert updates == ["room", "other", "room"]
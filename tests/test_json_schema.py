@@ -0,0 +1,113 @@
+import pytest
+from y_py import ValidationError, YArray, YDoc, YMap, YText
+
+TASKS = {
+    "type": "array",
+    "maxItems": 3,
+    "items": {
+        "type": "object",
+        "properties": {
+            "title": {"type": "string", "minLength": 1},
+            "done": {"type": "boolean"},
+            "priority": {"type": "integer", "minimum": 1, "maximum": 5},
+            "tags": {"type": "array", "items": {"enum": ["docs", "bug"]}},
+        },
+        "required": ["title"],
+        "additionalProperties": False,
+    },
+}
+
+
+def test_valid_inserts():
+    doc = YDoc()
+    doc.set_schema("tasks", TASKS)
+    tasks = doc.get_array("tasks")
+    with doc.begin_transaction() as txn:
+        tasks.append(txn, {"title": "Write docs", "done": False, "tags": ["docs"]})
+        task = tasks.append(txn, YMap({"title": YText("Fix bug"), "priority": 2}))
+        task.set(txn, "tags", YArray(["bug"]))
+        task["tags"].append(txn, "docs")
+    assert len(tasks) == 2
+
+
+def test_invalid_inserts():
+    doc = YDoc()
+    doc.set_schema("tasks", TASKS)
+    tasks = doc.get_array("tasks")
+    with doc.begin_transaction() as txn:
+        task = tasks.append(txn, YMap({"title": "Write docs"}))
+        with pytest.raises(ValidationError, match=r"tasks\[1\]: missing required property 'title'"):
+            tasks.append(txn, {"done": True})
+        with pytest.raises(ValidationError, match=r"tasks\[0\].priority: expected a number <= 5"):
+            task.set(txn, "priority", 7)
+        with pytest.raises(ValidationError, match=r"tasks\[1\].title: expected string, found int"):
+            tasks.insert(txn, 1, YMap({"title": 1}))
+        with pytest.raises(ValidationError, match=r"tasks\[1\].owner: no value is allowed here"):
+            tasks.extend(txn, [{"title": "Review", "owner": "me"}])
+        with pytest.raises(ValidationError, match="expected at most 3 items"):
+            tasks.extend(txn, [{"title": "a"}, {"title": "b"}, {"title": "c"}])
+        assert len(tasks) == 1
+
+        nested = tasks.append(txn, YMap({"title": "Nested"}))
+        with pytest.raises(ValidationError, match=r"tasks\[1\].tags\[0\]: value is not one"):
+            nested.set(txn, "tags", ["feature"])
+        with pytest.raises(ValidationError, match=r"tasks\[1\]: missing required property 'title'"):
+            nested.pop(txn, "title")
+        assert nested.to_dict() == {"title": "Nested"}
+
+
+def test_removals():
+    doc = YDoc()
+    doc.set_schema("tags", {"type": "array", "minItems": 1})
+    tags = doc.get_array("tags")
+    with doc.begin_transaction() as txn:
+        tags.extend(txn, ["a", "b"])
+        tags.delete(txn, 0)
+        with pytest.raises(ValidationError, match="expected at least 1 items, found 0"):
+            tags.delete(txn, 0)
+    assert list(tags) == ["b"]
+
+
+def test_existing_content_and_detaching():
+    doc = YDoc()
+    settings = doc.get_map("settings")
+    with doc.begin_transaction() as txn:
+        settings.set(txn, "theme", "dark")
+
+    schema = {"type": "object", "properties": {"theme": {"const": "light"}}}
+    with pytest.raises(ValidationError, match="settings.theme"):
+        doc.set_schema("settings", schema)
+
+    doc.set_schema("settings", {"properties": {"theme": {"enum": ["dark", "light"]}}})
+    with doc.begin_transaction() as txn:
+        with pytest.raises(ValidationError):
+            settings.set(txn, "theme", "blue")
+        with pytest.raises(ValidationError):
+            settings.update(txn, {"theme": "blue"})
+        with pytest.raises(ValidationError):
+            settings.insert_json(txn, "theme", '"blue"')
+    doc.set_schema("settings", None)
+    with doc.begin_transaction() as txn:
+        settings.set(txn, "theme", "blue")
+    assert settings["theme"] == "blue"
+
+
+def test_invalid_schemas():
+    doc = YDoc()
+    with pytest.raises(ValueError, match="'#/type': unknown type"):
+        doc.set_schema("tasks", {"type": "list"})
+    with pytest.raises(ValueError, match="unsupported keyword 'pattern'"):
+        doc.set_schema("tasks", {"items": {"pattern": "^a"}})
+    with pytest.raises(ValueError, match="'#/maxItems': expected a non-negative integer"):
+        doc.set_schema("tasks", {"maxItems": -1})
+
+
+def test_transaction_root_types():
+    doc = YDoc()
+    doc.set_schema("tasks", {"type": "array", "items": {"type": "string"}})
+    with doc.begin_transaction() as txn:
+        tasks = txn.get_array("tasks")
+        tasks.append(txn, "a")
+        with pytest.raises(ValidationError, match=r"tasks\[1\]: expected string, found int"):
+            tasks.append(txn, 42)
+    assert list(doc.get_array("tasks")) == ["a"]
@@ -0,0 +1,84 @@
+import pytest
+from y_py import Awareness, LocalHub, YDoc
+
+
+def test_propagation():
+    hub = LocalHub()
+    a = YDoc(1)
+    with a.begin_transaction() as txn:
+        a.get_text("text").extend(txn, "hello")
+    b = YDoc(2)
+    hub.join(a)
+    hub.join(b)
+    assert str(b.get_text("text")) == "hello"
+    assert hub.docs == [a, b]
+
+    text = b.get_text("text")
+    origins = []
+    b.observe_after_transaction(lambda e: origins.append(e.origin))
+    with a.begin_transaction() as txn:
+        a.get_text("text").extend(txn, " world")
+    assert str(text) == "hello world"
+    assert origins == [hub]
+
+    c = YDoc(3)
+    hub.join(c)
+    with c.begin_transaction() as txn:
+        c.get_text("text").insert(txn, 0, ">")
+    assert str(a.get_text("text")) == ">hello world"
+    assert str(b.get_text("text")) == ">hello world"
+
+    hub.leave(c)
+    with c.begin_transaction() as txn:
+        c.get_text("text").extend(txn, "!")
+    assert str(a.get_text("text")) == ">hello world"
+    with pytest.raises(ValueError):
+        hub.leave(c)
+    with pytest.raises(ValueError):
+        hub.join(a)
+
+
+def test_latency():
+    hub = LocalHub(latency=0.5)
+    a, b = YDoc(1), YDoc(2)
+    hub.join(a)
+    hub.join(b)
+    # States exchanged by joining documents are delayed as well
+    assert hub.pending == 2
+    assert hub.flush() == 2
+    with a.begin_transaction() as txn:
+        a.get_text("text").extend(txn, "a")
+    with b.begin_transaction() as txn:
+        b.get_text("text").extend(txn, "b")
+    assert hub.pending == 2
+    assert hub.advance(0.2) == 0
+    assert str(a.get_text("text")) == "a"
+    assert hub.advance(0.3) == 2
+    assert hub.time == pytest.approx(1.0)
+    assert str(a.get_text("text")) == str(b.get_text("text"))
+
+    with a.begin_transaction() as txn:
+        a.get_text("text").extend(txn, "!")
+    assert hub.flush() == 1
+    assert hub.pending == 0
+    assert str(a.get_text("text")) == str(b.get_text("text"))
+
+    with pytest.raises(ValueError):
+        LocalHub(latency=-1)
+
+
+def test_awareness():
+    hub = LocalHub()
+    a, b = YDoc(1), YDoc(2)
+    awareness_a, awareness_b = Awareness(a), Awareness(b)
+    awareness_a.set_local_state({"user": "alice"})
+    hub.join(a, awareness_a)
+    hub.join(b, awareness_b)
+    assert awareness_b.get_states() == {1: {"user": "alice"}}
+
+    awareness_b.set_local_state({"user": "bob"})
+    assert awareness_a.get_states() == {1: {"user": "alice"}, 2: {"user": "bob"}}
+
+    hub.leave(b)
+    awareness_b.set_local_state(None)
+    assert 2 in awareness_a.get_states()
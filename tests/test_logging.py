@@ -0,0 +1,89 @@
+import logging
+from contextlib import suppress
+
+import pytest
+import y_py as Y
+from y_py import YDoc
+
+
+@pytest.fixture
+def records():
+    records = []
+    Y.set_log_handler(records.append)
+    yield records
+    Y.set_log_handler(None)
+
+
+def events(records):
+    return [record["event"] for record in records]
+
+
+def test_transaction_committed(records):
+    doc = YDoc()
+    text = doc.get_text("text")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 0, 2)
+    # Transactions which don't change the document aren't logged
+    with doc.begin_transaction() as txn:
+        str(text)
+    assert records == [
+        {"event": "transaction_committed", "level": logging.DEBUG, "inserted": 5, "deleted": 0},
+        {"event": "transaction_committed", "level": logging.DEBUG, "inserted": 0, "deleted": 2},
+    ]
+
+
+def test_update_applied(records):
+    remote = YDoc()
+    with remote.begin_transaction() as txn:
+        remote.get_text("text").extend(txn, "hello")
+    update = Y.encode_state_as_update(remote)
+    records.clear()
+
+    Y.apply_update(YDoc(), update)
+    applied = [r for r in records if r["event"] == "update_applied"]
+    assert applied == [{"event": "update_applied", "level": logging.DEBUG, "size": len(update)}]
+
+    with pytest.raises(Y.EncodingError):
+        Y.apply_update(YDoc(), b"\xff\xff")
+    rejected = [r for r in records if r["event"] == "update_rejected"]
+    assert len(rejected) == 1
+    assert rejected[0]["level"] == logging.WARNING
+    assert rejected[0]["reason"]
+
+
+def test_observer_error(records):
+    doc = YDoc()
+    text = doc.get_text("text")
+
+    def callback(_):
+        raise ValueError("failed")
+
+    text.observe(callback)
+    # The exception is raised again once the transaction returns to Python
+    with suppress(Exception):
+        with doc.begin_transaction() as txn:
+            text.extend(txn, "hello")
+    errors = [r for r in records if r["event"] == "observer_error"]
+    assert len(errors) == 1
+    assert errors[0]["level"] == logging.ERROR
+    assert isinstance(errors[0]["exception"], ValueError)
+
+
+def test_gc_run(records):
+    doc = YDoc(7)
+    doc.compact()
+    assert records[-1]["event"] == "gc_run"
+    assert records[-1]["client_id"] == 7
+    assert records[-1]["level"] == logging.INFO
+
+
+def test_remove_handler():
+    records = []
+    Y.set_log_handler(records.append)
+    Y.set_log_handler(None)
+    doc = YDoc()
+    with doc.begin_transaction() as txn:
+        doc.get_text("text").extend(txn, "hello")
+    assert records == []
@@ -0,0 +1,108 @@
+import y_py as Y
+from y_py import YDoc
+
+
+def replicas():
+    base = YDoc(1)
+    with base.begin_transaction() as txn:
+        txn.get_text("content").extend(txn, "hello world")
+        meta = txn.get_map("meta")
+        meta.set(txn, "title", "Draft")
+        meta.set(txn, "notes", Y.YText("abc"))
+        txn.get_array("items").extend(txn, [1, 2, 3])
+    update = Y.encode_state_as_update(base)
+    a, b = YDoc(2), YDoc(3)
+    Y.apply_update(a, update)
+    Y.apply_update(b, update)
+    return a, b
+
+
+def merge(a, b):
+    merged = YDoc(4)
+    Y.apply_update(merged, Y.encode_state_as_update(a))
+    Y.apply_update(merged, Y.encode_state_as_update(b))
+    return merged
+
+
+def test_overlapping_edits():
+    a, b = replicas()
+    with a.begin_transaction() as txn:
+        a.get_text("content").insert(txn, 6, "big ")
+        a.get_map("meta").set(txn, "title", "A")
+        a.get_map("meta")["notes"].extend(txn, "x")
+    with b.begin_transaction() as txn:
+        b.get_text("content").insert(txn, 6, "small ")
+        b.get_map("meta").set(txn, "title", "B")
+        b.get_map("meta").set(txn, "author", "Bob")
+        b.get_map("meta")["notes"].extend(txn, "y")
+        b.get_array("items").append(txn, 4)
+
+    report = Y.explain_merge(Y.encode_state_as_update(a), Y.encode_state_as_update(b))
+    edits = {(edit["kind"], tuple(edit["path"])): edit for edit in report}
+    assert set(edits) == {
+        ("text", ("content",)),
+        ("map", ("meta",)),
+        ("text", ("meta", "notes")),
+    }
+
+    merged = merge(a, b)
+    content = str(merged.get_text("content"))
+    text = edits[("text", ("content",))]
+    assert content[text["a"][0] : text["a"][1]] == "big "
+    assert content[text["b"][0] : text["b"][1]] == "small "
+
+    title = edits[("map", ("meta",))]
+    assert title["key"] == "title"
+    assert title["winner"] == ("a" if merged.get_map("meta")["title"] == "A" else "b")
+
+
+def test_disjoint_edits():
+    a, b = replicas()
+    with a.begin_transaction() as txn:
+        a.get_text("content").insert(txn, 0, "Oh, ")
+        a.get_map("meta").set(txn, "title", "A")
+    with b.begin_transaction() as txn:
+        b.get_text("content").extend(txn, "!")
+        b.get_array("items").delete(txn, 0)
+
+    report = Y.explain_merge(Y.encode_state_as_update(a), Y.encode_state_as_update(b))
+    assert report == []
+
+
+def test_removed_entry():
+    a, b = replicas()
+    with a.begin_transaction() as txn:
+        a.get_map("meta").set(txn, "title", "A")
+    with a.begin_transaction() as txn:
+        a.get_map("meta").pop(txn, "title")
+    with b.begin_transaction() as txn:
+        b.get_map("meta").set(txn, "title", "B")
+
+    report = Y.explain_merge(Y.encode_state_as_update(a), Y.encode_state_as_update(b))
+    assert len(report) == 1
+    assert report[0]["key"] == "title"
+    merged = merge(a, b)
+    meta = merged.get_map("meta")
+    assert report[0]["winner"] == ("b" if "title" in meta else None)
+
+
+def test_nested_map_entry():
+    base = YDoc(1)
+    with base.begin_transaction() as txn:
+        txn.get_array("rows").extend(txn, ["header", Y.YMap({"state": "new"})])
+    update = Y.encode_state_as_update(base)
+    a, b = YDoc(2), YDoc(3)
+    Y.apply_update(a, update)
+    Y.apply_update(b, update)
+    with a.begin_transaction() as txn:
+        a.get_array("rows")[1].set(txn, "state", "done")
+    with b.begin_transaction() as txn:
+        b.get_array("rows")[1].set(txn, "state", "rejected")
+
+    report = Y.explain_merge(Y.encode_state_as_update(a), Y.encode_state_as_update(b))
+    assert len(report) == 1
+    assert report[0]["kind"] == "map"
+    assert report[0]["path"] == ["rows", 1]
+    assert report[0]["key"] == "state"
+    state = merge(a, b).get_array("rows")[1]["state"]
+    assert report[0]["winner"] == ("a" if state == "done" else "b")
@@ -0,0 +1,44 @@
+import pytest
+import y_py as Y
+from y_py import YDoc
+
+
+@pytest.fixture
+def metrics():
+    Y.reset_metrics()
+    Y.enable_metrics()
+    yield
+    Y.disable_metrics()
+    Y.reset_metrics()
+
+
+def test_metrics(metrics):
+    doc = YDoc()
+    array = doc.get_array("array")
+    events = []
+    array.observe(events.append)
+    with doc.begin_transaction() as txn:
+        array.append(txn, {"key": "value"})
+    assert array[0] == {"key": "value"}
+    remote = YDoc()
+    Y.apply_update(remote, Y.encode_state_as_update(doc))
+
+    collected = Y.get_metrics()
+    assert set(collected) == {"transactions", "conversions", "updates", "callbacks"}
+    assert collected["callbacks"]["count"] == 1
+    assert collected["updates"]["count"] == 1
+    assert collected["transactions"]["count"] >= 2
+    assert collected["conversions"]["count"] >= 2
+    for metric in collected.values():
+        assert 0 <= metric["max_time"] <= metric["total_time"]
+
+    Y.reset_metrics()
+    assert all(metric["count"] == 0 for metric in Y.get_metrics().values())
+
+
+def test_disabled_metrics(metrics):
+    Y.disable_metrics()
+    doc = YDoc()
+    with doc.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, "hello")
+    assert all(metric["count"] == 0 for metric in Y.get_metrics().values())
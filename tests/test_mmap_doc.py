@@ -0,0 +1,44 @@
+import pytest
+from y_py import MmapDoc
+
+pytest.importorskip("fcntl")
+
+
+def test_mmap_doc(tmp_path):
+    path = str(tmp_path / "doc.ypy")
+    first = MmapDoc(path, capacity=4096, client_id=1)
+    second = MmapDoc(path, client_id=2)
+    assert second.capacity == 4096
+
+    text = first.doc.get_text("text")
+    first.transact(lambda txn: text.extend(txn, "hello"))
+    assert second.sync()
+    assert str(second.doc.get_text("text")) == "hello"
+    assert not second.sync()
+
+    other = second.doc.get_text("text")
+    assert second.transact(lambda txn: other.extend(txn, " world")) is None
+    assert first.sync()
+    assert str(text) == "hello world"
+
+    # Changes made outside of `transact` are stored by `sync`
+    with first.doc.begin_transaction() as txn:
+        first.doc.get_map("map").set(txn, "key", "value")
+    assert not first.sync()
+    with MmapDoc(path, client_id=3) as third:
+        assert third.doc.get_map("map")["key"] == "value"
+        assert str(third.doc.get_text("text")) == "hello world"
+
+    first.close()
+    with pytest.raises(RuntimeError):
+        first.sync()
+    assert str(text) == "hello world"
+    second.close()
+
+
+def test_mmap_doc_capacity(tmp_path):
+    shared = MmapDoc(str(tmp_path / "doc.ypy"), capacity=64)
+    text = shared.doc.get_text("text")
+    with pytest.raises(ValueError):
+        shared.transact(lambda txn: text.extend(txn, "x" * 100))
+    shared.close()
@@ -0,0 +1,62 @@
+import pytest
+import y_py as Y
+from y_py import YArray, YDoc
+
+
+def delete_out_of_range():
+    doc = YDoc()
+    array = YArray([1, 2])
+    with doc.begin_transaction() as txn:
+        with pytest.raises(Y.InternalError, match="out of range") as info:
+            array.delete_range(txn, 1, 5)
+    return info.value
+
+
+def test_panic_raises_internal_error():
+    error = delete_out_of_range()
+    assert isinstance(error, Y.YError)
+    # The location of the panic is included
+    assert ".rs:" in str(error)
+
+
+def test_set_panic_hook(capfd):
+    delete_out_of_range()
+    assert "panicked" not in capfd.readouterr().err
+
+    Y.set_panic_hook(True)
+    try:
+        delete_out_of_range()
+    finally:
+        Y.set_panic_hook(False)
+    assert "panicked" in capfd.readouterr().err
+
+
+def test_xml_panic_raises_internal_error():
+    doc = YDoc()
+    element = doc.get_xml_element("body")
+    with doc.begin_transaction() as txn:
+        element.push_xml_text(txn)
+        with pytest.raises(Y.InternalError, match="Couldn't remove"):
+            element.delete(txn, 3, 2)
+
+
+def test_update_panic_raises_encoding_error(capfd):
+    doc = YDoc()
+    with pytest.raises(Y.EncodingError, match="could not be decoded"):
+        Y.apply_update(doc, b"\x01\x01\x01\x00\x28\x01\x01x\x01\x77\x01a\x00")
+    assert "panicked" not in capfd.readouterr().err
+
+
+def test_untyped_root_types_are_skipped_silently(capfd):
+    remote = YDoc()
+    with remote.begin_transaction() as txn:
+        remote.get_map("settings").set(txn, "theme", "dark")
+    doc = YDoc()
+    Y.apply_update(doc, Y.encode_state_as_update(remote))
+
+    Y.set_panic_hook(True)
+    try:
+        assert "settings" in dir(doc.root)
+    finally:
+        Y.set_panic_hook(False)
+    assert "panicked" not in capfd.readouterr().err
@@ -0,0 +1,81 @@
+import time
+
+import pytest
+from y_py import Awareness, Presence, YDoc
+
+
+def test_join_and_leave():
+    alice = Awareness(YDoc(1))
+    bob = Awareness(YDoc(2))
+    presence = Presence(alice, {"user": "alice"})
+    bob_presence = Presence(bob, {"user": "bob"})
+    assert presence.ttl == 30
+    joined = []
+    left = []
+    presence.on_join(lambda client, state: joined.append((client, state)))
+    subscription_id = presence.on_leave(left.append)
+
+    alice.apply_update(bob.encode_update())
+    assert joined == [(2, {"user": "bob"})]
+    assert presence.peers() == {2: {"user": "bob"}}
+    bob_presence.set_state({"user": "bob", "cursor": 1})
+    alice.apply_update(bob.encode_update())
+    assert joined == [(2, {"user": "bob"})]
+    assert presence.peers() == {2: {"user": "bob", "cursor": 1}}
+
+    # Closing a presence removes the local state, which is propagated as a leave
+    bob_presence.close()
+    assert bob.get_local_state() is None
+    alice.apply_update(bob.encode_update())
+    assert left == [2]
+    assert presence.peers() == {}
+
+    presence.unobserve(subscription_id)
+    carol = Awareness(YDoc(3))
+    carol_presence = Presence(carol, {"user": "carol"})
+    alice.apply_update(carol.encode_update())
+    carol_presence.close()
+    alice.apply_update(carol.encode_update())
+    assert joined[-1] == (3, {"user": "carol"})
+    assert left == [2]
+
+
+def test_prune():
+    alice = Awareness(YDoc(1))
+    bob = Awareness(YDoc(2))
+    presence = Presence(alice, {"user": "alice"}, ttl=0.2)
+    bob_presence = Presence(bob, {"user": "bob"})
+    left = []
+    presence.on_leave(left.append)
+    alice.apply_update(bob.encode_update())
+
+    # Peers which keep updating their states stay present
+    time.sleep(0.15)
+    assert presence.prune() == []
+    bob_presence.set_state({"user": "bob", "cursor": 1})
+    alice.apply_update(bob.encode_update())
+    time.sleep(0.1)
+    assert presence.prune() == []
+
+    time.sleep(0.15)
+    assert presence.prune() == [2]
+    assert left == [2]
+    assert presence.peers() == {}
+
+
+def test_heartbeat():
+    awareness = Awareness(YDoc(1))
+    presence = Presence(awareness, {"user": "alice"}, ttl=0.2)
+    assert not presence.heartbeat()
+    time.sleep(0.1)
+    assert presence.heartbeat()
+    assert not presence.heartbeat()
+
+    replica = Awareness(YDoc(2))
+    replica.apply_update(awareness.encode_update())
+    assert replica.get_states() == {1: {"user": "alice"}}
+
+    presence.close()
+    assert not presence.heartbeat()
+    with pytest.raises(ValueError):
+        Presence(awareness, {}, ttl=0)
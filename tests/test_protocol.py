@@ -0,0 +1,24 @@
+import pytest
+import y_py as Y
+from y_py import protocol
+
+
+def test_auth_denied():
+    message = protocol.encode_auth_denied("read only")
+    # Message type, permission denied subtype and the reason
+    assert message == b"\x02\x00\x09read only"
+    assert protocol.is_auth_message(message)
+    assert protocol.decode_auth_denied(message) == "read only"
+    assert protocol.decode_auth_denied(bytearray(message)) == "read only"
+
+
+def test_other_messages():
+    sync_step1 = b"\x00\x00\x01\x00"
+    assert not protocol.is_auth_message(sync_step1)
+    assert not protocol.is_auth_message(b"")
+    with pytest.raises(Y.EncodingError):
+        protocol.decode_auth_denied(sync_step1)
+    with pytest.raises(Y.EncodingError):
+        protocol.decode_auth_denied(b"\x02\x00\x09read")
+    with pytest.raises(Y.EncodingError):
+        protocol.decode_auth_denied(protocol.encode_auth_denied("denied") + b"\x00")
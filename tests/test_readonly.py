@@ -0,0 +1,92 @@
+import pytest
+import y_py as Y
+from y_py import YDoc
+
+
+def test_reads():
+    doc = YDoc()
+    text = doc.get_text("text")
+    array = doc.get_array("array")
+    root = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+        array.extend(txn, [1, Y.YMap({"key": "value"})])
+        root.set(txn, "nested", Y.YArray(["a", "b"]))
+
+    view = doc.readonly_view()
+    assert view.client_id == doc.client_id
+    assert view.state_hash() == doc.state_hash()
+    ro_text = view.get_text("text")
+    assert str(ro_text) == "hello"
+    assert len(ro_text) == 5
+    assert ro_text.to_json() == '"hello"'
+    assert ro_text.doc is view
+    assert ro_text == text
+    assert hash(ro_text) == hash(text)
+
+    ro_array = view.get_array("array")
+    assert ro_array[0] == 1
+    assert isinstance(ro_array[1], Y.ReadOnly)
+    assert ro_array[1]["key"] == "value"
+    assert all(isinstance(item, Y.ReadOnly) for item in ro_array.to_list()[1:])
+    # Iteration yields plain values, like for YArray
+    assert list(ro_array) == [1, {"key": "value"}]
+
+    ro_map = view.get_map("map")
+    assert "nested" in ro_map
+    assert list(ro_map["nested"]) == ["a", "b"]
+    assert isinstance(ro_map.get("nested"), Y.ReadOnly)
+    key, value = next(iter(ro_map.items()))
+    assert key == "nested"
+    assert isinstance(value, Y.ReadOnly)
+
+
+def test_modifications():
+    doc = YDoc()
+    root = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        root.set(txn, "nested", Y.YArray([1]))
+    view = doc.readonly_view()
+    ro_map = view.get_map("map")
+    nested = ro_map["nested"]
+    ro_text = view.get_text("text")
+
+    with doc.begin_transaction() as txn:
+        with pytest.raises(Y.ReadOnlyError):
+            ro_map.set(txn, "key", "value")
+        with pytest.raises(Y.ReadOnlyError):
+            nested.append(txn=txn, item=2)
+        with pytest.raises(Y.ReadOnlyError):
+            ro_text.extend(txn, "hello")
+    with pytest.raises(Y.ReadOnlyError):
+        nested.transaction()
+    assert not hasattr(view, "begin_transaction")
+    assert root["nested"].to_list() == [1]
+    assert "key" not in root
+
+
+def test_observers():
+    doc = YDoc()
+    text = doc.get_text("text")
+    view = doc.readonly_view()
+    targets = []
+
+    def callback(event):
+        assert isinstance(event, Y.ReadOnly)
+        targets.append(event.target)
+
+    view.get_text("text").observe(callback)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    assert len(targets) == 1
+    assert isinstance(targets[0], Y.ReadOnly)
+    with doc.begin_transaction() as txn:
+        with pytest.raises(Y.ReadOnlyError):
+            targets[0].extend(txn, "!")
+    assert str(text) == "hello"
+
+
+def test_dropped_doc():
+    view = YDoc().readonly_view()
+    with pytest.raises(RuntimeError):
+        view.get_text("text")
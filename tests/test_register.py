@@ -0,0 +1,68 @@
+import pytest
+import y_py as Y
+from test_helper import exchange_updates
+from y_py import YDoc, YRegister
+
+
+def test_register():
+    doc = YDoc()
+    theme = YRegister(doc.get_map("settings"), "theme")
+    assert theme.key == "theme"
+    with doc.begin_transaction() as txn:
+        assert theme.get(txn) is None
+        assert theme.timestamp(txn) is None
+        assert theme.set(txn, "light", timestamp=10.0)
+        assert theme.get(txn) == "light"
+        # Stale writes don't change the value
+        assert not theme.set(txn, "blue", timestamp=5.0)
+        assert theme.get(txn) == "light"
+        assert theme.timestamp(txn) == 10.0
+        # Writes without a timestamp always win
+        assert theme.set(txn, "dark")
+        assert theme.get(txn) == "dark"
+
+
+def test_register_concurrent_writes():
+    d1, d2 = YDoc(1), YDoc(2)
+    r1 = YRegister(d1.get_map("settings"), "theme")
+    r2 = YRegister(d2.get_map("settings"), "theme")
+    with d1.begin_transaction() as txn:
+        r1.set(txn, "late", timestamp=20.0)
+    with d2.begin_transaction() as txn:
+        r2.set(txn, "early", timestamp=10.0)
+    exchange_updates([d1, d2])
+    for doc, register in [(d1, r1), (d2, r2)]:
+        with doc.begin_transaction() as txn:
+            assert register.get(txn) == "late"
+
+    # Ties are broken by client identifiers
+    with d1.begin_transaction() as txn:
+        r1.set(txn, "first", timestamp=30.0)
+    with d2.begin_transaction() as txn:
+        r2.set(txn, "second", timestamp=30.0)
+    exchange_updates([d1, d2])
+    for doc, register in [(d1, r1), (d2, r2)]:
+        with doc.begin_transaction() as txn:
+            assert register.get(txn) == "second"
+
+
+def test_register_observe():
+    doc = YDoc()
+    settings = doc.get_map("settings")
+    theme = YRegister(settings, "theme")
+    values = []
+    subscription = theme.observe(values.append)
+    with doc.begin_transaction() as txn:
+        theme.set(txn, "dark")
+    with doc.begin_transaction() as txn:
+        settings.set(txn, "font", "mono")
+    assert values == ["dark"]
+    theme.unobserve(subscription)
+    with doc.begin_transaction() as txn:
+        theme.set(txn, "light")
+    assert values == ["dark"]
+
+
+def test_register_prelim():
+    with pytest.raises(Y.PreliminaryAccessError):
+        YRegister(Y.YMap({}), "theme")
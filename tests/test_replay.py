@@ -0,0 +1,55 @@
+import y_py as Y
+from y_py import DeleteSet, YDoc
+
+
+def recorded_updates():
+    doc = YDoc(1)
+    updates = []
+
+    def record(event):
+        update = event.get_update()
+        # Reads, like obtaining root types, run in transactions which change nothing
+        if update != b"\x00\x00":
+            updates.append(update)
+
+    doc.observe_after_transaction(record)
+    text = doc.get_text("text")
+    todos = doc.get_array("todos")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    with doc.begin_transaction() as txn:
+        todos.append(txn, Y.YMap({"title": "write docs"}))
+    with doc.begin_transaction() as txn:
+        todos[0].set(txn, "done", True)
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 0, 2)
+    return doc, updates
+
+
+def test_replay_updates():
+    source, updates = recorded_updates()
+    summaries = []
+    doc = Y.replay_updates(iter(updates), summaries.append)
+    assert Y.encode_state_vector(doc) == Y.encode_state_vector(source)
+    assert str(doc.get_text("text")) == "llo"
+
+    assert [s["index"] for s in summaries] == [0, 1, 2, 3]
+    assert summaries[0]["inserted"] == {1: (0, 5)}
+    assert summaries[0]["paths"] == [("text",)]
+    assert summaries[0]["deleted"] == DeleteSet()
+    assert summaries[1]["paths"] == [("todos",), ("todos", None, "title")]
+    assert summaries[2]["paths"] == [("todos", None, "done")]
+    assert summaries[3]["inserted"] == {}
+    assert summaries[3]["deleted"] == DeleteSet({1: [(0, 2)]})
+    assert summaries[3]["paths"] == [("text",)]
+
+
+def test_replay_out_of_order():
+    _, updates = recorded_updates()
+    summaries = []
+    Y.replay_updates([updates[1], updates[0]], summaries.append)
+    # The first update depends on the second one, so it's only integrated afterwards
+    assert summaries[0]["inserted"] == {}
+    assert summaries[0]["paths"] == []
+    assert summaries[1]["inserted"] == {1: (0, 7)}
+    assert summaries[1]["paths"] == [("text",), ("todos",), ("todos", None, "title")]
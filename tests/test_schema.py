@@ -0,0 +1,102 @@
+import pytest
+import y_py as Y
+from y_py import YDoc, schema
+
+Todo = schema.Map(
+    {
+        "title": schema.Text(),
+        "done": bool,
+        "priority": schema.Field(int, optional=True),
+        "tags": schema.Array(str),
+    }
+)
+Model = schema.Model({"todos": schema.Array(Todo), "settings": schema.Map({"theme": str})})
+
+
+def test_insert_and_read():
+    doc = YDoc()
+    model = Model.bind(doc)
+    with doc.begin_transaction() as txn:
+        model.todos.append(txn, {"title": "write docs", "done": False, "tags": ["docs"]})
+        model.settings.set(txn, "theme", "dark")
+
+    assert len(model.todos) == 1
+    todo = model.todos[0]
+    assert isinstance(todo, schema.MapAccessor)
+    assert isinstance(todo.title, Y.YText)
+    assert str(todo.title) == "write docs"
+    assert todo.done is False
+    assert todo.priority is None
+    assert isinstance(todo.tags, schema.ArrayAccessor)
+    assert list(todo.tags) == ["docs"]
+    assert model["settings"].theme == "dark"
+    assert model.doc is doc
+    # Accessors share the underlying document
+    assert doc.get_map("settings")["theme"] == "dark"
+    assert model.todos.shared == doc.get_array("todos")
+
+    with doc.begin_transaction() as txn:
+        todo.set(txn, "done", True)
+        todo.tags.extend(txn, ["urgent"])
+    assert todo.to_dict()["done"] is True
+    assert model.todos.to_list()[0]["tags"] == ["docs", "urgent"]
+
+
+def test_missing_fields():
+    doc = YDoc()
+    model = Model.bind(doc)
+    with doc.begin_transaction() as txn:
+        model.todos.append(txn, {"done": True})
+        with pytest.raises(schema.SchemaError, match=r"\[1\]\.done: missing required field"):
+            model.todos.append(txn, {"title": "missing done"})
+    todo = model.todos[0]
+    assert str(todo.title) == ""
+    assert len(todo.tags) == 0
+
+
+def test_validation_errors():
+    doc = YDoc()
+    model = Model.bind(doc)
+    with doc.begin_transaction() as txn:
+        with pytest.raises(schema.SchemaError, match=r"\[0\]\.done: expected bool, found str"):
+            model.todos.append(txn, {"title": "a", "done": "no"})
+        with pytest.raises(schema.SchemaError, match=r"\[0\]\.tags\[1\]: expected str"):
+            model.todos.append(txn, {"title": "a", "done": False, "tags": ["a", 1]})
+        with pytest.raises(schema.SchemaError, match="unexpected field 'owner'"):
+            model.todos.append(txn, {"done": False, "owner": "me"})
+        with pytest.raises(schema.SchemaError, match="expected dict, found list"):
+            model.todos.append(txn, [])
+        with pytest.raises(schema.SchemaError):
+            model.settings.update(txn, {"theme": 1})
+        with pytest.raises(AttributeError):
+            model.settings.color
+    # Failed writes leave the document untouched
+    assert len(model.todos) == 0
+    assert len(doc.get_map("settings")) == 0
+    # Schema errors are type errors
+    assert issubclass(schema.SchemaError, TypeError)
+
+
+def test_field_types():
+    number = schema.Field((int, float))
+    number.validate(1)
+    number.validate(1.5)
+    with pytest.raises(schema.SchemaError):
+        number.validate(True)
+    with pytest.raises(schema.SchemaError):
+        number.validate(None)
+    schema.Field(int, optional=True).validate(None)
+    schema.Field(bool).validate(True)
+    assert repr(schema.Array(schema.Field(str, optional=True))) == "Array(Field(str, optional=True))"
+    assert repr(Todo).startswith("Map({'title': Text(), 'done': Field(bool)")
+
+    with pytest.raises(TypeError):
+        schema.Field("int")
+    with pytest.raises(TypeError):
+        schema.Model({"count": int})
+
+
+def test_import_submodule():
+    import y_py.schema
+
+    assert y_py.schema.Model is schema.Model
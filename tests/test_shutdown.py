@@ -0,0 +1,71 @@
+import subprocess
+import sys
+import threading
+
+import y_py as Y
+from y_py import Dispatcher, YDoc
+
+
+def test_shutdown():
+    doc = YDoc()
+    text = doc.get_text("text")
+    events = []
+    doc.observe_after_transaction(lambda e: events.append("doc"))
+    text.observe(lambda e: events.append("text"))
+    text.observe_deep(lambda e: events.append("deep"))
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "a")
+    assert sorted(events) == ["deep", "doc", "text"]
+
+    Y.shutdown()
+    events.clear()
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "b")
+    assert events == []
+    assert str(text) == "ab"
+
+    # Callbacks subscribed afterwards are called as usual
+    text.observe(lambda e: events.append("new"))
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "c")
+    assert events == ["new"]
+
+
+def test_shutdown_drops_dispatched_callbacks():
+    doc = YDoc()
+    started = threading.Event()
+    release = threading.Event()
+    calls = []
+
+    def callback(record):
+        started.set()
+        release.wait()
+        calls.append(record)
+
+    with Dispatcher() as dispatcher:
+        doc.observe_after_transaction(callback, dispatcher)
+        for c in "ab":
+            with doc.begin_transaction() as txn:
+                doc.get_text("text").extend(txn, c)
+        started.wait()
+        Y.shutdown()
+        release.set()
+        dispatcher.wait()
+    assert len(calls) == 1
+
+
+def test_exit_with_observers():
+    script = """
+import y_py as Y
+from y_py import Dispatcher, YDoc
+
+doc = YDoc()
+text = doc.get_text("text")
+dispatcher = Dispatcher()
+doc.observe_after_transaction(lambda e: doc, dispatcher)
+text.observe(lambda e: print(text, doc))
+with doc.begin_transaction() as txn:
+    text.extend(txn, "hello")
+"""
+    result = subprocess.run([sys.executable, "-c", script], capture_output=True)
+    assert result.returncode == 0, result.stderr
@@ -0,0 +1,65 @@
+import pytest
+import y_py as Y
+from y_py import Simulator
+
+
+def test_random_operations():
+    sim = Simulator(replicas=3, seed=7)
+    assert [doc.client_id for doc in sim.replicas] == [1, 2, 3]
+    sim.run(200)
+    assert len(sim.history) == 200
+    assert any(step.startswith("sync") for step in sim.history)
+    assert any(step.startswith("replica 2:") for step in sim.history)
+    texts = {str(doc.get_text("text")) for doc in sim.replicas}
+    assert len(texts) == 1
+
+
+def test_deterministic():
+    first = Simulator(replicas=3, seed=42)
+    second = Simulator(replicas=3, seed=42)
+    first.run(100)
+    second.run(100)
+    assert first.history == second.history
+    assert first.replicas[0].state_hash() == second.replicas[0].state_hash()
+
+    other = Simulator(replicas=3, seed=43)
+    other.run(100)
+    assert other.history != first.history
+
+
+def test_custom_operations():
+    def add_todo(txn, doc, rand):
+        todos = txn.get_array("todos")
+        todos.insert(txn, rand.randint(0, len(todos)), rand.randint(0, 100))
+
+    def remove_todo(txn, doc, rand):
+        todos = txn.get_array("todos")
+        if len(todos):
+            todos.delete(txn, rand.randrange(len(todos)))
+
+    sim = Simulator(replicas=2, seed=1, operations=[add_todo, remove_todo])
+    sim.run(50, sync_probability=0.5)
+    assert set(step.split(": ")[-1] for step in sim.history if ": " in step) <= {
+        "add_todo",
+        "remove_todo",
+    }
+    lists = [doc.get_array("todos").to_list() for doc in sim.replicas]
+    assert lists[0] == lists[1]
+
+
+def test_assert_converged():
+    sim = Simulator(replicas=2)
+    with sim.replicas[1].begin_transaction() as txn:
+        txn.get_text("text").extend(txn, "hello")
+    with pytest.raises(AssertionError):
+        sim.assert_converged()
+    sim.sync(1, 0)
+    sim.assert_converged()
+    assert str(sim.replicas[0].get_text("text")) == "hello"
+
+    with pytest.raises(ValueError):
+        sim.sync(0, 2)
+    with pytest.raises(ValueError):
+        sim.step(sync_probability=2)
+    with pytest.raises(ValueError):
+        Simulator(replicas=0)
@@ -0,0 +1,77 @@
+import pytest
+import y_py as Y
+from y_py import StateVector, YDoc
+
+
+def edit(doc, content):
+    text = doc.get_text("text")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, content)
+
+
+def test_doc_state_vector():
+    doc = YDoc(1)
+    assert doc.state_vector() == StateVector()
+    assert len(doc.state_vector()) == 0
+
+    edit(doc, "hello")
+    sv = doc.state_vector()
+    assert sv == StateVector.decode(Y.encode_state_vector(doc))
+    assert sv == StateVector({1: 5})
+    assert sv[1] == 5
+    assert sv[2] == 0
+    assert 1 in sv
+    assert 2 not in sv
+    assert list(sv) == [1]
+    assert sv.items() == [(1, 5)]
+    assert sv.to_dict() == {1: 5}
+    assert repr(sv) == "StateVector({1: 5})"
+
+
+def test_encode_decode():
+    sv = StateVector({3: 10, 1: 2, 7: 0})
+    assert len(sv) == 2
+    assert StateVector.decode(sv.encode()) == sv
+    assert StateVector.decode(bytearray(sv.encode())) == sv
+    with pytest.raises(Exception):
+        StateVector.decode(b"\x02\x01")
+
+
+def test_comparison():
+    a, b = YDoc(1), YDoc(2)
+    edit(a, "a")
+    Y.apply_update(b, Y.encode_state_as_update(a))
+    assert a.state_vector() == b.state_vector()
+    assert a.state_vector() >= b.state_vector()
+    assert not a.state_vector() > b.state_vector()
+
+    edit(b, "b")
+    assert b.state_vector() > a.state_vector()
+    assert a.state_vector() < b.state_vector()
+    assert b.state_vector().dominates(a.state_vector())
+    assert not a.state_vector().dominates(b.state_vector())
+
+    # concurrent changes are neither greater nor smaller than each other
+    edit(a, "c")
+    sv_a, sv_b = a.state_vector(), b.state_vector()
+    assert sv_a != sv_b
+    assert not sv_a >= sv_b
+    assert not sv_a <= sv_b
+    assert sv_a != {1: 2}
+
+    merged = sv_a.merge(sv_b)
+    assert merged == StateVector({1: 2, 2: 1})
+    assert merged >= sv_a and merged >= sv_b
+
+
+def test_missing_updates():
+    a, b = YDoc(1), YDoc(2)
+    edit(a, "hello")
+    update = Y.encode_state_as_update(a, b.state_vector())
+    Y.apply_update(b, update)
+    edit(a, " world")
+    with a.begin_transaction() as txn:
+        update = txn.diff_v1(b.state_vector())
+    Y.apply_update(b, update)
+    assert b.state_vector() == a.state_vector()
+    assert str(b.get_text("text")) == "hello world"
@@ -0,0 +1,51 @@
+import ast
+import inspect
+from pathlib import Path
+
+import y_py as Y
+
+STUB_PATH = Path(__file__).parent.parent / "y_py.pyi"
+
+
+def stub_declarations():
+    """Collects top level names and class members declared in the type stub."""
+    tree = ast.parse(STUB_PATH.read_text())
+    names = set()
+    classes = {}
+    for node in tree.body:
+        if isinstance(node, ast.ClassDef):
+            members = set()
+            for item in node.body:
+                if isinstance(item, ast.FunctionDef):
+                    members.add(item.name)
+                elif isinstance(item, ast.AnnAssign):
+                    members.add(item.target.id)
+            classes[node.name] = members
+            names.add(node.name)
+        elif isinstance(node, ast.FunctionDef):
+            names.add(node.name)
+        elif isinstance(node, ast.AnnAssign):
+            names.add(node.target.id)
+        elif isinstance(node, ast.Assign):
+            names.update(target.id for target in node.targets)
+    return names, classes
+
+
+def test_stub_covers_module():
+    names, classes = stub_declarations()
+    # The package generated by maturin also exposes the native extension module itself
+    exported = [
+        name
+        for name in dir(Y)
+        if not name.startswith("_") and not inspect.ismodule(getattr(Y, name))
+    ]
+    missing = [name for name in exported if name not in names]
+    assert missing == [], f"Missing from y_py.pyi: {missing}"
+
+    for name in exported:
+        value = getattr(Y, name)
+        if not inspect.isclass(value):
+            continue
+        members = [member for member in vars(value) if not member.startswith("_")]
+        undeclared = [member for member in members if member not in classes[name]]
+        assert undeclared == [], f"Missing from {name} in y_py.pyi: {undeclared}"
@@ -0,0 +1,89 @@
+import pytest
+import y_py as Y
+from y_py import Awareness, SyncConnection, YDoc, protocol
+
+
+def exchange(a, b, messages):
+    """Delivers messages from `a` to `b` until neither of them has anything to reply."""
+    while messages:
+        replies = []
+        for message in messages:
+            replies.extend(b.receive(message))
+        messages = replies
+        a, b = b, a
+
+
+def test_sync():
+    server_doc = YDoc(1)
+    with server_doc.begin_transaction() as txn:
+        server_doc.get_text("text").extend(txn, "hello")
+    client_doc = YDoc(2)
+    with client_doc.begin_transaction() as txn:
+        client_doc.get_array("array").append(txn, 1)
+
+    server = SyncConnection(server_doc)
+    client = SyncConnection(client_doc)
+    assert not client.synced
+    exchange(server, client, [server.greeting()])
+    exchange(client, server, [client.greeting()])
+    assert client.synced and server.synced
+    assert str(client_doc.get_text("text")) == "hello"
+    assert list(server_doc.get_array("array")) == [1]
+
+    # Local changes are sent as updates, which are applied with the connection as their origin
+    server_text = server_doc.get_text("text")
+    client_text = client_doc.get_text("text")
+    origins = []
+    client_doc.observe_after_transaction(lambda e: origins.append(e.origin))
+    sent = []
+    server_doc.observe_after_transaction(
+        lambda e: e.origin is not server and sent.append(server.encode_update(e.get_update()))
+    )
+    with server_doc.begin_transaction() as txn:
+        server_text.extend(txn, " world")
+    assert len(sent) == 1
+    assert client.receive(sent[0]) == []
+    assert str(client_text) == "hello world"
+    assert origins == [client]
+
+
+def test_awareness():
+    server_doc = YDoc(1)
+    client_doc = YDoc(2)
+    server_awareness = Awareness(server_doc)
+    client_awareness = Awareness(client_doc)
+    server = SyncConnection(server_doc, server_awareness)
+    client = SyncConnection(client_doc, client_awareness)
+
+    changes = []
+    server_awareness.observe(lambda change, origin: changes.append((change, origin)))
+    client_awareness.set_local_state({"user": "alice"})
+    assert client_awareness.get_local_state() == {"user": "alice"}
+    assert server.receive(client.encode_awareness()) == []
+    assert server_awareness.get_states() == {2: {"user": "alice"}}
+    assert changes == [({"added": [2], "updated": [], "removed": []}, server)]
+
+    # Queries are answered with all known states
+    replies = client.receive(b"\x03")
+    assert len(replies) == 1
+    server_awareness.set_local_state({"user": "server"})
+    server.receive(replies[0])
+
+    client_awareness.set_local_state(None)
+    server.receive(client.encode_awareness())
+    assert server_awareness.get_states() == {1: {"user": "server"}}
+    assert changes[-1] == ({"added": [], "updated": [], "removed": [2]}, server)
+
+    with pytest.raises(ValueError):
+        SyncConnection(server_doc).encode_awareness()
+
+
+def test_auth_and_malformed_messages():
+    conn = SyncConnection(YDoc())
+    assert conn.denied is None
+    assert conn.receive(protocol.encode_auth_denied("read only")) == []
+    assert conn.denied == "read only"
+    with pytest.raises(Y.EncodingError):
+        conn.receive(b"\x07")
+    with pytest.raises(Y.EncodingError):
+        conn.receive(b"\x00\x05")
@@ -0,0 +1,75 @@
+import pytest
+import y_py as Y
+from y_py import Awareness, LocalHub, YDoc
+from y_py.testing import lossy_channel
+
+
+def edit(doc, count):
+    text = doc.get_text("text")
+    for i in range(count):
+        with doc.begin_transaction() as txn:
+            text.extend(txn, str(i % 10))
+
+
+def test_delay_and_reordering():
+    a, b = YDoc(1), YDoc(2)
+    channel = lossy_channel(a, b, delay_ms=50, seed=7)
+    assert isinstance(channel, LocalHub)
+    assert channel.docs == [a, b]
+    # Initial states of both documents
+    assert channel.pending == 2
+    channel.flush()
+
+    edit(a, 20)
+    assert channel.pending == 20
+    channel.advance(0.05)
+    assert channel.pending == 0
+    # Updates delivered out of order are integrated once their dependencies arrive
+    assert str(b.get_text("text")) == str(a.get_text("text"))
+    assert channel.dropped == 0
+
+
+def test_losses_are_reproducible():
+    def run(seed):
+        a, b = YDoc(1), YDoc(2)
+        channel = lossy_channel(a, b, drop_rate=0.3, delay_ms=10, seed=seed)
+        edit(a, 50)
+        edit(b, 50)
+        channel.flush()
+        return channel.dropped, str(a.get_text("text")), str(b.get_text("text"))
+
+    dropped, text_a, text_b = run(42)
+    assert dropped > 0
+    assert text_a != text_b
+    assert run(42) == (dropped, text_a, text_b)
+
+
+def test_recovery_from_losses():
+    a, b = YDoc(1), YDoc(2)
+    channel = lossy_channel(a, b, drop_rate=1.0)
+    edit(a, 3)
+    # Initial states of both documents are lost as well
+    assert channel.dropped == 5
+    assert str(b.get_text("text")) == ""
+
+    # Lost updates are recovered by exchanging the missing state, as sync protocols do
+    Y.apply_update(b, Y.encode_state_as_update(a, Y.encode_state_vector(b)))
+    assert str(b.get_text("text")) == "012"
+
+
+def test_awareness():
+    a, b = YDoc(1), YDoc(2)
+    awareness_a, awareness_b = Awareness(a), Awareness(b)
+    awareness_a.set_local_state({"user": "alice"})
+    channel = lossy_channel(a, b, delay_ms=100, awareness=(awareness_a, awareness_b))
+    assert awareness_b.get_states() == {}
+    channel.flush()
+    assert awareness_b.get_states() == {1: {"user": "alice"}}
+
+
+def test_invalid_arguments():
+    a, b = YDoc(), YDoc()
+    with pytest.raises(ValueError, match="drop_rate"):
+        lossy_channel(a, b, drop_rate=1.5)
+    with pytest.raises(ValueError, match="delay_ms"):
+        lossy_channel(a, b, delay_ms=-1)
@@ -1,4 +1,6 @@
-from y_py import YMap, YDoc
+from y_py import YArray, YMap, YDoc
+import y_py as Y
+import pytest
 from math import isclose
 
 
@@ -28,3 +30,129 @@ def test_int_conversion():
     assert type(map["edge_number"]) == float and isclose(
         float(JS_MAX_NUMBER), map["edge_number"]
     )
+
+
+def test_container_conversion():
+    """
+    Tuples are stored as arrays, while sets and frozensets are stored as arrays sorted in a
+    deterministic order.
+    """
+    doc = YDoc()
+    map = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        map.update(
+            txn,
+            {
+                "tuple": (1, "two", (3,)),
+                "set": {3, 1, 2},
+                "frozenset": frozenset(["b", "c", "a"]),
+            },
+        )
+
+    assert map["tuple"] == [1, "two", [3]]
+    assert map["set"] == [1, 2, 3]
+    assert map["frozenset"] == ["a", "b", "c"]
+
+    with pytest.raises(TypeError):
+        YMap({"mixed": {1, "one"}}).to_json()
+
+
+def test_undefined_conversion():
+    doc = YDoc()
+    map = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        map.update(txn, {"null": None, "undefined": Y.Undefined})
+
+    assert map["null"] is None
+    assert map["undefined"] is None
+
+    Y.set_conversion_options(undefined_sentinel=True)
+    try:
+        assert map["null"] is None
+        assert map["undefined"] is Y.Undefined
+        assert not Y.Undefined
+    finally:
+        Y.set_conversion_options(undefined_sentinel=False)
+
+
+def test_buffer_conversion():
+    doc = YDoc()
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [b"bytes", bytearray(b"bytearray")])
+
+    assert list(array) == [b"bytes", b"bytearray"]
+    assert all(type(value) == bytes for value in array)
+    assert hash(array[0]) == hash(b"bytes")
+
+    Y.set_conversion_options(bytearray_buffers=True)
+    try:
+        assert type(array[0]) == bytearray
+        assert array[1] == bytearray(b"bytearray")
+    finally:
+        Y.set_conversion_options(bytearray_buffers=False)
+
+
+def test_circular_reference():
+    doc = YDoc()
+    array = doc.get_array("array")
+    circular = [1, 2]
+    circular.append(circular)
+    nested = {"key": {}}
+    nested["key"]["parent"] = nested
+    shared = [1]
+
+    with doc.begin_transaction() as txn:
+        with pytest.raises(ValueError, match="circular reference"):
+            array.insert_range(txn, 0, [circular])
+        with pytest.raises(ValueError, match="circular reference"):
+            array.insert_range(txn, 0, [nested])
+        with pytest.raises(ValueError, match="circular reference"):
+            array.append(txn, circular)
+        with pytest.raises(ValueError, match="circular reference"):
+            array.append(txn, YArray([1, YMap({"nested": circular})]))
+        with pytest.raises(ValueError, match="circular reference"):
+            doc.get_map("map").set(txn, "key", nested)
+        # The same object may still appear multiple times, as long as it doesn't contain itself.
+        array.insert_range(txn, 0, [[shared, shared]])
+
+    assert array[0] == [[1], [1]]
+
+
+def test_conversion_limits():
+    doc = YDoc()
+    array = doc.get_array("array")
+
+    # Deeply nested values are converted without exhausting the stack.
+    deep = []
+    for _ in range(10_000):
+        deep = [deep]
+    with doc.begin_transaction() as txn:
+        array.insert_range(txn, 0, [deep])
+    assert len(array) == 1
+
+    Y.set_conversion_options(max_depth=3, max_size=5)
+    try:
+        with doc.begin_transaction() as txn:
+            array.insert_range(txn, 0, [[[[1]]]])
+            with pytest.raises(ValueError, match="depth"):
+                array.insert_range(txn, 0, [[[[[1]]]]])
+            with pytest.raises(ValueError, match="size"):
+                array.insert_range(txn, 0, [{"a": [1, 2, 3, 4]}])
+    finally:
+        Y.set_conversion_options(max_depth=0, max_size=0)
+
+
+def test_dict_keys():
+    doc = YDoc()
+    map = doc.get_map("map")
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        with pytest.raises(TypeError, match="key 1 of type 'int'"):
+            map.update(txn, {1: "invalid"})
+        with pytest.raises(TypeError, match="key 2 of type 'int'"):
+            array.insert_range(txn, 0, [{"nested": {2: "invalid"}}])
+    with pytest.raises(TypeError, match="key 3"):
+        YMap({3: "invalid"})
+    with pytest.raises(TypeError, match="key 4"):
+        YMap({"nested": {4: "invalid"}}).to_json()
@@ -0,0 +1,78 @@
+import time
+
+import pytest
+import y_py as Y
+from y_py import UpdateBuffer, YDoc
+
+
+def test_compaction_by_count():
+    doc = YDoc()
+    text = doc.get_text("text")
+    buffer = UpdateBuffer(doc, max_updates=3)
+    for i in range(2):
+        with doc.begin_transaction() as txn:
+            text.extend(txn, str(i))
+    assert len(buffer) == 2
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "2")
+    assert len(buffer) == 1
+
+    # Transactions without changes are not recorded
+    with doc.begin_transaction():
+        pass
+    assert len(buffer) == 1
+
+    update = buffer.drain()
+    assert len(buffer) == 0
+    assert buffer.size == 0
+    assert buffer.drain() is None
+
+    remote = YDoc()
+    Y.apply_update(remote, update)
+    assert str(remote.get_text("text")) == "012"
+
+
+def test_compaction_by_delay():
+    doc = YDoc()
+    array = doc.get_array("array")
+    buffer = UpdateBuffer(doc, max_updates=1000, max_delay=0.01)
+    with doc.begin_transaction() as txn:
+        array.append(txn, 1)
+    time.sleep(0.02)
+    with doc.begin_transaction() as txn:
+        array.append(txn, 2)
+    assert len(buffer) == 1
+    with doc.begin_transaction() as txn:
+        array.append(txn, 3)
+    assert len(buffer) == 2
+    assert buffer.size > 0
+
+    buffer.compact()
+    assert len(buffer) == 1
+    remote = YDoc()
+    Y.apply_update(remote, buffer.drain())
+    assert remote.get_array("array").to_list() == [1, 2, 3]
+
+
+def test_close():
+    doc = YDoc()
+    text = doc.get_text("text")
+    buffer = UpdateBuffer(doc)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "kept")
+    buffer.close()
+    assert buffer.closed
+    with doc.begin_transaction() as txn:
+        text.extend(txn, " ignored")
+    assert len(buffer) == 1
+    remote = YDoc()
+    Y.apply_update(remote, buffer.drain())
+    assert str(remote.get_text("text")) == "kept"
+
+
+def test_invalid_thresholds():
+    doc = YDoc()
+    with pytest.raises(ValueError):
+        UpdateBuffer(doc, max_updates=0)
+    with pytest.raises(ValueError):
+        UpdateBuffer(doc, max_delay=-1)
@@ -0,0 +1,55 @@
+import y_py as Y
+from y_py import YDoc
+
+
+def build():
+    doc = YDoc(1)
+    with doc.begin_transaction() as txn:
+        txn.get_map("metadata").set(txn, "title", "Draft")
+        txn.get_text("content").extend(txn, "hello")
+    with doc.begin_transaction() as txn:
+        txn.get_map("metadata").set(txn, "author", "Ann")
+    return doc
+
+
+def test_split_update():
+    doc = build()
+    parts = Y.split_update(Y.encode_state_as_update(doc))
+    assert set(parts) == {"metadata", "content"}
+
+    metadata = YDoc(2)
+    Y.apply_update(metadata, parts["metadata"])
+    assert metadata.get_map("metadata").to_dict() == doc.get_map("metadata").to_dict()
+    assert str(metadata.get_text("content")) == ""
+
+    content = YDoc(3)
+    Y.apply_update(content, parts["content"])
+    assert str(content.get_text("content")) == "hello"
+    assert len(content.get_map("metadata")) == 0
+
+
+def test_split_update_roots():
+    doc = build()
+    update = Y.encode_state_as_update(doc)
+    parts = Y.split_update(bytearray(update), roots=["metadata", "missing"])
+    assert list(parts) == ["metadata"]
+
+
+def test_split_update_with_doc():
+    doc = build()
+    replica = YDoc(2)
+    Y.apply_update(replica, Y.encode_state_as_update(doc))
+    state_vector = Y.encode_state_vector(replica)
+    with doc.begin_transaction() as txn:
+        txn.get_text("content").extend(txn, " world")
+        txn.get_map("metadata").pop(txn, "author")
+    update = Y.encode_state_as_update(doc, state_vector)
+
+    # Both changes refer to items which are not part of the update itself
+    assert list(Y.split_update(update)) == [None]
+
+    parts = Y.split_update(update, doc=replica)
+    assert set(parts) == {"metadata", "content"}
+    Y.apply_update(replica, parts["content"])
+    assert str(replica.get_text("content")) == "hello world"
+    assert "author" in replica.get_map("metadata")
@@ -0,0 +1,74 @@
+import pytest
+import y_py as Y
+from y_py import YDoc, View
+
+
+def test_map_view():
+    doc = YDoc()
+    root = doc.get_map("root")
+    view = View(root)
+    value = view.value
+    assert value == {}
+
+    with doc.begin_transaction() as txn:
+        root.update(txn, {"title": "draft", "tags": Y.YArray(["a"])})
+    assert value == {"title": "draft", "tags": ["a"]}
+
+    with doc.begin_transaction() as txn:
+        root["tags"].append(txn, "b")
+        root.set(txn, "title", "final")
+    assert value == {"title": "final", "tags": ["a", "b"]}
+
+    with doc.begin_transaction() as txn:
+        root.pop(txn, "tags")
+    assert value == {"title": "final"}
+    # Dictionaries are updated in place
+    assert view.value is value
+
+
+def test_array_view():
+    doc = YDoc()
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, ["a", "b", "c", Y.YText("d")])
+    view = View(array)
+    assert view.value == ["a", "b", "c", "d"]
+
+    with doc.begin_transaction() as txn:
+        array.delete(txn, 1)
+        array.insert(txn, 2, "x")
+        array[3].insert(txn, 1, "!")
+    assert view.value == ["a", "c", "x", "d!"]
+
+
+def test_text_view():
+    doc = YDoc()
+    text = doc.get_text("text")
+    view = View(text)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    assert view.value == "hello"
+
+
+def test_remote_updates_and_close():
+    remote = YDoc()
+    local = YDoc()
+    view = View(local.get_map("root"))
+    with remote.begin_transaction() as txn:
+        txn.get_map("root").set(txn, "key", "value")
+    Y.apply_update(local, Y.encode_state_as_update(remote))
+    assert view.value == {"key": "value"}
+
+    view.close()
+    assert view.closed
+    with remote.begin_transaction() as txn:
+        txn.get_map("root").set(txn, "key", "changed")
+    Y.apply_update(local, Y.encode_state_as_update(remote))
+    assert view.value == {"key": "value"}
+
+
+def test_invalid_views():
+    with pytest.raises(Y.PreliminaryObservationException):
+        View(Y.YMap({}))
+    with pytest.raises(TypeError):
+        View({})
@@ -1,8 +1,9 @@
 from test_helper import exchange_updates
 import pytest
 
-from y_py import YDoc, YArray, YArrayEvent
+from y_py import PreliminaryAccessError, YDoc, YArray, YArrayEvent, YMap, YText
 from copy import deepcopy
+import io
 import json
 
 def test_inserts():
@@ -47,6 +48,58 @@ def test_to_json():
     # ensure that it works with python json
     assert json.loads(integrated.to_json()) == contents
 
+def test_to_json_depth():
+    doc = YDoc()
+    array = doc.get_array("tasks")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, ["plain", {"nested": [1, 2]}])
+        array.append(txn, YMap({"title": "Write docs", "tags": YArray(["docs", "bug"])}))
+
+    placeholder = {"$type": "YMap", "length": 2}
+    assert json.loads(array.to_json(None, 0)) == ["plain", {"nested": [1, 2]}, placeholder]
+    with doc.begin_transaction() as txn:
+        assert json.loads(array.to_json(txn, depth=1)) == [
+            "plain",
+            {"nested": [1, 2]},
+            {"title": "Write docs", "tags": {"$type": "YArray", "length": 2}},
+        ]
+    assert json.loads(array.to_json(depth=2)) == json.loads(array.to_json())
+
+    prelim = YArray([YMap({"tags": YArray(["docs"])}), YText("text")])
+    assert json.loads(prelim.to_json(depth=0)) == [
+        {"$type": "YMap", "length": 1},
+        {"$type": "YText", "length": 4},
+    ]
+    assert json.loads(prelim.to_json(depth=1)) == [
+        {"tags": {"$type": "YArray", "length": 1}},
+        "text",
+    ]
+
+def test_write_json():
+    doc = YDoc()
+    array = doc.get_array("events")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [{"id": i, "tags": ["a", "b"]} for i in range(5000)])
+        array.append(txn, YMap({"text": YText("hello"), "nested": YArray([1, 2])}))
+
+    chunks = []
+
+    class Sink:
+        def write(self, chunk):
+            chunks.append(chunk)
+
+    with doc.begin_transaction() as txn:
+        array.write_json(txn, Sink())
+    assert len(chunks) > 1
+    assert json.loads("".join(chunks)) == json.loads(array.to_json())
+
+    file = io.StringIO()
+    with doc.begin_transaction() as txn:
+        YArray([1, "two"]).write_json(txn, file)
+        with pytest.raises(TypeError, match="write method"):
+            array.write_json(txn, object())
+    assert file.getvalue() == '[1,"two"]'
+
 def test_inserts_nested():
     d1 = YDoc()
     x = d1.get_array("test")
@@ -384,3 +437,347 @@ def test_move_range_to():
         arr.extend(t, [0,1,2,3,4,5,6,7,8,9])
     with pytest.raises(Exception):
         doc.transact(lambda t: arr.move_range_to(t, 0, -1, 3))
+
+
+def test_numpy_interop():
+    np = pytest.importorskip("numpy")
+    doc = YDoc()
+    array = doc.get_array("test")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, np.array([1.5, 2.5, 3.5]))
+        array.append(txn, np.array([[1, 2], [3, 4]]))
+    assert list(array)[:3] == [1.5, 2.5, 3.5]
+    assert array[3] == [[1, 2], [3, 4]]
+
+    numeric = doc.get_array("numeric")
+    with doc.begin_transaction() as txn:
+        numeric.insert_range(txn, 0, [1, 2, 3])
+    assert np.array_equal(numeric.to_numpy(), np.array([1.0, 2.0, 3.0]))
+    assert np.array_equal(YArray([True, False]).to_numpy(), np.array([True, False]))
+    with pytest.raises(TypeError):
+        YArray([1, "two"]).to_numpy()
+
+
+def test_to_list():
+    doc = YDoc()
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [1, "two", YArray([3, YMap({"four": 4})]), YText("five")])
+
+    shallow = array.to_list()
+    assert shallow[:2] == [1, "two"]
+    assert isinstance(shallow[2], YArray)
+    assert isinstance(shallow[3], YText)
+    assert array.to_list(deep=True) == [1, "two", [3, {"four": 4}], "five"]
+
+    prelim = YArray([1, YArray([2, YText("three")])])
+    assert isinstance(prelim.to_list()[1], YArray)
+    assert prelim.to_list(deep=True) == [1, [2, "three"]]
+
+
+def test_to_list_range():
+    doc = YDoc()
+    array = doc.get_array("array")
+    contents = list(range(10))
+    with doc.begin_transaction() as txn:
+        array.extend(txn, contents)
+    prelim = YArray(contents)
+
+    for arr in (array, prelim):
+        assert arr.to_list(start=2, stop=5) == [2, 3, 4]
+        assert arr.to_list(start=-3) == [7, 8, 9]
+        assert arr.to_list(stop=2) == [0, 1]
+        assert arr.to_list(start=8, stop=100) == [8, 9]
+        assert arr.to_list(start=5, stop=2) == []
+
+
+def test_to_arrow():
+    pa = pytest.importorskip("pyarrow")
+    doc = YDoc()
+    table = doc.get_array("table")
+    with doc.begin_transaction() as txn:
+        table.extend(txn, [{"name": "a", "value": 1}, {"name": "b"}, YMap({"value": 3, "extra": True})])
+
+    result = table.to_arrow()
+    assert isinstance(result, pa.Table)
+    assert result.column_names == ["name", "value", "extra"]
+    assert result.to_pydict() == {
+        "name": ["a", "b", None],
+        "value": [1, None, 3],
+        "extra": [None, None, True],
+    }
+
+    prelim = YArray([{"flag": True, "label": "x"}, {"label": None}, YMap({"flag": False})])
+    assert prelim.to_arrow().to_pydict() == {
+        "flag": [True, None, False],
+        "label": ["x", None, None],
+    }
+
+    with pytest.raises(TypeError):
+        YArray([{"mixed": 1}, {"mixed": "one"}]).to_arrow()
+
+    with doc.begin_transaction() as txn:
+        table.append(txn, 4)
+    with pytest.raises(TypeError):
+        table.to_arrow()
+
+
+def test_range():
+    doc = YDoc()
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, list(range(10)))
+        page, token = array.range(txn, 0, 4)
+        assert page == [0, 1, 2, 3]
+        # Elements inserted before the token don't shift the next page
+        array.insert(txn, 0, -1)
+        page, token = array.range(txn, token, 4)
+        assert page == [4, 5, 6, 7]
+        array.delete(txn, 9)
+        page, token = array.range(txn, token, 4)
+        assert page == [9]
+        assert token is None
+
+        with pytest.raises(ValueError):
+            array.range(txn, "not a token", 4)
+    with pytest.raises(PreliminaryAccessError):
+        YArray([1]).range(txn, 0, 1)
+
+
+def test_range_nested():
+    d1 = YDoc()
+    root = d1.get_map("root")
+    with d1.begin_transaction() as txn:
+        root.set(txn, "items", YArray(list(range(5))))
+    d2 = YDoc()
+    d2.get_map("root")
+    exchange_updates([d1, d2])
+
+    items = root["items"]
+    with d1.begin_transaction() as txn:
+        page, token = items.range(txn, 0, 2)
+    assert page == [0, 1]
+    # Remote insertions before the token don't shift the next page
+    with d2.begin_transaction() as txn:
+        d2.get_map("root")["items"].insert(txn, 0, "remote")
+    exchange_updates([d1, d2])
+    with d1.begin_transaction() as txn:
+        page, token = items.range(txn, token, 2)
+        assert page == [2, 3]
+        assert items.range(txn, token, 2) == ([4], None)
+
+
+def test_column():
+    doc = YDoc()
+    table = doc.get_array("table")
+    with doc.begin_transaction() as txn:
+        table.extend(txn, [{"name": "a", "value": 1}, {"name": "b"}, YMap({"value": 3, "nested": YArray([1])})])
+        assert table.column(txn, "name") == ["a", "b", None]
+        assert table.column(txn, "value", 0) == [1, 0, 3]
+        nested = table.column(txn, "nested")
+        assert nested[:2] == [None, None]
+        assert isinstance(nested[2], YArray)
+
+        table.append(txn, 4)
+        with pytest.raises(TypeError):
+            table.column(txn, "name")
+
+    prelim = YArray([{"name": "a"}, YMap({"name": "b"}), {}])
+    with doc.begin_transaction() as txn:
+        assert prelim.column(txn, "name", "?") == ["a", "b", "?"]
+
+
+def test_values_where():
+    doc = YDoc()
+    table = doc.get_array("table")
+    with doc.begin_transaction() as txn:
+        table.extend(
+            txn,
+            [
+                {"name": "a", "size": 1, "done": True},
+                {"name": "b", "size": 2.0},
+                YMap({"name": "c", "size": 2, "done": False}),
+                {"name": None},
+            ],
+        )
+        assert table.values_where(txn, "name", "a") == [{"name": "a", "size": 1, "done": True}]
+        sized = table.values_where(txn, "size", 2)
+        assert [row["name"] for row in sized] == ["b", "c"]
+        assert isinstance(sized[1], YMap)
+        assert table.values_where(txn, "done", False)[0]["name"] == "c"
+        assert table.values_where(txn, "name", None) == [{"name": None}]
+        assert table.values_where(txn, "name", "z") == []
+        with pytest.raises(TypeError):
+            table.values_where(txn, "name", {"nested": 1})
+
+        table.append(txn, 4)
+        with pytest.raises(TypeError):
+            table.values_where(txn, "name", "a")
+
+    prelim = YArray([{"name": "a"}, YMap({"name": "b"}), {}])
+    with doc.begin_transaction() as txn:
+        assert prelim.values_where(txn, "name", "b")[0]["name"] == "b"
+
+
+def test_push_primitives():
+    doc = YDoc()
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        array.append(txn, "first")
+        array.push_primitives(txn, range(1000))
+        array.push_primitives(txn, [None, True, 1.5, "text", {"key": [1, 2]}])
+    assert len(array) == 1006
+    assert array[1] == 0
+    assert array[1000] == 999
+    assert array.to_list()[-5:] == [None, True, 1.5, "text", {"key": [1, 2]}]
+
+    with doc.begin_transaction() as txn:
+        with pytest.raises(TypeError):
+            array.push_primitives(txn, [1, YText("nested")])
+    assert len(array) == 1006
+
+    prelim = YArray([0])
+    with doc.begin_transaction() as txn:
+        prelim.push_primitives(txn, [1, 2])
+        with pytest.raises(TypeError):
+            prelim.push_primitives(txn, [3, YMap({})])
+    assert prelim.to_list() == [0, 1, 2]
+
+
+def test_contains():
+    doc = YDoc()
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [1, 2.5, "text", None, {"key": [1, 2]}, [3, 4], b"bytes"])
+        array.append(txn, YMap({"nested": True}))
+    assert 1 in array
+    assert 1.0 in array
+    assert 2.5 in array
+    assert "text" in array
+    assert None in array
+    assert {"key": [1, 2]} in array
+    assert {"key": [1.0, 2]} in array
+    assert [3, 4] in array
+    assert (3, 4) in array
+    assert b"bytes" in array
+    assert "missing" not in array
+    assert {"key": [1]} not in array
+    assert object() not in array
+    assert array[7] in array
+    assert YMap({"nested": True}) not in array
+
+    prelim = YArray([1, "text"])
+    assert "text" in prelim
+    assert 2 not in prelim
+
+
+def test_sort():
+    doc = YDoc()
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [3, 1, 4, 1, 5, 9, 2, 6])
+    with doc.begin_transaction() as txn:
+        array.sort(txn)
+    assert array.to_list() == [1, 1, 2, 3, 4, 5, 6, 9]
+    with doc.begin_transaction() as txn:
+        array.sort(txn, reverse=True)
+    assert array.to_list() == [9, 6, 5, 4, 3, 2, 1, 1]
+    with doc.begin_transaction() as txn:
+        array.reverse(txn)
+    assert array.to_list() == [1, 1, 2, 3, 4, 5, 6, 9]
+
+    tasks = doc.get_array("tasks")
+    with doc.begin_transaction() as txn:
+        tasks.extend(txn, [YMap({"title": "b", "priority": 2}), {"title": "a", "priority": 1}])
+        tasks.append(txn, YMap({"title": "c", "priority": 2}))
+    first = tasks[0]
+    with doc.begin_transaction() as txn:
+        tasks.sort(txn, key=lambda task: task["priority"])
+    # Sorting is stable and elements keep their identity
+    assert [task["title"] for task in tasks] == ["a", "b", "c"]
+    assert tasks[1] == first
+
+
+def test_sort_concurrent_changes():
+    d1 = YDoc(1)
+    items = d1.get_array("items")
+    with d1.begin_transaction() as txn:
+        items.extend(txn, [YMap({"n": n}) for n in [2, 0, 1]])
+    d2 = YDoc(2)
+    exchange_updates([d1, d2])
+
+    with d1.begin_transaction() as txn:
+        items.sort(txn, key=lambda item: item["n"])
+    # A concurrent change of a moved element survives the sort
+    remote_item = d2.get_array("items")[0]
+    with d2.begin_transaction() as txn:
+        remote_item.set(txn, "edited", True)
+    exchange_updates([d1, d2])
+    for doc in [d1, d2]:
+        array = doc.get_array("items")
+        assert [item["n"] for item in array] == [0, 1, 2]
+        assert array[2]["edited"]
+
+
+def test_sort_prelim():
+    prelim = YArray(["b", "c", "a"])
+    doc = YDoc()
+    with doc.begin_transaction() as txn:
+        prelim.sort(txn, key=str.upper)
+        assert prelim.to_list() == ["a", "b", "c"]
+        prelim.reverse(txn)
+    assert prelim.to_list() == ["c", "b", "a"]
+
+
+def test_insert_returns_elements():
+    doc = YDoc()
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        todo = array.append(txn, YMap({"title": "write docs"}))
+        assert todo.integrated
+        todo.set(txn, "done", False)
+        text = array.insert(txn, 0, YText("hello"))
+        text.extend(txn, " world")
+        assert array.insert(txn, 1, 42) == 42
+        inserted = array.extend(txn, [1, YArray([2])])
+        assert inserted[0] == 1
+        inserted[1].append(txn, 3)
+        assert array.insert_range(txn, 0, []) == []
+    assert json.loads(array.to_json()) == [
+        "hello world",
+        42,
+        {"title": "write docs", "done": False},
+        1,
+        [2, 3],
+    ]
+
+    prelim = YArray()
+    item = YMap({})
+    with doc.begin_transaction() as txn:
+        assert prelim.append(txn, item) is item
+        assert prelim.extend(txn, [1, 2]) == [1, 2]
+
+
+def test_debounced_observer():
+    import threading
+
+    doc = YDoc()
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [1, 2])
+    deltas = []
+    called = threading.Event()
+
+    def callback(delta):
+        deltas.append(delta)
+        called.set()
+
+    array.observe(callback, debounce_ms=50)
+    for value in [3, 4, 5]:
+        with doc.begin_transaction() as txn:
+            array.append(txn, value)
+    with doc.begin_transaction() as txn:
+        array.delete(txn, 0)
+        array.delete(txn, 2)
+    assert called.wait(5)
+    assert deltas == [[{"delete": 1}, {"retain": 1}, {"insert": [3, 5]}]]
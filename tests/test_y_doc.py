@@ -1,3 +1,4 @@
+import zlib
 from y_py import YDoc, AfterTransactionEvent
 
 import y_py as Y
@@ -126,3 +127,1003 @@ def test_get_update():
         m.set(txn, "hi", "there")
 
     assert type(update) == bytes
+
+
+def test_buffer_inputs():
+    doc = YDoc()
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+
+    update = Y.encode_state_as_update(doc)
+    for payload in [update, bytearray(update), memoryview(update), list(update)]:
+        receiver = YDoc()
+        Y.apply_update(receiver, payload)
+        assert str(receiver.get_text("test")) == "hello"
+
+    state_vector = Y.encode_state_vector(YDoc())
+    assert Y.encode_state_as_update(doc, memoryview(state_vector)) == update
+
+    array = doc.get_array("binary")
+    with doc.begin_transaction() as txn:
+        array.append(txn, memoryview(b"view"))
+    assert array[0] == b"view"
+
+    # Non-contiguous buffers are supported as well
+    interleaved = bytes(b for byte in update for b in (byte, 0))
+    receiver = YDoc()
+    Y.apply_update(receiver, memoryview(interleaved)[::2])
+    assert str(receiver.get_text("test")) == "hello"
+
+
+def test_memoryview_outputs():
+    doc = YDoc()
+    array = doc.get_array("binary")
+    with doc.begin_transaction() as txn:
+        array.append(txn, b"payload")
+
+    Y.set_conversion_options(memoryview_buffers=True)
+    try:
+        update = Y.encode_state_as_update(doc)
+        state_vector = Y.encode_state_vector(doc)
+        assert type(update) == memoryview and update.readonly
+        assert type(state_vector) == memoryview
+        assert type(array[0]) == memoryview
+        assert array[0] == b"payload"
+    finally:
+        Y.set_conversion_options(memoryview_buffers=False)
+
+    receiver = YDoc()
+    Y.apply_update(receiver, update)
+    assert receiver.get_array("binary")[0] == b"payload"
+    assert bytes(update) == Y.encode_state_as_update(doc)
+    missing = Y.encode_state_as_update(doc, state_vector)
+    assert missing == Y.encode_state_as_update(doc, Y.encode_state_vector(doc))
+
+
+def test_error_hierarchy():
+    for error in (
+        Y.IntegrationError,
+        Y.TransactionError,
+        Y.EncodingError,
+        Y.PreliminaryAccessError,
+    ):
+        assert issubclass(error, Y.YError)
+    assert issubclass(Y.MultipleIntegrationError, Y.IntegrationError)
+    assert issubclass(Y.PreliminaryObservationException, Y.PreliminaryAccessError)
+    assert issubclass(Y.IntegratedOperationException, Y.PreliminaryAccessError)
+
+    doc = YDoc()
+    with pytest.raises(Y.EncodingError):
+        Y.apply_update(doc, b"\xff\xff\xff")
+    with pytest.raises(Y.PreliminaryAccessError):
+        Y.YArray([]).observe(lambda e: None)
+
+    txn = doc.begin_transaction()
+    with txn:
+        doc.get_text("text").extend(txn, "hello")
+    with pytest.raises(Y.TransactionError):
+        with txn:
+            pass
+
+
+def test_shared_type_doc():
+    doc = YDoc()
+    root = doc.get_map("root")
+    assert root.integrated
+    assert root.doc is doc
+
+    nested = Y.YArray([1])
+    assert not nested.integrated
+    with pytest.raises(Y.PreliminaryAccessError):
+        nested.doc
+    with doc.begin_transaction() as txn:
+        root.set(txn, "nested", nested)
+    assert nested.integrated
+    assert nested.doc is doc
+    assert root["nested"].doc is doc
+    assert doc.get_text("text").doc is doc
+
+
+def test_shared_type_doc_with_untyped_roots():
+    # Root types received in updates have no type until they are obtained
+    remote = YDoc()
+    with remote.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, "hello")
+        txn.get_array("array").append(txn, 1)
+    doc = YDoc()
+    Y.apply_update(doc, Y.encode_state_as_update(remote))
+    root = doc.get_map("root")
+    assert root.doc is doc
+    assert str(doc.get_text("text")) == "hello"
+
+
+def test_shared_type_parent():
+    doc = YDoc()
+    root = doc.get_map("root")
+    with doc.begin_transaction() as txn:
+        root.set(txn, "array", Y.YArray([Y.YText("text")]))
+    array = root["array"]
+    text = array[0]
+    assert root.parent is None
+    assert isinstance(array.parent, Y.YMap)
+    assert isinstance(text.parent, Y.YArray)
+    assert text.parent.parent.to_json() == root.to_json()
+    assert text.doc is doc
+    with pytest.raises(Y.PreliminaryAccessError):
+        Y.YMap({}).parent
+
+    xml = doc.get_xml_element("xml")
+    assert xml.doc is doc
+    with doc.begin_transaction() as txn:
+        child = xml.push_xml_text(txn)
+    assert child.doc is doc
+
+
+def test_shared_type_transaction():
+    doc = YDoc()
+    root = doc.get_map("root")
+    with root.transaction() as txn:
+        root.set(txn, "array", Y.YArray([1]))
+    array = root["array"]
+    with array.transaction() as txn:
+        array.append(txn, 2)
+    assert array.to_list() == [1, 2]
+
+    xml = doc.get_xml_element("xml")
+    with xml.transaction() as txn:
+        text = xml.push_xml_text(txn)
+    with text.transaction() as txn:
+        text.push(txn, "hello")
+    assert str(xml) == "<UNDEFINED>hello</UNDEFINED>"
+
+    with pytest.raises(Y.PreliminaryAccessError):
+        Y.YText("prelim").transaction()
+
+    other = YDoc()
+    with other.begin_transaction() as txn:
+        text = txn.get_text("text")
+    assert text.doc is other
+    with text.transaction() as txn:
+        text.extend(txn, "hello")
+    assert str(text) == "hello"
+
+    del other, txn
+    with pytest.raises(Y.TransactionError):
+        text.transaction()
+
+
+def test_shared_type_identity():
+    doc = YDoc()
+    root = doc.get_map("root")
+    with doc.begin_transaction() as txn:
+        root.set(txn, "array", Y.YArray([1]))
+    assert doc.get_map("root") == root
+    assert root["array"] == root["array"]
+    assert root["array"] != root
+    assert root["array"].parent == root
+    assert len({root, doc.get_map("root"), root["array"]}) == 2
+
+    xml = doc.get_xml_element("xml")
+    assert doc.get_xml_element("xml") == xml
+    assert hash(doc.get_xml_element("xml")) == hash(xml)
+
+    prelim = Y.YText("text")
+    assert prelim == prelim
+    assert prelim != Y.YText("text")
+    with pytest.raises(TypeError):
+        hash(prelim)
+
+
+def test_root_type_mismatch():
+    doc = YDoc()
+    doc.get_map("root")
+    assert doc.get_map("root") == doc.get_map("root")
+    with pytest.raises(TypeError, match="already exists as YMap"):
+        doc.get_text("root")
+    with pytest.raises(TypeError):
+        doc.get_xml_element("root")
+    with doc.begin_transaction() as txn:
+        with pytest.raises(TypeError):
+            txn.get_array("root")
+
+    # Root types loaded from an update take the type of their first access
+    remote = YDoc()
+    with remote.begin_transaction() as txn:
+        txn.get_array("items").append(txn, 1)
+    local = YDoc()
+    Y.apply_update(local, Y.encode_state_as_update(remote))
+    assert local.get_array("items").to_list() == [1]
+    with pytest.raises(TypeError):
+        local.get_map("items")
+
+
+def test_state_hash():
+    a = YDoc(1)
+    b = YDoc(2)
+    assert a.state_hash() == b.state_hash()
+    with a.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, "hello")
+    with b.begin_transaction() as txn:
+        txn.get_map("map").set(txn, "key", 1)
+    assert a.state_hash() != b.state_hash()
+
+    # Replicas converge regardless of the order in which updates are exchanged
+    update_a = Y.encode_state_as_update(a)
+    update_b = Y.encode_state_as_update(b)
+    Y.apply_update(a, update_b)
+    Y.apply_update(b, update_a)
+    assert a.state_hash() == b.state_hash()
+    assert len(a.state_hash()) == 64
+
+    # Deletions change the state without advancing the state vector
+    text = a.get_text("text")
+    with a.begin_transaction() as txn:
+        text.delete_range(txn, 0, 1)
+    assert a.state_hash() != b.state_hash()
+
+
+def test_content_hash():
+    a = YDoc(1)
+    b = YDoc(2)
+    with a.begin_transaction() as txn:
+        m = txn.get_map("map")
+        m.set(txn, "x", 1)
+        m.set(txn, "y", {"nested": [1, 2]})
+        txn.get_array("array").extend(txn, [1, "two"])
+        txn.get_text("text").extend(txn, "hello")
+    with b.begin_transaction() as txn:
+        m = txn.get_map("map")
+        m.set(txn, "y", {"nested": [1, 2]})
+        m.set(txn, "x", 1)
+        array = txn.get_array("array")
+        array.extend(txn, ["two"])
+        array.insert(txn, 0, 1)
+        text = txn.get_text("text")
+        text.extend(txn, "world")
+        text.delete_range(txn, 0, 5)
+        text.extend(txn, "hello")
+
+    # Content hashes ignore editing history and authorship
+    assert a.get_map("map").content_hash() == b.get_map("map").content_hash()
+    assert a.get_array("array").content_hash() == b.get_array("array").content_hash()
+    assert a.get_text("text").content_hash() == b.get_text("text").content_hash()
+    assert Y.YMap({"x": 1}).content_hash() != a.get_map("map").content_hash()
+    assert Y.YArray([1, "two"]).content_hash() == a.get_array("array").content_hash()
+    assert Y.YText("hello").content_hash() == a.get_text("text").content_hash()
+
+def test_sync_with():
+    a = YDoc(1)
+    b = YDoc(2)
+    with a.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, "hello")
+    with b.begin_transaction() as txn:
+        txn.get_array("array").append(txn, 1)
+    a.sync_with(b)
+    assert str(b.get_text("text")) == "hello"
+    assert a.get_array("array").to_list() == [1]
+    assert a.state_hash() == b.state_hash()
+
+    # Syncing again is a no-op, and syncing with itself is harmless
+    b.sync_with(a)
+    a.sync_with(a)
+    assert a.state_hash() == b.state_hash()
+    assert str(a.get_text("text")) == "hello"
+
+
+def test_materialize_at():
+    doc = YDoc(skip_gc=True)
+    text = doc.get_text("text")
+    items = doc.get_map("items")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+        items.set(txn, "a", 1)
+        items.set(txn, "nested", Y.YArray([1, 2, 3]))
+    v1 = doc.snapshot()
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 0, 6)
+        text.extend(txn, "!")
+        items.set(txn, "a", 2)
+        items.pop(txn, "nested")
+    v2 = doc.snapshot()
+
+    past = doc.materialize_at(v1)
+    assert str(past.get_text("text")) == "hello world"
+    assert past.get_map("items").to_dict(deep=True) == {"a": 1, "nested": [1, 2, 3]}
+    present = doc.materialize_at(v2)
+    assert str(present.get_text("text")) == "world!"
+    assert present.get_map("items").to_dict() == {"a": 2}
+    assert str(doc.materialize_at(YDoc().snapshot()).get_text("text")) == ""
+
+    # Materialized documents are detached from their source
+    with past.begin_transaction() as txn:
+        past.get_text("text").extend(txn, "?")
+    assert str(text) == "world!"
+
+
+def test_materialize_at_with_moves():
+    doc = YDoc(skip_gc=True)
+    array = doc.get_array("array")
+    with doc.begin_transaction() as txn:
+        array.extend(txn, [1, 2, 3])
+    v1 = doc.snapshot()
+    with doc.begin_transaction() as txn:
+        array.move_to(txn, 0, 3)
+    v2 = doc.snapshot()
+    with doc.begin_transaction() as txn:
+        array.append(txn, 4)
+
+    assert doc.materialize_at(v1).get_array("array").to_list() == [1, 2, 3]
+    assert doc.materialize_at(v2).get_array("array").to_list() == [2, 3, 1]
+
+
+def test_materialize_garbage_collected():
+    doc = YDoc()
+    text = doc.get_text("text")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    version = doc.snapshot()
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 0, 5)
+    with pytest.raises(ValueError, match="garbage collected"):
+        doc.materialize_at(version)
+    with pytest.raises(Y.EncodingError):
+        doc.materialize_at(b"\xff")
+
+
+def test_versions():
+    doc = YDoc(skip_gc=True)
+    text = doc.get_text("text")
+    items = doc.get_map("items")
+    tags = doc.get_array("tags")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+        items.set(txn, "a", 1)
+        items.set(txn, "b", 2)
+    doc.create_version("draft")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, " world")
+        items.set(txn, "a", 3)
+        items.pop(txn, "b")
+        items.set(txn, "c", "new")
+        tags.append(txn, "done")
+    doc.create_version("final")
+
+    versions = doc.list_versions()
+    assert [v["name"] for v in versions] == ["draft", "final"]
+    assert versions[0]["created"] <= versions[1]["created"]
+    draft = doc.materialize_at(versions[0]["snapshot"])
+    assert str(draft.get_text("text")) == "hello"
+
+    changes = doc.diff_versions("draft", "final")
+    assert set(changes) == {"text", "items", "tags"}
+    assert changes["text"] == {"kind": "text", "before": "hello", "after": "hello world"}
+    assert changes["items"] == {
+        "kind": "map",
+        "added": {"c": "new"},
+        "removed": {"b": 2},
+        "updated": {"a": (1, 3)},
+    }
+    assert changes["tags"] == {"kind": "array", "before": [], "after": ["done"]}
+    assert doc.diff_versions("final", "final") == {}
+
+    # Versions are synchronized with other replicas
+    remote = YDoc(skip_gc=True)
+    Y.apply_update(remote, Y.encode_state_as_update(doc))
+    assert [v["name"] for v in remote.list_versions()] == ["draft", "final"]
+    assert remote.diff_versions("draft", "final")["text"]["after"] == "hello world"
+
+    with pytest.raises(ValueError, match="already exists"):
+        doc.create_version("draft")
+    with pytest.raises(KeyError):
+        doc.diff_versions("draft", "unknown")
+
+
+def test_stats():
+    assert YDoc().stats()["blocks"] == 0
+    for skip_gc in (False, True):
+        doc = YDoc(client_id=1, skip_gc=skip_gc)
+        text = doc.get_text("text")
+        items = doc.get_map("items")
+        with doc.begin_transaction() as txn:
+            text.extend(txn, "hello world")
+            items.set(txn, "nested", Y.YArray([1, 2]))
+        with doc.begin_transaction() as txn:
+            text.insert(txn, 5, ",")
+            text.delete_range(txn, 0, 1)
+            items.get("nested").append(txn, 3)
+
+        stats = doc.stats()
+        assert stats["update_size"] == len(Y.encode_state_as_update(doc))
+        assert stats["update_v2_size"] > 0
+        assert stats["deleted_blocks"] == 1
+        assert stats["gc_blocks"] == (0 if skip_gc else 1)
+        assert stats["delete_set_ranges"] == 1
+        assert set(stats["roots"]) == {"text", "items"}
+        # Nested types are accounted to their root type
+        assert stats["roots"]["items"]["blocks"] == 3
+        assert stats["blocks"] == sum(root["blocks"] for root in stats["roots"].values())
+        assert stats["roots"]["text"]["size"] > 0
+
+
+def test_update_filter():
+    remote = YDoc(1)
+    local = YDoc(2)
+    with remote.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, "hello")
+        txn.get_array("todos").append(txn, Y.YMap({"title": "write docs"}))
+        txn.get_map("settings").set(txn, "theme", "dark")
+
+    seen = []
+
+    def record(paths):
+        seen.append(set(paths))
+        return True
+
+    filter_id = local.add_update_filter(record)
+    Y.apply_update(local, Y.encode_state_as_update(remote))
+    assert seen == [
+        {("text",), ("todos",), ("todos", None, "title"), ("settings", "theme")}
+    ]
+    local.remove_update_filter(filter_id)
+
+    local.add_update_filter(
+        lambda paths: all(path[:1] != ("settings",) for path in paths)
+    )
+    with remote.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, " world")
+    Y.apply_update(local, Y.encode_state_as_update(remote, Y.encode_state_vector(local)))
+    assert str(local.get_text("text")) == "hello world"
+
+    with remote.begin_transaction() as txn:
+        txn.get_map("settings").set(txn, "theme", "light")
+    update = Y.encode_state_as_update(remote, Y.encode_state_vector(local))
+    with pytest.raises(Y.UpdateRejectedError):
+        Y.apply_update(local, update)
+    assert local.get_map("settings")["theme"] == "dark"
+
+    # Neither of the documents changes when an update is rejected during sync
+    with local.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, "!")
+    with pytest.raises(Y.UpdateRejectedError):
+        local.sync_with(remote)
+    assert str(remote.get_text("text")) == "hello world"
+    assert seen == [
+        {("text",), ("todos",), ("todos", None, "title"), ("settings", "theme")}
+    ]
+
+
+
+def test_limits():
+    remote = YDoc(1)
+    with remote.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, "hello world")
+        txn.get_array("rows").append(txn, [1, [2, {"three": 3}]])
+    update = Y.encode_state_as_update(remote)
+
+    local = YDoc(2)
+    assert local.get_limits() == {"max_update_size": 0, "max_string_length": 0, "max_depth": 0}
+    local.set_limits(max_update_size=len(update) - 1)
+    with pytest.raises(Y.LimitExceededError):
+        Y.apply_update(local, update)
+    local.set_limits(max_update_size=0, max_string_length=5)
+    with pytest.raises(Y.LimitExceededError):
+        Y.apply_update(local, update)
+    local.set_limits(max_string_length=0, max_depth=2)
+    with pytest.raises(Y.LimitExceededError):
+        Y.apply_update(local, update)
+    assert str(local.get_text("text")) == ""
+    assert issubclass(Y.LimitExceededError, Y.UpdateRejectedError)
+
+    local.set_limits(max_depth=3, max_string_length=11)
+    assert local.get_limits() == {
+        "max_update_size": 0,
+        "max_string_length": 11,
+        "max_depth": 3,
+    }
+    Y.apply_update(local, update)
+    assert str(local.get_text("text")) == "hello world"
+
+    # Local changes are not limited
+    with local.begin_transaction() as txn:
+        local.get_text("text").extend(txn, "!" * 20)
+
+
+def test_client_stats():
+    alice, bob, server = YDoc(1), YDoc(2), YDoc(3)
+    with alice.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, "hello")
+    with bob.begin_transaction() as txn:
+        txn.get_array("rows").extend(txn, [1, 2])
+    with bob.begin_transaction() as txn:
+        txn.get_map("settings").set(txn, "theme", "dark")
+
+    Y.apply_update(server, Y.encode_state_as_update(alice))
+    assert server.client_stats() == {}
+
+    server.enable_client_stats()
+    update = Y.encode_state_as_update(bob)
+    Y.apply_update(server, update)
+    stats = server.client_stats()
+    assert list(stats) == [2]
+    assert stats[2]["updates"] == 1
+    assert stats[2]["operations"] == 2
+    assert 0 < stats[2]["bytes"] < len(update)
+
+    # Rejected updates are not recorded
+    server.set_limits(max_string_length=1)
+    with alice.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, " world")
+    with pytest.raises(Y.LimitExceededError):
+        Y.apply_update(server, Y.encode_state_as_update(alice, Y.encode_state_vector(server)))
+    assert list(server.client_stats()) == [2]
+
+    server.disable_client_stats()
+    assert server.client_stats() == {}
+
+
+def test_update_transforms():
+    remote = YDoc(1)
+    local = YDoc(2)
+    for doc in [remote, local]:
+        doc.add_update_transform(zlib.compress, zlib.decompress)
+        # reversible XOR cipher, applied after compression
+        doc.add_update_transform(
+            lambda data: bytes(b ^ 0x5A for b in data),
+            lambda data: bytes(b ^ 0x5A for b in data),
+        )
+    updates = []
+    remote.observe_after_transaction(lambda e: updates.append(e.get_update()))
+    with remote.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, "hello " * 100)
+
+    payload = Y.encode_state_as_update(remote)
+    raw = zlib.decompress(bytes(b ^ 0x5A for b in payload))
+    assert len(payload) < len(raw)
+    assert raw == zlib.decompress(bytes(b ^ 0x5A for b in updates[0]))
+
+    Y.apply_update(local, payload)
+    assert str(local.get_text("text")) == "hello " * 100
+    with pytest.raises(zlib.error):
+        Y.apply_update(local, raw)
+
+    transform_id = local.add_update_transform(lambda data: data, lambda data: "invalid")
+    with pytest.raises(TypeError):
+        Y.apply_update(local, payload)
+    local.remove_update_transform(transform_id)
+    with local.begin_transaction() as txn:
+        txn.apply_v1(payload)
+
+def test_malformed_updates():
+    remote = YDoc(1)
+    with remote.begin_transaction() as txn:
+        txn.get_text("text").extend(txn, "hello")
+    update = Y.encode_state_as_update(remote)
+    assert Y.validate_update(update) == []
+
+    doc = YDoc(2)
+    for corrupted in [update[:-3], update + b"\x00", b"\xff" * 12]:
+        problems = Y.validate_update(corrupted)
+        assert len(problems) == 1
+        assert "at byte" in problems[0]
+    for corrupted in [update[:-3], b"\xff" * 12]:
+        with pytest.raises(Y.EncodingError):
+            Y.apply_update(doc, corrupted)
+    assert Y.validate_update(update + b"\x00") == [
+        f"Malformed data at byte {len(update)}: unexpected trailing bytes"
+    ]
+
+    # A string inserted after an item of the same client, which is created later
+    dangling = b"\x01\x01\x01\x00\x84\x01\x05\x01a\x00"
+    assert Y.validate_update(dangling) == [
+        "struct 1:0 refers to 1:5, which is created after it"
+    ]
+    # Yrs keeps it pending until the missing item arrives
+    Y.apply_update(doc, dangling)
+    assert str(doc.get_text("text")) == ""
+
+
+def test_batch():
+    source = YDoc(1)
+    with source.begin_transaction() as txn:
+        txn.get_text("imported").extend(txn, "hello")
+    imported = Y.encode_state_as_update(source)
+
+    doc = YDoc(2)
+    rows = doc.get_array("rows")
+    updates = []
+    doc.observe_after_transaction(
+        lambda e: updates.append(e.get_update()) if e.get_update() != b"\x00\x00" else None
+    )
+    deltas = []
+    rows.observe(lambda e: deltas.append(e.delta))
+
+    with doc.batch() as batch_txn:
+        for i in range(3):
+            with doc.begin_transaction() as txn:
+                assert txn is batch_txn
+                rows.append(txn, i)
+        doc.transact(lambda txn: rows.append(txn, 3))
+        Y.apply_update(doc, imported)
+        with doc.batch() as nested_txn:
+            assert nested_txn is batch_txn
+        # Changes are only committed once the outermost batch exits
+        assert updates == []
+        assert deltas == []
+    assert len(updates) == 1
+    assert deltas == [[{"insert": [0, 1, 2, 3]}]]
+
+    replica = YDoc(3)
+    Y.apply_update(replica, updates[0])
+    assert list(replica.get_array("rows")) == [0, 1, 2, 3]
+    assert str(replica.get_text("imported")) == "hello"
+
+    # Transactions are independent again after the batch
+    with doc.begin_transaction() as txn:
+        rows.append(txn, 4)
+    assert len(updates) == 2
+
+    with pytest.raises(ValueError):
+        with doc.batch() as txn:
+            rows.append(txn, 5)
+            raise ValueError()
+    assert len(updates) == 3
+    assert list(rows) == [0, 1, 2, 3, 4, 5]
+
+
+@pytest.mark.parametrize("skip_gc", [False, True])
+def test_compact(skip_gc):
+    doc = YDoc(1, skip_gc=skip_gc)
+    text = doc.get_text("text")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello world, hello again")
+    for _ in range(5):
+        with doc.begin_transaction() as txn:
+            text.delete_range(txn, 0, 2)
+            text.insert(txn, 3, "xy")
+    content = str(text)
+    state_vector = Y.encode_state_vector(doc)
+    size = doc.stats()["update_size"]
+
+    saved = doc.compact()
+    assert saved >= 0
+    assert doc.stats()["update_size"] == size - saved
+    assert doc.compact() == 0
+    assert str(text) == content
+    assert Y.encode_state_vector(doc) == state_vector
+
+    # Compacted documents keep synchronizing with their replicas
+    replica = YDoc(2)
+    Y.apply_update(replica, Y.encode_state_as_update(doc))
+    with doc.begin_transaction() as txn:
+        text.insert(txn, 0, "!")
+    Y.apply_update(replica, Y.encode_state_as_update(doc, Y.encode_state_vector(replica)))
+    assert str(replica.get_text("text")) == str(text)
+
+
+def test_memory_usage():
+    doc = YDoc(1)
+    text = doc.get_text("text")
+    usage = doc.memory_usage()
+    assert usage == {"blocks": 0, "deleted": 0, "delete_set": 0, "pending": 0, "total": 0}
+
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+    first = Y.encode_state_as_update(doc)
+    state_vector = Y.encode_state_vector(doc)
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 0, 6)
+    usage = doc.memory_usage()
+    assert usage["blocks"] > 0
+    assert usage["deleted"] > 0
+    assert usage["delete_set"] > 0
+    assert usage["pending"] == 0
+    assert usage["total"] == sum(v for k, v in usage.items() if k != "total")
+
+    # Updates received out of order are held until their dependencies arrive
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "!")
+    replica = YDoc(2)
+    Y.apply_update(replica, Y.encode_state_as_update(doc, state_vector))
+    assert replica.memory_usage()["pending"] > 0
+    Y.apply_update(replica, first)
+    Y.apply_update(replica, Y.encode_state_as_update(doc, Y.encode_state_vector(replica)))
+    assert replica.memory_usage()["pending"] == 0
+    assert str(replica.get_text("text")) == "world!"
+
+
+def test_pending_updates():
+    doc = YDoc(1)
+    text = doc.get_text("text")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    first = Y.encode_state_as_update(doc)
+    state_vector = Y.encode_state_vector(doc)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, " world")
+    second = Y.encode_state_as_update(doc, state_vector)
+
+    replica = YDoc(2)
+    integrated = []
+    replica.observe_pending_integrated(lambda: integrated.append(True))
+    assert not replica.has_pending_updates()
+    assert replica.pending_update_bytes() == 0
+
+    Y.apply_update(replica, second)
+    assert replica.has_pending_updates()
+    assert replica.pending_update_bytes() > 0
+    assert integrated == []
+
+    Y.apply_update(replica, first)
+    Y.apply_update(replica, Y.encode_state_as_update(doc, Y.encode_state_vector(replica)))
+    assert not replica.has_pending_updates()
+    assert replica.pending_update_bytes() == 0
+    assert integrated == [True]
+    assert str(replica.get_text("text")) == "hello world"
+
+
+def test_from_json_snapshot():
+    snapshot = {
+        "title": "Notes",
+        "tags": ["draft", {"color": "red"}],
+        "meta": {"version": 1, "authors": ["ada"], "published": False},
+    }
+    doc = YDoc.from_json_snapshot(snapshot, client_id=7)
+    assert doc.client_id == 7
+    assert str(doc.get_text("title")) == "Notes"
+    assert list(doc.get_array("tags")) == ["draft", {"color": "red"}]
+    assert dict(doc.get_map("meta")) == snapshot["meta"]
+
+    replica = YDoc()
+    Y.apply_update(replica, Y.encode_state_as_update(doc))
+    assert str(replica.get_text("title")) == "Notes"
+    assert dict(replica.get_map("meta")) == snapshot["meta"]
+
+    with pytest.raises(TypeError):
+        YDoc.from_json_snapshot({"count": 1})
+
+
+def test_apply_template():
+    template = {
+        "title": "Untitled",
+        "tasks": [Y.YMap({"title": Y.YText("Invite collaborators"), "done": False})],
+        "settings": {"theme": "light"},
+        "body": Y.YXmlElement,
+        "notes": Y.YText,
+    }
+    doc = YDoc()
+    doc.get_text("notes")
+    updates = []
+    doc.observe_after_transaction(lambda event: updates.append(event.get_update()))
+    assert doc.apply_template(template) == ["title", "tasks", "settings", "body"]
+    # All root types are initialized by a single transaction, reads of arrays commit empty ones
+    assert len([update for update in updates if update != b"\x00\x00"]) == 1
+    assert str(doc.get_text("title")) == "Untitled"
+    task = doc.get_array("tasks")[0]
+    assert isinstance(task["title"], Y.YText)
+    assert task.doc is doc
+    assert dict(doc.get_map("settings")) == {"theme": "light"}
+    assert len(doc.get_xml_element("body")) == 0
+
+    # Root types with content are left untouched
+    with doc.begin_transaction() as txn:
+        doc.get_text("title").extend(txn, "!")
+    assert doc.apply_template(template) == []
+    assert str(doc.get_text("title")) == "Untitled!"
+
+    with pytest.raises(TypeError, match="Template of root type 'count'"):
+        doc.apply_template({"count": 1})
+    with pytest.raises(TypeError):
+        doc.apply_template({"title": ["a"]})
+    # Invalid contents are reported before any root type is created
+    with pytest.raises(TypeError):
+        doc.apply_template({"extra": "text", "items": [object()]})
+    assert doc.apply_template({"extra": "text"}) == ["extra"]
+
+
+def test_root_proxy():
+    doc = YDoc()
+    doc.get_map("settings")
+    root = doc.root(tasks=Y.YArray, body=Y.YXmlElement)
+    assert isinstance(root, Y.RootProxy)
+    with doc.begin_transaction() as txn:
+        root.tasks.append(txn, "Write docs")
+        root.settings.set(txn, "theme", "dark")
+    assert list(doc.get_array("tasks")) == ["Write docs"]
+    assert isinstance(root.body, Y.YXmlElement)
+    assert doc.root.tasks.doc is doc
+    assert {"tasks", "settings", "body"} <= set(dir(doc.root))
+
+    # Types of root types received from remote peers are inferred from their contents
+    remote = YDoc()
+    Y.apply_update(remote, Y.encode_state_as_update(doc))
+    assert isinstance(remote.root.tasks, Y.YArray)
+    assert remote.root.settings["theme"] == "dark"
+    doc.set_schema("title", {"type": "string"})
+    assert isinstance(doc.root["title"], Y.YText)
+
+    with pytest.raises(AttributeError, match="Type of root type 'missing' can't be inferred"):
+        doc.root.missing
+    with pytest.raises(AttributeError):
+        doc.root._private
+    with pytest.raises(TypeError):
+        doc.root(tasks=Y.YMap).tasks
+    with pytest.raises(TypeError, match="Type of root type 'tasks' must be one of"):
+        doc.root(tasks=list)
+
+
+def test_weak_observer():
+    import gc
+
+    class Receiver:
+        def __init__(self):
+            self.updates = []
+
+        def on_transaction(self, event):
+            self.updates.append(event.get_update())
+
+    doc = YDoc()
+    text = doc.get_text("text")
+    receiver = Receiver()
+    doc.observe_after_transaction(receiver.on_transaction, weak=True)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "a")
+    assert len(receiver.updates) == 1
+
+    updates = receiver.updates
+    del receiver
+    gc.collect()
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "b")
+    assert len(updates) == 1
+
+    # Weakly referenced functions are called for as long as they are alive
+    events = []
+    callback = lambda e: events.append(e)
+    text.observe(callback, weak=True)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "c")
+    assert len(events) == 1
+    del callback
+    gc.collect()
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "d")
+    assert len(events) == 1
+
+
+def test_revert_update():
+    doc = YDoc(client_id=1, skip_gc=True)
+    text = doc.get_text("text")
+    items = doc.get_map("items")
+    values = doc.get_array("values")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+        items.set(txn, "a", 1)
+        items.set(txn, "b", 2)
+        values.extend(txn, [1, 2, 3])
+
+    spammer = YDoc(client_id=2, skip_gc=True)
+    Y.apply_update(spammer, Y.encode_state_as_update(doc))
+    before = Y.encode_state_vector(spammer)
+    with spammer.begin_transaction() as txn:
+        spammer.get_text("text").delete_range(txn, 5, 6)
+        spammer.get_text("text").extend(txn, " spam")
+        spammer.get_map("items").set(txn, "a", "spam")
+        spammer.get_map("items").pop(txn, "b")
+        spammer.get_map("items").set(txn, "c", "spam")
+        spammer.get_array("values").delete(txn, 1)
+        spammer.get_array("values").append(txn, "spam")
+    spam = Y.encode_state_as_update(spammer, before)
+    Y.apply_update(doc, spam)
+    with doc.begin_transaction() as txn:
+        text.insert(txn, 0, ">")
+    assert str(text) == ">hello spam"
+
+    assert doc.revert_update(spam) == {"removed": 8, "restored": 8, "skipped": 0}
+    assert str(text) == ">hello world"
+    assert items.to_dict() == {"a": 1, "b": 2}
+    assert list(values) == [1, 2, 3]
+
+
+def test_revert_origin():
+    doc = YDoc(skip_gc=True)
+    text = doc.get_text("text")
+    with pytest.raises(RuntimeError, match="record_origins"):
+        doc.revert_origin("mallory")
+    doc.record_origins()
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    with doc.begin_transaction() as txn, txn.with_origin("mallory"):
+        text.extend(txn, " spam")
+    with doc.begin_transaction() as txn, txn.with_origin("alice"):
+        text.extend(txn, "!")
+    with doc.begin_transaction() as txn, txn.with_origin("mallory"):
+        text.insert(txn, 0, "spam ")
+
+    assert doc.revert_origin("mallory")["removed"] == 10
+    assert str(text) == "hello!"
+    assert doc.revert_origin("mallory")["removed"] == 0
+
+
+def test_autosave(tmp_path):
+    doc = YDoc()
+    text = doc.get_text("text")
+    with pytest.raises(ValueError):
+        doc.start_autosave(0, lambda state: None)
+
+    states = []
+    autosave = doc.start_autosave(60, states.append)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    autosave.flush()
+    assert autosave.saves == 1
+    autosave.flush()
+    assert autosave.saves == 1
+    with doc.begin_transaction() as txn:
+        text.extend(txn, " world")
+    autosave.stop()
+    assert not autosave.running
+    assert autosave.saves == 2
+    replica = YDoc()
+    Y.apply_update(replica, states[-1])
+    assert str(replica.get_text("text")) == "hello world"
+
+    path = tmp_path / "doc.ydoc"
+    with doc.start_autosave(60, str(path)):
+        with doc.begin_transaction() as txn:
+            text.extend(txn, "!")
+    replica = YDoc()
+    Y.apply_update(replica, path.read_bytes())
+    assert str(replica.get_text("text")) == "hello world!"
+
+
+def test_destroy():
+    doc = YDoc()
+    text = doc.get_text("text")
+    updates = []
+    events = []
+    destroyed = []
+    doc.observe_after_transaction(lambda e: updates.append(e.get_update()))
+    text.observe(lambda e: events.append(e.delta))
+    doc.on_destroy(lambda: destroyed.append(doc.destroyed))
+    removed = doc.on_destroy(lambda: destroyed.append("removed"))
+    doc.remove_on_destroy(removed)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    assert len(updates) == 1 and len(events) == 1
+
+    assert not doc.destroyed
+    doc.destroy()
+    assert doc.destroyed
+    assert destroyed == [True]
+
+    with pytest.raises(RuntimeError):
+        doc.begin_transaction()
+    with pytest.raises(RuntimeError):
+        doc.get_text("text")
+    with pytest.raises(RuntimeError):
+        doc.transact(lambda txn: None)
+
+    # Destroying a document again does nothing
+    doc.destroy()
+    assert destroyed == [True]
+
+
+def test_destroy_releases_callbacks():
+    import gc
+    import weakref
+
+    class Room:
+        def __init__(self, doc):
+            self.doc = doc
+            doc.observe_after_transaction(self.broadcast)
+
+        def broadcast(self, event):
+            pass
+
+    room = Room(YDoc())
+    doc_ref = weakref.ref(room.doc)
+    room.doc.destroy()
+    del room
+    gc.collect()
+    assert doc_ref() is None
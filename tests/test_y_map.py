@@ -1,4 +1,5 @@
 from copy import deepcopy
+import io
 import json
 import pytest
 import y_py as Y
@@ -57,6 +58,49 @@ def test_to_json():
     assert json.loads(integrated.to_json()) == contents
 
 
+def test_to_json_depth():
+    doc = Y.YDoc()
+    settings = doc.get_map("settings")
+    with doc.begin_transaction() as txn:
+        settings.set(txn, "theme", "dark")
+        settings.set(txn, "editor", Y.YMap({"font": Y.YMap({"size": 12}), "tabs": [2, 4]}))
+
+    assert json.loads(settings.to_json(depth=0)) == {
+        "theme": "dark",
+        "editor": {"$type": "YMap", "length": 2},
+    }
+    assert json.loads(settings.to_json(None, 1)) == {
+        "theme": "dark",
+        "editor": {"font": {"$type": "YMap", "length": 1}, "tabs": [2, 4]},
+    }
+    assert json.loads(settings.to_json(depth=5)) == json.loads(settings.to_json())
+    prelim = Y.YMap({"nested": Y.YMap({"a": 1})})
+    assert json.loads(prelim.to_json(depth=0)) == {"nested": {"$type": "YMap", "length": 1}}
+
+
+def test_write_json():
+    doc = Y.YDoc()
+    settings = doc.get_map("settings")
+    text = doc.get_text("text")
+    with doc.begin_transaction() as txn:
+        settings.update(txn, {"theme": "dark", "quote": 'say "hi"'})
+        settings.set(txn, "editor", Y.YMap({"tabs": Y.YArray([2, 4])}))
+        text.extend(txn, "line\nbreak")
+
+    file = io.StringIO()
+    with doc.begin_transaction() as txn:
+        settings.write_json(txn, file)
+    assert json.loads(file.getvalue()) == {
+        "theme": "dark",
+        "quote": 'say "hi"',
+        "editor": {"tabs": [2, 4]},
+    }
+    file = io.StringIO()
+    with doc.begin_transaction() as txn:
+        text.write_json(txn, file)
+    assert json.loads(file.getvalue()) == "line\nbreak"
+
+
 def test_update():
     doc = Y.YDoc()
     ymap = doc.get_map("dict")
@@ -274,3 +318,171 @@ def test_borrow_issue():
 
     with doc.begin_transaction() as txn:
         wrapper.append(txn, inner)
+
+
+def test_insert_json():
+    doc = Y.YDoc()
+    map = doc.get_map("map")
+    payload = {"name": "quote \" and \\ backslash", "values": [1, 2.5, True, None], "nested": {"a": {}}}
+    with doc.begin_transaction() as txn:
+        map.insert_json(txn, "payload", json.dumps(payload))
+        with pytest.raises(ValueError):
+            map.insert_json(txn, "invalid", "{not json")
+
+    assert map["payload"] == payload
+    assert json.loads(map.to_json()) == {"payload": payload}
+    prelim = Y.YMap({})
+    with doc.begin_transaction() as txn:
+        prelim.insert_json(txn, "payload", json.dumps(payload))
+    assert json.loads(prelim.to_json()) == {"payload": payload}
+
+
+def test_to_dict():
+    doc = Y.YDoc()
+    map = doc.get_map("map")
+    with doc.begin_transaction() as txn:
+        map.update(txn, {"a": 1, "b": Y.YArray([1, Y.YText("two")]), "c": Y.YMap({"d": "e"})})
+
+    shallow = map.to_dict()
+    assert shallow["a"] == 1
+    assert isinstance(shallow["b"], Y.YArray)
+    assert isinstance(shallow["c"], Y.YMap)
+    assert map.to_dict(deep=True) == {"a": 1, "b": [1, "two"], "c": {"d": "e"}}
+
+    prelim = Y.YMap({"x": Y.YMap({"y": Y.YArray([1])})})
+    assert isinstance(prelim.to_dict()["x"], Y.YMap)
+    assert prelim.to_dict(deep=True) == {"x": {"y": [1]}}
+
+
+def test_to_dict_keys():
+    doc = Y.YDoc()
+    map = doc.get_map("map")
+    contents = {"a": 1, "b": Y.YArray([2]), "c": 3}
+    with doc.begin_transaction() as txn:
+        map.update(txn, contents)
+    prelim = Y.YMap({"a": 1, "b": Y.YArray([2]), "c": 3})
+
+    for m in (map, prelim):
+        assert m.to_dict(keys=["a", "c", "missing"]) == {"a": 1, "c": 3}
+        assert m.to_dict(deep=True, keys=["b"]) == {"b": [2]}
+        assert m.to_dict(keys=[]) == {}
+
+
+def test_msgpack():
+    doc = Y.YDoc()
+    source = doc.get_map("source")
+    contents = {
+        "int": 42,
+        "negative": -70000,
+        "float": 1.5,
+        "text": "hello" * 10,
+        "bytes": b"\x00\x01",
+        "list": [None, True, False, list(range(20))],
+        "nested": {"a": {"b": "c"}},
+    }
+    with doc.begin_transaction() as txn:
+        source.update(txn, contents)
+
+    data = source.to_msgpack()
+    assert isinstance(data, bytes)
+    assert Y.YMap(deepcopy(contents)).to_msgpack() != b""
+
+    target = doc.get_map("target")
+    prelim = Y.YMap({})
+    with doc.begin_transaction() as txn:
+        target.update_from_msgpack(txn, data)
+        prelim.update_from_msgpack(txn, data)
+        with pytest.raises(ValueError):
+            target.update_from_msgpack(txn, data[:-1])
+        with pytest.raises(ValueError):
+            target.update_from_msgpack(txn, Y.YArray([1]).to_msgpack())
+    assert target.to_dict(deep=True) == contents
+    assert prelim.to_dict() == contents
+
+    msgpack = pytest.importorskip("msgpack")
+    assert msgpack.unpackb(data) == contents
+    assert msgpack.unpackb(doc.get_text("text").to_msgpack()) == ""
+    with doc.begin_transaction() as txn:
+        target.update_from_msgpack(txn, msgpack.packb({"other": [1, "two"]}))
+    assert target["other"] == [1, "two"]
+
+
+def test_iterators_outlive_sources():
+    doc = Y.YDoc()
+    with doc.begin_transaction() as txn:
+        doc_map = txn.get_map("map")
+        doc_map.update(txn, {"a": 1, "b": 2})
+
+    # Iterators keep their source alive, even if it's a temporary object
+    assert sorted(doc.get_map("map")) == ["a", "b"]
+    assert sorted(doc.get_map("map").values()) == [1, 2]
+
+    # Once the document is dropped, iterators fail instead of reading freed memory
+    keys = iter(doc.get_map("map"))
+    items = doc.get_map("map").items()
+    next(keys)
+    del doc
+    with pytest.raises(RuntimeError, match="document .* has been dropped"):
+        next(keys)
+    with pytest.raises(RuntimeError):
+        len(items)
+
+
+def test_set_returns_previous_value():
+    doc = Y.YDoc()
+    settings = doc.get_map("settings")
+    with doc.begin_transaction() as txn:
+        assert settings.set(txn, "theme", "dark") is None
+        assert settings.set(txn, "theme", "light") == "dark"
+        assert settings.set(txn, "layout", {"columns": 2}) is None
+    with doc.begin_transaction() as txn:
+        assert settings.set(txn, "layout", None) == {"columns": 2}
+        assert settings.set(txn, "layout", 1) is None
+    assert settings["theme"] == "light"
+    assert settings["layout"] == 1
+
+    prelim = YMap({"key": "old"})
+    with doc.begin_transaction() as txn:
+        assert prelim.set(txn, "key", "new") == "old"
+        assert prelim.set(txn, "other", 1) is None
+
+
+def test_paths():
+    doc = Y.YDoc()
+    root = doc.get_map("root")
+    with doc.begin_transaction() as txn:
+        assert root.set_path(txn, ["users", 0, "name"], "Alice") is None
+        root.set_path(txn, ["users", 1], {"name": "Bob", "tags": ["admin"]})
+        assert root.set_path(txn, ["users", 0, "name"], "Alicia") == "Alice"
+
+    users = root["users"]
+    assert isinstance(users, Y.YArray)
+    assert isinstance(users[0], Y.YMap)
+    assert root.get_path(["users", 0, "name"]) == "Alicia"
+    assert root.get_path(["users", -1, "tags", 0]) == "admin"
+    assert root.get_path(["users", 2, "name"]) is None
+    assert root.get_path(["users", "name"], "missing") == "missing"
+    with pytest.raises(ValueError):
+        root.get_path([])
+    with pytest.raises(TypeError):
+        root.get_path(["users", 1.5])
+
+    with doc.begin_transaction() as txn:
+        # Array elements are replaced
+        assert root.set_path(txn, ["users", 1], "Bob") == {
+            "name": "Bob",
+            "tags": ["admin"],
+        }
+        with pytest.raises(KeyError):
+            root.set_path(txn, ["settings", "theme"], "dark", create_parents=False)
+        with pytest.raises(IndexError):
+            root.set_path(txn, ["users", 5], "Eve")
+        with pytest.raises(TypeError):
+            root.set_path(txn, ["users", 1, "name"], "Bob")
+    assert len(users) == 2
+    assert root.get_path(["users", 1]) == "Bob"
+    assert "settings" not in root
+
+    prelim = YMap({"nested": {"list": [1, 2]}})
+    assert prelim.get_path(["nested", "list", 1]) == 2
+    assert prelim.get_path(["nested", "other"]) is None
@@ -1,4 +1,5 @@
 from test_helper import exchange_updates
+import pytest
 import y_py as Y
 from y_py import YText, YTextEvent
 
@@ -63,6 +64,35 @@ def test_deletes():
     assert value == expected
 
 
+def test_index_validation():
+    doc = Y.YDoc(offset_kind="utf32")
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "héllo")
+        with pytest.raises(IndexError, match="out of range for a text of length 5"):
+            text.insert(txn, 6, "!")
+        with pytest.raises(IndexError):
+            text.format(txn, 3, 3, {"bold": True})
+        with pytest.raises(IndexError):
+            text.delete_range(txn, 4, 2)
+        with pytest.raises(IndexError):
+            text.delete(txn, 5)
+        assert str(text) == "héllo"
+
+        text.insert(txn, 10, "!", clamp=True)
+        text.format(txn, 4, 10, {"bold": True}, clamp=True)
+        text.delete_range(txn, 1, 100, clamp=True)
+        text.delete(txn, 100, clamp=True)
+    assert str(text) == "h"
+
+    prelim = YText("héllo")
+    with doc.begin_transaction() as txn:
+        with pytest.raises(IndexError, match="middle of a character"):
+            prelim.insert(txn, 2, "!")
+        prelim.insert(txn, 100, "!", clamp=True)
+    assert str(prelim) == "héllo!"
+
+
 def test_observer():
     d1 = Y.YDoc()
 
@@ -192,13 +222,69 @@ def test_formatting():
     ]
 
     with d1.begin_transaction() as txn:
-        text.format(txn, 4, 7, {"bold": True})
+        text.format(txn, 4, 3, {"bold": True})
 
     assert delta == [{"retain": 4}, {"retain": 3, "attributes": {"bold": True}}]
 
     text.unobserve(sub)
 
 
+def test_attribute_values():
+    doc = Y.YDoc()
+    text = doc.get_text("test")
+    deltas = []
+    text.observe(lambda e: deltas.append(e.delta))
+    attributes = {
+        "link": {"href": "https://example.com", "external": True},
+        "tags": ["a", "b"],
+        "bold": False,
+    }
+    with doc.begin_transaction() as txn:
+        text.insert(txn, 0, "hello", attributes)
+    assert deltas[-1] == [{"insert": "hello", "attributes": attributes}]
+    assert deltas[-1][0]["attributes"]["link"]["external"] is True
+
+    with doc.begin_transaction() as txn:
+        with pytest.raises(TypeError, match="attribute 'color'"):
+            text.format(txn, 0, 5, {"bold": True, "color": object()})
+        with pytest.raises(TypeError, match="attribute 'comment'.*insert_embed"):
+            text.format(txn, 0, 5, {"comment": YText("spam")})
+    assert len(deltas) == 1
+
+
+def test_format_all_and_clear_format():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    deltas = []
+    sub = text.observe(lambda e: deltas.append(e.delta))
+
+    with d1.begin_transaction() as txn:
+        text.insert(txn, 0, "stylish")
+        text.format(txn, 0, 4, {"italic": True})
+    with d1.begin_transaction() as txn:
+        text.format_all(txn, {"bold": True})
+    assert deltas[-1] == [{"retain": 7, "attributes": {"bold": True}}]
+
+    with d1.begin_transaction() as txn:
+        text.clear_format(txn, 2, 4)
+    assert deltas[-1] == [
+        {"retain": 2},
+        {"retain": 2, "attributes": {"bold": None, "italic": None}},
+        {"retain": 2, "attributes": {"bold": None}},
+    ]
+
+    # Clearing unformatted text doesn't change anything
+    with d1.begin_transaction() as txn:
+        text.clear_format(txn, 2, 4)
+    assert len(deltas) == 3
+
+    prelim = YText("plain")
+    with d1.begin_transaction() as txn:
+        with pytest.raises(Y.IntegratedOperationException):
+            prelim.clear_format(txn, 0, 1)
+    text.unobserve(sub)
+
+
 def test_deep_observe():
     d = Y.YDoc()
     text = d.get_text("text")
@@ -226,3 +312,340 @@ def test_deep_observe():
         text.extend(txn, " should not trigger")
 
     assert events is None
+
+
+def test_attribution():
+    alice = Y.YDoc(client_id=1, skip_gc=True)
+    bob = Y.YDoc(client_id=2, skip_gc=True)
+    text = alice.get_text("text")
+    with alice.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+    Y.apply_update(bob, Y.encode_state_as_update(alice))
+    version = alice.snapshot()
+
+    remote = bob.get_text("text")
+    with bob.begin_transaction() as txn:
+        remote.insert(txn, 5, " there")
+        remote.delete_range(txn, 11, 6)
+    Y.apply_update(alice, Y.encode_state_as_update(bob))
+    assert str(text) == "hello there"
+
+    with alice.begin_transaction() as txn:
+        assert text.attribution(txn) == [
+            {"insert": "hello", "client": 1},
+            {"insert": " there", "client": 2},
+        ]
+        assert text.attribution(txn, since=version) == [
+            {"insert": "hello", "client": 1, "status": "unchanged"},
+            {"insert": " there", "client": 2, "status": "added"},
+            {"insert": " world", "client": 1, "status": "removed"},
+        ]
+        assert YText("draft").attribution(txn) == [{"insert": "draft", "client": None}]
+
+
+def test_attribution_of_short_edits():
+    alice = Y.YDoc(client_id=1)
+    bob = Y.YDoc(client_id=2)
+    text = alice.get_text("text")
+    with alice.begin_transaction() as txn:
+        text.extend(txn, "a")
+    Y.apply_update(bob, Y.encode_state_as_update(alice))
+    remote = bob.get_text("text")
+    with bob.begin_transaction() as txn:
+        remote.extend(txn, "bcd")
+        remote.insert(txn, 0, "X")
+    Y.apply_update(alice, Y.encode_state_as_update(bob))
+
+    with alice.begin_transaction() as txn:
+        assert text.attribution(txn) == [
+            {"insert": "X", "client": 2},
+            {"insert": "a", "client": 1},
+            {"insert": "bcd", "client": 2},
+        ]
+
+
+def test_diff_against_snapshot():
+    alice = Y.YDoc(client_id=1, skip_gc=True)
+    bob = Y.YDoc(client_id=2, skip_gc=True)
+    text = alice.get_text("text")
+    with alice.begin_transaction() as txn:
+        text.extend(txn, "hello world")
+        text.format(txn, 0, 5, {"bold": True})
+    Y.apply_update(bob, Y.encode_state_as_update(alice))
+    version = alice.snapshot()
+
+    remote = bob.get_text("text")
+    with bob.begin_transaction() as txn:
+        remote.extend(txn, " there")
+        remote.insert_embed(txn, 17, {"image": "cat.png"})
+        remote.delete_range(txn, 5, 6)
+    Y.apply_update(alice, Y.encode_state_as_update(bob))
+
+    added = {"ychange": {"type": "added", "client": 2}}
+    with alice.begin_transaction() as txn:
+        assert text.diff_against_snapshot(txn, version) == [
+            {"insert": "hello", "attributes": {"bold": True}},
+            {
+                "insert": " world",
+                "attributes": {"ychange": {"type": "removed", "client": 1}},
+            },
+            {"insert": " there", "attributes": added},
+            {"insert": {"image": "cat.png"}, "attributes": added},
+        ]
+        current = alice.snapshot()
+        assert text.diff_against_snapshot(txn, current) == [
+            {"insert": "hello", "attributes": {"bold": True}},
+            {"insert": " there"},
+            {"insert": {"image": "cat.png"}},
+        ]
+    with pytest.raises(Y.IntegratedOperationException):
+        with alice.begin_transaction() as txn:
+            YText("draft").diff_against_snapshot(txn, version)
+
+
+def test_lines():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    assert text.line_count() == 1
+    assert text.line(0) == ""
+
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "first\nsecond\n")
+        text.insert_embed(txn, 6, {"image": "cat.png"})
+    assert text.line_count() == 3
+    assert text.line(0) == "first"
+    assert text.line(1) == "second"
+    assert text.line(2) == ""
+    with pytest.raises(IndexError):
+        text.line(3)
+
+    with d1.begin_transaction() as txn:
+        text.insert_at_line(txn, 1, 0, "the ")
+        text.insert_at_line(txn, 0, 5, "!")
+        text.insert_at_line(txn, 2, 0, "third")
+        with pytest.raises(IndexError):
+            text.insert_at_line(txn, 0, 7, "?")
+        with pytest.raises(IndexError):
+            text.insert_at_line(txn, 3, 0, "?")
+    assert text.line(0) == "first!"
+    assert text.line(1) == "the second"
+    assert text.line(2) == "third"
+
+    prelim = YText("a\nb")
+    with d1.begin_transaction() as txn:
+        prelim.insert_at_line(txn, 1, 1, "c")
+    assert str(prelim) == "a\nbc"
+    assert prelim.line_count() == 2
+
+
+def test_replace():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "a cat and a cat and a cat")
+    d2 = Y.YDoc()
+    exchange_updates([d1, d2])
+
+    with d1.begin_transaction() as txn:
+        assert text.replace(txn, "cat", "car", 2) == 2
+    # A concurrent edit next to a replaced word is preserved
+    with d2.begin_transaction() as txn:
+        d2.get_text("test").insert(txn, 5, "s")
+    exchange_updates([d1, d2])
+    assert str(text) == "a cars and a car and a cat"
+    assert str(d2.get_text("test")) == str(text)
+
+    with d1.begin_transaction() as txn:
+        assert text.replace(txn, "a ", "") == 3
+        assert text.replace(txn, "dog", "cat") == 0
+        with pytest.raises(ValueError):
+            text.replace(txn, "", "x")
+    assert str(text) == "cars and car and cat"
+
+    prelim = YText("żółw żółw")
+    with d1.begin_transaction() as txn:
+        assert prelim.replace(txn, "żółw", "żaba") == 2
+    assert str(prelim) == "żaba żaba"
+
+
+def test_regex_replace():
+    import re
+
+    d1 = Y.YDoc(offset_kind="utf16")
+    text = d1.get_text("test")
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "🎉 born 1990, died 2050")
+        assert text.regex_replace(txn, r"\d+", lambda m: str(int(m[0]) + 1)) == 2
+    assert str(text) == "🎉 born 1991, died 2051"
+
+    with d1.begin_transaction() as txn:
+        pattern = re.compile(r"(\w+) (\d+)")
+        assert text.regex_replace(txn, pattern, r"\2 \1") == 2
+        assert text.regex_replace(txn, "^", "> ") == 1
+    assert str(text) == "> 🎉 1991 born, 2051 died"
+
+
+def test_markdown():
+    markdown = (
+        "# Title\n"
+        "\n"
+        "Some **bold**, *italic*, ~~struck~~ and `code` with a [link](https://example.com).\n"
+        "\n"
+        "- first\n"
+        "- second\n"
+        "\n"
+        "1. one\n"
+        "2. two\n"
+        "\n"
+        "> quoted\n"
+        "\n"
+        "```\n"
+        "def f():\n"
+        "    return 1\n"
+        "```\n"
+    )
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    deltas = []
+    sub = text.observe(lambda e: deltas.append(e.delta))
+    with d1.begin_transaction() as txn:
+        text.from_markdown(txn, markdown)
+    text.unobserve(sub)
+
+    assert deltas[0][:3] == [
+        {"insert": "Title"},
+        {"insert": "\n", "attributes": {"header": 1}},
+        {"insert": "Some "},
+    ]
+    assert {"insert": "bold", "attributes": {"bold": True}} in deltas[0]
+    assert {
+        "insert": "link",
+        "attributes": {"link": "https://example.com"},
+    } in deltas[0]
+    assert {"insert": "\n", "attributes": {"list": "ordered"}} in deltas[0]
+    with d1.begin_transaction() as txn:
+        assert text.to_markdown(txn) == markdown
+
+    # Paragraph lines are joined, and text which looks like Markdown is escaped
+    with d1.begin_transaction() as txn:
+        text.from_markdown(txn, "first\nline\n\n\\# not a *heading*_")
+    assert str(text) == "first line\n# not a heading_\n"
+    with d1.begin_transaction() as txn:
+        assert text.to_markdown(txn) == "first line\n\n\\# not a *heading*\\_\n"
+        assert YText("plain *text*").to_markdown(txn) == "plain \\*text\\*\n"
+
+
+@pytest.mark.parametrize("offset_kind", ["utf8", "utf16", "utf32"])
+def test_markdown_offset_kinds(offset_kind):
+    doc = Y.YDoc(offset_kind=offset_kind)
+    text = doc.get_text("test")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "plain")
+        text.from_markdown(txn, "**héllo** wörld *ok*")
+        assert text.to_markdown(txn) == "**héllo** wörld *ok*\n"
+
+
+def test_embedded_shared_types():
+    d1 = Y.YDoc()
+    text = d1.get_text("test")
+    deltas = []
+    sub = text.observe(lambda e: deltas.append(e.delta))
+    with d1.begin_transaction() as txn:
+        text.extend(txn, "ab")
+        text.insert_embed(txn, 1, Y.YMap({"kind": "mention"}), {"bold": True})
+
+    delta = deltas[-1]
+    assert delta[0] == {"insert": "a"}
+    assert isinstance(delta[1]["insert"], Y.YMap)
+    assert delta[1]["insert"]["kind"] == "mention"
+    assert delta[1]["attributes"] == {"bold": True}
+    assert delta[2] == {"insert": "b"}
+    assert str(text) == "ab"
+    assert len(text) == 3
+
+    with d1.begin_transaction() as txn:
+        text.apply_delta(
+            txn,
+            [
+                {"retain": 3},
+                {"insert": Y.YArray([1, 2])},
+                {"insert": "c", "attributes": {"italic": True}},
+            ],
+        )
+    delta = deltas[-1]
+    assert delta[0] == {"retain": 3}
+    assert delta[1]["insert"].to_list() == [1, 2]
+    assert delta[2] == {"insert": "c", "attributes": {"italic": True}}
+
+    with d1.begin_transaction() as txn:
+        text.apply_delta(txn, [{"delete": 1}, {"retain": 2, "attributes": {"bold": True}}])
+        with pytest.raises(ValueError):
+            text.apply_delta(txn, [{"replace": 1}])
+    assert deltas[-1][0] == {"delete": 1}
+    assert str(text) == "bc"
+    text.unobserve(sub)
+
+    # Embedded shared types are replicated
+    d2 = Y.YDoc()
+    remote = []
+    d2.get_text("test").observe(lambda e: remote.append(e.delta))
+    exchange_updates([d1, d2])
+    inserts = [op["insert"] for op in remote[0]]
+    assert isinstance(inserts[0], Y.YMap)
+    assert inserts[0].to_dict() == {"kind": "mention"}
+
+
+def test_range():
+    doc = Y.YDoc()
+    text = doc.get_text("text")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello world, hello again")
+        page, token = text.range(txn, 0, 6)
+        assert page == "hello "
+        # Text inserted before the token doesn't shift the next page
+        text.insert(txn, 0, ">> ")
+        page, token = text.range(txn, token, 6)
+        assert page == "world,"
+        pages = [page]
+        while token is not None:
+            page, token = text.range(txn, token, 6)
+            pages.append(page)
+        assert "".join(pages) == "world, hello again"
+
+        with pytest.raises(ValueError):
+            text.range(txn, "1:x", 6)
+
+
+def test_debounced_observer():
+    import threading
+
+    doc = Y.YDoc()
+    text = doc.get_text("text")
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "hello")
+    deltas = []
+    called = threading.Event()
+
+    def callback(delta):
+        deltas.append(delta)
+        called.set()
+
+    text.observe(callback, debounce_ms=50)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, " world")
+    with doc.begin_transaction() as txn:
+        text.delete_range(txn, 0, 1)
+    with doc.begin_transaction() as txn:
+        text.format(txn, 0, 2, {"bold": True})
+    with doc.begin_transaction() as txn:
+        text.insert(txn, 4, "!")
+        text.delete_range(txn, 5, 2)
+    assert called.wait(5)
+    assert deltas == [
+        [
+            {"delete": 1},
+            {"retain": 2, "attributes": {"bold": True}},
+            {"retain": 2},
+            {"insert": "!orld"},
+        ]
+    ]
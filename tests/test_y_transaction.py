@@ -9,4 +9,28 @@ def test_before_state():
     with doc.begin_transaction() as txn:
         text.extend(txn, " World")
         assert len(txn.before_state) == 1
-    
\ No newline at end of file
+    
+
+def test_with_origin():
+    doc = Y.YDoc()
+    text = doc.get_text("text")
+    origins = []
+    text.observe(lambda e: origins.append(e.origin))
+    doc.observe_after_transaction(lambda e: origins.append(e.origin))
+
+    with doc.begin_transaction() as txn:
+        assert txn.origin is None
+        with txn.with_origin("user") as scoped:
+            assert scoped is txn
+            text.extend(txn, "hello")
+            with txn.with_origin("import"):
+                assert txn.origin == "import"
+                text.extend(txn, " world")
+            assert txn.origin == "user"
+        assert txn.origin is None
+    assert origins == ["import", "import"]
+
+    origins.clear()
+    with doc.begin_transaction() as txn:
+        text.extend(txn, "!")
+    assert origins == [None, None]
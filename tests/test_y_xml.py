@@ -1,4 +1,6 @@
+import json
 from test_helper import exchange_updates
+import pytest
 import unittest
 import y_py as Y
 
@@ -40,6 +42,29 @@ def test_attributes():
     assert actual == {"key1": None, "key2": "value2"}
 
 
+
+def test_xml_text_attributes():
+    d1 = Y.YDoc()
+    text = d1.get_xml_text("test")
+    with d1.begin_transaction() as txn:
+        text.push(txn, "comment")
+        text.set_attribute(txn, "comment-id", "c1")
+        text.set_attribute(txn, "author", "alice")
+    assert text.get_attribute("comment-id") == "c1"
+    assert dict(text.attributes()) == {"comment-id": "c1", "author": "alice"}
+
+    d2 = Y.YDoc()
+    exchange_updates([d1, d2])
+    remote = d2.get_xml_text("test")
+    assert str(remote) == "comment"
+    assert dict(remote.attributes()) == {"comment-id": "c1", "author": "alice"}
+
+    with d2.begin_transaction() as txn:
+        remote.remove_attribute(txn, "author")
+    exchange_updates([d1, d2])
+    assert text.get_attribute("author") is None
+    assert dict(text.attributes()) == {"comment-id": "c1"}
+
 def test_siblings():
     d1 = Y.YDoc()
     root = d1.get_xml_element("test")
@@ -273,3 +298,184 @@ def test_deep_observe():
         container.first_child.push(txn, "nested")
 
     assert events != None
+
+
+def test_markdown():
+    d1 = Y.YDoc()
+    root = d1.get_xml_element("test")
+    markdown = "## Todo\n\n- **milk**\n- eggs\n\n```\nfirst\nsecond\n```\n"
+    with d1.begin_transaction() as txn:
+        root.push_xml_text(txn).push(txn, "replaced")
+        root.from_markdown(txn, markdown)
+
+    assert str(root) == (
+        "<UNDEFINED><h2>Todo</h2>"
+        "<ul><li><strong>milk</strong></li><li>eggs</li></ul>"
+        "<pre>first\nsecond</pre></UNDEFINED>"
+    )
+    assert root.to_markdown() == markdown
+
+
+def test_rename_and_move_child():
+    d1 = Y.YDoc()
+    root = d1.get_xml_element("test")
+    with d1.begin_transaction() as txn:
+        first = root.push_xml_element(txn, "p")
+        first.set_attribute(txn, "class", "intro")
+        first.push_xml_text(txn).push(txn, "hello")
+        root.push_xml_element(txn, "hr")
+        root.push_xml_text(txn).push(txn, "world")
+
+    with d1.begin_transaction() as txn:
+        heading = root.first_child.rename(txn, "h1")
+    assert heading.name == "h1"
+    assert heading.get_attribute("class") == "intro"
+    assert str(root) == '<UNDEFINED><h1 "class"="intro">hello</h1><hr></hr>world</UNDEFINED>'
+
+    with d1.begin_transaction() as txn:
+        moved = root.move_child(txn, 0, 3)
+        with pytest.raises(IndexError):
+            root.move_child(txn, 3, 0)
+        with pytest.raises(ValueError):
+            root.rename(txn, "root")
+    assert moved.name == "h1"
+    assert str(root) == '<UNDEFINED><hr></hr>world<h1 "class"="intro">hello</h1></UNDEFINED>'
+
+    with d1.begin_transaction() as txn:
+        text = root.move_child(txn, 1, 0)
+    assert str(text) == "world"
+    assert str(root) == '<UNDEFINED>world<hr></hr><h1 "class"="intro">hello</h1></UNDEFINED>'
+
+    d2 = Y.YDoc()
+    exchange_updates([d1, d2])
+    assert str(d2.get_xml_element("test")) == str(root)
+
+
+def test_children_and_queries():
+    d1 = Y.YDoc()
+    root = d1.get_xml_element("test")
+    with d1.begin_transaction() as txn:
+        section = root.push_xml_element(txn, "section")
+        section.push_xml_element(txn, "p").push_xml_text(txn).push(txn, "first")
+        root.push_xml_text(txn).push(txn, "text")
+        root.push_xml_element(txn, "p").push_xml_text(txn).push(txn, "second")
+
+    children = root.children()
+    assert [type(child) for child in children] == [Y.YXmlElement, Y.YXmlText, Y.YXmlElement]
+    assert children[0].name == "section"
+    assert [str(p.first_child) for p in root.find_all("p")] == ["first", "second"]
+    assert str(root.first("p").first_child) == "first"
+    assert root.first("h1") is None
+    assert root.find_all("h1") == []
+    assert section.children()[0].name == "p"
+
+
+def test_prosemirror():
+    d1 = Y.YDoc()
+    fragment = d1.get_xml_element("prosemirror")
+    paragraph = {
+        "type": "paragraph",
+        "content": [{"type": "text", "text": "Hello world"}],
+    }
+    with d1.begin_transaction() as txn:
+        fragment.apply_prosemirror_steps(
+            txn,
+            json.dumps(
+                [{"stepType": "replace", "from": 0, "to": 0, "slice": {"content": [paragraph]}}]
+            ),
+        )
+    with d1.begin_transaction() as txn:
+        assert json.loads(fragment.to_prosemirror_json(txn)) == {
+            "type": "doc",
+            "content": [paragraph],
+        }
+
+    steps = [
+        {"stepType": "addMark", "from": 7, "to": 12, "mark": {"type": "strong"}},
+        {
+            "stepType": "replace",
+            "from": 7,
+            "to": 7,
+            "slice": {"content": [{"type": "text", "text": "big "}]},
+        },
+        # splits the paragraph after "Hello"
+        {
+            "stepType": "replace",
+            "from": 6,
+            "to": 6,
+            "slice": {
+                "content": [{"type": "paragraph"}, {"type": "paragraph"}],
+                "openStart": 1,
+                "openEnd": 1,
+            },
+        },
+        {"stepType": "attr", "pos": 0, "attr": "align", "value": "center"},
+    ]
+    with d1.begin_transaction() as txn:
+        fragment.apply_prosemirror_steps(txn, json.dumps(steps))
+    expected = {
+        "type": "doc",
+        "content": [
+            {
+                "type": "paragraph",
+                "attrs": {"align": "center"},
+                "content": [{"type": "text", "text": "Hello"}],
+            },
+            {
+                "type": "paragraph",
+                "content": [
+                    {"type": "text", "text": " big "},
+                    {"type": "text", "text": "world", "marks": [{"type": "strong"}]},
+                ],
+            },
+        ],
+    }
+    with d1.begin_transaction() as txn:
+        assert json.loads(fragment.to_prosemirror_json(txn)) == expected
+    first = fragment.first_child
+    assert first.name == "paragraph"
+    assert first.get_attribute("align") == "center"
+    assert first.next_sibling.name == "paragraph"
+
+    d2 = Y.YDoc()
+    exchange_updates([d1, d2])
+    remote = d2.get_xml_element("prosemirror")
+    with d2.begin_transaction() as txn:
+        assert json.loads(remote.to_prosemirror_json(txn)) == expected
+
+    steps = [
+        {"stepType": "removeMark", "from": 13, "to": 18, "mark": {"type": "strong"}},
+        # wraps the first paragraph in a blockquote
+        {
+            "stepType": "replaceAround",
+            "from": 0,
+            "to": 7,
+            "gapFrom": 0,
+            "gapTo": 7,
+            "insert": 1,
+            "slice": {"content": [{"type": "blockquote"}]},
+            "structure": True,
+        },
+    ]
+    with d2.begin_transaction() as txn:
+        remote.apply_prosemirror_steps(txn, json.dumps(steps))
+    exchange_updates([d1, d2])
+    with d1.begin_transaction() as txn:
+        content = json.loads(fragment.to_prosemirror_json(txn))
+    assert content == {
+        "type": "doc",
+        "content": [
+            {"type": "blockquote", "content": [expected["content"][0]]},
+            {"type": "paragraph", "content": [{"type": "text", "text": " big world"}]},
+        ],
+    }
+
+    with d1.begin_transaction() as txn:
+        before = json.loads(fragment.to_prosemirror_json(txn))
+        with pytest.raises(ValueError):
+            fragment.apply_prosemirror_steps(txn, json.dumps([{"stepType": "unknown"}]))
+        with pytest.raises(ValueError):
+            fragment.apply_prosemirror_steps(
+                txn, json.dumps([{"stepType": "replace", "from": 100, "to": 100}])
+            )
+        assert json.loads(fragment.to_prosemirror_json(txn)) == before
@@ -1,24 +1,254 @@
+from random import Random
+from re import Match, Pattern
 from typing import (
     Any,
     Callable,
     Iterator,
     List,
     Iterable,
+    MutableMapping,
     Literal,
     Optional,
+    TextIO,
     Tuple,
     TypedDict,
     Union,
     Dict,
 )
 
+class UndefinedType:
+    """
+    Type of the `Undefined` sentinel.
+    """
+
+    def __repr__(self) -> str: ...
+    def __bool__(self) -> bool:
+        """
+        Returns:
+            Always `False`, so that `Undefined` is falsy like `None`.
+        """
+
+Undefined: UndefinedType
+"""
+A sentinel value representing JavaScript `undefined`, as opposed to `None` which represents `null`.
+Inserting `Undefined` into a shared type always stores an `undefined` value. Stored `undefined`
+values are read back as `None`, unless `set_conversion_options(undefined_sentinel=True)` is used.
+"""
+
+def set_conversion_options(
+    undefined_sentinel: Optional[bool] = None,
+    bytearray_buffers: Optional[bool] = None,
+    memoryview_buffers: Optional[bool] = None,
+    max_depth: Optional[int] = None,
+    max_size: Optional[int] = None,
+):
+    """
+    Configures how values stored in Ypy documents are converted into Python objects. Options which
+    are not provided keep their current setting.
+
+    Args:
+        undefined_sentinel: Yjs distinguishes between `null` and `undefined` values. Both of them
+            are converted into `None` by default. When this option is enabled, `undefined` values
+            are returned as `Undefined` instead.
+        bytearray_buffers: Binary values are returned as immutable `bytes` by default. When this
+            option is enabled, they are returned as `bytearray` objects instead.
+        memoryview_buffers: Binary values and payloads encoded by Ypy (updates, state vectors and
+            delete sets) are copied into `bytes` by default. When this option is enabled, they are
+            returned as read-only `memoryview` objects sharing memory with Ypy instead, so that
+            large payloads are never copied. `bytearray_buffers` takes precedence for binary values.
+        max_depth: Maximum nesting depth of lists and dictionaries inserted into shared types.
+            Deeper values raise a `ValueError`. Zero (the default) means no limit.
+        max_size: Maximum number of elements (counting nested ones) of a single value inserted
+            into shared types. Larger values raise a `ValueError`. Zero (the default) means no limit.
+    """
+
+def enable_metrics():
+    """
+    Starts collecting counts and durations of transactions, conversions of values, applied updates
+    and observer callbacks across all documents. Metrics are disabled by default, as measuring adds
+    a small overhead to every operation.
+
+    Example:
+
+    ```
+    import y_py as Y
+
+    Y.enable_metrics()
+    run_workload()
+    print(Y.get_metrics()['callbacks'])
+    ```
+    """
+
+def disable_metrics():
+    """
+    Stops collecting metrics. Metrics collected so far are kept until `reset_metrics` is called.
+    """
+
+def reset_metrics():
+    """
+    Clears all metrics collected so far.
+    """
+
+class Metric(TypedDict):
+    count: int
+    total_time: float
+    """Total duration of measured operations in seconds."""
+    max_time: float
+    """Duration of the longest measured operation in seconds."""
+
+def get_metrics() -> Dict[str, Metric]:
+    """
+    Returns:
+        Collected metrics of "transactions", "conversions", "updates" and "callbacks".
+    """
+
+def set_log_handler(handler: Optional[Callable[[Dict[str, Any]], None]]):
+    """
+    Registers a `handler(record)` which receives structured records of events happening inside of
+    Ypy, or removes a registered handler if `None` is given. There can be only one handler at a
+    time, shared by all documents.
+
+    A record is a dictionary with an "event" name and a "level" of the Python `logging` module,
+    followed by entries describing the event:
+
+    - "transaction_committed" (`DEBUG`): a transaction which changed its document has been
+      committed, with numbers of `inserted` and `deleted` elements.
+    - "update_applied" (`DEBUG`): a remote update of a given `size` in bytes has been applied.
+    - "update_rejected" (`WARNING`): a remote update of a given `size` couldn't be applied, for a
+      given `reason`.
+    - "observer_error" (`ERROR`): an observer callback raised an `exception`.
+    - "gc_run" (`INFO`): `YDoc.compact` has garbage collected a document with a given `client_id`,
+      saving `removed` bytes of its encoded state.
+
+    Exceptions raised by the handler are printed and otherwise ignored.
+
+    Example::
+
+        import logging
+        import y_py as Y
+
+        logger = logging.getLogger('y_py')
+        Y.set_log_handler(lambda record: logger.log(record['level'], '%s', record))
+    """
+
+def set_panic_hook(verbose: bool):
+    """
+    Configures how Rust panics are reported. Panics of operations changing shared types are
+    always raised as `InternalError`s, and panics of applying updates as `EncodingError`s, with the
+    Rust message and the location of the panic. Panics elsewhere, eg. in methods of `YDoc`,
+    `YTransaction`, events or iterators, are raised by PyO3 as a `PanicException`. Given `verbose`,
+    panics raised as `InternalError`s or `EncodingError`s are also printed to stderr by the default
+    Rust panic hook, along with a backtrace if the `RUST_BACKTRACE` environment variable is set.
+    Panics of other Rust code are always passed to the default hook.
+
+    Args:
+        verbose: Whether panics raised as Python exceptions should be printed to stderr too.
+    """
+
+class Versions(TypedDict):
+    y_py: str
+    yrs: str
+    lib0: str
+    update_encodings: List[int]
+    """Versions of the lib0 encoding of updates, which peers exchanging updates have to support."""
+    protocol_messages: List[str]
+    """Message types of y-protocols understood by `SyncConnection`."""
+    mmap_format: int
+    """Version of the layout of files of `MmapDoc`."""
+
+def versions() -> Versions:
+    """
+    Returns versions of Ypy and of the bundled yrs and lib0 crates, along with versions of the
+    formats it supports, so that deployments can check their compatibility with peers at startup.
+
+    Example::
+
+        import y_py as Y
+
+        assert Y.versions()["update_encodings"] == [1]
+    """
+
+def shutdown():
+    """
+    Drops callbacks of all subscriptions of Ypy objects, including callbacks queued by a
+    `Dispatcher` which haven't been called yet. Shared types and documents remain usable, and
+    callbacks subscribed afterwards are called as usual.
+
+    Ypy calls this function when the interpreter exits, after which observers aren't called
+    anymore. Calling it beforehand, eg. when a server is shutting down, releases objects
+    referenced by callbacks while they can still be finalized safely.
+
+    Example::
+
+        import y_py as Y
+
+        doc = Y.YDoc()
+        doc.observe_after_transaction(lambda event: print(event.get_update()))
+        Y.shutdown()
+        with doc.begin_transaction() as txn:
+            doc.get_text('text').extend(txn, 'hello')  # nothing is printed
+    """
+
+class YError(Exception):
+    """Base class of all errors raised by Ypy."""
+
+class IntegrationError(YError):
+    """Occurs when a Ypy data type cannot be integrated into a YDoc."""
+
+class MultipleIntegrationError(IntegrationError):
+    """
+    A Ypy data type instance cannot be integrated into multiple YDocs or the same YDoc multiple times.
+    """
+
+class TransactionError(YError):
+    """
+    Occurs when a transaction is used in an invalid way, e.g. when a transaction which was already
+    committed by a `with` block is entered again.
+    """
+
+class EncodingError(YError):
+    """Occurs due to issues in the encoding/decoding process of y_py updates."""
+
+class PreliminaryAccessError(YError):
+    """
+    Occurs when an operation requires a Ypy data type to be integrated into a YDoc, but it is
+    still preliminary.
+    """
+
+class PreliminaryObservationException(PreliminaryAccessError):
+    """Occurs when an observer is attached to a Y type that is not integrated into a YDoc."""
+
+class IntegratedOperationException(PreliminaryAccessError):
+    """Occurs when a method requires a type to be integrated, but is called on a preliminary type."""
+
+class ReadOnlyError(YError):
+    """Occurs when a shared type obtained from a read-only view of a YDoc is modified."""
+
+class UpdateRejectedError(YError):
+    """Occurs when an update is rejected by a filter registered with `YDoc.add_update_filter`."""
+
+class LimitExceededError(UpdateRejectedError):
+    """Occurs when an update exceeds limits set with `YDoc.set_limits`."""
+
+class InternalError(YError):
+    """
+    Occurs when an operation fails because of an internal error of Ypy or Yrs, eg. a Rust panic.
+    The document which was being changed may be left in an inconsistent state.
+    """
+
+class ValidationError(YError):
+    """
+    Occurs when a change of a shared type is rejected by a JSON Schema attached with
+    `YDoc.set_schema`.
+    """
+
 class SubscriptionId:
     """
     Tracks an observer callback. Pass this to the `unobserve` method to cancel
     its associated callback.
     """
 
-Event = Union[YTextEvent, YArrayEvent, YMapEvent, YXmlTextEvent, YXmlElementEvent]
+Event = Union[YTextEvent, YArrayEvent, YMapEvent, YXmlTextEvent, YXmlEvent]
 
 class YDoc:
     """
@@ -43,6 +273,12 @@ class YDoc:
     """
 
     client_id: int
+    destroyed: bool
+    root: RootProxy
+    """
+    A proxy giving access to root types of this document as its attributes, eg. `doc.root.tasks`,
+    which creates missing root types and infers their types. See `RootProxy`.
+    """
     def __init__(
         self,
         client_id: Optional[int] = None,
@@ -54,6 +290,66 @@ class YDoc:
         document globally unique identifier (it's up to caller to ensure that requirement).
         Otherwise it will be assigned a randomly generated number.
         """
+    @staticmethod
+    def from_json_snapshot(
+        snapshot: Dict[str, Any],
+        client_id: Optional[int] = None,
+        offset_kind: str = "utf8",
+        skip_gc: bool = False,
+    ) -> YDoc:
+        """
+        Creates a new document with root types matching a plain JSON `snapshot`, as exported from
+        Yjs with `Y.Doc.toJSON()`. Strings become `YText` root types, lists become `YArray` root
+        types and dictionaries become `YMap` root types, while their contents are stored as plain
+        values. Remaining arguments are passed to the `YDoc` constructor.
+
+        This allows to seed collaborative documents with content generated outside of Ypy, eg. to
+        migrate from a non-CRDT storage.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc.from_json_snapshot({'title': 'Notes', 'tags': ['draft'], 'meta': {'version': 1}})
+            assert str(doc.get_text('title')) == 'Notes'
+        """
+    def apply_template(self, spec: Dict[str, Any]) -> List[str]:
+        """
+        Creates root types and their initial content from a declarative `spec`, in a single
+        transaction, so that servers can initialize new rooms consistently. Root types which
+        already have content are left untouched, so applying a template to an initialized document
+        is a no-op. Contents of the `spec` are checked before any root type is changed.
+
+        Example::
+
+            from y_py import YDoc, YMap, YText, YXmlElement
+
+            TEMPLATE = {
+                'title': 'Untitled',
+                'tasks': [YMap({'title': YText('Invite collaborators'), 'done': False})],
+                'settings': {'theme': 'light'},
+                'body': YXmlElement,
+            }
+
+            doc = YDoc()
+            assert doc.apply_template(TEMPLATE) == ['title', 'tasks', 'settings', 'body']
+            assert doc.apply_template(TEMPLATE) == []
+
+        Args:
+            spec: A dictionary mapping names of root types to their content. Strings declare
+                `YText` root types, lists declare `YArray` root types and dictionaries declare
+                `YMap` root types. Preliminary shared types can be used as well, eg. to nest shared
+                types in the initial content, and Ypy classes, such as `YXmlElement`, declare empty
+                root types of their kind.
+
+        Returns:
+            Names of root types which have been created or filled by the template.
+        """
+    def state_vector(self) -> StateVector:
+        """
+        Returns:
+            A current state vector of this document, which describes updates it has seen so far.
+        """
     def begin_transaction(self) -> YTransaction:
         """
 
@@ -63,7 +359,7 @@ class YDoc:
             transaction at the time - subsequent attempts will cause exception to be thrown.
 
         Transactions started with `doc.begin_transaction` can be released by deleting the transaction object
-        method.
+        method. Within a `batch` scope, the transaction shared by the batch is returned instead.
 
         Example::
 
@@ -75,6 +371,26 @@ class YDoc:
 
         """
     def transact(self, callback: Callable[[YTransaction]]): ...
+    def batch(self) -> Batch:
+        """
+        Returns a scope grouping all transactions of this document into a single one until it's
+        exited, so that observers are called once with all the changes made in the scope. This
+        reduces network chatter during bulk imports, as a single update is emitted.
+
+        Transactions started with `begin_transaction` or `transact`, and updates applied with
+        `apply_update` share a transaction, which is also returned when the scope is entered.
+        Changes are committed even if the scope is exited with an exception.
+
+        Example::
+
+            from y_py import YDoc, apply_update
+
+            doc = YDoc()
+            doc.observe_after_transaction(lambda e: send(e.get_update()))
+            with doc.batch():
+                for update in imported_updates:
+                    apply_update(doc, update)
+        """
     def get_map(self, name: str) -> YMap:
         """
         Returns:
@@ -82,8 +398,8 @@ class YDoc:
 
         If there was no instance with this name before, it will be created and then returned.
 
-        If there was an instance with this name, but it was of different type, it will be projected
-        onto `YMap` instance.
+        If there was an instance with this name, but it was of different type, a `TypeError` is
+        raised.
         """
     def get_xml_element(self, name: str) -> YXmlElement:
         """
@@ -92,8 +408,8 @@ class YDoc:
 
         If there was no instance with this name before, it will be created and then returned.
 
-        If there was an instance with this name, but it was of different type, it will be projected
-        onto `YXmlElement` instance.
+        If there was an instance with this name, but it was of different type, a `TypeError` is
+        raised.
         """
     def get_xml_text(self, name: str) -> YXmlText:
         """
@@ -102,8 +418,8 @@ class YDoc:
 
         If there was no instance with this name before, it will be created and then returned.
 
-        If there was an instance with this name, but it was of different type, it will be projected
-        onto `YXmlText` instance.
+        If there was an instance with this name, but it was of different type, a `TypeError` is
+        raised.
         """
     def get_array(self, name: str) -> YArray:
         """
@@ -112,8 +428,8 @@ class YDoc:
 
         If there was no instance with this name before, it will be created and then returned.
 
-        If there was an instance with this name, but it was of different type, it will be projected
-        onto `YArray` instance.
+        If there was an instance with this name, but it was of different type, a `TypeError` is
+        raised.
         """
     def get_text(self, name: str) -> YText:
         """
@@ -124,453 +440,3066 @@ class YDoc:
             A `YText` shared data type, that's accessible for subsequent accesses using given `name`.
 
         If there was no instance with this name before, it will be created and then returned.
-        If there was an instance with this name, but it was of different type, it will be projected
-        onto `YText` instance.
-        """
-    def observe_after_transaction(
-        self, callback: Callable[[AfterTransactionEvent]]
-    ) -> SubscriptionId:
+        If there was an instance with this name, but it was of different type, a `TypeError` is
+        raised.
         """
-        Subscribe callback function to updates on the YDoc. The callback will receive encoded state updates and
-        deletions when a document transaction is committed.
-
-        Args:
-            callback: A function that receives YDoc state information affected by the transaction.
-
-        Returns:
-            A subscription identifier that can be used to cancel the callback.
+    def readonly_view(self) -> ReadOnlyDoc:
         """
+        Shared types obtained from the view can be read and observed, but raise a `ReadOnlyError`
+        on any attempt to modify them, which is useful to enforce permissions of individual
+        connections on a server. The view doesn't keep this document alive.
 
-EncodedStateVector = bytes
-EncodedDeleteSet = bytes
-YDocUpdate = bytes
+        Example:
 
-class AfterTransactionEvent:
-    """
-    Holds transaction update information from a commit after state vectors have been compressed.
-    """
+        ```
+        from y_py import YDoc
 
-    before_state: EncodedStateVector
-    """
-    Encoded state of YDoc before the transaction.
-    """
-    after_state: EncodedStateVector
-    """
-    Encoded state of the YDoc after the transaction.
-    """
-    delete_set: EncodedDeleteSet
-    """
-    Elements deleted by the associated transaction.
-    """
+        doc = YDoc()
+        view = doc.readonly_view()
+        text = view.get_text('name')
+        print(str(text))
+        ```
 
-    def get_update(self) -> YDocUpdate:
-        """
         Returns:
-            Encoded payload of all updates produced by the transaction.
+            A read-only view of this document.
         """
+    def state_hash(self) -> str:
+        """
+        Replicas which integrated the same updates produce the same digest, no matter in which
+        order they received them, so digests can be exchanged to cheaply verify that documents
+        converged.
 
-def encode_state_vector(doc: YDoc) -> EncodedStateVector:
-    """
-    Encodes a state vector of a given Ypy document into its binary representation using lib0 v1
-    encoding. State vector is a compact representation of updates performed on a given document and
-    can be used by `encode_state_as_update` on remote peer to generate a delta update payload to
-    synchronize changes between peers.
+        Returns:
+            A hex encoded SHA-256 digest of this document's state.
 
-    Example::
+        Example:
 
-        from y_py import YDoc, encode_state_vector, encode_state_as_update, apply_update from y_py
+        ```
+        from y_py import YDoc, encode_state_as_update, apply_update
 
-        # document on machine A
         local_doc = YDoc()
-        local_sv = encode_state_vector(local_doc)
-
-        # document on machine B
         remote_doc = YDoc()
-        remote_delta = encode_state_as_update(remote_doc, local_sv)
-
-        apply_update(local_doc, remote_delta)
-
-    """
-
-def encode_state_as_update(
-    doc: YDoc, vector: Optional[Union[EncodedStateVector, List[int]]] = None
-) -> YDocUpdate:
-    """
-    Encodes all updates that have happened since a given version `vector` into a compact delta
-    representation using lib0 v1 encoding. If `vector` parameter has not been provided, generated
-    delta payload will contain all changes of a current Ypy document, working effectively as its
-    state snapshot.
+        apply_update(remote_doc, encode_state_as_update(local_doc))
+        assert local_doc.state_hash() == remote_doc.state_hash()
+        ```
+        """
+    def sync_with(self, other: YDoc):
+        """
+        Synchronizes this document with `other` document living in the same process, by
+        exchanging their state vectors and missing updates in both directions. Afterwards both
+        documents have the same state.
 
-    Example::
+        Example:
 
-        from y_py import YDoc, encode_state_vector, encode_state_as_update, apply_update
+        ```
+        from y_py import YDoc
 
-        # document on machine A
         local_doc = YDoc()
-        local_sv = encode_state_vector(local_doc)
-
-        # document on machine B
         remote_doc = YDoc()
-        remote_delta = encode_state_as_update(remote_doc, local_sv)
+        with local_doc.begin_transaction() as txn:
+            txn.get_text('name').extend(txn, 'hello')
+        local_doc.sync_with(remote_doc)
+        assert str(remote_doc.get_text('name')) == 'hello'
+        ```
+        """
+    def snapshot(self) -> bytes:
+        """
+        Captures a current state of this document, which can be passed to `materialize_at` later
+        on to browse or restore this version of the document. Snapshots combine a state vector with
+        a delete set, as deletions don't advance state vectors.
 
-        apply_update(local_doc, remote_delta)
-    """
+        Returns:
+            An encoded snapshot of this document.
+        """
+    def materialize_at(self, snapshot: BinaryInput) -> YDoc:
+        """
+        Rebuilds a state of this document at the time a given `snapshot` was taken with
+        `YDoc.snapshot`.
 
-def apply_update(doc: YDoc, diff: Union[YDocUpdate, List[int]]):
-    """
-    Applies delta update generated by the remote document replica to a current document. This
-    method assumes that a payload maintains lib0 v1 encoding format.
+        History is only preserved by documents created with `skip_gc=True`. If content visible in
+        the snapshot has been garbage collected since then, a `ValueError` is raised.
 
-    Example::
+        Args:
+            snapshot: A snapshot produced by `YDoc.snapshot`.
 
-        from y_py import YDoc, encode_state_vector, encode_state_as_update, apply_update
+        Returns:
+            A new, detached document: changes made to it don't affect this document.
 
-        # document on machine A
-        local_doc = YDoc()
-        local_sv = encode_state_vector(local_doc)
+        Example:
 
-        # document on machine B
-        remote_doc = YDoc()
-        remote_delta = encode_state_as_update(remote_doc, local_sv)
+        ```
+        from y_py import YDoc
 
-        apply_update(local_doc, remote_delta)
-    """
+        doc = YDoc(skip_gc=True)
+        text = doc.get_text('name')
+        with doc.begin_transaction() as txn:
+            text.extend(txn, 'hello')
+        version = doc.snapshot()
+        with doc.begin_transaction() as txn:
+            text.delete_range(txn, 0, 5)
+        assert str(doc.materialize_at(version).get_text('name')) == 'hello'
+        ```
+        """
+    def create_version(self, name: str):
+        """
+        Stores a snapshot of the current state of this document as a named version, so that
+        apps can keep a list of versions without an external storage. Versions live in a root
+        map named `__versions__`, which is synchronized with other replicas like any other root
+        type.
 
-class YTransaction:
-    """
-    A transaction that serves as a proxy to document block store. Ypy shared data types execute
-    their operations in a context of a given transaction. Each document can have only one active
-    transaction at the time - subsequent attempts will cause exception to be thrown.
+        As with `materialize_at`, versions can only be compared as long as their content hasn't
+        been garbage collected, ie. for documents created with `skip_gc=True`.
 
-    Transactions started with `doc.begin_transaction` can be released by deleting the transaction object
-    method.
+        Args:
+            name: A unique name of the version. A `ValueError` is raised if a version of the
+                same name already exists.
 
-    Example::
+        Example:
 
+        ```
         from y_py import YDoc
-        doc = YDoc()
+
+        doc = YDoc(skip_gc=True)
         text = doc.get_text('name')
         with doc.begin_transaction() as txn:
-            text.insert(txn, 0, 'hello world')
-    """
-
-    before_state: Dict[int, int]
-
-    def get_text(self, name: str) -> YText:
+            text.extend(txn, 'hello')
+        doc.create_version('draft')
+        with doc.begin_transaction() as txn:
+            text.extend(txn, ' world')
+        doc.create_version('final')
+        assert doc.diff_versions('draft', 'final') == {
+            'name': {'kind': 'text', 'before': 'hello', 'after': 'hello world'}
+        }
+        ```
+        """
+    def list_versions(self) -> List[Dict[str, Any]]:
         """
         Returns:
-            A `YText` shared data type, that's accessible for subsequent accesses using given `name`.
+            Versions of this document stored with `create_version`, from the oldest to the
+            newest one. Every version is a dictionary with its `name`, a `created` timestamp in
+            seconds since the epoch and a `snapshot`, which can be passed to `materialize_at`.
+        """
+    def diff_versions(self, a: str, b: str) -> Dict[str, Dict[str, Any]]:
+        """
+        Compares two versions of this document stored with `create_version`. Nested shared
+        types are compared by their JSON representation. A `KeyError` is raised if any of the
+        versions doesn't exist.
 
-        If there was no instance with this name before, it will be created and then returned.
+        Args:
+            a: A name of the earlier version.
+            b: A name of the later version.
 
-        If there was an instance with this name, but it was of different type, it will be projected
-        onto `YText` instance.
+        Returns:
+            A dictionary of root types which differ between the versions, keyed by their names.
+            Every root type is described by a dictionary with its `kind`: "text" and "array"
+            root types have their contents `before` and `after`, as a string and a list
+            respectively, while "map" root types have entries which have been `added` and
+            `removed`, and entries which have been `updated` with their `(before, after)`
+            values.
         """
-    def get_array(self, name: str) -> YArray:
+    def stats(self) -> Dict[str, Any]:
+        """
+        Returns statistics of this document, which help to monitor its growth and decide when to
+        compact it.
+
+        Returns:
+            A dictionary with following entries:
+
+            - `blocks`: number of blocks (chunks of content) stored by the document,
+            - `deleted_blocks`: number of blocks which have been deleted,
+            - `gc_blocks`: number of deleted blocks which content has been garbage collected,
+            - `delete_set_ranges`: number of ranges of deleted blocks,
+            - `update_size` and `update_v2_size`: sizes in bytes of the full document state, using
+              lib0 v1 and v2 encodings respectively,
+            - `roots`: for each root type, a dictionary with its number of `blocks` and their
+              `size` in bytes using lib0 v1 encoding.
+        """
+    def memory_usage(self) -> Dict[str, int]:
+        """
+        Returns an estimate of memory held by this document, to help with capacity planning and
+        leak detection without a heap profiler. Sizes of contents are approximated by their
+        encoded sizes, and memory held by Python objects, eg. shared type wrappers and
+        observers, is not included.
+
+        Example::
+
+            if doc.memory_usage()["deleted"] > 10 * 1024 * 1024:
+                doc.compact()
+
+        Returns:
+            A dictionary with following entries, in bytes:
+
+            - `blocks`: blocks (chunks of content) which haven't been deleted,
+            - `deleted`: deleted blocks, including those which content has been garbage collected,
+            - `delete_set`: ranges of deleted blocks,
+            - `pending`: updates which can't be integrated yet, because they depend on updates
+              which haven't been received,
+            - `total`: sum of all the above.
+        """
+    def compact(self) -> int:
+        """
+        Compacts the internal store of this document by squashing adjacent deleted blocks, and
+        garbage collecting content of deleted blocks unless the document has been created with
+        `skip_gc=True`. Long-lived documents, eg. on a server, can be compacted by maintenance jobs
+        once `stats` reports many deleted blocks.
+
+        Compaction is committed as a transaction which doesn't change the content of the document,
+        so shared types observers are not called. Observers registered with
+        `observe_after_transaction` are called with an update containing deletions only, which
+        are already known to other replicas.
+
+        Example::
+
+            if doc.stats()['deleted_blocks'] > 10000:
+                saved = doc.compact()
+
+        Returns:
+            A number of bytes saved, as a difference of sizes of the full document state using
+            lib0 v1 encoding.
+        """
+    def observe_after_transaction(
+        self,
+        callback: Callable[[Union[AfterTransactionEvent, TransactionRecord]]],
+        dispatcher: Optional[Dispatcher] = None,
+        weak: bool = False,
+    ) -> SubscriptionId:
+        """
+        Subscribe callback function to updates on the YDoc. The callback will receive encoded state updates and
+        deletions when a document transaction is committed.
+
+        Args:
+            callback: A function that receives YDoc state information affected by the transaction.
+            dispatcher: A dispatcher running the callback on its own thread. Dispatched callbacks
+                receive a `TransactionRecord` instead of an `AfterTransactionEvent`.
+            weak: If `True`, the subscription only holds a weak reference to the callback, so that
+                it doesn't keep the callback, or the receiver of a bound method, alive. The
+                callback stops being called once it has been garbage collected. Lambdas passed
+                this way are collected right away, unless they are referenced elsewhere.
+
+        Returns:
+            A subscription identifier that can be used to cancel the callback.
+        """
+    def has_pending_updates(self) -> bool:
+        """
+        Checks if this document holds updates which can't be integrated yet, because they depend
+        on updates which haven't been received. Providers can use it to detect missing updates,
+        and request them from their peers.
+        """
+    def pending_update_bytes(self) -> int:
+        """
+        Returns:
+            A number of bytes of updates which can't be integrated yet, see
+            `has_pending_updates`, using lib0 v1 encoding.
+        """
+    def observe_pending_integrated(
+        self, callback: Callable[[], None], weak: bool = False
+    ) -> SubscriptionId:
+        """
+        Subscribes a callback which is called after a transaction, eg. applying a missing update,
+        has integrated all pending updates of this document.
+
+        Example::
+
+            from y_py import YDoc, apply_update, encode_state_vector
+
+            doc = YDoc()
+            doc.observe_pending_integrated(lambda: print("caught up"))
+            apply_update(doc, update)
+            if doc.has_pending_updates():
+                request_missing_updates(encode_state_vector(doc))
+
+        Args:
+            callback: A callback called without arguments.
+            weak: If `True`, only a weak reference to the callback is held, like in
+                `observe_after_transaction`.
+
+        Returns:
+            A reference to the callback subscription.
+        """
+    def add_update_filter(
+        self, callback: Callable[[List[Tuple[Optional[str], ...]]], bool]
+    ) -> int:
+        """
+        Registers a filter which is called before a remote update is applied to this document with
+        `apply_update`, `YTransaction.apply_v1` or `sync_with`, and which can reject the update by
+        returning a falsy value. Rejected updates are not applied at all, and raise an
+        `UpdateRejectedError`. Local changes are not filtered.
+
+        Filtering requires decoding the full state of this document, which makes it linear to the
+        document size.
+
+        Example:
+
+        ```
+        from y_py import YDoc, apply_update
+
+        def protect_settings(paths):
+            return all(path[:1] != ('settings',) for path in paths)
+
+        doc = YDoc()
+        doc.add_update_filter(protect_settings)
+        apply_update(doc, update)  # raises UpdateRejectedError if the update changes settings
+        ```
+
+        Args:
+            callback: A function receiving a list of locations changed by an update. Each location
+                is a tuple of a root type name followed by keys of map entries leading to the
+                changed shared type or map entry. Elements of arrays, texts and XML nodes are
+                represented by `None`, so inserting text into a root `YText` changes `('name',)`,
+                while setting a title of a `YMap` nested in a root `YArray` changes
+                `('todos', None, 'title')`. Changes which cannot be located yet, because they depend
+                on updates which haven't been received, are reported with an empty tuple.
+
+        Returns:
+            An id which can be used to remove the filter with `remove_update_filter`.
+        """
+    def remove_update_filter(self, filter_id: int):
+        """
+        Removes a filter registered with `add_update_filter`.
+        """
+    def set_limits(
+        self,
+        max_update_size: Optional[int] = None,
+        max_string_length: Optional[int] = None,
+        max_depth: Optional[int] = None,
+    ):
+        """
+        Limits remote updates applied to this document, so that servers applying updates from
+        untrusted clients can't be overwhelmed by pathological payloads. Updates exceeding any of
+        the limits are not applied at all, and raise a `LimitExceededError`. Limits which are not
+        provided keep their current setting, and zero (the default) means no limit. Local changes
+        are not limited.
+
+        Example::
+
+            from y_py import YDoc, LimitExceededError, apply_update
+
+            doc = YDoc()
+            doc.set_limits(max_update_size=1024 * 1024, max_string_length=65536, max_depth=32)
+            try:
+                apply_update(doc, payload)
+            except LimitExceededError:
+                connection.close()
+
+        Args:
+            max_update_size: Maximum size of an update in bytes, after transforms registered with
+                `add_update_transform` have decoded it.
+            max_string_length: Maximum length of a single string inserted by an update, counted in
+                UTF-16 code units like in Yjs.
+            max_depth: Maximum nesting depth of lists and maps of a single value inserted by an
+                update.
+        """
+    def enable_client_stats(self):
+        """
+        Starts collecting statistics of remote updates applied to this document per client id,
+        returned by `client_stats`, eg. to enforce quotas or detect abusive clients of a hosted
+        service. Statistics are not collected by default, as collecting them requires decoding
+        every applied update once more.
+        """
+    def disable_client_stats(self):
+        """
+        Stops collecting statistics of applied updates, and drops the ones collected so far.
+        """
+    def client_stats(self) -> Dict[int, Dict[str, int]]:
+        """
+        Returns statistics of remote updates applied to this document. Changes of a client may be
+        relayed by other peers, so they're attributed to their author rather than to a connection
+        they've been received from.
+
+        Example::
+
+            doc.enable_client_stats()
+            ...
+            for client_id, stats in doc.client_stats().items():
+                if stats['bytes'] > quota:
+                    block(client_id)
+
+        Returns:
+            Statistics of remote updates applied to this document since `enable_client_stats` has
+            been called, keyed by client ids. Every entry is a dictionary with a number of
+            `updates` containing changes of the client, a number of `operations`, ie. individual
+            insertions, made by the client, and a number of `bytes` taken by them.
+        """
+    def get_limits(self) -> Dict[str, int]:
+        """
+        Returns:
+            Limits set with `set_limits`, as a dictionary with `max_update_size`,
+            `max_string_length` and `max_depth` entries.
+        """
+    def add_update_transform(
+        self, encode: Callable[[bytes], bytes], decode: Callable[[bytes], bytes]
+    ) -> int:
+        """
+        Registers a transform of updates of this document, eg. compression or encryption. The
+        `encode` callback is applied to every update encoded by `encode_state_as_update`,
+        `YTransaction.diff_v1` and `AfterTransactionEvent.get_update`, while `decode` reverses it
+        before a payload is applied with `apply_update` or `YTransaction.apply_v1`. When many
+        transforms are registered, updates are encoded in order of their registration, and
+        decoded in reverse order.
+
+        Example::
+
+            import zlib
+            from y_py import YDoc, encode_state_as_update
+
+            doc = YDoc()
+            doc.add_update_transform(zlib.compress, zlib.decompress)
+            compressed = encode_state_as_update(doc)
+
+        Args:
+            encode: A callback receiving an encoded update and returning its transformed `bytes`.
+            decode: A callback reversing `encode`.
+
+        Returns:
+            An id which can be used to remove the transform with `remove_update_transform`.
+        """
+    def remove_update_transform(self, transform_id: int):
+        """
+        Removes a transform registered with `add_update_transform`.
+        """
+    def set_schema(self, name: str, schema: Union[Dict[str, Any], bool, None]):
+        """
+        Attaches a JSON Schema to a root type of a given `name`. Values inserted into the root
+        type and into `YArray` and `YMap` types nested in it are validated against the part of the
+        schema describing their location, and a `ValidationError` is raised before the shared type
+        is changed if they don't match. Removals of required map entries and of array elements
+        below `minItems` are rejected as well.
+
+        Schemas are compiled once, when they're attached. Keywords describing plain JSON values
+        are supported: `type`, `enum`, `const`, `minimum`, `maximum`, `exclusiveMinimum`,
+        `exclusiveMaximum`, `minLength`, `maxLength`, `items`, `minItems`, `maxItems`,
+        `properties`, `required` and `additionalProperties`. Shared types are validated as their
+        content, eg. a `YText` as a string, but edits of texts and XML types aren't checked. Remote
+        updates aren't checked either, use `add_update_filter` to reject them.
+
+        Example::
+
+            from y_py import YDoc, ValidationError
+
+            doc = YDoc()
+            doc.set_schema('tasks', {
+                'type': 'array',
+                'items': {
+                    'type': 'object',
+                    'properties': {'title': {'type': 'string'}, 'done': {'type': 'boolean'}},
+                    'required': ['title'],
+                },
+            })
+            tasks = doc.get_array('tasks')
+            with doc.begin_transaction() as txn:
+                tasks.append(txn, {'title': 'Write docs', 'done': False})
+                tasks.append(txn, {'done': True})  # raises ValidationError
+
+        Args:
+            name: The name of the root type.
+            schema: The schema, as a dictionary or a boolean, or `None` to detach the current
+                schema.
+
+        Raises:
+            ValueError: If the schema is malformed or uses unsupported keywords.
+            ValidationError: If the root type already exists and its content doesn't match the
+                schema.
+        """
+    def record_origins(self):
+        """
+        Starts recording which changes are made by transactions of each origin, eg. a connection
+        of a user passed as an origin of the updates it receives, so that they can be reverted
+        with `revert_origin`. Transactions without an origin are not recorded.
+        """
+    def revert_update(self, update: BinaryInput) -> Dict[str, int]:
+        """
+        Reverts changes made by an update, which has been applied to this document, where
+        possible: content inserted by the update is deleted, and content deleted by it is
+        inserted again as a copy. Map entries overwritten by the update are set back to their
+        previous values. Changes made on top of the update are preserved. An update should
+        only be reverted once, as deleted content is restored again otherwise.
+
+        Deleted content can only be restored by documents created with `skip_gc=True`, and
+        neither nested shared types nor formatting attributes are restored. Root types which
+        haven't been obtained with `get_*` methods are left as they are.
+
+        Args:
+            update: An update using lib0 v1 encoding.
+
+        Returns:
+            Numbers of `removed`, `restored` and `skipped` units of content, eg. characters of
+            a text or elements of an array.
+
+        Example:
+
+        ```
+        from y_py import YDoc, apply_update
+
+        doc = YDoc(skip_gc=True)
+        apply_update(doc, spam)
+        doc.revert_update(spam)
+        ```
+        """
+    def revert_origin(self, origin: Any) -> Dict[str, int]:
+        """
+        Reverts changes made by all transactions of a given origin since `record_origins` has
+        been called, eg. to remove everything a given user has contributed to this document.
+        Reverted changes are dropped from the record. See `revert_update` for the limitations.
+
+        A `RuntimeError` is raised if origins are not recorded.
+
+        Args:
+            origin: An origin of reverted transactions, compared by equality.
+
+        Returns:
+            Numbers of `removed`, `restored` and `skipped` units of content.
+
+        Example:
+
+        ```
+        from y_py import YDoc
+
+        doc = YDoc(skip_gc=True)
+        doc.record_origins()
+        with doc.begin_transaction() as txn, txn.with_origin('mallory'):
+            doc.get_text('text').extend(txn, 'spam')
+        doc.revert_origin('mallory')
+        assert str(doc.get_text('text')) == ''
+        ```
+        """
+    def start_autosave(
+        self, interval: float, target: Union[str, Callable[[bytes], None]]
+    ) -> Autosave:
+        """
+        Starts saving this document periodically, once per `interval` if it has changed. States
+        are encoded on a background thread, which keeps its own replica of this document and
+        only acquires the GIL to call a callback, so that persistence doesn't stall the main
+        loop of an application. Errors of saves are printed and logged as `autosave_error`
+        records.
+
+        Args:
+            interval: A number of seconds between saves, which has to be positive.
+            target: A path of a file, which is replaced atomically with the whole state of this
+                document using lib0 v1 encoding, or a `callback(state)` receiving the state as
+                `bytes`.
+
+        Returns:
+            An `Autosave`, which saves pending changes when it's stopped.
+
+        Example:
+
+        ```
+        from y_py import YDoc
+
+        doc = YDoc()
+        with doc.start_autosave(5.0, 'notes.ydoc'):
+            run_editor(doc)
+        ```
+        """
+    def on_destroy(self, callback: Callable[[], None]) -> int:
+        """
+        Registers a callback which is called once this document is destroyed with `destroy`.
+
+        Returns:
+            An id which can be used to remove the callback with `remove_on_destroy`.
+        """
+    def remove_on_destroy(self, callback_id: int):
+        """
+        Removes a callback registered with `on_destroy`.
+        """
+    def destroy(self):
+        """
+        Destroys this document, so that long-running servers can deterministically release
+        documents they don't need anymore, eg. once the last client leaves a room. Callbacks of
+        all subscriptions to this document and its shared types are dropped, which releases the
+        objects they reference and breaks reference cycles between the callbacks and the
+        document, and `on_destroy` callbacks are called. Destroying a document twice does nothing.
+
+        A destroyed document can't be changed anymore: starting a transaction, or obtaining a
+        root type, raises a `RuntimeError`, and so do iterators, views and helpers of its shared
+        types, like `Annotations` and `Cursor`. Transactions started beforehand should be
+        committed before the document is destroyed.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            doc.observe_after_transaction(lambda e: room.broadcast(e.get_update()))
+            doc.on_destroy(lambda: rooms.pop(room.name))
+            ...
+            doc.destroy()
+        """
+
+EncodedStateVector = bytes
+EncodedDeleteSet = bytes
+YDocUpdate = bytes
+BinaryInput = Union[bytes, bytearray, memoryview, List[int]]
+"""
+Binary payloads accepted by Ypy. `bytes` and other read-only, contiguous byte buffers (such as a
+`memoryview` of `bytes`) are read without copying. Writable buffers, such as `bytearray`, are
+copied once.
+"""
+
+class Autosave:
+    """
+    Periodically saves a document on a background thread, see `YDoc.start_autosave`. An autosave
+    can be used as a context manager, which stops it on exit.
+    """
+
+    saves: int
+    """Number of times the document has been saved so far."""
+    running: bool
+    """Checks if the document is still being saved."""
+
+    def flush(self):
+        """
+        Saves changes made so far right away, and blocks until they have been saved.
+        """
+    def stop(self):
+        """
+        Saves pending changes and stops the background thread. Changes made afterwards are not
+        saved anymore.
+        """
+    def __enter__(self) -> Autosave: ...
+    def __exit__(self, exception_type, exception_value, traceback) -> bool: ...
+
+class Batch:
+    """
+    A scope returned by `YDoc.batch()`, in which all transactions of a document are grouped into a
+    single one. Transactions started with `YDoc.begin_transaction` or `YDoc.transact`, as well as
+    updates applied with `apply_update`, share a transaction which is only committed once the
+    scope is exited. Observers are therefore called once for all the changes made in the scope,
+    and `AfterTransactionEvent.get_update` returns a single update containing all of them.
+
+    Changes can't be rolled back, so they are committed even if the scope is exited with an
+    exception. Nested batches share the transaction of the outermost batch.
+    """
+
+    def __enter__(self) -> YTransaction: ...
+    def __exit__(self, exception_type, exception_value, traceback) -> bool: ...
+
+class AfterTransactionEvent:
+    """
+    Holds transaction update information from a commit after state vectors have been compressed.
+    """
+
+    before_state: EncodedStateVector
+    """
+    Encoded state of YDoc before the transaction.
+    """
+    after_state: EncodedStateVector
+    """
+    Encoded state of the YDoc after the transaction.
+    """
+    delete_set: EncodedDeleteSet
+    """
+    Elements deleted by the associated transaction, which can be decoded with `DeleteSet.decode`.
+    """
+    origin: Any
+    """Origin of the transaction which produced this event, see `YTransaction.with_origin`."""
+
+    def get_update(self) -> YDocUpdate:
+        """
+        Returns:
+            Encoded payload of all updates produced by the transaction, transformed by transforms
+            registered with `YDoc.add_update_transform`.
+        """
+
+class Dispatcher:
+    """
+    Runs observer callbacks on a dedicated thread, which acquires the GIL on its own. This way a
+    thread committing transactions, eg. a request handler of a server, doesn't wait for callbacks
+    doing heavy work, such as broadcasting updates over the network or persisting them.
+
+    Callbacks are called one at a time, in order in which they have been queued. Exceptions raised
+    by callbacks are passed to an `on_error(exception)` callback if it's provided, and printed
+    otherwise. As Python runs signal handlers on the main thread only, signals such as
+    `KeyboardInterrupt` don't interrupt callbacks in the middle of their work.
+
+    Only callbacks registered with `YDoc.observe_after_transaction` can be dispatched, which
+    receive a `TransactionRecord` instead of an `AfterTransactionEvent`, as events are only valid
+    while transactions are being committed. A dispatcher can be used as a context manager, which
+    closes it on exit.
+
+    Example::
+
+        from y_py import Dispatcher, YDoc
+
+        dispatcher = Dispatcher()
+        doc = YDoc()
+        doc.observe_after_transaction(lambda record: broadcast(record.get_update()), dispatcher)
+    """
+
+    pending: int
+    """Number of callbacks which have been queued but haven't finished yet."""
+
+    def __init__(self, on_error: Optional[Callable[[BaseException], None]] = None): ...
+    def wait(self):
+        """
+        Blocks until all queued callbacks have finished.
+        """
+    def close(self):
+        """
+        Waits for queued callbacks to finish and stops the thread of this dispatcher. Callbacks
+        dispatched afterwards are called right away by the thread committing a transaction.
+        """
+    def __enter__(self) -> Dispatcher: ...
+    def __exit__(self, exception_type, exception_value, traceback) -> bool: ...
+
+class TransactionRecord:
+    """
+    A record of a committed transaction, passed to `YDoc.observe_after_transaction` callbacks run
+    by a `Dispatcher`. Unlike `AfterTransactionEvent`, it's encoded as soon as the transaction is
+    committed, so it remains valid afterwards.
+    """
+
+    before_state: EncodedStateVector
+    """
+    Encoded state of YDoc before the transaction.
+    """
+    after_state: EncodedStateVector
+    """
+    Encoded state of the YDoc after the transaction.
+    """
+    delete_set: EncodedDeleteSet
+    """
+    Elements deleted by the associated transaction.
+    """
+    origin: Any
+    """Origin of the transaction, see `YTransaction.with_origin`."""
+
+    def get_update(self) -> YDocUpdate:
+        """
+        Returns:
+            Encoded payload of all updates produced by the transaction, transformed by transforms
+            registered with `YDoc.add_update_transform`.
+        """
+
+class MmapDoc:
+    """
+    An experimental document shared by many processes, eg. workers of a gunicorn-style server,
+    through a memory-mapped file, without a central broker. Every process keeps its own replica of
+    the document, available as `doc`, which is merged with the state stored in the file by `sync`
+    and `transact`, while other processes are locked out of the file.
+
+    The file holds the whole state of the document, encoded as a single update, so it has to be
+    large enough to hold it: a new file is created with a given `capacity`, and writing a larger
+    state raises a `ValueError`. Locking relies on `fcntl.flock`, which is only available on Unix.
+
+    Example::
+
+        from y_py import MmapDoc
+
+        shared = MmapDoc('/tmp/document.ypy')
+        shared.transact(lambda txn: shared.doc.get_text('text').extend(txn, 'hello'))
+        ...
+        # in another process
+        shared = MmapDoc('/tmp/document.ypy')
+        shared.sync()
+        print(shared.doc.get_text('text'))
+    """
+
+    doc: YDoc
+    """The local replica of the document."""
+    capacity: int
+    """Size of the file, which limits the size of the stored state."""
+
+    def __init__(
+        self,
+        path: str,
+        capacity: Optional[int] = None,
+        client_id: Optional[int] = None,
+    ):
+        """
+        Opens a document stored in a file at a given `path`, creating the file if it doesn't exist
+        yet.
+
+        Args:
+            path: Path of the file.
+            capacity: Files smaller than `capacity` bytes are extended. Existing files keep
+                their size by default, while new files take 16 MiB.
+            client_id: Identifier of the local replica, which has to be unique among all
+                processes.
+        """
+    def sync(self) -> bool:
+        """
+        Merges changes made by other processes into the local replica, and stores changes of the
+        local replica in the file.
+
+        Returns:
+            `True` if the local replica has received new changes.
+        """
+    def transact(self, callback: Callable[[YTransaction], Any]) -> Any:
+        """
+        Calls a `callback(txn)` with a transaction of the local replica, which has been synced
+        beforehand, and stores the changes made by the callback in the file afterwards. Other
+        processes are locked out of the file in the meantime, so that none of their changes are
+        missed.
+
+        Returns:
+            The result of the callback.
+        """
+    def close(self):
+        """
+        Unmaps and closes the file. The local replica remains usable.
+        """
+    def __enter__(self) -> MmapDoc: ...
+    def __exit__(self, exception_type, exception_value, traceback) -> bool: ...
+
+class Awareness:
+    """
+    Presence information of the users of a document, eg. their names and cursor positions, which
+    is exchanged with the awareness protocol of y-protocols, but isn't stored in the document.
+    Every client has a state, which can be any JSON serializable value, and a clock which is
+    incremented whenever its state changes, so that replicas keep the latest state of every
+    client.
+
+    Example::
+
+        from y_py import Awareness, YDoc
+
+        doc = YDoc()
+        awareness = Awareness(doc)
+        awareness.observe(lambda changes, origin: print(changes))
+        awareness.set_local_state({"user": "alice", "cursor": 3})
+        send(awareness.encode_update())
+    """
+
+    client_id: int
+    def __init__(self, doc: YDoc):
+        """
+        Creates an awareness of users of a given document, where the local state belongs to the
+        client of the document.
+        """
+    def get_local_state(self) -> Any:
+        """
+        Returns:
+            The state of the local client, or `None` if it has no state.
+        """
+    def set_local_state(self, state: Any):
+        """
+        Sets a state of the local client, which has to be JSON serializable. Setting `None`
+        removes the local state, eg. when a user leaves a document.
+        """
+    def get_states(self) -> Dict[int, Any]:
+        """
+        Returns:
+            States of all clients with a state, keyed by their ids.
+        """
+    def remove_states(self, clients: List[int], origin: Any = None):
+        """
+        Removes states of given clients, eg. once a connection of a remote client is closed.
+        Removals are propagated to other replicas by `encode_update`.
+        """
+    def encode_update(self, clients: Optional[List[int]] = None) -> bytes:
+        """
+        Encodes states of given `clients`, or of all known clients, as an update of the
+        awareness protocol, which can be applied to other replicas with `apply_update`.
+        """
+    def apply_update(self, update: bytes, origin: Any = None):
+        """
+        Applies an update encoded by `encode_update` of another replica. Observers are called
+        with a given `origin`.
+        """
+    def observe(self, callback: Callable[[Dict[str, List[int]], Any], None]) -> int:
+        """
+        Subscribes a `callback(changes, origin)` which is called whenever states of clients
+        change.
+
+        Args:
+            callback: A callback receiving a dictionary with lists of `added`, `updated` and
+                `removed` client ids, and an origin of the change, which is "local" for changes
+                of the local state.
+
+        Returns:
+            An id which can be used to cancel the subscription with `unobserve`.
+        """
+    def unobserve(self, subscription_id: int):
+        """
+        Cancels a subscription created with `observe`.
+        """
+
+class Presence:
+    """
+    A list of users present in a document, built on top of an `Awareness`. The local user
+    announces its `state`, which is renewed by `heartbeat` so that other replicas know it's still
+    there, while remote users which haven't been heard of for `ttl` seconds are removed by
+    `prune`. Callbacks registered with `on_join` and `on_leave` are called whenever remote users
+    appear or disappear, either because of awareness updates received from other replicas or of
+    `prune`.
+
+    Example::
+
+        from y_py import Awareness, Presence, YDoc
+
+        awareness = Awareness(YDoc())
+        presence = Presence(awareness, {"user": "alice"}, ttl=30)
+        presence.on_join(lambda client, state: print(state["user"], "joined"))
+        presence.on_leave(lambda client: print(client, "left"))
+
+        # every few seconds
+        if presence.heartbeat():
+            send(awareness.encode_update([awareness.client_id]))
+        presence.prune()
+    """
+
+    ttl: float
+    def __init__(self, awareness: Awareness, state: Any, ttl: float = 30):
+        """
+        Creates a presence of the local user of a given `awareness`, with a given `state`.
+        Remote users are considered gone once they haven't updated their states for `ttl`
+        seconds.
+        """
+    def set_state(self, state: Any):
+        """
+        Changes the state of the local user.
+        """
+    def peers(self) -> Dict[int, Any]:
+        """
+        Returns:
+            States of remote users which are present, keyed by their client ids.
+        """
+    def heartbeat(self) -> bool:
+        """
+        Renews the state of the local user, if it hasn't been updated for half of `ttl`.
+
+        Returns:
+            `True` if the state has been renewed, in which case the local state should be sent to
+            other replicas with `Awareness.encode_update`.
+        """
+    def prune(self) -> List[int]:
+        """
+        Removes states of remote users which haven't been updated for `ttl`, and calls
+        `on_leave` callbacks for each of them.
+
+        Returns:
+            Client ids of the removed users.
+        """
+    def on_join(self, callback: Callable[[int, Any], None]) -> int:
+        """
+        Subscribes a `callback(client_id, state)` which is called whenever a remote user joins.
+
+        Returns:
+            An id which can be used to cancel the subscription with `unobserve`.
+        """
+    def on_leave(self, callback: Callable[[int], None]) -> int:
+        """
+        Subscribes a `callback(client_id)` which is called whenever a remote user leaves.
+
+        Returns:
+            An id which can be used to cancel the subscription with `unobserve`.
+        """
+    def unobserve(self, subscription_id: int):
+        """
+        Cancels a subscription created with `on_join` or `on_leave`.
+        """
+    def close(self):
+        """
+        Removes the state of the local user, eg. when the user leaves a document, and stops
+        calling `on_join` and `on_leave` callbacks.
+        """
+
+class SyncConnection:
+    """
+    Drives the y-protocols sync and awareness protocols of a single connection to a remote peer,
+    eg. a client of a server or the server of a client, independently of the transport carrying
+    its messages, so it can be used with ZeroMQ, WebRTC data channels or plain TCP as well as
+    websockets. Messages are compatible with y-websocket.
+
+    `greeting` returns the first message to send once the connection is open. Every message
+    received from the peer is passed to `receive`, which returns a list of replies to send back.
+    Local changes are sent with `encode_update`, and changes of awareness states with
+    `encode_awareness`. Updates received from the peer are applied with the connection as their
+    origin, so that observers can avoid sending them back.
+
+    Example::
+
+        from y_py import Awareness, SyncConnection, YDoc
+
+        doc = YDoc()
+        conn = SyncConnection(doc, Awareness(doc))
+        doc.observe_after_transaction(
+            lambda e: e.origin is not conn and sock.send(conn.encode_update(e.get_update()))
+        )
+        sock.send(conn.greeting())
+        for message in sock:
+            for reply in conn.receive(message):
+                sock.send(reply)
+    """
+
+    doc: YDoc
+    awareness: Optional[Awareness]
+    synced: bool
+    """
+    Set once the peer has sent all updates missing from the document, in reply to the greeting.
+    """
+    denied: Optional[str]
+    """
+    A reason given by the peer for denying access to the document, if it did.
+    """
+    def __init__(self, doc: YDoc, awareness: Optional[Awareness] = None):
+        """
+        Creates a connection synchronizing a given document, and optionally awareness states of
+        its users, with a remote peer.
+        """
+    def greeting(self) -> bytes:
+        """
+        Returns:
+            The first message to send to the peer: a sync step 1 message with a state vector of
+            the document, to which the peer replies with updates missing from the document.
+        """
+    def receive(self, message: bytes) -> List[bytes]:
+        """
+        Handles a message received from the peer. Sync messages are answered, or applied to the
+        document, awareness updates are applied to the awareness of this connection, and
+        awareness queries are answered with all known awareness states. An authentication
+        message denying access sets `denied`.
+
+        Raises:
+            EncodingError: If the message is malformed or of an unknown type.
+
+        Returns:
+            A list of messages to send back to the peer.
+        """
+    def encode_update(self, update: bytes) -> bytes:
+        """
+        Encodes an update of the document, eg. obtained with `AfterTransactionEvent.get_update`,
+        as a sync message to send to the peer.
+        """
+    def encode_awareness(self, clients: Optional[List[int]] = None) -> bytes:
+        """
+        Encodes awareness states of given `clients`, or of all known clients, as an awareness
+        message to send to the peer.
+
+        Raises:
+            ValueError: If this connection has no awareness.
+        """
+
+class LocalHub:
+    """
+    Connects documents living in the same process, eg. replicas of a test or a simulation, or
+    mirrors of rooms on a single server, and propagates updates and awareness states between
+    them automatically. Changes made to a member are sent to all other members, and applied with
+    the hub as their origin.
+
+    Messages are delivered right away by default. Given a `latency`, in seconds, they are
+    delivered once the simulated time of the hub has been moved forward by at least that much
+    with `advance`, so that concurrent edits can be reproduced deterministically. `flush`
+    delivers all messages in flight.
+
+    An unreliable network can be simulated as well: each message is delayed by a further random
+    amount of up to `jitter` seconds, which reorders messages, and is lost with a probability of
+    `drop_rate`. Lost messages are never sent again. Random choices are derived from a `seed`, so
+    that runs are reproducible. `y_py.testing.lossy_channel` connects two documents this way.
+
+    Example::
+
+        from y_py import LocalHub, YDoc
+
+        hub = LocalHub(latency=0.1)
+        a, b = YDoc(), YDoc()
+        hub.join(a)
+        hub.join(b)
+        with a.begin_transaction() as txn:
+            a.get_text("text").extend(txn, "hello")
+        assert str(b.get_text("text")) == ""
+        hub.advance(0.1)
+        assert str(b.get_text("text")) == "hello"
+    """
+
+    latency: float
+    """
+    Delay of messages sent between members, in seconds.
+    """
+    time: float
+    """
+    Simulated time of the hub, in seconds, which is moved forward by `advance`.
+    """
+    pending: int
+    """
+    Number of messages in flight.
+    """
+    dropped: int
+    """
+    Number of messages lost so far, following the `drop_rate` of the hub.
+    """
+    docs: List[YDoc]
+    """
+    Documents connected to the hub, in order in which they have joined.
+    """
+    def __init__(
+        self,
+        latency: float = 0.0,
+        jitter: float = 0.0,
+        drop_rate: float = 0.0,
+        seed: int = 0,
+    ): ...
+    def join(self, doc: YDoc, awareness: Optional[Awareness] = None):
+        """
+        Connects a document, and optionally an awareness of its users, to the hub. The document
+        exchanges its whole state, and awareness states, with all other members.
+        """
+    def leave(self, doc: YDoc):
+        """
+        Disconnects a document from the hub. Messages in flight to the document are dropped.
+        """
+    def advance(self, seconds: float) -> int:
+        """
+        Moves the simulated time forward by a given number of `seconds`, and delivers messages
+        which are due.
+
+        Returns:
+            A number of delivered messages.
+        """
+    def flush(self) -> int:
+        """
+        Delivers all messages in flight, moving the simulated time forward as needed.
+
+        Returns:
+            A number of delivered messages.
+        """
+
+class ReadOnlyDoc:
+    """
+    A read-only view of a `YDoc`, returned by `YDoc.readonly_view`. Shared types obtained from it
+    can be read and observed as usual, but raise a `ReadOnlyError` when passed a transaction, which
+    all modifying methods require. This makes it possible to hand out documents to code which
+    should not change them, eg. connections of users with read-only permissions.
+
+    Methods raise a `RuntimeError` once the underlying document has been dropped.
+    """
+
+    client_id: int
+    """Globally unique identifier of the underlying `YDoc`."""
+
+    def get_text(self, name: str) -> ReadOnly:
+        """
+        Returns:
+            A read-only `YText` root type of a given `name`.
+        """
+    def get_array(self, name: str) -> ReadOnly:
+        """
+        Returns:
+            A read-only `YArray` root type of a given `name`.
+        """
+    def get_map(self, name: str) -> ReadOnly:
+        """
+        Returns:
+            A read-only `YMap` root type of a given `name`.
+        """
+    def get_xml_element(self, name: str) -> ReadOnly:
+        """
+        Returns:
+            A read-only `YXmlElement` root type of a given `name`.
+        """
+    def get_xml_text(self, name: str) -> ReadOnly:
+        """
+        Returns:
+            A read-only `YXmlText` root type of a given `name`.
+        """
+    def state_hash(self) -> str:
+        """
+        Returns:
+            A hash of the state of the underlying document, see `YDoc.state_hash`.
+        """
+    def snapshot(self) -> bytes:
+        """
+        Returns:
+            A snapshot of the underlying document, see `YDoc.snapshot`.
+        """
+    def stats(self) -> Dict[str, Any]:
+        """
+        Returns:
+            Statistics of the underlying document, see `YDoc.stats`.
+        """
+    def observe_after_transaction(
+        self,
+        callback: Callable[[Union[AfterTransactionEvent, TransactionRecord]]],
+        dispatcher: Optional[Dispatcher] = None,
+        weak: bool = False,
+    ) -> SubscriptionId:
+        """
+        Subscribes a callback to transactions committed on the underlying document, optionally run
+        by a `dispatcher` or held by a weak reference.
+        """
+
+class ReadOnly:
+    """
+    A read-only proxy of a shared type, map view or event obtained from a `ReadOnlyDoc`. Attributes
+    are forwarded to the proxied object, and shared types they return are wrapped in read-only
+    proxies as well, so do the events passed to observers. Calling a method with a transaction, or
+    calling `transaction`, raises a `ReadOnlyError`. The `doc` attribute of proxied shared types
+    returns the `ReadOnlyDoc`.
+    """
+
+    def __getattr__(self, name: str) -> Any: ...
+    def __len__(self) -> int: ...
+    def __getitem__(self, key: Any) -> Any: ...
+    def __contains__(self, item: Any) -> bool: ...
+    def __iter__(self) -> Iterator[Any]: ...
+
+class RootProxy:
+    """
+    A proxy returned by `YDoc.root`, which gives access to root types of a document as its
+    attributes, eg. `doc.root.tasks` in place of `doc.get_array("tasks")`. Root types which aren't
+    valid Python identifiers can be accessed with `doc.root["my-tasks"]`.
+
+    The type of a root type is resolved in the following order:
+
+    - a type declared by calling the proxy, eg. `doc.root(tasks=YArray)`, which returns a new
+      proxy with the declarations,
+    - the type of an existing root type,
+    - a type inferred from contents of a root type received from a remote peer, which is a `YMap`
+      if it has any entries, a `YText` if it contains a string and a `YArray` otherwise. XML root
+      types can't be told apart from these, so they have to be declared,
+    - a type declared by a JSON Schema attached with `YDoc.set_schema`, whose `type` is either
+      "array", "object" or "string".
+
+    Missing root types are created. Accessing a root type whose type can't be resolved raises an
+    `AttributeError`, and accessing one as a type different from its actual type a `TypeError`.
+
+    Example::
+
+        from y_py import YArray, YDoc
+
+        doc = YDoc()
+        root = doc.root(tasks=YArray)
+        with doc.begin_transaction() as txn:
+            root.tasks.append(txn, "Write docs")
+        assert list(doc.root.tasks) == ["Write docs"]
+    """
+
+    def __call__(self, **types: type) -> RootProxy:
+        """
+        Declares types of root types, which take precedence over inferred types.
+
+        Args:
+            types: Ypy shared type classes, eg. `YArray`, keyed by names of root types.
+
+        Returns:
+            A new proxy with the declarations of this proxy and given `types`.
+        """
+    def __getattr__(self, name: str) -> Any: ...
+    def __getitem__(self, name: str) -> Any: ...
+    def __dir__(self) -> List[str]: ...
+
+class Simulator:
+    """
+    Simulates concurrent editing of a document by a number of replicas, which exchange their
+    updates in a random order. Simulations are deterministic: the same `seed` always produces the
+    same operations and interleavings, so failures can be reproduced.
+
+    Replicas perform random operations on "text", "array" and "map" root types by default. Custom
+    `operations` can be used instead to exercise a schema of an application. Each of them is a
+    callable receiving a transaction, a replica and a `random.Random` instance derived from the
+    seed, which should be the only source of randomness of an operation.
+
+    Example:
+
+    ```
+    from y_py import Simulator
+
+    def add_todo(txn, doc, rand):
+        todos = txn.get_array('todos')
+        todos.insert(txn, rand.randint(0, len(todos)), {'done': False})
+
+    sim = Simulator(replicas=3, seed=42, operations=[add_todo])
+    sim.run(200)
+    ```
+    """
+
+    replicas: List[YDoc]
+    """Simulated documents, which have client ids starting from 1."""
+    seed: int
+    history: List[str]
+    """Descriptions of all steps performed so far."""
+    def __init__(
+        self,
+        replicas: int = 2,
+        seed: int = 0,
+        operations: Optional[List[Callable[[YTransaction, YDoc, Random], None]]] = None,
+    ): ...
+    def step(self, sync_probability: float = 0.3) -> str:
+        """
+        Performs a single random step: either an operation on one of the replicas, or delivery of
+        updates from one replica to another, which happens with a given `sync_probability`.
+
+        Returns:
+            A description of the step.
+        """
+    def run(self, steps: int, sync_probability: float = 0.3):
+        """
+        Performs a given number of random `steps`, then synchronizes all replicas and checks that
+        they have converged.
+        """
+    def sync(self, source: int, target: int):
+        """
+        Delivers all updates of a `source` replica which a `target` replica is missing.
+        """
+    def sync_all(self):
+        """
+        Exchanges updates between all replicas, so that all of them have the same state.
+        """
+    def assert_converged(self):
+        """
+        Raises an `AssertionError` unless all replicas have the same state.
+        """
+
+class StateVector:
+    """
+    A state vector of a document, which maps ids of clients to the next clocks expected from them.
+    It describes which updates a document has seen, eg. to compute updates missing from a peer
+    with `encode_state_as_update`, which accepts `StateVector` instances wherever an encoded state
+    vector is expected.
+
+    State vectors are partially ordered: `a >= b` if `a` dominates `b`, which means that a
+    document with state `a` has seen all updates seen by a document with state `b`. Vectors of
+    replicas which have made concurrent changes are neither greater nor smaller than each other.
+
+    Example::
+
+        from y_py import StateVector
+
+        remote = StateVector.decode(payload)
+        if not doc.state_vector() >= remote:
+            request_missing_updates(remote)
+    """
+
+    def __init__(self, clocks: Optional[Dict[int, int]] = None):
+        """
+        Creates a state vector with given `clocks` of clients, which is empty by default.
+        """
+    @staticmethod
+    def decode(data: BinaryInput) -> StateVector:
+        """
+        Decodes a state vector encoded using lib0 v1 encoding, eg. by `encode_state_vector`.
+        """
+    def encode(self) -> EncodedStateVector:
+        """
+        Encodes this state vector using lib0 v1 encoding.
+        """
+    def dominates(self, other: StateVector) -> bool:
+        """
+        Returns:
+            `True` if this state vector has seen all clocks of `other` state vector.
+        """
+    def merge(self, other: StateVector) -> StateVector:
+        """
+        Returns:
+            A new state vector with the greater clock of every client of this and `other` state
+            vector.
+        """
+    def __getitem__(self, client: int) -> int:
+        """
+        Returns:
+            A clock of a given client, which is 0 for unknown clients.
+        """
+    def __contains__(self, client: int) -> bool: ...
+    def __len__(self) -> int: ...
+    def __iter__(self) -> Iterator[int]: ...
+    def items(self) -> List[Tuple[int, int]]:
+        """
+        Returns:
+            A list of `(client, clock)` pairs, ordered by client ids.
+        """
+    def to_dict(self) -> Dict[int, int]: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+    def __ge__(self, other: StateVector) -> bool: ...
+    def __gt__(self, other: StateVector) -> bool: ...
+    def __le__(self, other: StateVector) -> bool: ...
+    def __lt__(self, other: StateVector) -> bool: ...
+
+class DeleteSet:
+    """
+    A set of deleted items of a document, described by ranges of their clocks grouped by ids of
+    clients which have created them. Unlike state vectors, delete sets reflect deletions, which
+    makes them necessary to compact stored updates without losing information, as done by
+    y-leveldb.
+
+    Ranges of every client are kept sorted, with overlapping and adjacent ranges merged.
+
+    Example::
+
+        from y_py import DeleteSet
+
+        deleted = DeleteSet()
+        for update in stored_updates:
+            deleted = deleted.merge(DeleteSet.from_update(update))
+        for client, ranges in deleted.items():
+            print(client, ranges)
+    """
+
+    def __init__(self, ranges: Optional[Dict[int, List[Tuple[int, int]]]] = None):
+        """
+        Creates a delete set with given `ranges` of clients, each a list of `(clock, len)` pairs,
+        which is empty by default.
+        """
+    @staticmethod
+    def decode(data: BinaryInput) -> DeleteSet:
+        """
+        Decodes a delete set encoded using lib0 v1 encoding, eg. `AfterTransactionEvent.delete_set`.
+        """
+    @staticmethod
+    def from_update(update: BinaryInput) -> DeleteSet:
+        """
+        Reads a delete set of an update encoded using lib0 v1 encoding.
+        """
+    def encode(self) -> EncodedDeleteSet:
+        """
+        Encodes this delete set using lib0 v1 encoding.
+        """
+    def add(self, client: int, clock: int, len: int):
+        """
+        Marks `len` items of a given client, starting from `clock`, as deleted.
+        """
+    def merge(self, other: DeleteSet) -> DeleteSet:
+        """
+        Returns:
+            A new delete set with items deleted in this or `other` delete set.
+        """
+    def is_deleted(self, client: int, clock: int, len: int = 1) -> bool:
+        """
+        Returns:
+            `True` if all `len` items of a given client, starting from `clock`, are deleted.
+        """
+    def __getitem__(self, client: int) -> List[Tuple[int, int]]:
+        """
+        Returns:
+            Deleted ranges of a given client as `(clock, len)` pairs, which is empty for clients
+            without deletions.
+        """
+    def __contains__(self, client: int) -> bool: ...
+    def __len__(self) -> int: ...
+    def __iter__(self) -> Iterator[int]: ...
+    def items(self) -> List[Tuple[int, List[Tuple[int, int]]]]:
+        """
+        Returns:
+            A list of `(client, ranges)` pairs, ordered by client ids.
+        """
+    def __eq__(self, other: object) -> bool: ...
+    def __ne__(self, other: object) -> bool: ...
+
+class DocRegistry:
+    """
+    Manages a set of named `YDoc` instances, eg. collaborative rooms of a server.
+
+    Documents are loaded lazily on their first `acquire` using an optional `load(name)` callback,
+    which returns a document update or `None` for new documents. Each `acquire` must be paired with
+    a `release`. Documents which are no longer in use for at least `idle_timeout` seconds are
+    unloaded by `unload_idle`, after their state has been passed to an optional `save(name, update)`
+    callback.
+
+    Example:
+
+    ```
+    from y_py import DocRegistry
+
+    storage = {}
+    registry = DocRegistry(load=storage.get, save=storage.__setitem__, idle_timeout=60)
+    registry.observe(lambda name, update: broadcast(name, update))
+
+    doc = registry.acquire('room')
+    ...
+    registry.release('room')
+    registry.unload_idle()
+    ```
+    """
+
+    def __init__(
+        self,
+        load: Optional[Callable[[str], Optional[BinaryInput]]] = None,
+        save: Optional[Callable[[str, YDocUpdate], Any]] = None,
+        idle_timeout: float = 0.0,
+    ): ...
+    def acquire(self, name: str) -> YDoc:
+        """
+        Returns a document registered under a given `name`, loading it if necessary, and marks it as
+        being in use until a matching `release` call.
+        """
+    def release(self, name: str):
+        """
+        Marks a document previously returned by `acquire` as no longer used by the caller.
+
+        Raises:
+            KeyError: If the document is not loaded.
+            ValueError: If the document has been released more times than acquired.
+        """
+    def refcount(self, name: str) -> int:
+        """
+        Returns:
+            A number of `acquire` calls not yet matched by `release` for a given document.
+        """
+    def unload_idle(self) -> List[str]:
+        """
+        Saves and unloads all documents which haven't been in use for at least `idle_timeout`
+        seconds.
+
+        Returns:
+            Names of unloaded documents.
+        """
+    def unload(self, name: str):
+        """
+        Saves and unloads a given document.
+
+        Raises:
+            ValueError: If the document is still in use.
+        """
+    def flush(self):
+        """
+        Passes a current state of all loaded documents to the `save` callback.
+        """
+    def observe(
+        self, callback: Callable[[str, YDocUpdate], None], name: Optional[str] = None
+    ) -> int:
+        """
+        Subscribes a `callback(name, update)` to updates of all documents managed by this registry,
+        or only to the document called `name` if given. Subscriptions outlive unloading and
+        reloading of documents.
+
+        Returns:
+            A subscription id which can be used to cancel the callback with `unobserve`.
+        """
+    def unobserve(self, subscription_id: int):
+        """
+        Cancels the observer callback associated with the `subscription_id`.
+        """
+    def names(self) -> List[str]:
+        """
+        Returns:
+            Names of all loaded documents.
+        """
+    def __contains__(self, name: str) -> bool: ...
+    def __len__(self) -> int: ...
+    def __repr__(self) -> str: ...
+
+def encode_state_vector(doc: YDoc) -> EncodedStateVector:
+    """
+    Encodes a state vector of a given Ypy document into its binary representation using lib0 v1
+    encoding. State vector is a compact representation of updates performed on a given document and
+    can be used by `encode_state_as_update` on remote peer to generate a delta update payload to
+    synchronize changes between peers.
+
+    Example::
+
+        from y_py import YDoc, encode_state_vector, encode_state_as_update, apply_update from y_py
+
+        # document on machine A
+        local_doc = YDoc()
+        local_sv = encode_state_vector(local_doc)
+
+        # document on machine B
+        remote_doc = YDoc()
+        remote_delta = encode_state_as_update(remote_doc, local_sv)
+
+        apply_update(local_doc, remote_delta)
+
+    """
+
+def encode_state_as_update(
+    doc: YDoc, vector: Optional[Union[BinaryInput, StateVector]] = None
+) -> YDocUpdate:
+    """
+    Encodes all updates that have happened since a given version `vector` into a compact delta
+    representation using lib0 v1 encoding. If `vector` parameter has not been provided, generated
+    delta payload will contain all changes of a current Ypy document, working effectively as its
+    state snapshot.
+
+    Example::
+
+        from y_py import YDoc, encode_state_vector, encode_state_as_update, apply_update
+
+        # document on machine A
+        local_doc = YDoc()
+        local_sv = encode_state_vector(local_doc)
+
+        # document on machine B
+        remote_doc = YDoc()
+        remote_delta = encode_state_as_update(remote_doc, local_sv)
+
+        apply_update(local_doc, remote_delta)
+    """
+
+def apply_update(doc: YDoc, diff: BinaryInput):
+    """
+    Applies delta update generated by the remote document replica to a current document. This
+    method assumes that a payload maintains lib0 v1 encoding format.
+
+    Example::
+
+        from y_py import YDoc, encode_state_vector, encode_state_as_update, apply_update
+
+        # document on machine A
+        local_doc = YDoc()
+        local_sv = encode_state_vector(local_doc)
+
+        # document on machine B
+        remote_doc = YDoc()
+        remote_delta = encode_state_as_update(remote_doc, local_sv)
+
+        apply_update(local_doc, remote_delta)
+    """
+
+def validate_update(update: BinaryInput) -> List[str]:
+    """
+    Checks if an update using lib0 v1 encoding can be applied to a document, without applying it.
+    Malformed encoding is reported together with its byte offset.
+
+    Example:
+
+    ```
+    from y_py import apply_update, validate_update
+
+    problems = validate_update(payload)
+    if problems:
+        log.warning('Rejected update: %s', '; '.join(problems))
+    else:
+        apply_update(doc, payload)
+    ```
+
+    Returns:
+        Descriptions of problems found in the update. The list is empty if the update is valid.
+    """
+
+def copy_shared(
+    source: Union[YText, YArray, YMap, YXmlElement, YXmlText],
+    target: Union[YDoc, YMap, YArray, YXmlElement],
+    key: Optional[Union[str, int]] = None,
+    txn: Optional[YTransaction] = None,
+) -> Union[YText, YArray, YMap, YXmlElement, YXmlText]:
+    """
+    Deep-copies a shared type into another document (or another place of the same document) as
+    fresh items, which are independent from the source.
+
+    A `target` can be:
+
+    - a `YDoc`, in which case `key` is the name of the root type receiving the copied content. If
+      `txn` is not given, a new transaction of the target document is used.
+    - a `YMap`, in which case the copy is stored under `key`.
+    - a `YArray`, in which case the copy is inserted at index `key`, or appended if it's `None`.
+    - a `YXmlElement`, in which case an XML `source` is inserted as a child at index `key`, or
+      appended if it's `None`.
+
+    `YText` content is copied as a plain string, without formatting attributes. XML types can only
+    be copied into document roots and other XML elements.
+
+    Example:
+
+    ```
+    from y_py import YDoc, copy_shared
+
+    notebook = YDoc()
+    other = YDoc()
+    page = notebook.get_map('page')
+    with other.begin_transaction() as txn:
+        pages = txn.get_array('pages')
+        copy_shared(page, pages, txn=txn)
+    ```
+
+    Returns:
+        The copied shared type.
+    """
+
+def bind(
+    map: YMap,
+    target: MutableMapping[str, Any],
+    conflict: Literal["crdt-wins", "local-wins"] = "crdt-wins",
+) -> Binding:
+    """
+    Binds a `YMap` to a plain, dictionary-like `target` object, eg. a `dict`, which is kept updated
+    with changes of the map. Changes made to the target are written back to the map with
+    `Binding.flush_local_changes`. This makes it possible to use a synchronized map as a regular
+    dictionary.
+
+    The target is filled with entries of the map right away. `conflict` decides what happens when
+    the map receives a change of an entry which has been changed in the target since the last
+    synchronization: with "crdt-wins" the change of the target is overwritten, while with
+    "local-wins" it's kept and written to the map by the next flush. Entries of the target which
+    are missing from the map are kept in both cases.
+
+    Example:
+
+    ```
+    from y_py import YDoc, bind
+
+    doc = YDoc()
+    settings = {}
+    binding = bind(doc.get_map('settings'), settings)
+    settings['theme'] = 'dark'
+    binding.flush_local_changes()
+    ```
+    """
+
+class Binding:
+    """
+    Keeps a plain, dictionary-like `target` object in sync with a `YMap`, as returned by `bind`.
+
+    Changes of the map, including changes of nested shared types, are copied to the target as
+    soon as they're committed, with nested shared types converted into `str`, `list` and `dict`
+    values. Changes of the target are written to the map by `flush_local_changes`, which compares
+    top level entries of the target with their values as of the last synchronization.
+    """
+
+    target: MutableMapping[str, Any]
+    """The dictionary-like object kept in sync with the map."""
+    def flush_local_changes(self, txn: Optional[YTransaction] = None) -> List[str]:
+        """
+        Writes entries of the target which have been changed since the last synchronization to
+        the map, within a given transaction or a new transaction of the document of the map.
+        Nested values are written as plain values, replacing nested shared types.
+
+        Returns:
+            Keys of the written entries.
+        """
+    def unbind(self):
+        """
+        Stops copying changes of the map to the target.
+        """
+
+def split_update(
+    update: BinaryInput,
+    roots: Optional[List[str]] = None,
+    doc: Optional[YDoc] = None,
+) -> Dict[Optional[str], bytes]:
+    """
+    Partitions a lib0 v1 encoded `update` into sub-updates, one per root type changed by it. This
+    allows to implement selective sync, eg. to only send changes of a "metadata" root type to some
+    of the peers.
+
+    Structs of a client are numbered consecutively regardless of the root type they belong to.
+    Structs of other root types are therefore replaced with garbage collected placeholders, which
+    makes every sub-update complete on its own. As a consequence, a document which applied a
+    sub-update of one root type ignores changes of other root types made at the same time.
+
+    Example:
+
+    ```
+    from y_py import encode_state_as_update, split_update
+
+    update = encode_state_as_update(doc, remote_state_vector)
+    metadata_update = split_update(update, roots=['metadata']).get('metadata')
+    ```
+
+    Args:
+        update: An update to split.
+        roots: Names of root types to return sub-updates of. All of them are returned by default.
+        doc: A document containing the state the update has been produced against. It's used to
+            locate changes referring to items which are not part of the update.
+
+    Returns:
+        Sub-updates keyed by root type names. Changes which cannot be attributed to a root type are
+        stored under a `None` key.
+    """
+
+def explain_merge(update_a: BinaryInput, update_b: BinaryInput) -> List[Dict[str, Any]]:
+    """
+    Describes edits made concurrently by two replicas to the same parts of a document: overlapping
+    or adjacent ranges of the same text or array, and the same keys of a map. This allows to show
+    which parts of a merged document have been edited by both sides, eg. to let users review them.
+
+    Example:
+
+    ```
+    from y_py import encode_state_as_update, explain_merge
+
+    report = explain_merge(encode_state_as_update(mine), encode_state_as_update(theirs))
+    for edit in report:
+        print(f"Both of you edited {'/'.join(map(str, edit['path']))}")
+    ```
+
+    Args:
+        update_a: A state of the first replica, encoded using lib0 v1 encoding.
+        update_b: A state of the second replica, encoded using lib0 v1 encoding.
+
+    Returns:
+        A list of overlapping edits, each of them a dictionary with a `kind` ("text", "array" or
+        "map") and a `path` leading to the edited shared type from its root type. Ranges `a` and
+        `b` of texts and arrays are `(start, end)` index ranges of content changed by each replica
+        in the merged document, where removed content takes no space. Map edits have a `key` and
+        a `winner`, which is "a" or "b" depending on whose value has been kept after the merge, or
+        `None` if the entry has been removed.
+    """
+
+def replay_updates(
+    updates: Iterable[BinaryInput], observer: Callable[[Dict[str, Any]], None]
+) -> YDoc:
+    """
+    Applies updates to a new document one by one, and calls an `observer` with a summary of
+    changes made by each of them. This allows to build a timeline of a document history from a log
+    of updates, eg. for auditing, without creating a document per update or encoding intermediate
+    states.
+
+    Items of an update may only be integrated once updates they depend on have been applied, in
+    which case they are reported by the summary of the latter update.
+
+    Example:
+
+    ```
+    from y_py import replay_updates
+
+    def record(summary):
+        for client, (start, end) in summary['inserted'].items():
+            timeline.append((summary['index'], client, end - start, summary['paths']))
+
+    doc = replay_updates(stored_updates, record)
+    ```
+
+    Args:
+        updates: An iterable of updates encoded using lib0 v1 encoding, eg. a generator reading
+            them from a storage.
+        observer: A callback receiving a summary of each update, which is a dictionary with:
+
+            - `index`: a position of the update in `updates`,
+            - `inserted`: a dictionary of ranges `(start, end)` of clocks of items integrated by
+              the update, keyed by client ids,
+            - `deleted`: a `DeleteSet` of items deleted by the update,
+            - `paths`: sorted locations of changes, as passed to filters registered with
+              `YDoc.add_update_filter`.
+
+    Returns:
+        The document with all updates applied.
+    """
+
+class UpdateBuffer:
+    """
+    Collects updates of a document while they cannot be sent, eg. when an offline-first client has
+    no connection to its server. Pending updates are merged into a single update once there are
+    `max_updates` of them, or once the oldest of them is older than `max_delay` seconds, so the
+    buffer doesn't grow with every single edit. Updates are encoded using lib0 v1 encoding.
+
+    Example:
+
+    ```
+    from y_py import YDoc, UpdateBuffer
+
+    doc = YDoc()
+    buffer = UpdateBuffer(doc, max_updates=50)
+    ...
+    update = buffer.drain()
+    if update is not None:
+        send(update)
+    ```
+    """
+
+    size: int
+    """Total number of bytes of all pending updates."""
+    closed: bool
+    """True if this buffer no longer collects updates of its document."""
+
+    def __init__(
+        self, doc: YDoc, max_updates: int = 100, max_delay: Optional[float] = None
+    ):
+        """
+        Starts collecting updates of a given `doc`.
+
+        Args:
+            doc: The document which updates are collected.
+            max_updates: Number of pending updates which triggers their compaction.
+            max_delay: Age in seconds of the oldest pending update which triggers compaction. Age
+                is checked whenever a new update arrives.
+        """
+    def compact(self):
+        """
+        Merges all pending updates into a single update right away.
+        """
+    def drain(self) -> Optional[YDocUpdate]:
+        """
+        Empties the buffer.
+
+        Returns:
+            All pending updates merged into a single update, or `None` if there were no pending
+            updates.
+        """
+    def close(self):
+        """
+        Stops collecting updates. Updates which are already pending can still be drained.
+        """
+    def __len__(self) -> int:
+        """
+        Returns:
+            The number of pending updates.
+        """
+
+class OriginScope:
+    """
+    A scope returned by `YTransaction.with_origin`, in which changes made with a transaction are
+    tagged with a given origin. Scopes can be nested, and the origin of the enclosing scope is
+    restored once a scope is exited.
+    """
+
+    def __enter__(self) -> YTransaction: ...
+    def __exit__(self, exception_type, exception_value, traceback) -> bool: ...
+
+class YTransaction:
+    """
+    A transaction that serves as a proxy to document block store. Ypy shared data types execute
+    their operations in a context of a given transaction. Each document can have only one active
+    transaction at the time - subsequent attempts will cause exception to be thrown.
+
+    Transactions started with `doc.begin_transaction` can be released by deleting the transaction object
+    method.
+
+    Example::
+
+        from y_py import YDoc
+        doc = YDoc()
+        text = doc.get_text('name')
+        with doc.begin_transaction() as txn:
+            text.insert(txn, 0, 'hello world')
+    """
+
+    before_state: Dict[int, int]
+    origin: Any
+    """Origin of the innermost active `with_origin` scope of this transaction, or `None`."""
+
+    def with_origin(self, origin: Any) -> OriginScope:
+        """
+        Returns a scope in which changes made with this transaction are tagged with a given
+        `origin`, so that helper libraries can tag their operations without clobbering an origin
+        set by their caller. Scopes can be nested, and the enclosing origin is restored once a
+        scope is exited.
+
+        Observers of the transaction see the innermost origin pushed while it was active, as
+        events are only emitted once the transaction is committed.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            text = doc.get_text('text')
+            text.observe(lambda event: print(event.origin))
+            with doc.begin_transaction() as txn:
+                with txn.with_origin('user'):
+                    text.extend(txn, 'hello')
+                    with txn.with_origin('import'):
+                        text.extend(txn, ' world')
+                    assert txn.origin == 'user'
+            # prints 'import'
+        """
+    def get_text(self, name: str) -> YText:
+        """
+        Returns:
+            A `YText` shared data type, that's accessible for subsequent accesses using given `name`.
+
+        If there was no instance with this name before, it will be created and then returned.
+
+        If there was an instance with this name, but it was of different type, a `TypeError` is
+        raised.
+        """
+    def get_array(self, name: str) -> YArray:
         """
         Returns:
             A `YArray` shared data type, that's accessible for subsequent accesses using given `name`.
 
-        If there was no instance with this name before, it will be created and then returned.
+        If there was no instance with this name before, it will be created and then returned.
+
+        If there was an instance with this name, but it was of different type, a `TypeError` is
+        raised.
+        """
+    def get_map(self, name: str) -> YMap:
+        """
+        Returns:
+            A `YMap` shared data type, that's accessible for subsequent accesses using given `name`.
+
+        If there was no instance with this name before, it will be created and then returned.
+
+        If there was an instance with this name, but it was of different type, a `TypeError` is
+        raised.
+        """
+    def commit(self):
+        """
+        Triggers a post-update series of operations without `free`ing the transaction. This includes
+        compaction and optimization of internal representation of updates, triggering events etc.
+        Ypy transactions are auto-committed when they are `free`d.
+        """
+    def delete_set(self) -> DeleteSet:
+        """
+        Returns:
+            A set of items deleted by this transaction so far.
+        """
+    def state_vector_v1(self) -> EncodedStateVector:
+        """
+        Encodes a state vector of a given transaction document into its binary representation using
+        lib0 v1 encoding. State vector is a compact representation of updates performed on a given
+        document and can be used by `encode_state_as_update` on remote peer to generate a delta
+        update payload to synchronize changes between peers.
+
+        Example::
+
+            from y_py import YDoc
+
+            # document on machine A
+            local_doc = YDoc()
+            local_txn = local_doc.begin_transaction()
+
+            # document on machine B
+            remote_doc = YDoc()
+            remote_txn = local_doc.begin_transaction()
+
+            try:
+                local_sv = local_txn.state_vector_v1()
+                remote_delta = remote_txn.diff_v1(local_sv)
+                local_txn.apply_v1(remote_delta)
+            finally:
+                del local_txn
+                del remote_txn
+
+        """
+    def diff_v1(
+        self, vector: Optional[Union[BinaryInput, StateVector]] = None
+    ) -> YDocUpdate:
+        """
+        Encodes all updates that have happened since a given version `vector` into a compact delta
+        representation using lib0 v1 encoding. If `vector` parameter has not been provided, generated
+        delta payload will contain all changes of a current Ypy document, working effectively as
+        its state snapshot. Transforms registered with `YDoc.add_update_transform` are applied to
+        the payload.
+
+        Example::
+
+            from y_py import YDoc
+
+            # document on machine A
+            local_doc = YDoc()
+            local_txn = local_doc.begin_transaction()
+
+            # document on machine B
+            remote_doc = YDoc()
+            remote_txn = local_doc.begin_transaction()
+
+            try:
+                local_sv = local_txn.state_vector_v1()
+                remote_delta = remote_txn.diff_v1(local_sv)
+                local_txn.apply_v1(remote_delta)
+            finally:
+                del local_txn
+                del remote_txn
+        """
+    def apply_v1(self, diff: BinaryInput):
+        """
+        Applies delta update generated by the remote document replica to a current transaction's
+        document. This method assumes that a payload maintains lib0 v1 encoding format, once
+        transforms registered with `YDoc.add_update_transform` have been reversed.
+
+        Example::
+
+            from y_py import YDoc
+
+            # document on machine A
+            local_doc = YDoc()
+            local_txn = local_doc.begin_transaction()
+
+            # document on machine B
+            remote_doc = YDoc()
+            remote_txn = local_doc.begin_transaction()
+
+            try:
+                local_sv = local_txn.state_vector_v1()
+                remote_delta = remote_txn.diff_v1(local_sv)
+                local_txn.apply_v1(remote_delta)
+            finally:
+                del local_txn
+                del remote_txn
+        """
+    def __enter__() -> YTransaction: ...
+    def __exit__() -> bool: ...
+
+class YText:
+    """
+    A shared data type used for collaborative text editing. It enables multiple users to add and
+    remove chunks of text in efficient manner. This type is internally represented as able
+    double-linked list of text chunks - an optimization occurs during `YTransaction.commit`, which
+    allows to squash multiple consecutively inserted characters together as a single chunk of text
+    even between transaction boundaries in order to preserve more efficient memory model.
+
+    `YText` structure internally uses UTF-8 encoding and its length is described in a number of
+    bytes rather than individual characters (a single UTF-8 code point can consist of many bytes).
+
+    Like all Yrs shared data types, `YText` is resistant to the problem of interleaving (situation
+    when characters inserted one after another may interleave with other peers concurrent inserts
+    after merging all updates together). In case of Yrs conflict resolution is solved by using
+    unique document id to determine correct and consistent ordering.
+    """
+
+    prelim: bool
+    """True if this element has not been integrated into a YDoc."""
+    integrated: bool
+    """True if this element has been integrated into a YDoc. This is an opposite of `prelim`."""
+    doc: Optional[YDoc]
+    """
+    The `YDoc` this instance belongs to, or `None` if the document is unknown or has been dropped.
+    Documents are known for shared types reached from their root types, which have been obtained
+    from a `YDoc` or its transactions. Accessing it on a preliminary instance raises a
+    `PreliminaryAccessError`.
+    """
+    parent: Optional[Union[YText, YArray, YMap, YXmlElement, YXmlText]]
+    """
+    The shared type containing this instance, or `None` if it is a root type. The parent is
+    remembered when this instance is reached from it, so it is also `None` when the document is
+    unknown.
+    Accessing it on a preliminary instance raises a `PreliminaryAccessError`.
+    """
 
-        If there was an instance with this name, but it was of different type, it will be projected
-        onto `YArray` instance.
+    def __init__(self, init: str = ""):
         """
-    def get_map(self, name: str) -> YMap:
+        Creates a new preliminary instance of a `YText` shared data type, with its state initialized
+        to provided parameter.
+
+        Preliminary instances can be nested into other shared data types such as `YArray` and `YMap`.
+        Once a preliminary instance has been inserted this way, it becomes integrated into Ypy
+        document store and cannot be nested again: attempt to do so will result in an exception.
+        """
+    def transaction(self) -> YTransaction:
+        """
+        Starts a new transaction of the document this `YText` belongs to, which makes it possible
+        to modify it without a reference to its `YDoc`. The document must be known, as described by
+        the `doc` property, otherwise a `TransactionError` is raised. Raises a
+        `PreliminaryAccessError` for preliminary instances.
+
+        Example:
+
+        ```
+        with text.transaction() as txn:
+            text.extend(txn, '!')
+        ```
+        """
+    def __str__(self) -> str:
+        """
+        Returns:
+            The underlying shared string stored in this data type.
+        """
+    def __repr__(self) -> str:
+        """
+        Returns:
+            The string representation wrapped in 'YText()'
+        """
+    def __eq__(self, other: object) -> bool:
+        """
+        Checks if both objects refer to the same integrated `YText`. Preliminary instances are only
+        equal to themselves.
+        """
+    def __hash__(self) -> int:
+        """
+        Returns a hash of this `YText`, consistent with `__eq__`. Preliminary instances are unhashable
+        and raise a `TypeError`.
+        """
+    def __len__(self) -> int:
+        """
+        Returns:
+            The length of an underlying string stored in this `YText` instance, understood as a number of UTF-8 encoded bytes.
+        """
+    def to_json(
+        self, txn: Optional[YTransaction] = None, depth: Optional[int] = None
+    ) -> str:
+        """
+        Args:
+            txn: Optional transaction the text is read within.
+            depth: Accepted for consistency with other shared types. Texts contain no nested
+                shared types, so it has no effect.
+
+        Returns:
+            The underlying shared string stored in this data type.
+        """
+    def write_json(self, txn: YTransaction, file: TextIO):
+        """
+        Writes the JSON representation of the underlying string to a file object, in chunks
+        passed to its `write` method.
+
+        Args:
+            txn: A transaction the text is read within.
+            file: A text file object, or any object with a `write` method accepting strings.
+        """
+    def to_msgpack(self) -> bytes:
+        """
+        Serializes an underlying string stored in this `YText` instance using the MessagePack format.
+        """
+    def content_hash(self) -> str:
+        """
+        Args:
+            txn: The current transaction from a YDoc.
+        Returns:
+            A hex encoded SHA-256 digest of the string stored in this `YText` instance. Formatting
+            attributes are not taken into account.
+        """
+    def attribution(
+        self, txn: YTransaction, since: Optional[BinaryInput] = None
+    ) -> List[Dict[str, Any]]:
+        """
+        Returns contents of this `YText` instance as a list of runs `{"insert": str, "client": int}`,
+        where `client` is the id of the client which inserted a given run, enabling "who wrote
+        this" features. Embedded values are not included. The `client` is `None` for content whose
+        author can't be determined.
+
+        Args:
+            txn: The current transaction from a YDoc.
+            since: A snapshot produced by `YDoc.snapshot`. If given, every run also has a
+                `"status"` of either `"unchanged"`, `"added"` or `"removed"` compared to that
+                version. Removed text is only available in documents created with `skip_gc=True`.
+
+        Example:
+
+        ```
+        from y_py import YDoc
+
+        doc = YDoc(client_id=1)
+        text = doc.get_text('name')
+        with doc.begin_transaction() as txn:
+            text.extend(txn, 'hello')
+            assert text.attribution(txn) == [{'insert': 'hello', 'client': 1}]
+        ```
+        """
+    def diff_against_snapshot(
+        self, txn: YTransaction, snapshot: BinaryInput
+    ) -> List[Dict[str, Any]]:
+        """
+        Compares contents of this `YText` instance with its version at the time a given
+        `snapshot` was taken, to render "track changes" views.
+
+        Removed content is only available in documents created with `skip_gc=True`. Formatting
+        attributes reflect the current state of the text, so removed content has none.
+
+        Args:
+            txn: A transaction in which the text is read.
+            snapshot: A snapshot produced by `YDoc.snapshot`.
+
+        Returns:
+            A delta of the current contents merged with removed contents. Every operation is a
+            dictionary with an `"insert"`, which is a string or an embedded value, and optional
+            `"attributes"` with its formatting. Spans inserted or removed since the snapshot
+            also have a `"ychange"` attribute, which is a dictionary with a `"type"` of either
+            `"added"` or `"removed"`, and the `"client"` id of the author of the span.
+
+        Example:
+
+        ```
+        from y_py import YDoc
+
+        doc = YDoc(client_id=1, skip_gc=True)
+        text = doc.get_text('name')
+        with doc.begin_transaction() as txn:
+            text.extend(txn, 'hello world')
+        version = doc.snapshot()
+        with doc.begin_transaction() as txn:
+            text.delete_range(txn, 5, 6)
+            text.extend(txn, '!')
+            delta = text.diff_against_snapshot(txn, version)
+        assert delta == [
+            {'insert': 'hello'},
+            {'insert': ' world', 'attributes': {'ychange': {'type': 'removed', 'client': 1}}},
+            {'insert': '!', 'attributes': {'ychange': {'type': 'added', 'client': 1}}},
+        ]
+        ```
+        """
+    def insert(
+        self,
+        txn: YTransaction,
+        index: int,
+        chunk: str,
+        attributes: Dict[str, Any] = {},
+        clamp: bool = False,
+    ):
+        """
+        Inserts a string of text into the `YText` instance starting at a given `index`.
+        Attributes are optional style modifiers (`{"bold": True}`) that can be attached to the inserted string.
+        Attributes are only supported for a `YText` instance which already has been integrated into document store.
+        Their values can be any JSON-like values, including nested dictionaries and lists, such as
+        `{"link": {"href": "https://example.com"}}`.
+
+        An `IndexError` is raised if `index` is beyond the end of the text, unless `clamp` is set,
+        in which case the string is appended instead.
+        """
+    def insert_embed(
+        self,
+        txn: YTransaction,
+        index: int,
+        embed: Any,
+        attributes: Dict[str, Any] = {},
+        clamp: bool = False,
+    ):
+        """
+        Inserts embedded content into the YText at the provided index. Attributes are user-defined metadata associated with the embedded content.
+        Attributes are only supported for a `YText` instance which already has been integrated into document store.
+
+        Preliminary `YText`, `YArray` and `YMap` instances are integrated into the document as
+        children of this text, and are reported as such in `YTextEvent.delta`. Indexes beyond the
+        end of the text are handled like in `insert`.
+        """
+    def apply_delta(self, txn: YTransaction, delta: List[YTextDelta]):
+        """
+        Applies a `delta` in the format of `YTextEvent.delta`, starting at the beginning of the
+        text. Inserts and retains may have `attributes`, which format inserted or retained content.
+        Inserted values other than strings are embedded like with `insert_embed`.
+
+        Example::
+
+            from y_py import YDoc, YMap
+
+            doc = YDoc()
+            text = doc.get_text('name')
+            with doc.begin_transaction() as txn:
+                text.extend(txn, 'Hello world')
+                text.apply_delta(txn, [
+                    {'retain': 6, 'attributes': {'bold': True}},
+                    {'insert': YMap({'mention': 'alice'})},
+                    {'delete': 5},
+                ])
+        """
+    def format(
+        self,
+        txn: YTransaction,
+        index: int,
+        length: int,
+        attributes: Dict[str, Any],
+        clamp: bool = False,
+    ):
+        """
+        Wraps an existing piece of text within a range described by `index`-`length` parameters with
+        formatting blocks containing provided `attributes` metadata. This method only works for
+        `YText` instances that already have been integrated into document store.
+
+        An `IndexError` is raised if the range exceeds the text, unless `clamp` is set, in which
+        case only the part of the range within the text is formatted.
+        """
+    def format_all(self, txn: YTransaction, attributes: Dict[str, Any]):
+        """
+        Formats the whole text with provided `attributes`. Attributes set to `None` are removed.
+        """
+    def clear_format(self, txn: YTransaction, index: int, length: int):
+        """
+        Removes all formatting attributes from a range of text described by `index`-`length`
+        parameters. This method only works for `YText` instances that already have been integrated
+        into document store.
+        """
+    def extend(self, txn: YTransaction, chunk: str):
+        """
+        Appends a given `chunk` of text at the end of current `YText` instance.
+        """
+    def delete(self, txn: YTransaction, index: int, clamp: bool = False):
+        """
+        Deletes the character at the specified `index`. Indexes beyond the end of the text are
+        handled like in `delete_range`.
+        """
+    def delete_range(
+        self, txn: YTransaction, index: int, length: int, clamp: bool = False
+    ):
+        """
+        Deletes a specified range of of characters, starting at a given `index`.
+        Both `index` and `length` are counted in terms of a number of UTF-8 character bytes.
+
+        An `IndexError` is raised if the range exceeds the text, unless `clamp` is set, in which
+        case only the part of the range within the text is deleted.
+        """
+    def range(
+        self, txn: YTransaction, start: Union[int, str], count: int
+    ) -> Tuple[str, Optional[str]]:
+        """
+        Reads a page of at most `count` characters of this text, eg. to serve a huge document
+        through a web API. Raises a `PreliminaryAccessError` for preliminary instances.
+
+        Tokens are stored relative to the characters they point at, so pages stay consistent while
+        the text is being edited: characters inserted before a token are not read again, and a
+        token pointing at a deleted character continues from the place of the deletion.
+
+        Example::
+
+            chunk, token = text.range(txn, 0, 1000)
+            while token is not None:
+                more, token = text.range(txn, token, 1000)
+
+        Args:
+            txn: A transaction of the document containing the text.
+            start: An index of the first character, or a continuation token returned by a read of
+                a previous page.
+            count: The maximum length of the page.
+
+        Returns:
+            The characters of the page with a token of the next page, or `None` once the end of
+            the text has been reached.
+        """
+    def line_count(self) -> int:
+        """
+        Returns:
+            The number of lines of this `YText` instance, separated by `\\n` characters. An empty
+            text consists of a single empty line.
+        """
+    def line(self, n: int) -> str:
+        """
+        Returns contents of a line of this `YText` instance. Embedded values are skipped.
+
+        Args:
+            n: The number of the line, counted from 0.
+
+        Returns:
+            Contents of the line without its trailing `\\n`. Raises an `IndexError` if there is no
+            such line.
+        """
+    def insert_at_line(self, txn: YTransaction, line: int, column: int, chunk: str):
+        """
+        Inserts a given `chunk` of text at a `column` of a `line`, both counted from 0. Columns are
+        counted like indexes of other `YText` methods. Raises an `IndexError` if the line doesn't
+        exist or is shorter than `column`.
+        """
+    def replace(
+        self, txn: YTransaction, old: str, new: str, count: int = -1
+    ) -> int:
+        """
+        Replaces occurrences of `old` text with `new` text, like `str.replace`. Every occurrence is
+        changed separately and only characters which differ are touched, so concurrent edits of
+        the rest of the text are preserved.
+
+        Args:
+            txn: A transaction to perform the replacements.
+            old: The text to replace, which must not be empty.
+            new: The replacement.
+            count: If not negative, only that many first occurrences are replaced.
+
+        Returns:
+            The number of replaced occurrences.
+        """
+    def regex_replace(
+        self,
+        txn: YTransaction,
+        pattern: Union[str, Pattern[str]],
+        repl: Union[str, Callable[[Match[str]], str]],
+    ) -> int:
+        """
+        Replaces matches of a regular expression with minimal edits, like `replace`. Embedded
+        values are not visible to the pattern.
+
+        Args:
+            txn: A transaction to perform the replacements.
+            pattern: A regular expression, either as a string or compiled with `re.compile`.
+            repl: Like in `re.sub`, a string with backreferences, or a function receiving a match
+                object and returning a replacement.
+
+        Returns:
+            The number of replaced matches.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            text = doc.get_text('name')
+            with doc.begin_transaction() as txn:
+                text.extend(txn, 'born 1990, died 2050')
+                text.regex_replace(txn, r'(\d+)', lambda m: str(int(m[1]) + 1))
+        """
+    def to_markdown(self, txn: YTransaction) -> str:
+        """
+        Returns contents of this `YText` instance as Markdown. Formatting attributes follow the
+        conventions of the Quill editor: `bold`, `italic`, `strike`, `code` and `link` attributes
+        of text, and `header`, `list`, `blockquote` and `code-block` attributes of newline
+        characters ending lines. Embedded values are skipped.
+
+        Args:
+            txn: The current transaction from a YDoc.
+        """
+    def from_markdown(self, txn: YTransaction, markdown: str):
+        """
+        Replaces contents of this `YText` instance with formatted text parsed from `markdown`,
+        using the same attributes as `to_markdown`. Headings, paragraphs, lists, quotes, fenced
+        code blocks, as well as bold, italic, strikethrough, code and link formatting of text are
+        supported.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            text = doc.get_text('notes')
+            with doc.begin_transaction() as txn:
+                text.from_markdown(txn, '# Notes\n\nSome **bold** text')
+            assert str(text) == 'Notes\nSome bold text\n'
+        """
+    def observe(
+        self,
+        f: Callable[[Union[YTextEvent, List[YTextDelta]]]],
+        weak: bool = False,
+        debounce_ms: Optional[int] = None,
+    ) -> SubscriptionId:
+        """
+        Assigns a callback function to listen to YText updates.
+
+        Args:
+            f: Callback function that runs when the text object receives an update.
+            weak: Whether only a weak reference to the callback is held, see
+                `YDoc.observe_after_transaction`.
+            debounce_ms: If given, deltas of events following each other within that many
+                milliseconds are merged, and the callback receives a single delta once the
+                text stays unchanged for `debounce_ms`, instead of an event. Debounced callbacks
+                are called on a timer thread.
+        Returns:
+            A reference to the callback subscription.
+        """
+    def observe_deep(
+        self, f: Callable[[List[Event]]], weak: bool = False
+    ) -> SubscriptionId:
+        """
+        Assigns a callback function to listen to the updates of the YText instance and those of its nested attributes.
+        Currently, this listens to the same events as YText.observe, but in the future this will also listen to
+        the events of embedded values.
+
+        Args:
+            f: Callback function that runs when the text object or its nested attributes receive an update.
+            weak: Whether only a weak reference to the callback is held, see
+                `YDoc.observe_after_transaction`.
+        Returns:
+            A reference to the callback subscription.
+        """
+    def unobserve(self, subscription_id: SubscriptionId):
+        """
+        Cancels the observer callback associated with the `subscripton_id`.
+
+        Args:
+            subscription_id: reference to a subscription provided by the `observe` method.
         """
-        Returns:
-            A `YMap` shared data type, that's accessible for subsequent accesses using given `name`.
 
-        If there was no instance with this name before, it will be created and then returned.
+class YTextEvent:
+    """
+    Communicates updates that occurred during a transaction for an instance of `YText`.
+    The `target` references the `YText` element that receives the update.
+    The `delta` is a list of updates applied by the transaction.
+    """
 
-        If there was an instance with this name, but it was of different type, it will be projected
-        onto `YMap` instance.
-        """
-    def commit(self):
-        """
-        Triggers a post-update series of operations without `free`ing the transaction. This includes
-        compaction and optimization of internal representation of updates, triggering events etc.
-        Ypy transactions are auto-committed when they are `free`d.
+    target: YText
+    origin: Any
+    """Origin of the transaction which produced this event, see `YTransaction.with_origin`."""
+    delta: List[YTextDelta]
+    def __repr__(self) -> str: ...
+    def path(self) -> List[Union[int, str]]:
         """
-    def state_vector_v1(self) -> EncodedStateVector:
+        Returns:
+            Array of keys and indexes creating a path from root type down to current instance of shared type (accessible via `target` getter).
         """
-        Encodes a state vector of a given transaction document into its binary representation using
-        lib0 v1 encoding. State vector is a compact representation of updates performed on a given
-        document and can be used by `encode_state_as_update` on remote peer to generate a delta
-        update payload to synchronize changes between peers.
 
-        Example::
+YTextDelta = Union[YTextChangeInsert, YTextChangeDelete, YTextChangeRetain]
 
-            from y_py import YDoc
+class YTextChangeInsert(TypedDict):
+    insert: str
+    attributes: Optional[Any]
 
-            # document on machine A
-            local_doc = YDoc()
-            local_txn = local_doc.begin_transaction()
+class YTextChangeDelete(TypedDict):
+    delete: int
 
-            # document on machine B
-            remote_doc = YDoc()
-            remote_txn = local_doc.begin_transaction()
+class YTextChangeRetain(TypedDict):
+    retain: int
+    attributes: Optional[Any]
 
-            try:
-                local_sv = local_txn.state_vector_v1()
-                remote_delta = remote_txn.diff_v1(local_sv)
-                local_txn.apply_v1(remote_delta)
-            finally:
-                del local_txn
-                del remote_txn
+class Cursor:
+    """
+    A selection within a `YText`, eg. a cursor of a user in a collaborative text editor. Both ends
+    of the selection are stored relative to the characters they point at, so they follow local and
+    remote edits of the text: inserting text before a cursor moves it forward, and deleting a
+    selected fragment collapses the selection at the place of the deletion. Indexes use the offset
+    kind of the document, like all other `YText` methods. Embedded values have no identity a cursor
+    could refer to, so a position right before one follows the text after it.
+
+    Example:
+
+    ```
+    from y_py import YDoc, Cursor
+
+    doc = YDoc()
+    text = doc.get_text('name')
+    cursor = Cursor(text)
+    with doc.begin_transaction() as txn:
+        text.extend(txn, 'hello world')
+        cursor.set(txn, 6, 11)
+    ...
+    with doc.begin_transaction() as txn:
+        anchor, head = cursor.range(txn)
+    ```
+    """
 
+    def __init__(self, text: YText):
         """
-    def diff_v1(self, vector: Optional[EncodedStateVector] = None) -> YDocUpdate:
+        Creates a cursor collapsed at the start of an integrated `text`. Raises a
+        `PreliminaryAccessError` for preliminary instances.
         """
-        Encodes all updates that have happened since a given version `vector` into a compact delta
-        representation using lib0 v1 encoding. If `vector` parameter has not been provided, generated
-        delta payload will contain all changes of a current Ypy document, working effectively as
-        its state snapshot.
+    def set(self, txn: YTransaction, anchor: int, head: Optional[int] = None):
+        """
+        Moves this cursor to select text between the `anchor` and `head` indexes. Indexes past the
+        end of the text stick to its end.
 
-        Example::
+        Args:
+            txn: A transaction of the document containing the text.
+            anchor: The index where the selection starts.
+            head: The index where the selection ends. If not given, the selection is collapsed at
+                the `anchor`.
+        """
+    def range(self, txn: YTransaction) -> Tuple[int, int]:
+        """
+        Returns:
+            Current `(anchor, head)` indexes of this cursor. The anchor can be placed after the
+            head, if the text was selected backwards.
+        """
 
-            from y_py import YDoc
+class Annotations:
+    """
+    Comments anchored to ranges of a `YText`, eg. review comments of a collaborative document.
+    Annotations are stored in a companion `YMap`, so they are synchronized together with the text.
+    Every annotation remembers the characters it covers and the one right after them, instead of
+    indexes, so ranges follow local and remote edits of the text: text inserted within a range,
+    or right at its end, extends it, and deleting annotated text shrinks it. Once the character
+    right after a range has been deleted, the range ends after its last remaining character.
 
-            # document on machine A
-            local_doc = YDoc()
-            local_txn = local_doc.begin_transaction()
+    Every annotation is stored under a unique identifier as a dictionary with a `comment`, which
+    can be any value supported by shared types, and a `resolved` flag.
 
-            # document on machine B
-            remote_doc = YDoc()
-            remote_txn = local_doc.begin_transaction()
+    Example::
 
-            try:
-                local_sv = local_txn.state_vector_v1()
-                remote_delta = remote_txn.diff_v1(local_sv)
-                local_txn.apply_v1(remote_delta)
-            finally:
-                del local_txn
-                del remote_txn
+        from y_py import Annotations, YDoc
+
+        doc = YDoc()
+        text = doc.get_text('content')
+        annotations = Annotations(text, doc.get_map('comments'))
+        with doc.begin_transaction() as txn:
+            text.extend(txn, 'hello world')
+            id = annotations.add(txn, 6, 11, {'author': 'alice', 'body': 'Which world?'})
+        ...
+        with doc.begin_transaction() as txn:
+            for annotation in annotations.list(txn):
+                print(annotation['start'], annotation['end'], annotation['comment'])
+    """
+
+    def __init__(self, text: YText, map: YMap):
         """
-    def apply_v1(self, diff: YDocUpdate):
+        Creates annotations of an integrated `text`, stored in an integrated `map`. Raises a
+        `PreliminaryAccessError` for preliminary instances.
         """
-        Applies delta update generated by the remote document replica to a current transaction's
-        document. This method assumes that a payload maintains lib0 v1 encoding format.
+    def add(self, txn: YTransaction, start: int, end: int, comment: Any) -> str:
+        """
+        Annotates text between the `start` and `end` indexes with a given `comment`. Indexes past
+        the end of the text stick to its end.
 
-        Example::
+        Returns:
+            An identifier of the new annotation.
+        """
+    def resolve(self, txn: YTransaction, id: str, resolved: bool = True):
+        """
+        Marks an annotation with a given `id` as resolved, or as unresolved if `resolved` is
+        `False`. Raises a `KeyError` if there's no such annotation.
+        """
+    def remove(self, txn: YTransaction, id: str):
+        """
+        Removes an annotation with a given `id`. Raises a `KeyError` if there's no such annotation.
+        """
+    def range_of(self, txn: YTransaction, id: str) -> Tuple[int, int]:
+        """
+        Returns:
+            Current `(start, end)` indexes of an annotation with a given `id`. Raises a `KeyError`
+            if there's no such annotation.
+        """
+    def list(
+        self, txn: YTransaction, include_resolved: bool = False
+    ) -> List[Dict[str, Any]]:
+        """
+        Returns:
+            Annotations as dictionaries with their `id`, current `start` and `end` indexes,
+            `comment` and `resolved` flag, ordered by their ranges. Resolved annotations are only
+            included if `include_resolved` is `True`.
+        """
 
-            from y_py import YDoc
+class YCounter:
+    """
+    A number shared by all replicas of a document, which can be incremented concurrently without
+    losing increments, as opposed to `map.set(txn, 'count', map['count'] + 1)`, where concurrent
+    writes of the same key overwrite each other.
 
-            # document on machine A
-            local_doc = YDoc()
-            local_txn = local_doc.begin_transaction()
+    A counter is backed by a `YMap`, in which every client only updates its own entry holding a
+    sum of its increments. A value of the counter is a sum of all entries, so it converges once
+    replicas have exchanged their updates. Increments can be negative.
 
-            # document on machine B
-            remote_doc = YDoc()
-            remote_txn = local_doc.begin_transaction()
+    Example::
 
-            try:
-                local_sv = local_txn.state_vector_v1()
-                remote_delta = remote_txn.diff_v1(local_sv)
-                local_txn.apply_v1(remote_delta)
-            finally:
-                del local_txn
-                del remote_txn
+        from y_py import YCounter, YDoc
+
+        doc = YDoc()
+        likes = YCounter(doc.get_map('likes'))
+        with doc.begin_transaction() as txn:
+            likes.increment(txn)
+            print(likes.value(txn))
+    """
+
+    def __init__(self, map: YMap):
         """
-    def __enter__() -> YTransaction: ...
-    def __exit__() -> bool: ...
+        Creates a counter backed by an integrated `map`. Raises a `PreliminaryAccessError` for
+        preliminary instances.
+        """
+    def increment(self, txn: YTransaction, n: int = 1) -> int:
+        """
+        Adds `n` to this counter.
 
-class YText:
+        Returns:
+            A new value of the counter.
+        """
+    def value(self, txn: YTransaction) -> int:
+        """
+        Returns:
+            A current value of this counter.
+        """
+
+class YRegister:
     """
-    A shared data type used for collaborative text editing. It enables multiple users to add and
-    remove chunks of text in efficient manner. This type is internally represented as able
-    double-linked list of text chunks - an optimization occurs during `YTransaction.commit`, which
-    allows to squash multiple consecutively inserted characters together as a single chunk of text
-    even between transaction boundaries in order to preserve more efficient memory model.
+    A single value stored under a `key` of a `YMap`, eg. a setting of a shared document, which is
+    resolved deterministically when replicas change it concurrently: the value written with the
+    latest timestamp wins, and ties are broken by client identifiers of the writers.
 
-    `YText` structure internally uses UTF-8 encoding and its length is described in a number of
-    bytes rather than individual characters (a single UTF-8 code point can consist of many bytes).
+    Every client writes its values to its own entry of the map, under a `"{key}:{client_id}"` key,
+    as concurrent writes of the same key would be resolved by client identifiers alone.
 
-    Like all Yrs shared data types, `YText` is resistant to the problem of interleaving (situation
-    when characters inserted one after another may interleave with other peers concurrent inserts
-    after merging all updates together). In case of Yrs conflict resolution is solved by using
-    unique document id to determine correct and consistent ordering.
+    Example::
+
+        from y_py import YDoc, YRegister
+
+        doc = YDoc()
+        theme = YRegister(doc.get_map('settings'), 'theme')
+        theme.observe(lambda value: print('theme changed to', value))
+        with doc.begin_transaction() as txn:
+            theme.set(txn, 'dark')
     """
 
-    prelim: bool
-    """True if this element has not been integrated into a YDoc."""
+    key: str
+    """Key of the map under which this register is stored."""
 
-    def __init__(self, init: str = ""):
+    def __init__(self, map: YMap, key: str):
         """
-        Creates a new preliminary instance of a `YText` shared data type, with its state initialized
-        to provided parameter.
-
-        Preliminary instances can be nested into other shared data types such as `YArray` and `YMap`.
-        Once a preliminary instance has been inserted this way, it becomes integrated into Ypy
-        document store and cannot be nested again: attempt to do so will result in an exception.
+        Creates a register stored under a `key` of an integrated `map`. Raises a
+        `PreliminaryAccessError` for preliminary instances.
         """
-    def __str__(self) -> str:
+    def set(
+        self, txn: YTransaction, value: Any, timestamp: Optional[float] = None
+    ) -> bool:
         """
+        Writes a `value` with a given `timestamp` in seconds since the epoch, which defaults to the
+        current time. Without an explicit timestamp, the value always becomes current: if the
+        current value has a later timestamp, eg. because of a clock skew between replicas, the
+        timestamp is moved right after it.
+
         Returns:
-            The underlying shared string stored in this data type.
+            `True` if the value has become the current value of this register, or `False` without
+            writing it if the current value has a later timestamp.
         """
-    def __repr__(self) -> str:
+    def get(self, txn: YTransaction) -> Any:
         """
         Returns:
-            The string representation wrapped in 'YText()'
+            The current value of this register, or `None` if it has never been written.
         """
-    def __len__(self) -> int:
+    def timestamp(self, txn: YTransaction) -> Optional[float]:
         """
         Returns:
-            The length of an underlying string stored in this `YText` instance, understood as a number of UTF-8 encoded bytes.
+            A timestamp of the current value of this register, or `None` if it has never been
+            written.
         """
-    def to_json(self) -> str:
+    def observe(
+        self, callback: Callable[[Any], None], weak: bool = False
+    ) -> SubscriptionId:
         """
+        Subscribes a `callback(value)` which is called with the current value of this register
+        whenever a transaction changes any of its entries, including writes which lose against a
+        value with a later timestamp. If `weak` is `True`, only a weak reference to the callback
+        is held, like in `YDoc.observe_after_transaction`.
+
         Returns:
-            The underlying shared string stored in this data type.
+            A subscription identifier that can be used to cancel the callback.
         """
-    def insert(
-        self,
-        txn: YTransaction,
-        index: int,
-        chunk: str,
-        attributes: Dict[str, Any] = {},
-    ):
+    def unobserve(self, subscription_id: SubscriptionId):
         """
-        Inserts a string of text into the `YText` instance starting at a given `index`.
-        Attributes are optional style modifiers (`{"bold": True}`) that can be attached to the inserted string.
-        Attributes are only supported for a `YText` instance which already has been integrated into document store.
+        Cancels an observer callback associated with a given `subscription_id`.
         """
-    def insert_embed(
-        self,
-        txn: YTransaction,
-        index: int,
-        embed: Any,
-        attributes: Dict[str, Any] = {},
-    ):
+
+class YArray:
+    prelim: bool
+    """True if this element has not been integrated into a YDoc."""
+    integrated: bool
+    """True if this element has been integrated into a YDoc. This is an opposite of `prelim`."""
+    doc: Optional[YDoc]
+    """
+    The `YDoc` this instance belongs to, or `None` if the document is unknown or has been dropped.
+    Documents are known for shared types reached from their root types, which have been obtained
+    from a `YDoc` or its transactions. Accessing it on a preliminary instance raises a
+    `PreliminaryAccessError`.
+    """
+    parent: Optional[Union[YText, YArray, YMap, YXmlElement, YXmlText]]
+    """
+    The shared type containing this instance, or `None` if it is a root type. The parent is
+    remembered when this instance is reached from it, so it is also `None` when the document is
+    unknown.
+    Accessing it on a preliminary instance raises a `PreliminaryAccessError`.
+    """
+
+    def __init__(init: Optional[Iterable[Any]] = None):
         """
-        Inserts embedded content into the YText at the provided index. Attributes are user-defined metadata associated with the embedded content.
-        Attributes are only supported for a `YText` instance which already has been integrated into document store.
+        Creates a new preliminary instance of a `YArray` shared data type, with its state
+        initialized to provided parameter.
+
+        Preliminary instances can be nested into other shared data types such as `YArray` and `YMap`.
+        Once a preliminary instance has been inserted this way, it becomes integrated into Ypy
+        document store and cannot be nested again: attempt to do so will result in an exception.
         """
-    def format(
-        self, txn: YTransaction, index: int, length: int, attributes: Dict[str, Any]
-    ):
+    def __eq__(self, other: object) -> bool:
         """
-        Wraps an existing piece of text within a range described by `index`-`length` parameters with
-        formatting blocks containing provided `attributes` metadata. This method only works for
-        `YText` instances that already have been integrated into document store
+        Checks if both objects refer to the same integrated `YArray`. Preliminary instances are only
+        equal to themselves.
         """
-    def extend(self, txn: YTransaction, chunk: str):
+    def __hash__(self) -> int:
         """
-        Appends a given `chunk` of text at the end of current `YText` instance.
+        Returns a hash of this `YArray`, consistent with `__eq__`. Preliminary instances are unhashable
+        and raise a `TypeError`.
         """
-    def delete(self, txn: YTransaction, index: int):
+    def __len__(self) -> int:
         """
-        Deletes the character at the specified `index`.
+        Returns:
+            Number of elements in the `YArray`
         """
-    def delete_range(self, txn: YTransaction, index: int, length: int):
+    def transaction(self) -> YTransaction:
         """
-        Deletes a specified range of of characters, starting at a given `index`.
-        Both `index` and `length` are counted in terms of a number of UTF-8 character bytes.
+        Starts a new transaction of the document this `YArray` belongs to, which makes it possible
+        to modify it without a reference to its `YDoc`. The document must be known, as described by
+        the `doc` property, otherwise a `TransactionError` is raised. Raises a
+        `PreliminaryAccessError` for preliminary instances.
+
+        Example:
+
+        ```
+        with array.transaction() as txn:
+            array.append(txn, 1)
+        ```
         """
-    def observe(self, f: Callable[[YTextEvent]]) -> SubscriptionId:
+    def __str__(self) -> str:
         """
-        Assigns a callback function to listen to YText updates.
+        Returns:
+            The string representation of YArray
+        """
+    def __repr__(self) -> str:
+        """
+        Returns:
+            The string representation of YArray wrapped in `YArray()`
+        """
+    def to_json(
+        self, txn: Optional[YTransaction] = None, depth: Optional[int] = None
+    ) -> str:
+        """
+        Converts an underlying contents of this `YArray` instance into their JSON representation.
+
+        Example::
+
+            import json
+            from y_py import YDoc, YMap
+
+            doc = YDoc()
+            array = doc.get_array("tasks")
+            with doc.begin_transaction() as txn:
+                array.append(txn, YMap({"title": "Write docs", "tags": ["docs"]}))
+                shallow = json.loads(array.to_json(txn, depth=0))
+                assert shallow == [{"$type": "YMap", "length": 2}]
 
         Args:
-            f: Callback function that runs when the text object receives an update.
-        Returns:
-            A reference to the callback subscription.
+            txn: Optional transaction the array is read within.
+            depth: If given, nested shared types are resolved only up to `depth` levels below this
+                array, and deeper ones are replaced by `{"$type": ..., "length": ...}` placeholders.
+                With a `depth` of 0, only the elements of this array are resolved.
         """
-    def observe_deep(self, f: Callable[[List[Event]]]) -> SubscriptionId:
+    def write_json(self, txn: YTransaction, file: TextIO):
         """
-        Assigns a callback function to listen to the updates of the YText instance and those of its nested attributes.
-        Currently, this listens to the same events as YText.observe, but in the future this will also listen to
-        the events of embedded values.
+        Serializes an underlying contents of this `YArray` instance into JSON, like `to_json`
+        does, but writes it to a file object in chunks instead of returning a single string.
+        Nested shared types are serialized as they are traversed, which keeps memory usage low
+        when serializing large documents.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            array = doc.get_array("events")
+            with doc.begin_transaction() as txn:
+                array.extend(txn, range(100_000))
+            with open("events.json", "w") as file, doc.begin_transaction() as txn:
+                array.write_json(txn, file)
+
+        Args:
+            txn: A transaction the array is read within.
+            file: A text file object, or any object with a `write` method accepting strings.
+        """
+    def to_msgpack(self) -> bytes:
+        """
+        Serializes an underlying contents of this `YArray` instance using the MessagePack format.
+        Unlike document updates, the result contains only the current values, without any
+        collaborative editing metadata, which makes it a compact snapshot readable by any
+        MessagePack library.
+        """
+    def content_hash(self) -> str:
+        """
+        Digests depend only on the current elements, regardless of their editing history, so
+        replicas can cheaply verify that they converged.
 
         Args:
-            f: Callback function that runs when the text object or its nested attributes receive an update.
+            txn: The current transaction from a YDoc.
         Returns:
-            A reference to the callback subscription.
+            A hex encoded SHA-256 digest of the contents of this `YArray` instance.
         """
-    def unobserve(self, subscription_id: SubscriptionId):
+    def to_list(
+        self, deep: bool = False, start: Optional[int] = None, stop: Optional[int] = None
+    ) -> List[Any]:
         """
-        Cancels the observer callback associated with the `subscripton_id`.
+        Converts an underlying contents of this `YArray` instance into a Python list. Elements are
+        extracted in a single pass, which is much faster than reading them one by one.
 
         Args:
-            subscription_id: reference to a subscription provided by the `observe` method.
+            deep: If `True`, nested shared types are recursively converted into plain `str`, `list`
+                and `dict` values. Otherwise they are returned as `YText`, `YArray` and `YMap` instances.
+            start: Index of the first element to return. Follows the same rules as Python slices.
+            stop: Index past the last element to return. Follows the same rules as Python slices.
         """
+    def to_numpy(self) -> Any:
+        """
+        Converts an underlying contents of this `YArray` instance into a NumPy array. Only arrays
+        of numbers and booleans (or nested lists of them) can be converted. Since all numbers are
+        stored as 64-bit floats, integer values will produce a float array.
 
-class YTextEvent:
-    """
-    Communicates updates that occurred during a transaction for an instance of `YText`.
-    The `target` references the `YText` element that receives the update.
-    The `delta` is a list of updates applied by the transaction.
-    """
-
-    target: YText
-    delta: List[YTextDelta]
-    def path(self) -> List[Union[int, str]]:
+        NumPy arrays can also be inserted into any shared type, where they are stored as nested lists.
         """
-        Returns:
-            Array of keys and indexes creating a path from root type down to current instance of shared type (accessible via `target` getter).
+    def to_arrow(self) -> Any:
         """
+        Converts an underlying contents of this `YArray` instance into a `pyarrow.Table`, which can
+        be turned into a pandas DataFrame with `to_pandas()`. Requires `pyarrow` to be installed.
 
-YTextDelta = Union[YTextChangeInsert, YTextChangeDelete, YTextChangeRetain]
-
-class YTextChangeInsert(TypedDict):
-    insert: str
-    attributes: Optional[Any]
+        Every element of the array is treated as a row and has to be a map. Table columns are
+        created from the keys of all rows, in order of their first appearance (keys of a single row
+        are taken in sorted order), and cells missing from a row are set to `None`. Columns are
+        built natively and passed to `pyarrow` through the Arrow C data interface, so they have to
+        hold booleans, integers, floats or strings. Arrays containing other elements, or columns
+        mixing incompatible values, raise a `TypeError`.
+        """
+    def range(
+        self, txn: YTransaction, start: Union[int, str], count: int
+    ) -> Tuple[List[Any], Optional[str]]:
+        """
+        Reads a page of at most `count` elements of this array, eg. to serve a huge list through a
+        web API. Raises a `PreliminaryAccessError` for preliminary instances.
 
-class YTextChangeDelete(TypedDict):
-    delete: int
+        Tokens are stored relative to the elements they point at, so pages stay consistent while
+        the array is being edited: elements inserted before a token are not read again, and a
+        token pointing at a deleted element continues from the place of the deletion.
 
-class YTextChangeRetain(TypedDict):
-    retain: int
-    attributes: Optional[Any]
+        Example::
 
-class YArray:
-    prelim: bool
-    """True if this element has not been integrated into a YDoc."""
+            items, token = array.range(txn, 0, 100)
+            while token is not None:
+                more, token = array.range(txn, token, 100)
 
-    def __init__(init: Optional[Iterable[Any]] = None):
-        """
-        Creates a new preliminary instance of a `YArray` shared data type, with its state
-        initialized to provided parameter.
+        Args:
+            txn: A transaction of the document containing the array.
+            start: An index of the first element, or a continuation token returned by a read of a
+                previous page.
+            count: The maximum number of elements of the page.
 
-        Preliminary instances can be nested into other shared data types such as `YArray` and `YMap`.
-        Once a preliminary instance has been inserted this way, it becomes integrated into Ypy
-        document store and cannot be nested again: attempt to do so will result in an exception.
-        """
-    def __len__(self) -> int:
-        """
         Returns:
-            Number of elements in the `YArray`
+            The elements of the page with a token of the next page, or `None` once the end of the
+            array has been reached.
         """
-    def __str__(self) -> str:
+    def column(self, txn: YTransaction, key: str, default: Any = None) -> List[Any]:
         """
+        Reads a single column of an array of maps, eg. rows of a table. Other values of the rows
+        are not converted, which makes reading a single column much cheaper than converting the
+        whole array. Arrays containing other elements raise a `TypeError`.
+
+        Example::
+
+            from y_py import YDoc, YMap
+
+            doc = YDoc()
+            rows = doc.get_array('rows')
+            with doc.begin_transaction() as txn:
+                rows.extend(txn, [YMap({'name': 'a', 'size': 1}), {'name': 'b'}])
+                assert rows.column(txn, 'size', 0) == [1, 0]
+
+        Args:
+            txn: A transaction of the document containing the array.
+            key: The key of the column.
+            default: A value of rows missing the key.
+
         Returns:
-            The string representation of YArray
+            Values stored under the `key` by every element of this array.
         """
-    def __repr__(self) -> str:
+    def values_where(self, txn: YTransaction, key: str, value: Any) -> List[Any]:
         """
+        Filters rows of an array of maps, eg. a table, by a value stored under a given key.
+        Rows are compared without being converted, and only matching rows are returned, which
+        makes querying a large table much cheaper than filtering the whole array in Python.
+        Arrays containing other elements raise a `TypeError`.
+
+        Example::
+
+            from y_py import YDoc, YMap
+
+            doc = YDoc()
+            rows = doc.get_array('rows')
+            with doc.begin_transaction() as txn:
+                rows.extend(txn, [{'name': 'a', 'done': True}, {'name': 'b', 'done': False}])
+                assert rows.values_where(txn, 'done', True) == [{'name': 'a', 'done': True}]
+
+        Args:
+            txn: A transaction of the document containing the array.
+            key: The key of the compared field.
+            value: A primitive value (`None`, a boolean, a number or a string) of matching rows.
+                Integers and floats are compared by their numeric value.
+
         Returns:
-            The string representation of YArray wrapped in `YArray()`
+            Rows storing the `value` under the `key`.
         """
-    def to_json(self) -> str:
+    def insert(self, txn: YTransaction, index: int, item: Any) -> Any:
         """
-        Converts an underlying contents of this `YArray` instance into their JSON representation.
+        Inserts an item at the provided index in the `YArray`. Returns the inserted element, so that
+        preliminary shared types can be used in their integrated form right away.
+
+        Tuples are stored as lists. Sets and frozensets are stored as sorted lists, so that their
+        order is the same on every peer. Sets containing elements that cannot be sorted raise a `TypeError`.
         """
-    def insert(self, txn: YTransaction, index: int, item: Any):
+    def insert_range(self, txn: YTransaction, index: int, items: Iterable) -> List[Any]:
         """
-        Inserts an item at the provided index in the `YArray`.
+        Inserts a given range of `items` into this `YArray` instance, starting at given `index`.
+        Returns a list of inserted elements.
         """
-    def insert_range(self, txn: YTransaction, index: int, items: Iterable):
+    def push_primitives(self, txn: YTransaction, items: Iterable[Any]):
         """
-        Inserts a given range of `items` into this `YArray` instance, starting at given `index`.
+        Appends primitive `items` (`None`, booleans, numbers, strings, bytes and lists or
+        dictionaries of them) at the end of this `YArray` instance. Unlike `extend`, all items are
+        converted in a single pass and inserted as one range, which makes bulk loads of large lists
+        much faster. Shared types are not accepted and a `TypeError` is raised for them, in which
+        case the array is left untouched.
+
+        Example:
+
+        ```
+        from y_py import YDoc
+
+        doc = YDoc()
+        array = doc.get_array('measurements')
+        with doc.begin_transaction() as txn:
+            array.push_primitives(txn, range(100_000))
+        ```
         """
-    def append(self, txn: YTransaction, item: Any):
+    def append(self, txn: YTransaction, item: Any) -> Any:
         """
-        Adds a single item to the end of the `YArray`
+        Adds a single item to the end of the `YArray`. Returns the appended element, so that
+        preliminary shared types can be used in their integrated form right away.
+
+        Example:
+
+        ```
+        from y_py import YDoc, YMap
+
+        doc = YDoc()
+        todos = doc.get_array('todos')
+        with doc.begin_transaction() as txn:
+            todo = todos.append(txn, YMap({'title': 'write docs'}))
+            todo.set(txn, 'done', False)
+        ```
         """
-    def extend(self, txn: YTransaction, items: Iterable):
+    def extend(self, txn: YTransaction, items: Iterable) -> List[Any]:
         """
-        Appends a sequence of `items` at the end of this `YArray` instance.
+        Appends a sequence of `items` at the end of this `YArray` instance. Returns a list of
+        appended elements.
         """
     def delete(self, txn: YTransaction, index: int):
         """
@@ -621,11 +3550,45 @@ class YArray:
             array.move_range_to(t, 1, 2, 4);
         ```
         """
+    def sort(
+        self,
+        txn: YTransaction,
+        key: Optional[Callable[[Any], Any]] = None,
+        reverse: bool = False,
+    ):
+        """
+        Sorts elements of this `YArray` in place, like `list.sort` does. Elements are reordered by
+        moving them, so they keep their identity and concurrent changes made to them are preserved.
+        Elements which already are in order relative to each other stay where they are, so that as
+        few of them as possible are moved.
+
+        Example:
+
+        ```
+        from y_py import YDoc
+
+        doc = YDoc()
+        tasks = doc.get_array('tasks')
+        with doc.begin_transaction() as txn:
+            tasks.sort(txn, key=lambda task: task['priority'], reverse=True)
+        ```
+        """
+    def reverse(self, txn: YTransaction):
+        """
+        Reverses the order of elements of this `YArray` in place. Like with `sort`, elements are
+        reordered by moving them.
+        """
     def __getitem__(self, index: Union[int, slice]) -> Any:
         """
         Returns:
             The element stored under given `index` or a new list of elements from the slice range.
         """
+    def __contains__(self, value: Any) -> bool:
+        """
+        Checks if a given value is stored in this `YArray`. Values are compared on the Rust side,
+        without converting elements of the array into Python objects. Only nested shared types are
+        compared using Python equality.
+        """
     def __iter__(self) -> Iterator:
         """
         Returns:
@@ -642,21 +3605,36 @@ class YArray:
             for item in array:
                 print(item)
         """
-    def observe(self, f: Callable[[YArrayEvent]]) -> SubscriptionId:
+    def observe(
+        self,
+        f: Callable[[Union[YArrayEvent, List[ArrayDelta]]]],
+        weak: bool = False,
+        debounce_ms: Optional[int] = None,
+    ) -> SubscriptionId:
         """
         Assigns a callback function to listen to YArray updates.
 
         Args:
             f: Callback function that runs when the array object receives an update.
+            weak: Whether only a weak reference to the callback is held, see
+                `YDoc.observe_after_transaction`.
+            debounce_ms: If given, deltas of events following each other within that many
+                milliseconds are merged, and the callback receives a single delta once the
+                array stays unchanged for `debounce_ms`, instead of an event. Debounced callbacks
+                are called on a timer thread.
         Returns:
             An identifier associated with the callback subscription.
         """
-    def observe_deep(self, f: Callable[[List[Event]]]) -> SubscriptionId:
+    def observe_deep(
+        self, f: Callable[[List[Event]]], weak: bool = False
+    ) -> SubscriptionId:
         """
         Assigns a callback function to listen to the aggregated updates of the YArray and its child elements.
 
         Args:
             f: Callback function that runs when the array object or components receive an update.
+            weak: Whether only a weak reference to the callback is held, see
+                `YDoc.observe_after_transaction`.
         Returns:
             An identifier associated with the callback subscription.
         """
@@ -678,7 +3656,10 @@ class YArrayEvent:
     """
 
     target: YArray
+    origin: Any
+    """Origin of the transaction which produced this event, see `YTransaction.with_origin`."""
     delta: List[ArrayDelta]
+    def __repr__(self) -> str: ...
     def path(self) -> List[Union[int, str]]:
         """
         Returns:
@@ -706,6 +3687,22 @@ class ArrayChangeRetain:
 class YMap:
     prelim: bool
     """True if this element has not been integrated into a YDoc."""
+    integrated: bool
+    """True if this element has been integrated into a YDoc. This is an opposite of `prelim`."""
+    doc: Optional[YDoc]
+    """
+    The `YDoc` this instance belongs to, or `None` if the document is unknown or has been dropped.
+    Documents are known for shared types reached from their root types, which have been obtained
+    from a `YDoc` or its transactions. Accessing it on a preliminary instance raises a
+    `PreliminaryAccessError`.
+    """
+    parent: Optional[Union[YText, YArray, YMap, YXmlElement, YXmlText]]
+    """
+    The shared type containing this instance, or `None` if it is a root type. The parent is
+    remembered when this instance is reached from it, so it is also `None` when the document is
+    unknown.
+    Accessing it on a preliminary instance raises a `PreliminaryAccessError`.
+    """
     def __init__(dict: dict):
         """
         Creates a new preliminary instance of a `YMap` shared data type, with its state
@@ -715,11 +3712,35 @@ class YMap:
         Once a preliminary instance has been inserted this way, it becomes integrated into Ypy
         document store and cannot be nested again: attempt to do so will result in an exception.
         """
+    def __eq__(self, other: object) -> bool:
+        """
+        Checks if both objects refer to the same integrated `YMap`. Preliminary instances are only
+        equal to themselves.
+        """
+    def __hash__(self) -> int:
+        """
+        Returns a hash of this `YMap`, consistent with `__eq__`. Preliminary instances are unhashable
+        and raise a `TypeError`.
+        """
     def __len__(self) -> int:
         """
         Returns:
             The number of entries stored within this instance of `YMap`.
         """
+    def transaction(self) -> YTransaction:
+        """
+        Starts a new transaction of the document this `YMap` belongs to, which makes it possible
+        to modify it without a reference to its `YDoc`. The document must be known, as described by
+        the `doc` property, otherwise a `TransactionError` is raised. Raises a
+        `PreliminaryAccessError` for preliminary instances.
+
+        Example:
+
+        ```
+        with map.transaction() as txn:
+            map.set(txn, 'key', 'value')
+        ```
+        """
     def __str__(self) -> str:
         """
         Returns:
@@ -737,14 +3758,77 @@ class YMap:
         Returns:
             The string representation of the `YMap` wrapped in 'YMap()'
         """
-    def to_json(self) -> str:
+    def to_json(
+        self, txn: Optional[YTransaction] = None, depth: Optional[int] = None
+    ) -> str:
         """
         Converts contents of this `YMap` instance into a JSON representation.
+
+        Args:
+            txn: Optional transaction the map is read within.
+            depth: If given, nested shared types are resolved only up to `depth` levels below this
+                map, and deeper ones are replaced by `{"$type": ..., "length": ...}` placeholders.
+                With a `depth` of 0, only the values of this map are resolved.
+        """
+    def write_json(self, txn: YTransaction, file: TextIO):
+        """
+        Serializes contents of this `YMap` instance into JSON, like `to_json` does, but writes it
+        to a file object in chunks instead of returning a single string. Nested shared types are
+        serialized as they are traversed, which keeps memory usage low when serializing large
+        documents.
+
+        Args:
+            txn: A transaction the map is read within.
+            file: A text file object, or any object with a `write` method accepting strings.
+        """
+    def to_msgpack(self) -> bytes:
+        """
+        Serializes contents of this `YMap` instance using the MessagePack format. Unlike document
+        updates, the result contains only the current values, without any collaborative editing
+        metadata, which makes it a compact snapshot readable by any MessagePack library.
+        """
+    def content_hash(self) -> str:
+        """
+        Digests depend only on the current entries, regardless of their insertion order or
+        editing history, so replicas can cheaply verify that they converged.
+
+        Args:
+            txn: The current transaction from a YDoc.
+        Returns:
+            A hex encoded SHA-256 digest of the contents of this `YMap` instance.
+        """
+    def to_dict(self, deep: bool = False, keys: Optional[List[str]] = None) -> Dict[str, Any]:
+        """
+        Converts contents of this `YMap` instance into a Python dictionary. Entries are extracted
+        in a single pass, which is much faster than reading them one by one.
+
+        Args:
+            deep: If `True`, nested shared types are recursively converted into plain `str`, `list`
+                and `dict` values. Otherwise they are returned as `YText`, `YArray` and `YMap` instances.
+            keys: If provided, only entries stored under these keys are returned. Missing keys are skipped.
         """
-    def set(self, txn: YTransaction, key: str, value: Any):
+    def set(self, txn: YTransaction, key: str, value: Any) -> Any:
         """
         Sets a given `key`-`value` entry within this instance of `YMap`. If another entry was
         already stored under given `key`, it will be overridden with new `value`.
+
+        Returns:
+            The value previously stored under given `key`, or `None` if there was no such entry.
+        """
+    def insert_json(self, txn: YTransaction, key: str, json: str):
+        """
+        Parses a `json` string and stores the result under a given `key`. JSON is parsed directly
+        into Yrs values without creating intermediate Python objects, which makes it much faster
+        than `json.loads` followed by `set` for large payloads.
+
+        Raises a `ValueError` if `json` is not a valid JSON document.
+        """
+    def update_from_msgpack(self, txn: YTransaction, data: BinaryInput):
+        """
+        Decodes a MessagePack encoded map, such as one produced by `to_msgpack`, and stores all of
+        its entries in this `YMap` instance.
+
+        Raises a `ValueError` if `data` is not a valid MessagePack map with string keys.
         """
     def update(
         self, txn: YTransaction, items: Union[Iterable[Tuple[str, Any]], Dict[str, Any]]
@@ -786,6 +3870,56 @@ class YMap:
         Returns:
             Value of an entry stored under given `key` within this instance of `YMap`. Will throw a `KeyError` if the provided key is unassigned.
         """
+    def get_path(
+        self, path: List[Union[str, int]], fallback: Optional[Any] = None
+    ) -> Any:
+        """
+        Follows a `path` through nested maps and arrays in a single call. Strings are keys of map
+        entries and integers are indexes of array elements, where negative indexes count from the
+        end.
+
+        Args:
+            path: Keys and indexes leading to the requested value.
+            fallback: Returned if there is no value at the `path`.
+
+        Returns:
+            The value found at the `path`, or the `fallback` value.
+
+        Example::
+
+            from y_py import YDoc
+
+            doc = YDoc()
+            root = doc.get_map('root')
+            with doc.begin_transaction() as txn:
+                root.set_path(txn, ['users', 0, 'name'], 'Alice')
+
+            assert root.get_path(['users', 0, 'name']) == 'Alice'
+            assert root.get_path(['users', 1, 'name']) is None
+        """
+    def set_path(
+        self,
+        txn: YTransaction,
+        path: List[Union[str, int]],
+        value: Any,
+        create_parents: bool = True,
+    ) -> Any:
+        """
+        Stores a `value` at a location described by a `path`, as accepted by `get_path`. An existing
+        array element at the end of the path is replaced, and an index equal to the length of an
+        array appends to it.
+
+        Args:
+            txn: A transaction to perform the update.
+            path: Keys and indexes leading to the value.
+            value: The value to store.
+            create_parents: If set, missing maps and arrays along the path are created: a `YMap`
+                when the following segment is a key, and a `YArray` when it's an index. Otherwise
+                a `KeyError` or an `IndexError` is raised.
+
+        Returns:
+            The value previously stored at the `path`, or `None` if there was no such value.
+        """
     def __iter__(self) -> Iterator[str]:
         """
         Returns:
@@ -819,21 +3953,27 @@ class YMap:
         Returns:
             A view of all values in the YMap. The order of values is not stable.
         """
-    def observe(self, f: Callable[[YMapEvent]]) -> SubscriptionId:
+    def observe(self, f: Callable[[YMapEvent]], weak: bool = False) -> SubscriptionId:
         """
         Assigns a callback function to listen to YMap updates.
 
         Args:
             f: Callback function that runs when the map object receives an update.
+            weak: Whether only a weak reference to the callback is held, see
+                `YDoc.observe_after_transaction`.
         Returns:
             A reference to the callback subscription. Delete this observer in order to erase the associated callback function.
         """
-    def observe_deep(self, f: Callable[[List[Event]]]) -> SubscriptionId:
+    def observe_deep(
+        self, f: Callable[[List[Event]]], weak: bool = False
+    ) -> SubscriptionId:
         """
         Assigns a callback function to listen to YMap and child element updates.
 
         Args:
             f: Callback function that runs when the map object or any of its tracked elements receive an update.
+            weak: Whether only a weak reference to the callback is held, see
+                `YDoc.observe_after_transaction`.
         Returns:
             A reference to the callback subscription. Delete this observer in order to erase the associated callback function.
         """
@@ -885,9 +4025,12 @@ class YMapEvent:
 
     target: YMap
     """The element modified during this event."""
+    origin: Any
+    """Origin of the transaction which produced this event, see `YTransaction.with_origin`."""
     keys: Dict[str, YMapEventKeyChange]
     """A list of modifications to the YMap by key. 
     Includes the type of modification along with the before and after state."""
+    def __repr__(self) -> str: ...
     def path(self) -> List[Union[int, str]]:
         """
         Returns:
@@ -899,6 +4042,48 @@ class YMapEventKeyChange(TypedDict):
     oldValue: Optional[Any]
     newValue: Optional[Any]
 
+class View:
+    """
+    A plain Python mirror (`str`, `list` or `dict`) of a `YText`, `YArray` or `YMap`, kept up to
+    date by a deep observer. Reading a view requires neither transactions nor conversions.
+
+    Changes are applied incrementally: map entries and array ranges are updated in place, so lists
+    and dictionaries returned by `value` keep reflecting the latest state. Texts are replaced
+    instead.
+
+    Example:
+
+    ```
+    from y_py import YDoc, View
+
+    doc = YDoc()
+    todos = doc.get_array('todos')
+    view = View(todos)
+    with doc.begin_transaction() as txn:
+        todos.append(txn, {'title': 'write docs'})
+    assert view.value == [{'title': 'write docs'}]
+    view.close()
+    ```
+    """
+
+    value: Union[str, List[Any], Dict[str, Any]]
+    """Current state of the observed shared type as a plain Python value."""
+    closed: bool
+    """True if this view no longer follows changes of its shared type."""
+
+    def __init__(self, shared: Union[YText, YArray, YMap]):
+        """
+        Creates a view of an integrated `YText`, `YArray` or `YMap` instance.
+
+        Raises:
+            PreliminaryObservationException: If the shared type is preliminary.
+        """
+    def close(self):
+        """
+        Stops following changes of the observed shared type. The last known value stays available.
+        """
+    def __repr__(self) -> str: ...
+
 YXmlAttributes = Iterator[Tuple[str, str]]
 """Generates a sequence of key/value properties for an XML Element"""
 
@@ -907,10 +4092,13 @@ YXmlTreeWalker = Iterator[Xml]
 """Visits elements in an Xml tree"""
 EntryChange = Dict[Literal["action", "newValue", "oldValue"], Any]
 
-class YXmlElementEvent:
+class YXmlEvent:
     target: YXmlElement
+    origin: Any
+    """Origin of the transaction which produced this event, see `YTransaction.with_origin`."""
     keys: Dict[str, EntryChange]
     delta: List[Dict]
+    def __repr__(self) -> str: ...
     def path(self) -> List[Union[int, str]]:
         """
         Returns a current shared type instance, that current event changes refer to.
@@ -937,7 +4125,21 @@ class YXmlElement:
     first_child: Optional[Xml]
     next_sibling: Optional[Xml]
     prev_sibling: Optional[Xml]
+    doc: Optional[YDoc]
+    """
+    The `YDoc` this instance belongs to, or `None` if the document is unknown or has been dropped.
+    Documents are known for XML nodes reached from their root elements, which have been obtained
+    from a `YDoc`.
+    """
     parent: Optional[YXmlElement]
+    def __eq__(self, other: object) -> bool:
+        """
+        Checks if both objects refer to the same `YXmlElement` node.
+        """
+    def __hash__(self) -> int:
+        """
+        Returns a hash of this `YXmlElement`, consistent with `__eq__`.
+        """
     def __len__(self) -> int:
         """
         Returns a number of child XML nodes stored within this `YXMlElement` instance.
@@ -968,6 +4170,96 @@ class YXmlElement:
         """
         Appends a new instance of `YXmlText` as the last child of this XML node and returns it.
         """
+    def rename(self, txn: YTransaction, name: str) -> YXmlElement:
+        """
+        Replaces this XML node with a new element with a given tag `name`, which receives copies of
+        all attributes and children of this node. Tags of XML elements cannot be changed, so the
+        element is recreated in place: this instance refers to the removed element afterwards, and
+        changes made concurrently to its contents are lost. Root elements cannot be renamed.
+
+        Returns:
+            The new element.
+        """
+    def move_child(
+        self, txn: YTransaction, source: int, target: int
+    ) -> YXmlElement | YXmlText:
+        """
+        Moves a child XML node from a `source` index to a `target` index, which describes a position
+        before the move like in `YArray.move_to`. XML nodes are moved by copying them together with
+        their attributes and children, and removing the originals.
+
+        Returns:
+            The moved node.
+        """
+    def to_markdown(self) -> str:
+        """
+        Returns children of this XML node as Markdown. Headings `h1`-`h6`, paragraphs `p`, lists
+        `ul` and `ol` with `li` items, quotes `blockquote` containing paragraphs and code blocks
+        `pre` are converted, together with text formatted by `strong`, `em`, `s`, `code` and `a`
+        elements. Content of other elements is treated as paragraphs.
+        """
+    def from_markdown(self, txn: YTransaction, markdown: str):
+        """
+        Replaces children of this XML node with elements parsed from `markdown`, using the same
+        elements as `to_markdown`.
+        """
+    def to_prosemirror_json(self, txn: YTransaction) -> str:
+        """
+        Returns children of this XML node as a ProseMirror document serialized to JSON, following
+        the mapping of y-prosemirror: elements become nodes of the same type with attributes of
+        elements as node attributes, and text becomes text nodes with formatting attributes as
+        marks.
+
+        Args:
+            txn: The current transaction from a YDoc.
+        """
+    def apply_prosemirror_steps(
+        self,
+        txn: YTransaction,
+        steps: str,
+        leaf_nodes: Optional[List[str]] = None,
+    ):
+        """
+        Applies ProseMirror `steps` to a document represented by children of this XML node. Only
+        the changed nodes are updated, using the same mapping as `to_prosemirror_json`, so that
+        documents edited by Python backends stay in sync with y-prosemirror editors.
+
+        Args:
+            txn: A transaction used to update the document.
+            steps: A JSON array of serialized replace, replace-around, mark and attribute steps.
+            leaf_nodes: Names of nodes without content, which are needed to compute positions in
+                the document. Defaults to leaf nodes of the basic ProseMirror schema: `image`,
+                `hard_break` and `horizontal_rule`.
+
+        Example::
+
+            import json
+            from y_py import YDoc
+
+            doc = YDoc()
+            fragment = doc.get_xml_element('prosemirror')
+            step = {
+                'stepType': 'replace',
+                'from': 0,
+                'to': 0,
+                'slice': {'content': [{'type': 'paragraph'}]},
+            }
+            with doc.begin_transaction() as txn:
+                fragment.apply_prosemirror_steps(txn, json.dumps([step]))
+        """
+    def transaction(self) -> YTransaction:
+        """
+        Starts a new transaction of the document this `YXmlElement` belongs to, which makes it
+        possible to modify it without a reference to its `YDoc`. The document must be known, as
+        described by the `doc` property, otherwise a `TransactionError` is raised.
+
+        Example:
+
+        ```
+        with element.transaction() as txn:
+            element.push_xml_text(txn)
+        ```
+        """
     def __str__(self) -> str:
         """
         Returns:
@@ -1002,23 +4294,46 @@ class YXmlElement:
         Returns an iterator that enables a deep traversal of this XML node - starting from first
         child over this XML node successors using depth-first strategy.
         """
-    def observe(self, f: Callable[[YXmlElementEvent]]) -> SubscriptionId:
+    def children(self) -> List[YXmlElement | YXmlText]:
+        """
+        Returns:
+            Direct children of this XML node.
+        """
+    def find_all(self, name: str) -> List[YXmlElement]:
+        """
+        Returns:
+            All descendant XML elements with a given tag `name`, in the depth-first order used by
+            `tree_walker`.
+        """
+    def first(self, name: str) -> Optional[YXmlElement]:
+        """
+        Returns:
+            The first descendant XML element with a given tag `name` in the depth-first order used
+            by `tree_walker`, or `None` if there is no such element.
+        """
+    def observe(self, f: Callable[[YXmlEvent]], weak: bool = False) -> SubscriptionId:
         """
         Subscribes to all operations happening over this instance of `YXmlElement`. All changes are
         batched and eventually triggered during transaction commit phase.
 
         Args:
             f: A callback function that receives update events.
+            weak: Whether only a weak reference to the callback is held, see
+                `YDoc.observe_after_transaction`.
         Returns:
             A `SubscriptionId` that can be used to cancel the observer callback.
         """
-    def observe_deep(self, f: Callable[[List[Event]]]) -> SubscriptionId:
+    def observe_deep(
+        self, f: Callable[[List[Event]]], weak: bool = False
+    ) -> SubscriptionId:
         """
         Subscribes to all operations happening over this instance of `YXmlElement` and its children. All changes are
         batched and eventually triggered during transaction commit phase.
 
         Args:
             f: A callback function that receives update events from the Xml element and its children.
+            weak: Whether only a weak reference to the callback is held, see
+                `YDoc.observe_after_transaction`.
         Returns:
             A `SubscriptionId` that can be used to cancel the observer callback.
         """
@@ -1033,7 +4348,21 @@ class YXmlElement:
 class YXmlText:
     next_sibling: Optional[Xml]
     prev_sibling: Optional[Xml]
+    doc: Optional[YDoc]
+    """
+    The `YDoc` this instance belongs to, or `None` if the document is unknown or has been dropped.
+    Documents are known for XML nodes reached from their root elements, which have been obtained
+    from a `YDoc`.
+    """
     parent: Optional[YXmlElement]
+    def __eq__(self, other: object) -> bool:
+        """
+        Checks if both objects refer to the same `YXmlText` node.
+        """
+    def __hash__(self) -> int:
+        """
+        Returns a hash of this `YXmlText`, consistent with `__eq__`.
+        """
     def __len__():
         """
         Returns:
@@ -1052,6 +4381,19 @@ class YXmlText:
         Deletes a specified range of of characters, starting at a given `index`.
         Both `index` and `length` are counted in terms of a number of UTF-8 character bytes.
         """
+    def transaction(self) -> YTransaction:
+        """
+        Starts a new transaction of the document this `YXmlText` belongs to, which makes it possible
+        to modify it without a reference to its `YDoc`. The document must be known, as described by
+        the `doc` property, otherwise a `TransactionError` is raised.
+
+        Example:
+
+        ```
+        with text.transaction() as txn:
+            text.push(txn, '!')
+        ```
+        """
     def __str__(self) -> str:
         """
         Returns:
@@ -1083,7 +4425,9 @@ class YXmlText:
             An iterator that enables to traverse over all attributes of this XML node in
         unspecified order.
         """
-    def observe(self, f: Callable[[YXmlTextEvent]]) -> SubscriptionId:
+    def observe(
+        self, f: Callable[[YXmlTextEvent]], weak: bool = False
+    ) -> SubscriptionId:
         """
         Subscribes to all operations happening over this instance of `YXmlText`. All changes are
         batched and eventually triggered during transaction commit phase.
@@ -1091,10 +4435,14 @@ class YXmlText:
         Args:
             f: A callback function that receives update events.
             deep: Determines whether observer is triggered by changes to elements in the YXmlText.
+            weak: Whether only a weak reference to the callback is held, see
+                `YDoc.observe_after_transaction`.
         Returns:
             A `SubscriptionId` that can be used to cancel the observer callback.
         """
-    def observe_deep(self, f: Callable[[List[Event]]]) -> SubscriptionId:
+    def observe_deep(
+        self, f: Callable[[List[Event]]], weak: bool = False
+    ) -> SubscriptionId:
         """
         Subscribes to all operations happening over this instance of `YXmlText` and its children. All changes are
         batched and eventually triggered during transaction commit phase.
@@ -1102,6 +4450,8 @@ class YXmlText:
         Args:
             f: A callback function that receives update events of this element and its descendants.
             deep: Determines whether observer is triggered by changes to elements in the YXmlText.
+            weak: Whether only a weak reference to the callback is held, see
+                `YDoc.observe_after_transaction`.
         Returns:
             A `SubscriptionId` that can be used to cancel the observer callback.
         """
@@ -1115,8 +4465,11 @@ class YXmlText:
 
 class YXmlTextEvent:
     target: YXmlText
+    origin: Any
+    """Origin of the transaction which produced this event, see `YTransaction.with_origin`."""
     keys: List[EntryChange]
     delta: List[YTextDelta]
+    def __repr__(self) -> str: ...
     def path(self) -> List[Union[int, str]]:
         """
         Returns a current shared type instance, that current event changes refer to.